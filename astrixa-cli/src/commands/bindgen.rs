@@ -0,0 +1,126 @@
+// Bindgen Command: typed frontend clients from contract ABIs
+
+use colored::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+use astrixa::bindgen::{typescript, TsClient};
+use crate::config::find_project_root;
+use super::migrate::source_files;
+
+pub fn generate_bindings(
+    target: &str,
+    client: &str,
+    abi_paths: &[String],
+    output: Option<&String>,
+) -> Result<(), String> {
+    if target != "ts" {
+        return Err(format!("Unknown bindgen target '{}' (supported: ts)", target));
+    }
+    let client = TsClient::from_name(client).ok_or_else(|| {
+        let supported: Vec<&str> = TsClient::ALL.iter().map(|c| c.name()).collect();
+        format!("Unknown client library '{}' (supported: {})", client, supported.join(", "))
+    })?;
+
+    // Without --abi, use every ABI the last `astrixa build` wrote
+    let (abi_files, output_dir) = if abi_paths.is_empty() {
+        let root = find_project_root()?;
+        let build_dir = root.join("build");
+        let files = built_abis(&build_dir)?;
+        if files.is_empty() {
+            return Err("No contract ABIs in build/. Run `astrixa build` first".to_string());
+        }
+        if let Some(stale) = files.iter().find(|file| is_stale(file, &root)) {
+            return Err(format!("{}: ABI is stale, run `astrixa build`", stale.display()));
+        }
+        (files, build_dir.join("bindings"))
+    } else {
+        (abi_paths.iter().map(PathBuf::from).collect(), PathBuf::from("."))
+    };
+    let output_dir = output.map(PathBuf::from).unwrap_or(output_dir);
+
+    println!("{} TypeScript bindings ({})", "Generating".green().bold(), client.name());
+    fs::create_dir_all(&output_dir)
+        .map_err(|e| format!("Failed to create {}: {}", output_dir.display(), e))?;
+
+    for path in abi_files {
+        let contract = contract_name(&path)?;
+        let text = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let abi: serde_json::Value = serde_json::from_str(&text)
+            .map_err(|e| format!("{} is not valid JSON: {}", path.display(), e))?;
+
+        let module = typescript(&contract, &abi, client)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        let out_path = output_dir.join(format!("{}.ts", contract));
+        fs::write(&out_path, module)
+            .map_err(|e| format!("Failed to write {}: {}", out_path.display(), e))?;
+        println!("   {} {}", "Generated".green(), out_path.display());
+    }
+
+    Ok(())
+}
+
+/// `build/*.abi.json`, sorted so output order is stable
+fn built_abis(build_dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let Ok(entries) = fs::read_dir(build_dir) else { return Ok(Vec::new()) };
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.to_string_lossy().ends_with(".abi.json"))
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Whether a source of the project at `root`, or its manifest, changed
+/// after `abi` was written
+fn is_stale(abi: &Path, root: &Path) -> bool {
+    let modified = |path: &Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+    let Some(built) = modified(abi) else { return false };
+    let mut sources = vec![root.join("astrixa.toml")];
+    for dir in ["src", "modules"] {
+        sources.extend(source_files(&root.join(dir)).unwrap_or_default());
+    }
+    sources.iter().filter_map(|source| modified(source)).any(|changed| changed > built)
+}
+
+/// `Vault.abi.json` holds the ABI of `Vault`
+fn contract_name(path: &Path) -> Result<String, String> {
+    let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+    let name = file_name
+        .strip_suffix(".abi.json")
+        .or_else(|| file_name.strip_suffix(".json"))
+        .unwrap_or(file_name);
+    if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        return Err(format!("Cannot tell the contract name from {}; name the file <Contract>.abi.json", path.display()));
+    }
+    Ok(name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn test_abis_older_than_a_source_are_stale() {
+        let root = std::env::temp_dir().join(format!("astrixa-bindgen-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("src/tokens")).unwrap();
+        fs::create_dir_all(root.join("build")).unwrap();
+        let touch = |path: &Path, seconds_ago: u64| {
+            let file = fs::File::create(path).unwrap();
+            file.set_modified(SystemTime::now() - Duration::from_secs(seconds_ago)).unwrap();
+        };
+        let abi = root.join("build/Vault.abi.json");
+        touch(&root.join("astrixa.toml"), 60);
+        touch(&root.join("src/main.ax"), 60);
+        touch(&root.join("src/tokens/erc20.ax"), 60);
+        touch(&abi, 30);
+        assert!(!is_stale(&abi, &root));
+
+        // An imported module edited since the build counts too
+        touch(&root.join("src/tokens/erc20.ax"), 0);
+        assert!(is_stale(&abi, &root));
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
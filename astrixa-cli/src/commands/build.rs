@@ -1,21 +1,92 @@
 // STEP 50: Build Command
 
 use colored::*;
+use std::collections::HashSet;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use astrixa::codegen::contract::ChainFamily;
+use astrixa::codegen::wasm::HostImports;
+use astrixa::edition::{Edition, Warning};
+use astrixa::error::CompileError;
+use astrixa::opt::Level;
+use astrixa::profile::Profile;
+use astrixa::session::Session;
+use astrixa::stats::Stats;
 use crate::config::{Config, find_project_root};
+use crate::crash;
+use crate::plugins;
 
-pub fn build_project(release: bool, output: Option<&String>) -> Result<(), String> {
+/// What `--target` builds for
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Target {
+    #[default]
+    Script,
+    Contract(ChainFamily), // Through the chain family's backend
+    Wasi,                  // Started by, and doing I/O through, a WASI runtime
+    Web,                   // With a JavaScript loader for pages
+    Native,                // Bytecode (.axb) for the VM, run by `astrixa run`
+}
+
+/// How `astrixa build` was asked to build, on top of astrixa.toml
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BuildOptions<'a> {
+    pub release: bool,
+    pub opt_level: Option<&'a String>, // -O, over the profile's level
+    pub output: Option<&'a String>,    // Instead of build/<package>.<ext>
+    pub wat: bool,                     // WAT text instead of a binary module
+    pub target: Target,
+    pub checked_arith: bool,           // Int overflow traps, as contracts' always does
+    pub strip: bool,                   // No debug info, whatever the profile says
+    pub stats: bool,                   // Report the compiler's time and memory after
+    pub plugins: &'a [String],
+}
+
+/// Build src/main.ax to a binary module, or WAT text with `wat`, for
+/// `target`; a native build writes bytecode instead. The mode's profile
+/// decides optimization, checks and debug info, as adjusted by `options`.
+pub fn build_project(options: &BuildOptions) -> Result<(), String> {
+    let BuildOptions { release, opt_level, output, wat, target, checked_arith, strip, stats, plugins } = *options;
     let root = find_project_root()?;
     let config = Config::load(root.join("astrixa.toml"))?;
+    config.diagnostics.apply();
+    let edition = config.edition()?;
+    let mut profile = config.profile(release, opt_level)?;
+    let memory = config.memory()?;
+    let host = config.host()?;
+    if !config.host.is_default() && !matches!(target, Target::Script) {
+        return Err("[host] in astrixa.toml renames a script build's imports; this target's runtime defines its own".to_string());
+    }
+    let level = profile.opt_level;
     
     let mode = if release { "release" } else { "debug" };
+    if target == Target::Native && wat {
+        return Err("--target=native writes bytecode; --emit=wat is for the WASM targets".to_string());
+    }
+    // The VM's Int arithmetic wraps; it has no opcodes to trap with
+    if target == Target::Native && checked_arith {
+        return Err("--target=native: the VM wraps Int arithmetic, so --checked-arith needs a WASM target".to_string());
+    }
+    // Contracts always trap on overflow; see astrixa::overflow
+    profile.overflow_checks |= checked_arith || matches!(target, Target::Contract(_));
+    profile.overflow_checks &= target != Target::Native;
+    profile.debug_info &= !strip;
+    let mut description = match target {
+        Target::Contract(chain) => format!("{} -O{}, {} contract", mode, level.name(), chain.name()),
+        Target::Wasi => format!("{} -O{}, wasi", mode, level.name()),
+        Target::Web => format!("{} -O{}, web", mode, level.name()),
+        Target::Native => format!("{} -O{}, native bytecode", mode, level.name()),
+        Target::Script => format!("{} -O{}", mode, level.name()),
+    };
+    // Contracts always check, so only the other targets say so
+    if profile.overflow_checks && !matches!(target, Target::Contract(_)) {
+        description.push_str(", checked arithmetic");
+    }
     println!("{} {} {} ({})", 
         "Compiling".green().bold(),
         config.package.name,
         config.package.version.dimmed(),
-        mode.cyan()
+        description.cyan()
     );
     
     // Find main source file
@@ -33,22 +104,38 @@ pub fn build_project(release: bool, output: Option<&String>) -> Result<(), Strin
     let output_path = if let Some(out) = output {
         PathBuf::from(out)
     } else {
-        build_dir.join(format!("{}.wat", config.package.name))
+        let extension = match target {
+            Target::Native => "axb",
+            _ if wat => "wat",
+            _ => "wasm",
+        };
+        build_dir.join(format!("{}.{}", config.package.name, extension))
     };
     
+    let session = plugins::load_session(plugins)?;
     println!("   {} src/main.ax", "Parsing".cyan());
     
     // Call the ASTRIXA compiler
-    let compiler_result = compile_file(&main_file, &output_path, release)?;
+    let settings = Settings { profile, memory, host, edition };
+    let compiler_result = compile_file(&main_file, &output_path, settings, options, &session)?;
     
     println!("   {} {} ({} functions)", 
         "Compiled".green(),
         output_path.display(),
         compiler_result.function_count
     );
+    if let Some(path) = &compiler_result.source_map {
+        println!("   {} {}", "Generated".green(), path.display());
+    }
+    for path in &compiler_result.generated {
+        println!("   {} {}", "Generated".green(), path.display());
+    }
+    if !compiler_result.entry_points.is_empty() {
+        println!("   {} {}", "Exported".green(), compiler_result.entry_points.join(", "));
+    }
     
-    if release {
-        println!("   {} Applied optimizations", "Optimized".yellow());
+    if level != Level::O0 {
+        println!("   {} Applied -O{} optimizations", "Optimized".yellow(), level.name());
     }
     
     println!();
@@ -58,12 +145,190 @@ pub fn build_project(release: bool, output: Option<&String>) -> Result<(), Strin
         compiler_result.duration
     );
     
+    if stats {
+        println!();
+        print_stats(&compiler_result.stats);
+    }
+    
+    Ok(())
+}
+
+/// Time per phase, program size at each stage and peak memory
+fn print_stats(stats: &Stats) {
+    let row = |label: &str, value: String| println!("   {:<13} {}", label, value);
+    println!("   {}", "Compiler stats".bold());
+    let memory = match astrixa::stats::peak_rss() {
+        Some(bytes) => format!("{:.1} MB peak resident", bytes as f64 / (1024.0 * 1024.0)),
+        None => "unavailable on this platform".dimmed().to_string(),
+    };
+    row("memory", memory);
+    row("ast", format!("{} statements, {} expressions", stats.statements, stats.expressions));
+    let instructions: usize = stats.functions.iter().map(|(_, count)| count).sum();
+    row("ir", format!("{} instructions in {} functions", instructions, stats.functions.len()));
+    row("strings", format!("{} interned", stats.strings));
+    
+    println!();
+    println!("   {}", "Time per phase".bold());
+    for (phase, time) in &stats.phases {
+        row(phase, format!("{:>8.2}ms", time.as_secs_f64() * 1000.0));
+    }
+    row("total", format!("{:>8.2}ms", stats.total_time().as_secs_f64() * 1000.0).bold().to_string());
+    
+    // Largest first, as that is where a regression usually is
+    let mut functions = stats.functions.clone();
+    functions.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    let width = functions.iter().map(|(name, _)| name.len()).chain([8]).max().unwrap_or(8);
+    println!();
+    println!("   {}", "IR instructions per function".bold());
+    for (name, count) in functions {
+        println!("   {:<width$}  {:>6}", name, count, width = width);
+    }
+}
+
+pub fn emit_ast(output: Option<&String>, typed: bool) -> Result<(), String> {
+    let root = find_project_root()?;
+    let config = Config::load(root.join("astrixa.toml"))?;
+    config.diagnostics.apply();
+    let edition = config.edition()?;
+    
+    let main_file = root.join("src/main.ax");
+    if !main_file.exists() {
+        return Err("src/main.ax not found".to_string());
+    }
+    
+    let output_path = if let Some(out) = output {
+        PathBuf::from(out)
+    } else {
+        let build_dir = root.join("build");
+        fs::create_dir_all(&build_dir)
+            .map_err(|e| format!("Failed to create build directory: {}", e))?;
+        build_dir.join(format!("{}.ast.json", config.package.name))
+    };
+    
+    println!("   {} src/main.ax", "Parsing".cyan());
+    
+    let source = fs::read_to_string(&main_file)
+        .map_err(|e| format!("Failed to read source file: {}", e))?;
+    crash::set_source(&main_file, &source);
+    
+    crash::set_phase("parse");
+    let lexer = astrixa::lexer::Lexer::new(&source);
+    let mut parser = astrixa::parser::Parser::new(lexer);
+    parser.set_edition(edition);
+    let ast = parser.parse()
+        .map_err(|_| syntax_errors(&main_file, parser.errors()))?;
+    print_warnings(&main_file, parser.warnings());
+    
+    crash::set_phase("typecheck");
+    let mut checker = astrixa::typechecker::TypeChecker::new();
+    if typed {
+        checker.check(&ast)
+            .map_err(|errors| type_errors(&main_file, &errors))?;
+    }
+    let signatures = if typed { Some(checker.functions()) } else { None };
+    
+    crash::set_phase("emit");
+    let json = astrixa::ast_json::program_to_json(&ast, signatures);
+    fs::write(&output_path, format!("{:#}\n", json))
+        .map_err(|e| format!("Failed to write output file: {}", e))?;
+    
+    println!("   {} {}", "Emitted".green(), output_path.display());
+    
+    Ok(())
+}
+
+/// Compile with each optimization configuration and compare the functions'
+/// size and estimated gas; with `contract`, only contract methods
+pub fn compare_opt(contract: bool, plugins: &[String]) -> Result<(), String> {
+    let root = find_project_root()?;
+    let config = Config::load(root.join("astrixa.toml"))?;
+    config.diagnostics.apply();
+    let edition = config.edition()?;
+    
+    let target = if contract { "contract" } else { "script" };
+    println!("{} {} {} ({})",
+        "Comparing".green().bold(),
+        config.package.name,
+        config.package.version.dimmed(),
+        target.cyan()
+    );
+    
+    let main_file = root.join("src/main.ax");
+    if !main_file.exists() {
+        return Err("src/main.ax not found".to_string());
+    }
+    
+    let session = plugins::load_session(plugins)?;
+    let ast = check_file(&main_file, edition, &session)?;
+    
+    crash::set_phase("lower");
+    let mut ir = astrixa::lowering::lower(&ast);
+    if contract {
+        astrixa::overflow::check_arithmetic(&mut ir);
+    }
+    
+    // Contract methods are lowered as Contract.method
+    let mut methods = HashSet::new();
+    for stmt in &ast {
+        let astrixa::ast::StmtKind::Contract { name, constructor, methods: declared, .. } = &stmt.kind else { continue };
+        for method in constructor.as_deref().into_iter().chain(declared) {
+            if let astrixa::ast::StmtKind::Function { name: method, .. } = &method.kind {
+                methods.insert(format!("{}.{}", name, method));
+            }
+        }
+    }
+    if contract && methods.is_empty() {
+        return Err("--target=contract: src/main.ax declares no contracts".to_string());
+    }
+    
+    crash::set_phase("optimize");
+    let comparison = astrixa::cost::compare(
+        &ir,
+        |function| !contract || methods.contains(&function.name),
+        |module| session.run_ir_passes(module),
+    );
+    
+    println!();
+    print_cost_table("Bytecode size (bytes)", &comparison, |cost| cost.size as u64);
+    println!();
+    print_cost_table("Estimated gas", &comparison, |cost| cost.gas);
+    println!();
+    println!("{}", "Gas counts each instruction once: loops run once and both sides of a branch count".dimmed());
+    
     Ok(())
 }
 
-pub fn check_project() -> Result<(), String> {
+/// One row per function, one column per configuration, then the total and
+/// what each configuration saves over none
+fn print_cost_table(title: &str, comparison: &astrixa::cost::Comparison, value: impl Fn(&astrixa::cost::Cost) -> u64) {
+    let name_width = comparison.functions.iter().map(|f| f.len()).chain([8]).max().unwrap_or(8);
+    let widths: Vec<usize> = comparison.columns.iter().map(|c| c.name.len().max(7)).collect();
+    let row = |label: &str, cells: Vec<String>| {
+        let cells: Vec<String> = cells.iter().zip(&widths).map(|(cell, width)| format!("{:>width$}", cell, width = width)).collect();
+        format!("   {:<name_width$}  {}", label, cells.join("  "), name_width = name_width)
+    };
+    
+    println!("   {}", title.bold());
+    println!("{}", row("function", comparison.columns.iter().map(|c| c.name.to_string()).collect()).dimmed());
+    for (i, function) in comparison.functions.iter().enumerate() {
+        println!("{}", row(function, comparison.columns.iter().map(|c| value(&c.costs[i]).to_string()).collect()));
+    }
+    
+    let totals: Vec<u64> = comparison.columns.iter().map(|c| value(&c.total())).collect();
+    println!("{}", row("total", totals.iter().map(|t| t.to_string()).collect()).bold());
+    let baseline = totals.first().copied().unwrap_or(0);
+    let saved = totals.iter().map(|&total| match baseline {
+        0 => "-".to_string(),
+        _ => format!("{:.1}%", (baseline as f64 - total as f64) * 100.0 / baseline as f64),
+    });
+    println!("{}", row("saved", saved.collect()).green());
+}
+
+pub fn check_project(plugins: &[String]) -> Result<(), String> {
     let root = find_project_root()?;
     let config = Config::load(root.join("astrixa.toml"))?;
+    config.diagnostics.apply();
+    let edition = config.edition()?;
     
     println!("{} {}", "Checking".green().bold(), config.package.name);
     
@@ -73,7 +338,8 @@ pub fn check_project() -> Result<(), String> {
     }
     
     // Parse and type-check without building
-    check_file(&main_file)?;
+    let session = plugins::load_session(plugins)?;
+    check_file(&main_file, edition, &session)?;
     
     println!();
     println!("{}", "✅ No errors found".green().bold());
@@ -99,71 +365,324 @@ pub fn clean_project() -> Result<(), String> {
 
 struct CompileResult {
     function_count: usize,
+    generated: Vec<PathBuf>, // Written next to the module: ABIs, a web build's loader
+    source_map: Option<PathBuf>, // Written next to the module with debug info
+    entry_points: Vec<String>, // A contract build's exports
     duration: f64,
+    stats: Stats,
 }
 
-fn compile_file(input: &PathBuf, output: &PathBuf, optimize: bool) -> Result<CompileResult, String> {
+/// What a project's astrixa.toml, with the mode, makes of a build
+struct Settings {
+    profile: Profile,
+    memory: astrixa::ir::Memory,
+    host: HostImports,
+    edition: Edition,
+}
+
+fn compile_file(
+    input: &PathBuf,
+    output: &PathBuf,
+    settings: Settings,
+    options: &BuildOptions,
+    session: &Session,
+) -> Result<CompileResult, String> {
     use std::time::Instant;
+    let Settings { profile, memory, host, edition } = settings;
+    let BuildOptions { wat, target, .. } = *options;
+    crash::set_build(target, profile, wat);
     
     let start = Instant::now();
+    let mut stats = Stats::new();
     
     // Read source file
     let source = fs::read_to_string(input)
         .map_err(|e| format!("Failed to read source file: {}", e))?;
+    crash::set_source(input, &source);
     
     // Parse
+    crash::set_phase("parse");
+    stats.enter("parse");
     let lexer = astrixa::lexer::Lexer::new(&source);
     let mut parser = astrixa::parser::Parser::new(lexer);
+    parser.set_edition(edition);
     let ast = parser.parse()
-        .map_err(|e| format!("Parse error: {:?}", e))?;
+        .map_err(|_| syntax_errors(input, parser.errors()))?;
+    print_warnings(input, parser.warnings());
+    stats.count_ast(&ast);
     
     // Type check
+    crash::set_phase("typecheck");
+    stats.enter("typecheck");
     let mut checker = astrixa::typechecker::TypeChecker::new();
     checker.check(&ast)
-        .map_err(|e| format!("Type error: {:?}", e))?;
+        .map_err(|errors| type_errors(input, &errors))?;
+    
+    crash::set_phase("lint");
+    stats.enter("lint");
+    check_lints(input, session, &ast, parser.spans())?;
+    print_warnings(input, &astrixa::loops::analyze(&ast));
+    print_warnings(input, &astrixa::unused::analyze(&ast));
+    
+    // Contract ABIs go next to the module, for `astrixa bindgen`
+    crash::set_phase("abi");
+    stats.enter("abi");
+    let mut generated = Vec::new();
+    for abi in astrixa::abi::contract_abis(&ast, checker.functions())? {
+        let path = output.with_file_name(format!("{}.abi.json", abi.name));
+        fs::write(&path, format!("{:#}\n", abi.entries))
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        generated.push(path);
+    }
     
     // Lower to IR
-    let mut ir = astrixa::lowering::lower(&ast);
+    crash::set_phase("lower");
+    stats.enter("lower");
+    // Debug info maps each statement's code back to it; WAT has no use for that
+    let mut ir = astrixa::lowering::lower_for_build(&ast, profile.assertions, profile.debug_info && !wat)
+        .map_err(|errors| located("Cannot compile", input, &errors))?;
+    ir.memory = memory;
+    if profile.overflow_checks {
+        astrixa::overflow::check_arithmetic(&mut ir);
+    }
     
-    // Optimize if in release mode
-    if optimize {
-        ir = astrixa::opt::optimize_module(&ir);
+    // Optimize at the build's level; -O0 runs no passes
+    if profile.opt_level != Level::O0 {
+        crash::set_phase("optimize");
+        stats.enter("optimize");
+        ir = astrixa::opt::optimize_module_with(&ir, profile.opt_level.passes());
     }
     
+    // Plugin passes see the final IR, optimized or not
+    crash::set_phase("plugin passes");
+    stats.enter("plugin passes");
+    session.run_ir_passes(&mut ir);
+    
     let function_count = ir.functions.len();
+    stats.count_ir(&ir);
+    
+    // Bytecode for the VM, with the declarations' spans as debug info
+    if target == Target::Native {
+        crash::set_phase("codegen");
+        stats.enter("codegen");
+        let mut module = astrixa::translate::to_bytecode_module(&ir)
+            .map_err(|e| format!("--target=native: {}", e))?;
+        if !profile.debug_info {
+            module.strip();
+        }
+        fs::write(output, module.to_bytes())
+            .map_err(|e| format!("Failed to write output file: {}", e))?;
+        stats.finish();
+        return Ok(CompileResult {
+            function_count,
+            generated,
+            source_map: None,
+            entry_points: Vec::new(),
+            duration: start.elapsed().as_secs_f64(),
+            stats,
+        });
+    }
     
-    // Generate WASM
-    let wasm = astrixa::codegen::wasm::generate_wasm_module(&ir);
+    // Generate WASM, for contracts through the chain family's backend
+    crash::set_phase("codegen");
+    stats.enter("codegen");
+    let (wasm, entry_points) = match target {
+        Target::Contract(_) if ir.contracts.is_empty() => {
+            return Err("--target=contract: src/main.ax declares no contracts".to_string());
+        }
+        Target::Contract(chain) => {
+            let artifact = chain.backend().lower(&ir);
+            (artifact.wat, artifact.entry_points)
+        }
+        Target::Wasi => {
+            let wat = astrixa::codegen::wasi::lower(&ir).map_err(|e| format!("--target=wasi: {}", e))?;
+            (wat, vec!["_start".to_string()])
+        }
+        Target::Web => {
+            let wasm_file = output.with_extension("wasm");
+            let wasm_name = wasm_file.file_name().unwrap_or_default().to_string_lossy();
+            let artifact = astrixa::codegen::web::lower(&ast, &ir, &wasm_name);
+            for (extension, text) in [("js", &artifact.js), ("d.ts", &artifact.dts)] {
+                let path = output.with_extension(extension);
+                fs::write(&path, text)
+                    .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+                generated.push(path);
+            }
+            (artifact.wat, Vec::new())
+        }
+        Target::Script => (astrixa::codegen::wasm::generate_wasm_module_with(&ir, &host), Vec::new()),
+        Target::Native => unreachable!("written as bytecode above"),
+    };
     
-    // Write output
-    fs::write(output, wasm)
+    // Write output, assembled unless the text was asked for; with debug
+    // info, named and mapped back to the source
+    let mut source_map = None;
+    let bytes = if wat {
+        wasm.into_bytes()
+    } else if profile.debug_info {
+        let map_path = PathBuf::from(format!("{}.map", output.display()));
+        let file_name = |path: &Path| path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        let spans = ir.functions.iter().map(|f| (f.name.replace('.', "_"), f.span)).collect();
+        let assembled = astrixa::codegen::binary::assemble_debug(&wasm, Some(&file_name(&map_path)), &spans)
+            .map_err(|e| format!("Failed to assemble WASM: {}", e))?;
+        let functions = assembled.functions.iter().filter_map(|(id, offset)| {
+            let function = ir.functions.iter().find(|f| f.name.replace('.', "_") == *id)?;
+            Some((*offset, function.span))
+        });
+        let lines = assembled.lines.iter().map(|&(offset, line, column)| {
+            (offset, astrixa::ast::Span { start_line: line, start_column: column, end_line: line, end_column: column })
+        });
+        let points: Vec<_> = functions.chain(lines).collect();
+        let map = astrixa::sourcemap::wasm_source_map(&file_name(output), &relative_to(input, output), &source, &points);
+        fs::write(&map_path, map)
+            .map_err(|e| format!("Failed to write {}: {}", map_path.display(), e))?;
+        source_map = Some(map_path);
+        assembled.bytes
+    } else {
+        // A map left from a debug build would no longer match
+        let _ = fs::remove_file(format!("{}.map", output.display()));
+        astrixa::codegen::binary::assemble(&wasm)
+            .map_err(|e| format!("Failed to assemble WASM: {}", e))?
+    };
+    if !wat {
+        crash::set_phase("validate");
+        stats.enter("validate");
+        validate(&bytes)?;
+    }
+    fs::write(output, bytes)
         .map_err(|e| format!("Failed to write output file: {}", e))?;
     
+    stats.finish();
     let duration = start.elapsed().as_secs_f64();
     
     Ok(CompileResult {
         function_count,
+        generated,
+        source_map,
+        entry_points,
         duration,
+        stats,
     })
 }
 
-fn check_file(input: &PathBuf) -> Result<(), String> {
+/// Fail on a module runtimes would refuse. Codegen should never make one,
+/// so this is a compiler bug; better found here than by the user's first
+/// run.
+pub fn validate(bytes: &[u8]) -> Result<(), String> {
+    astrixa::codegen::binary::validate(bytes).map_err(|e| {
+        format!("Generated an invalid WASM module: {}\nThis is a compiler bug; please report it with the source that caused it", e)
+    })
+}
+
+/// `input`'s path as seen from the directory `output` is written to, for
+/// a source map next to the module
+fn relative_to(input: &Path, output: &Path) -> String {
+    let from = output.parent().and_then(|dir| dir.canonicalize().ok());
+    let to = input.canonicalize().ok();
+    let (Some(from), Some(to)) = (from, to) else {
+        return input.display().to_string();
+    };
+    let common = from.components().zip(to.components()).take_while(|(a, b)| a == b).count();
+    let mut parts: Vec<String> = from.components().skip(common).map(|_| "..".to_string()).collect();
+    parts.extend(to.components().skip(common).map(|c| c.as_os_str().to_string_lossy().into_owned()));
+    parts.join("/")
+}
+
+fn check_file(input: &PathBuf, edition: Edition, session: &Session) -> Result<Vec<astrixa::ast::Stmt>, String> {
     // Read source file
     let source = fs::read_to_string(input)
         .map_err(|e| format!("Failed to read source file: {}", e))?;
+    crash::set_source(input, &source);
     
     // Parse
+    crash::set_phase("parse");
     let lexer = astrixa::lexer::Lexer::new(&source);
     let mut parser = astrixa::parser::Parser::new(lexer);
+    parser.set_edition(edition);
     let ast = parser.parse()
-        .map_err(|e| format!("Parse error: {:?}", e))?;
+        .map_err(|_| syntax_errors(input, parser.errors()))?;
+    print_warnings(input, parser.warnings());
     
     // Type check
+    crash::set_phase("typecheck");
     let mut checker = astrixa::typechecker::TypeChecker::new();
     checker.check(&ast)
-        .map_err(|e| format!("Type error: {:?}", e))?;
+        .map_err(|errors| type_errors(input, &errors))?;
+    
+    crash::set_phase("lint");
+    check_lints(input, session, &ast, parser.spans())?;
+    print_warnings(input, &astrixa::loops::analyze(&ast));
+    print_warnings(input, &astrixa::unused::analyze(&ast));
     
     println!("   {} Syntax and types", "Checked".green());
     
-    Ok(())
+    Ok(ast)
+}
+
+/// Report plugin lint findings; any finding fails the build
+fn check_lints(
+    input: &Path,
+    session: &Session,
+    ast: &[astrixa::ast::Stmt],
+    spans: &[astrixa::ast::Span],
+) -> Result<(), String> {
+    let findings = session.lint(ast, spans);
+    for finding in &findings {
+        eprintln!("{}: {}", "lint".red().bold(), finding.message);
+        eprintln!("   {} {}:{}:{}", "-->".cyan(), input.display(), finding.line, finding.column);
+    }
+    
+    match findings.len() {
+        0 => Ok(()),
+        1 => Err("1 lint error".to_string()),
+        n => Err(format!("{} lint errors", n)),
+    }
+}
+
+/// Every syntax error in `input` as one error message
+pub fn syntax_errors(input: &Path, errors: &[CompileError]) -> String {
+    located("Parse failed", input, errors)
+}
+
+/// A failed type check as one error message
+pub fn type_errors(input: &Path, errors: &[CompileError]) -> String {
+    located("Type check failed", input, errors)
+}
+
+/// `heading`, then each error with its location on a line of its own,
+/// followed by its help if it has any. One outside the file, in the
+/// standard library, has only the file.
+fn located(heading: &str, input: &Path, errors: &[CompileError]) -> String {
+    let lines: Vec<String> = errors
+        .iter()
+        .map(|e| {
+            let error = match e.line {
+                0 => format!("{}: {}", input.display(), e.message),
+                line => format!("{}:{}:{}: {}", input.display(), line, e.column, e.message),
+            };
+            match &e.help {
+                Some(help) => format!("{}\n   {} {}", error, "help:".cyan(), help),
+                None => error,
+            }
+        })
+        .collect();
+    format!("{}:\n{}", heading, lines.join("\n"))
+}
+
+/// Report edition deprecations, gas and unused code warnings; they never
+/// fail the build
+pub fn print_warnings(input: &Path, warnings: &[Warning]) {
+    for warning in warnings {
+        eprintln!("{}: {}", format!("warning[{}]", warning.code).yellow().bold(), warning.message);
+        eprintln!(
+            "   {} {}:{}:{}",
+            "-->".cyan(),
+            input.display(),
+            warning.span.start_line,
+            warning.span.start_column
+        );
+        if let Some(help) = &warning.help {
+            eprintln!("   {} {}", "help:".cyan(), help);
+        }
+    }
 }
@@ -0,0 +1,73 @@
+// Decode-tx Command: print the call a transaction makes, from known ABIs
+
+use colored::*;
+use std::fs;
+use astrixa::abi::ContractAbi;
+use astrixa::multicall::RpcCallSource;
+use astrixa::txdecode::{self, Input, Transaction};
+use astrixa::u256::U256;
+use crate::config::find_project_root;
+use crate::crash;
+use super::build;
+
+pub fn decode_tx(input: &str) -> Result<(), String> {
+    let transaction = match Input::parse(input)? {
+        Input::Calldata(input) => Transaction { to: None, value: U256::zero(), input },
+        Input::Hash(hash) => {
+            let source = RpcCallSource::from_env()
+                .ok_or_else(|| astrixa::messages::render("E0475", &[&"decode-tx"]))?;
+            println!("{} {}", "Fetching".green().bold(), hash);
+            let transaction = source.transaction(&hash)?;
+            match &transaction.to {
+                Some(to) => println!("   {} {}", "to".dimmed(), to),
+                None => println!("   {} {}", "to".dimmed(), "(contract deployment)".dimmed()),
+            }
+            println!("   {} {} wei", "value".dimmed(), transaction.value);
+            transaction
+        }
+    };
+    if transaction.input.is_empty() {
+        println!();
+        println!("{} a plain transfer, with no calldata", "Decoded".green().bold());
+        return Ok(());
+    }
+
+    let contracts = project_abis()?;
+    if contracts.is_empty() {
+        println!("{}", "   note: not in a project with contracts; only the standard bindings' ABIs are known".dimmed());
+    }
+    let call = txdecode::decode_call(&transaction.input, &txdecode::known_functions(&contracts))?;
+
+    println!();
+    println!("{} {}.{}", "Decoded".green().bold(), call.abi, call.function.bold());
+    for (name, kind, value) in &call.args {
+        println!("   {}: {} = {}", name, kind.dimmed(), txdecode::render(value));
+    }
+    Ok(())
+}
+
+/// The ABIs of the current project's contracts, or none outside a project
+fn project_abis() -> Result<Vec<ContractAbi>, String> {
+    let Ok(root) = find_project_root() else {
+        return Ok(Vec::new());
+    };
+    let input = root.join("src/main.ax");
+    let Ok(source) = fs::read_to_string(&input) else {
+        return Ok(Vec::new());
+    };
+    crash::set_source(&input, &source);
+
+    crash::set_phase("parse");
+    let lexer = astrixa::lexer::Lexer::new(&source);
+    let mut parser = astrixa::parser::Parser::new(lexer);
+    let ast = parser.parse()
+        .map_err(|_| build::syntax_errors(&input, parser.errors()))?;
+
+    crash::set_phase("typecheck");
+    let mut checker = astrixa::typechecker::TypeChecker::new();
+    checker.check(&ast)
+        .map_err(|errors| build::type_errors(&input, &errors))?;
+
+    crash::set_phase("abi");
+    astrixa::abi::contract_abis(&ast, checker.functions())
+}
@@ -0,0 +1,83 @@
+// Fuzz Command: random call sequences against a contract's #[invariant]s
+
+use colored::*;
+use std::fs;
+use std::path::Path;
+use std::time::Instant;
+use astrixa::fuzz::FuzzConfig;
+use crate::crash;
+use super::build;
+
+pub fn fuzz_file(input: &str, config: &FuzzConfig) -> Result<(), String> {
+    let start = Instant::now();
+    let input = Path::new(input);
+
+    println!(
+        "{} {} ({} runs of {} calls, seed {})",
+        "Fuzzing".green().bold(),
+        input.display(),
+        config.runs,
+        config.depth,
+        config.seed
+    );
+
+    let source = fs::read_to_string(input)
+        .map_err(|e| format!("Failed to read {}: {}", input.display(), e))?;
+    crash::set_source(input, &source);
+
+    // Parse
+    crash::set_phase("parse");
+    let lexer = astrixa::lexer::Lexer::new(&source);
+    let mut parser = astrixa::parser::Parser::new(lexer);
+    let ast = parser.parse()
+        .map_err(|_| build::syntax_errors(input, parser.errors()))?;
+    build::print_warnings(input, parser.warnings());
+
+    // Type check
+    crash::set_phase("typecheck");
+    let mut checker = astrixa::typechecker::TypeChecker::new();
+    checker.check(&ast)
+        .map_err(|errors| build::type_errors(input, &errors))?;
+
+    crash::set_phase("fuzz");
+    let reports = astrixa::fuzz::fuzz(&ast, config)?;
+    if reports.is_empty() {
+        return Err(format!("No contract in {} declares an #[invariant] method", input.display()));
+    }
+
+    println!();
+    let mut failed = 0;
+    for report in &reports {
+        let Some(failure) = &report.failure else {
+            println!(
+                "   {} {} ... {} ({} invariants, {} calls)",
+                "contract".dimmed(),
+                report.contract,
+                "ok".green(),
+                report.invariants.len(),
+                report.calls
+            );
+            continue;
+        };
+
+        failed += 1;
+        println!("   {} {} ... {}", "contract".dimmed(), report.contract, "FAILED".red());
+        println!("      invariant {} broke: {}", failure.invariant.bold(), failure.reason.dimmed());
+        println!("      minimal reproducer ({} calls):", failure.calls.len());
+        for (i, call) in failure.calls.iter().enumerate() {
+            println!("        {}. {}.{}", i + 1, report.contract, call);
+        }
+    }
+
+    println!();
+    if failed > 0 {
+        return Err(format!(
+            "{} of {} contracts broke an invariant (replay with --seed {})",
+            failed,
+            reports.len(),
+            config.seed
+        ));
+    }
+    println!("{} in {:.2}s", "Finished".green().bold(), start.elapsed().as_secs_f64());
+    Ok(())
+}
@@ -0,0 +1,52 @@
+// Editor grammar generation
+
+use colored::*;
+use std::fs;
+use std::path::PathBuf;
+
+pub fn emit_grammar(format: &str, output: Option<&String>) -> Result<(), String> {
+    let files: Vec<(&str, String)> = match format {
+        "textmate" => vec![(
+            "astrixa.tmLanguage.json",
+            format!("{:#}\n", astrixa::grammar::textmate()),
+        )],
+        "treesitter" => astrixa::grammar::tree_sitter(),
+        _ => return Err(format!("Unknown grammar format '{}'", format)),
+    };
+    
+    let output = match output {
+        Some(out) => PathBuf::from(out),
+        None => {
+            for (name, contents) in &files {
+                if files.len() > 1 {
+                    println!("// ==> {} <==", name);
+                }
+                print!("{}", contents);
+            }
+            return Ok(());
+        }
+    };
+    
+    if format == "textmate" {
+        // A single file: the output path names it
+        write_file(&output, &files[0].1)?;
+        println!("   {} {}", "Generated".green(), output.display());
+    } else {
+        for (name, contents) in &files {
+            let path = output.join(name);
+            write_file(&path, contents)?;
+            println!("   {} {}", "Generated".green(), path.display());
+        }
+    }
+    
+    Ok(())
+}
+
+fn write_file(path: &PathBuf, contents: &str) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    fs::write(path, contents)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
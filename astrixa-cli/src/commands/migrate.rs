@@ -0,0 +1,119 @@
+// Migrate Command: upgrade a project to a newer language edition
+
+use colored::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+use astrixa::edition::Edition;
+use astrixa::migrate::{migrate, unified_diff};
+use crate::config::{Config, find_project_root};
+use crate::crash;
+use super::build;
+
+pub fn migrate_project(edition: Option<&String>, dry_run: bool) -> Result<(), String> {
+    let root = find_project_root()?;
+    let config_path = root.join("astrixa.toml");
+    let mut config = Config::load(&config_path)?;
+    config.diagnostics.apply();
+
+    let from = config.edition()?;
+    let to = match edition {
+        Some(text) => Edition::parse(text).ok_or_else(|| {
+            let supported: Vec<&str> = Edition::ALL.iter().map(|e| e.as_str()).collect();
+            format!("Unknown edition '{}' (supported: {})", text, supported.join(", "))
+        })?,
+        None => Edition::LATEST,
+    };
+    if to < from {
+        return Err(format!("Cannot migrate from edition {} back to {}", from, to));
+    }
+
+    let mode = if dry_run { " (dry run)" } else { "" };
+    println!("{} {} from edition {} to {}{}", "Migrating".green().bold(), config.package.name, from, to, mode);
+
+    // Migrate everything in memory first, so a parse error leaves no file half-done
+    crash::set_phase("migrate");
+    let mut updates = Vec::new();
+    let mut fixes = 0;
+    for path in source_files(&root.join("src"))? {
+        let source = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        crash::set_source(&path, &source);
+
+        let migration = migrate(&source, to)
+            .map_err(|e| format!("Parse error in {}: {:?}", path.display(), e))?;
+        build::print_warnings(&path, &migration.remaining);
+
+        if migration.changed() {
+            fixes += migration.applied.len();
+            updates.push((path, source, migration.source));
+        }
+    }
+
+    for (path, before, after) in &updates {
+        let relative = path.strip_prefix(&root).unwrap_or(path).display().to_string();
+        if dry_run {
+            print_diff(&unified_diff(&relative, before, after));
+        } else {
+            fs::write(path, after)
+                .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+            println!("   {} {}", "Updated".green(), relative);
+        }
+    }
+
+    let bumps_edition = from != to;
+    if bumps_edition {
+        if dry_run {
+            println!("   {} astrixa.toml edition = \"{}\"", "Would set".cyan(), to);
+        } else {
+            config.package.edition = Some(to.to_string());
+            config.save(&config_path)?;
+            println!("   {} astrixa.toml edition = \"{}\"", "Set".green(), to);
+        }
+    }
+
+    println!();
+    if updates.is_empty() && !bumps_edition {
+        println!("{}", "Nothing to migrate".dimmed());
+    } else if dry_run {
+        println!("{} {} fixes in {} files; re-run without --dry-run to apply", "✅".green(), fixes, updates.len());
+    } else {
+        println!("{} {} fixes in {} files", "✅ Migrated".green().bold(), fixes, updates.len());
+    }
+
+    Ok(())
+}
+
+/// Every .ax file under `dir`, in a stable order
+pub(crate) fn source_files(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut files = Vec::new();
+    let entries = fs::read_dir(dir)
+        .map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+
+    for entry in entries {
+        let path = entry.map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?.path();
+        if path.is_dir() {
+            files.extend(source_files(&path)?);
+        } else if path.extension().is_some_and(|ext| ext == "ax") {
+            files.push(path);
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+fn print_diff(diff: &str) {
+    for line in diff.lines() {
+        if line.starts_with("---") || line.starts_with("+++") {
+            println!("{}", line.bold());
+        } else if line.starts_with("@@") {
+            println!("{}", line.cyan());
+        } else if line.starts_with('-') {
+            println!("{}", line.red());
+        } else if line.starts_with('+') {
+            println!("{}", line.green());
+        } else {
+            println!("{}", line);
+        }
+    }
+}
@@ -4,3 +4,16 @@ pub mod new;
 pub mod build;
 pub mod run;
 pub mod add;
+pub mod grammar;
+pub mod test;
+pub mod migrate;
+pub mod translate;
+pub mod bindgen;
+pub mod fuzz;
+pub mod simulate;
+pub mod verify;
+pub mod storage_diff;
+pub mod decode_tx;
+pub mod self_bench;
+pub mod spec;
+pub mod toolchain;
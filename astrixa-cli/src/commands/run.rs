@@ -1,23 +1,35 @@
 // STEP 50: Run Command
 
 use colored::*;
-use std::path::PathBuf;
+use std::path::Path;
 use std::process::Command;
+use std::time::Duration;
+use astrixa::shutdown::{self, Signals, Stop};
 use crate::config::{Config, find_project_root};
 use super::build;
 
-pub fn run_project(release: bool) -> Result<(), String> {
+pub fn run_project(release: bool, opt_level: Option<&String>, plugins: &[String]) -> Result<(), String> {
     let root = find_project_root()?;
     let config = Config::load(root.join("astrixa.toml"))?;
+    if !config.host.is_default() {
+        return Err("astrixa run hosts a module under env; [host] in astrixa.toml builds it for another runtime".to_string());
+    }
     
     println!("{} {}", "Running".green().bold(), config.package.name);
     println!();
     
-    // Build the project first
-    build::build_project(release, None)?;
+    // Build the project first, with the web loader as its host: it
+    // implements every import a module can have, as `astrixa spec` runs it
+    build::build_project(&build::BuildOptions {
+        release,
+        opt_level,
+        target: build::Target::Web,
+        plugins,
+        ..Default::default()
+    })?;
     
     // Determine WASM file path
-    let wasm_file = root.join("build").join(format!("{}.wat", config.package.name));
+    let wasm_file = root.join("build").join(format!("{}.wasm", config.package.name));
     
     if !wasm_file.exists() {
         return Err("Build artifact not found. Build may have failed.".to_string());
@@ -37,100 +49,163 @@ pub fn run_project(release: bool) -> Result<(), String> {
     Ok(())
 }
 
-fn execute_wasm(wasm_file: &PathBuf) -> Result<(), String> {
-    // For now, we'll create a simple Node.js runner
-    // In a production system, you'd use wasmtime or wasmer
+/// Run a bytecode file's `main` on the VM, without a project
+pub fn run_bytecode(file: &Path) -> Result<(), String> {
+    if file.extension().is_none_or(|ext| ext != "axb") {
+        return Err(format!("{} is not a bytecode file; build one with `astrixa build --target=native`", file.display()));
+    }
+    let bytes = std::fs::read(file)
+        .map_err(|e| format!("Failed to read {}: {}", file.display(), e))?;
+    let module = astrixa::bytecode::BytecodeModule::from_bytes(&bytes)
+        .map_err(|e| format!("{}: {}", file.display(), e))?;
+    
+    println!("{} {}", "Running".green().bold(), file.display());
+    println!();
+    println!("{}", "─".repeat(50).dimmed());
+    println!("{}", "Output:".cyan().bold());
+    println!();
+    
+    // On the scheduler, so Ctrl-C or SIGTERM runs the program's
+    // on_shutdown handlers before it stops
+    let mut vm = astrixa::vm::VM::new();
+    vm.load_module(&module, "main")
+        .map_err(|e| format!("Runtime error: {}", e))?;
+    let mut scheduler = astrixa::vm::Scheduler::new(SLICE).catch_signals();
+    let id = scheduler.spawn(vm);
+    while scheduler.tick() > 0 {}
+    let result = scheduler.result(id).cloned().expect("a finished VM has a result");
+    if let Some(signal) = scheduler.interrupted() {
+        if let Err(e) = result {
+            eprintln!("{}", e);
+        }
+        std::process::exit(shutdown::exit_code(signal));
+    }
+    let result = result.map_err(|e| format!("Runtime error: {}", e))?;
+    if !matches!(result, astrixa::interpreter::Value::Null) {
+        println!("Program returned: {}", astrixa::vm::printed(result));
+    }
+    
+    println!();
+    println!("{}", "─".repeat(50).dimmed());
+    
+    Ok(())
+}
+
+/// The exit code of a program that panicked, as WASI builds exit with
+const PANIC_EXIT_CODE: i32 = 101;
+
+/// Instructions a bytecode program runs between checks for a signal
+const SLICE: usize = 10_000;
+
+fn execute_wasm(wasm_file: &Path) -> Result<(), String> {
+    // The web build's loader is the host: it defines each of the module's
+    // imports. Node only takes it as an ES module under an .mjs name.
+    let dir = wasm_file.parent().unwrap();
+    let loader = dir.join("_run_loader.mjs");
+    std::fs::copy(wasm_file.with_extension("js"), &loader)
+        .map_err(|e| format!("Failed to copy the loader: {}", e))?;
     
     let js_runner = format!(r#"
-const fs = require('fs');
-const wabt = require('wabt')();
+import {{ readFileSync }} from 'node:fs';
+import {{ load }} from './_run_loader.mjs';
 
-async function run() {{
-    try {{
-        // Read WAT file
-        const watContent = fs.readFileSync('{}', 'utf8');
-        
-        // Convert WAT to WASM
-        const module = wabt.parseWat('module.wat', watContent);
-        const {{ buffer }} = module.toBinary({{}});
-        
-        // Runtime environment
-        const memory = new WebAssembly.Memory({{ initial: 1 }});
-        const imports = {{
-            env: {{
-                memory: memory,
-                print_str: (ptr, len) => {{
-                    const bytes = new Uint8Array(memory.buffer, ptr, len);
-                    const str = new TextDecoder().decode(bytes);
-                    console.log(str);
-                }},
-                println_str: (ptr, len) => {{
-                    const bytes = new Uint8Array(memory.buffer, ptr, len);
-                    const str = new TextDecoder().decode(bytes);
-                    console.log(str);
-                }},
-                panic: (ptr, len) => {{
-                    const bytes = new Uint8Array(memory.buffer, ptr, len);
-                    const str = new TextDecoder().decode(bytes);
-                    throw new Error('Panic: ' + str);
-                }},
-            }}
+// Where each function was declared, by index, from the module's
+// astrixa.spans section: a count, then each function's index and span
+function declarations(module) {{
+    const declared = new Map();
+    for (const section of WebAssembly.Module.customSections(module, 'astrixa.spans')) {{
+        const bytes = new Uint8Array(section);
+        let at = 0;
+        const uleb = () => {{
+            let n = 0, shift = 0, byte;
+            do {{
+                byte = bytes[at++];
+                n += (byte & 0x7f) * 2 ** shift;
+                shift += 7;
+            }} while (byte & 0x80);
+            return n;
         }};
-        
-        // Instantiate and run
-        const instance = await WebAssembly.instantiate(buffer, imports);
-        
-        if (instance.instance.exports.main) {{
-            const result = instance.instance.exports.main();
-            if (result !== undefined) {{
-                console.log('Program returned:', result);
-            }}
-        }} else {{
-            console.error('No main function found');
-            process.exit(1);
+        for (let count = uleb(); count > 0; count--) {{
+            const [index, line, column] = [uleb(), uleb(), uleb()];
+            uleb();
+            uleb();
+            declared.set(index, `line ${{line}}, column ${{column}}`);
         }}
-    }} catch (error) {{
+    }}
+    return declared;
+}}
+
+const buffer = readFileSync('{}');
+try {{
+    const program = await load(buffer);
+    if (!program.main) {{
+        console.error('No main function found');
+        process.exit(1);
+    }}
+    const result = program.main();
+    if (result !== undefined) {{
+        // Ints come back as BigInt, tuples as arrays
+        console.log('Program returned:', String(result));
+    }}
+}} catch (error) {{
+    // The loader's panic() throws with its message after "Panic: "
+    if (!error.message.startsWith('Panic: ')) {{
         console.error('Runtime error:', error.message);
         process.exit(1);
     }}
+    // The message, then the functions it unwound through, innermost
+    // first, with where each was declared
+    console.error(error.message);
+    const declared = declarations(new WebAssembly.Module(buffer));
+    for (const [, name, index] of error.stack.matchAll(/at (\S+) \(wasm:\/\/[^)]*wasm-function\[(\d+)\]/g)) {{
+        const at = declared.get(Number(index));
+        console.error(`    at ${{name}}` + (at ? ` (declared at ${{at}})` : ''));
+    }}
+    process.exit({});
 }}
-
-run();
-"#, wasm_file.display());
+"#, wasm_file.display(), PANIC_EXIT_CODE);
     
     // Write an ephemeral JS runner file
-    let temp_js = wasm_file.parent().unwrap().join("_run_ephemeral.js");
+    let temp_js = dir.join("_run_ephemeral.mjs");
     std::fs::write(&temp_js, js_runner)
         .map_err(|e| format!("Failed to create runner: {}", e))?;
     
-    // Execute with Node.js
-    let output = Command::new("node")
+    // Execute with Node.js, its output passed straight through. Ctrl-C or
+    // SIGTERM stops it, and this reports the interruption instead of dying
+    // with it. A module's on_shutdown handlers do not run: Node delivers
+    // no signal while the module's code runs.
+    let signals = Signals::catch(Stop::default());
+    let status = Command::new("node")
         .arg(&temp_js)
-        .output();
+        .spawn()
+        .and_then(|mut child| loop {
+            if let Some(status) = child.try_wait()? {
+                break Ok(status);
+            }
+            // Ctrl-C reaches Node too; SIGTERM may have been sent to us alone
+            if signals.received().is_some() {
+                let _ = child.kill();
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        });
     
-    // Clean up runner file
+    // Clean up the runner and its loader
     let _ = std::fs::remove_file(&temp_js);
+    let _ = std::fs::remove_file(&loader);
     
-    match output {
-        Ok(output) => {
-            // Print stdout
-            if !output.stdout.is_empty() {
-                print!("{}", String::from_utf8_lossy(&output.stdout));
-            }
-            
-            // Print stderr
-            if !output.stderr.is_empty() {
-                eprint!("{}", String::from_utf8_lossy(&output.stderr));
-            }
-            
-            if !output.status.success() {
-                return Err("Program exited with error".to_string());
-            }
-            
-            Ok(())
-        }
-        Err(e) => {
-            Err(format!("Failed to execute: {}. Is Node.js installed?", e))
-        }
+    let status = status.map_err(|e| format!("Failed to execute: {}. Is Node.js installed?", e))?;
+    if let Some(signal) = signals.received() {
+        eprintln!("{}", astrixa::messages::render("E0488", &[&shutdown::name(signal)]));
+        std::process::exit(shutdown::exit_code(signal));
     }
+    
+    // A panic has reported itself; exit as the program did
+    if status.code() == Some(PANIC_EXIT_CODE) {
+        std::process::exit(PANIC_EXIT_CODE);
+    }
+    if !status.success() {
+        return Err("Program exited with error".to_string());
+    }
+    
+    Ok(())
 }
@@ -0,0 +1,67 @@
+// Self-Bench Command: time the compiler's phases, gating on a baseline
+
+use colored::*;
+use std::fs;
+use std::time::Instant;
+use astrixa::bench;
+
+/// Corpus size: big enough that each case takes a measurable time
+const FUNCTIONS: usize = 200;
+
+/// Time each phase on each corpus (see `astrixa::bench`). With `baseline`,
+/// compare against the times stored there and fail if any case got more
+/// than 10% slower; with `save`, store these times there. The numbers are
+/// this binary's, so gate with a release build.
+pub fn self_bench(baseline: Option<&str>, save: Option<&str>) -> Result<(), String> {
+    let start = Instant::now();
+    let stored = match baseline {
+        Some(path) => {
+            let json = fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read baseline {}: {} (record one with --save)", path, e))?;
+            Some(bench::baseline_from_json(&json).map_err(|e| format!("{}: {}", path, e))?)
+        }
+        None => None,
+    };
+
+    println!("{} the compiler ({} functions per corpus)", "Benchmarking".green().bold(), FUNCTIONS);
+    let corpora = bench::corpora(FUNCTIONS);
+    let results = bench::run_all(&corpora);
+    let regressions = stored.as_ref().map(|stored| bench::regressions(stored, &results)).unwrap_or_default();
+
+    println!();
+    let width = results.keys().map(|case| case.len()).max().unwrap_or(0);
+    // In the order a build runs the phases
+    let cases = bench::PHASES.iter().flat_map(|phase| corpora.iter().map(move |corpus| format!("{}/{}", phase, corpus.name)));
+    for case in cases {
+        let nanos = &results[&case];
+        let time = format!("{:>10.1} us", *nanos as f64 / 1000.0);
+        let change = match stored.as_ref().and_then(|stored| stored.get(&case)) {
+            Some(&before) if before > 0 => {
+                let change = format!("{:+.1}%", (*nanos as f64 / before as f64 - 1.0) * 100.0);
+                if regressions.iter().any(|r| r.case == case) { change.red().bold() } else { change.dimmed() }
+            }
+            Some(_) | None if stored.is_some() => "new".dimmed(),
+            _ => "".normal(),
+        };
+        println!("   {:width$} {} {}", case, time, change, width = width);
+    }
+    println!();
+
+    if let Some(path) = save {
+        fs::write(path, bench::baseline_to_json(&results))
+            .map_err(|e| format!("Failed to write baseline {}: {}", path, e))?;
+        println!("   {} baseline {}", "Saved".green(), path);
+    }
+    if !regressions.is_empty() {
+        let worst = regressions.iter().map(|r| r.slowdown()).fold(0.0, f64::max);
+        return Err(format!(
+            "{} of {} cases regressed by more than {:.0}% (worst {:+.1}%)",
+            regressions.len(),
+            results.len(),
+            bench::REGRESSION_THRESHOLD * 100.0,
+            worst * 100.0
+        ));
+    }
+    println!("{} in {:.2}s", "Finished".green().bold(), start.elapsed().as_secs_f64());
+    Ok(())
+}
@@ -0,0 +1,237 @@
+// Simulate Command: run a program in the interpreter, recording contract
+// storage writes and events, optionally stepping through them interactively
+
+use colored::*;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+use astrixa::timeline::{Record, Timeline};
+use crate::config::find_project_root;
+use crate::crash;
+use super::build;
+
+pub fn simulate(input: Option<&String>, inspect: bool) -> Result<(), String> {
+    let input = match input {
+        Some(path) => PathBuf::from(path),
+        None => find_project_root()?.join("src/main.ax"),
+    };
+
+    println!("{} {}", "Simulating".green().bold(), input.display());
+
+    let source = fs::read_to_string(&input)
+        .map_err(|e| format!("Failed to read {}: {}", input.display(), e))?;
+    crash::set_source(&input, &source);
+
+    // Parse
+    crash::set_phase("parse");
+    let lexer = astrixa::lexer::Lexer::new(&source);
+    let mut parser = astrixa::parser::Parser::new(lexer);
+    let ast = parser.parse()
+        .map_err(|_| build::syntax_errors(&input, parser.errors()))?;
+    build::print_warnings(&input, parser.warnings());
+
+    // Type check
+    crash::set_phase("typecheck");
+    let mut checker = astrixa::typechecker::TypeChecker::new();
+    checker.check(&ast)
+        .map_err(|errors| build::type_errors(&input, &errors))?;
+
+    // Run, recording every storage write and event. A failing run still
+    // has a timeline worth inspecting, up to the failure.
+    crash::set_phase("simulate");
+    let mut interpreter = astrixa::interpreter::Interpreter::new();
+    interpreter.record();
+    let result = interpreter.run(ast);
+    let timeline = interpreter.take_timeline().unwrap_or_default();
+
+    println!();
+    print_log(&timeline);
+    println!();
+    match &result {
+        Ok(()) => println!(
+            "{} {} transactions, {} steps",
+            "Finished".green().bold(),
+            timeline.transactions.len(),
+            timeline.latest()
+        ),
+        Err(e) => {
+            println!("{} {}", "Failed".red().bold(), e);
+            if let Some(span) = interpreter.error_span() {
+                println!("   {} {}:{}:{}", "-->".cyan(), input.display(), span.start_line, span.start_column);
+            }
+            if let Some(panic) = interpreter.take_panic() {
+                for frame in &panic.backtrace {
+                    println!("   {} {} ({}:{}:{})", "at".dimmed(), frame.function, input.display(), frame.span.start_line, frame.span.start_column);
+                }
+                if panic.reverted {
+                    let data: String = panic.revert_data().iter().map(|b| format!("{:02x}", b)).collect();
+                    println!("   {} 0x{}", "revert data".cyan(), data);
+                }
+            }
+        }
+    }
+
+    // Stopped by Ctrl-C or SIGTERM: exit as a process killed by it would
+    if let Some(signal) = interpreter.interrupted() {
+        std::process::exit(astrixa::shutdown::exit_code(signal));
+    }
+    if inspect {
+        Inspector { timeline: &timeline, version: timeline.latest() }.run()?;
+    }
+    result
+}
+
+/// Every step, grouped by transaction
+fn print_log(timeline: &Timeline) {
+    let mut current = None;
+    for (i, step) in timeline.steps.iter().enumerate() {
+        if step.transaction != current {
+            current = step.transaction;
+            let call = current.map_or("(outside any transaction)", |tx| timeline.transactions[tx].as_str());
+            println!("   {} {}", "tx".cyan().bold(), call);
+        }
+        println!("      #{} {}", i + 1, step.record);
+    }
+}
+
+const HELP: &str = "\
+  next, n [count]     step forwards
+  back, b [count]     step backwards
+  goto, g VERSION     jump to a version (0 is before the first step)
+  state, s [VERSION]  show every storage slot
+  diff, d A [B]       storage changes from version A to B (default: current)
+  log, l              list every step
+  quit, q             leave the inspector";
+
+struct Inspector<'a> {
+    timeline: &'a Timeline,
+    version: usize,
+}
+
+impl Inspector<'_> {
+    fn run(&mut self) -> Result<(), String> {
+        println!();
+        println!("{} versions 0..={}; type 'help' for commands", "Inspecting".cyan().bold(), self.timeline.latest());
+
+        let stdin = io::stdin();
+        let mut lines = stdin.lock().lines();
+        loop {
+            print!("[{}/{}] > ", self.version, self.timeline.latest());
+            io::stdout().flush().map_err(|e| e.to_string())?;
+
+            let Some(line) = lines.next() else {
+                println!();
+                return Ok(());
+            };
+            let line = line.map_err(|e| format!("Failed to read command: {}", e))?;
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words.as_slice() {
+                [] => {}
+                ["quit" | "q" | "exit"] => return Ok(()),
+                ["help" | "h" | "?"] => println!("{}", HELP),
+                ["log" | "l"] => print_log(self.timeline),
+                [cmd @ ("next" | "n" | "back" | "b"), rest @ ..] => match rest {
+                    [] => self.step(cmd.starts_with('n'), 1),
+                    [count] => match count.parse() {
+                        Ok(count) => self.step(cmd.starts_with('n'), count),
+                        Err(_) => println!("{} '{}' is not a count", "error:".red(), count),
+                    },
+                    _ => println!("{} usage: {} [count]", "error:".red(), cmd),
+                },
+                ["goto" | "g", version] => {
+                    if let Some(version) = self.parse_version(version) {
+                        self.version = version;
+                        self.show_position();
+                    }
+                }
+                ["state" | "s"] => self.show_state(self.version),
+                ["state" | "s", version] => {
+                    if let Some(version) = self.parse_version(version) {
+                        self.show_state(version);
+                    }
+                }
+                ["diff" | "d", from] => {
+                    if let Some(from) = self.parse_version(from) {
+                        self.show_diff(from, self.version);
+                    }
+                }
+                ["diff" | "d", from, to] => {
+                    if let (Some(from), Some(to)) = (self.parse_version(from), self.parse_version(to)) {
+                        self.show_diff(from, to);
+                    }
+                }
+                _ => println!("{} unknown command '{}'; type 'help' for commands", "error:".red(), line.trim()),
+            }
+        }
+    }
+
+    fn parse_version(&self, text: &str) -> Option<usize> {
+        match text.parse::<usize>() {
+            Ok(version) if version <= self.timeline.latest() => Some(version),
+            _ => {
+                println!("{} versions run from 0 to {}", "error:".red(), self.timeline.latest());
+                None
+            }
+        }
+    }
+
+    /// Move `count` steps, printing each step crossed (undone when going back)
+    fn step(&mut self, forwards: bool, count: usize) {
+        for _ in 0..count {
+            if forwards && self.version < self.timeline.latest() {
+                self.version += 1;
+                println!("   {} #{} {}", "+".green(), self.version, self.timeline.steps[self.version - 1].record);
+            } else if !forwards && self.version > 0 {
+                println!("   {} #{} {}", "-".red(), self.version, undo(&self.timeline.steps[self.version - 1].record));
+                self.version -= 1;
+            } else {
+                println!("   {}", if forwards { "(at the last version)" } else { "(at version 0)" }.dimmed());
+                break;
+            }
+        }
+    }
+
+    fn show_position(&self) {
+        match self.version.checked_sub(1).map(|i| &self.timeline.steps[i]) {
+            None => println!("   version 0: before the first step"),
+            Some(step) => {
+                let call = step.transaction.map_or("(outside any transaction)", |tx| self.timeline.transactions[tx].as_str());
+                println!("   version {}: after #{} {} in {}", self.version, self.version, step.record, call);
+            }
+        }
+    }
+
+    fn show_state(&self, version: usize) {
+        let state = self.timeline.state_at(version);
+        if state.is_empty() {
+            println!("   {}", "(no contract state)".dimmed());
+        }
+        for (slot, value) in state {
+            println!("   {} = {}", slot, value);
+        }
+    }
+
+    fn show_diff(&self, from: usize, to: usize) {
+        let changes = self.timeline.diff(from, to);
+        let events = self.timeline.events_between(from, to);
+        if changes.is_empty() && events.is_empty() {
+            println!("   {}", format!("no changes between versions {} and {}", from, to).dimmed());
+            return;
+        }
+        for change in changes {
+            println!("   {} {}: {} -> {}", "~".yellow(), change.slot, change.before, change.after);
+        }
+        let direction = if from <= to { "emitted" } else { "undone" };
+        for event in events {
+            println!("   {} {} ({})", "!".cyan(), event.record, direction);
+        }
+    }
+}
+
+/// How a step reads when stepping back over it
+fn undo(record: &Record) -> String {
+    match record {
+        Record::Write { slot, old, new } => format!("{}: {} -> {}", slot, new, old),
+        Record::Event { .. } => format!("{} (undone)", record),
+    }
+}
@@ -0,0 +1,61 @@
+// Spec Command: run the conformance suite on every backend
+
+use colored::*;
+use std::path::Path;
+use std::time::Instant;
+use astrixa::spec::{self, Verdict};
+
+/// Run each program under `dir` on every backend (see `astrixa::spec`),
+/// failing if any backend disagrees with what a program expects. A
+/// backend that cannot run a program is reported, not failed.
+pub fn run_spec(dir: &str) -> Result<(), String> {
+    let start = Instant::now();
+    let cases = spec::load(Path::new(dir))?;
+    if cases.is_empty() {
+        return Err(format!("No .ax programs in {}", dir));
+    }
+
+    println!("{} {} programs in {}", "Checking".green().bold(), cases.len(), dir);
+    println!();
+    let width = cases.iter().map(|case| case.name.len()).max().unwrap_or(0);
+    let (mut failed, mut unsupported) = (0, 0);
+    for case in &cases {
+        let verdicts = spec::check(case);
+        let summary: Vec<String> = verdicts
+            .iter()
+            .map(|(backend, verdict)| match verdict {
+                Verdict::Pass => format!("{} {}", backend.name(), "ok".green()),
+                Verdict::Fail(_) => format!("{} {}", backend.name(), "FAILED".red()),
+                Verdict::Unsupported(_) => format!("{} {}", backend.name(), "unsupported".yellow()),
+            })
+            .collect();
+        println!("   {:width$}  {}", case.name, summary.join("  "), width = width);
+        for (backend, verdict) in &verdicts {
+            match verdict {
+                Verdict::Fail(why) => {
+                    failed += 1;
+                    println!("      {}: {}", backend.name(), why.dimmed());
+                }
+                Verdict::Unsupported(why) => {
+                    unsupported += 1;
+                    println!("      {}: {}", backend.name(), why.dimmed());
+                }
+                Verdict::Pass => {}
+            }
+        }
+    }
+    println!();
+
+    let runs = cases.len() * spec::Backend::ALL.len();
+    if failed > 0 {
+        return Err(format!("{} of {} runs failed", failed, runs));
+    }
+    println!(
+        "   {} {} runs passed, {} unsupported, in {:.2}s",
+        "Conforms:".green().bold(),
+        runs - unsupported,
+        unsupported,
+        start.elapsed().as_secs_f64()
+    );
+    Ok(())
+}
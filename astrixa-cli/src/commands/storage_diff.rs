@@ -0,0 +1,68 @@
+// Storage Diff Command: check that an upgrade keeps contract storage readable
+
+use colored::*;
+use std::fs;
+use std::path::Path;
+use astrixa::storage::{self, Layout};
+use crate::crash;
+use super::build;
+
+pub fn storage_diff(old: &str, new: &str) -> Result<(), String> {
+    let (old, new) = (Path::new(old), Path::new(new));
+
+    println!("{} {} -> {}", "Comparing".green().bold(), old.display(), new.display());
+
+    let before = layouts(old)?;
+    let after = layouts(new)?;
+    if before.is_empty() {
+        return Err(format!("No contract in {} to compare", old.display()));
+    }
+
+    let changes = storage::diff(&before, &after);
+    println!();
+    let mut broken = 0;
+    for layout in &before {
+        let contract_changes: Vec<_> = changes
+            .iter()
+            .filter(|(contract, _)| *contract == layout.contract)
+            .map(|(_, change)| change)
+            .collect();
+        let incompatible = contract_changes.iter().filter(|change| !change.is_compatible()).count();
+        let status = if incompatible == 0 { "ok".green() } else { "INCOMPATIBLE".red() };
+        println!("   {} {} ... {} ({} slots)", "contract".dimmed(), layout.contract, status, layout.slots.len());
+        for change in contract_changes {
+            if change.is_compatible() {
+                println!("      {}", change.to_string().dimmed());
+            } else {
+                println!("      {} {}", "-".red(), change);
+            }
+        }
+        broken += usize::from(incompatible > 0);
+    }
+
+    println!();
+    if broken > 0 {
+        return Err(format!(
+            "{} of {} contracts change their storage layout; upgrading in place would corrupt their state",
+            broken,
+            before.len()
+        ));
+    }
+    println!("{} storage layouts are compatible", "Finished".green().bold());
+    Ok(())
+}
+
+/// The contracts' storage layouts in `input`
+fn layouts(input: &Path) -> Result<Vec<Layout>, String> {
+    let source = fs::read_to_string(input)
+        .map_err(|e| format!("Failed to read {}: {}", input.display(), e))?;
+    crash::set_source(input, &source);
+
+    crash::set_phase("parse");
+    let lexer = astrixa::lexer::Lexer::new(&source);
+    let mut parser = astrixa::parser::Parser::new(lexer);
+    let ast = parser.parse()
+        .map_err(|_| build::syntax_errors(input, parser.errors()))?;
+
+    Ok(storage::layouts(&ast))
+}
@@ -0,0 +1,68 @@
+// Test Command: run every #[test] function in the project
+
+use colored::*;
+use std::fs;
+use crate::config::{Config, find_project_root};
+use crate::crash;
+use super::build;
+
+pub fn test_project(fork: Option<&String>) -> Result<(), String> {
+    let root = find_project_root()?;
+    let config = Config::load(root.join("astrixa.toml"))?;
+    config.diagnostics.apply();
+    let edition = config.edition()?;
+
+    println!("{} {}", "Testing".green().bold(), config.package.name);
+
+    let main_file = root.join("src/main.ax");
+    let source = fs::read_to_string(&main_file)
+        .map_err(|e| format!("Failed to read source file: {}", e))?;
+    crash::set_source(&main_file, &source);
+
+    // Parse
+    crash::set_phase("parse");
+    let lexer = astrixa::lexer::Lexer::new(&source);
+    let mut parser = astrixa::parser::Parser::new(lexer);
+    parser.set_edition(edition);
+    let ast = parser.parse()
+        .map_err(|_| build::syntax_errors(&main_file, parser.errors()))?;
+    build::print_warnings(&main_file, parser.warnings());
+
+    // Type check
+    crash::set_phase("typecheck");
+    let mut checker = astrixa::typechecker::TypeChecker::new();
+    checker.check(&ast)
+        .map_err(|errors| build::type_errors(&main_file, &errors))?;
+
+    // Run the tests
+    crash::set_phase("test");
+    let mut interpreter = astrixa::interpreter::Interpreter::new();
+    if let Some(fork) = fork {
+        let spec = astrixa::fork::ForkSpec::parse(fork)?;
+        println!("{} {} at block {}", "Forking".green().bold(), spec.url, spec.block);
+        interpreter.set_fork(Box::new(astrixa::fork::RpcStateSource { spec }))?;
+    }
+    let results = interpreter.run_tests(ast)?;
+
+    println!();
+    let mut failed = 0;
+    for (name, result) in &results {
+        match result {
+            Ok(()) => println!("   test {} ... {}", name, "ok".green()),
+            Err(e) => {
+                failed += 1;
+                println!("   test {} ... {}", name, "FAILED".red());
+                println!("      {}", e.dimmed());
+            }
+        }
+    }
+
+    println!();
+    let summary = format!("{} passed, {} failed", results.len() - failed, failed);
+    if failed > 0 {
+        return Err(format!("Tests failed: {}", summary));
+    }
+    println!("{} {}", "✅".green(), summary.green().bold());
+
+    Ok(())
+}
@@ -0,0 +1,309 @@
+// Toolchain Command: compiler versions side by side
+//
+// Each installed version is a copy of its `astrixa` binary under
+// ~/.astrixa/toolchains/<version>/, or $ASTRIXA_HOME/toolchains/ when that
+// is set. `astrixa toolchain use` picks the one that runs by default, and a
+// project pins its own with `compiler = "0.2"` under [package] in
+// astrixa.toml. A pin matches every version it is a prefix of, component
+// by component, so "0.2" takes 0.2.0 and 0.2.5 but not 0.20.0.
+//
+// Before any other command runs, `switch` hands the whole command line to
+// the newest installed toolchain the pin, or else the default, asks for,
+// unless that is this compiler. Without a match, a pinned project gets a
+// warning and this compiler carries on.
+
+use colored::*;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, ExitStatus};
+use crate::config::{Config, find_project_root};
+
+/// This compiler's version
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Set for a toolchain run by `switch`, so it does not switch again
+const SWITCHED: &str = "ASTRIXA_TOOLCHAIN";
+
+/// Install `version` from the `astrixa` binary at `from`; this compiler
+/// installs itself without one
+pub fn install(version: &str, from: Option<&String>) -> Result<(), String> {
+    if parse(version).is_none() {
+        return Err(format!("'{}' is not a version such as 0.2.1", version));
+    }
+    let source = match from {
+        Some(path) => PathBuf::from(path),
+        None if version == VERSION => std::env::current_exe()
+            .map_err(|e| format!("Failed to find this compiler's binary: {}", e))?,
+        None => return Err(format!("This is astrixa {}; install {} from its binary with --from <FILE>", VERSION, version)),
+    };
+
+    // A binary of another version would be found under the wrong name
+    let output = Command::new(&source)
+        .arg("--version")
+        .output()
+        .map_err(|e| format!("Failed to run {}: {}", source.display(), e))?;
+    let reported = String::from_utf8_lossy(&output.stdout);
+    if reported.split_whitespace().last() != Some(version) {
+        return Err(format!("{} reports '{}', not astrixa {}", source.display(), reported.trim(), version));
+    }
+
+    let target = binary(&toolchains()?, version);
+    let dir = target.parent().unwrap_or(&target);
+    fs::create_dir_all(dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    fs::copy(&source, &target)
+        .map_err(|e| format!("Failed to install {}: {}", target.display(), e))?;
+    println!("{} astrixa {} to {}", "Installed".green().bold(), version, target.display());
+    Ok(())
+}
+
+/// Every installed version, marking the default and this compiler
+pub fn list() -> Result<(), String> {
+    let versions = installed()?;
+    if versions.is_empty() {
+        println!("No toolchains installed; this is astrixa {}", VERSION);
+        println!("Install one with `astrixa toolchain install <VERSION>`");
+        return Ok(());
+    }
+    let default = default_version();
+    for version in versions {
+        let mut notes = Vec::new();
+        if default.as_deref() == Some(version.as_str()) {
+            notes.push("default");
+        }
+        if version == VERSION {
+            notes.push("this compiler");
+        }
+        match notes.is_empty() {
+            true => println!("  {}", version),
+            false => println!("  {} {}", version, format!("({})", notes.join(", ")).dimmed()),
+        }
+    }
+    Ok(())
+}
+
+/// Make the newest installed version `requirement` matches the default
+pub fn use_version(requirement: &str) -> Result<(), String> {
+    let version = newest(&installed()?, requirement).ok_or_else(|| {
+        format!("No installed toolchain matches {}; install one with `astrixa toolchain install`", requirement)
+    })?;
+    let path = home()?.join("default-toolchain");
+    fs::write(&path, format!("{}\n", version))
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    println!("{} astrixa {} by default", "Using".green().bold(), version);
+    Ok(())
+}
+
+/// Whether a command line runs on the pinned or default toolchain: every
+/// command but the one that manages them
+pub fn switches(args: impl IntoIterator<Item = String>) -> bool {
+    args.into_iter().find(|arg| !arg.starts_with('-')).as_deref() != Some("toolchain")
+}
+
+/// Run this command line on the toolchain the project pins, or the default
+/// one, and exit as it does. Returns when this compiler is to run it.
+pub fn switch() {
+    if std::env::var_os(SWITCHED).is_some() {
+        return;
+    }
+    let pinned = find_project_root()
+        .ok()
+        .and_then(|root| Config::load(root.join("astrixa.toml")).ok())
+        .and_then(|config| config.package.compiler);
+    let warn = |message: String| eprintln!("{} {}", "Warning:".yellow().bold(), message);
+    let installed = installed().unwrap_or_default();
+    let version = match choose(pinned.as_deref(), default_version().as_deref(), &installed) {
+        Ok(Some(version)) => version,
+        Ok(None) => return,
+        Err(warning) => return warn(warning),
+    };
+    let Ok(dir) = toolchains() else { return };
+    match run(&binary(&dir, &version), &version, std::env::args_os().skip(1)) {
+        Ok(status) => process::exit(status.code().unwrap_or(1)),
+        Err(e) => warn(format!("Failed to run toolchain {}: {}; running {}", version, e, VERSION)),
+    }
+}
+
+/// The installed version to run instead of this compiler, given the
+/// project's pin and the default: none when neither asks for another, and
+/// a warning when nothing installed matches
+fn choose(pinned: Option<&str>, default: Option<&str>, installed: &[String]) -> Result<Option<String>, String> {
+    let Some(requirement) = pinned.or(default) else {
+        return Ok(None);
+    };
+    if matches(requirement, VERSION) {
+        return Ok(None);
+    }
+    if parse(requirement).is_none() {
+        return Err(format!("compiler = \"{}\" in astrixa.toml is not a version such as 0.2", requirement));
+    }
+    match newest(installed, requirement) {
+        Some(version) => Ok(Some(version)),
+        None if pinned.is_some() => Err(format!(
+            "astrixa.toml asks for compiler {}, but this is {} and no installed toolchain matches; install one with `astrixa toolchain install`",
+            requirement, VERSION
+        )),
+        None => Err(format!("The default toolchain {} is no longer installed; running {}", requirement, VERSION)),
+    }
+}
+
+/// Run `args` on the toolchain binary of `version`, marked so that it does
+/// not switch again
+fn run(binary: &Path, version: &str, args: impl IntoIterator<Item = OsString>) -> io::Result<ExitStatus> {
+    Command::new(binary).args(args).env(SWITCHED, version).status()
+}
+
+/// Whether `version` is one `requirement` asks for
+fn matches(requirement: &str, version: &str) -> bool {
+    let wanted: Vec<&str> = requirement.trim().split('.').collect();
+    let have: Vec<&str> = version.split('.').collect();
+    wanted.len() <= have.len() && wanted.iter().zip(&have).all(|(w, h)| w == h)
+}
+
+/// A version's numeric components, for ordering
+fn parse(version: &str) -> Option<Vec<u64>> {
+    version.trim().split('.').map(|part| part.parse().ok()).collect()
+}
+
+fn home() -> Result<PathBuf, String> {
+    if let Some(home) = std::env::var_os("ASTRIXA_HOME") {
+        return Ok(PathBuf::from(home));
+    }
+    std::env::var_os("HOME")
+        .map(|home| PathBuf::from(home).join(".astrixa"))
+        .ok_or_else(|| "Neither ASTRIXA_HOME nor HOME is set".to_string())
+}
+
+fn toolchains() -> Result<PathBuf, String> {
+    Ok(home()?.join("toolchains"))
+}
+
+fn binary(toolchains: &Path, version: &str) -> PathBuf {
+    toolchains.join(version).join(format!("astrixa{}", std::env::consts::EXE_SUFFIX))
+}
+
+/// Installed versions, oldest first
+fn installed() -> Result<Vec<String>, String> {
+    Ok(installed_in(&toolchains()?))
+}
+
+fn installed_in(dir: &Path) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut versions: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .filter(|version| parse(version).is_some() && binary(dir, version).exists())
+        .collect();
+    versions.sort_by_key(|version| parse(version));
+    versions
+}
+
+/// The newest of `installed`, oldest first, that `requirement` matches
+fn newest(installed: &[String], requirement: &str) -> Option<String> {
+    installed.iter().rev().find(|version| matches(requirement, version)).cloned()
+}
+
+fn default_version() -> Option<String> {
+    let text = fs::read_to_string(home().ok()?.join("default-toolchain")).ok()?;
+    Some(text.trim().to_string()).filter(|version| !version.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn versions(list: &[&str]) -> Vec<String> {
+        list.iter().map(|version| version.to_string()).collect()
+    }
+
+    #[test]
+    fn test_versions_parse_into_numeric_components() {
+        assert_eq!(parse("0.2.10"), Some(vec![0, 2, 10]));
+        assert_eq!(parse(" 1.0 "), Some(vec![1, 0]));
+        assert_eq!(parse("0.2.x"), None);
+        assert_eq!(parse("latest"), None);
+    }
+
+    #[test]
+    fn test_a_pin_matches_by_whole_components() {
+        assert!(matches("0.2", "0.2.0") && matches("0.2", "0.2.5"));
+        assert!(!matches("0.2", "0.20.0") && !matches("0.2", "0.3.0"));
+        assert!(matches("0.2.5", "0.2.5") && !matches("0.2.5", "0.2"));
+        assert!(matches(" 0.2 ", "0.2.1"));
+    }
+
+    #[test]
+    fn test_newest_orders_numerically() {
+        let installed = versions(&["0.2.0", "0.2.5", "0.2.10", "0.20.0"]);
+        assert_eq!(newest(&installed, "0.2").as_deref(), Some("0.2.10"));
+        assert_eq!(newest(&installed, "0.20").as_deref(), Some("0.20.0"));
+        assert_eq!(newest(&installed, "0.3"), None);
+    }
+
+    #[test]
+    fn test_installed_toolchains_need_their_binary() {
+        let dir = std::env::temp_dir().join(format!("astrixa-toolchains-test-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        for version in ["0.2.10", "0.2.5", "nightly"] {
+            let path = binary(&dir, version);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+        fs::create_dir_all(dir.join("0.3.0")).unwrap(); // An interrupted install
+
+        assert_eq!(installed_in(&dir), versions(&["0.2.5", "0.2.10"]));
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(installed_in(&dir).is_empty());
+    }
+
+    #[test]
+    fn test_the_pin_wins_over_the_default() {
+        let installed = versions(&["0.2.5", "0.3.1"]);
+        assert_eq!(choose(Some("0.2"), Some("0.3"), &installed), Ok(Some("0.2.5".to_string())));
+        assert_eq!(choose(None, Some("0.3"), &installed), Ok(Some("0.3.1".to_string())));
+
+        // This compiler runs what asks for it, or when nothing asks
+        assert_eq!(choose(Some(VERSION), Some("0.3"), &installed), Ok(None));
+        assert_eq!(choose(None, None, &installed), Ok(None));
+
+        // Without a match it runs too, with a warning
+        let missing = choose(Some("0.9"), None, &installed).unwrap_err();
+        assert!(missing.contains("asks for compiler 0.9"), "{}", missing);
+        let uninstalled = choose(None, Some("0.9"), &installed).unwrap_err();
+        assert!(uninstalled.contains("default toolchain 0.9 is no longer installed"), "{}", uninstalled);
+        assert!(choose(Some("two"), None, &installed).unwrap_err().contains("is not a version"));
+    }
+
+    #[test]
+    fn test_every_command_but_toolchain_switches() {
+        let args = |line: &str| line.split_whitespace().map(String::from).collect::<Vec<_>>();
+        assert!(switches(args("build --release")));
+        assert!(switches(args("--crash-include-source run main.ax")));
+        assert!(switches(args("")));
+        assert!(!switches(args("toolchain use 0.2")));
+        assert!(!switches(args("--crash-include-source toolchain list")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_switching_reruns_the_command_line_on_the_toolchain() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("astrixa-switch-test-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let path = binary(&dir, "0.2.5");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let log = dir.join("ran");
+        fs::write(&path, format!("#!/bin/sh\necho \"$ASTRIXA_TOOLCHAIN $*\" > '{}'\nexit 3\n", log.display())).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let status = run(&path, "0.2.5", ["build", "--release"].map(OsString::from)).unwrap();
+        assert_eq!(status.code(), Some(3));
+        assert_eq!(fs::read_to_string(&log).unwrap(), "0.2.5 build --release\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,69 @@
+// Translate Command: bytecode artifacts (.axb) to WASM, without the source
+
+use colored::*;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use astrixa::bytecode::BytecodeModule;
+use crate::crash;
+use crate::plugins;
+
+/// Translate `input` to WASM, named and with the artifact's spans when it
+/// carries them, unless `strip`
+pub fn translate_file(input: &str, output: Option<&String>, release: bool, strip: bool, plugins: &[String]) -> Result<(), String> {
+    let start = Instant::now();
+    let input = Path::new(input);
+    let output = match output {
+        Some(out) => PathBuf::from(out),
+        None => input.with_extension("wasm"),
+    };
+
+    println!("{} {}", "Translating".green().bold(), input.display());
+    let session = plugins::load_session(plugins)?;
+
+    let bytes = fs::read(input)
+        .map_err(|e| format!("Failed to read {}: {}", input.display(), e))?;
+    crash::set_phase("translate");
+    let mut module = BytecodeModule::from_bytes(&bytes)
+        .map_err(|e| format!("{}: {}", input.display(), e))?;
+    if strip {
+        module.strip();
+    }
+    let debug = module.functions.iter().any(|f| f.span.is_some());
+    let mut ir = astrixa::translate::translate(&module)?;
+
+    if release {
+        crash::set_phase("optimize");
+        ir = astrixa::opt::optimize_module(&ir);
+    }
+
+    crash::set_phase("plugin passes");
+    session.run_ir_passes(&mut ir);
+
+    crash::set_phase("codegen");
+    let wasm = astrixa::codegen::wasm::generate_wasm_module(&ir);
+    // A .wat output gets the text; anything else the binary module
+    let text = output.extension().is_some_and(|ext| ext == "wat");
+    let bytes = if text {
+        wasm.into_bytes()
+    } else if debug {
+        let spans: HashMap<String, _> = ir.functions.iter().map(|f| (f.name.replace('.', "_"), f.span)).collect();
+        astrixa::codegen::binary::assemble_debug(&wasm, None, &spans)
+            .map_err(|e| format!("Failed to assemble WASM: {}", e))?
+            .bytes
+    } else {
+        astrixa::codegen::binary::assemble(&wasm)
+            .map_err(|e| format!("Failed to assemble WASM: {}", e))?
+    };
+    if !text {
+        super::build::validate(&bytes)?;
+    }
+    fs::write(&output, bytes)
+        .map_err(|e| format!("Failed to write output file: {}", e))?;
+
+    println!("   {} {} ({} functions)", "Compiled".green(), output.display(), ir.functions.len());
+    println!();
+    println!("{} in {:.2}s", "Finished".green().bold(), start.elapsed().as_secs_f64());
+    Ok(())
+}
@@ -0,0 +1,92 @@
+// Verify Command: check #[ensures] conditions by bounded symbolic execution
+
+use colored::*;
+use std::fs;
+use std::path::Path;
+use std::time::Instant;
+use astrixa::ast::Span;
+use astrixa::text::byte_of_column;
+use crate::crash;
+use super::build;
+
+pub fn verify_file(input: &str, depth: usize) -> Result<(), String> {
+    let start = Instant::now();
+    let input = Path::new(input);
+
+    println!("{} {} (up to {} branches per path)", "Verifying".green().bold(), input.display(), depth);
+
+    let source = fs::read_to_string(input)
+        .map_err(|e| format!("Failed to read {}: {}", input.display(), e))?;
+    crash::set_source(input, &source);
+
+    // Parse
+    crash::set_phase("parse");
+    let lexer = astrixa::lexer::Lexer::new(&source);
+    let mut parser = astrixa::parser::Parser::new(lexer);
+    let ast = parser.parse()
+        .map_err(|_| build::syntax_errors(input, parser.errors()))?;
+    build::print_warnings(input, parser.warnings());
+
+    // Type check
+    crash::set_phase("typecheck");
+    let mut checker = astrixa::typechecker::TypeChecker::new();
+    checker.check(&ast)
+        .map_err(|errors| build::type_errors(input, &errors))?;
+
+    crash::set_phase("verify");
+    let reports = astrixa::verify::verify(&ast, depth);
+    if reports.is_empty() {
+        return Err(format!("No contract method in {} declares an #[ensures] condition", input.display()));
+    }
+
+    println!();
+    let mut failed = 0;
+    for report in &reports {
+        let unchecked = match report.unchecked {
+            0 => String::new(),
+            n => format!(", {} unchecked", n),
+        };
+        let Some(counterexample) = &report.counterexample else {
+            println!(
+                "   {} {} ... {} ({} ensures, {} paths{})",
+                "fn".dimmed(),
+                report.function,
+                "ok".green(),
+                report.ensures,
+                report.paths,
+                unchecked.yellow()
+            );
+            continue;
+        };
+
+        failed += 1;
+        println!("   {} {} ... {}", "fn".dimmed(), report.function, "FAILED".red());
+        println!(
+            "      #[ensures({})] at line {} does not hold for:",
+            excerpt(&source, counterexample.condition).bold(),
+            counterexample.condition.start_line
+        );
+        for (name, value) in &counterexample.inputs {
+            println!("        {} = {}", name, value);
+        }
+        if let Some(result) = counterexample.result {
+            println!("        {}", format!("(returns {})", result).dimmed());
+        }
+    }
+
+    println!();
+    if failed > 0 {
+        return Err(format!("{} of {} methods break an #[ensures] condition", failed, reports.len()));
+    }
+    println!("{} in {:.2}s", "Finished".green().bold(), start.elapsed().as_secs_f64());
+    Ok(())
+}
+
+/// The source text `span` covers, when it sits on one line
+fn excerpt(source: &str, span: Span) -> String {
+    let line = source.lines().nth(span.start_line.saturating_sub(1)).unwrap_or("");
+    let start = byte_of_column(line, span.start_column);
+    let end = if span.end_line == span.start_line { byte_of_column(line, span.end_column) } else { line.len() };
+    line.get(start..end)
+        .map_or_else(|| line.trim().to_string(), |text| text.trim().to_string())
+}
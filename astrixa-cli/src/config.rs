@@ -1,5 +1,9 @@
 // STEP 50: Project Configuration (astrixa.toml)
 
+use astrixa::codegen::wasm::HostImports;
+use astrixa::edition::Edition;
+use astrixa::opt::Level;
+use astrixa::profile::Profile as BuildProfile;
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::Path;
@@ -11,6 +15,14 @@ pub struct Config {
     pub dependencies: std::collections::HashMap<String, String>,
     #[serde(default)]
     pub dev_dependencies: std::collections::HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "Diagnostics::is_default")]
+    pub diagnostics: Diagnostics,
+    #[serde(default, skip_serializing_if = "Profiles::is_default")]
+    pub profile: Profiles,
+    #[serde(default, skip_serializing_if = "Memory::is_default")]
+    pub memory: Memory,
+    #[serde(default, skip_serializing_if = "Host::is_default")]
+    pub host: Host,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -23,6 +35,110 @@ pub struct Package {
     pub authors: Vec<String>,
     #[serde(default)]
     pub license: Option<String>,
+    /// Language edition; projects without one stay on the oldest
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edition: Option<String>,
+    /// Compiler version the project builds with, as "0.2"; see `astrixa toolchain`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compiler: Option<String>,
+}
+
+/// `[diagnostics]` table: how compiler messages are presented
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct Diagnostics {
+    /// Message language, e.g. "es"; the ASTRIXA_LOCALE variable takes precedence
+    #[serde(default)]
+    pub locale: Option<String>,
+}
+
+impl Diagnostics {
+    fn is_default(&self) -> bool {
+        self.locale.is_none()
+    }
+
+    /// Point the compiler's message catalog at the configured locale
+    pub fn apply(&self) {
+        if std::env::var_os("ASTRIXA_LOCALE").is_some() {
+            return;
+        }
+        if let Some(locale) = &self.locale {
+            astrixa::messages::set_locale(locale);
+        }
+    }
+}
+
+/// `[profile.debug]` and `[profile.release]` tables: how each build mode
+/// compiles
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct Profiles {
+    #[serde(default, skip_serializing_if = "Profile::is_default")]
+    pub debug: Profile,
+    #[serde(default, skip_serializing_if = "Profile::is_default")]
+    pub release: Profile,
+}
+
+impl Profiles {
+    fn is_default(&self) -> bool {
+        self.debug.is_default() && self.release.is_default()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct Profile {
+    /// Optimization level, as after `-O`: 0, 1, 2 or "s"
+    #[serde(default, rename = "opt-level", skip_serializing_if = "Option::is_none")]
+    pub opt_level: Option<toml::Value>,
+    /// Whether Int overflow traps
+    #[serde(default, rename = "overflow-checks", skip_serializing_if = "Option::is_none")]
+    pub overflow_checks: Option<bool>,
+    /// Whether `#[requires]` and `#[ensures]` are checked at run time
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub assertions: Option<bool>,
+    /// Whether the module keeps function names and gets a source map
+    #[serde(default, rename = "debug-info", skip_serializing_if = "Option::is_none")]
+    pub debug_info: Option<bool>,
+}
+
+impl Profile {
+    fn is_default(&self) -> bool {
+        self.opt_level.is_none() && self.overflow_checks.is_none() && self.assertions.is_none() && self.debug_info.is_none()
+    }
+}
+
+/// `[memory]` table: the module's linear memory, in 64 KiB pages
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct Memory {
+    /// Pages the module starts with, which its static data has to fit
+    #[serde(default, rename = "initial-pages", skip_serializing_if = "Option::is_none")]
+    pub initial_pages: Option<u32>,
+    /// Pages the string heap may grow it to; as many as the runtime allows without
+    #[serde(default, rename = "max-pages", skip_serializing_if = "Option::is_none")]
+    pub max_pages: Option<u32>,
+}
+
+impl Memory {
+    fn is_default(&self) -> bool {
+        self.initial_pages.is_none() && self.max_pages.is_none()
+    }
+}
+
+/// `[host]` table: where a build imports its host functions from, for
+/// runtimes that name them their own way
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct Host {
+    /// The module every host function comes from; `env` without
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub module: Option<String>,
+    /// The runtime's name for each standard library call or host property
+    /// it renames, as in `print = "log_line"`
+    #[serde(default, skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+    pub names: std::collections::BTreeMap<String, String>,
+}
+
+impl Host {
+    pub fn is_default(&self) -> bool {
+        self.module.is_none() && self.names.is_empty()
+    }
 }
 
 impl Config {
@@ -50,10 +166,94 @@ impl Config {
                 description: None,
                 authors: vec![],
                 license: Some("MIT".to_string()),
+                edition: Some(Edition::LATEST.to_string()),
+                compiler: None,
             },
             dependencies: std::collections::HashMap::new(),
             dev_dependencies: std::collections::HashMap::new(),
+            diagnostics: Diagnostics::default(),
+            profile: Profiles::default(),
+            memory: Memory::default(),
+            host: Host::default(),
+        }
+    }
+    
+    /// The language edition the project is written in
+    pub fn edition(&self) -> Result<Edition, String> {
+        match &self.package.edition {
+            None => Ok(Edition::default()),
+            Some(text) => Edition::parse(text).ok_or_else(|| {
+                let supported: Vec<&str> = Edition::ALL.iter().map(|e| e.as_str()).collect();
+                format!(
+                    "Unknown edition '{}' in astrixa.toml (supported: {})",
+                    text,
+                    supported.join(", ")
+                )
+            }),
+        }
+    }
+    
+    /// The profile a build uses: the mode's defaults, then its table in
+    /// astrixa.toml, then `flag` (`-O`) when given
+    pub fn profile(&self, release: bool, flag: Option<&String>) -> Result<BuildProfile, String> {
+        let mut profile = BuildProfile::for_mode(release);
+        let (mode, table) = if release { ("release", &self.profile.release) } else { ("debug", &self.profile.debug) };
+        if let Some(value) = &table.opt_level {
+            let text = match value {
+                toml::Value::String(text) => text.clone(),
+                other => other.to_string(),
+            };
+            profile.opt_level = Level::parse(&text).ok_or_else(|| {
+                format!(
+                    "Unknown opt-level {} in [profile.{}] of astrixa.toml (supported: {})",
+                    text,
+                    mode,
+                    Level::NAMES.join(", ")
+                )
+            })?;
+        }
+        profile.overflow_checks = table.overflow_checks.unwrap_or(profile.overflow_checks);
+        profile.assertions = table.assertions.unwrap_or(profile.assertions);
+        profile.debug_info = table.debug_info.unwrap_or(profile.debug_info);
+        if let Some(flag) = flag {
+            profile.opt_level = Level::parse(flag).ok_or_else(|| format!("Unknown optimization level '-O{}'", flag))?;
+        }
+        Ok(profile)
+    }
+    
+    /// The memory a build's module has, from the [memory] table. WASM
+    /// addresses 65536 pages (4 GiB) at most.
+    pub fn memory(&self) -> Result<astrixa::ir::Memory, String> {
+        let mut memory = astrixa::ir::Memory::default();
+        memory.initial = self.memory.initial_pages.unwrap_or(memory.initial);
+        memory.maximum = self.memory.max_pages;
+        if memory.initial == 0 || memory.initial > 65536 {
+            return Err(format!("initial-pages = {} in [memory] of astrixa.toml is not from 1 to 65536", memory.initial));
+        }
+        match memory.maximum {
+            Some(maximum) if maximum < memory.initial || maximum > 65536 => Err(format!(
+                "max-pages = {} in [memory] of astrixa.toml is not from initial-pages ({}) to 65536",
+                maximum, memory.initial
+            )),
+            _ => Ok(memory),
+        }
+    }
+    
+    /// Where a build's host functions are imported from, from the [host]
+    /// table. Only names the module could import may be renamed.
+    pub fn host(&self) -> Result<HostImports, String> {
+        let mut host = HostImports::new(self.host.module.as_deref().unwrap_or("env"));
+        if host.module.is_empty() {
+            return Err("module in [host] of astrixa.toml is empty".to_string());
+        }
+        for (call, name) in &self.host.names {
+            let property = call.split_once('.').and_then(|(object, name)| astrixa::stdlib::get_property_info(object, name));
+            if astrixa::prelude::get(call).is_none() && astrixa::stdlib::get_stdlib_info(call).is_none() && property.is_none() {
+                return Err(format!("{} in [host.names] of astrixa.toml is not a standard library function or host property", call));
+            }
+            host.rename(call, name);
         }
+        Ok(host)
     }
     
     pub fn add_dependency(&mut self, name: String, version: String) {
@@ -0,0 +1,338 @@
+// Crash reporting: compiler panics are caught at the CLI boundary and written
+// to a local report. Nothing is ever uploaded; the user decides what to share.
+
+use colored::*;
+use std::fs;
+use std::io::{ErrorKind, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use astrixa::opt::Level;
+use astrixa::profile::Profile;
+use crate::commands::build::Target;
+
+const ISSUE_URL: &str = "https://github.com/Podamekalajagadeesh/astrixa-lang/issues/new";
+
+/// Upper bound on compiler re-runs while minimizing a repro
+const MAX_MINIMIZE_ATTEMPTS: usize = 500;
+
+static PHASE: Mutex<&str> = Mutex::new("startup");
+static SOURCE: Mutex<Option<(PathBuf, String)>> = Mutex::new(None);
+static LAST_PANIC: Mutex<Option<PanicDetails>> = Mutex::new(None);
+static BUILD: Mutex<Option<Build>> = Mutex::new(None);
+
+#[derive(Clone)]
+struct PanicDetails {
+    message: String,
+    location: String,
+}
+
+/// How the crashing build compiled, so a repro compiles the same way
+#[derive(Debug, Clone, Copy)]
+struct Build {
+    target: Target,
+    profile: Profile, // With --checked-arith and --strip applied
+    wat: bool,        // WAT text, never assembled
+}
+
+/// Record the compiler phase about to run
+pub fn set_phase(phase: &'static str) {
+    if let Ok(mut current) = PHASE.lock() {
+        *current = phase;
+    }
+}
+
+/// Record the source being compiled, for the report's repro snippet
+pub fn set_source(path: &std::path::Path, text: &str) {
+    if let Ok(mut source) = SOURCE.lock() {
+        *source = Some((path.to_path_buf(), text.to_string()));
+    }
+}
+
+/// Record how `astrixa build` is compiling, for minimizing a crash
+pub fn set_build(target: Target, profile: Profile, wat: bool) {
+    if let Ok(mut build) = BUILD.lock() {
+        *build = Some(Build { target, profile, wat });
+    }
+}
+
+/// Run a CLI command, turning a panic into a crash report.
+///
+/// The minimized source is only written when `include_source` is set
+/// (the user's explicit consent via `--crash-include-source`).
+pub fn guard<F>(include_source: bool, command: F) -> Result<(), String>
+where
+    F: FnOnce() -> Result<(), String>,
+{
+    // Keep the default "thread panicked" noise out of the user's terminal;
+    // the details go into the report instead
+    panic::set_hook(Box::new(|info| {
+        let message = if let Some(s) = info.payload().downcast_ref::<&str>() {
+            s.to_string()
+        } else if let Some(s) = info.payload().downcast_ref::<String>() {
+            s.clone()
+        } else {
+            "unknown panic payload".to_string()
+        };
+        let location = info
+            .location()
+            .map(|l| format!("{}:{}:{}", l.file(), l.line(), l.column()))
+            .unwrap_or_else(|| "unknown".to_string());
+        if let Ok(mut last) = LAST_PANIC.lock() {
+            *last = Some(PanicDetails { message, location });
+        }
+    }));
+
+    match panic::catch_unwind(AssertUnwindSafe(command)) {
+        Ok(result) => result,
+        Err(_) => {
+            let report = build_report(include_source);
+            let path = write_report(&std::env::temp_dir().join("astrixa-crash-reports"), &report)?;
+            let phase = *PHASE.lock().unwrap_or_else(|e| e.into_inner());
+
+            eprintln!();
+            eprintln!("{} the ASTRIXA compiler crashed during {}", "internal error:".red().bold(), phase.yellow());
+            eprintln!("   A crash report was written to {}", path.display().to_string().cyan());
+            eprintln!("   Please file an issue at {} and attach the report.", ISSUE_URL);
+            if !include_source {
+                eprintln!("   Re-run with {} to add a minimized snippet of your source.", "--crash-include-source".cyan());
+            }
+            eprintln!("   {}", "Nothing has been uploaded. Review the report before sharing it.".dimmed());
+
+            std::process::exit(101);
+        }
+    }
+}
+
+fn build_report(include_source: bool) -> String {
+    let phase = *PHASE.lock().unwrap_or_else(|e| e.into_inner());
+    let details = LAST_PANIC.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let source = SOURCE.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let build = *BUILD.lock().unwrap_or_else(|e| e.into_inner());
+
+    let mut report = String::new();
+    report.push_str("ASTRIXA crash report\n");
+    report.push_str("====================\n\n");
+    report.push_str(&format!("cli version:      {}\n", env!("CARGO_PKG_VERSION")));
+    report.push_str(&format!("compiler version: {}\n", astrixa::VERSION));
+    report.push_str(&format!("platform:         {}-{}\n", std::env::consts::OS, std::env::consts::ARCH));
+    report.push_str(&format!("command:          {}\n", std::env::args().skip(1).collect::<Vec<_>>().join(" ")));
+    report.push_str(&format!("phase:            {}\n", phase));
+    if let Some(build) = &build {
+        report.push_str(&format!("target:           {:?}\n", build.target));
+        report.push_str(&format!("profile:          {:?}\n", build.profile));
+    }
+
+    match &details {
+        Some(details) => {
+            report.push_str(&format!("panic:            {}\n", details.message));
+            report.push_str(&format!("location:         {}\n", details.location));
+        }
+        None => report.push_str("panic:            (no details captured)\n"),
+    }
+
+    match source {
+        Some((path, text)) => {
+            report.push_str(&format!("input:            {}\n\n", path.display()));
+            if include_source {
+                let message = details.map(|d| d.message);
+                let snippet = minimize(&text, |candidate| still_crashes(candidate, message.as_deref(), build));
+                report.push_str(&format!(
+                    "--- minimized source ({} of {} lines) ---\n",
+                    snippet.lines().count(),
+                    text.lines().count()
+                ));
+                report.push_str(&snippet);
+                report.push_str("\n--- end of source ---\n");
+            } else {
+                report.push_str("source not included (re-run with --crash-include-source to attach a minimized snippet)\n");
+            }
+        }
+        None => report.push_str("input:            (none)\n"),
+    }
+
+    report
+}
+
+/// Write the report to a file of its own in `dir`: named for the time and
+/// the process, and numbered past any other crash's in the same second
+fn write_report(dir: &Path, report: &str) -> Result<PathBuf, String> {
+    fs::create_dir_all(dir)
+        .map_err(|e| format!("Failed to create crash report directory: {}", e))?;
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let name = format!("crash-{}-{}", timestamp, std::process::id());
+    for n in 0.. {
+        let path = match n {
+            0 => dir.join(format!("{}.txt", name)),
+            _ => dir.join(format!("{}-{}.txt", name, n)),
+        };
+        match fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => {
+                return file.write_all(report.as_bytes())
+                    .map(|_| path)
+                    .map_err(|e| format!("Failed to write crash report: {}", e));
+            }
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(format!("Failed to write crash report: {}", e)),
+        }
+    }
+    unreachable!("some report name is free")
+}
+
+/// Shrink the source to fewer lines for which `crashes` still holds: the
+/// compiler still crashing the same way (line-based delta debugging)
+fn minimize(source: &str, mut crashes: impl FnMut(&str) -> bool) -> String {
+    let mut lines: Vec<&str> = source.lines().collect();
+    let mut attempts = 0;
+
+    let mut chunk = (lines.len() / 2).max(1);
+    while attempts < MAX_MINIMIZE_ATTEMPTS {
+        let mut i = 0;
+        while i < lines.len() && attempts < MAX_MINIMIZE_ATTEMPTS {
+            let end = (i + chunk).min(lines.len());
+            let candidate: Vec<&str> = lines[..i].iter().chain(&lines[end..]).copied().collect();
+            attempts += 1;
+
+            if !candidate.is_empty() && crashes(&candidate.join("\n")) {
+                lines = candidate;
+            } else {
+                i += chunk;
+            }
+        }
+
+        if chunk == 1 {
+            break;
+        }
+        chunk /= 2;
+    }
+
+    lines.join("\n")
+}
+
+fn still_crashes(source: &str, message: Option<&str>, build: Option<Build>) -> bool {
+    if let Ok(mut last) = LAST_PANIC.lock() {
+        *last = None;
+    }
+
+    let crashed = panic::catch_unwind(|| compile_all_phases(source, build)).is_err();
+
+    // A different panic is a different bug; don't shrink towards it
+    crashed && match message {
+        Some(expected) => LAST_PANIC
+            .lock()
+            .map(|last| last.as_ref().is_some_and(|d| d.message == expected))
+            .unwrap_or(false),
+        None => true,
+    }
+}
+
+/// Every front- and back-end phase, for reproducing a crash: those of the
+/// crashing build, with its target and profile, or else a default build's
+fn compile_all_phases(source: &str, build: Option<Build>) {
+    let lexer = astrixa::lexer::Lexer::new(source);
+    let mut parser = astrixa::parser::Parser::new(lexer);
+    let Ok(ast) = parser.parse() else { return };
+
+    let mut checker = astrixa::typechecker::TypeChecker::new();
+    if checker.check(&ast).is_err() {
+        return;
+    }
+
+    let Some(Build { target, profile, wat }) = build else {
+        let ir = astrixa::lowering::lower(&ast);
+        let ir = astrixa::opt::optimize_module(&ir);
+        let _ = astrixa::codegen::wasm::generate_wasm_module(&ir);
+        return;
+    };
+    let Ok(mut ir) = astrixa::lowering::lower_for_build(&ast, profile.assertions, profile.debug_info && !wat) else { return };
+    if profile.overflow_checks {
+        astrixa::overflow::check_arithmetic(&mut ir);
+    }
+    if profile.opt_level != Level::O0 {
+        ir = astrixa::opt::optimize_module_with(&ir, profile.opt_level.passes());
+    }
+    let text = match target {
+        Target::Native => {
+            let _ = astrixa::translate::to_bytecode_module(&ir);
+            return;
+        }
+        Target::Contract(_) if ir.contracts.is_empty() => return,
+        Target::Contract(chain) => chain.backend().lower(&ir).wat,
+        Target::Wasi => match astrixa::codegen::wasi::lower(&ir) {
+            Ok(text) => text,
+            Err(_) => return,
+        },
+        Target::Web => astrixa::codegen::web::lower(&ast, &ir, "repro.wasm").wat,
+        Target::Script => astrixa::codegen::wasm::generate_wasm_module(&ir),
+    };
+    if !wat {
+        let _ = astrixa::codegen::binary::assemble(&text);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_minimize_keeps_only_the_lines_that_crash() {
+        let source = "fn helper() {\n    return 1\n}\nfn main() {\n    let x = boom()\n    print(x)\n}";
+        let mut tried = Vec::new();
+        let snippet = minimize(source, |candidate| {
+            tried.push(candidate.to_string());
+            candidate.contains("fn main") && candidate.contains("boom")
+        });
+        assert_eq!(snippet, "fn main() {\n    let x = boom()");
+        assert!(tried.iter().all(|candidate| !candidate.is_empty()));
+
+        // Nothing is cut when no smaller source crashes
+        assert_eq!(minimize(source, |_| false), source);
+    }
+
+    #[test]
+    fn test_build_report_describes_the_crash_and_its_build() {
+        set_phase("codegen");
+        set_source(Path::new("src/main.ax"), "fn main() {\n    print(\"hi\")\n}");
+        set_build(Target::Wasi, Profile::RELEASE, false);
+        *LAST_PANIC.lock().unwrap() = Some(PanicDetails {
+            message: "index out of bounds".to_string(),
+            location: "src/codegen/wasi.rs:1:1".to_string(),
+        });
+
+        let report = build_report(false);
+        for line in [
+            "phase:            codegen\n",
+            "target:           Wasi\n",
+            "panic:            index out of bounds\n",
+            "location:         src/codegen/wasi.rs:1:1\n",
+            "input:            src/main.ax\n",
+            "source not included",
+        ] {
+            assert!(report.contains(line), "{:?} missing from\n{}", line, report);
+        }
+        assert!(report.contains(&format!("{:?}", Profile::RELEASE)));
+
+        // With consent, the source, minimized with the build's settings;
+        // this one compiles, so no smaller one crashes the same way
+        let report = build_report(true);
+        assert!(report.contains("--- minimized source (3 of 3 lines) ---\nfn main() {\n    print(\"hi\")\n}\n--- end of source ---\n"));
+    }
+
+    #[test]
+    fn test_reports_never_overwrite_each_other() {
+        let dir = std::env::temp_dir().join(format!("astrixa-crash-test-{}", std::process::id()));
+        let first = write_report(&dir, "first").unwrap();
+        let second = write_report(&dir, "second").unwrap();
+
+        assert_ne!(first, second);
+        assert!(first.file_name().unwrap().to_string_lossy().contains(&std::process::id().to_string()));
+        assert_eq!(fs::read_to_string(&first).unwrap(), "first");
+        assert_eq!(fs::read_to_string(&second).unwrap(), "second");
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -7,17 +7,40 @@ use std::process;
 
 mod commands;
 mod config;
+mod crash;
+mod plugins;
 mod templates;
 
-use commands::{new, build, run, add};
+use commands::{new, build, run, add, grammar, test, migrate, translate, bindgen, fuzz, simulate, verify, storage_diff, decode_tx, self_bench, spec, toolchain};
 
 fn main() {
+    // A pinned or default toolchain runs the command instead, except the
+    // command that manages them
+    if toolchain::switches(std::env::args().skip(1)) {
+        toolchain::switch();
+    }
+    
     let matches = Command::new("astrixa")
-        .version("0.1.0")
+        .version(toolchain::VERSION)
         .author("ASTRIXA Team")
         .about("ASTRIXA Language Toolchain - Build, run, and manage ASTRIXA projects")
         .subcommand_required(true)
         .arg_required_else_help(true)
+        .arg(
+            Arg::new("crash-include-source")
+                .long("crash-include-source")
+                .help("If the compiler crashes, include a minimized snippet of the source in the local crash report")
+                .global(true)
+                .action(clap::ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("plugin")
+                .long("plugin")
+                .help("Load custom lints and IR passes from a compiler plugin library (repeatable)")
+                .value_name("PATH")
+                .global(true)
+                .action(clap::ArgAction::Append)
+        )
         .subcommand(
             Command::new("new")
                 .about("Create a new ASTRIXA project")
@@ -42,9 +65,16 @@ fn main() {
                     Arg::new("release")
                         .long("release")
                         .short('r')
-                        .help("Build in release mode with optimizations")
+                        .help("Build with the release profile: full optimization, no overflow checks, assertions or debug info")
                         .action(clap::ArgAction::SetTrue)
                 )
+                .arg(
+                    Arg::new("opt-level")
+                        .short('O')
+                        .help("Optimization level: 0 (none, the debug default), 1 (folding and dead code), 2 (adds inlining, CSE and loop-invariant code motion; the release default) or s (smallest: peephole and dead function elimination)")
+                        .value_name("LEVEL")
+                        .value_parser(astrixa::opt::Level::NAMES.to_vec())
+                )
                 .arg(
                     Arg::new("output")
                         .long("output")
@@ -52,17 +82,81 @@ fn main() {
                         .help("Output file path")
                         .value_name("FILE")
                 )
+                .arg(
+                    Arg::new("emit")
+                        .long("emit")
+                        .help("Artifact to produce: wasm (default, a binary module), wat (its text) or ast (JSON with spans)")
+                        .value_parser(["wasm", "wat", "ast"])
+                        .default_value("wasm")
+                )
+                .arg(
+                    Arg::new("typed")
+                        .long("typed")
+                        .help("With --emit=ast, type-check first and include function types")
+                        .action(clap::ArgAction::SetTrue)
+                )
+                .arg(
+                    Arg::new("target")
+                        .long("target")
+                        .help("What the build is for: script (default); contract, which limits reports to contract methods; wasi, a module WASI runtimes such as wasmtime run directly; web, a module with a JavaScript loader and TypeScript types for pages; or native, bytecode (.axb) for the VM that `astrixa run` runs")
+                        .value_parser(["script", "contract", "wasi", "web", "native"])
+                        .default_value("script")
+                )
+                .arg(
+                    Arg::new("chain")
+                        .long("chain")
+                        .help("Chain family a --target=contract build is for: evm (default) or near, which exports each method with NEAR-style entry points")
+                        .value_parser(astrixa::codegen::contract::ChainFamily::NAMES)
+                        .default_value("evm")
+                )
+                .arg(
+                    Arg::new("checked-arith")
+                        .long("checked-arith")
+                        .help("Trap on Int overflow instead of wrapping around; contract builds always do")
+                        .action(clap::ArgAction::SetTrue)
+                )
+                .arg(
+                    Arg::new("strip")
+                        .long("strip")
+                        .help("Leave function names, spans and the source map out of the module, whatever the profile says")
+                        .action(clap::ArgAction::SetTrue)
+                )
+                .arg(
+                    Arg::new("compare-opt")
+                        .long("compare-opt")
+                        .help("Compile with each optimization pass off and on and report bytecode size and estimated gas per function")
+                        .action(clap::ArgAction::SetTrue)
+                )
+                .arg(
+                    Arg::new("stats")
+                        .long("stats")
+                        .help("After building, report peak memory, AST and IR sizes, interned strings and time per phase")
+                        .action(clap::ArgAction::SetTrue)
+                )
         )
         .subcommand(
             Command::new("run")
-                .about("Build and run the current project")
+                .about("Build and run the current project, or run a bytecode file on the VM")
+                .arg(
+                    Arg::new("file")
+                        .help("A bytecode file (.axb), as `build --target=native` writes, to run instead of building")
+                        .value_name("FILE")
+                        .index(1)
+                )
                 .arg(
                     Arg::new("release")
                         .long("release")
                         .short('r')
-                        .help("Run in release mode")
+                        .help("Run with the release profile, as `build --release` builds")
                         .action(clap::ArgAction::SetTrue)
                 )
+                .arg(
+                    Arg::new("opt-level")
+                        .short('O')
+                        .help("Optimization level: 0 (none, the debug default), 1 (folding and dead code), 2 (adds inlining, CSE and loop-invariant code motion; the release default) or s (smallest: peephole and dead function elimination)")
+                        .value_name("LEVEL")
+                        .value_parser(astrixa::opt::Level::NAMES.to_vec())
+                )
         )
         .subcommand(
             Command::new("add")
@@ -89,25 +183,299 @@ fn main() {
             Command::new("check")
                 .about("Check the project for errors without building")
         )
+        .subcommand(
+            Command::new("test")
+                .about("Run the project's #[test] functions")
+                .arg(
+                    Arg::new("fork")
+                        .long("fork")
+                        .help("Read accounts from a live chain, as of a block")
+                        .value_name("RPC_URL@BLOCK")
+                )
+        )
+        .subcommand(
+            Command::new("migrate")
+                .about("Upgrade the project's sources to a newer language edition")
+                .arg(
+                    Arg::new("edition")
+                        .long("edition")
+                        .help("Edition to migrate to (default: the latest)")
+                        .value_name("EDITION")
+                )
+                .arg(
+                    Arg::new("dry-run")
+                        .long("dry-run")
+                        .help("Show the changes as a diff without writing any files")
+                        .action(clap::ArgAction::SetTrue)
+                )
+        )
+        .subcommand(
+            Command::new("translate")
+                .about("Compile a bytecode artifact (.axb) to WASM without its source")
+                .arg(
+                    Arg::new("input")
+                        .help("Bytecode file to translate")
+                        .required(true)
+                        .index(1)
+                )
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .short('o')
+                        .help("Output file (default: the input with a .wasm extension); a .wat path gets the text")
+                        .value_name("PATH")
+                )
+                .arg(
+                    Arg::new("release")
+                        .long("release")
+                        .help("Optimize the translated code")
+                        .action(clap::ArgAction::SetTrue)
+                )
+                .arg(
+                    Arg::new("strip")
+                        .long("strip")
+                        .help("Leave the artifact's function names and spans out of the module")
+                        .action(clap::ArgAction::SetTrue)
+                )
+        )
+        .subcommand(
+            Command::new("bindgen")
+                .about("Generate a typed frontend client from the contracts' ABIs")
+                .arg(
+                    Arg::new("target")
+                        .long("target")
+                        .help("Language to generate")
+                        .value_parser(["ts"])
+                        .required(true)
+                )
+                .arg(
+                    Arg::new("client")
+                        .long("client")
+                        .help("Web3 library the client calls")
+                        .value_parser(["viem", "ethers"])
+                        .default_value("viem")
+                )
+                .arg(
+                    Arg::new("abi")
+                        .long("abi")
+                        .help("ABI file to generate from (repeatable; default: build/*.abi.json)")
+                        .value_name("PATH")
+                        .action(clap::ArgAction::Append)
+                )
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .short('o')
+                        .help("Output directory (default: build/bindings, or . with --abi)")
+                        .value_name("DIR")
+                )
+        )
+        .subcommand(
+            Command::new("fuzz")
+                .about("Check a contract's #[invariant] methods against random call sequences")
+                .arg(
+                    Arg::new("input")
+                        .help("Source file with the contracts to fuzz")
+                        .required(true)
+                        .index(1)
+                )
+                .arg(
+                    Arg::new("runs")
+                        .long("runs")
+                        .help("Call sequences to try per contract")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("256")
+                )
+                .arg(
+                    Arg::new("depth")
+                        .long("depth")
+                        .help("Calls per sequence, after deploying")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("16")
+                )
+                .arg(
+                    Arg::new("seed")
+                        .long("seed")
+                        .help("Random seed; the same seed replays the same sequences")
+                        .value_parser(clap::value_parser!(u64))
+                        .default_value("0")
+                )
+        )
+        .subcommand(
+            Command::new("verify")
+                .about("Check contract methods' #[ensures] conditions by symbolic execution, reporting counterexamples")
+                .arg(
+                    Arg::new("input")
+                        .help("Source file with the contracts to verify")
+                        .required(true)
+                        .index(1)
+                )
+                .arg(
+                    Arg::new("depth")
+                        .long("depth")
+                        .help("Branches on inputs followed per path before giving it up")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("8")
+                )
+        )
+        .subcommand(
+            Command::new("storage-diff")
+                .about("Report state variable changes that would corrupt a contract's storage under a proxy upgrade")
+                .arg(
+                    Arg::new("old")
+                        .help("Source of the deployed version")
+                        .required(true)
+                        .index(1)
+                )
+                .arg(
+                    Arg::new("new")
+                        .help("Source of the upgrade")
+                        .required(true)
+                        .index(2)
+                )
+        )
+        .subcommand(
+            Command::new("decode-tx")
+                .about("Decode a transaction's calldata against the project's contract ABIs and the standard bindings")
+                .arg(
+                    Arg::new("input")
+                        .help("Raw calldata as hex, or a transaction hash to fetch from ASTRIXA_RPC_URL")
+                        .required(true)
+                        .index(1)
+                )
+        )
+        .subcommand(
+            Command::new("simulate")
+                .about("Run a program in the simulator, recording contract storage writes and events")
+                .arg(
+                    Arg::new("input")
+                        .help("Source file to run (default: the project's src/main.ax)")
+                        .index(1)
+                )
+                .arg(
+                    Arg::new("inspect")
+                        .long("inspect")
+                        .help("Step backwards and forwards through state versions and diff them afterwards")
+                        .action(clap::ArgAction::SetTrue)
+                )
+        )
         .subcommand(
             Command::new("clean")
                 .about("Remove build artifacts")
         )
+        .subcommand(
+            Command::new("self-bench")
+                .about("Time each compiler phase on generated corpora, optionally gating on a stored baseline")
+                .arg(
+                    Arg::new("baseline")
+                        .long("baseline")
+                        .help("Compare against the baseline at PATH (default: bench-baseline.json) and fail on a >10% regression")
+                        .value_name("PATH")
+                        .num_args(0..=1)
+                        .default_missing_value("bench-baseline.json")
+                )
+                .arg(
+                    Arg::new("save")
+                        .long("save")
+                        .help("Store the times as the baseline at PATH (default: bench-baseline.json)")
+                        .value_name("PATH")
+                        .num_args(0..=1)
+                        .default_missing_value("bench-baseline.json")
+                )
+        )
+        .subcommand(
+            Command::new("spec")
+                .about("Run the language specification suite on every execution backend")
+                .arg(
+                    Arg::new("dir")
+                        .help("Directory of spec programs (default: spec)")
+                        .default_value("spec")
+                        .index(1)
+                )
+        )
+        .subcommand(
+            Command::new("toolchain")
+                .about("Install compiler versions and pick the one that runs")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("install")
+                        .about("Install a compiler version under ~/.astrixa/toolchains")
+                        .arg(
+                            Arg::new("version")
+                                .help("Version to install, as 0.2.1")
+                                .required(true)
+                                .index(1)
+                        )
+                        .arg(
+                            Arg::new("from")
+                                .long("from")
+                                .help("The astrixa binary of that version; without it, this compiler installs itself")
+                                .value_name("FILE")
+                        )
+                )
+                .subcommand(
+                    Command::new("list")
+                        .about("List installed compiler versions")
+                )
+                .subcommand(
+                    Command::new("use")
+                        .about("Run the newest installed version matching VERSION by default; projects pin theirs with `compiler` in astrixa.toml")
+                        .arg(
+                            Arg::new("version")
+                                .help("Version, or a prefix of one such as 0.2")
+                                .required(true)
+                                .index(1)
+                        )
+                )
+        )
+        .subcommand(
+            Command::new("emit-grammar")
+                .about("Generate editor syntax grammars from the compiler's token definitions")
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .short('f')
+                        .help("Grammar format")
+                        .value_parser(["textmate", "treesitter"])
+                        .required(true)
+                )
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .short('o')
+                        .help("Output file (textmate) or directory (treesitter); prints to stdout if omitted")
+                        .value_name("PATH")
+                )
+        )
         .get_matches();
 
-    let result = match matches.subcommand() {
+    let include_source = matches.get_flag("crash-include-source");
+    let result = crash::guard(include_source, || match matches.subcommand() {
         Some(("new", sub_matches)) => handle_new(sub_matches),
         Some(("build", sub_matches)) => handle_build(sub_matches),
         Some(("run", sub_matches)) => handle_run(sub_matches),
         Some(("add", sub_matches)) => handle_add(sub_matches),
         Some(("init", _)) => handle_init(),
-        Some(("check", _)) => handle_check(),
+        Some(("check", sub_matches)) => handle_check(sub_matches),
+        Some(("test", sub_matches)) => handle_test(sub_matches),
+        Some(("migrate", sub_matches)) => handle_migrate(sub_matches),
+        Some(("translate", sub_matches)) => handle_translate(sub_matches),
+        Some(("bindgen", sub_matches)) => handle_bindgen(sub_matches),
+        Some(("fuzz", sub_matches)) => handle_fuzz(sub_matches),
+        Some(("verify", sub_matches)) => handle_verify(sub_matches),
+        Some(("storage-diff", sub_matches)) => handle_storage_diff(sub_matches),
+        Some(("decode-tx", sub_matches)) => handle_decode_tx(sub_matches),
+        Some(("simulate", sub_matches)) => handle_simulate(sub_matches),
         Some(("clean", _)) => handle_clean(),
+        Some(("self-bench", sub_matches)) => handle_self_bench(sub_matches),
+        Some(("spec", sub_matches)) => handle_spec(sub_matches),
+        Some(("toolchain", sub_matches)) => handle_toolchain(sub_matches),
+        Some(("emit-grammar", sub_matches)) => handle_emit_grammar(sub_matches),
         _ => {
             eprintln!("{}", "Unknown command".red());
             process::exit(1);
         }
-    };
+    });
 
     if let Err(e) = result {
         eprintln!("{} {}", "Error:".red().bold(), e);
@@ -125,14 +493,48 @@ fn handle_new(matches: &ArgMatches) -> Result<(), String> {
 fn handle_build(matches: &ArgMatches) -> Result<(), String> {
     let release = matches.get_flag("release");
     let output = matches.get_one::<String>("output");
+    let emit = matches.get_one::<String>("emit").unwrap();
+    
+    if emit == "ast" {
+        return build::emit_ast(output, matches.get_flag("typed"));
+    }
+    
+    if matches.get_flag("compare-opt") {
+        let contract = matches.get_one::<String>("target").unwrap() == "contract";
+        return build::compare_opt(contract, &plugin_paths(matches));
+    }
     
-    build::build_project(release, output)
+    let target = match matches.get_one::<String>("target").unwrap().as_str() {
+        "contract" => {
+            let chain = astrixa::codegen::contract::ChainFamily::parse(matches.get_one::<String>("chain").unwrap());
+            build::Target::Contract(chain.expect("clap checks --chain"))
+        }
+        "wasi" => build::Target::Wasi,
+        "web" => build::Target::Web,
+        "native" => build::Target::Native,
+        _ => build::Target::Script,
+    };
+    build::build_project(&build::BuildOptions {
+        release,
+        opt_level: matches.get_one::<String>("opt-level"),
+        output,
+        wat: emit == "wat",
+        target,
+        checked_arith: matches.get_flag("checked-arith"),
+        strip: matches.get_flag("strip"),
+        stats: matches.get_flag("stats"),
+        plugins: &plugin_paths(matches),
+    })
 }
 
 fn handle_run(matches: &ArgMatches) -> Result<(), String> {
     let release = matches.get_flag("release");
+    let opt_level = matches.get_one::<String>("opt-level");
     
-    run::run_project(release)
+    if let Some(file) = matches.get_one::<String>("file") {
+        return run::run_bytecode(std::path::Path::new(file));
+    }
+    run::run_project(release, opt_level, &plugin_paths(matches))
 }
 
 fn handle_add(matches: &ArgMatches) -> Result<(), String> {
@@ -146,10 +548,119 @@ fn handle_init() -> Result<(), String> {
     new::init_project()
 }
 
-fn handle_check() -> Result<(), String> {
-    build::check_project()
+fn handle_check(matches: &ArgMatches) -> Result<(), String> {
+    build::check_project(&plugin_paths(matches))
+}
+
+fn handle_test(matches: &ArgMatches) -> Result<(), String> {
+    let fork = matches.get_one::<String>("fork");
+    test::test_project(fork)
+}
+
+fn handle_migrate(matches: &ArgMatches) -> Result<(), String> {
+    let edition = matches.get_one::<String>("edition");
+    let dry_run = matches.get_flag("dry-run");
+    
+    migrate::migrate_project(edition, dry_run)
+}
+
+fn handle_translate(matches: &ArgMatches) -> Result<(), String> {
+    let input = matches.get_one::<String>("input").unwrap();
+    let output = matches.get_one::<String>("output");
+    let release = matches.get_flag("release");
+    let strip = matches.get_flag("strip");
+    
+    translate::translate_file(input, output, release, strip, &plugin_paths(matches))
+}
+
+fn handle_bindgen(matches: &ArgMatches) -> Result<(), String> {
+    let target = matches.get_one::<String>("target").unwrap();
+    let client = matches.get_one::<String>("client").unwrap();
+    let abi_paths: Vec<String> = matches
+        .get_many::<String>("abi")
+        .map(|paths| paths.cloned().collect())
+        .unwrap_or_default();
+    let output = matches.get_one::<String>("output");
+    
+    bindgen::generate_bindings(target, client, &abi_paths, output)
+}
+
+fn handle_fuzz(matches: &ArgMatches) -> Result<(), String> {
+    let input = matches.get_one::<String>("input").unwrap();
+    let config = astrixa::fuzz::FuzzConfig {
+        runs: *matches.get_one::<usize>("runs").unwrap(),
+        depth: *matches.get_one::<usize>("depth").unwrap(),
+        seed: *matches.get_one::<u64>("seed").unwrap(),
+    };
+    
+    fuzz::fuzz_file(input, &config)
+}
+
+fn handle_verify(matches: &ArgMatches) -> Result<(), String> {
+    let input = matches.get_one::<String>("input").unwrap();
+    let depth = *matches.get_one::<usize>("depth").unwrap();
+    
+    verify::verify_file(input, depth)
+}
+
+fn handle_storage_diff(matches: &ArgMatches) -> Result<(), String> {
+    let old = matches.get_one::<String>("old").unwrap();
+    let new = matches.get_one::<String>("new").unwrap();
+    
+    storage_diff::storage_diff(old, new)
+}
+
+fn handle_decode_tx(matches: &ArgMatches) -> Result<(), String> {
+    let input = matches.get_one::<String>("input").unwrap();
+    
+    decode_tx::decode_tx(input)
+}
+
+fn handle_self_bench(matches: &ArgMatches) -> Result<(), String> {
+    let baseline = matches.get_one::<String>("baseline");
+    let save = matches.get_one::<String>("save");
+    
+    self_bench::self_bench(baseline.map(String::as_str), save.map(String::as_str))
+}
+
+fn handle_spec(matches: &ArgMatches) -> Result<(), String> {
+    let dir = matches.get_one::<String>("dir").unwrap();
+    
+    spec::run_spec(dir)
+}
+
+fn handle_simulate(matches: &ArgMatches) -> Result<(), String> {
+    let input = matches.get_one::<String>("input");
+    let inspect = matches.get_flag("inspect");
+    
+    simulate::simulate(input, inspect)
 }
 
 fn handle_clean() -> Result<(), String> {
     build::clean_project()
 }
+
+fn handle_toolchain(matches: &ArgMatches) -> Result<(), String> {
+    match matches.subcommand() {
+        Some(("install", sub_matches)) => {
+            let version = sub_matches.get_one::<String>("version").unwrap();
+            toolchain::install(version, sub_matches.get_one::<String>("from"))
+        }
+        Some(("use", sub_matches)) => toolchain::use_version(sub_matches.get_one::<String>("version").unwrap()),
+        _ => toolchain::list(),
+    }
+}
+
+fn handle_emit_grammar(matches: &ArgMatches) -> Result<(), String> {
+    let format = matches.get_one::<String>("format").unwrap();
+    let output = matches.get_one::<String>("output");
+    
+    grammar::emit_grammar(format, output)
+}
+
+fn plugin_paths(matches: &ArgMatches) -> Vec<String> {
+    matches
+        .get_many::<String>("plugin")
+        .map(|paths| paths.cloned().collect())
+        .unwrap_or_default()
+}
@@ -0,0 +1,55 @@
+// Compiler plugins loaded with --plugin: shared libraries that register
+// custom lints and IR passes on the session (see astrixa::plugin)
+
+use colored::*;
+use astrixa::plugin::{RegisterFn, ABI_FINGERPRINT, FINGERPRINT_SYMBOL, PLUGIN_API_VERSION, REGISTER_SYMBOL, VERSION_SYMBOL};
+use astrixa::session::Session;
+use libloading::{Library, Symbol};
+
+/// A session with every plugin in `paths` registered
+pub fn load_session(paths: &[String]) -> Result<Session, String> {
+    let mut session = Session::new();
+    for path in paths {
+        load(&mut session, path)?;
+        println!("   {} plugin {}", "Loaded".cyan(), path);
+    }
+    Ok(session)
+}
+
+fn load(session: &mut Session, path: &str) -> Result<(), String> {
+    // SAFETY: loading a plugin runs its code; the user asked for it by path.
+    // The version and fingerprint checks reject libraries built against
+    // another plugin API or other AST, IR, session or error types, but not
+    // ones built by a different compiler
+    // (see astrixa::plugin).
+    unsafe {
+        let library = Library::new(path)
+            .map_err(|e| format!("Failed to load plugin {}: {}", path, e))?;
+
+        let version: Symbol<*const u32> = library
+            .get(VERSION_SYMBOL)
+            .map_err(|_| format!("{} is not an ASTRIXA plugin (missing declare_plugin!)", path))?;
+        if **version != PLUGIN_API_VERSION {
+            return Err(format!(
+                "Plugin {} targets plugin API {}, but this compiler provides {}",
+                path, **version, PLUGIN_API_VERSION
+            ));
+        }
+        let fingerprint: Symbol<*const u64> = library
+            .get(FINGERPRINT_SYMBOL)
+            .map_err(|_| format!("{} was built for an older compiler; rebuild it against this one", path))?;
+        if **fingerprint != ABI_FINGERPRINT {
+            return Err(format!("Plugin {} was built against different compiler types; rebuild it against this compiler", path));
+        }
+
+        let register: Symbol<RegisterFn> = library
+            .get(REGISTER_SYMBOL)
+            .map_err(|_| format!("{} is not an ASTRIXA plugin (missing declare_plugin!)", path))?;
+        register(session);
+
+        // The registered lints and passes point into the library's code,
+        // so it must stay loaded for the rest of the process
+        std::mem::forget(library);
+    }
+    Ok(())
+}
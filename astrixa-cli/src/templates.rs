@@ -45,8 +45,8 @@ export fn multiply(a, b) {
 }
 
 export fn power(base, exp) {
-    let result = 1
-    let i = 0
+    let mut result = 1
+    let mut i = 0
     while i < exp {
         result = result * base
         i = i + 1
@@ -93,12 +93,12 @@ fn main {
     print(hash)
 }
 
-export fn sign_transaction(tx_data) {
+export fn sign_transaction(tx_data: String) {
     let hash = keccak(tx_data)
     return hash
 }
 
-export fn verify_signature(signature, message) {
+export fn verify_signature(signature: String, message: String) {
     let expected = keccak(message)
     return signature == expected
 }
@@ -137,8 +137,8 @@ fn main {
 
 export fn predict(data) {
     // Simple linear prediction
-    let sum = 0
-    let count = 0
+    let mut sum = 0
+    let mut count = 0
     
     while count < 5 {
         sum = sum + data
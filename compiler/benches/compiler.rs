@@ -0,0 +1,24 @@
+//! Benchmarks: each compiler phase on each corpus (see `astrixa::bench`)
+//!
+//! The same cases `astrixa self-bench` gates on, measured in detail with
+//! criterion.
+//!
+//!   cargo bench --bench compiler [phase/corpus filter]
+
+use std::hint::black_box;
+use criterion::{criterion_group, criterion_main, Criterion};
+use astrixa::bench;
+
+fn phases(c: &mut Criterion) {
+    let corpora = bench::corpora(200);
+    for phase in bench::PHASES {
+        for corpus in &corpora {
+            c.bench_function(&format!("{}/{}", phase, corpus.name), |b| {
+                b.iter(|| black_box(corpus.run(phase)))
+            });
+        }
+    }
+}
+
+criterion_group!(benches, phases);
+criterion_main!(benches);
@@ -0,0 +1,52 @@
+//! Benchmark: build a large text report in a script
+//!
+//! Appending with `report = report + line` grows the string in place, and
+//! a StringBuilder does the same explicitly, so both stay linear in the
+//! report's size. Starting the sum with anything else copies the report
+//! for every line; that form is timed on a much smaller report, and is
+//! still the slowest.
+//!
+//!   cargo run --release --example string_report [megabytes]
+
+use std::time::Instant;
+use astrixa::interpreter::Interpreter;
+use astrixa::lexer::Lexer;
+use astrixa::parser::Parser;
+
+const LINE: usize = 28; // Bytes in most lines: "row 123456: total 864192 ok\n"
+
+fn script(rows: usize, append: &str) -> String {
+    format!(
+        r#"
+fn main() {{
+    let mut report = ""
+    let builder = StringBuilder()
+    let mut i = 0
+    while i < {rows} {{
+        let line = "row " + str(i) + ": total " + str(i * 7) + " ok\n"
+        {append}
+        i = i + 1
+    }}
+    print(len(report) + len(builder))
+}}
+"#
+    )
+}
+
+fn time(label: &str, rows: usize, append: &str) {
+    let program = Parser::new(Lexer::new(&script(rows, append))).parse().expect("benchmark script parses");
+    let start = Instant::now();
+    Interpreter::new().run(program).expect("benchmark script runs");
+    let elapsed = start.elapsed();
+    let megabytes = (rows * LINE) as f64 / 1_000_000.0;
+    println!("{:<16} ~{:>5.1} MB in {:>8.3}s ({:.1} MB/s)", label, megabytes, elapsed.as_secs_f64(), megabytes / elapsed.as_secs_f64());
+}
+
+fn main() {
+    let megabytes: usize = std::env::args().nth(1).and_then(|arg| arg.parse().ok()).unwrap_or(10);
+    let rows = megabytes * 1_000_000 / LINE;
+
+    time("report + line", rows, "report = report + line");
+    time("StringBuilder", rows, "builder.append(line)");
+    time("\"\" + report", rows / 10, "report = \"\" + report + line");
+}
@@ -0,0 +1,60 @@
+//! Benchmark: start a VM per request from a shared compiled program
+//!
+//! A `ModuleCache` compiles each program once; every VM it starts shares
+//! that program and allocates only its own stack, variables and gas, so
+//! starting one costs microseconds however large the program is. Each
+//! instance here also runs it, a loop of 10 instructions per iteration.
+//!
+//!   cargo run --release --example vm_instances [instances]
+
+use std::time::Instant;
+use astrixa::bytecode::{Instruction, OpCode};
+use astrixa::module_cache::ModuleCache;
+use astrixa::vm::Tick;
+
+/// Counts `i` to `n`, then returns it
+fn count_to(n: i64) -> Vec<Instruction> {
+    vec![
+        Instruction::const_instr("0".to_string()),
+        Instruction::store_var("i".to_string()),
+        Instruction::new(OpCode::Pop, None),
+        Instruction::var_instr("i".to_string()),
+        Instruction::const_instr(n.to_string()),
+        Instruction::new(OpCode::Less, None),
+        Instruction::jump_if_false(13),
+        Instruction::var_instr("i".to_string()),
+        Instruction::const_instr("1".to_string()),
+        Instruction::new(OpCode::Add, None),
+        Instruction::store_var("i".to_string()),
+        Instruction::new(OpCode::Pop, None),
+        Instruction::jump_instr(3),
+        Instruction::var_instr("i".to_string()),
+        Instruction::new(OpCode::Return, None),
+    ]
+}
+
+fn main() {
+    let instances: u32 = std::env::args().nth(1).and_then(|arg| arg.parse().ok()).unwrap_or(10_000);
+    let cache = ModuleCache::new();
+    cache.get_or_compile("handler", || Ok(count_to(100))).expect("benchmark program compiles");
+
+    let start = Instant::now();
+    for _ in 0..instances {
+        cache.instance("handler").expect("cached");
+    }
+    let startup = start.elapsed() / instances;
+
+    let start = Instant::now();
+    for _ in 0..instances {
+        let mut vm = cache.instance("handler").expect("cached");
+        assert!(matches!(vm.run_for(usize::MAX), Ok(Tick::Finished(_))));
+    }
+    let request = start.elapsed() / instances;
+
+    println!(
+        "{} instances: {:.1} us to start each, {:.1} us to start and run each",
+        instances,
+        startup.as_secs_f64() * 1e6,
+        request.as_secs_f64() * 1e6
+    );
+}
@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+use serde_json::{json, Value};
+use crate::ast::{Attribute, Stmt, StmtKind};
+use crate::messages;
+use crate::typechecker::FunctionSignature;
+use crate::types::{self, Type};
+
+// Contract ABIs.
+//
+// Each contract gets an Ethereum-style JSON ABI (the format viem, ethers and
+// most wallets read), written next to the build output. Parameter types come
+// from their annotations (`to: Address`, through any type alias);
+// unannotated parameters and return values use the type checker's view. V1
+// represents values the checker cannot type yet, such as contract state, as
+// Int, so they appear as int64.
+
+/// A contract's name and its ABI entries
+#[derive(Debug, Clone)]
+pub struct ContractAbi {
+    pub name: String,
+    pub entries: Value,
+}
+
+/// The ABI of every contract in a type-checked program. `signatures` is the
+/// checker's function table, where methods are named `Contract.method`.
+pub fn contract_abis(
+    program: &[Stmt],
+    signatures: &HashMap<String, FunctionSignature>,
+) -> Result<Vec<ContractAbi>, String> {
+    let aliases = types::aliases(program);
+    let mut abis = Vec::new();
+    for stmt in program {
+        let StmtKind::Contract { name: contract, constructor, methods, .. } = &stmt.kind else { continue };
+
+        let mut entries = Vec::new();
+        for method in constructor.as_deref().into_iter().chain(methods) {
+            let StmtKind::Function { name, params, param_types, attributes, .. } = &method.kind else { continue };
+
+            let mut inputs = Vec::new();
+            for (param, annotation) in params.iter().zip(param_types) {
+                let abi_type = match annotation {
+                    Some(annotation) => types::expand_aliases(annotation, &aliases).ok().and_then(|t| annotation_type(&t)),
+                    None => Some("int64"),
+                };
+                let abi_type = abi_type.ok_or_else(|| {
+                    let written = annotation.as_deref().unwrap_or_default();
+                    messages::render("E0220", &[&param, &format!("{}.{}", contract, name), &written])
+                })?;
+                inputs.push(json!({ "name": param, "type": abi_type }));
+            }
+
+            let mutability = if attributes.contains(&Attribute::View) || attributes.contains(&Attribute::Invariant) {
+                "view"
+            } else if attributes.contains(&Attribute::Payable) {
+                "payable"
+            } else {
+                "nonpayable"
+            };
+
+            if name == "constructor" {
+                entries.push(json!({ "type": "constructor", "inputs": inputs, "stateMutability": mutability }));
+                continue;
+            }
+
+            let qualified = format!("{}.{}", contract, name);
+            let returns = signatures.get(&qualified).map_or(Type::Unknown, |sig| sig.return_type.clone());
+            let outputs = output_types(&returns)
+                .ok_or_else(|| messages::render("E0221", &[&qualified, &returns.to_string()]))?;
+            let outputs: Vec<Value> = outputs.iter().map(|t| json!({ "name": "", "type": t })).collect();
+
+            entries.push(json!({
+                "type": "function",
+                "name": name,
+                "inputs": inputs,
+                "outputs": outputs,
+                "stateMutability": mutability,
+            }));
+        }
+
+        abis.push(ContractAbi { name: contract.clone(), entries: Value::Array(entries) });
+    }
+    Ok(abis)
+}
+
+/// The ABI type for a parameter annotation, if it has one
+fn annotation_type(annotation: &str) -> Option<&'static str> {
+    match annotation.to_ascii_lowercase().as_str() {
+        "int" | "i64" => Some("int64"),
+        "u256" | "uint" => Some("uint256"),
+        "address" => Some("address"),
+        "bool" => Some("bool"),
+        "string" => Some("string"),
+        "bytes" => Some("bytes"),
+        _ => None,
+    }
+}
+
+/// The ABI outputs for a checked return type: one per tuple element
+fn output_types(returns: &Type) -> Option<Vec<&'static str>> {
+    match returns {
+        Type::Void => Some(Vec::new()),
+        Type::Int | Type::Unknown => Some(vec!["int64"]),
+        Type::Bool => Some(vec!["bool"]),
+        Type::String => Some(vec!["string"]),
+        Type::Address => Some(vec!["address"]),
+        Type::U256 => Some(vec!["uint256"]),
+        Type::Tuple(items) => items.iter().map(|t| output_types(t)?.pop()).collect(),
+        Type::Float | Type::Range | Type::AIResult | Type::Option(_) | Type::Result(..) | Type::Function(..) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    use crate::typechecker::TypeChecker;
+
+    fn abis(source: &str) -> Result<Vec<ContractAbi>, String> {
+        let program = Parser::new(Lexer::new(source)).parse().unwrap();
+        let mut checker = TypeChecker::new();
+        checker.check(&program).unwrap();
+        contract_abis(&program, checker.functions())
+    }
+
+    #[test]
+    fn test_contract_abi_from_annotations_and_attributes() {
+        let source = "contract Vault {\n    state owner: Address\n    constructor(admin: Address) {\n        owner = admin\n    }\n    #[payable]\n    fn deposit() {\n    }\n    fn transfer(to: Address, amount: U256) {\n        return true\n    }\n    #[view]\n    fn name() {\n        return (\"Vault\", 1)\n    }\n}\n";
+
+        let vault = abis(source).unwrap();
+        assert_eq!(vault.len(), 1);
+        assert_eq!(vault[0].name, "Vault");
+        assert_eq!(
+            vault[0].entries,
+            json!([
+                { "type": "constructor", "inputs": [{ "name": "admin", "type": "address" }], "stateMutability": "nonpayable" },
+                { "type": "function", "name": "deposit", "inputs": [], "outputs": [], "stateMutability": "payable" },
+                {
+                    "type": "function",
+                    "name": "transfer",
+                    "inputs": [{ "name": "to", "type": "address" }, { "name": "amount", "type": "uint256" }],
+                    "outputs": [{ "name": "", "type": "bool" }],
+                    "stateMutability": "nonpayable"
+                },
+                {
+                    "type": "function",
+                    "name": "name",
+                    "inputs": [],
+                    "outputs": [{ "name": "", "type": "string" }, { "name": "", "type": "int64" }],
+                    "stateMutability": "view"
+                }
+            ])
+        );
+
+        let err = abis("contract Registry {\n    fn set(entries: map<Address, U256>) {\n    }\n}\n").unwrap_err();
+        assert!(err.contains("map<Address, U256>"), "{}", err);
+    }
+}
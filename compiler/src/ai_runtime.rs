@@ -92,7 +92,7 @@ impl AIRuntime for LocalAIRuntime {
             ModelType::Embedding => {
                 // Create a simple deterministic embedding
                 let embedding = simple_embedding(input);
-                Ok(Value::Array(
+                Ok(Value::array(
                     embedding
                         .iter()
                         .map(|&f| Value::Number((f * 100.0) as i64))
@@ -168,9 +168,9 @@ fn simple_embedding(text: &str) -> Vec<f64> {
 
     // Generate 8-dimensional embedding from hash
     let mut embedding = vec![0.0; 8];
-    for i in 0..8 {
+    for (i, value) in embedding.iter_mut().enumerate() {
         let shifted = hash.wrapping_shr((i * 8) as u32);
-        embedding[i] = ((shifted as f64) / 256.0) - 0.5;
+        *value = ((shifted as f64) / 256.0) - 0.5;
     }
     embedding
 }
@@ -0,0 +1,247 @@
+use std::collections::HashMap;
+use crate::ast::{Expr, ExprKind, Stmt, StmtKind};
+
+// Named and default arguments.
+//
+//   fn greet(name, greeting = "Hello") {
+//       print(greeting + ", " + name)
+//   }
+//
+//   greet("Ana")                       // Hello, Ana
+//   greet("Ana", "Hi")                 // Hi, Ana
+//   greet(greeting: "Hey", name: "Ana")
+//
+// A call passes its positional arguments first, then any by name. Defaults
+// are literals, and every parameter after one with a default has one too,
+// so a call always comes down to a plain positional one: `bind` finds its
+// arguments in parameter order with the defaults filled in. Lowering runs
+// `resolve` so only positional calls reach the IR; the interpreter binds
+// each call as it makes it, and the type checker reports calls that do not
+// bind.
+
+/// Why a call's arguments do not fit its function's parameters
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArgumentError {
+    Unknown(String),  // No parameter has this name
+    Repeated(String), // Passed by position and by name, or twice by name
+    Missing(String),  // Neither passed nor defaulted
+    PositionalAfterNamed,
+}
+
+impl ArgumentError {
+    /// The catalog code reporting this error, and the argument it is about
+    pub fn code(&self) -> (&'static str, &str) {
+        match self {
+            ArgumentError::Unknown(name) => ("E0248", name),
+            ArgumentError::Repeated(name) => ("E0249", name),
+            ArgumentError::Missing(name) => ("E0250", name),
+            ArgumentError::PositionalAfterNamed => ("E0251", ""),
+        }
+    }
+}
+
+/// Whether a call has to be bound before its arguments line up with the
+/// parameters. Other calls are left to the arity check.
+pub fn needs_binding(defaults: &[Option<Expr>], args: &[Expr]) -> bool {
+    defaults.iter().any(Option::is_some) || args.iter().any(is_named)
+}
+
+pub fn is_named(arg: &Expr) -> bool {
+    matches!(arg.kind, ExprKind::Named(..))
+}
+
+/// `args` in parameter order, with defaults filled in. Extra positional
+/// arguments are kept, for the arity check to report.
+pub fn bind(params: &[String], defaults: &[Option<Expr>], args: &[Expr]) -> Result<Vec<Expr>, ArgumentError> {
+    let positional = args.iter().take_while(|arg| !is_named(arg)).count();
+    let mut slots: Vec<Option<Expr>> = args[..positional].iter().cloned().map(Some).collect();
+    slots.resize(params.len().max(positional), None);
+
+    for arg in &args[positional..] {
+        let ExprKind::Named(name, value) = &arg.kind else {
+            return Err(ArgumentError::PositionalAfterNamed);
+        };
+        let i = params.iter().position(|p| p == name).ok_or_else(|| ArgumentError::Unknown(name.clone()))?;
+        if slots[i].is_some() {
+            return Err(ArgumentError::Repeated(name.clone()));
+        }
+        slots[i] = Some((**value).clone());
+    }
+
+    slots
+        .into_iter()
+        .enumerate()
+        .map(|(i, slot)| {
+            slot.or_else(|| defaults.get(i).cloned().flatten())
+                .ok_or_else(|| ArgumentError::Missing(params[i].clone()))
+        })
+        .collect()
+}
+
+/// `stmts` with every call to a declared function or contract method
+/// passing its arguments by position. Calls that do not bind are left as
+/// written.
+pub fn resolve(stmts: &[Stmt]) -> Vec<Stmt> {
+    let mut signatures: HashMap<String, (Vec<String>, Vec<Option<Expr>>)> = HashMap::new();
+    for stmt in stmts {
+        match &stmt.kind {
+            StmtKind::Function { name, params, defaults, .. } => {
+                signatures.insert(name.clone(), (params.clone(), defaults.clone()));
+            }
+            StmtKind::Contract { name, constructor, methods, .. } => {
+                for method in constructor.iter().map(|c| c.as_ref()).chain(methods.iter()) {
+                    if let StmtKind::Function { name: method_name, params, defaults, .. } = &method.kind {
+                        signatures.insert(format!("{}.{}", name, method_name), (params.clone(), defaults.clone()));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut program = stmts.to_vec();
+    for stmt in &mut program {
+        match &mut stmt.kind {
+            StmtKind::Function { body, .. } => resolve_body(body, &signatures),
+            StmtKind::Contract { constructor, methods, .. } => {
+                for method in constructor.iter_mut().map(|c| c.as_mut()).chain(methods.iter_mut()) {
+                    if let StmtKind::Function { body, .. } = &mut method.kind {
+                        resolve_body(body, &signatures);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    program
+}
+
+type Signatures = HashMap<String, (Vec<String>, Vec<Option<Expr>>)>;
+
+fn resolve_body(body: &mut [Stmt], signatures: &Signatures) {
+    for stmt in body {
+        match &mut stmt.kind {
+            StmtKind::Let { value, .. } | StmtKind::LetTuple { value, .. } | StmtKind::Assign { value, .. }
+            | StmtKind::Expression(value) | StmtKind::Return(value) | StmtKind::Value(value) | StmtKind::Panic(value) => {
+                resolve_expr(value, signatures)
+            }
+            StmtKind::Defer(body) => resolve_body(body, signatures),
+            StmtKind::If { condition, then_body, else_body } => {
+                resolve_expr(condition, signatures);
+                resolve_body(then_body, signatures);
+                if let Some(else_body) = else_body {
+                    resolve_body(else_body, signatures);
+                }
+            }
+            StmtKind::While { condition: value, body } | StmtKind::For { iterable: value, body, .. } => {
+                resolve_expr(value, signatures);
+                resolve_body(body, signatures);
+            }
+            StmtKind::Match { value, arms } => {
+                resolve_expr(value, signatures);
+                for arm in arms {
+                    resolve_body(&mut arm.body, signatures);
+                }
+            }
+            StmtKind::Import { .. } | StmtKind::Function { .. } | StmtKind::Contract { .. }
+            | StmtKind::Trait { .. } | StmtKind::Impl { .. } | StmtKind::TypeAlias { .. } => {}
+        }
+    }
+}
+
+fn resolve_expr(expr: &mut Expr, signatures: &Signatures) {
+    let callee = match &expr.kind {
+        ExprKind::Call(name, _) => Some(name.clone()),
+        ExprKind::ModuleCall(receiver, method, _) => Some(format!("{}.{}", receiver, method)),
+        _ => None,
+    };
+    match &mut expr.kind {
+        ExprKind::Call(_, args) | ExprKind::ModuleCall(_, _, args) => {
+            if let Some((params, defaults)) = callee.and_then(|name| signatures.get(&name)) {
+                if needs_binding(defaults, args) {
+                    if let Ok(bound) = bind(params, defaults, args) {
+                        *args = bound;
+                    }
+                }
+            }
+            for arg in args {
+                resolve_expr(arg, signatures);
+            }
+        }
+        ExprKind::Tuple(items) | ExprKind::Array(items) => {
+            for item in items {
+                resolve_expr(item, signatures);
+            }
+        }
+        ExprKind::Await(inner) | ExprKind::Try(inner) | ExprKind::Named(_, inner) | ExprKind::Cast(inner, _) => resolve_expr(inner, signatures),
+        ExprKind::Range(l, r) | ExprKind::Slice(l, r)
+        | ExprKind::Add(l, r) | ExprKind::Sub(l, r) | ExprKind::Mul(l, r) | ExprKind::Div(l, r) | ExprKind::Mod(l, r)
+        | ExprKind::Eq(l, r) | ExprKind::Ne(l, r) | ExprKind::Lt(l, r) | ExprKind::Le(l, r)
+        | ExprKind::Gt(l, r) | ExprKind::Ge(l, r)
+            | ExprKind::And(l, r) | ExprKind::Or(l, r) => {
+            resolve_expr(l, signatures);
+            resolve_expr(r, signatures);
+        }
+        ExprKind::Number(_) | ExprKind::Float(_) | ExprKind::Bool(_) | ExprKind::String(_)
+        | ExprKind::Identifier(_) | ExprKind::Property(..) => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::IRInstr;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    #[test]
+    fn test_calls_are_resolved_to_positional_arguments() {
+        let source = r#"
+            fn fee(amount, rate = 3, floor = 1) {
+                return amount * rate / 100 + floor
+            }
+            fn main() {
+                let a = fee(100)
+                let b = fee(floor: 5, amount: 200)
+                return fee(300, 2)
+            }
+        "#;
+        let program = Parser::new(Lexer::new(source)).parse().unwrap();
+        let module = crate::lowering::lower(&program);
+        let main = module.functions.iter().find(|f| f.name == "main").unwrap();
+
+        let consts: Vec<i64> = main
+            .instructions
+            .iter()
+            .filter_map(|i| match i {
+                IRInstr::LoadConstInt(n) => Some(*n),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(consts, vec![100, 3, 1, 200, 3, 5, 300, 2, 1]);
+        let calls = main.instructions.iter().filter(|i| **i == IRInstr::Call("fee".to_string(), 3)).count();
+        assert_eq!(calls, 3);
+    }
+
+    #[test]
+    fn test_binding_reports_arguments_that_do_not_fit() {
+        let program = Parser::new(Lexer::new("fn f(a, b = 1) { }\nfn g() { f(1, b: 2, c: 3); f(b: 2); f(1, a: 2); f(a: 1, 2) }")).parse().unwrap();
+        let StmtKind::Function { params, defaults, .. } = &program[0].kind else { panic!("not a function") };
+        let StmtKind::Function { body, .. } = &program[1].kind else { panic!("not a function") };
+        let errors: Vec<ArgumentError> = body
+            .iter()
+            .filter_map(|stmt| match &stmt.kind {
+                StmtKind::Expression(Expr { kind: ExprKind::Call(_, args), .. })
+                | StmtKind::Return(Expr { kind: ExprKind::Call(_, args), .. })
+                | StmtKind::Value(Expr { kind: ExprKind::Call(_, args), .. }) => bind(params, defaults, args).err(),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(errors, vec![
+            ArgumentError::Unknown("c".to_string()),
+            ArgumentError::Missing("a".to_string()),
+            ArgumentError::Repeated("a".to_string()),
+            ArgumentError::PositionalAfterNamed,
+        ]);
+    }
+}
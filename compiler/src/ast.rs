@@ -4,12 +4,40 @@ use crate::types::Type;
 #[derive(Debug, Clone)]
 pub struct Module {
     pub name: String,
-    pub imports: Vec<String>,
+    pub imports: Vec<String>, // Names of imported modules
     pub statements: Vec<Stmt>,
 }
 
+/// Source range of a node: 1-based, end is just past the last token
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Span {
+    pub start_line: usize,
+    pub start_column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+}
+
+impl std::fmt::Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "line {}, column {}", self.start_line, self.start_column)
+    }
+}
+
+/// An expression and where it was written
 #[derive(Debug, Clone)]
-pub enum Expr {
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+impl Expr {
+    pub fn new(kind: ExprKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ExprKind {
     Number(i64),
     Float(f64),
     Bool(bool),
@@ -17,6 +45,15 @@ pub enum Expr {
     Identifier(String),
     Call(String, Vec<Expr>), // Function call: name, arguments
     ModuleCall(String, String, Vec<Expr>), // STEP 49: module.function(args)
+    Property(String, String),              // object.name: a value the host provides, such as msg.sender
+    Named(String, Box<Expr>),              // name: value, an argument passed by parameter name
+    Await(Box<Expr>),                      // await task_or_async_call
+    Tuple(Vec<Expr>),                      // (a, b): two or more values
+    Array(Vec<Expr>),                      // [a, b]: any number of values, built at runtime
+    Range(Box<Expr>, Box<Expr>),           // start..end, end excluded
+    Slice(Box<Expr>, Box<Expr>),           // value[range]: part of a string or array
+    Try(Box<Expr>),                        // value?: unwrap an Option or Result, or return its None/Err
+    Cast(Box<Expr>, String),               // value as Float: convert between Int and Float
     
     // Binary operators (STEP 43)
     Add(Box<Expr>, Box<Expr>),
@@ -32,22 +69,120 @@ pub enum Expr {
     Le(Box<Expr>, Box<Expr>),
     Gt(Box<Expr>, Box<Expr>),
     Ge(Box<Expr>, Box<Expr>),
+
+    // Logical operators: the right side runs only when the left leaves the answer open
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
 }
 
+/// Function attribute, written `#[name]` before the declaration
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Attribute {
+    Test,    // Run by `astrixa test`, never by main
+    Payable, // Contract method that accepts value
+    View,    // Contract method that never writes state
+    Inline,  // Always inline when the optimizer can
+    Invariant, // Contract method that must hold after every call, checked by `astrixa fuzz`
+    Unbounded, // Contract method whose loops may outgrow a block on purpose
+    NoPrelude, // Calls nothing implicitly: only declared and imported functions
+}
+
+impl Attribute {
+    pub const ALL: &'static [Attribute] = &[
+        Attribute::Test,
+        Attribute::Payable,
+        Attribute::View,
+        Attribute::Inline,
+        Attribute::Invariant,
+        Attribute::Unbounded,
+        Attribute::NoPrelude,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Attribute::Test => "test",
+            Attribute::Payable => "payable",
+            Attribute::View => "view",
+            Attribute::Inline => "inline",
+            Attribute::Invariant => "invariant",
+            Attribute::Unbounded => "unbounded",
+            Attribute::NoPrelude => "no_prelude",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Attribute> {
+        Self::ALL.iter().copied().find(|a| a.name() == name)
+    }
+}
+
+/// A function's `#[requires(expr)]` or `#[ensures(expr)]`: what a contract
+/// method assumes of its arguments and state, or promises when it returns
+/// (`result` names the returned value)
+#[derive(Debug, Clone)]
+pub struct Condition {
+    pub kind: ConditionKind,
+    pub expr: Expr,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConditionKind {
+    Requires,
+    Ensures,
+}
+
+impl ConditionKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ConditionKind::Requires => "requires",
+            ConditionKind::Ensures => "ensures",
+        }
+    }
+}
+
+/// A statement and where it was written
 #[derive(Debug, Clone)]
-pub enum Stmt {
-    Import(String),    // STEP 49: import module_name
+pub struct Stmt {
+    pub kind: StmtKind,
+    pub span: Span,
+}
+
+impl Stmt {
+    pub fn new(kind: StmtKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum StmtKind {
+    Import {           // STEP 49: import module_name
+        module: String,
+        alias: Option<String>,      // import math as m
+        items: Option<Vec<String>>, // import math.{add, sub}
+    },
     Function {
         name: String,
         params: Vec<String>,  // STEP 46: Function parameters
+        param_types: Vec<Option<String>>, // Each parameter's annotation as written, e.g. "Address"
+        defaults: Vec<Option<Expr>>,      // Each parameter's default, a literal: `greeting = "Hello"`
+        type_params: Vec<String>,         // fn max<T>: names usable in the annotations
+        return_annotation: Option<String>, // `-> T` as written
         return_type: Type,
         body: Vec<Stmt>,
         exported: bool,      // STEP 49: export fn
+        is_async: bool,      // async fn: calls return a task handle
+        attributes: Vec<Attribute>,
+        conditions: Vec<Condition>, // #[requires(...)] and #[ensures(...)], in order
     },
     Expression(Expr),  // Expression statement
     Let {              // Variable declaration
         name: String,
         value: Expr,
+        mutable: bool,         // let mut x: may be reassigned
+    },
+    LetTuple {         // let (a, b) = pair; `_` skips an element
+        names: Vec<String>,
+        value: Expr,
+        mutable: bool,         // let mut (a, b): every name may be reassigned
     },
     Assign {           // Variable assignment (NEW)
         name: String,
@@ -62,6 +197,67 @@ pub enum Stmt {
         condition: Expr,
         body: Vec<Stmt>,
     },
+    For {              // for i in 0..10 { }: counts through a range
+        var: String,
+        iterable: Expr,
+        body: Vec<Stmt>,
+    },
     Return(Expr),      // STEP 46: Return statement
+    Value(Expr),       // A function's final expression without `;`: returned, unless it is Void
     Panic(Expr),       // STEP 48: Panic statement - explicit failure
+    Defer(Vec<Stmt>),  // defer close(file) or defer { ... }: runs as the enclosing block exits
+    Contract {         // Smart contract declaration
+        name: String,
+        state: Vec<String>,              // Persistent state variables
+        state_types: Vec<Option<String>>, // Each state variable's annotation as written, e.g. "Address"
+        constructor: Option<Box<Stmt>>,  // StmtKind::Function named "constructor"
+        methods: Vec<Stmt>,              // StmtKind::Function entries
+    },
+    Trait {            // trait Printable { fn to_string(self) -> String }
+        name: String,
+        methods: Vec<Stmt>, // StmtKind::Function signatures, without bodies
+    },
+    Impl {             // impl Printable for Int { ... }
+        trait_name: String,
+        target: String,     // The implementing type, as written
+        methods: Vec<Stmt>, // StmtKind::Function entries, `self` first
+    },
+    TypeAlias {        // type Wei = U256: another name for a type
+        name: String,
+        target: String,    // The type it stands for, as written
+    },
+    Match {            // match value { Some(x) => ..., None => ... }
+        value: Expr,
+        arms: Vec<MatchArm>, // Tried in order
+    },
+}
+
+/// One `pattern => body` of a match
+#[derive(Debug, Clone)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub body: Vec<Stmt>,
+}
+
+/// What a match arm accepts: a variant of Option or Result, binding its
+/// payload to a name (`_` binds nothing), or anything at all
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pattern {
+    Some(String),
+    None,
+    Ok(String),
+    Err(String),
+    Wildcard,
+}
+
+impl std::fmt::Display for Pattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Pattern::Some(name) => write!(f, "Some({})", name),
+            Pattern::None => write!(f, "None"),
+            Pattern::Ok(name) => write!(f, "Ok({})", name),
+            Pattern::Err(name) => write!(f, "Err({})", name),
+            Pattern::Wildcard => write!(f, "_"),
+        }
+    }
 }
@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+use serde_json::{json, Value};
+use crate::ast::{Expr, ExprKind, Span, Stmt, StmtKind};
+use crate::typechecker::FunctionSignature;
+
+/// Bumped whenever the JSON shape changes in a way consumers must handle
+pub const AST_JSON_VERSION: u32 = 1;
+
+/// Serialize a parsed program to JSON for external tools. Every statement
+/// and expression carries its span.
+///
+/// When `signatures` is given (the type checker's function table), each
+/// function also carries its parameter and return types.
+pub fn program_to_json(
+    program: &[Stmt],
+    signatures: Option<&HashMap<String, FunctionSignature>>,
+) -> Value {
+    let writer = AstWriter { signatures };
+    let items: Vec<Value> = program.iter().map(|stmt| writer.stmt(stmt, None)).collect();
+
+    json!({
+        "version": AST_JSON_VERSION,
+        "typed": signatures.is_some(),
+        "items": items,
+    })
+}
+
+struct AstWriter<'a> {
+    signatures: Option<&'a HashMap<String, FunctionSignature>>,
+}
+
+impl AstWriter<'_> {
+    /// `contract` names the enclosing contract for method signature lookup
+    fn stmt(&self, stmt: &Stmt, contract: Option<&str>) -> Value {
+        let mut node = match &stmt.kind {
+            StmtKind::Import { module, alias, items } => json!({
+                "kind": "Import",
+                "module": module,
+                "alias": alias,
+                "items": items,
+            }),
+            StmtKind::Function { name, params, defaults, type_params, body, exported, is_async, attributes, .. } => {
+                let mut node = json!({
+                    "kind": "Function",
+                    "name": name,
+                    "type_params": type_params,
+                    "params": params,
+                    "defaults": defaults.iter().map(|d| d.as_ref().map(expr_json)).collect::<Vec<_>>(),
+                    "exported": exported,
+                    "async": is_async,
+                    "attributes": attributes.iter().map(|a| a.name()).collect::<Vec<_>>(),
+                    "body": self.block(body),
+                });
+                let qualified = match contract {
+                    Some(contract) => format!("{}.{}", contract, name),
+                    None => name.clone(),
+                };
+                if let Some(sig) = self.signatures.and_then(|sigs| sigs.get(&qualified)) {
+                    node["param_types"] = json!(sig.params.iter().map(|t| t.to_string()).collect::<Vec<_>>());
+                    node["return_type"] = json!(sig.return_type.to_string());
+                }
+                node
+            }
+            StmtKind::Contract { name, state, state_types, constructor, methods } => json!({
+                "kind": "Contract",
+                "name": name,
+                "state": state,
+                "state_types": state_types,
+                "constructor": constructor.as_ref().map(|c| self.stmt(c, Some(name))),
+                "methods": methods.iter().map(|m| self.stmt(m, Some(name))).collect::<Vec<_>>(),
+            }),
+            StmtKind::Trait { name, methods } => json!({
+                "kind": "Trait",
+                "name": name,
+                "methods": methods.iter().map(|m| self.stmt(m, None)).collect::<Vec<_>>(),
+            }),
+            StmtKind::TypeAlias { name, target } => json!({ "kind": "TypeAlias", "name": name, "target": target }),
+            StmtKind::Impl { trait_name, target, methods } => json!({
+                "kind": "Impl",
+                "trait": trait_name,
+                "target": target,
+                "methods": methods.iter().map(|m| self.stmt(m, Some(target))).collect::<Vec<_>>(),
+            }),
+            StmtKind::Expression(expr) => json!({ "kind": "Expression", "expr": expr_json(expr) }),
+            StmtKind::Let { name, value, mutable } => {
+                json!({ "kind": "Let", "name": name, "mutable": mutable, "value": expr_json(value) })
+            }
+            StmtKind::LetTuple { names, value, mutable } => {
+                json!({ "kind": "LetTuple", "names": names, "mutable": mutable, "value": expr_json(value) })
+            }
+            StmtKind::Assign { name, value } => json!({ "kind": "Assign", "name": name, "value": expr_json(value) }),
+            StmtKind::If { condition, then_body, else_body } => json!({
+                "kind": "If",
+                "condition": expr_json(condition),
+                "then": self.block(then_body),
+                "else": else_body.as_ref().map(|body| self.block(body)),
+            }),
+            StmtKind::While { condition, body } => json!({
+                "kind": "While",
+                "condition": expr_json(condition),
+                "body": self.block(body),
+            }),
+            StmtKind::For { var, iterable, body } => json!({
+                "kind": "For",
+                "var": var,
+                "iterable": expr_json(iterable),
+                "body": self.block(body),
+            }),
+            StmtKind::Return(expr) => json!({ "kind": "Return", "value": expr_json(expr) }),
+            StmtKind::Value(expr) => json!({ "kind": "Value", "value": expr_json(expr) }),
+            StmtKind::Panic(expr) => json!({ "kind": "Panic", "message": expr_json(expr) }),
+            StmtKind::Defer(body) => json!({ "kind": "Defer", "body": self.block(body) }),
+            StmtKind::Match { value, arms } => json!({
+                "kind": "Match",
+                "value": expr_json(value),
+                "arms": arms
+                    .iter()
+                    .map(|arm| json!({ "pattern": arm.pattern.to_string(), "body": self.block(&arm.body) }))
+                    .collect::<Vec<_>>(),
+            }),
+        };
+
+        node["span"] = span_json(&stmt.span);
+        node
+    }
+
+    fn block(&self, body: &[Stmt]) -> Vec<Value> {
+        body.iter().map(|stmt| self.stmt(stmt, None)).collect()
+    }
+}
+
+fn span_json(span: &Span) -> Value {
+    json!({
+        "start": { "line": span.start_line, "column": span.start_column },
+        "end": { "line": span.end_line, "column": span.end_column },
+    })
+}
+
+fn expr_json(expr: &Expr) -> Value {
+    let binary = |op: &str, l: &Expr, r: &Expr| {
+        json!({ "kind": "Binary", "op": op, "left": expr_json(l), "right": expr_json(r) })
+    };
+
+    let mut node = match &expr.kind {
+        ExprKind::Number(n) => json!({ "kind": "Number", "value": n }),
+        ExprKind::Float(f) => json!({ "kind": "Float", "value": f }),
+        ExprKind::Bool(b) => json!({ "kind": "Bool", "value": b }),
+        ExprKind::String(s) => json!({ "kind": "String", "value": s }),
+        ExprKind::Identifier(name) => json!({ "kind": "Identifier", "name": name }),
+        ExprKind::Call(name, args) => json!({
+            "kind": "Call",
+            "callee": name,
+            "args": args.iter().map(expr_json).collect::<Vec<_>>(),
+        }),
+        ExprKind::ModuleCall(module, func, args) => json!({
+            "kind": "ModuleCall",
+            "module": module,
+            "callee": func,
+            "args": args.iter().map(expr_json).collect::<Vec<_>>(),
+        }),
+        ExprKind::Property(object, name) => json!({ "kind": "Property", "object": object, "name": name }),
+        ExprKind::Named(name, value) => json!({ "kind": "Named", "name": name, "value": expr_json(value) }),
+        ExprKind::Await(inner) => json!({ "kind": "Await", "expr": expr_json(inner) }),
+        ExprKind::Tuple(items) => json!({ "kind": "Tuple", "items": items.iter().map(expr_json).collect::<Vec<_>>() }),
+        ExprKind::Array(items) => json!({ "kind": "Array", "items": items.iter().map(expr_json).collect::<Vec<_>>() }),
+        ExprKind::Range(start, end) => json!({ "kind": "Range", "start": expr_json(start), "end": expr_json(end) }),
+        ExprKind::Slice(value, range) => json!({ "kind": "Slice", "value": expr_json(value), "range": expr_json(range) }),
+        ExprKind::Try(inner) => json!({ "kind": "Try", "expr": expr_json(inner) }),
+        ExprKind::Cast(inner, target) => json!({ "kind": "Cast", "expr": expr_json(inner), "type": target }),
+        ExprKind::Add(l, r) => binary("+", l, r),
+        ExprKind::Sub(l, r) => binary("-", l, r),
+        ExprKind::Mul(l, r) => binary("*", l, r),
+        ExprKind::Div(l, r) => binary("/", l, r),
+        ExprKind::Mod(l, r) => binary("%", l, r),
+        ExprKind::Eq(l, r) => binary("==", l, r),
+        ExprKind::Ne(l, r) => binary("!=", l, r),
+        ExprKind::Lt(l, r) => binary("<", l, r),
+        ExprKind::Le(l, r) => binary("<=", l, r),
+        ExprKind::Gt(l, r) => binary(">", l, r),
+        ExprKind::Ge(l, r) => binary(">=", l, r),
+        ExprKind::And(l, r) => binary("&&", l, r),
+        ExprKind::Or(l, r) => binary("||", l, r),
+    };
+
+    node["span"] = span_json(&expr.span);
+    node
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    use crate::typechecker::TypeChecker;
+
+    #[test]
+    fn test_spans_follow_pre_order() {
+        let source = "contract C {\n    fn get() {\n        return 1\n    }\n    constructor() {\n    }\n}\n";
+        let mut parser = Parser::new(Lexer::new(source));
+        let program = parser.parse().unwrap();
+        let json = program_to_json(&program, None);
+
+        let contract = &json["items"][0];
+        assert_eq!(contract["span"]["start"]["line"], 1);
+        assert_eq!(contract["constructor"]["span"]["start"]["line"], 5);
+        assert_eq!(contract["methods"][0]["span"]["start"]["line"], 2);
+        assert_eq!(contract["methods"][0]["body"][0]["span"]["start"]["line"], 3);
+        assert_eq!(contract["methods"][0]["body"][0]["span"]["start"]["column"], 9);
+    }
+
+    #[test]
+    fn test_typed_export_includes_signatures() {
+        let source = "fn answer() {\n    return 42\n}\n";
+        let mut parser = Parser::new(Lexer::new(source));
+        let program = parser.parse().unwrap();
+        let mut checker = TypeChecker::new();
+        checker.check(&program).unwrap();
+
+        let json = program_to_json(&program, Some(checker.functions()));
+        assert_eq!(json["typed"], true);
+        assert_eq!(json["items"][0]["return_type"], "Int");
+        assert_eq!(json["items"][0]["span"]["end"]["line"], 3);
+    }
+}
@@ -0,0 +1,192 @@
+use std::collections::BTreeMap;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use crate::ast::Stmt;
+use crate::ir::IRModule;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use crate::token::Token;
+
+// Compiler benchmarks, for `benches/compiler.rs` and `astrixa self-bench`.
+//
+// Each phase runs on its own, from the previous phase's output, over
+// generated corpora shaped like real programs: arithmetic and loops,
+// string handling, and a contract with many methods. Criterion gives the
+// detailed view; `self-bench` measures the same cases quickly enough for
+// a gate, comparing each against a stored baseline.
+
+/// The phases measured, in the order a build runs them
+pub const PHASES: &[&str] = &["lex", "parse", "typecheck", "lower", "optimize", "emit"];
+
+/// A case slower than its baseline by more than this fails the gate
+pub const REGRESSION_THRESHOLD: f64 = 0.10;
+
+/// A program to compile, with each phase's input ready
+pub struct Corpus {
+    pub name: &'static str,
+    pub source: String,
+    ast: Vec<Stmt>,
+    ir: IRModule,
+    optimized: IRModule,
+}
+
+impl Corpus {
+    fn new(name: &'static str, source: String) -> Self {
+        let ast = Parser::new(Lexer::new(&source)).parse().expect("benchmark corpora parse");
+        let ir = crate::lowering::lower(&ast);
+        let optimized = crate::opt::optimize_module(&ir);
+        Corpus { name, source, ast, ir, optimized }
+    }
+
+    /// Run `phase` once, returning the size of what it made
+    pub fn run(&self, phase: &str) -> usize {
+        match phase {
+            "lex" => {
+                let mut lexer = Lexer::new(&self.source);
+                let mut tokens = 0;
+                while lexer.next_token() != Token::EOF {
+                    tokens += 1;
+                }
+                tokens
+            }
+            "parse" => Parser::new(Lexer::new(&self.source)).parse().map_or(0, |ast| ast.len()),
+            "typecheck" => crate::typechecker::TypeChecker::new().check(&self.ast).map_or(0, |_| 1),
+            "lower" => crate::lowering::lower(&self.ast).functions.len(),
+            "optimize" => crate::opt::optimize_module(&self.ir).functions.len(),
+            "emit" => {
+                let wat = crate::codegen::wasm::generate_wasm_module(&self.optimized);
+                crate::codegen::binary::assemble(&wat).map_or(0, |bytes| bytes.len())
+            }
+            _ => panic!("unknown phase '{}'", phase),
+        }
+    }
+}
+
+/// The benchmark corpora, each of `functions` functions
+pub fn corpora(functions: usize) -> Vec<Corpus> {
+    let arithmetic: String = (0..functions)
+        .map(|i| {
+            format!(
+                "fn step_{i}(n: Int) {{\n    let mut total = n * {i} + 1\n    let mut k = 0\n    while k < n {{\n        if k % 3 == 0 {{\n            total = total + k\n        }} else {{\n            total = total - 1\n        }}\n        k = k + 1\n    }}\n    return total\n}}\n\n"
+            )
+        })
+        .collect();
+    let strings: String = (0..functions)
+        .map(|i| {
+            format!(
+                "fn label_{i}(name: String, count: Int) {{\n    let prefix = \"item {i} \" + name\n    if prefix[0..4] == \"item\" {{\n        print(prefix + \": \" + str(count))\n    }}\n    return prefix\n}}\n\n"
+            )
+        })
+        .collect();
+    let methods: String = (0..functions)
+        .map(|i| format!("    fn add_{i}(n: Int) {{\n        count = count + n * {i}\n        return count\n    }}\n"))
+        .collect();
+    let contract = format!(
+        "contract Counter {{\n    state count: Int\n    constructor() {{\n        count = 0\n    }}\n{}    #[view]\n    fn get() {{\n        return count\n    }}\n}}\n",
+        methods
+    );
+    vec![
+        Corpus::new("arithmetic", arithmetic),
+        Corpus::new("strings", strings),
+        Corpus::new("contract", contract),
+    ]
+}
+
+/// Each case's time, as "phase/corpus" to nanoseconds: the form baselines
+/// are stored in.
+///
+/// A case's time is the fastest of several samples, each repeating it for
+/// at least 20ms. The samples go round the cases in turn rather than one
+/// case at a time, so a stretch where the machine is busy slows one sample
+/// of many cases instead of every sample of a few.
+pub fn run_all(corpora: &[Corpus]) -> BTreeMap<String, u64> {
+    const SAMPLE: Duration = Duration::from_millis(20);
+    const ROUNDS: usize = 15;
+    let time = |corpus: &Corpus, phase: &str, iterations: u32| {
+        let start = Instant::now();
+        for _ in 0..iterations {
+            black_box(corpus.run(black_box(phase)));
+        }
+        start.elapsed()
+    };
+
+    // How many runs fill a sample; finding out doubles as the warm-up
+    let mut cases = Vec::new();
+    for phase in PHASES {
+        for corpus in corpora {
+            let mut iterations = 1;
+            while time(corpus, phase, iterations) < SAMPLE {
+                iterations *= 2;
+            }
+            cases.push((format!("{}/{}", phase, corpus.name), corpus, *phase, iterations, Duration::MAX));
+        }
+    }
+    for _ in 0..ROUNDS {
+        for (_, corpus, phase, iterations, fastest) in &mut cases {
+            *fastest = (*fastest).min(time(corpus, phase, *iterations) / *iterations);
+        }
+    }
+    cases.into_iter().map(|(case, _, _, _, fastest)| (case, fastest.as_nanos() as u64)).collect()
+}
+
+/// A case slower than its baseline beyond the threshold
+#[derive(Debug, Clone, PartialEq)]
+pub struct Regression {
+    pub case: String,
+    pub baseline: u64,
+    pub current: u64,
+}
+
+impl Regression {
+    /// How much slower, as a fraction of the baseline
+    pub fn slowdown(&self) -> f64 {
+        self.current as f64 / self.baseline as f64 - 1.0
+    }
+}
+
+/// The cases in both `baseline` and `current` that got slower by more than
+/// the threshold
+pub fn regressions(baseline: &BTreeMap<String, u64>, current: &BTreeMap<String, u64>) -> Vec<Regression> {
+    current
+        .iter()
+        .filter_map(|(case, &current)| {
+            let baseline = *baseline.get(case)?;
+            let regression = Regression { case: case.clone(), baseline, current };
+            (baseline > 0 && regression.slowdown() > REGRESSION_THRESHOLD).then_some(regression)
+        })
+        .collect()
+}
+
+pub fn baseline_to_json(results: &BTreeMap<String, u64>) -> String {
+    format!("{:#}\n", serde_json::json!(results))
+}
+
+pub fn baseline_from_json(json: &str) -> Result<BTreeMap<String, u64>, String> {
+    serde_json::from_str(json).map_err(|e| format!("not a benchmark baseline: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_corpora_compile_and_regressions_are_flagged() {
+        for corpus in corpora(3) {
+            crate::typechecker::TypeChecker::new().check(&corpus.ast).unwrap();
+            for phase in PHASES {
+                assert!(corpus.run(phase) > 0, "{} on {}", phase, corpus.name);
+            }
+        }
+
+        let baseline = baseline_from_json(r#"{"lex/strings": 1000, "parse/strings": 1000}"#).unwrap();
+        let current = BTreeMap::from([
+            ("lex/strings".to_string(), 1090),
+            ("parse/strings".to_string(), 1200),
+            ("emit/strings".to_string(), 5000), // New, nothing to compare
+        ]);
+        let found = regressions(&baseline, &current);
+        assert_eq!(found, [Regression { case: "parse/strings".to_string(), baseline: 1000, current: 1200 }]);
+        assert_eq!(baseline_from_json(&baseline_to_json(&current)).unwrap(), current);
+    }
+}
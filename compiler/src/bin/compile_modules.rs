@@ -11,7 +11,8 @@ use astrixa::parser::Parser;
 use astrixa::lowering::lower;
 use astrixa::opt::optimize_module;
 use astrixa::codegen::wasm::generate_wasm_module;
-use astrixa::diagnostics::display_error;
+use astrixa::typechecker::TypeChecker;
+use astrixa::diagnostics::{display_error, display_errors};
 use astrixa::ast;
 
 fn main() {
@@ -76,12 +77,22 @@ fn compile_with_modules(input_file: &str, output_file: &str) -> Result<(), Strin
     
     println!("✅ Main module parsed ({} top-level items)", main_ast.len());
     
+    // Catch missing selected functions and name collisions up front
+    loader.check_imports(&main_ast)
+        .map_err(|e| {
+            display_error(e);
+            "Import error".to_string()
+        })?;
+    
     // Extract imports and load imported modules
     let mut all_modules = vec![main_ast.clone()];
     let mut imported_modules = Vec::new();
     
     for stmt in &main_ast {
-        if let ast::Stmt::Import(module_name) = stmt {
+        if let ast::StmtKind::Import { module: module_name, .. } = &stmt.kind {
+            if module_name == astrixa::prelude::MODULE {
+                continue; // Built in, with no file to load
+            }
             println!("📦 Loading module: {}", module_name);
             
             let module = loader.load_module(module_name)
@@ -107,13 +118,24 @@ fn compile_with_modules(input_file: &str, output_file: &str) -> Result<(), Strin
     let mut combined_ast = Vec::new();
     for module_stmts in all_modules {
         for stmt in module_stmts {
-            // Skip import statements (already processed)
-            if !matches!(stmt, ast::Stmt::Import(_)) {
+            // Skip import statements (already processed), but for the
+            // prelude's, which the type checker reads
+            let processed = matches!(&stmt.kind, ast::StmtKind::Import { module, .. } if module != astrixa::prelude::MODULE);
+            if !processed {
                 combined_ast.push(stmt);
             }
         }
     }
     
+    println!();
+    println!("🔎 Type checking...");
+    TypeChecker::new().check(&combined_ast)
+        .map_err(|errors| {
+            display_errors(&errors);
+            "Type check failed".to_string()
+        })?;
+    println!("  ✅ Type check passed");
+    
     println!();
     println!("🔄 Lowering to IR...");
     let ir_module = lower(&combined_ast);
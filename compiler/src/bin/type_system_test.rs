@@ -57,7 +57,7 @@ fn test_type_mismatch() {
     println!("Test 3: Type mismatch detection");
     let code = r#"
         fn test {
-            let x = 10
+            let mut x = 10
             x = "hello"
         }
     "#;
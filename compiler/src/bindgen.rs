@@ -0,0 +1,334 @@
+use serde_json::Value;
+
+// Typed frontend bindings from contract ABIs.
+//
+// `astrixa bindgen --target=ts` turns each contract ABI written by
+// `astrixa build` into a TypeScript module: the ABI as a const (so viem and
+// ethers can infer types from it), a typed wrapper per method, and a type
+// per event. Regenerating after every build keeps dapp code in lockstep with
+// the contract: a renamed method or changed parameter becomes a TypeScript
+// compile error instead of a failed transaction.
+
+/// The web3 library the generated wrappers call
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TsClient {
+    Viem,
+    Ethers,
+}
+
+impl TsClient {
+    pub const ALL: &'static [TsClient] = &[TsClient::Viem, TsClient::Ethers];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TsClient::Viem => "viem",
+            TsClient::Ethers => "ethers",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<TsClient> {
+        Self::ALL.iter().copied().find(|c| c.name() == name)
+    }
+}
+
+/// One ABI function, with TypeScript types already resolved
+struct Method<'a> {
+    name: &'a str,
+    params: Vec<(&'a str, String)>,
+    outputs: Vec<String>,
+    view: bool,
+    payable: bool,
+}
+
+/// A TypeScript module for the contract `contract` with ABI `abi`
+pub fn typescript(contract: &str, abi: &Value, client: TsClient) -> Result<String, String> {
+    let entries = abi
+        .as_array()
+        .ok_or_else(|| format!("The {} ABI is not a JSON array", contract))?;
+    let abi_name = format!("{}Abi", lower_first(contract));
+
+    let mut methods = Vec::new();
+    let mut events = Vec::new();
+    for entry in entries {
+        let name = entry["name"].as_str().unwrap_or_default();
+        match entry["type"].as_str() {
+            Some("function") => {
+                let mutability = entry["stateMutability"].as_str().unwrap_or("nonpayable");
+                methods.push(Method {
+                    name,
+                    params: params(&entry["inputs"], client)?,
+                    outputs: params(&entry["outputs"], client)?.into_iter().map(|(_, ty)| ty).collect(),
+                    view: matches!(mutability, "view" | "pure"),
+                    payable: mutability == "payable",
+                });
+            }
+            Some("event") => events.push((name, params(&entry["inputs"], client)?)),
+            _ => {}
+        }
+    }
+
+    let mut out = format!(
+        "// Generated by `astrixa bindgen --target=ts --client={}` from the {} ABI. Do not edit.\n\n",
+        client.name(),
+        contract
+    );
+    out.push_str(&imports(client, &methods, &events));
+
+    out.push_str(&format!("\nexport const {} = {:#} as const;\n", abi_name, abi));
+
+    out.push_str(&event_types(contract, &events));
+    out.push_str(&match client {
+        TsClient::Viem => viem_wrapper(contract, &abi_name, &methods),
+        TsClient::Ethers => ethers_wrapper(contract, &abi_name, &methods),
+    });
+    Ok(out)
+}
+
+/// The import line, naming only what the module uses so it also compiles
+/// under `noUnusedLocals`
+fn imports(client: TsClient, methods: &[Method], events: &[(&str, Vec<(&str, String)>)]) -> String {
+    let writes = methods.iter().any(|m| !m.view);
+    match client {
+        TsClient::Viem => {
+            let uses_hex = methods
+                .iter()
+                .flat_map(|m| m.params.iter().map(|(_, ty)| ty).chain(&m.outputs))
+                .chain(events.iter().flat_map(|(_, fields)| fields.iter().map(|(_, ty)| ty)))
+                .any(|ty| ty == "Hex");
+            let mut names = vec!["Account", "Address", "Chain"];
+            if writes {
+                names.push("Hash");
+            }
+            if uses_hex {
+                names.push("Hex");
+            }
+            names.extend(["PublicClient", "Transport", "WalletClient"]);
+            format!("import type {{ {} }} from \"viem\";\n", names.join(", "))
+        }
+        TsClient::Ethers => {
+            let mut names = vec!["Contract", "type BaseContract", "type ContractRunner"];
+            if writes {
+                names.extend(["type ContractTransactionResponse", "type Overrides"]);
+            }
+            format!("import {{ {} }} from \"ethers\";\n", names.join(", "))
+        }
+    }
+}
+
+/// `(name, TypeScript type)` for each ABI parameter
+fn params(list: &Value, client: TsClient) -> Result<Vec<(&str, String)>, String> {
+    let Some(list) = list.as_array() else { return Ok(Vec::new()) };
+    list.iter()
+        .map(|param| {
+            let abi_type = param["type"].as_str().unwrap_or_default();
+            let ts_type = ts_type(abi_type, client)
+                .ok_or_else(|| format!("ABI type '{}' is not supported by bindgen yet", abi_type))?;
+            Ok((param["name"].as_str().unwrap_or_default(), ts_type.to_string()))
+        })
+        .collect()
+}
+
+fn ts_type(abi_type: &str, client: TsClient) -> Option<&'static str> {
+    let hex = match client {
+        TsClient::Viem => "Hex",
+        TsClient::Ethers => "string",
+    };
+    // `uint256`, `bytes32`, ...: a base name and an optional size
+    let sized = |base: &str| {
+        abi_type
+            .strip_prefix(base)
+            .is_some_and(|size| size.chars().all(|c| c.is_ascii_digit()))
+    };
+    match abi_type {
+        _ if sized("int") || sized("uint") => Some("bigint"),
+        "address" if client == TsClient::Viem => Some("Address"),
+        "address" | "string" => Some("string"),
+        "bool" => Some("boolean"),
+        _ if sized("bytes") => Some(hex),
+        _ => None,
+    }
+}
+
+fn event_types(contract: &str, events: &[(&str, Vec<(&str, String)>)]) -> String {
+    let mut out = String::new();
+    let mut variants = Vec::new();
+    for (name, fields) in events {
+        let type_name = format!("{}{}Event", contract, name);
+        out.push_str(&format!("\nexport interface {} {{\n", type_name));
+        for (field, ty) in fields {
+            out.push_str(&format!("  {}: {};\n", field, ty));
+        }
+        out.push_str("}\n");
+        variants.push(format!("{{ eventName: \"{}\"; args: {} }}", name, type_name));
+    }
+
+    let union = if variants.is_empty() { "never".to_string() } else { variants.join("\n  | ") };
+    out.push_str(&format!("\n/** Every event the contract emits, by name */\nexport type {}Event =\n  | {};\n", contract, union));
+    out
+}
+
+fn signature(method: &Method) -> String {
+    let params: Vec<String> = method.params.iter().map(|(name, ty)| format!("{}: {}", name, ty)).collect();
+    params.join(", ")
+}
+
+fn arg_list(method: &Method) -> String {
+    let names: Vec<&str> = method.params.iter().map(|(name, _)| *name).collect();
+    names.join(", ")
+}
+
+/// What a read returns: nothing, one value, or a tuple
+fn read_type(outputs: &[String], readonly: bool) -> String {
+    match outputs {
+        [] => "void".to_string(),
+        [single] => single.clone(),
+        many => format!("{}[{}]", if readonly { "readonly " } else { "" }, many.join(", ")),
+    }
+}
+
+fn viem_wrapper(contract: &str, abi_name: &str, methods: &[Method]) -> String {
+    let mut out = format!(
+        "\n/** Typed calls to a deployed {0}. Writes need `walletClient`. */\nexport function get{0}(\n  address: Address,\n  publicClient: PublicClient,\n  walletClient?: WalletClient<Transport, Chain, Account>,\n) {{\n",
+        contract
+    );
+    if methods.iter().any(|m| !m.view) {
+        out.push_str("  const wallet = (method: string) => {\n");
+        out.push_str(&format!(
+            "    if (!walletClient) throw new Error(`{}.${{method}} sends a transaction and needs a wallet client`);\n",
+            contract
+        ));
+        out.push_str("    return walletClient;\n  };\n\n");
+    }
+    out.push_str(&format!("  return {{\n    address,\n    abi: {},\n", abi_name));
+
+    for method in methods {
+        let args = if method.params.is_empty() { String::new() } else { format!(", args: [{}]", arg_list(method)) };
+        let call = format!("address, abi: {}, functionName: \"{}\"{}", abi_name, method.name, args);
+        if method.view {
+            out.push_str(&format!(
+                "    {}({}): Promise<{}> {{\n      return publicClient.readContract({{ {} }});\n    }},\n",
+                method.name,
+                signature(method),
+                read_type(&method.outputs, true),
+                call
+            ));
+        } else {
+            let mut params = signature(method);
+            let mut call = call;
+            if method.payable {
+                if !params.is_empty() {
+                    params.push_str(", ");
+                }
+                params.push_str("value?: bigint");
+                call.push_str(", value");
+            }
+            out.push_str(&format!(
+                "    {}({}): Promise<Hash> {{\n      return wallet(\"{}\").writeContract({{ {} }});\n    }},\n",
+                method.name, params, method.name, call
+            ));
+        }
+    }
+
+    out.push_str("  };\n}\n");
+    out
+}
+
+fn ethers_wrapper(contract: &str, abi_name: &str, methods: &[Method]) -> String {
+    let mut out = format!("\n/** A deployed {} with typed methods */\nexport interface {} extends BaseContract {{\n", contract, contract);
+    for method in methods {
+        if method.view {
+            out.push_str(&format!(
+                "  {}({}): Promise<{}>;\n",
+                method.name,
+                signature(method),
+                read_type(&method.outputs, false)
+            ));
+        } else {
+            let mut params = signature(method);
+            if !params.is_empty() {
+                params.push_str(", ");
+            }
+            params.push_str("overrides?: Overrides");
+            out.push_str(&format!("  {}({}): Promise<ContractTransactionResponse>;\n", method.name, params));
+        }
+    }
+    out.push_str("}\n");
+
+    out.push_str(&format!(
+        "\nexport function connect{0}(address: string, runner?: ContractRunner | null): {0} {{\n  return new Contract(address, {1}, runner) as unknown as {0};\n}}\n",
+        contract, abi_name
+    ));
+    out
+}
+
+fn lower_first(name: &str) -> String {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_lowercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn vault_abi() -> Value {
+        json!([
+            { "type": "constructor", "inputs": [{ "name": "admin", "type": "address" }], "stateMutability": "nonpayable" },
+            { "type": "function", "name": "deposit", "inputs": [], "outputs": [], "stateMutability": "payable" },
+            {
+                "type": "function",
+                "name": "transfer",
+                "inputs": [{ "name": "to", "type": "address" }, { "name": "amount", "type": "uint256" }],
+                "outputs": [{ "name": "", "type": "bool" }],
+                "stateMutability": "nonpayable"
+            },
+            {
+                "type": "function",
+                "name": "info",
+                "inputs": [],
+                "outputs": [{ "name": "", "type": "string" }, { "name": "", "type": "int64" }],
+                "stateMutability": "view"
+            },
+            {
+                "type": "event",
+                "name": "Transfer",
+                "inputs": [{ "name": "to", "type": "address" }, { "name": "amount", "type": "uint256" }]
+            }
+        ])
+    }
+
+    #[test]
+    fn test_viem_bindings() {
+        let ts = typescript("Vault", &vault_abi(), TsClient::Viem).unwrap();
+
+        assert!(ts.contains("import type { Account, Address, Chain, Hash, PublicClient, Transport, WalletClient } from \"viem\";\n"));
+        assert!(ts.contains("export const vaultAbi = [\n"));
+        assert!(ts.contains("] as const;\n"));
+        assert!(ts.contains("export function getVault(\n  address: Address,"));
+        assert!(ts.contains(
+            "    info(): Promise<readonly [string, bigint]> {\n      return publicClient.readContract({ address, abi: vaultAbi, functionName: \"info\" });\n    },\n"
+        ));
+        assert!(ts.contains(
+            "    transfer(to: Address, amount: bigint): Promise<Hash> {\n      return wallet(\"transfer\").writeContract({ address, abi: vaultAbi, functionName: \"transfer\", args: [to, amount] });\n    },\n"
+        ));
+        assert!(ts.contains("    deposit(value?: bigint): Promise<Hash> {\n"));
+        assert!(ts.contains("export interface VaultTransferEvent {\n  to: Address;\n  amount: bigint;\n}\n"));
+        assert!(ts.contains("export type VaultEvent =\n  | { eventName: \"Transfer\"; args: VaultTransferEvent };\n"));
+    }
+
+    #[test]
+    fn test_ethers_bindings() {
+        let ts = typescript("Vault", &vault_abi(), TsClient::Ethers).unwrap();
+
+        assert!(ts.contains("export interface Vault extends BaseContract {\n"));
+        assert!(ts.contains("  transfer(to: string, amount: bigint, overrides?: Overrides): Promise<ContractTransactionResponse>;\n"));
+        assert!(ts.contains("  info(): Promise<[string, bigint]>;\n"));
+        assert!(ts.contains("export function connectVault(address: string, runner?: ContractRunner | null): Vault {\n"));
+
+        let unsupported = json!([{ "type": "function", "name": "f", "inputs": [{ "name": "xs", "type": "uint256[]" }], "outputs": [] }]);
+        assert!(typescript("Vault", &unsupported, TsClient::Ethers).unwrap_err().contains("uint256[]"));
+    }
+}
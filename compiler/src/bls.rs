@@ -0,0 +1,158 @@
+use crate::interpreter::Value;
+use crate::messages;
+
+// BLS12-381 signatures, the `bls` module of scripts, as Ethereum's consensus
+// layer and other proof-of-stake chains use them.
+//
+//   bls.verify(public_key, message, signature)
+//   bls.aggregate_verify([key_a, key_b, key_c], block_root, aggregate)
+//
+// Public keys are 48-byte compressed G1 points and signatures 96-byte
+// compressed G2 points, both hex; messages are hashed to G2 with the
+// proof-of-possession ciphersuite (BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_POP_).
+// `aggregate_verify` checks one signature aggregated from every key signing
+// the same message, so the keys' possession proofs must have been checked
+// when they were registered. The keys may also be passed as one hex string
+// of them back to back, the only form WASM builds can pass. A malformed
+// key or signature verifies nothing rather than failing.
+//
+// Verification needs the `bls` cargo feature; WASM builds import both
+// functions from the host instead.
+
+/// Call `bls.<function>` with `args`
+pub fn call(function: &str, args: &[Value]) -> Result<Value, String> {
+    let usage = |expected: &str| messages::render("E0472", &[&function, &expected]);
+    match (function, args) {
+        ("verify", [Value::String(key), Value::String(message), Value::String(signature)]) => {
+            imp::verify(std::slice::from_ref(key), message, signature).map(Value::Bool)
+        }
+        ("aggregate_verify", [keys, Value::String(message), Value::String(signature)]) => {
+            let keys = match keys {
+                Value::Array(keys) => keys
+                    .borrow()
+                    .iter()
+                    .map(|key| match key {
+                        Value::String(key) => Ok(key.clone()),
+                        _ => Err(usage("public keys that are hex strings")),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?,
+                Value::String(keys) => split_keys(keys).ok_or_else(|| usage("48-byte public keys back to back"))?,
+                _ => return Err(usage("(public_keys, message, signature)")),
+            };
+            imp::verify(&keys, message, signature).map(Value::Bool)
+        }
+        ("verify", _) => Err(usage("(public_key, message, signature)")),
+        ("aggregate_verify", _) => Err(usage("(public_keys, message, signature)")),
+        _ => Err(messages::render("E0406", &[&format!("bls.{}", function)])),
+    }
+}
+
+/// Public keys written back to back, 96 hex digits each
+fn split_keys(keys: &str) -> Option<Vec<String>> {
+    let digits = keys.strip_prefix("0x").unwrap_or(keys);
+    if digits.is_empty() || !digits.len().is_multiple_of(96) || !digits.is_ascii() {
+        return None;
+    }
+    Some((0..digits.len()).step_by(96).map(|i| format!("0x{}", &digits[i..i + 96])).collect())
+}
+
+#[cfg(feature = "bls")]
+mod imp {
+    use bls12_381::hash_to_curve::{ExpandMsgXmd, HashToCurve};
+    use bls12_381::{pairing, G1Affine, G1Projective, G2Affine, G2Projective};
+    use crate::logs::{message_bytes, unhex};
+
+    const DST: &[u8] = b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_POP_";
+
+    /// `text` as exactly `N` bytes of hex
+    fn bytes<const N: usize>(text: &str) -> Option<[u8; N]> {
+        unhex(text).ok().and_then(|bytes| bytes.try_into().ok())
+    }
+
+    /// A public key, refused if it is not a valid point or is the identity
+    fn public_key(text: &str) -> Option<G1Affine> {
+        let point = Option::<G1Affine>::from(G1Affine::from_compressed(&bytes::<48>(text)?))?;
+        (!bool::from(point.is_identity())).then_some(point)
+    }
+
+    /// Whether `signature` aggregates every key's signature of `message`
+    pub fn verify(keys: &[String], message: &str, signature: &str) -> Result<bool, String> {
+        let keys: Option<Vec<G1Affine>> = keys.iter().map(|key| public_key(key)).collect();
+        let signature = bytes::<96>(signature).and_then(|bytes| Option::<G2Affine>::from(G2Affine::from_compressed(&bytes)));
+        let (Some(keys), Some(signature)) = (keys, signature) else {
+            return Ok(false);
+        };
+        if keys.is_empty() {
+            return Ok(false);
+        }
+        let aggregate = G1Affine::from(keys.iter().fold(G1Projective::identity(), |sum, key| sum + key));
+        let hashed = <G2Projective as HashToCurve<ExpandMsgXmd<sha2_09::Sha256>>>::hash_to_curve(message_bytes(message), DST);
+        Ok(pairing(&aggregate, &G2Affine::from(hashed)) == pairing(&G1Affine::generator(), &signature))
+    }
+
+    #[cfg(test)]
+    pub(super) fn sign(secret: u64, message: &str) -> (String, String) {
+        use bls12_381::Scalar;
+        let secret = Scalar::from(secret);
+        let key = G1Affine::from(G1Affine::generator() * secret);
+        let hashed = <G2Projective as HashToCurve<ExpandMsgXmd<sha2_09::Sha256>>>::hash_to_curve(message_bytes(message), DST);
+        let signature = G2Affine::from(hashed * secret);
+        (format!("0x{}", crate::logs::hex(&key.to_compressed())), format!("0x{}", crate::logs::hex(&signature.to_compressed())))
+    }
+}
+
+#[cfg(not(feature = "bls"))]
+mod imp {
+    use crate::messages;
+
+    pub fn verify(keys: &[String], _message: &str, _signature: &str) -> Result<bool, String> {
+        let function = if keys.len() == 1 { "bls.verify" } else { "bls.aggregate_verify" };
+        Err(messages::render("E0473", &[&function, &"bls"]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "bls")]
+    fn string(text: &str) -> Value {
+        Value::String(text.to_string())
+    }
+
+    #[cfg(feature = "bls")]
+    #[test]
+    fn test_aggregate_signatures_verify_against_every_key() {
+        use bls12_381::G2Affine;
+        let signers: Vec<(String, String)> = (1..=3).map(|secret| imp::sign(secret * 7919, "0xbeef")).collect();
+        let (key, signature) = &signers[0];
+        assert_eq!(call("verify", &[string(key), string("0xbeef"), string(signature)]), Ok(Value::Bool(true)));
+        assert_eq!(call("verify", &[string(key), string("0xbeee"), string(signature)]), Ok(Value::Bool(false)));
+
+        // The aggregate of the three signatures is the sum of their points
+        let points: Vec<G2Affine> = signers
+            .iter()
+            .map(|(_, signature)| {
+                let bytes: [u8; 96] = crate::logs::unhex(signature).unwrap().try_into().unwrap();
+                G2Affine::from_compressed(&bytes).unwrap()
+            })
+            .collect();
+        let aggregate = G2Affine::from(points.iter().fold(bls12_381::G2Projective::identity(), |sum, point| sum + point));
+        let aggregate = string(&format!("0x{}", crate::logs::hex(&aggregate.to_compressed())));
+        let keys: Vec<Value> = signers.iter().map(|(key, _)| string(key)).collect();
+        assert_eq!(call("aggregate_verify", &[Value::array(keys.clone()), string("0xbeef"), aggregate.clone()]), Ok(Value::Bool(true)));
+        assert_eq!(call("aggregate_verify", &[Value::array(keys[..2].to_vec()), string("0xbeef"), aggregate.clone()]), Ok(Value::Bool(false)));
+
+        // The same keys back to back, as WASM builds pass them
+        let packed: String = signers.iter().map(|(key, _)| key.trim_start_matches("0x")).collect();
+        assert_eq!(call("aggregate_verify", &[string(&packed), string("0xbeef"), aggregate]), Ok(Value::Bool(true)));
+        assert_eq!(call("verify", &[string("0x12"), string("0xbeef"), string(signature)]), Ok(Value::Bool(false)));
+    }
+
+    #[test]
+    fn test_packed_keys_split_every_48_bytes() {
+        let keys = format!("0x{}{}", "ab".repeat(48), "cd".repeat(48));
+        assert_eq!(split_keys(&keys), Some(vec![format!("0x{}", "ab".repeat(48)), format!("0x{}", "cd".repeat(48))]));
+        assert_eq!(split_keys("0xabcd"), None);
+    }
+}
@@ -0,0 +1,183 @@
+use std::sync::OnceLock;
+use crate::ast::{Span, Stmt, StmtKind};
+use crate::ir::{IRInstr, IRModule};
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use crate::stdlib::{StdlibCategory, StdlibFunction};
+use crate::types::Type;
+
+// The part of the standard library written in ASTRIXA.
+//
+// Helpers that need nothing from the host, such as integer math and string
+// padding, are ordinary functions in the .ax files under `std/`, built
+// into the compiler. Each is typed by its annotations and described by the
+// comment just above it, so adding one takes no Rust: the registry lists
+// it with the host functions (see `stdlib`), and the typechecker, the
+// prelude and completion take it from there. The interpreter declares
+// them before a program's own functions, and lowering adds the ones a
+// module calls, and those they call in turn, to its IR, so they build
+// for every backend. A function a program declares under the same name
+// replaces them.
+//
+// Arrays have no annotation yet, so the array helpers leave their array
+// parameters unannotated; like arrays anywhere else, they only run in the
+// interpreter until compiled builds can hold them.
+
+/// Each file under `std/`, by the module its functions are filed under
+const SOURCES: &[(&str, &str)] = &[
+    ("array", include_str!("../std/array.ax")),
+    ("math", include_str!("../std/math.ax")),
+    ("string", include_str!("../std/string.ax")),
+];
+
+/// A standard library function written in ASTRIXA
+#[derive(Debug)]
+pub struct Bootstrapped {
+    pub module: &'static str,
+    pub description: String,
+    pub declaration: Stmt,
+}
+
+impl Bootstrapped {
+    pub fn name(&self) -> &str {
+        match &self.declaration.kind {
+            StmtKind::Function { name, .. } => name,
+            _ => unreachable!("only functions are kept"),
+        }
+    }
+
+    /// Its registry entry, with the types its annotations name
+    pub(crate) fn signature(&'static self) -> StdlibFunction {
+        let StmtKind::Function { name, param_types, return_annotation, .. } = &self.declaration.kind else {
+            unreachable!("only functions are kept");
+        };
+        let annotated = |annotation: &Option<String>| annotation.as_deref().and_then(Type::from_annotation).unwrap_or(Type::Unknown);
+        StdlibFunction {
+            name,
+            category: match self.module {
+                "array" => StdlibCategory::Array,
+                "math" => StdlibCategory::Math,
+                _ => StdlibCategory::String,
+            },
+            description: &self.description,
+            params: param_types.iter().map(annotated).collect(),
+            returns: annotated(return_annotation),
+        }
+    }
+}
+
+/// Every function under `std/`
+pub fn functions() -> &'static [Bootstrapped] {
+    static FUNCTIONS: OnceLock<Vec<Bootstrapped>> = OnceLock::new();
+    FUNCTIONS.get_or_init(|| SOURCES.iter().flat_map(|(module, source)| parse(module, source)).collect())
+}
+
+/// Whether the standard library function `name` is written in ASTRIXA
+pub fn defines(name: &str) -> bool {
+    functions().iter().any(|f| f.name() == name)
+}
+
+/// Add to `module` the functions under `std/` it calls without declaring
+/// them, and those they call in turn
+pub fn link(module: &mut IRModule) {
+    let mut lowered: Option<IRModule> = None;
+    loop {
+        let mut missing: Vec<String> = module
+            .functions
+            .iter()
+            .flat_map(|function| &function.instructions)
+            .filter_map(|instr| match instr {
+                IRInstr::Call(name, _) | IRInstr::LoadFunction(name) => Some(name),
+                _ => None,
+            })
+            .filter(|name| defines(name) && module.find_function(name).is_none())
+            .cloned()
+            .collect();
+        if missing.is_empty() {
+            return;
+        }
+        missing.sort();
+        missing.dedup();
+
+        let lowered = lowered.get_or_insert_with(|| {
+            let declarations: Vec<Stmt> = functions().iter().map(|f| f.declaration.clone()).collect();
+            crate::lowering::lower_unlinked(&declarations, false, false)
+        });
+        for name in missing {
+            let mut function = lowered.find_function(&name).expect("every std function lowers").clone();
+            // Its spans are in std/, not the program's source
+            function.span = Span::default();
+            for unsupported in &mut function.unsupported {
+                unsupported.span = Span::default();
+            }
+            function.arithmetic.fill(Span::default());
+            module.add_function(function);
+        }
+    }
+}
+
+fn parse(module: &'static str, source: &str) -> Vec<Bootstrapped> {
+    let program = Parser::new(Lexer::new(source))
+        .parse()
+        .unwrap_or_else(|e| panic!("std/{}.ax does not parse: {}", module, e));
+    let descriptions = descriptions(source);
+    program
+        .into_iter()
+        .filter_map(|declaration| {
+            let StmtKind::Function { name, .. } = &declaration.kind else {
+                return None;
+            };
+            let description = descriptions.iter().find(|(n, _)| n == name).map(|(_, d)| d.clone()).unwrap_or_default();
+            Some(Bootstrapped { module, description, declaration })
+        })
+        .collect()
+}
+
+/// Each function's name and the comment lines just above its `fn`, joined
+fn descriptions(source: &str) -> Vec<(String, String)> {
+    let mut comment = Vec::new();
+    let mut described = Vec::new();
+    for line in source.lines().map(str::trim) {
+        if let Some(text) = line.strip_prefix("//") {
+            comment.push(text.trim());
+            continue;
+        }
+        if let Some(rest) = line.strip_prefix("fn ") {
+            let name = rest.split(['(', '<']).next().unwrap_or_default().trim();
+            described.push((name.to_string(), comment.join(" ")));
+        }
+        comment.clear();
+    }
+    described
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::typechecker::TypeChecker;
+
+    #[test]
+    fn test_std_functions_check_and_link_into_programs() {
+        // The compiler checks its own standard library
+        let declarations: Vec<Stmt> = functions().iter().map(|f| f.declaration.clone()).collect();
+        assert!(TypeChecker::new().check(&declarations).is_ok());
+        for function in functions() {
+            let signature = crate::stdlib::stdlib_functions().iter().find(|f| f.name == function.name()).unwrap();
+            assert!(!signature.description.is_empty(), "{} has no comment", function.name());
+            // Array parameters are the only ones left unannotated
+            let annotated = function.module == "array" || !signature.params.contains(&Type::Unknown);
+            assert!(annotated && signature.returns != Type::Unknown, "{} is not annotated", function.name());
+        }
+        assert_eq!(crate::stdlib::resolve_path("std::math::min").unwrap().description, "Minimum of two numbers");
+        assert!(crate::prelude::get("pad_start").is_some());
+
+        // Only what is called; a program's own `max` wins
+        let source = "fn max(a, b) {\n    return 99\n}\nfn main() {\n    return min(pow(2, 3), max(1, 2)) + len(repeat(\"ab\", 2))\n}\n";
+        let program = Parser::new(Lexer::new(source)).parse().unwrap();
+        let module = crate::lowering::lower(&program);
+        let mut names: Vec<&str> = module.functions.iter().map(|f| f.name.as_str()).collect();
+        names.sort();
+        assert_eq!(names, vec!["main", "max", "min", "pow", "repeat"]);
+        assert_eq!(module.find_function("pow").unwrap().span, Span::default());
+    }
+}
@@ -0,0 +1,588 @@
+use std::fmt;
+use serde::de::{self, DeserializeOwned, IntoDeserializer};
+use serde::ser::{self, Serialize};
+use crate::interpreter::{unshare, Value};
+use crate::u256::U256;
+use crate::messages;
+
+// Rust <-> ASTRIXA value bridge.
+//
+// Embedders pass Rust data into script functions and read results back
+// through serde instead of building `Value`s by hand. The language has no
+// records, so a struct travels as a tuple of its fields in declaration
+// order, which scripts destructure with `let (name, age) = user`:
+//
+//   Rust                          ASTRIXA
+//   bool, integers, floats        Bool, Number (U256 past i64), Float
+//   char, String                  String
+//   Vec, slices, sets             Array
+//   tuples, structs               Tuple
+//   maps                          Array of (key, value) tuples
+//   None, ()                      Null; Some(v) is just v
+//   enum variant `A`              String "A"
+//   enum variant `A(x)`, `A{..}`  Tuple ("A", payload)
+//
+// Reading back is the reverse, and also accepts an Address or Range where
+// a string or pair of numbers is expected, a Map (such as a decoded event
+// log) where a map or struct is expected, and a script Option or Result
+// where a Rust Option or Result is expected.
+
+impl Value {
+    /// Convert any serializable Rust value into a script value
+    pub fn from_serde<T: Serialize + ?Sized>(value: &T) -> Result<Value, String> {
+        value.serialize(ValueSerializer).map_err(|e| messages::render("E0438", &[&e]))
+    }
+
+    /// Convert a script value into a Rust value
+    pub fn to_serde<T: DeserializeOwned>(&self) -> Result<T, String> {
+        T::deserialize(self.clone()).map_err(|e| messages::render("E0438", &[&e]))
+    }
+}
+
+#[derive(Debug)]
+pub struct BridgeError(String);
+
+impl fmt::Display for BridgeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for BridgeError {}
+
+impl ser::Error for BridgeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        BridgeError(msg.to_string())
+    }
+}
+
+impl de::Error for BridgeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        BridgeError(msg.to_string())
+    }
+}
+
+struct ValueSerializer;
+
+/// Elements of a sequence, tuple, map or struct as they are serialized.
+/// `variant` wraps the result as `(variant, items)` for enum variants.
+struct Items {
+    items: Vec<Value>,
+    pending_key: Option<Value>,
+    kind: ItemsKind,
+    variant: Option<&'static str>,
+}
+
+enum ItemsKind {
+    Array,
+    Tuple,
+}
+
+impl Items {
+    fn new(kind: ItemsKind, variant: Option<&'static str>, len: Option<usize>) -> Self {
+        Items { items: Vec::with_capacity(len.unwrap_or(0)), pending_key: None, kind, variant }
+    }
+
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), BridgeError> {
+        self.items.push(value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Value, BridgeError> {
+        let value = match self.kind {
+            ItemsKind::Array => Value::array(self.items),
+            ItemsKind::Tuple => Value::Tuple(self.items),
+        };
+        Ok(match self.variant {
+            Some(variant) => Value::Tuple(vec![Value::String(variant.to_string()), value]),
+            None => value,
+        })
+    }
+}
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = Value;
+    type Error = BridgeError;
+    type SerializeSeq = Items;
+    type SerializeTuple = Items;
+    type SerializeTupleStruct = Items;
+    type SerializeTupleVariant = Items;
+    type SerializeMap = Items;
+    type SerializeStruct = Items;
+    type SerializeStructVariant = Items;
+
+    fn serialize_bool(self, v: bool) -> Result<Value, BridgeError> {
+        Ok(Value::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Value, BridgeError> {
+        Ok(Value::Number(v.into()))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Value, BridgeError> {
+        Ok(Value::Number(v.into()))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Value, BridgeError> {
+        Ok(Value::Number(v.into()))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Value, BridgeError> {
+        Ok(Value::Number(v))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Value, BridgeError> {
+        match i64::try_from(v) {
+            Ok(n) => Ok(Value::Number(n)),
+            Err(_) => u128::try_from(v)
+                .map(|v| Value::U256(U256::from(v)))
+                .map_err(|_| BridgeError(format!("{} is out of range for a number", v))),
+        }
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Value, BridgeError> {
+        Ok(Value::Number(v.into()))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Value, BridgeError> {
+        Ok(Value::Number(v.into()))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Value, BridgeError> {
+        Ok(Value::Number(v.into()))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Value, BridgeError> {
+        self.serialize_u128(v.into())
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Value, BridgeError> {
+        Ok(i64::try_from(v).map_or(Value::U256(U256::from(v)), Value::Number))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Value, BridgeError> {
+        Ok(Value::Float(v.into()))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Value, BridgeError> {
+        Ok(Value::Float(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Value, BridgeError> {
+        Ok(Value::String(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Value, BridgeError> {
+        Ok(Value::String(v.to_string()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Value, BridgeError> {
+        Ok(Value::array(v.iter().map(|b| Value::Number((*b).into())).collect()))
+    }
+
+    fn serialize_none(self) -> Result<Value, BridgeError> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Value, BridgeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Value, BridgeError> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value, BridgeError> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, _index: u32, variant: &'static str) -> Result<Value, BridgeError> {
+        Ok(Value::String(variant.to_string()))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, value: &T) -> Result<Value, BridgeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Value, BridgeError> {
+        Ok(Value::Tuple(vec![Value::String(variant.to_string()), value.serialize(self)?]))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Items, BridgeError> {
+        Ok(Items::new(ItemsKind::Array, None, len))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Items, BridgeError> {
+        Ok(Items::new(ItemsKind::Tuple, None, Some(len)))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<Items, BridgeError> {
+        Ok(Items::new(ItemsKind::Tuple, None, Some(len)))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Items, BridgeError> {
+        Ok(Items::new(ItemsKind::Tuple, Some(variant), Some(len)))
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Items, BridgeError> {
+        Ok(Items::new(ItemsKind::Array, None, len))
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Items, BridgeError> {
+        Ok(Items::new(ItemsKind::Tuple, None, Some(len)))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Items, BridgeError> {
+        Ok(Items::new(ItemsKind::Tuple, Some(variant), Some(len)))
+    }
+}
+
+impl ser::SerializeSeq for Items {
+    type Ok = Value;
+    type Error = BridgeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), BridgeError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, BridgeError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for Items {
+    type Ok = Value;
+    type Error = BridgeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), BridgeError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, BridgeError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for Items {
+    type Ok = Value;
+    type Error = BridgeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), BridgeError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, BridgeError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for Items {
+    type Ok = Value;
+    type Error = BridgeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), BridgeError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, BridgeError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeMap for Items {
+    type Ok = Value;
+    type Error = BridgeError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), BridgeError> {
+        self.pending_key = Some(key.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), BridgeError> {
+        let key = self.pending_key.take().unwrap_or(Value::Null);
+        self.items.push(Value::Tuple(vec![key, value.serialize(ValueSerializer)?]));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, BridgeError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for Items {
+    type Ok = Value;
+    type Error = BridgeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, _key: &'static str, value: &T) -> Result<(), BridgeError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, BridgeError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for Items {
+    type Ok = Value;
+    type Error = BridgeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, _key: &'static str, value: &T) -> Result<(), BridgeError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, BridgeError> {
+        self.finish()
+    }
+}
+
+/// Name of a value's kind in conversion errors
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::String(_) => "a string",
+        Value::StringBuilder(_) => "a string builder",
+        Value::Number(_) => "a number",
+        Value::Float(_) => "a float",
+        Value::Bool(_) => "a bool",
+        Value::Array(_) => "an array",
+        Value::Tuple(_) => "a tuple",
+        Value::Map(_) => "a map",
+        Value::Option(_) => "an option",
+        Value::Result(_) => "a result",
+        Value::Range(..) => "a range",
+        Value::Iter(_) => "an iterator",
+        Value::Function(_) => "a function",
+        Value::Address(_) => "an address",
+        Value::U256(_) => "a u256",
+        Value::AIResult { .. } => "an AI result",
+        Value::Task(_) => "a task",
+        Value::Null => "null",
+    }
+}
+
+impl<'de> IntoDeserializer<'de, BridgeError> for Value {
+    type Deserializer = Value;
+
+    fn into_deserializer(self) -> Value {
+        self
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Value {
+    type Error = BridgeError;
+
+    fn deserialize_any<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, BridgeError> {
+        match self {
+            Value::String(s) | Value::Address(s) | Value::Function(s) => visitor.visit_string(s),
+            Value::StringBuilder(text) => visitor.visit_string(unshare(text)),
+            Value::Number(n) => visitor.visit_i64(n),
+            Value::Float(f) => visitor.visit_f64(f),
+            Value::Bool(b) => visitor.visit_bool(b),
+            Value::U256(n) => match u64::try_from(n) {
+                Ok(n) => visitor.visit_u64(n),
+                Err(_) => match u128::try_from(n) {
+                    Ok(n) => visitor.visit_u128(n),
+                    Err(_) => visitor.visit_string(n.to_string()),
+                },
+            },
+            Value::Task(id) => visitor.visit_u64(id as u64),
+            Value::Array(items) => visitor.visit_seq(de::value::SeqDeserializer::new(unshare(items).into_iter())),
+            Value::Tuple(items) => visitor.visit_seq(de::value::SeqDeserializer::new(items.into_iter())),
+            Value::Map(entries) => {
+                let entries = unshare(entries).into_iter().map(|(key, value)| (Value::String(key), value));
+                visitor.visit_map(de::value::MapDeserializer::new(entries))
+            }
+            Value::Option(Some(payload)) => visitor.visit_some(*payload),
+            Value::Option(None) => visitor.visit_none(),
+            Value::Result(result) => {
+                let (variant, payload) = match result {
+                    Ok(payload) => ("Ok", payload),
+                    Err(payload) => ("Err", payload),
+                };
+                Value::Tuple(vec![Value::String(variant.to_string()), *payload]).deserialize_any(visitor)
+            }
+            Value::Range(start, end) => {
+                visitor.visit_seq(de::value::SeqDeserializer::new([Value::Number(start), Value::Number(end)].into_iter()))
+            }
+            Value::AIResult { label, score } => {
+                visitor.visit_seq(de::value::SeqDeserializer::new([Value::String(label), Value::Float(score)].into_iter()))
+            }
+            // Its items need the script's functions; collect() it first
+            Value::Iter(_) => Err(BridgeError("an iterator cannot be converted; collect() it into an array first".to_string())),
+            Value::Null => visitor.visit_unit(),
+        }
+    }
+
+    fn deserialize_option<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, BridgeError> {
+        match self {
+            Value::Null | Value::Option(None) => visitor.visit_none(),
+            Value::Option(Some(payload)) => visitor.visit_some(*payload),
+            other => visitor.visit_some(other),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: de::Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, BridgeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_map<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, BridgeError> {
+        if let Value::Map(_) = self {
+            return self.deserialize_any(visitor);
+        }
+        let Value::Array(entries) = self else {
+            return Err(BridgeError(format!("expected an array of (key, value) tuples, got {}", kind(&self))));
+        };
+        let pairs = unshare(entries)
+            .into_iter()
+            .map(|entry| match entry {
+                Value::Tuple(mut pair) if pair.len() == 2 => {
+                    let value = pair.pop().unwrap_or(Value::Null);
+                    let key = pair.pop().unwrap_or(Value::Null);
+                    Ok((key, value))
+                }
+                other => Err(BridgeError(format!("expected a (key, value) tuple, got {}", kind(&other)))),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        visitor.visit_map(de::value::MapDeserializer::new(pairs.into_iter()))
+    }
+
+    fn deserialize_enum<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, BridgeError> {
+        match self {
+            Value::String(variant) => visitor.visit_enum(variant.into_deserializer()),
+            Value::Result(Ok(payload)) => visitor.visit_enum(Variant { variant: "Ok".to_string(), payload: *payload }),
+            Value::Result(Err(payload)) => visitor.visit_enum(Variant { variant: "Err".to_string(), payload: *payload }),
+            Value::Tuple(mut pair) if pair.len() == 2 => {
+                let payload = pair.pop().unwrap_or(Value::Null);
+                match pair.pop() {
+                    Some(Value::String(variant)) => visitor.visit_enum(Variant { variant, payload }),
+                    _ => Err(BridgeError("expected a variant name as the first element".to_string())),
+                }
+            }
+            other => Err(BridgeError(format!("expected an enum variant, got {}", kind(&other)))),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct struct identifier ignored_any
+    }
+}
+
+/// An enum variant with a payload: `("Name", payload)`
+struct Variant {
+    variant: String,
+    payload: Value,
+}
+
+impl<'de> de::EnumAccess<'de> for Variant {
+    type Error = BridgeError;
+    type Variant = Value;
+
+    fn variant_seed<S: de::DeserializeSeed<'de>>(self, seed: S) -> Result<(S::Value, Value), BridgeError> {
+        let variant = seed.deserialize(de::value::StringDeserializer::<BridgeError>::new(self.variant))?;
+        Ok((variant, self.payload))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for Value {
+    type Error = BridgeError;
+
+    fn unit_variant(self) -> Result<(), BridgeError> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<S: de::DeserializeSeed<'de>>(self, seed: S) -> Result<S::Value, BridgeError> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: de::Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, BridgeError> {
+        de::Deserializer::deserialize_any(self, visitor)
+    }
+
+    fn struct_variant<V: de::Visitor<'de>>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value, BridgeError> {
+        de::Deserializer::deserialize_any(self, visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use serde::{Deserialize, Serialize};
+    use crate::interpreter::Interpreter;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct User {
+        name: String,
+        age: u32,
+        admin: Option<bool>,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Shape {
+        Empty,
+        Circle(f64),
+        Rect { w: i64, h: i64 },
+    }
+
+    #[test]
+    fn test_rust_values_round_trip_through_script_values() {
+        let user = User { name: "ada".to_string(), age: 36, admin: None };
+        let value = Value::from_serde(&user).unwrap();
+        assert_eq!(value, Value::Tuple(vec![Value::String("ada".to_string()), Value::Number(36), Value::Null]));
+        assert_eq!(value.to_serde::<User>().unwrap(), user);
+
+        for shape in [Shape::Empty, Shape::Circle(1.5), Shape::Rect { w: 2, h: 3 }] {
+            assert_eq!(Value::from_serde(&shape).unwrap().to_serde::<Shape>().unwrap(), shape);
+        }
+
+        let scores = BTreeMap::from([("a".to_string(), 1u64), ("b".to_string(), u64::MAX)]);
+        let value = Value::from_serde(&scores).unwrap();
+        assert_eq!(value.to_serde::<BTreeMap<String, u64>>().unwrap(), scores);
+
+        let err = Value::Bool(true).to_serde::<User>().unwrap_err();
+        assert!(err.contains("cannot convert"), "{}", err);
+    }
+
+    #[test]
+    fn test_call_script_function_with_typed_arguments() {
+        let source = r#"
+            fn greet(user, greeting) {
+                let (name, age, admin) = user
+                return (greeting + ", " + name, age + 1, true)
+            }
+        "#;
+        let program = Parser::new(Lexer::new(source)).parse().unwrap();
+        let mut interpreter = Interpreter::new();
+        interpreter.load(program).unwrap();
+
+        let user = User { name: "ada".to_string(), age: 36, admin: None };
+        let greeted: (String, u32, bool) = interpreter.call_serde("greet", &(&user, "hello")).unwrap();
+        assert_eq!(greeted, ("hello, ada".to_string(), 37, true));
+        assert!(interpreter.call_serde::<_, i64>("missing", &()).is_err());
+    }
+}
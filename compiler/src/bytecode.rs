@@ -1,5 +1,7 @@
 // ASTRIXA Bytecode Instructions
 
+use crate::ast::Span;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum OpCode {
     LoadConst,      // Push constant to stack (operand: value)
@@ -26,6 +28,59 @@ pub enum OpCode {
     Index,          // Index into array/string (pop 2: index, array)
 }
 
+/// Every opcode, in the order of its byte in the .axb format. Append only:
+/// reordering changes the meaning of existing artifacts.
+const OPCODES: &[OpCode] = &[
+    OpCode::LoadConst,
+    OpCode::LoadVar,
+    OpCode::StoreVar,
+    OpCode::Add,
+    OpCode::Sub,
+    OpCode::Mul,
+    OpCode::Div,
+    OpCode::Mod,
+    OpCode::Equal,
+    OpCode::NotEqual,
+    OpCode::Greater,
+    OpCode::Less,
+    OpCode::GreaterEqual,
+    OpCode::LessEqual,
+    OpCode::JumpIfFalse,
+    OpCode::Jump,
+    OpCode::Call,
+    OpCode::Return,
+    OpCode::Print,
+    OpCode::Pop,
+    OpCode::Array,
+    OpCode::Index,
+];
+
+/// What an opcode's operand holds
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operand {
+    Text,  // A constant or a name
+    Index, // An instruction index or a count
+}
+
+impl OpCode {
+    /// The operand this opcode needs, or None if it takes none
+    fn operand(&self) -> Option<Operand> {
+        match self {
+            OpCode::LoadConst | OpCode::LoadVar | OpCode::StoreVar | OpCode::Call => Some(Operand::Text),
+            OpCode::JumpIfFalse | OpCode::Jump | OpCode::Array => Some(Operand::Index),
+            _ => None,
+        }
+    }
+
+    fn to_byte(&self) -> u8 {
+        OPCODES.iter().position(|op| op == self).unwrap_or_default() as u8
+    }
+
+    fn from_byte(byte: u8) -> Option<OpCode> {
+        OPCODES.get(byte as usize).cloned()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Instruction {
     pub opcode: OpCode,
@@ -65,3 +120,213 @@ impl Instruction {
         Instruction::new(OpCode::Array, Some(count.to_string()))
     }
 }
+
+/// A compiled function: parameters are bound by name, as the VM does
+#[derive(Debug, Clone)]
+pub struct BytecodeFunction {
+    pub name: String,
+    pub params: Vec<String>,
+    pub instructions: Vec<Instruction>,
+    pub span: Option<Span>, // Where it was declared, unless stripped
+}
+
+/// A bytecode artifact (.axb): the unit the VM loads and the registry ships
+/// for binary-only packages.
+///
+/// Layout, integers little-endian:
+///   "AXB\0", format version (u16), then the constant pool: a count (u32)
+///   and each string once, as a byte length (u32) followed by UTF-8. Then
+///   function count (u32), and per function: name, param count (u32) and
+///   names, instruction count (u32), then per instruction: opcode (u8) and
+///   operand, 0 for none or its pool index plus one (u32). Then a debug
+///   flag (u8) and if it is 1, per function, the start and end line and
+///   column of its declaration (u32 each).
+/// Names are pool indexes (u32) too, so a variable loaded in every loop is
+/// stored once.
+#[derive(Debug, Clone, Default)]
+pub struct BytecodeModule {
+    pub functions: Vec<BytecodeFunction>,
+}
+
+const MAGIC: &[u8; 4] = b"AXB\0";
+
+/// Bumped whenever the .axb layout or OPCODES change incompatibly
+pub const FORMAT_VERSION: u16 = 3;
+
+impl BytecodeModule {
+    pub fn find_function(&self, name: &str) -> Option<&BytecodeFunction> {
+        self.functions.iter().find(|f| f.name == name)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut pool = Pool::default();
+        let mut body = Vec::new();
+        write_u32(&mut body, self.functions.len());
+        for function in &self.functions {
+            write_u32(&mut body, pool.index(&function.name));
+            write_u32(&mut body, function.params.len());
+            for param in &function.params {
+                write_u32(&mut body, pool.index(param));
+            }
+            write_u32(&mut body, function.instructions.len());
+            for instr in &function.instructions {
+                body.push(instr.opcode.to_byte());
+                write_u32(&mut body, instr.operand.as_ref().map_or(0, |operand| pool.index(operand) + 1));
+            }
+        }
+
+        let mut out = MAGIC.to_vec();
+        out.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        write_u32(&mut out, pool.strings.len());
+        for string in &pool.strings {
+            write_str(&mut out, string);
+        }
+        out.extend(body);
+        let spans: Option<Vec<Span>> = self.functions.iter().map(|f| f.span).collect();
+        match spans.filter(|spans| !spans.is_empty()) {
+            Some(spans) => {
+                out.push(1);
+                for span in spans {
+                    for n in [span.start_line, span.start_column, span.end_line, span.end_column] {
+                        write_u32(&mut out, n);
+                    }
+                }
+            }
+            None => out.push(0),
+        }
+        out
+    }
+
+    /// Drop the debug information, for the smallest artifact
+    pub fn strip(&mut self) {
+        for function in &mut self.functions {
+            function.span = None;
+        }
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<BytecodeModule, String> {
+        let mut reader = Reader { bytes, pos: 0 };
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err("Not an ASTRIXA bytecode file (bad magic number)".to_string());
+        }
+        let version = u16::from_le_bytes([reader.byte()?, reader.byte()?]);
+        if version != FORMAT_VERSION {
+            return Err(format!(
+                "Unsupported bytecode format version {} (this compiler reads version {})",
+                version, FORMAT_VERSION
+            ));
+        }
+
+        let pool = (0..reader.u32()?).map(|_| reader.string()).collect::<Result<Vec<_>, _>>()?;
+        let constant = |index: u32| {
+            pool.get(index as usize)
+                .cloned()
+                .ok_or_else(|| format!("Constant {} is out of range; the pool has {}", index, pool.len()))
+        };
+        let string = |reader: &mut Reader| constant(reader.u32()?);
+
+        let mut functions = Vec::new();
+        for _ in 0..reader.u32()? {
+            let name = string(&mut reader)?;
+            let params = (0..reader.u32()?)
+                .map(|_| string(&mut reader))
+                .collect::<Result<Vec<_>, _>>()?;
+            let mut instructions = Vec::new();
+            for _ in 0..reader.u32()? {
+                let byte = reader.byte()?;
+                let opcode = OpCode::from_byte(byte)
+                    .ok_or_else(|| format!("Unknown opcode 0x{:02x} in function '{}'", byte, name))?;
+                let operand = match reader.u32()? {
+                    0 => None,
+                    index => Some(constant(index - 1)?),
+                };
+                check_operand(&opcode, operand.as_deref(), &name)?;
+                instructions.push(Instruction::new(opcode, operand));
+            }
+            functions.push(BytecodeFunction { name, params, instructions, span: None });
+        }
+        if reader.byte()? == 1 {
+            for function in &mut functions {
+                let mut n = || reader.u32().map(|n| n as usize);
+                function.span = Some(Span { start_line: n()?, start_column: n()?, end_line: n()?, end_column: n()? });
+            }
+        }
+
+        if reader.pos != bytes.len() {
+            return Err(format!("Trailing data after the last function at byte {}", reader.pos));
+        }
+        Ok(BytecodeModule { functions })
+    }
+}
+
+/// Check an instruction's operand is the one its opcode needs, so the VM
+/// never meets an instruction it cannot run
+fn check_operand(opcode: &OpCode, operand: Option<&str>, function: &str) -> Result<(), String> {
+    match (opcode.operand(), operand) {
+        (None, None) | (Some(Operand::Text), Some(_)) => Ok(()),
+        (Some(Operand::Index), Some(index)) if index.parse::<usize>().is_ok() => Ok(()),
+        (Some(Operand::Index), Some(other)) => {
+            Err(format!("{:?} in function '{}' needs an index or count, not '{}'", opcode, function, other))
+        }
+        (Some(_), None) => Err(format!("{:?} in function '{}' has no operand", opcode, function)),
+        (None, Some(other)) => Err(format!("{:?} in function '{}' takes no operand, but has '{}'", opcode, function, other)),
+    }
+}
+
+/// The strings of a module being written, each once, in first-use order
+#[derive(Default)]
+struct Pool {
+    strings: Vec<String>,
+    indexes: std::collections::HashMap<String, usize>,
+}
+
+impl Pool {
+    fn index(&mut self, string: &str) -> usize {
+        if let Some(index) = self.indexes.get(string) {
+            return *index;
+        }
+        self.strings.push(string.to_string());
+        self.indexes.insert(string.to_string(), self.strings.len() - 1);
+        self.strings.len() - 1
+    }
+}
+
+fn write_u32(out: &mut Vec<u8>, value: usize) {
+    out.extend_from_slice(&(value as u32).to_le_bytes());
+}
+
+fn write_str(out: &mut Vec<u8>, text: &str) {
+    write_u32(out, text.len());
+    out.extend_from_slice(text.as_bytes());
+}
+
+/// Cursor over an .axb file that reports truncation instead of panicking
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let end = self.pos.checked_add(len).filter(|end| *end <= self.bytes.len());
+        let end = end.ok_or_else(|| format!("Truncated bytecode file at byte {}", self.pos))?;
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn byte(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let len = self.u32()? as usize;
+        let bytes = self.take(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| format!("Invalid UTF-8 string at byte {}", self.pos - len))
+    }
+}
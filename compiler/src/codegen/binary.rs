@@ -0,0 +1,1043 @@
+// WAT to the WASM binary format, which is what runtimes load.
+//
+// The generators here write WAT, which stays readable and is what
+// `--emit=wat` keeps; `assemble` turns it into a binary module. It covers
+// the part of the text format they use: function imports, one memory
+// (and its maximum, if it has one),
+// one table of functions and its element segments, mutable i32 and i64
+// globals, data segments, functions over i32, i64, f32 and f64 with flat
+// instructions and labelled block, loop and if, and exports.
+// Anything outside that is an error rather than a guess, so a generator
+// growing a new construct fails its build instead of producing a module
+// no runtime accepts. So is static data running past the memory a module
+// starts with, which no runtime could instantiate. What assembles can still be ill-typed, e.g. a
+// function leaving the wrong values on the stack; `validate` checks a
+// module the way runtimes do before running it, so that fails the build
+// too. `(@line N C)` annotations in a body mark where the code of source
+// line N, column C starts; other annotations are skipped, as WAT tools do.
+
+use std::collections::HashMap;
+
+use crate::ast::Span;
+
+const MAGIC: &[u8] = b"\0asm";
+const VERSION: &[u8] = &[1, 0, 0, 0];
+
+/// A parsed WAT expression
+#[derive(Debug, Clone, PartialEq)]
+enum Sexp {
+    Atom(String),
+    Str(Vec<u8>),
+    List(Vec<Sexp>),
+}
+
+impl Sexp {
+    fn atom(&self) -> Option<&str> {
+        match self {
+            Sexp::Atom(atom) => Some(atom),
+            _ => None,
+        }
+    }
+
+    /// A list's items when it starts with `head`: `(param i32)`
+    fn form(&self, head: &str) -> Option<&[Sexp]> {
+        match self {
+            Sexp::List(items) if items.first().and_then(Sexp::atom) == Some(head) => Some(&items[1..]),
+            _ => None,
+        }
+    }
+}
+
+/// A module assembled with debug information
+#[derive(Debug, Clone)]
+pub struct Assembled {
+    pub bytes: Vec<u8>,
+    pub functions: Vec<(String, usize)>, // Each defined function's id, without `$`, and where its code starts
+    pub lines: Lines,
+}
+
+/// Where each `(@line ..)` marked code starts, with its line and column
+pub type Lines = Vec<(usize, usize, usize)>;
+
+/// Assemble a WAT module into its binary encoding
+pub fn assemble(wat: &str) -> Result<Vec<u8>, String> {
+    let tree = parse(wat)?;
+    Module::collect(fields(&tree)?)?.encode().map(|(bytes, ..)| bytes)
+}
+
+/// Assemble a WAT module with a `name` section, so runtimes show function
+/// names in traces and debuggers the names of locals with an `$id`, an `astrixa.spans` section with where each function in
+/// `spans` (by id, without `$`) was declared, and with a `source_map_url` a
+/// `sourceMappingURL` section pointing at the module's source map.
+///
+/// The spans section is a count, then per function its index and its start
+/// and end line and column, all unsigned LEB128.
+pub fn assemble_debug(wat: &str, source_map_url: Option<&str>, spans: &HashMap<String, Span>) -> Result<Assembled, String> {
+    let tree = parse(wat)?;
+    let module = Module::collect(fields(&tree)?)?;
+
+    // Function names by index, imports first
+    let mut names: Vec<(u32, String)> = module
+        .function_ids
+        .iter()
+        .map(|(id, index)| (*index, id.trim_start_matches('$').to_string()))
+        .collect();
+    names.sort();
+    let defined: Vec<Option<String>> = module.functions.iter().map(|f| f.id.map(|id| id.trim_start_matches('$').to_string())).collect();
+    // Each function's named locals, by function and local index
+    let imported = module.imports.len();
+    let locals: Vec<(usize, Vec<(usize, &str)>)> = module
+        .functions
+        .iter()
+        .enumerate()
+        .map(|(i, f)| {
+            let named = f.names.iter().enumerate().filter_map(|(local, id)| Some((local, (*id)?.trim_start_matches('$'))));
+            (imported + i, named.collect::<Vec<_>>())
+        })
+        .filter(|(_, named)| !named.is_empty())
+        .collect();
+
+    let (mut bytes, offsets, lines) = module.encode()?;
+    let mut name_map = Vec::new();
+    uleb(&mut name_map, names.len() as u64);
+    for (index, name) in &names {
+        uleb(&mut name_map, *index as u64);
+        self::bytes(&mut name_map, name.as_bytes());
+    }
+    let mut payload = vec![0x01]; // Function names
+    self::bytes(&mut payload, &name_map);
+    if !locals.is_empty() {
+        let mut local_map = Vec::new();
+        uleb(&mut local_map, locals.len() as u64);
+        for (function, named) in &locals {
+            uleb(&mut local_map, *function as u64);
+            uleb(&mut local_map, named.len() as u64);
+            for (local, name) in named {
+                uleb(&mut local_map, *local as u64);
+                self::bytes(&mut local_map, name.as_bytes());
+            }
+        }
+        payload.push(0x02); // Local names
+        self::bytes(&mut payload, &local_map);
+    }
+    custom_section(&mut bytes, "name", &payload);
+    let declared: Vec<(u32, &Span)> = names.iter().filter_map(|(index, name)| Some((*index, spans.get(name)?))).collect();
+    if !declared.is_empty() {
+        let mut payload = Vec::new();
+        uleb(&mut payload, declared.len() as u64);
+        for (index, span) in declared {
+            uleb(&mut payload, index as u64);
+            for n in [span.start_line, span.start_column, span.end_line, span.end_column] {
+                uleb(&mut payload, n as u64);
+            }
+        }
+        custom_section(&mut bytes, "astrixa.spans", &payload);
+    }
+    if let Some(url) = source_map_url {
+        let mut payload = Vec::new();
+        self::bytes(&mut payload, url.as_bytes());
+        custom_section(&mut bytes, "sourceMappingURL", &payload);
+    }
+
+    let functions = defined.into_iter().zip(offsets).filter_map(|(id, offset)| Some((id?, offset))).collect();
+    Ok(Assembled { bytes, functions, lines })
+}
+
+/// Check an assembled module as a runtime would before instantiating it.
+/// The error names the function and instruction at fault, when the
+/// problem is in one: by name when the module has a name section.
+pub fn validate(bytes: &[u8]) -> Result<(), String> {
+    let Err(error) = wasmparser::Validator::new().validate_all(bytes) else {
+        return Ok(());
+    };
+    Err(match locate(bytes, error.offset()) {
+        Some((function, index, instruction)) => {
+            format!("{} (function {}, instruction {}: {})", error.message(), function, index, instruction)
+        }
+        None => format!("{} (at byte {:#x})", error.message(), error.offset()),
+    })
+}
+
+/// The function whose code holds byte `offset` of a module, and the index
+/// and text of its instruction there
+fn locate(bytes: &[u8], offset: usize) -> Option<(String, usize, String)> {
+    use wasmparser::{Name, NameSectionReader, Payload, TypeRef};
+
+    let mut imported = 0;
+    let mut bodies = Vec::new();
+    let mut names = HashMap::new();
+    for payload in wasmparser::Parser::new(0).parse_all(bytes) {
+        match payload.ok()? {
+            Payload::ImportSection(imports) => {
+                imported += imports.into_iter().filter(|import| matches!(import, Ok(i) if matches!(i.ty, TypeRef::Func(_)))).count();
+            }
+            Payload::CodeSectionEntry(body) => bodies.push(body),
+            Payload::CustomSection(section) if section.name() == "name" => {
+                for name in NameSectionReader::new(section.data(), section.data_offset()) {
+                    if let Ok(Name::Function(map)) = name {
+                        names.extend(map.into_iter().flatten().map(|naming| (naming.index as usize, naming.name.to_string())));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let (position, body) = bodies.iter().enumerate().find(|(_, body)| body.range().contains(&offset))?;
+    let index = imported + position;
+    let function = names.get(&index).map_or_else(|| format!("#{}", index), |name| format!("${}", name));
+    let (count, (operator, _)) = body
+        .get_operators_reader()
+        .ok()?
+        .into_iter_with_offsets()
+        .map_while(Result::ok)
+        .take_while(|(_, at)| *at <= offset)
+        .enumerate()
+        .last()?;
+    Some((function, count, format!("{:?}", operator)))
+}
+
+fn parse(wat: &str) -> Result<Sexp, String> {
+    let mut chars = wat.char_indices().peekable();
+    read(wat, &mut chars)?.ok_or_else(|| "expected a module".to_string())
+}
+
+fn fields(tree: &Sexp) -> Result<&[Sexp], String> {
+    tree.form("module").ok_or_else(|| "expected (module ...)".to_string())
+}
+
+type Chars<'a> = std::iter::Peekable<std::str::CharIndices<'a>>;
+
+/// The next expression, skipping whitespace and `;;` comments
+fn read(text: &str, chars: &mut Chars) -> Result<Option<Sexp>, String> {
+    loop {
+        match chars.peek() {
+            None => return Ok(None),
+            Some((_, c)) if c.is_whitespace() => {
+                chars.next();
+            }
+            Some(&(i, ';')) if text[i..].starts_with(";;") => {
+                while chars.next_if(|(_, c)| *c != '\n').is_some() {}
+            }
+            Some((_, '(')) => {
+                chars.next();
+                let mut items = Vec::new();
+                loop {
+                    while chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+                    match chars.peek() {
+                        Some((_, ')')) => {
+                            chars.next();
+                            return Ok(Some(Sexp::List(items)));
+                        }
+                        None => return Err("unclosed '('".to_string()),
+                        _ => items.extend(read(text, chars)?),
+                    }
+                }
+            }
+            Some((_, ')')) => return Err("unexpected ')'".to_string()),
+            Some((_, '"')) => {
+                chars.next();
+                return read_string(chars).map(Some);
+            }
+            Some(&(start, _)) => {
+                let mut end = text.len();
+                while let Some(&(i, c)) = chars.peek() {
+                    if c.is_whitespace() || c == '(' || c == ')' {
+                        end = i;
+                        break;
+                    }
+                    chars.next();
+                }
+                return Ok(Some(Sexp::Atom(text[start..end].to_string())));
+            }
+        }
+    }
+}
+
+/// A string's bytes, after its opening quote
+fn read_string(chars: &mut Chars) -> Result<Sexp, String> {
+    let mut bytes = Vec::new();
+    loop {
+        match chars.next().map(|(_, c)| c) {
+            None => return Err("unclosed string".to_string()),
+            Some('"') => return Ok(Sexp::Str(bytes)),
+            Some('\\') => match chars.next().map(|(_, c)| c) {
+                Some('n') => bytes.push(b'\n'),
+                Some('r') => bytes.push(b'\r'),
+                Some('t') => bytes.push(b'\t'),
+                Some(c @ ('"' | '\'' | '\\')) => bytes.push(c as u8),
+                Some(high) => {
+                    let low = chars.next().map(|(_, c)| c);
+                    let digits = format!("{}{}", high, low.unwrap_or(' '));
+                    bytes.push(u8::from_str_radix(&digits, 16).map_err(|_| format!("bad escape '\\{}'", digits))?);
+                }
+                None => return Err("unclosed string".to_string()),
+            },
+            Some(c) => bytes.extend(c.to_string().as_bytes()),
+        }
+    }
+}
+
+/// A function's signature: parameter and result value types
+type Signature = (Vec<u8>, Vec<u8>);
+
+struct Import<'a> {
+    module: &'a [u8],
+    field: &'a [u8],
+    signature: Signature,
+}
+
+struct Function<'a> {
+    id: Option<&'a str>,
+    signature: Signature,
+    names: Vec<Option<&'a str>>, // Each local's `$id`, params first
+    locals: Vec<u8>,
+    body: &'a [Sexp],
+}
+
+/// A module's fields, sorted into the sections they encode to
+#[derive(Default)]
+struct Module<'a> {
+    types: Vec<Signature>,
+    imports: Vec<Import<'a>>,
+    functions: Vec<Function<'a>>,
+    function_ids: HashMap<&'a str, u32>, // Imports first, as WASM numbers them
+    memory: Option<(u32, Option<u32>)>,  // Pages at first, and at most
+    table: Option<u32>,                  // Function references
+    elements: Vec<(i32, Vec<&'a str>)>,  // The functions at each offset in the table
+    globals: HashMap<&'a str, u32>,
+    globals_init: Vec<(u8, i64)>, // Each mutable global's type and initial value
+    exports: Vec<(&'a [u8], u8, &'a str)>, // Name, kind and what it exports
+    data: Vec<(i32, Vec<u8>)>,
+}
+
+impl<'a> Module<'a> {
+    fn collect(fields: &'a [Sexp]) -> Result<Self, String> {
+        let mut module = Module::default();
+        let mut defined = Vec::new();
+        for field in fields {
+            if let Some(items) = field.form("import") {
+                let [Sexp::Str(from), Sexp::Str(name), func] = items else {
+                    return Err("expected (import \"module\" \"name\" (func ...))".to_string());
+                };
+                let func = func.form("func").ok_or("only functions can be imported")?;
+                let (id, rest) = split_id(func);
+                let (signature, _) = signature(rest)?;
+                if let Some(id) = id {
+                    module.function_ids.insert(id, module.imports.len() as u32);
+                }
+                module.imports.push(Import { module: from, field: name, signature });
+            } else if let Some(items) = field.form("func") {
+                defined.push(items);
+            } else if let Some(items) = field.form("memory") {
+                let mut pages = Vec::new();
+                for item in items {
+                    match item {
+                        Sexp::Atom(n) => pages.push(n.parse().map_err(|_| format!("bad memory size '{}'", n))?),
+                        _ => {
+                            let [Sexp::Str(name)] = item.form("export").ok_or("unexpected memory field")? else {
+                                return Err("expected (export \"name\")".to_string());
+                            };
+                            module.exports.push((name, 0x02, ""));
+                        }
+                    }
+                }
+                module.memory = match pages[..] {
+                    [initial] => Some((initial, None)),
+                    [initial, maximum] => Some((initial, Some(maximum))),
+                    _ => return Err("expected (memory initial) or (memory initial maximum)".to_string()),
+                };
+            } else if let Some(items) = field.form("table") {
+                let (_, rest) = split_id(items);
+                let [Sexp::Atom(size), Sexp::Atom(kind)] = rest else {
+                    return Err("expected (table size funcref)".to_string());
+                };
+                if kind != "funcref" {
+                    return Err(format!("unsupported table of {}", kind));
+                }
+                module.table = Some(size.parse().map_err(|_| format!("bad table size '{}'", size))?);
+            } else if let Some(items) = field.form("elem") {
+                let offset = items.first().and_then(|o| o.form("i32.const")).and_then(|n| n.first()?.atom());
+                let offset = offset.ok_or("expected (elem (i32.const offset) func ...)")?;
+                let mut ids = &items[1..];
+                if ids.first().and_then(Sexp::atom) == Some("func") {
+                    ids = &ids[1..];
+                }
+                let ids = ids.iter().map(|id| id.atom().ok_or("expected function ids")).collect::<Result<_, _>>()?;
+                module.elements.push((int(offset)?, ids));
+            } else if let Some(items) = field.form("global") {
+                let (id, rest) = split_id(items);
+                let ty = rest.first().and_then(|t| t.form("mut")).and_then(|t| t.first()?.atom());
+                let init = rest.get(1).and_then(|init| {
+                    let constant = init.form(&format!("{}.const", ty?))?;
+                    constant.first()?.atom()?.parse::<i64>().ok()
+                });
+                let unsupported = || "only (mut i32) and (mut i64) globals set to a constant are supported".to_string();
+                let ty = match ty {
+                    Some("i32") => 0x7F,
+                    Some("i64") => 0x7E,
+                    _ => return Err(unsupported()),
+                };
+                let init = init.ok_or_else(unsupported)?;
+                if let Some(id) = id {
+                    module.globals.insert(id, module.globals_init.len() as u32);
+                }
+                module.globals_init.push((ty, init));
+            } else if let Some(items) = field.form("data") {
+                let offset = items.first().and_then(|o| o.form("i32.const")).and_then(|n| n.first()?.atom());
+                let offset = offset.ok_or("expected (data (i32.const offset) ...)")?;
+                let mut bytes = Vec::new();
+                for item in &items[1..] {
+                    let Sexp::Str(text) = item else { return Err("expected data strings".to_string()) };
+                    bytes.extend(text);
+                }
+                module.data.push((int(offset)?, bytes));
+            } else if let Some(items) = field.form("export") {
+                let [Sexp::Str(name), target] = items else {
+                    return Err("expected (export \"name\" (func $id))".to_string());
+                };
+                let id = target.form("func").and_then(|f| f.first()?.atom()).ok_or("only functions can be exported")?;
+                module.exports.push((name, 0x00, id));
+            } else {
+                return Err(format!("unsupported module field {:?}", field));
+            }
+        }
+
+        // Defined functions number after every import
+        for items in defined {
+            let (id, rest) = split_id(items);
+            if let Some(id) = id {
+                module.function_ids.insert(id, (module.imports.len() + module.functions.len()) as u32);
+            }
+            let mut names = local_ids(rest, "param");
+            let (signature, rest) = signature(rest)?;
+            let mut locals = Vec::new();
+            let mut body = rest;
+            while let Some(types) = body.first().and_then(|item| item.form("local")) {
+                for t in split_id(types).1 {
+                    locals.push(value_type(t)?);
+                }
+                body = &body[1..];
+            }
+            names.extend(local_ids(rest, "local"));
+            module.functions.push(Function { id, signature, names, locals, body });
+        }
+
+        // Only the heap grows memory; static data has to fit at the start
+        if let Some((pages, _)) = module.memory {
+            let size = pages as usize * crate::ir::Memory::PAGE;
+            let end = module.data.iter().map(|(offset, bytes)| *offset as usize + bytes.len()).max().unwrap_or(0);
+            if end > size {
+                return Err(format!(
+                    "static data runs to byte {}, past the {} bytes of initial memory ({} 64 KiB page{}); raise initial-pages under [memory] in astrixa.toml",
+                    end,
+                    size,
+                    pages,
+                    if pages == 1 { "" } else { "s" }
+                ));
+            }
+        }
+        Ok(module)
+    }
+
+    /// The index of `signature` in the type section, adding it if new
+    fn type_index(&mut self, signature: &Signature) -> u32 {
+        match self.types.iter().position(|t| t == signature) {
+            Some(index) => index as u32,
+            None => {
+                self.types.push(signature.clone());
+                self.types.len() as u32 - 1
+            }
+        }
+    }
+
+    /// The module's bytes, where each defined function's code starts, and
+    /// where each `(@line ..)` marked code starts, with its line and column
+    fn encode(mut self) -> Result<(Vec<u8>, Vec<usize>, Lines), String> {
+        let import_types: Vec<u32> = (0..self.imports.len())
+            .map(|i| {
+                let signature = self.imports[i].signature.clone();
+                self.type_index(&signature)
+            })
+            .collect();
+        let function_types: Vec<u32> = (0..self.functions.len())
+            .map(|i| {
+                let signature = self.functions[i].signature.clone();
+                self.type_index(&signature)
+            })
+            .collect();
+        let mut bodies = Vec::new();
+        let mut marked = Vec::new();
+        for i in 0..self.functions.len() {
+            let (body, lines) = self.code(i)?;
+            bodies.push(body);
+            marked.push(lines);
+        }
+
+        let mut out = [MAGIC, VERSION].concat();
+        section(&mut out, 1, &self.types, |out, (params, results)| {
+            out.push(0x60);
+            bytes(out, params);
+            bytes(out, results);
+        });
+        let imports: Vec<_> = self.imports.iter().zip(&import_types).collect();
+        section(&mut out, 2, &imports, |out, (import, index)| {
+            bytes(out, import.module);
+            bytes(out, import.field);
+            out.push(0x00);
+            uleb(out, **index as u64);
+        });
+        section(&mut out, 3, &function_types, |out, index| uleb(out, *index as u64));
+        section(&mut out, 4, &self.table.into_iter().collect::<Vec<_>>(), |out, size| {
+            out.extend([0x70, 0x00]); // funcref, no maximum
+            uleb(out, *size as u64);
+        });
+        section(&mut out, 5, &self.memory.into_iter().collect::<Vec<_>>(), |out, (initial, maximum)| {
+            out.push(u8::from(maximum.is_some()));
+            uleb(out, *initial as u64);
+            if let Some(maximum) = maximum {
+                uleb(out, *maximum as u64);
+            }
+        });
+        section(&mut out, 6, &self.globals_init, |out, (ty, init)| {
+            let constant = if *ty == 0x7E { 0x42 } else { 0x41 };
+            out.extend([*ty, 0x01, constant]); // (mut ty) (ty.const init)
+            sleb(out, *init);
+            out.push(0x0B);
+        });
+        let mut exports = Vec::new();
+        for (name, kind, id) in &self.exports {
+            let index = match kind {
+                0x00 => *self.function_ids.get(id).ok_or_else(|| format!("export of unknown function {}", id))?,
+                _ => 0,
+            };
+            exports.push((*name, *kind, index));
+        }
+        section(&mut out, 7, &exports, |out, (name, kind, index)| {
+            bytes(out, name);
+            out.push(*kind);
+            uleb(out, *index as u64);
+        });
+        let mut elements = Vec::new();
+        for (offset, ids) in &self.elements {
+            let indices = ids.iter().map(|id| self.function_ids.get(id).copied().ok_or_else(|| format!("element of unknown function {}", id)));
+            elements.push((*offset, indices.collect::<Result<Vec<u32>, String>>()?));
+        }
+        section(&mut out, 9, &elements, |out, (offset, indices)| {
+            out.extend([0x00, 0x41]); // Table 0, at (i32.const offset)
+            sleb(out, *offset as i64);
+            out.push(0x0B);
+            uleb(out, indices.len() as u64);
+            for index in indices {
+                uleb(out, *index as u64);
+            }
+        });
+        // Code, noting where each body starts for source maps
+        let mut code = Vec::new();
+        let mut starts = Vec::new();
+        uleb(&mut code, bodies.len() as u64);
+        for body in &bodies {
+            uleb(&mut code, body.len() as u64);
+            starts.push(code.len());
+            code.extend(body);
+        }
+        let mut offsets = Vec::new();
+        if !bodies.is_empty() {
+            out.push(10);
+            uleb(&mut out, code.len() as u64);
+            offsets = starts.iter().map(|start| out.len() + start).collect();
+            out.extend(code);
+        }
+        let lines = offsets
+            .iter()
+            .zip(marked)
+            .flat_map(|(start, lines)| lines.into_iter().map(move |(at, line, column)| (start + at, line, column)))
+            .collect();
+        section(&mut out, 11, &self.data, |out, (offset, data)| {
+            out.extend([0x00, 0x41]);
+            sleb(out, *offset as i64);
+            out.push(0x0B);
+            bytes(out, data);
+        });
+        Ok((out, offsets, lines))
+    }
+
+    /// Function `index`'s entry in the code section: its locals, run-length
+    /// encoded, then its instructions; and where in it each `(@line ..)`
+    /// marked code starts, with its line and column
+    fn code(&mut self, index: usize) -> Result<(Vec<u8>, Lines), String> {
+        let mut out = Vec::new();
+        let mut lines = Vec::new();
+        let mut runs: Vec<(u32, u8)> = Vec::new();
+        for &t in &self.functions[index].locals {
+            match runs.last_mut() {
+                Some((count, last)) if *last == t => *count += 1,
+                _ => runs.push((1, t)),
+            }
+        }
+        uleb(&mut out, runs.len() as u64);
+        for (count, t) in runs {
+            uleb(&mut out, count as u64);
+            out.push(t);
+        }
+
+        let body = self.functions[index].body;
+        let mut labels: Vec<Option<&str>> = Vec::new();
+        let mut words = body.iter().peekable();
+        while let Some(word) = words.next() {
+            if let Some(annotation) = annotation(word) {
+                if let ("@line", [line, column]) = annotation {
+                    let number = |n: &Sexp| n.atom().and_then(|n| n.parse().ok()).ok_or("expected (@line line column)");
+                    lines.push((out.len(), number(line)?, number(column)?));
+                }
+                continue;
+            }
+            let name = word.atom().ok_or_else(|| format!("folded instructions are not supported: {:?}", word))?;
+            if let Some(op) = simple_op(name) {
+                out.extend(op);
+                continue;
+            }
+            match name {
+                "block" | "loop" | "if" => {
+                    out.push(match name {
+                        "block" => 0x02,
+                        "loop" => 0x03,
+                        _ => 0x04,
+                    });
+                    let label = words.next_if(|w| w.atom().is_some_and(|a| a.starts_with('$')));
+                    labels.push(label.and_then(Sexp::atom));
+                    let signature = inline_signature(&mut words)?;
+                    match (&signature.0[..], &signature.1[..]) {
+                        ([], []) => out.push(0x40),
+                        ([], [result]) => out.push(*result),
+                        _ => sleb(&mut out, self.type_index(&signature) as i64),
+                    }
+                }
+                "else" => {
+                    labels.last().ok_or("'else' outside an 'if'")?;
+                    out.push(0x05);
+                }
+                "end" => {
+                    labels.pop().ok_or("'end' without a block")?;
+                    out.push(0x0B);
+                }
+                "br" | "br_if" => {
+                    out.push(if name == "br" { 0x0C } else { 0x0D });
+                    let target = words.next().and_then(Sexp::atom).ok_or("branch without a label")?;
+                    let depth = match target.strip_prefix('$') {
+                        Some(_) => labels.iter().rev().position(|label| *label == Some(target)),
+                        None => target.parse().ok(),
+                    };
+                    uleb(&mut out, depth.ok_or_else(|| format!("branch to unknown label {}", target))? as u64);
+                }
+                "local.get" | "local.set" | "local.tee" => {
+                    out.push(match name {
+                        "local.get" => 0x20,
+                        "local.set" => 0x21,
+                        _ => 0x22,
+                    });
+                    let slot = words.next().and_then(Sexp::atom).ok_or("local without an index")?;
+                    let slot: u32 = slot.parse().map_err(|_| format!("unknown local {}", slot))?;
+                    uleb(&mut out, slot as u64);
+                }
+                "global.get" | "global.set" => {
+                    out.push(if name == "global.get" { 0x23 } else { 0x24 });
+                    let id = words.next().and_then(Sexp::atom).ok_or("global without a name")?;
+                    let index = self.globals.get(id).ok_or_else(|| format!("unknown global {}", id))?;
+                    uleb(&mut out, *index as u64);
+                }
+                "call" => {
+                    out.push(0x10);
+                    let id = words.next().and_then(Sexp::atom).ok_or("call without a function")?;
+                    let index = self.function_ids.get(id).ok_or_else(|| format!("call to unknown function {}", id))?;
+                    uleb(&mut out, *index as u64);
+                }
+                // Through table 0, typed by its inline signature
+                "call_indirect" => {
+                    let signature = inline_signature(&mut words)?;
+                    out.push(0x11);
+                    uleb(&mut out, self.type_index(&signature) as u64);
+                    out.push(0x00);
+                }
+                "i32.const" | "i64.const" => {
+                    out.push(if name == "i32.const" { 0x41 } else { 0x42 });
+                    let n = words.next().and_then(Sexp::atom).ok_or("constant without a value")?;
+                    let n: i64 = n.parse().map_err(|_| format!("bad integer '{}'", n))?;
+                    // An i32 constant out of range wraps, as WAT tools do
+                    sleb(&mut out, if name == "i32.const" { n as i32 as i64 } else { n });
+                }
+                "f32.const" => {
+                    out.push(0x43);
+                    let f = words.next().and_then(Sexp::atom).ok_or("constant without a value")?;
+                    let f: f32 = f.parse().map_err(|_| format!("bad float '{}'", f))?;
+                    out.extend(f.to_le_bytes());
+                }
+                "f64.const" => {
+                    out.push(0x44);
+                    let f = words.next().and_then(Sexp::atom).ok_or("constant without a value")?;
+                    let f: f64 = f.parse().map_err(|_| format!("bad float '{}'", f))?;
+                    out.extend(f.to_le_bytes());
+                }
+                _ => return Err(format!("unsupported instruction '{}'", name)),
+            }
+        }
+        if !labels.is_empty() {
+            return Err("block without an 'end'".to_string());
+        }
+        out.push(0x0B);
+        Ok((out, lines))
+    }
+}
+
+/// An optional `$id` at the start of a field, and the rest
+fn split_id(items: &[Sexp]) -> (Option<&str>, &[Sexp]) {
+    match items.first().and_then(Sexp::atom) {
+        Some(id) if id.starts_with('$') => (Some(id), &items[1..]),
+        _ => (None, items),
+    }
+}
+
+/// A `(@name ...)` annotation's name and items
+fn annotation(word: &Sexp) -> Option<(&str, &[Sexp])> {
+    match word {
+        Sexp::List(items) => items.first()?.atom().filter(|head| head.starts_with('@')).map(|head| (head, &items[1..])),
+        _ => None,
+    }
+}
+
+/// The `$id` of each local that `head` forms at the start of `items`
+/// declare: `(param $n i64)` names one, `(param i64 i64)` two without
+fn local_ids<'a>(items: &'a [Sexp], head: &str) -> Vec<Option<&'a str>> {
+    items
+        .iter()
+        .map_while(|item| item.form(head))
+        .flat_map(|types| match split_id(types) {
+            (Some(id), _) => vec![Some(id)],
+            (None, types) => vec![None; types.len()],
+        })
+        .collect()
+}
+
+/// `(param ...)` and `(result ...)` at the start of a function, and the rest
+fn signature(mut items: &[Sexp]) -> Result<(Signature, &[Sexp]), String> {
+    let mut signature: Signature = (Vec::new(), Vec::new());
+    while let Some(first) = items.first() {
+        let (types, list) = match (first.form("param"), first.form("result")) {
+            (Some(types), _) => (types, &mut signature.0),
+            (_, Some(types)) => (types, &mut signature.1),
+            _ => break,
+        };
+        for t in split_id(types).1 {
+            list.push(value_type(t)?);
+        }
+        items = &items[1..];
+    }
+    Ok((signature, items))
+}
+
+/// The `(param ...)` and `(result ...)` after a block or call_indirect
+fn inline_signature(words: &mut std::iter::Peekable<std::slice::Iter<Sexp>>) -> Result<Signature, String> {
+    let mut signature: Signature = (Vec::new(), Vec::new());
+    while let Some(types) = words.peek().and_then(|w| w.form("param").or_else(|| w.form("result"))) {
+        let is_param = words.next().and_then(|w| w.form("param")).is_some();
+        for t in types {
+            let list = if is_param { &mut signature.0 } else { &mut signature.1 };
+            list.push(value_type(t)?);
+        }
+    }
+    Ok(signature)
+}
+
+fn value_type(t: &Sexp) -> Result<u8, String> {
+    match t.atom() {
+        Some("i32") => Ok(0x7F),
+        Some("i64") => Ok(0x7E),
+        Some("f32") => Ok(0x7D),
+        Some("f64") => Ok(0x7C),
+        _ => Err(format!("unsupported value type {:?}", t)),
+    }
+}
+
+fn int(text: &str) -> Result<i32, String> {
+    text.parse().map_err(|_| format!("bad integer '{}'", text))
+}
+
+/// Opcodes of the instructions without immediates
+fn simple_op(name: &str) -> Option<&'static [u8]> {
+    Some(match name {
+        "unreachable" => &[0x00],
+        "nop" => &[0x01],
+        "return" => &[0x0F],
+        "drop" => &[0x1A],
+        // Naturally aligned, no offset
+        "i32.load" => &[0x28, 0x02, 0x00],
+        "i64.load" => &[0x29, 0x03, 0x00],
+        "i32.load8_u" => &[0x2D, 0x00, 0x00],
+        "i32.store" => &[0x36, 0x02, 0x00],
+        "i32.store8" => &[0x3A, 0x00, 0x00],
+        "memory.size" => &[0x3F, 0x00],
+        "memory.grow" => &[0x40, 0x00],
+        "i32.eqz" => &[0x45],
+        "i32.eq" => &[0x46],
+        "i32.ne" => &[0x47],
+        "i32.lt_s" => &[0x48],
+        "i32.gt_s" => &[0x4A],
+        "i32.le_s" => &[0x4C],
+        "i32.ge_s" => &[0x4E],
+        "i64.eqz" => &[0x50],
+        "i64.eq" => &[0x51],
+        "i64.ne" => &[0x52],
+        "i64.lt_s" => &[0x53],
+        "i64.gt_s" => &[0x55],
+        "i64.le_s" => &[0x57],
+        "i64.ge_s" => &[0x59],
+        "f64.eq" => &[0x61],
+        "f64.ne" => &[0x62],
+        "f64.lt" => &[0x63],
+        "f64.gt" => &[0x64],
+        "f64.le" => &[0x65],
+        "f64.ge" => &[0x66],
+        "i32.lt_u" => &[0x49],
+        "i32.gt_u" => &[0x4B],
+        "i32.le_u" => &[0x4D],
+        "i32.ge_u" => &[0x4F],
+        "i32.add" => &[0x6A],
+        "i32.sub" => &[0x6B],
+        "i32.mul" => &[0x6C],
+        "i32.div_s" => &[0x6D],
+        "i32.rem_s" => &[0x6F],
+        "i32.and" => &[0x71],
+        "i32.or" => &[0x72],
+        "i32.xor" => &[0x73],
+        "i32.shl" => &[0x74],
+        "i32.shr_u" => &[0x76],
+        "i64.add" => &[0x7C],
+        "i64.sub" => &[0x7D],
+        "i64.mul" => &[0x7E],
+        "i64.div_s" => &[0x7F],
+        "i64.div_u" => &[0x80],
+        "i64.rem_s" => &[0x81],
+        "i64.rem_u" => &[0x82],
+        "i64.and" => &[0x83],
+        "i64.or" => &[0x84],
+        "i64.xor" => &[0x85],
+        "f64.trunc" => &[0x9D],
+        "f64.add" => &[0xA0],
+        "f64.sub" => &[0xA1],
+        "f64.mul" => &[0xA2],
+        "f64.div" => &[0xA3],
+        "i32.wrap_i64" => &[0xA7],
+        "i64.extend_i32_s" => &[0xAC],
+        "i64.extend_i32_u" => &[0xAD],
+        "f32.convert_i32_s" => &[0xB2],
+        "f64.convert_i32_u" => &[0xB8],
+        "f64.convert_i64_s" => &[0xB9],
+        "i32.trunc_sat_f32_s" => &[0xFC, 0x00],
+        "i64.trunc_sat_f64_s" => &[0xFC, 0x06],
+        "memory.copy" => &[0xFC, 0x0A, 0x00, 0x00],
+        _ => return None,
+    })
+}
+
+/// A section: its id, then its size and its items, counted
+fn section<T>(out: &mut Vec<u8>, id: u8, items: &[T], mut encode: impl FnMut(&mut Vec<u8>, &T)) {
+    if items.is_empty() {
+        return;
+    }
+    let mut content = Vec::new();
+    uleb(&mut content, items.len() as u64);
+    for item in items {
+        encode(&mut content, item);
+    }
+    out.push(id);
+    bytes(out, &content);
+}
+
+/// A custom section: its name, then its payload
+fn custom_section(out: &mut Vec<u8>, name: &str, payload: &[u8]) {
+    let mut content = Vec::new();
+    bytes(&mut content, name.as_bytes());
+    content.extend(payload);
+    out.push(0x00);
+    bytes(out, &content);
+}
+
+/// A length-prefixed byte string
+fn bytes(out: &mut Vec<u8>, data: &[u8]) {
+    uleb(out, data.len() as u64);
+    out.extend(data);
+}
+
+fn uleb(out: &mut Vec<u8>, mut n: u64) {
+    loop {
+        let byte = (n & 0x7F) as u8;
+        n >>= 7;
+        if n == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn sleb(out: &mut Vec<u8>, mut n: i64) {
+    loop {
+        let byte = (n & 0x7F) as u8;
+        n >>= 7;
+        let done = (n == 0 && byte & 0x40 == 0) || (n == -1 && byte & 0x40 != 0);
+        if done {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::wasm::generate_wasm_module;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    #[test]
+    fn test_modules_assemble_to_binary() {
+        let source = "fn double(n) {\n    return n * 2\n}\nfn main() {\n    let mut i = 0\n    while i < 3 {\n        print(\"tick\")\n        i = double(i) + 1\n    }\n    return i\n}\n";
+        let module = crate::lowering::lower(&Parser::new(Lexer::new(source)).parse().unwrap());
+        let binary = assemble(&generate_wasm_module(&module)).unwrap();
+
+        assert_eq!(&binary[..8], b"\0asm\x01\0\0\0");
+        // Sections in order: types, the print import, two functions,
+        // memory, exports, code and the "tick" data
+        let ids: Vec<u8> = sections(&binary).iter().map(|(id, _)| *id).collect();
+        assert_eq!(ids, vec![1, 2, 3, 5, 7, 10, 11]);
+        let exports = &sections(&binary)[4].1;
+        assert!(exports.windows(4).any(|w| w == b"main") && exports.windows(6).any(|w| w == b"memory"));
+        // `call $double` is to index 1, after the import
+        let code = &sections(&binary)[5].1;
+        assert!(code.windows(2).any(|w| w == [0x10, 0x01]));
+
+        assert_eq!(sleb_bytes(-1), vec![0x7F]);
+        assert_eq!(sleb_bytes(64), vec![0xC0, 0x00]);
+        assert_eq!(assemble("(module (func call $missing))"), Err("call to unknown function $missing".to_string()));
+        assert!(assemble("(module (func block $a br $b end))").unwrap_err().contains("$b"));
+    }
+
+    #[test]
+    fn test_debug_sections_name_and_place_functions() {
+        let source = "fn double(n) {\n    return n * 2\n}\nfn main() {\n    return double(2)\n}\n";
+        let module = crate::lowering::lower(&Parser::new(Lexer::new(source)).parse().unwrap());
+        let wat = generate_wasm_module(&module);
+        let spans = module.functions.iter().map(|f| (f.name.clone(), f.span)).collect();
+        let debug = assemble_debug(&wat, Some("demo.wasm.map"), &spans).unwrap();
+
+        // Custom sections, each starting with its name
+        let custom: Vec<Vec<u8>> = sections(&debug.bytes).into_iter().filter(|(id, _)| *id == 0).map(|(_, s)| s).collect();
+        let named = |name: &str| custom.iter().find(|s| s[1..].starts_with(name.as_bytes())).cloned();
+        assert!(named("name").is_some() && named("sourceMappingURL").is_some());
+        // Both functions, `main` (index 1) declared on line 4
+        let spans = named("astrixa.spans").unwrap();
+        let payload = &spans[1 + "astrixa.spans".len()..];
+        assert_eq!(payload[..3], [2, 0, 1]);
+        assert_eq!(payload[6..8], [1, 4]);
+
+        // Stripped, none of them
+        assert!(sections(&assemble(&wat).unwrap()).iter().all(|(id, _)| *id != 0));
+    }
+
+    #[test]
+    fn test_debug_info_names_locals_and_marks_lines() {
+        use wasmparser::{Name, NameSectionReader, Payload};
+
+        let source = "fn label(n: Int, unit: String) -> Int {\n    let x = n * 2\n    let x = x + 1\n    return x\n}\n";
+        let program = Parser::new(Lexer::new(source)).parse().unwrap();
+        let module = crate::lowering::lower_with(&program, false, true);
+        let wat = generate_wasm_module(&module);
+        assert!(wat.contains("(param $n i64) (param $unit i32) (param $unit.len i32)"), "{}", wat);
+        let debug = assemble_debug(&wat, None, &HashMap::new()).unwrap();
+        assert_eq!(validate(&debug.bytes), Ok(()));
+
+        // A shadowed `x` is named apart from the first
+        let mut locals = Vec::new();
+        for payload in wasmparser::Parser::new(0).parse_all(&debug.bytes) {
+            let Ok(Payload::CustomSection(section)) = payload else { continue };
+            for name in NameSectionReader::new(section.data(), section.data_offset()).into_iter().flatten() {
+                if let Name::Local(map) = name {
+                    let function = map.into_iter().next().unwrap().unwrap();
+                    locals.extend(function.names.into_iter().flatten().map(|naming| (naming.index, naming.name.to_string())));
+                }
+            }
+        }
+        let names: Vec<(u32, &str)> = locals.iter().map(|(index, name)| (*index, name.as_str())).collect();
+        assert_eq!(names, vec![(0, "n"), (1, "unit"), (2, "unit.len"), (3, "x"), (4, "x.2")]);
+
+        // Each statement's code, in order, at its line and column
+        let lines: Vec<(usize, usize)> = debug.lines.iter().map(|&(_, line, column)| (line, column)).collect();
+        assert_eq!(lines, vec![(2, 5), (3, 5), (4, 5)]);
+        assert!(debug.lines.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        assert!(debug.lines[0].0 > debug.functions[0].1, "after the function's locals");
+        // Without debug info the markers are skipped
+        assert_eq!(validate(&assemble(&wat).unwrap()), Ok(()));
+    }
+
+    #[test]
+    fn test_invalid_modules_point_at_the_instruction() {
+        let wat = "(module (func $ok) (func $broken (result i64) nop i32.const 1))";
+        let named = assemble_debug(wat, None, &HashMap::new()).unwrap().bytes;
+        let error = validate(&named).unwrap_err();
+        assert!(error.contains("(function $broken, instruction 2: End)"), "{}", error);
+        // Stripped, the function goes by its index
+        assert!(validate(&assemble(wat).unwrap()).unwrap_err().contains("function #1"));
+
+        // A body ending in a call that returns nothing still returns a value
+        let source = "fn greet() {\n    print(\"hi\")\n}\nfn main() {\n    greet()\n}\n";
+        let module = crate::lowering::lower(&Parser::new(Lexer::new(source)).parse().unwrap());
+        assert_eq!(validate(&assemble(&generate_wasm_module(&module)).unwrap()), Ok(()));
+    }
+
+    #[test]
+    fn test_static_data_has_to_fit_initial_memory() {
+        let source = format!("fn main() {{\n    print(\"{}\")\n}}\n", "a".repeat(70000));
+        let mut module = crate::lowering::lower(&Parser::new(Lexer::new(&source)).parse().unwrap());
+        let error = assemble(&generate_wasm_module(&module)).unwrap_err();
+        assert!(error.starts_with("static data runs to byte 70000, past the 65536 bytes of initial memory (1 64 KiB page)"), "{}", error);
+
+        // Two pages hold it, and the heap may grow memory to four
+        module.memory = crate::ir::Memory { initial: 2, maximum: Some(4) };
+        let wat = generate_wasm_module(&module);
+        assert!(wat.contains("(memory (export \"memory\") 2 4)"));
+        let binary = assemble(&wat).unwrap();
+        validate(&binary).unwrap();
+        let memory = sections(&binary).into_iter().find(|(id, _)| *id == 5).unwrap().1;
+        assert_eq!(memory, [1, 0x01, 2, 4]); // One memory, limits with a maximum
+    }
+
+    fn sleb_bytes(n: i64) -> Vec<u8> {
+        let mut out = Vec::new();
+        sleb(&mut out, n);
+        out
+    }
+
+    /// Each section's id and content
+    fn sections(binary: &[u8]) -> Vec<(u8, Vec<u8>)> {
+        let mut sections = Vec::new();
+        let mut at = 8;
+        while at < binary.len() {
+            let id = binary[at];
+            let (mut size, mut shift) = (0usize, 0);
+            at += 1;
+            loop {
+                let byte = binary[at];
+                at += 1;
+                size |= ((byte & 0x7F) as usize) << shift;
+                shift += 7;
+                if byte & 0x80 == 0 {
+                    break;
+                }
+            }
+            sections.push((id, binary[at..at + size].to_vec()));
+            at += size;
+        }
+        sections
+    }
+}
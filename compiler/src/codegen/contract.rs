@@ -0,0 +1,350 @@
+// ASTRIXA Contract Backends
+//
+// A contract build targets a chain family, and the family's backend turns
+// the lowered module into an artifact with the entry convention its chains
+// expect:
+// - evm: the module as scripts get it, every function exported under its
+//   IR name (`Token.transfer`) with contract state in globals
+// - near: a WASM contract in the style of NEAR and CosmWasm. Each method
+//   is exported under its own name (`transfer`, the constructor as `new`)
+//   and takes no parameters: arguments come from the host's `argument`,
+//   results go back through `value_return`, and state is read from and
+//   written to host storage around every call, keyed `Token.var`. Chains
+//   with a wider ABI adapt these four imports with a host shim.
+//
+// A module with several contracts qualifies the near entry points with
+// the contract's name (`Token_transfer`) so that they cannot clash.
+//
+// To a chain, a call that panicked anywhere is a reverted transaction, so
+// both families turn every panic into a call to the host's `revert` with
+// the message as reason data: ABI-encoded as `Error(string)` on evm, as
+// Solidity's `revert("...")` leaves it, and as UTF-8 on near.
+
+use crate::codegen::wasm::{generate_module, host_calls, string_data_end, value_types, Shell};
+use crate::ir::{IRContract, IRModule};
+use crate::kinds::FunctionKinds;
+
+/// The chain families contracts can be built for
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChainFamily {
+    Evm,
+    Near,
+}
+
+impl ChainFamily {
+    pub const NAMES: [&'static str; 2] = ["evm", "near"];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "evm" => Some(ChainFamily::Evm),
+            "near" => Some(ChainFamily::Near),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ChainFamily::Evm => "evm",
+            ChainFamily::Near => "near",
+        }
+    }
+
+    pub fn backend(self) -> Box<dyn ContractBackend> {
+        match self {
+            ChainFamily::Evm => Box::new(EvmBackend),
+            ChainFamily::Near => Box::new(NearBackend),
+        }
+    }
+}
+
+/// A contract build's output
+#[derive(Debug, Clone)]
+pub struct Artifact {
+    pub wat: String,
+    pub entry_points: Vec<String>, // Exports a chain calls the contract through
+}
+
+/// Lowers a module with contracts for one chain family
+pub trait ContractBackend {
+    fn lower(&self, module: &IRModule) -> Artifact;
+}
+
+pub struct EvmBackend;
+
+impl ContractBackend for EvmBackend {
+    fn lower(&self, module: &IRModule) -> Artifact {
+        let entry_points = module
+            .contracts
+            .iter()
+            .flat_map(|contract| contract.constructor.iter().chain(&contract.methods).cloned())
+            .collect();
+        let mut shell = Shell::default();
+        revert_on_panic(module, &mut shell, EVM_REVERT);
+        Artifact { wat: generate_module(module, &shell), entry_points }
+    }
+}
+
+pub struct NearBackend;
+
+impl ContractBackend for NearBackend {
+    fn lower(&self, module: &IRModule) -> Artifact {
+        let mut shell = Shell {
+            imports: vec![
+                "  (import \"env\" \"argument\" (func $near_argument (param i32) (result i64)))\n".to_string(),
+                "  (import \"env\" \"value_return\" (func $near_value_return (param i64)))\n".to_string(),
+                "  (import \"env\" \"storage_read\" (func $near_storage_read (param i32 i32) (result i64)))\n".to_string(),
+                "  (import \"env\" \"storage_write\" (func $near_storage_write (param i32 i32 i64)))\n".to_string(),
+            ],
+            internal: true,
+            ..Shell::default()
+        };
+        revert_on_panic(module, &mut shell, NEAR_REVERT);
+        let mut entry_points = Vec::new();
+        let kinds = crate::kinds::infer_module(module);
+        let mut offset = string_data_end(module);
+        let qualify = module.contracts.len() > 1;
+
+        for contract in &module.contracts {
+            // State keys follow the module's strings
+            let mut keys = Vec::new();
+            for global in &contract.state {
+                keys.push((offset, global.len(), global.replace('.', "_")));
+                shell.data.push((offset, global.clone()));
+                offset += global.len();
+            }
+            shell.functions.push(state_function(contract, &keys, false));
+            shell.functions.push(state_function(contract, &keys, true));
+
+            let entries = contract.constructor.iter().map(|name| (name, "new")).chain(
+                contract.methods.iter().map(|name| (name, name.rsplit('.').next().unwrap_or(name))),
+            );
+            for (name, method) in entries {
+                let Some(function) = kinds.get(name) else { continue };
+                let export = if qualify { format!("{}_{}", contract.name, method) } else { method.to_string() };
+                let constructor = contract.constructor.as_ref() == Some(name);
+                shell.functions.push(entry_function(contract, name, &export, function, constructor));
+                entry_points.push(export);
+            }
+        }
+
+        Artifact { wat: generate_module(module, &shell), entry_points }
+    }
+}
+
+/// Define `$panic` and `$revert` over the host's `revert`, `revert` being
+/// the latter's definition, when the module fails either way
+fn revert_on_panic(module: &IRModule, shell: &mut Shell, revert: &str) {
+    let calls = host_calls(module);
+    if !calls.contains("panic") && !calls.contains("revert") {
+        return;
+    }
+    shell.imports.push("  (import \"env\" \"revert\" (func $host_revert (param i32 i32)))\n".to_string());
+    shell.functions.push("  (func $panic (param i32 i32)\n    local.get 0\n    local.get 1\n    call $revert\n  )\n".to_string());
+    shell.functions.push(revert.to_string());
+    shell.provided.extend(["panic", "revert"]);
+    shell.heap = true;
+}
+
+/// The message as it is
+const NEAR_REVERT: &str = "  (func $revert (param i32 i32)
+    local.get 0
+    local.get 1
+    call $host_revert
+  )
+";
+
+/// The message as `Error(string)`: the selector, the offset and length
+/// words, then the bytes padded to a word. Fresh heap is zeroed, so only
+/// the bytes that aren't need writing.
+const EVM_REVERT: &str = "  (func $revert (param i32 i32)
+    (local i32 i32)
+    local.get 1
+    i32.const 31
+    i32.add
+    i32.const -32
+    i32.and
+    i32.const 68
+    i32.add
+    local.tee 3
+    call $__alloc
+    local.tee 2
+    ;; 0x08c379a0, stored little-endian
+    i32.const -1602632952
+    i32.store
+    local.get 2
+    i32.const 35
+    i32.add
+    i32.const 32
+    i32.store8
+    ;; The length, big-endian in the last bytes of its word
+    local.get 2
+    i32.const 64
+    i32.add
+    local.get 1
+    i32.const 24
+    i32.shr_u
+    i32.store8
+    local.get 2
+    i32.const 65
+    i32.add
+    local.get 1
+    i32.const 16
+    i32.shr_u
+    i32.store8
+    local.get 2
+    i32.const 66
+    i32.add
+    local.get 1
+    i32.const 8
+    i32.shr_u
+    i32.store8
+    local.get 2
+    i32.const 67
+    i32.add
+    local.get 1
+    i32.store8
+    local.get 2
+    i32.const 68
+    i32.add
+    local.get 0
+    local.get 1
+    memory.copy
+    local.get 2
+    local.get 3
+    call $host_revert
+  )
+";
+
+/// `$near_load_Contract` reads every state variable from storage, and
+/// `$near_save_Contract` writes them back
+fn state_function(contract: &IRContract, keys: &[(usize, usize, String)], save: bool) -> String {
+    let mut func = format!("  (func $near_{}_{}\n", if save { "save" } else { "load" }, contract.name);
+    for (ptr, len, global) in keys {
+        func.push_str(&format!("    i32.const {}\n    i32.const {}\n", ptr, len));
+        if save {
+            func.push_str(&format!("    global.get ${}\n    call $near_storage_write\n", global));
+        } else {
+            func.push_str(&format!("    call $near_storage_read\n    global.set ${}\n", global));
+        }
+    }
+    func.push_str("  )\n");
+    func
+}
+
+/// An exported entry point calling `name` with the host's arguments;
+/// the constructor starts from empty state rather than loading it. The
+/// host passes and takes each WASM value as an i64 of its own, so a
+/// String is two arguments, its pointer and length.
+fn entry_function(contract: &IRContract, name: &str, export: &str, kinds: &FunctionKinds, constructor: bool) -> String {
+    let id = format!("near_entry_{}", name.replace('.', "_"));
+    let (params, results) = (value_types(&kinds.params), value_types(&kinds.results));
+    let mut func = format!("  (func ${}\n", id);
+    if !results.is_empty() {
+        func.push_str(&format!("    (local {})\n", results.join(" ")));
+    }
+    if !constructor {
+        func.push_str(&format!("    call $near_load_{}\n", contract.name));
+    }
+    for (i, ty) in params.iter().enumerate() {
+        func.push_str(&format!("    i32.const {}\n    call $near_argument\n", i));
+        func.push_str(match *ty {
+            "i32" => "    i32.wrap_i64\n",
+            "f64" => "    f64.convert_i64_s\n",
+            _ => "",
+        });
+    }
+    func.push_str(&format!("    call ${}\n", name.replace('.', "_")));
+
+    // Results come off the stack last first
+    for i in (0..results.len()).rev() {
+        func.push_str(&format!("    local.set {}\n", i));
+    }
+    func.push_str(&format!("    call $near_save_{}\n", contract.name));
+    for (i, ty) in results.iter().enumerate() {
+        func.push_str(&format!("    local.get {}\n", i));
+        func.push_str(match *ty {
+            "i32" => "    i64.extend_i32_u\n",
+            "f64" => "    i64.trunc_sat_f64_s\n",
+            _ => "",
+        });
+        func.push_str("    call $near_value_return\n");
+    }
+    func.push_str("  )\n");
+    func.push_str(&format!("  (export \"{}\" (func ${}))\n", export, id));
+    func
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lower_source(source: &str) -> IRModule {
+        let stmts = crate::parser::Parser::new(crate::lexer::Lexer::new(source))
+            .parse()
+            .unwrap();
+        crate::lowering::lower(&stmts)
+    }
+
+    #[test]
+    fn test_near_entry_points_wrap_methods() {
+        let module = lower_source(r#"
+            contract Token {
+                state: ["total_supply"]
+                constructor(supply) {
+                    total_supply = supply
+                }
+                fn mint(amount) {
+                    total_supply = total_supply + amount
+                    return total_supply
+                }
+            }
+        "#);
+
+        let evm = ChainFamily::Evm.backend().lower(&module);
+        assert_eq!(evm.entry_points, vec!["Token.constructor", "Token.mint"]);
+        assert!(evm.wat.contains("(export \"Token.mint\" (func $Token_mint))"));
+
+        let near = ChainFamily::Near.backend().lower(&module);
+        assert_eq!(near.entry_points, vec!["new", "mint"]);
+        assert!(!near.wat.contains("(export \"Token.mint\""));
+        assert!(near.wat.contains("(export \"mint\" (func $near_entry_Token_mint))"));
+        assert!(near.wat.contains("(data (i32.const 0) \"Token.total_supply\")"));
+        assert!(near.wat.contains("call $near_storage_read\n    global.set $Token_total_supply"));
+
+        // The constructor starts from empty state; methods load it first
+        let constructor = &near.wat[near.wat.find("(func $near_entry_Token_constructor").unwrap()..];
+        let constructor = &constructor[..constructor.find("(export").unwrap()];
+        assert!(!constructor.contains("call $near_load_Token"));
+        assert!(constructor.contains("i32.const 0\n    call $near_argument\n    call $Token_constructor"));
+        assert!(near.wat.contains("call $near_load_Token\n    i32.const 0\n    call $near_argument\n    call $Token_mint"));
+    }
+
+    #[test]
+    fn test_panics_anywhere_revert_with_reason_data() {
+        let module = lower_source(r#"
+            fn positive(amount: Int) {
+                if amount <= 0 {
+                    panic("amount must be positive")
+                }
+                return amount
+            }
+            contract Vault {
+                state: ["total"]
+                fn deposit(amount: Int) {
+                    require(total < 100, "vault is full")
+                    total = total + positive(amount)
+                    return total
+                }
+            }
+        "#);
+        for family in [ChainFamily::Evm, ChainFamily::Near] {
+            let wat = family.backend().lower(&module).wat;
+            assert!(wat.contains("(import \"env\" \"revert\" (func $host_revert (param i32 i32)))"));
+            assert!(!wat.contains("(import \"env\" \"panic\""), "{}", family.name());
+            wasmparser::Validator::new()
+                .validate_all(&crate::codegen::binary::assemble(&wat).unwrap())
+                .unwrap();
+            assert_eq!(wat.contains("i32.const -1602632952"), family == ChainFamily::Evm);
+        }
+    }
+}
@@ -4,5 +4,13 @@
 /// - WASM (WebAssembly) - primary target
 /// - Bytecode - VM execution
 /// - Native - future target
+/// - Contracts - one backend per chain family, wrapping the WASM output
+/// - WASI - the WASM output, started and doing its I/O through WASI
+/// - Web - the WASM output with a JavaScript loader hosting it in a page
 
 pub mod wasm;
+pub mod binary;
+pub mod contract;
+pub mod strings;
+pub mod wasi;
+pub mod web;
@@ -0,0 +1,470 @@
+// The string runtime WASM modules carry
+//
+// String constants are static data, but concatenation makes new strings
+// at run time. A module that needs them gets a heap after its data, a
+// bump allocator over it that grows memory as needed, and intrinsics the
+// string expressions lower to:
+//
+// `a + b`       → call $str_concat: a new string, both copied in
+// `a == b`      → call $str_eq: the same bytes
+// `s[start..end]` → call $str_substr: the characters in between, shared
+// `len(s)`      → call $str_len: how many characters it has
+// `print(n)`    → call $str_from_int (or $str_from_bool) first: the
+//                 text print takes, written out on the heap
+// `str(n)`      → the same conversion, in place of the call
+//
+// A Float is printed as the host formats it, through the `str.from_float`
+// import, since shortest round-trip formatting is the host's to do.
+//
+// Strings never change once made, so a substring points into the one it
+// came from instead of copying, and nothing is ever freed. Strings are
+// UTF-8, and positions and lengths count characters (code points), as the
+// interpreter and the VM count them: the runtime walks the bytes to find
+// them, so a slice never splits a character.
+
+use std::collections::{BTreeSet, HashMap};
+
+use crate::ir::{IRInstr, IRModule, ValueKind};
+use crate::kinds::FunctionKinds;
+
+/// The intrinsics, by the names calls to them use
+pub const INTRINSICS: &[&str] = &["str.concat", "str.eq", "str.substr", "str.len"];
+
+/// The intrinsic `instr` is, given the kinds on the stack before it
+pub fn intrinsic_for(instr: &IRInstr, stack: &[ValueKind]) -> Option<&'static str> {
+    let strings = matches!(stack, [.., ValueKind::Str, ValueKind::Str]);
+    match instr {
+        IRInstr::Add | IRInstr::CheckedAdd(_) if strings => Some("str.concat"),
+        IRInstr::Eq | IRInstr::Ne if strings => Some("str.eq"),
+        IRInstr::CallStd(name) if name == "len" && stack.last() == Some(&ValueKind::Str) => Some("str.len"),
+        IRInstr::CallStd(name) => INTRINSICS.iter().copied().find(|intrinsic| intrinsic == name),
+        _ => None,
+    }
+}
+
+/// Whether a call to `name` writes its argument out as a String
+pub fn converts(name: &str) -> bool {
+    matches!(name, "str" | "to_string")
+}
+
+/// What a print or `str` call of the value on top of `stack` converts it
+/// with first, when that is not already a String
+pub fn conversion(instr: &IRInstr, stack: &[ValueKind]) -> Option<&'static str> {
+    match (instr, stack.last()) {
+        (IRInstr::CallStd(name), Some(kind)) if crate::prelude::prints(name) || converts(name) => match kind {
+            ValueKind::Int => Some("str.from_int"),
+            ValueKind::Float => Some("str.from_float"),
+            ValueKind::Bool => Some("str.from_bool"),
+            ValueKind::Str | ValueKind::Ref => None,
+        },
+        _ => None,
+    }
+}
+
+/// Whether `instr` is a `str` call its conversion stands in for: all but
+/// those of host values, which the host writes out
+pub fn converted_in_place(instr: &IRInstr, stack: &[ValueKind]) -> bool {
+    matches!(instr, IRInstr::CallStd(name) if converts(name)) && stack.last() != Some(&ValueKind::Ref)
+}
+
+/// The intrinsics `module` uses
+pub fn used(module: &IRModule, kinds: &HashMap<String, FunctionKinds>) -> BTreeSet<&'static str> {
+    let mut used = BTreeSet::new();
+    for func in &module.functions {
+        let stacks = &kinds[&func.name].stacks;
+        for (instr, stack) in func.instructions.iter().zip(stacks) {
+            used.extend(intrinsic_for(instr, stack));
+            used.extend(conversion(instr, stack).filter(|name| *name != "str.from_float"));
+        }
+    }
+    used
+}
+
+/// The runtime for the `used` intrinsics, with the heap starting at
+/// `heap_start` when one of them allocates or the module needs it anyway
+/// (`heap`)
+pub fn runtime(used: &BTreeSet<&str>, heap_start: usize, heap: bool) -> String {
+    let mut wat = String::new();
+    if heap || ["str.concat", "str.from_int", "str.from_bool"].iter().any(|name| used.contains(name)) {
+        // Aligned, past every constant
+        wat.push_str(&format!("  (global $__heap (mut i32) (i32.const {}))\n\n", heap_start.next_multiple_of(8)));
+        wat.push_str(ALLOC);
+        wat.push('\n');
+    }
+    if used.contains("str.concat") {
+        wat.push_str(CONCAT);
+        wat.push('\n');
+    }
+    if used.contains("str.eq") {
+        wat.push_str(EQ);
+        wat.push('\n');
+    }
+    if used.contains("str.substr") || used.contains("str.len") {
+        wat.push_str(OFFSET);
+        wat.push('\n');
+    }
+    if used.contains("str.substr") {
+        wat.push_str(SUBSTR);
+        wat.push('\n');
+    }
+    if used.contains("str.len") {
+        wat.push_str(LEN);
+        wat.push('\n');
+    }
+    if used.contains("str.from_int") {
+        wat.push_str(FROM_INT);
+        wat.push('\n');
+    }
+    if used.contains("str.from_bool") {
+        wat.push_str(FROM_BOOL);
+        wat.push('\n');
+    }
+    wat
+}
+
+/// `$__alloc`: `len` bytes from the heap, growing memory when they run
+/// past its end
+const ALLOC: &str = "  (func $__alloc (param i32) (result i32)
+    (local i32)
+    global.get $__heap
+    local.set 1
+    global.get $__heap
+    local.get 0
+    i32.add
+    global.set $__heap
+    block $fits
+      global.get $__heap
+      memory.size
+      i32.const 16
+      i32.shl
+      i32.le_u
+      br_if $fits
+      ;; The pages the rest takes, rounded up
+      global.get $__heap
+      memory.size
+      i32.const 16
+      i32.shl
+      i32.sub
+      i32.const 65535
+      i32.add
+      i32.const 16
+      i32.shr_u
+      memory.grow
+      i32.const -1
+      i32.eq
+      if
+        unreachable
+      end
+    end
+    local.get 1
+  )
+";
+
+/// `$str_concat`: (ptr, len) of a new string, the first then the second
+const CONCAT: &str = "  (func $str_concat (param i32 i32 i32 i32) (result i32 i32)
+    (local i32)
+    local.get 1
+    local.get 3
+    i32.add
+    call $__alloc
+    local.tee 4
+    local.get 0
+    local.get 1
+    memory.copy
+    local.get 4
+    local.get 1
+    i32.add
+    local.get 2
+    local.get 3
+    memory.copy
+    local.get 4
+    local.get 1
+    local.get 3
+    i32.add
+  )
+";
+
+/// `$str_eq`: whether two strings have the same length and bytes
+const EQ: &str = "  (func $str_eq (param i32 i32 i32 i32) (result i32)
+    (local i32)
+    local.get 1
+    local.get 3
+    i32.ne
+    if
+      i32.const 0
+      return
+    end
+    block $done
+      loop $next
+        local.get 4
+        local.get 1
+        i32.ge_u
+        br_if $done
+        local.get 0
+        local.get 4
+        i32.add
+        i32.load8_u
+        local.get 2
+        local.get 4
+        i32.add
+        i32.load8_u
+        i32.ne
+        if
+          i32.const 0
+          return
+        end
+        local.get 4
+        i32.const 1
+        i32.add
+        local.set 4
+        br $next
+      end
+    end
+    i32.const 1
+  )
+";
+
+/// `$__str_offset`: the byte offset `n` characters into a string, after
+/// each lead byte and the continuation bytes (10xxxxxx) following it;
+/// trapping when it has fewer
+const OFFSET: &str = "  (func $__str_offset (param i32 i32 i64) (result i32)
+    (local i32)
+    block $done
+      loop $next
+        local.get 2
+        i64.eqz
+        br_if $done
+        local.get 3
+        local.get 1
+        i32.ge_u
+        if
+          unreachable
+        end
+        local.get 3
+        i32.const 1
+        i32.add
+        local.set 3
+        block $lead
+          loop $continuation
+            local.get 3
+            local.get 1
+            i32.ge_u
+            br_if $lead
+            local.get 0
+            local.get 3
+            i32.add
+            i32.load8_u
+            i32.const 192
+            i32.and
+            i32.const 128
+            i32.ne
+            br_if $lead
+            local.get 3
+            i32.const 1
+            i32.add
+            local.set 3
+            br $continuation
+          end
+        end
+        local.get 2
+        i64.const 1
+        i64.sub
+        local.set 2
+        br $next
+      end
+    end
+    local.get 3
+  )
+";
+
+/// `$str_substr`: the characters of a string from `start` up to `end`,
+/// trapping unless 0 <= start <= end <= how many it has. The end is
+/// counted on from the start.
+const SUBSTR: &str = "  (func $str_substr (param i32 i32 i64 i64) (result i32 i32)
+    (local i32)
+    local.get 2
+    i64.const 0
+    i64.lt_s
+    local.get 2
+    local.get 3
+    i64.gt_s
+    i32.or
+    if
+      unreachable
+    end
+    local.get 0
+    local.get 1
+    local.get 2
+    call $__str_offset
+    local.tee 4
+    local.get 0
+    i32.add
+    local.get 0
+    local.get 4
+    i32.add
+    local.get 1
+    local.get 4
+    i32.sub
+    local.get 3
+    local.get 2
+    i64.sub
+    call $__str_offset
+  )
+";
+
+/// `$str_len`: the characters in a string, one per byte that does not
+/// continue a character (10xxxxxx)
+const LEN: &str = "  (func $str_len (param i32 i32) (result i64)
+    (local i32 i64)
+    block $done
+      loop $next
+        local.get 2
+        local.get 1
+        i32.ge_u
+        br_if $done
+        local.get 3
+        local.get 0
+        local.get 2
+        i32.add
+        i32.load8_u
+        i32.const 192
+        i32.and
+        i32.const 128
+        i32.ne
+        i64.extend_i32_u
+        i64.add
+        local.set 3
+        local.get 2
+        i32.const 1
+        i32.add
+        local.set 2
+        br $next
+      end
+    end
+    local.get 3
+  )
+";
+
+/// `$str_from_int`: an Int in decimal, its digits written backwards from
+/// the end of room for the longest, then its sign. The magnitude is taken
+/// unsigned, so the most negative Int has one.
+const FROM_INT: &str = "  (func $str_from_int (param i64) (result i32 i32)
+    (local i32 i32 i64)
+    i32.const 20
+    call $__alloc
+    i32.const 20
+    i32.add
+    local.tee 1
+    local.set 2
+    local.get 0
+    i64.const 0
+    i64.lt_s
+    if (result i64)
+      i64.const 0
+      local.get 0
+      i64.sub
+    else
+      local.get 0
+    end
+    local.set 3
+    loop $digit
+      local.get 2
+      i32.const 1
+      i32.sub
+      local.tee 2
+      local.get 3
+      i64.const 10
+      i64.rem_u
+      i32.wrap_i64
+      i32.const 48
+      i32.add
+      i32.store8
+      local.get 3
+      i64.const 10
+      i64.div_u
+      local.tee 3
+      i64.const 0
+      i64.ne
+      br_if $digit
+    end
+    local.get 0
+    i64.const 0
+    i64.lt_s
+    if
+      local.get 2
+      i32.const 1
+      i32.sub
+      local.tee 2
+      i32.const 45
+      i32.store8
+    end
+    local.get 2
+    local.get 1
+    local.get 2
+    i32.sub
+  )
+";
+
+/// `$str_from_bool`: "true", or "fals" and then an "e", the first four
+/// bytes stored as one little-endian word; a Bool is 1 or 0, so the
+/// length is 5 less it
+const FROM_BOOL: &str = "  (func $str_from_bool (param i32) (result i32 i32)
+    (local i32)
+    i32.const 5
+    call $__alloc
+    local.set 1
+    local.get 0
+    if
+      local.get 1
+      i32.const 1702195828
+      i32.store
+    else
+      local.get 1
+      i32.const 1936482662
+      i32.store
+      local.get 1
+      i32.const 4
+      i32.add
+      i32.const 101
+      i32.store8
+    end
+    local.get 1
+    i32.const 5
+    local.get 0
+    i32.sub
+  )
+";
+
+#[cfg(test)]
+mod tests {
+    use crate::codegen::{binary, wasm};
+
+    fn build(source: &str) -> String {
+        let stmts = crate::parser::Parser::new(crate::lexer::Lexer::new(source))
+            .parse()
+            .unwrap();
+        wasm::generate_wasm_module(&crate::lowering::lower(&stmts))
+    }
+
+    #[test]
+    fn test_string_expressions_use_the_runtime() {
+        let wat = build(r#"
+            fn greet(name: String) {
+                let greeting = "Hello, " + name
+                if greeting[0..5] == "Hello" {
+                    print(greeting)
+                }
+                return 0
+            }
+        "#);
+        assert!(wat.contains("call $str_concat") && wat.contains("call $str_substr") && wat.contains("call $str_eq"));
+        // The 12 bytes of constants, rounded up
+        assert!(wat.contains("(global $__heap (mut i32) (i32.const 16))"));
+        assert!(!wat.contains("\"str_concat\""), "intrinsics are not imports");
+        wasmparser::Validator::new().validate_all(&binary::assemble(&wat).unwrap()).unwrap();
+
+        // Only what is used comes along
+        let wat = build("fn main() {\n    print(\"static\")\n    return 0\n}\n");
+        assert!(!wat.contains("$__alloc") && !wat.contains("$str_"));
+    }
+}
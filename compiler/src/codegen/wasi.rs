@@ -0,0 +1,238 @@
+// The WASI target: modules that run as they are under wasmtime, wasmer
+// and other WASI runtimes, with no host glue of their own.
+//
+// A script build leaves its I/O to `env` imports that `astrixa run`
+// supplies. A WASI build defines those functions in the module instead,
+// over the `wasi_snapshot_preview1` imports every such runtime provides:
+// print/println → fd_write to stdout, a line each
+// input         → fd_read from stdin up to the end of the line, onto the
+//                 string heap
+// time          → clock_time_get: the realtime clock, in milliseconds
+// rand          → random_get, reduced below the bound
+// exit          → proc_exit
+// panic         → the message to stderr, then proc_exit(101)
+// and exports `_start`, which runs `main` and, when that returns an Int,
+// exits with it. Any other host call has no WASI counterpart, so it is an
+// error rather than an import no runtime would satisfy.
+
+use crate::codegen::wasm::{generate_module, host_calls, string_data_end, value_types, Shell};
+use crate::ir::{IRModule, ValueKind};
+
+/// The host calls a WASI module defines for itself
+pub const PROVIDED: &[&str] = &["exit", "input", "panic", "print", "println", "rand", "time"];
+
+/// The WASI functions, by name, as the module imports them
+const IMPORTS: &[(&str, &str)] = &[
+    ("fd_write", "(param i32 i32 i32 i32) (result i32)"),
+    ("fd_read", "(param i32 i32 i32 i32) (result i32)"),
+    ("clock_time_get", "(param i32 i64 i32) (result i32)"),
+    ("random_get", "(param i32 i32) (result i32)"),
+    ("proc_exit", "(param i32)"),
+];
+
+/// The module's text for a WASI runtime
+pub fn lower(module: &IRModule) -> Result<String, String> {
+    let calls = host_calls(module);
+    if let Some(call) = calls.iter().find(|call| !PROVIDED.contains(&call.as_str())) {
+        return Err(format!("'{}' has no WASI equivalent", call));
+    }
+    let kinds = crate::kinds::infer_module(module);
+    let main = kinds.get("main").ok_or("there is no main function to start")?;
+    if !main.params.is_empty() {
+        return Err("main must take no parameters to be started".to_string());
+    }
+
+    // Past the constants: two iovecs, an 8-byte result and a newline
+    let scratch = string_data_end(module).next_multiple_of(8);
+    let mut shell = Shell {
+        data: vec![(scratch, format!("{}\n", "\0".repeat(24)))],
+        internal: true,
+        provided: PROVIDED.to_vec(),
+        heap: calls.contains("input"),
+        ..Shell::default()
+    };
+    let mut wasi = Vec::new();
+    let line = ["print", "println", "panic"].iter().any(|name| calls.contains(*name));
+    if line {
+        shell.functions.push(line_function(scratch));
+        wasi.push("fd_write");
+    }
+    for call in &calls {
+        let (function, uses) = provided_function(call, scratch);
+        shell.functions.push(function);
+        wasi.extend(uses);
+    }
+
+    // `_start` exits with main's Int; anything else main returns is dropped
+    let mut start = "  (func $_start\n    call $main\n".to_string();
+    if main.results == [ValueKind::Int] {
+        start.push_str("    i32.wrap_i64\n    call $wasi_proc_exit\n");
+        wasi.push("proc_exit");
+    } else {
+        start.push_str(&"    drop\n".repeat(value_types(&main.results).len()));
+    }
+    start.push_str("  )\n  (export \"_start\" (func $_start))\n");
+    shell.functions.push(start);
+
+    for (name, signature) in IMPORTS {
+        if wasi.contains(name) {
+            let import = format!("  (import \"wasi_snapshot_preview1\" \"{}\" (func $wasi_{} {}))\n", name, name, signature);
+            shell.imports.push(import);
+        }
+    }
+    Ok(generate_module(module, &shell))
+}
+
+/// `$__wasi_line`: write (ptr, len) and a newline to a file descriptor
+fn line_function(scratch: usize) -> String {
+    format!(
+        "  (func $__wasi_line (param i32 i32 i32)
+    i32.const {iov}
+    local.get 1
+    i32.store
+    i32.const {iov_len}
+    local.get 2
+    i32.store
+    i32.const {newline_iov}
+    i32.const {newline}
+    i32.store
+    i32.const {newline_iov_len}
+    i32.const 1
+    i32.store
+    local.get 0
+    i32.const {iov}
+    i32.const 2
+    i32.const {result}
+    call $wasi_fd_write
+    drop
+  )
+",
+        iov = scratch,
+        iov_len = scratch + 4,
+        newline_iov = scratch + 8,
+        newline_iov_len = scratch + 12,
+        result = scratch + 16,
+        newline = scratch + 24,
+    )
+}
+
+/// The definition of the host call `name`, and the WASI functions it uses
+fn provided_function(name: &str, scratch: usize) -> (String, Vec<&'static str>) {
+    let (iov, result) = (scratch, scratch + 16);
+    match name {
+        "print" | "println" => (
+            format!("  (func ${} (param i32 i32)\n    i32.const 1\n    local.get 0\n    local.get 1\n    call $__wasi_line\n  )\n", name),
+            vec![],
+        ),
+        "panic" => (
+            "  (func $panic (param i32 i32)\n    i32.const 2\n    local.get 0\n    local.get 1\n    call $__wasi_line\n    i32.const 101\n    call $wasi_proc_exit\n  )\n".to_string(),
+            vec!["proc_exit"],
+        ),
+        "exit" => (
+            "  (func $exit (param i64)\n    local.get 0\n    i32.wrap_i64\n    call $wasi_proc_exit\n  )\n".to_string(),
+            vec!["proc_exit"],
+        ),
+        "time" => (
+            format!(
+                "  (func $time (result i64)\n    i32.const 0\n    i64.const 1000000\n    i32.const {result}\n    call $wasi_clock_time_get\n    drop\n    i32.const {result}\n    i64.load\n    i64.const 1000000\n    i64.div_u\n  )\n"
+            ),
+            vec!["clock_time_get"],
+        ),
+        "rand" => (
+            format!(
+                "  (func $rand (param i64) (result i64)\n    i32.const {result}\n    i32.const 8\n    call $wasi_random_get\n    drop\n    i32.const {result}\n    i64.load\n    local.get 0\n    i64.rem_u\n  )\n"
+            ),
+            vec!["random_get"],
+        ),
+        // A byte at a time, so nothing after the line is consumed; each
+        // byte lands right after the last on the heap
+        "input" => (
+            format!(
+                "  (func $input (result i32 i32)
+    (local i32)
+    global.get $__heap
+    local.set 0
+    block $done
+      loop $next
+        i32.const {iov}
+        i32.const 1
+        call $__alloc
+        i32.store
+        i32.const {iov_len}
+        i32.const 1
+        i32.store
+        i32.const 0
+        i32.const {iov}
+        i32.const 1
+        i32.const {result}
+        call $wasi_fd_read
+        br_if $done
+        ;; Nothing read is the end of input
+        i32.const {result}
+        i32.load
+        i32.eqz
+        br_if $done
+        global.get $__heap
+        i32.const 1
+        i32.sub
+        i32.load8_u
+        i32.const 10
+        i32.ne
+        br_if $next
+      end
+    end
+    ;; Give back the byte the newline or the end took
+    global.get $__heap
+    i32.const 1
+    i32.sub
+    global.set $__heap
+    local.get 0
+    global.get $__heap
+    local.get 0
+    i32.sub
+  )
+",
+                iov_len = iov + 4,
+            ),
+            vec!["fd_read"],
+        ),
+        _ => unreachable!("'{}' is not provided", name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lower_source(source: &str) -> IRModule {
+        let stmts = crate::parser::Parser::new(crate::lexer::Lexer::new(source))
+            .parse()
+            .unwrap();
+        crate::lowering::lower(&stmts)
+    }
+
+    #[test]
+    fn test_wasi_modules_start_themselves() {
+        let module = lower_source(r#"
+            fn main() {
+                let name = input()
+                print("Hello, " + name)
+                if rand(10) > time() {
+                    panic("time travel")
+                }
+                return 3
+            }
+        "#);
+        let wat = lower(&module).unwrap();
+        assert!(wat.contains("(import \"wasi_snapshot_preview1\" \"fd_read\""));
+        assert!(wat.contains("(export \"_start\" (func $_start))"));
+        assert!(!wat.contains("(import \"env\""), "needs no host glue");
+        assert!(!wat.contains("(export \"main\""));
+        wasmparser::Validator::new()
+            .validate_all(&crate::codegen::binary::assemble(&wat).unwrap())
+            .unwrap();
+
+        let module = lower_source("fn main() {\n    print(web3.keccak(\"a\"))\n}\n");
+        assert_eq!(lower(&module).unwrap_err(), "'web3.keccak' has no WASI equivalent");
+    }
+}
@@ -5,27 +5,36 @@
 /// Design principles:
 /// - Simple and correct (not optimized for size/speed yet)
 /// - Stack-based (natural fit with WASM)
-/// - Type-neutral (all values as i32 for now)
+/// - Typed by value kind (see `kinds`): Int is i64, Float f64, Bool i32
+///   and a String an i32 pointer and length
 /// - Produces valid, verifiable WAT
 /// 
 /// IR → WASM Mapping:
-/// LoadConstInt(n)  → i32.const n
-/// LoadConstFloat(f)→ f32.const f
-/// Add              → i32.add / f32.add
-/// Sub              → i32.sub / f32.sub
-/// Mul              → i32.mul / f32.mul
-/// Div              → i32.div_s / f32.div
-/// Mod              → i32.rem_s
+/// LoadConstInt(n)  → i64.const n
+/// LoadConstFloat(f)→ f64.const f
+/// Add              → i64.add / f64.add
+/// Sub              → i64.sub / f64.sub
+/// Mul              → i64.mul / f64.mul
+/// Div              → i64.div_s / f64.div
+/// Mod              → i64.rem_s
+/// Add on Strings   → call $str_concat (see `strings`)
+/// Eq / Ne on Strings → call $str_eq
 /// Return           → return
+/// LoadFunction(f)  → i32.const f's index in the module's table
+/// CallIndirect(n)  → call_indirect, typed as the call's signature
+/// Jump(n)          → br to the loop restarting at n or the block ending there
 /// etc.
 
-use crate::ir::{IRInstr, IRModule};
-use std::collections::{HashMap, HashSet};
+use crate::ir::{IRInstr, IRModule, ValueKind};
+use crate::kinds::{self, FunctionKinds};
+use super::strings;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 
 /// Memory allocator for strings (simple linear allocator)
 pub struct MemoryAllocator {
     offset: usize,
     strings: HashMap<String, (usize, usize)>, // string -> (ptr, len)
+    shared: HashSet<String>, // Strings pointing into another's bytes, with no segment of their own
 }
 
 impl MemoryAllocator {
@@ -33,9 +42,57 @@ impl MemoryAllocator {
         Self {
             offset: 0,
             strings: HashMap::new(),
+            shared: HashSet::new(),
         }
     }
 
+    /// Every string constant in `module`, laid out once for the whole
+    /// module. Packed, the longest go first, and a string found inside one
+    /// already laid out points into its bytes instead of taking its own.
+    fn for_module(module: &IRModule, pack: bool) -> Self {
+        let mut allocator = Self::new();
+        let mut strings: Vec<String> = module
+            .functions
+            .iter()
+            .flat_map(|func| &func.instructions)
+            .filter_map(|instr| match instr {
+                IRInstr::LoadConstString(s) => Some(s.clone()),
+                IRInstr::CheckedAdd(span) | IRInstr::CheckedSub(span) | IRInstr::CheckedMul(span) => {
+                    Some(crate::overflow::message(*span))
+                }
+                _ => None,
+            })
+            .collect();
+        if !pack {
+            for s in &strings {
+                allocator.allocate_string(s);
+            }
+            return allocator;
+        }
+
+        strings.sort_by(|a, b| b.len().cmp(&a.len()).then(a.cmp(b)));
+        strings.dedup();
+        for s in strings {
+            let mut owners: Vec<(&String, usize)> = allocator
+                .strings
+                .iter()
+                .filter(|(owner, _)| !allocator.shared.contains(*owner))
+                .map(|(owner, (ptr, _))| (owner, *ptr))
+                .collect();
+            owners.sort_by_key(|(_, ptr)| *ptr);
+            match owners.iter().find_map(|(owner, ptr)| Some(ptr + owner.find(s.as_str())?)) {
+                Some(ptr) if !owners.is_empty() => {
+                    allocator.strings.insert(s.clone(), (ptr, s.len()));
+                    allocator.shared.insert(s.clone());
+                }
+                _ => {
+                    allocator.allocate_string(&s);
+                }
+            }
+        }
+        allocator
+    }
+
     fn allocate_string(&mut self, s: &str) -> (usize, usize) {
         if let Some(&entry) = self.strings.get(s) {
             return entry;
@@ -48,10 +105,17 @@ impl MemoryAllocator {
         (ptr, len)
     }
 
+    /// One data segment per string, in address order so that the same
+    /// source always assembles to the same bytes
     fn get_data_section(&self) -> String {
         let mut data = String::new();
+        let mut strings: Vec<_> = self.strings.iter().collect();
+        strings.sort_by_key(|(_, (ptr, _))| *ptr);
 
-        for (s, (ptr, _len)) in &self.strings {
+        for (s, (ptr, _len)) in strings {
+            if self.shared.contains(s) {
+                continue;
+            }
             // Escape string for WAT data section
             let escaped = escape_wat_string(s);
             data.push_str(&format!("  (data (i32.const {}) \"{}\")\n", ptr, escaped));
@@ -63,78 +127,185 @@ impl MemoryAllocator {
 
 /// Generate WASM module from IR
 pub fn generate_wasm_module(module: &IRModule) -> String {
+    generate_module(module, &Shell::default())
+}
+
+/// Generate a WASM module from IR for a release build, its data as small
+/// as it can be: strings found inside others share their bytes
+pub fn generate_release_module(module: &IRModule) -> String {
+    generate_module(module, &Shell { pack: true, ..Shell::default() })
+}
+
+/// Generate a WASM module from IR whose host functions are imported the
+/// way an embedder's runtime names them
+pub fn generate_wasm_module_with(module: &IRModule, host: &HostImports) -> String {
+    generate_module(module, &Shell { host: host.clone(), ..Shell::default() })
+}
+
+/// Where a module imports its host functions from, for runtimes other
+/// than ours. By default they all come from `env`, under the names in
+/// `env_name`; an embedder can pick another import module and rename any
+/// of them, keyed by the stdlib call or host property as written:
+///
+///   let mut host = HostImports::new("astrixa_host");
+///   host.rename("print", "log_line");
+///   host.rename("msg.sender", "caller");
+#[derive(Debug, Clone)]
+pub struct HostImports {
+    pub module: String,
+    pub names: HashMap<String, String>, // Stdlib call or property -> the host's name for it
+}
+
+impl Default for HostImports {
+    fn default() -> Self {
+        Self::new("env")
+    }
+}
+
+impl HostImports {
+    pub fn new(module: &str) -> Self {
+        Self { module: module.to_string(), names: HashMap::new() }
+    }
+
+    /// Import `call` under the host's name `name`
+    pub fn rename(&mut self, call: &str, name: &str) -> &mut Self {
+        self.names.insert(call.to_string(), name.to_string());
+        self
+    }
+
+    /// The name `call` is imported under
+    pub fn name(&self, call: &str) -> String {
+        self.names.get(call).cloned().unwrap_or_else(|| env_name(call))
+    }
+}
+
+/// What a backend (a contract's chain family, or WASI) wraps around a
+/// module's functions
+#[derive(Default)]
+pub(crate) struct Shell {
+    pub imports: Vec<String>,        // Host imports of its own
+    pub data: Vec<(usize, String)>,  // Constants at offsets from `string_data_end`
+    pub functions: Vec<String>,      // Entry points, exporting themselves
+    pub internal: bool,              // Keep the module's own functions unexported
+    pub provided: Vec<&'static str>, // Host calls `functions` define rather than import
+    pub heap: bool,                  // `functions` allocate from the string heap
+    pub pack: bool,                  // Strings inside other strings share their bytes
+    pub host: HostImports,           // Where the module's host calls are imported from
+}
+
+/// Where the module's string constants end, so a backend's own data can
+/// follow them
+pub fn string_data_end(module: &IRModule) -> usize {
+    MemoryAllocator::for_module(module, false).offset
+}
+
+/// Generate a WASM module from IR, inside a contract backend's shell
+pub(crate) fn generate_module(module: &IRModule, shell: &Shell) -> String {
     let mut wasm = String::new();
-    let mut allocator = MemoryAllocator::new();
     
     // First pass: collect all strings
-    for func in &module.functions {
-        for instr in &func.instructions {
-            if let IRInstr::LoadConstString(s) = instr {
-                allocator.allocate_string(s);
-            }
-        }
-    }
+    let allocator = MemoryAllocator::for_module(module, shell.pack);
     
     // Module header
     wasm.push_str("(module\n");
     
     // Collect all stdlib imports needed (MUST come before memory and data)
-    let imports = collect_stdlib_imports(module);
+    let mut imports = host_calls(module);
+    imports.retain(|name| !shell.provided.contains(&name.as_str()));
     
     // Generate imports first (WASM requires imports to come first)
     for import in &imports {
-        wasm.push_str(&generate_import(import));
+        wasm.push_str(&generate_import(import, &shell.host));
+    }
+    for import in &shell.imports {
+        wasm.push_str(import);
     }
     
-    if !imports.is_empty() {
+    if !imports.is_empty() || !shell.imports.is_empty() {
         wasm.push_str("\n");
     }
     
-    // Export memory (1 page = 64KB, should be enough for now)
-    wasm.push_str("  (memory (export \"memory\") 1)\n\n");
+    // Export memory, as many 64 KiB pages as the module asks for
+    let limits = match module.memory.maximum {
+        Some(maximum) => format!("{} {}", module.memory.initial, maximum),
+        None => module.memory.initial.to_string(),
+    };
+    wasm.push_str(&format!("  (memory (export \"memory\") {})\n\n", limits));
+    
+    // Module globals (contract state), zero-initialized
+    for global in &module.globals {
+        wasm.push_str(&format!("  (global ${} (mut i64) (i64.const 0))\n", global.replace('.', "_")));
+    }
+    if !module.globals.is_empty() {
+        wasm.push('\n');
+    }
+    
+    // The functions loaded as values, each called through its index
+    let table = module.function_table();
+    if !table.is_empty() {
+        wasm.push_str(&format!("  (table {} funcref)\n", table.len()));
+        let ids: Vec<String> = table.iter().map(|name| format!("${}", name.replace('.', "_"))).collect();
+        wasm.push_str(&format!("  (elem (i32.const 0) func {})\n\n", ids.join(" ")));
+    }
     
     // Add data section if there are strings (comes after memory definition)
     let data_section = allocator.get_data_section();
     if !data_section.is_empty() {
         wasm.push_str(&data_section);
+    }
+    for (ptr, data) in &shell.data {
+        wasm.push_str(&format!("  (data (i32.const {}) \"{}\")\n", ptr, escape_wat_string(data)));
+    }
+    if !data_section.is_empty() || !shell.data.is_empty() {
         wasm.push_str("\n");
     }
     
-    // Generate each function with string allocator
+    // Generate each function, its strings at their module-wide addresses
+    let kinds = kinds::infer_module(module);
     for func in &module.functions {
-        let mut func_allocator = MemoryAllocator::new();
-        
-        // Collect strings for this function
-        for instr in &func.instructions {
-            if let IRInstr::LoadConstString(s) = instr {
-                func_allocator.allocate_string(s);
-            }
-        }
-        
-        wasm.push_str(&generate_function(
-            func.name.as_str(),
-            &func.instructions,
-            &func_allocator,
-            func.param_count,  // STEP 46: Pass param count
-            func.local_count,
-        ));
+        let generate = if shell.internal { define_function } else { generate_function };
+        wasm.push_str(&generate(func.name.as_str(), &func.instructions, &func.local_names, &allocator, &table, &kinds[&func.name]));
         wasm.push_str("\n");
     }
     
+    for function in &shell.functions {
+        wasm.push_str(function);
+        wasm.push('\n');
+    }
+    
+    // Overflow-checked arithmetic, for --checked-arith builds
+    for (checked, op) in [checked_add as fn(&IRInstr) -> bool, checked_sub, checked_mul].into_iter().zip(["add", "sub", "mul"]) {
+        if module.functions.iter().flat_map(|func| &func.instructions).any(checked) {
+            wasm.push_str(&checked_helper(op));
+            wasm.push('\n');
+        }
+    }
+    
+    // The string runtime, its heap after all the data
+    let data_end = shell.data.iter().map(|(ptr, data)| ptr + data.len()).fold(allocator.offset, usize::max);
+    wasm.push_str(&strings::runtime(&strings::used(module, &kinds), data_end, shell.heap));
+    
     // Module footer
     wasm.push_str(")\n");
     
     wasm
 }
 
-/// Collect all stdlib function calls from the module
-fn collect_stdlib_imports(module: &IRModule) -> HashSet<String> {
-    let mut imports = HashSet::new();
+/// Every host function the module calls, sorted by name: what it imports
+/// unless a backend provides them
+pub(crate) fn host_calls(module: &IRModule) -> BTreeSet<String> {
+    let mut imports = BTreeSet::new();
+    let kinds = kinds::infer_module(module);
     
     for func in &module.functions {
-        for instr in &func.instructions {
+        for (instr, stack) in func.instructions.iter().zip(&kinds[&func.name].stacks) {
+            // A Float is printed as the host writes it
+            if let Some(name @ "str.from_float") = strings::conversion(instr, stack) {
+                imports.insert(name.to_string());
+            }
             match instr {
-                IRInstr::CallStd(name) => {
+                IRInstr::CallStd(_) if strings::converted_in_place(instr, stack) => {}
+                IRInstr::CallStd(name) if strings::intrinsic_for(instr, stack).is_none() && name != "on_shutdown" => {
                     imports.insert(name.clone());
                 }
                 IRInstr::CallAI(name) => {
@@ -149,10 +320,20 @@ fn collect_stdlib_imports(module: &IRModule) -> HashSet<String> {
                     // STEP 54: Collect file system function imports
                     imports.insert(name.clone());
                 }
+                IRInstr::LoadProperty(name) => {
+                    imports.insert(name.clone());
+                }
                 IRInstr::Panic => {
                     // STEP 48: Include panic in imports
                     imports.insert("panic".to_string());
                 }
+                IRInstr::CheckedAdd(_) | IRInstr::CheckedSub(_) | IRInstr::CheckedMul(_) => {
+                    // Its helper panics on overflow
+                    imports.insert("panic".to_string());
+                }
+                IRInstr::Revert => {
+                    imports.insert("revert".to_string());
+                }
                 _ => {}
             }
         }
@@ -161,342 +342,707 @@ fn collect_stdlib_imports(module: &IRModule) -> HashSet<String> {
     imports
 }
 
-/// Generate WASM import declaration for a stdlib function
-fn generate_import(func_name: &str) -> String {
-    // Handle different stdlib functions with their signatures
-    match func_name {
-        // Core I/O
-        "print" => {
-            "  (import \"env\" \"print_str\" (func $print (param i32 i32)))\n".to_string()
-        }
-        "println" => {
-            "  (import \"env\" \"println_str\" (func $println (param i32 i32)))\n".to_string()
-        }
-        "input" => {
-            "  (import \"env\" \"input\" (func $input (result i32)))\n".to_string()
-        }
-        "len" => {
-            "  (import \"env\" \"len\" (func $len (param i32) (result i32)))\n".to_string()
-        }
-        "exit" => {
-            "  (import \"env\" \"exit\" (func $exit (param i32)))\n".to_string()
-        }
-        "panic" => {
-            // STEP 48: Panic import - takes ptr and len for error message
-            "  (import \"env\" \"panic\" (func $panic (param i32 i32)))\n".to_string()
-        }
-        
-        // Math functions
-        "abs" => {
-            "  (import \"env\" \"abs\" (func $abs (param i32) (result i32)))\n".to_string()
-        }
-        "pow" => {
-            "  (import \"env\" \"pow\" (func $pow (param i32 i32) (result i32)))\n".to_string()
-        }
-        "sqrt" => {
-            "  (import \"env\" \"sqrt\" (func $sqrt (param i32) (result i32)))\n".to_string()
-        }
-        "min" => {
-            "  (import \"env\" \"min\" (func $min (param i32 i32) (result i32)))\n".to_string()
-        }
-        "max" => {
-            "  (import \"env\" \"max\" (func $max (param i32 i32) (result i32)))\n".to_string()
-        }
-        "rand" => {
-            "  (import \"env\" \"rand\" (func $rand (param i32) (result i32)))\n".to_string()
-        }
-        
-        // Time functions
-        "time" => {
-            "  (import \"env\" \"time\" (func $time (result i32)))\n".to_string()
-        }
-        "sleep" => {
-            "  (import \"env\" \"sleep\" (func $sleep (param i32)))\n".to_string()
-        }
-        
-        // Crypto functions
-        "hash" => {
-            "  (import \"env\" \"hash\" (func $hash (param i32 i32) (result i32)))\n".to_string()
-        }
-        "keccak" => {
-            "  (import \"env\" \"keccak\" (func $keccak (param i32 i32) (result i32)))\n".to_string()
-        }
-        "sha256" => {
-            "  (import \"env\" \"sha256\" (func $sha256 (param i32 i32) (result i32)))\n".to_string()
-        }
-        
-        // STEP 52: AI functions
-        "ai.generate" => {
-            "  (import \"env\" \"ai_generate\" (func $ai_generate (param i32 i32) (result i32)))\n".to_string()
-        }
-        "ai.embed" => {
-            "  (import \"env\" \"ai_embed\" (func $ai_embed (param i32 i32) (result i32)))\n".to_string()
-        }
-        "ai.classify" => {
-            "  (import \"env\" \"ai_classify\" (func $ai_classify (param i32 i32) (result i32)))\n".to_string()
-        }
-        
-        // STEP 53: Web3 functions
-        "web3.wallet" => {
-            "  (import \"env\" \"web3_wallet\" (func $web3_wallet (result i32)))\n".to_string()
-        }
-        "web3.sign" => {
-            "  (import \"env\" \"web3_sign\" (func $web3_sign (param i32 i32) (result i32)))\n".to_string()
-        }
-        "web3.verify" => {
-            "  (import \"env\" \"web3_verify\" (func $web3_verify (param i32 i32 i32 i32) (result i32)))\n".to_string()
-        }
-        "web3.keccak" => {
-            "  (import \"env\" \"web3_keccak\" (func $web3_keccak (param i32 i32) (result i32)))\n".to_string()
-        }
-        "web3.balance" => {
-            "  (import \"env\" \"web3_balance\" (func $web3_balance (param i32) (result i32)))\n".to_string()
-        }
-        "web3.send" => {
-            "  (import \"env\" \"web3_send\" (func $web3_send (param i32 i32) (result i32)))\n".to_string()
-        }
-        
-        // STEP 54: File system functions
-        "fs.read" => {
-            "  (import \"env\" \"fs_read\" (func $fs_read (param i32 i32) (result i32)))\n".to_string()
-        }
-        "fs.write" => {
-            "  (import \"env\" \"fs_write\" (func $fs_write (param i32 i32 i32 i32) (result i32)))\n".to_string()
-        }
-        "fs.delete" => {
-            "  (import \"env\" \"fs_delete\" (func $fs_delete (param i32 i32) (result i32)))\n".to_string()
-        }
-        
-        _ => {
-            // Default: function with one i32 parameter
-            format!("  (import \"env\" \"{}\" (func ${} (param i32)))\n", func_name, func_name)
+/// Generate WASM import declaration for a host function: a stdlib call
+/// or a host property, typed by the kinds it takes and returns
+fn generate_import(func_name: &str, host: &HostImports) -> String {
+    let (params, results) = import_signature(func_name);
+    let mut import = format!(
+        "  (import \"{}\" \"{}\" (func ${}",
+        escape_wat_string(&host.module),
+        escape_wat_string(&host.name(func_name)),
+        func_name.replace('.', "_")
+    );
+    if !params.is_empty() {
+        import.push_str(&format!(" (param {})", value_types(&params).join(" ")));
+    }
+    if !results.is_empty() {
+        import.push_str(&format!(" (result {})", value_types(&results).join(" ")));
+    }
+    import.push_str("))\n");
+    import
+}
+
+/// The kinds a host function or property takes and returns
+pub(crate) fn import_signature(func_name: &str) -> (Vec<ValueKind>, Vec<ValueKind>) {
+    match func_name.split_once('.') {
+        Some((object, property)) if is_property(func_name) => {
+            let info = crate::stdlib::get_property_info(object, property).expect("a host property");
+            (Vec::new(), kinds::of_type(&info.returns))
         }
+        _ => kinds::host_signature(func_name),
     }
 }
 
+/// The name the host gives a host function under `env`: its own, but for
+/// the core I/O functions
+pub(crate) fn env_name(func_name: &str) -> String {
+    match func_name {
+        name if crate::prelude::prints(name) => format!("{}_str", name),
+        "str" => "to_string".to_string(),
+        "int" => "parse_int".to_string(), // Traps on anything but an integer
+        _ => func_name.replace('.', "_"),
+    }
+}
+
+fn checked_add(instr: &IRInstr) -> bool {
+    matches!(instr, IRInstr::CheckedAdd(_))
+}
+
+fn checked_sub(instr: &IRInstr) -> bool {
+    matches!(instr, IRInstr::CheckedSub(_))
+}
+
+fn checked_mul(instr: &IRInstr) -> bool {
+    matches!(instr, IRInstr::CheckedMul(_))
+}
+
+/// `$__checked_<op>`: the i64 operation, panicking with the message passed
+/// after the operands when it overflows. A
+/// sum overflowed when its sign differs from both operands', a difference
+/// when the operands' signs differ and its own differs from the first's,
+/// and a product when dividing it by one factor does not give the other.
+fn checked_helper(op: &str) -> String {
+    let mut helper = format!("  (func $__checked_{} (param i64 i64 i32 i32) (result i64)\n", op);
+    helper.push_str("    (local i64)\n");
+    helper.push_str(&format!("    local.get 0\n    local.get 1\n    i64.{}\n    local.set 4\n", op));
+    let overflowed = match op {
+        "add" => "    local.get 0\n    local.get 4\n    i64.xor\n    local.get 1\n    local.get 4\n    i64.xor\n    i64.and\n    i64.const 0\n    i64.lt_s\n",
+        "sub" => "    local.get 0\n    local.get 1\n    i64.xor\n    local.get 0\n    local.get 4\n    i64.xor\n    i64.and\n    i64.const 0\n    i64.lt_s\n",
+        // Dividing MIN by -1 traps on its own
+        _ => "    local.get 0\n    i64.eqz\n    if (result i32)\n      i32.const 0\n    else\n      local.get 4\n      local.get 0\n      i64.div_s\n      local.get 1\n      i64.ne\n    end\n",
+    };
+    helper.push_str(overflowed);
+    helper.push_str("    if\n      local.get 2\n      local.get 3\n      call $panic\n      unreachable\n    end\n");
+    helper.push_str("    local.get 4\n");
+    helper.push_str("  )\n");
+    helper
+}
+
+/// Whether an import is a host property such as `msg.sender`
+fn is_property(name: &str) -> bool {
+    name.split_once('.')
+        .is_some_and(|(object, property)| crate::stdlib::get_property_info(object, property).is_some())
+}
+
 /// Generate a single function in WASM
 pub fn generate_function(
     name: &str,
     instrs: &[IRInstr],
+    names: &[String],      // Its slots' names in the source, for debug info
     allocator: &MemoryAllocator,
-    param_count: usize,   // STEP 46: Number of parameters
-    local_count: usize,
+    table: &[String],      // The module's functions loaded as values
+    kinds: &FunctionKinds, // Its parameters, results, locals and stack
 ) -> String {
+    let mut func_def = define_function(name, instrs, names, allocator, table, kinds);
+    
+    // Export function (use original name for export)
+    func_def.push_str(&format!("  (export \"{}\" (func ${}))\n", name, name.replace('.', "_")));
+    
+    func_def
+}
+
+/// Generate a single function in WASM, without exporting it
+fn define_function(name: &str, instrs: &[IRInstr], names: &[String], allocator: &MemoryAllocator, table: &[String], kinds: &FunctionKinds) -> String {
     let mut func_def = String::new();
     
     // STEP 49: Sanitize function names for WASM (replace dots with underscores)
     let wasm_func_name = name.replace('.', "_");
     
     // STEP 46: Function definition with parameters and result
-    // Parameters are the first `param_count` locals
+    // Parameters are the first locals, a String's taking two; locals are
+    // named after their slots, so debuggers show them
     func_def.push_str(&format!("  (func ${}", wasm_func_name));
-    
-    // Add parameters (all as i32 for V1)
-    for _ in 0..param_count {
-        func_def.push_str(" (param i32)");
+    let ids = local_ids(&kinds.locals, names);
+    let params = value_types(&kinds.params);
+    for (param, id) in params.iter().zip(&ids) {
+        match id {
+            Some(id) => func_def.push_str(&format!(" (param ${} {})", id, param)),
+            None => func_def.push_str(&format!(" (param {})", param)),
+        }
+    }
+    if !kinds.results.is_empty() {
+        func_def.push_str(&format!(" (result {})", value_types(&kinds.results).join(" ")));
     }
+    func_def.push('\n');
     
-    // Add result type
-    func_def.push_str(" (result i32)\n");
+    // The body first, for the scratch locals it needs
+    let slots = local_indices(&kinds.locals);
+    let mut scratch = Scratch::new(slots.last().copied().unwrap_or(0));
+    let body = generate_body(instrs, allocator, table, kinds, &slots, &mut scratch);
     
     // STEP 46: Declare non-parameter local variables
     // In WASM, parameters are already declared, so we only need to declare
     // locals that are NOT parameters
-    let non_param_locals = if local_count > param_count {
-        local_count - param_count
-    } else {
-        0
-    };
-    
-    if non_param_locals > 0 {
-        func_def.push_str("    (local");
-        for _ in 0..non_param_locals {
-            func_def.push_str(" i32");
-        }
-        func_def.push_str(")\n");
+    let types = value_types(&kinds.locals[kinds.params.len()..]);
+    let ids = ids.into_iter().skip(params.len()).chain(std::iter::repeat(None));
+    let locals: Vec<_> = types.into_iter().chain(scratch.types.iter().copied()).zip(ids).collect();
+    if !locals.is_empty() {
+        func_def.push_str(&format!("    {}\n", declare_locals(&locals)));
     }
-    
-    // Generate function body
-    let body = generate_body(instrs, allocator);
     func_def.push_str(&body);
     
     // Function close
     func_def.push_str("  )\n");
     
-    // Export function (use original name for export)
-    func_def.push_str(&format!("  (export \"{}\" (func ${}))\n", name, wasm_func_name));
-    
     func_def
 }
 
+/// The WASM types values of these kinds take
+pub(crate) fn value_types(kinds: &[ValueKind]) -> Vec<&'static str> {
+    kinds
+        .iter()
+        .flat_map(|kind| match kind {
+            ValueKind::Int => &["i64"][..],
+            ValueKind::Float => &["f64"],
+            ValueKind::Bool | ValueKind::Ref => &["i32"],
+            ValueKind::Str => &["i32", "i32"],
+        })
+        .copied()
+        .collect()
+}
+
+/// The `$id`, without `$`, of each WASM local that slots with these kinds
+/// take: the slot's name, and for a String's length `name.len`. A name
+/// taken before, e.g. by shadowing, goes on as `name.2`, `name.3` and so on.
+fn local_ids(kinds: &[ValueKind], names: &[String]) -> Vec<Option<String>> {
+    let mut taken = HashSet::new();
+    let mut ids = Vec::new();
+    for (slot, kind) in kinds.iter().enumerate() {
+        let name = names.get(slot).map(|name| {
+            let mut ids = (1..).map(|n| if n == 1 { name.clone() } else { format!("{}.{}", name, n) });
+            ids.find(|id| !taken.contains(id)).unwrap_or_default()
+        });
+        taken.extend(name.clone());
+        ids.push(name.clone());
+        if *kind == ValueKind::Str {
+            ids.push(name.map(|name| format!("{}.len", name)));
+        }
+    }
+    ids
+}
+
+/// `(local ...)` declarations of these types and ids: each named local on
+/// its own, those in between without ids together
+fn declare_locals(locals: &[(&str, Option<String>)]) -> String {
+    let mut forms: Vec<String> = Vec::new();
+    let mut unnamed: Vec<&str> = Vec::new();
+    for (ty, id) in locals {
+        match id {
+            Some(id) => {
+                if !unnamed.is_empty() {
+                    forms.push(format!("(local {})", unnamed.join(" ")));
+                    unnamed.clear();
+                }
+                forms.push(format!("(local ${} {})", id, ty));
+            }
+            None => unnamed.push(ty),
+        }
+    }
+    if !unnamed.is_empty() {
+        forms.push(format!("(local {})", unnamed.join(" ")));
+    }
+    forms.join(" ")
+}
+
+/// Where each slot's values start among the WASM locals, and after the
+/// last, where the next free local is
+fn local_indices(locals: &[ValueKind]) -> Vec<u32> {
+    let mut indices = vec![0];
+    for kind in locals {
+        let next = indices.last().unwrap() + value_types(&[*kind]).len() as u32;
+        indices.push(next);
+    }
+    indices
+}
+
+/// Locals a function body needs beyond its slots, to set a value aside:
+/// one per type and use, after the slots
+struct Scratch {
+    first: u32,
+    types: Vec<&'static str>,
+    taken: HashMap<(&'static str, usize), u32>,
+}
+
+impl Scratch {
+    fn new(first: u32) -> Self {
+        Self { first, types: Vec::new(), taken: HashMap::new() }
+    }
+
+    /// Scratch local `n` of type `ty`
+    fn get(&mut self, ty: &'static str, n: usize) -> u32 {
+        let next = self.first + self.types.len() as u32;
+        *self.taken.entry((ty, n)).or_insert_with(|| {
+            self.types.push(ty);
+            next
+        })
+    }
+}
+
+/// Convert the value on top of the stack from `from` to `to`: nothing when
+/// they share a WASM type or a String is involved
+fn convert(from: ValueKind, to: ValueKind) -> &'static str {
+    use ValueKind::*;
+    match (from, to) {
+        (Int, Float) => "    f64.convert_i64_s\n",
+        (Float, Int) => "    i64.trunc_sat_f64_s\n",
+        (Int, Bool) => "    i64.const 0\n    i64.ne\n",
+        (Float, Bool) => "    f64.const 0\n    f64.ne\n",
+        (Int, Ref) => "    i32.wrap_i64\n",
+        (Bool | Ref, Int) => "    i64.extend_i32_u\n",
+        (Bool | Ref, Float) => "    f64.convert_i32_u\n",
+        _ => "",
+    }
+}
+
+/// Convert a binary operation's operands, `a` under `b`, to `to`; `b` is
+/// set aside while `a` converts
+fn convert_operands(code: &mut String, a: ValueKind, b: ValueKind, to: ValueKind, scratch: &mut Scratch) {
+    if !convert(a, to).is_empty() && b != ValueKind::Str {
+        let aside = scratch.get(value_types(&[b])[0], 0);
+        code.push_str(&format!("    local.set {}\n", aside));
+        code.push_str(convert(a, to));
+        code.push_str(&format!("    local.get {}\n", aside));
+    }
+    code.push_str(convert(b, to));
+}
+
+/// The kind two values are compared as: Float if either is, else Int if
+/// either is, else as they are
+fn comparison(a: ValueKind, b: ValueKind) -> ValueKind {
+    match (a, b) {
+        (ValueKind::Float, _) | (_, ValueKind::Float) => ValueKind::Float,
+        (ValueKind::Int, _) | (_, ValueKind::Int) => ValueKind::Int,
+        _ => a,
+    }
+}
+
+/// The instruction for an arithmetic or comparison `instr` on values of
+/// `kind`
+fn typed_op(instr: &IRInstr, kind: ValueKind) -> &'static str {
+    use ValueKind::*;
+    match (instr, kind) {
+        (IRInstr::Add, Float) => "f64.add",
+        (IRInstr::Sub, Float) => "f64.sub",
+        (IRInstr::Mul, Float) => "f64.mul",
+        (IRInstr::Div, Float) => "f64.div",
+        (IRInstr::Add, _) => "i64.add",
+        (IRInstr::Sub, _) => "i64.sub",
+        (IRInstr::Mul, _) => "i64.mul",
+        (IRInstr::Div, _) => "i64.div_s",
+        (IRInstr::Mod, _) => "i64.rem_s",
+        (IRInstr::Eq, Float) => "f64.eq",
+        (IRInstr::Ne, Float) => "f64.ne",
+        (IRInstr::Lt, Float) => "f64.lt",
+        (IRInstr::Le, Float) => "f64.le",
+        (IRInstr::Gt, Float) => "f64.gt",
+        (IRInstr::Ge, Float) => "f64.ge",
+        (IRInstr::Eq, Int) => "i64.eq",
+        (IRInstr::Ne, Int) => "i64.ne",
+        (IRInstr::Lt, Int) => "i64.lt_s",
+        (IRInstr::Le, Int) => "i64.le_s",
+        (IRInstr::Gt, Int) => "i64.gt_s",
+        (IRInstr::Ge, Int) => "i64.ge_s",
+        (IRInstr::Eq, _) => "i32.eq",
+        (IRInstr::Ne, _) => "i32.ne",
+        (IRInstr::Lt, _) => "i32.lt_s",
+        (IRInstr::Le, _) => "i32.le_s",
+        (IRInstr::Gt, _) => "i32.gt_s",
+        (IRInstr::Ge, _) => "i32.ge_s",
+        _ => "nop",
+    }
+}
+
+/// Test the value on top of the stack for zero
+fn is_zero(kind: ValueKind) -> &'static str {
+    match kind {
+        ValueKind::Int => "    i64.eqz\n",
+        ValueKind::Float => "    f64.const 0\n    f64.eq\n",
+        _ => "    i32.eqz\n",
+    }
+}
+
 /// Generate function body from IR instructions
-fn generate_body(instrs: &[IRInstr], allocator: &MemoryAllocator) -> String {
+fn generate_body(
+    instrs: &[IRInstr],
+    allocator: &MemoryAllocator,
+    table: &[String],
+    kinds: &FunctionKinds,
+    slots: &[u32],
+    scratch: &mut Scratch,
+) -> String {
     let mut body = String::new();
+    let regions = regions(instrs);
+    let mut open: Vec<&Region> = Vec::new();
     
-    for instr in instrs {
+    for (i, instr) in instrs.iter().enumerate() {
+        // The kinds of the values on top of the stack, the top last
+        let stack = &kinds.stacks[i];
+        let top = stack.last().copied().unwrap_or(ValueKind::Int);
+        let (a, b) = match stack[..] {
+            [.., a, b] => (a, b),
+            _ => (top, top),
+        };
+        let condition = match instr {
+            IRInstr::JumpIfFalse(_) => value_types(&[top]).first().copied(),
+            _ => None,
+        };
+        enter_regions(&mut body, &regions, &mut open, i, condition);
+        let mut code = String::new();
         match instr {
             // Constants
             IRInstr::LoadConstInt(n) => {
-                body.push_str(&format!("    i32.const {}\n", n));
+                code.push_str(&format!("    i64.const {}\n", n));
             }
             IRInstr::LoadConstFloat(f) => {
-                body.push_str(&format!("    f32.const {}\n", f));
+                code.push_str(&format!("    f64.const {}\n", f));
             }
             IRInstr::LoadConstBool(b) => {
                 let value = if *b { 1 } else { 0 };
-                body.push_str(&format!("    i32.const {}\n", value));
+                code.push_str(&format!("    i32.const {}\n", value));
             }
             IRInstr::LoadConstString(s) => {
                 // Load string as (ptr, len) for print_str
                 if let Some(&(ptr, len)) = allocator.strings.get(s) {
-                    body.push_str(&format!("    i32.const {}  ;; ptr to \"{}\"\n", ptr, escape_string(s)));
-                    body.push_str(&format!("    i32.const {}  ;; len\n", len));
+                    code.push_str(&format!("    i32.const {}  ;; ptr to \"{}\"\n", ptr, escape_string(s)));
+                    code.push_str(&format!("    i32.const {}  ;; len\n", len));
                 } else {
                     // Fallback: string not in allocator (shouldn't happen)
-                    body.push_str(&format!("    i32.const 0  ;; string not found: {}\n", escape_string(s)));
-                    body.push_str("    i32.const 0\n");
+                    code.push_str(&format!("    i32.const 0  ;; string not found: {}\n", escape_string(s)));
+                    code.push_str("    i32.const 0\n");
                 }
             }
             
-            // Variables
+            // Variables: a String slot is two locals, its pointer first
             IRInstr::LoadVar(name) => {
-                body.push_str(&format!("    local.get ${}\n", name));
+                code.push_str(&format!("    local.get ${}\n", name));
             }
             IRInstr::StoreVar(name) => {
-                body.push_str(&format!("    local.set ${}\n", name));
+                code.push_str(&format!("    local.set ${}\n", name));
             }
             IRInstr::LoadLocal(slot) => {
-                body.push_str(&format!("    local.get {}  ;; load from slot {}\n", slot, slot));
+                let slot = *slot as usize;
+                for local in slots[slot]..slots[slot + 1] {
+                    code.push_str(&format!("    local.get {}  ;; load from slot {}\n", local, slot));
+                }
             }
             IRInstr::StoreLocal(slot) => {
-                body.push_str(&format!("    local.set {}  ;; store to slot {}\n", slot, slot));
-            }
-            
-            // Arithmetic (i32)
-            IRInstr::Add => {
-                body.push_str("    i32.add\n");
+                let slot = *slot as usize;
+                code.push_str(convert(top, kinds.locals[slot]));
+                for local in (slots[slot]..slots[slot + 1]).rev() {
+                    code.push_str(&format!("    local.set {}  ;; store to slot {}\n", local, slot));
+                }
             }
-            IRInstr::Sub => {
-                body.push_str("    i32.sub\n");
+            IRInstr::LoadGlobal(name) => {
+                code.push_str(&format!("    global.get ${}\n", name.replace('.', "_")));
             }
-            IRInstr::Mul => {
-                body.push_str("    i32.mul\n");
+            IRInstr::StoreGlobal(name) => {
+                code.push_str(convert(top, ValueKind::Int));
+                code.push_str(&format!("    global.set ${}\n", name.replace('.', "_")));
             }
-            IRInstr::Div => {
-                body.push_str("    i32.div_s\n");
+            IRInstr::LoadProperty(name) => {
+                code.push_str(&format!("    call ${}\n", name.replace('.', "_")));
             }
-            IRInstr::Mod => {
-                body.push_str("    i32.rem_s\n");
+            IRInstr::LoadFunction(name) => {
+                let index = table.iter().position(|f| f == name).expect("a function in the table");
+                code.push_str(&format!("    i32.const {}  ;; ${}\n", index, name.replace('.', "_")));
             }
             
-            // Comparison (i32)
-            IRInstr::Eq => {
-                body.push_str("    i32.eq\n");
+            // Arithmetic, at the wider of the operands' kinds
+            IRInstr::Mod if kinds::arithmetic(a, b) == ValueKind::Float => {
+                // No f64.rem: a - trunc(a / b) * b
+                convert_operands(&mut code, a, b, ValueKind::Float, scratch);
+                let (x, y) = (scratch.get("f64", 0), scratch.get("f64", 1));
+                code.push_str(&format!("    local.set {}\n    local.tee {}\n", y, x));
+                code.push_str(&format!("    local.get {}\n    local.get {}\n    f64.div\n    f64.trunc\n", x, y));
+                code.push_str(&format!("    local.get {}\n    f64.mul\n    f64.sub\n", y));
             }
-            IRInstr::Ne => {
-                body.push_str("    i32.ne\n");
+            IRInstr::Add | IRInstr::CheckedAdd(_) if kinds::arithmetic(a, b) == ValueKind::Str => {
+                code.push_str("    call $str_concat\n");
             }
-            IRInstr::Lt => {
-                body.push_str("    i32.lt_s\n");
+            IRInstr::Add | IRInstr::Sub | IRInstr::Mul | IRInstr::Div | IRInstr::Mod => {
+                let kind = kinds::arithmetic(a, b);
+                convert_operands(&mut code, a, b, kind, scratch);
+                code.push_str(&format!("    {}\n", typed_op(instr, kind)));
             }
-            IRInstr::Le => {
-                body.push_str("    i32.le_s\n");
+            IRInstr::CheckedAdd(span) | IRInstr::CheckedSub(span) | IRInstr::CheckedMul(span) => {
+                // Floats do not overflow, they become infinite
+                let (wrapping, op) = match instr {
+                    IRInstr::CheckedAdd(_) => (IRInstr::Add, "add"),
+                    IRInstr::CheckedSub(_) => (IRInstr::Sub, "sub"),
+                    _ => (IRInstr::Mul, "mul"),
+                };
+                let kind = kinds::arithmetic(a, b);
+                convert_operands(&mut code, a, b, kind, scratch);
+                match kind {
+                    ValueKind::Float => code.push_str(&format!("    {}\n", typed_op(&wrapping, kind))),
+                    _ => {
+                        let message = crate::overflow::message(*span);
+                        let (ptr, len) = allocator.strings[&message];
+                        code.push_str(&format!("    i32.const {}  ;; ptr to \"{}\"\n", ptr, escape_string(&message)));
+                        code.push_str(&format!("    i32.const {}  ;; len\n", len));
+                        code.push_str(&format!("    call $__checked_{}\n", op));
+                    }
+                }
             }
-            IRInstr::Gt => {
-                body.push_str("    i32.gt_s\n");
+            
+            // Comparison
+            IRInstr::Eq | IRInstr::Ne if (a, b) == (ValueKind::Str, ValueKind::Str) => {
+                code.push_str("    call $str_eq\n");
+                if *instr == IRInstr::Ne {
+                    code.push_str("    i32.eqz\n");
+                }
             }
-            IRInstr::Ge => {
-                body.push_str("    i32.ge_s\n");
+            IRInstr::Eq | IRInstr::Ne | IRInstr::Lt | IRInstr::Le | IRInstr::Gt | IRInstr::Ge => {
+                let kind = comparison(a, b);
+                convert_operands(&mut code, a, b, kind, scratch);
+                code.push_str(&format!("    {}\n", typed_op(instr, kind)));
             }
             
             // Logical
             IRInstr::And => {
-                body.push_str("    i32.and\n");
+                convert_operands(&mut code, a, b, ValueKind::Bool, scratch);
+                code.push_str("    i32.and\n");
             }
             IRInstr::Or => {
-                body.push_str("    i32.or\n");
+                convert_operands(&mut code, a, b, ValueKind::Bool, scratch);
+                code.push_str("    i32.or\n");
             }
             IRInstr::Not => {
-                // NOT is: xor with 1
-                body.push_str("    i32.const 1\n");
-                body.push_str("    i32.xor\n");
+                code.push_str(is_zero(top));
+            }
+            
+            // Conversions
+            IRInstr::FloatToInt => {
+                code.push_str(convert(top, ValueKind::Int));
+            }
+            IRInstr::IntToFloat => {
+                code.push_str(convert(top, ValueKind::Float));
             }
             
-            // Control flow
+            // Control flow: a jump back restarts the loop at its target, a
+            // jump ahead leaves the block ending there (see `regions`)
             IRInstr::Jump(target) => {
-                body.push_str(&format!("    br {}\n", target));
+                code.push_str(&format!("    br {}\n", branch_label(i, *target)));
             }
             IRInstr::JumpIfFalse(target) => {
-                body.push_str("    i32.eqz\n");
-                body.push_str(&format!("    br_if {}\n", target));
+                code.push_str(is_zero(top));
+                code.push_str(&format!("    br_if {}\n", branch_label(i, *target)));
             }
             
             // Function calls
             IRInstr::Call(func_name, _arg_count) => {
                 // STEP 49: Sanitize function names for WASM (replace dots with underscores)
                 let wasm_func_name = func_name.replace('.', "_");
-                body.push_str(&format!("    call ${}\n", wasm_func_name));
+                code.push_str(&format!("    call ${}\n", wasm_func_name));
+            }
+            
+            // A call through a reference, which the runtime checks has the
+            // signature the call was typed with
+            IRInstr::CallIndirect(_) => {
+                let (params, results) = &kinds.indirect[&i];
+                code.push_str("    call_indirect");
+                if !params.is_empty() {
+                    code.push_str(&format!(" (param {})", value_types(params).join(" ")));
+                }
+                if !results.is_empty() {
+                    code.push_str(&format!(" (result {})", value_types(results).join(" ")));
+                }
+                code.push('\n');
             }
             
             // Stdlib calls
+            // No WASM host delivers signals, so the handler's name is dropped
+            IRInstr::CallStd(func_name) if func_name == "on_shutdown" => {
+                code.push_str("    drop\n    drop\n");
+            }
             IRInstr::CallStd(func_name) => {
-                body.push_str(&format!("    call ${}\n", func_name));
+                // Print takes a String, so anything else is written out
+                // first; for `str`, that is the whole call
+                if let Some(conversion) = strings::conversion(instr, &kinds.stacks[i]) {
+                    code.push_str(&format!("    call ${}\n", conversion.replace('.', "_")));
+                }
+                if !strings::converted_in_place(instr, &kinds.stacks[i]) {
+                    let name = strings::intrinsic_for(instr, &kinds.stacks[i]).unwrap_or(func_name);
+                    code.push_str(&format!("    call ${}\n", name.replace('.', "_")));
+                }
             }
             
             // STEP 52: AI calls
             IRInstr::CallAI(func_name) => {
                 // Sanitize AI function names (ai.generate -> ai_generate)
                 let wasm_func_name = func_name.replace('.', "_");
-                body.push_str(&format!("    call ${}\n", wasm_func_name));
+                code.push_str(&format!("    call ${}\n", wasm_func_name));
             }
             
             // STEP 53: Web3 calls
             IRInstr::CallWeb3(func_name) => {
                 // Sanitize Web3 function names (web3.wallet -> web3_wallet)
                 let wasm_func_name = func_name.replace('.', "_");
-                body.push_str(&format!("    call ${}\n", wasm_func_name));
+                code.push_str(&format!("    call ${}\n", wasm_func_name));
             }
             
             // STEP 54: File system calls
             IRInstr::CallFS(func_name) => {
                 // Sanitize FS function names (fs.read -> fs_read)
                 let wasm_func_name = func_name.replace('.', "_");
-                body.push_str(&format!("    call ${}\n", wasm_func_name));
+                code.push_str(&format!("    call ${}\n", wasm_func_name));
             }
             
-            // Return
+            // Return, as the function's result kind
             IRInstr::Return => {
-                body.push_str("    return\n");
+                match kinds.results[..] {
+                    // Only the default 0 after a final Void call meets a Str
+                    // result: it returns the empty string
+                    [ValueKind::Str] if top == ValueKind::Int => {
+                        code.push_str("    drop\n    i32.const 0\n    i32.const 0\n");
+                    }
+                    [result] => code.push_str(convert(top, result)),
+                    _ => {}
+                }
+                code.push_str("    return\n");
             }
             
             // STEP 48: Panic
             IRInstr::Panic => {
                 // Stack has (ptr, len) from LoadConstString
                 // Call the panic function which will abort execution
-                body.push_str("    call $panic\n");
+                code.push_str("    call $panic\n");
                 // Panic never returns, but WASM requires unreachable after a call that doesn't return
-                body.push_str("    unreachable\n");
+                code.push_str("    unreachable\n");
+            }
+            IRInstr::Revert => {
+                code.push_str("    call $revert\n");
+                code.push_str("    unreachable\n");
             }
             
             // Stack manipulation
             IRInstr::Pop => {
-                body.push_str("    drop\n");
+                for _ in value_types(&[top]) {
+                    code.push_str("    drop\n");
+                }
             }
             IRInstr::Dup => {
-                body.push_str("    local.tee $__dup_temp\n");
-                body.push_str("    local.get $__dup_temp\n");
+                // Set the value aside in this function's scratch locals,
+                // keeping its first part on the stack, then load it again
+                let types = value_types(&[top]);
+                let aside: Vec<u32> = types.iter().enumerate().map(|(n, ty)| scratch.get(ty, n)).collect();
+                for local in aside[1..].iter().rev() {
+                    code.push_str(&format!("    local.set {}\n", local));
+                }
+                code.push_str(&format!("    local.tee {}\n", aside[0]));
+                for local in aside[1..].iter().chain(&aside) {
+                    code.push_str(&format!("    local.get {}\n", local));
+                }
             }
             
             // Special
             IRInstr::Nop => {
-                body.push_str("    nop\n");
+                code.push_str("    nop\n");
             }
+            // Only the assembler reads it, noting where the line's code starts
+            IRInstr::Line(line, column) => {
+                code.push_str(&format!("    (@line {} {})\n", line, column));
+            }
+        }
+        for line in code.lines() {
+            body.push_str(&"  ".repeat(open.len()));
+            body.push_str(line);
+            body.push('\n');
         }
     }
+    enter_regions(&mut body, &regions, &mut open, instrs.len(), None);
+    // A block ending with the body is left only by jumps after a return,
+    // as when both arms of a final if/else return: nothing follows it
+    if regions.iter().any(|region| region.end == instrs.len()) {
+        body.push_str("    unreachable\n");
+    }
     
     body
 }
 
+/// A stretch of a function body that branches can name. WASM has no jumps
+/// to arbitrary instructions: a branch either restarts a `loop` or leaves
+/// a `block`, and those nest. IR jumps come from structured source, so
+/// each jump back to an instruction makes a loop from there to the jump,
+/// and each jump ahead makes a block from the jump to its target.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Region {
+    start: usize,
+    end: usize, // Exclusive; a block's end is its jumps' target
+    is_loop: bool,
+    label: usize, // The instruction its branches target
+}
+
+/// The regions `instrs` branch to, outermost first where they start
+/// together
+fn regions(instrs: &[IRInstr]) -> Vec<Region> {
+    let mut loops: BTreeMap<usize, usize> = BTreeMap::new(); // Target to end
+    let mut blocks: BTreeMap<usize, usize> = BTreeMap::new(); // Target to start
+    for (i, instr) in instrs.iter().enumerate() {
+        if let IRInstr::Jump(target) | IRInstr::JumpIfFalse(target) = instr {
+            if *target <= i {
+                let end = loops.entry(*target).or_insert(i + 1);
+                *end = (*end).max(i + 1);
+            } else {
+                let start = blocks.entry(*target).or_insert(i);
+                *start = (*start).min(i);
+            }
+        }
+    }
+    let mut regions: Vec<Region> = loops
+        .into_iter()
+        .map(|(label, end)| Region { start: label, end, is_loop: true, label })
+        .chain(blocks.into_iter().map(|(label, start)| Region { start, end: label, is_loop: false, label }))
+        .collect();
+
+    // A block starting inside a region it outlasts starts before that
+    // region instead, so the two nest: leaving a while loop's block
+    // from its condition wraps the whole loop
+    let mut widened = true;
+    while widened {
+        widened = false;
+        for b in 0..regions.len() {
+            for r in 0..regions.len() {
+                let (block, other) = (regions[b], regions[r]);
+                if !block.is_loop && other.start < block.start && block.start < other.end && other.end < block.end {
+                    regions[b].start = other.start;
+                    widened = true;
+                }
+            }
+        }
+    }
+    regions.sort_by_key(|r| (r.start, std::cmp::Reverse(r.end), r.is_loop));
+    regions
+}
+
+/// Close the regions ending at instruction `at` and open those starting
+/// there. Blocks opening at a conditional jump take its condition, already
+/// on the stack, as a parameter of the `condition` type.
+fn enter_regions<'r>(body: &mut String, regions: &'r [Region], open: &mut Vec<&'r Region>, at: usize, condition: Option<&str>) {
+    while open.last().is_some_and(|region| region.end == at) {
+        open.pop();
+        body.push_str(&format!("{}    end\n", "  ".repeat(open.len())));
+    }
+    for region in regions.iter().filter(|region| region.start == at) {
+        let (kind, label) = match region.is_loop {
+            true => ("loop", format!("$loop{}", region.label)),
+            false => ("block", format!("$block{}", region.label)),
+        };
+        let param = condition.map(|ty| format!(" (param {})", ty)).unwrap_or_default();
+        body.push_str(&format!("{}    {} {}{}\n", "  ".repeat(open.len()), kind, label, param));
+        open.push(region);
+    }
+}
+
+/// The label a jump at `from` to `target` branches to
+fn branch_label(from: usize, target: usize) -> String {
+    match target <= from {
+        true => format!("$loop{}", target),
+        false => format!("$block{}", target),
+    }
+}
+
+
 /// Escape strings for WAT comments
 fn escape_string(s: &str) -> String {
     s.replace("\\", "\\\\")
@@ -526,61 +1072,10 @@ fn escape_wat_string(s: &str) -> String {
 
 /// Generate standalone WAT function (for testing)
 pub fn generate_wat(function_name: &str, instrs: &[IRInstr]) -> String {
-    let mut wat = String::new();
-    
-    wat.push_str("(module\n");
-    wat.push_str(&format!("  (func ${} (result i32)\n", function_name));
-    
-    for instr in instrs {
-        match instr {
-            IRInstr::LoadConstInt(n) => {
-                wat.push_str(&format!("    i32.const {}\n", n));
-            }
-            IRInstr::Add => {
-                wat.push_str("    i32.add\n");
-            }
-            IRInstr::Sub => {
-                wat.push_str("    i32.sub\n");
-            }
-            IRInstr::Mul => {
-                wat.push_str("    i32.mul\n");
-            }
-            IRInstr::Div => {
-                wat.push_str("    i32.div_s\n");
-            }
-            IRInstr::Mod => {
-                wat.push_str("    i32.rem_s\n");
-            }
-            IRInstr::Eq => {
-                wat.push_str("    i32.eq\n");
-            }
-            IRInstr::Ne => {
-                wat.push_str("    i32.ne\n");
-            }
-            IRInstr::Lt => {
-                wat.push_str("    i32.lt_s\n");
-            }
-            IRInstr::Le => {
-                wat.push_str("    i32.le_s\n");
-            }
-            IRInstr::Gt => {
-                wat.push_str("    i32.gt_s\n");
-            }
-            IRInstr::Ge => {
-                wat.push_str("    i32.ge_s\n");
-            }
-            IRInstr::Return => {
-                wat.push_str("    return\n");
-            }
-            _ => {}
-        }
-    }
-    
-    wat.push_str("  )\n");
-    wat.push_str(&format!("  (export \"{}\" (func ${}))\n", function_name, function_name));
-    wat.push_str(")\n");
-    
-    wat
+    let mut function = crate::ir::IRFunction::new(function_name.to_string());
+    function.instructions = instrs.to_vec();
+    let kinds = kinds::infer(&function, &HashMap::new(), &[]);
+    format!("(module\n{})\n", generate_function(function_name, instrs, &[], &MemoryAllocator::new(), &[], &kinds))
 }
 
 #[cfg(test)]
@@ -600,9 +1095,9 @@ mod tests {
         let wat = generate_wat("add_test", &instrs);
         
         // Verify it contains expected WASM instructions
-        assert!(wat.contains("i32.const 5"));
-        assert!(wat.contains("i32.const 3"));
-        assert!(wat.contains("i32.add"));
+        assert!(wat.contains("i64.const 5"));
+        assert!(wat.contains("i64.const 3"));
+        assert!(wat.contains("i64.add"));
         assert!(wat.contains("return"));
         assert!(wat.contains("(export \"add_test\""));
     }
@@ -618,9 +1113,9 @@ mod tests {
 
         let wat = generate_wat("mul_test", &instrs);
         
-        assert!(wat.contains("i32.const 4"));
-        assert!(wat.contains("i32.const 5"));
-        assert!(wat.contains("i32.mul"));
+        assert!(wat.contains("i64.const 4"));
+        assert!(wat.contains("i64.const 5"));
+        assert!(wat.contains("i64.mul"));
         assert!(wat.contains("(export \"mul_test\""));
     }
 
@@ -635,7 +1130,7 @@ mod tests {
 
         let wat = generate_wat("cmp_test", &instrs);
         
-        assert!(wat.contains("i32.lt_s"));
+        assert!(wat.contains("i64.lt_s"));
         assert!(wat.contains("(export \"cmp_test\""));
     }
 
@@ -654,10 +1149,27 @@ mod tests {
         assert!(wasm.contains("(module"));
         assert!(wasm.contains("(memory (export \"memory\")"));
         assert!(wasm.contains("(func $test"));
-        assert!(wasm.contains("i32.const 42"));
+        assert!(wasm.contains("i64.const 42"));
         assert!(wasm.contains("(export \"test\""));
         assert!(wasm.contains(")"));
     }
+
+    #[test]
+    fn test_tuple_return_uses_multi_value_result() {
+        use crate::ir::{IRFunction, IRModule};
+        
+        let mut module = IRModule::new();
+        let mut func = IRFunction::new("pair".to_string());
+        func.result_count = 2;
+        func.add_instruction(IRInstr::LoadConstInt(1));
+        func.add_instruction(IRInstr::LoadConstInt(2));
+        func.add_instruction(IRInstr::Return);
+        module.add_function(func);
+        
+        let wasm = generate_wasm_module(&module);
+        
+        assert!(wasm.contains("(func $pair (result i64 i64)"));
+    }
     
     #[test]
     fn test_stdlib_call() {
@@ -666,9 +1178,13 @@ mod tests {
         let mut module = IRModule::new();
         let mut func = IRFunction::new("main".to_string());
         
-        // print(42)
+        // print(42), print(2.5) and print(true)
         func.add_instruction(IRInstr::LoadConstInt(42));
         func.add_instruction(IRInstr::CallStd("print".to_string()));
+        func.add_instruction(IRInstr::LoadConstFloat(2.5));
+        func.add_instruction(IRInstr::CallStd("print".to_string()));
+        func.add_instruction(IRInstr::LoadConstBool(true));
+        func.add_instruction(IRInstr::CallStd("println".to_string()));
         func.add_instruction(IRInstr::LoadConstInt(0));
         func.add_instruction(IRInstr::Return);
         
@@ -676,10 +1192,13 @@ mod tests {
         
         let wasm = generate_wasm_module(&module);
         
-        // Should have import for print_str (not print anymore)
-        assert!(wasm.contains("(import \"env\" \"print_str\""));
-        assert!(wasm.contains("call $print"));
+        // Print takes a String, so each value is written out as one first
+        assert!(wasm.contains("(import \"env\" \"print_str\" (func $print (param i32 i32)))"));
+        assert!(wasm.contains("    call $str_from_int\n    call $print\n"));
+        assert!(wasm.contains("(import \"env\" \"str_from_float\" (func $str_from_float (param f64) (result i32 i32)))"));
+        assert!(wasm.contains("    call $str_from_bool\n    call $println\n"));
         assert!(wasm.contains("(memory (export \"memory\")"));
+        crate::codegen::binary::validate(&crate::codegen::binary::assemble(&wasm).unwrap()).unwrap();
     }
     
     #[test]
@@ -750,4 +1269,192 @@ mod tests {
         
         assert!(escaped.contains("\\n"));
     }
+
+    #[test]
+    fn test_branches_name_enclosing_blocks_and_loops() {
+        use crate::lexer::Lexer;
+        use crate::parser::Parser;
+
+        let source = "fn main() {\n    let mut total = 0\n    let mut i = 0\n    while i < 10 {\n        if i > 5 {\n            total = total + i\n        } else {\n            total = total - 1\n        }\n        i = i + 1\n    }\n    return total\n}\n";
+        let module = crate::lowering::lower(&Parser::new(Lexer::new(source)).parse().unwrap());
+        let wat = generate_wasm_module(&module);
+
+        // Every branch names a region it is inside, and every region ends
+        let mut open = Vec::new();
+        for line in wat.lines().map(str::trim) {
+            let mut words = line.split_whitespace();
+            match (words.next(), words.next()) {
+                (Some("block" | "loop"), Some(label)) => open.push(label),
+                (Some("end"), _) => assert!(open.pop().is_some(), "{}", wat),
+                (Some("br" | "br_if"), Some(label)) => assert!(open.contains(&label), "{} in {}", label, wat),
+                _ => {}
+            }
+        }
+        assert!(open.is_empty(), "{}", wat);
+        assert_eq!(wat.matches("    loop $loop").count(), 1);
+        assert_eq!(wat.matches("block $block").count(), 3);
+        // A block left by a conditional jump takes its condition
+        assert!(wat.contains("(param i32)\n") && wat.contains("    br $loop"));
+    }
+
+    #[test]
+    fn test_dup_copies_through_declared_scratch_locals() {
+        use crate::ir::{IRFunction, IRModule};
+
+        // (n, n), (x, x) and (s, s) from an Int, a Float and a String
+        let mut module = IRModule::new();
+        for (name, load, kind) in [
+            ("int", IRInstr::LoadConstInt(7), ValueKind::Int),
+            ("float", IRInstr::LoadConstFloat(1.5), ValueKind::Float),
+            ("string", IRInstr::LoadConstString("hi".to_string()), ValueKind::Str),
+        ] {
+            let mut func = IRFunction::new(name.to_string());
+            func.result_count = 2;
+            func.local_count = 1;
+            for instr in [load, IRInstr::Dup, IRInstr::StoreLocal(0), IRInstr::LoadLocal(0), IRInstr::Return] {
+                func.add_instruction(instr);
+            }
+            assert_eq!(kinds::infer(&func, &HashMap::new(), &[]).results, [kind, kind]);
+            module.add_function(func);
+        }
+
+        let wat = generate_wasm_module(&module);
+        assert!(!wat.contains("$__dup"));
+        assert!(wat.contains("(local i64 i64)") && wat.contains("(local f64 f64)") && wat.contains("(local i32 i32 i32 i32)"));
+        let bytes = crate::codegen::binary::assemble(&wat).unwrap();
+        wasmparser::Validator::new().validate_all(&bytes).unwrap();
+    }
+
+    #[test]
+    fn test_function_values_call_through_the_table() {
+        use crate::lexer::Lexer;
+        use crate::parser::Parser;
+
+        let source = r#"
+            fn double(n: Int) -> Int {
+                return n * 2
+            }
+            fn half(x: Float) -> Float {
+                return x / 2.0
+            }
+            fn apply(f: fn(Int) -> Int, n: Int) -> Int {
+                return f(n)
+            }
+            fn main() {
+                let h = half
+                let quarter = h(h(1.0))
+                return apply(double, 21)
+            }
+        "#;
+        let module = crate::lowering::lower(&Parser::new(Lexer::new(source)).parse().unwrap());
+        assert_eq!(module.function_table(), ["half", "double"]);
+        let wat = generate_wasm_module(&module);
+        assert!(wat.contains("(table 2 funcref)") && wat.contains("(elem (i32.const 0) func $half $double)"));
+        assert!(wat.contains("    i32.const 1  ;; $double"));
+        // Each call is typed as the function in the table it passes values to
+        assert!(wat.contains("call_indirect (param i64) (result i64)"));
+        assert!(wat.contains("call_indirect (param f64) (result f64)"));
+        crate::codegen::binary::validate(&crate::codegen::binary::assemble(&wat).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_builds_are_reproducible() {
+        use crate::codegen::binary::assemble;
+        use crate::codegen::contract::ChainFamily;
+        use crate::lexer::Lexer;
+        use crate::parser::Parser;
+
+        let source = r#"
+            contract Vault {
+                state total: Int
+                fn deposit(amount: Int) {
+                    total = total + amount
+                    print("deposited")
+                }
+            }
+            fn greet(name: String) {
+                print("hello")
+                print(name)
+            }
+            fn main() {
+                greet("world")
+                print(str(len("abc")))
+                print("done")
+            }
+        "#;
+        // Each build starts from scratch, so no map's iteration order carries over
+        let build = || {
+            let module = crate::lowering::lower(&Parser::new(Lexer::new(source)).parse().unwrap());
+            let wat = generate_wasm_module(&module);
+            let contract = ChainFamily::Evm.backend().lower(&module).wat;
+            (assemble(&wat).unwrap(), assemble(&contract).unwrap(), wat)
+        };
+        let (binary, contract, wat) = build();
+        for _ in 0..20 {
+            let again = build();
+            assert!(again.0 == binary && again.1 == contract, "{}\n{}", wat, again.2);
+        }
+
+        // Strings are laid out once for the whole module, in address order,
+        // and each function points at the right one
+        let segments: Vec<(usize, &str)> = wat
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix("(data (i32.const "))
+            .map(|rest| {
+                let (ptr, text) = rest.split_once(") \"").unwrap();
+                (ptr.parse().unwrap(), text.trim_end_matches("\")"))
+            })
+            .collect();
+        assert_eq!(segments.len(), 5);
+        assert!(segments.windows(2).all(|pair| pair[0].0 + pair[0].1.len() == pair[1].0));
+        for (ptr, text) in &segments {
+            assert!(wat.contains(&format!("i32.const {}  ;; ptr to \"{}\"", ptr, text)), "{}", wat);
+        }
+        let imports: Vec<&str> = wat.lines().filter(|line| line.contains("(import")).collect();
+        let mut sorted = imports.clone();
+        sorted.sort();
+        assert_eq!(imports, sorted);
+    }
+
+    #[test]
+    fn test_release_modules_share_string_data() {
+        use crate::codegen::binary::assemble;
+        use crate::lexer::Lexer;
+        use crate::parser::Parser;
+
+        let source = "fn main() {\n    print(\"hello world\")\n    print(\"world\")\n    print(\"hello\")\n    print(\"bye\")\n}\n";
+        let module = crate::lowering::lower(&Parser::new(Lexer::new(source)).parse().unwrap());
+        let (plain, release) = (generate_wasm_module(&module), generate_release_module(&module));
+
+        assert_eq!(plain.matches("(data (i32.const").count(), 4);
+        assert_eq!(release.matches("(data (i32.const").count(), 2, "{}", release);
+        assert!(release.contains("(data (i32.const 0) \"hello world\")") && release.contains("(data (i32.const 11) \"bye\")"));
+        assert!(release.contains("i32.const 6  ;; ptr to \"world\"") && release.contains("i32.const 0  ;; ptr to \"hello\""));
+
+        let (plain, release) = (assemble(&plain).unwrap(), assemble(&release).unwrap());
+        crate::codegen::binary::validate(&release).unwrap();
+        assert!(release.len() < plain.len());
+    }
+
+    #[test]
+    fn test_embedders_rename_host_imports() {
+        use crate::codegen::binary::assemble;
+        use crate::lexer::Lexer;
+        use crate::parser::Parser;
+
+        let source = "fn main() {\n    let who = msg.sender\n    print(\"hi\")\n    return sqrt(16)\n}\n";
+        let module = crate::lowering::lower(&Parser::new(Lexer::new(source)).parse().unwrap());
+
+        let mut host = HostImports::new("astrixa_host");
+        host.rename("print", "log_line").rename("msg.sender", "caller");
+        let wat = generate_wasm_module_with(&module, &host);
+        assert!(wat.contains("(import \"astrixa_host\" \"log_line\" (func $print"), "{}", wat);
+        assert!(wat.contains("(import \"astrixa_host\" \"caller\" (func $msg_sender"));
+        assert!(wat.contains("(import \"astrixa_host\" \"sqrt\""), "the rest keep their names");
+        assert!(!wat.contains("\"env\""));
+        assert!(wat.contains("call $print"), "calls inside the module are unchanged");
+        crate::codegen::binary::validate(&assemble(&wat).unwrap()).unwrap();
+
+        assert_eq!(generate_wasm_module_with(&module, &HostImports::default()), generate_wasm_module(&module));
+    }
 }
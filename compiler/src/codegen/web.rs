@@ -0,0 +1,318 @@
+// The web target: a module, and the JavaScript that runs it in a page.
+//
+// A script build imports its host functions from `env` and leaves
+// providing them to whoever instantiates it. `--target=web` writes that
+// host as well: `<name>.js`, an ES module whose `load()` fetches and
+// instantiates the WASM with every import it needs implemented and hands
+// back its functions as plain JavaScript ones (strings in and out, Ints as
+// BigInts), and `<name>.d.ts`, their types.
+//
+// WASM imports are synchronous, so the defaults cover what a page can
+// answer at once: printing to the console, input through prompt(), the
+// string and math builtins, and hashing and signing with ethers.js and a
+// local wallet. Anything that has to wait (a balance, a transaction, an
+// AI model) throws unless `load` is given an implementation in
+// `options.host`, keyed by its stdlib name.
+//
+// This loader is the one host for every import a module can have:
+// `astrixa run` and the spec suite run programs under Node through it too.
+
+use std::collections::HashMap;
+
+use crate::ast::{Stmt, StmtKind};
+use crate::codegen::wasm::{env_name, generate_module, host_calls, import_signature, value_types, Shell};
+use crate::ir::{IRModule, ValueKind};
+
+/// A web build's output
+#[derive(Debug, Clone)]
+pub struct WebArtifact {
+    pub wat: String,
+    pub js: String,  // The loader, `<name>.js`
+    pub dts: String, // Its types, `<name>.d.ts`
+}
+
+/// Lower `module`, compiled from `ast`, to a module at `wasm_file` (the
+/// path the loader fetches, relative to itself) and its loader
+pub fn lower(ast: &[Stmt], module: &IRModule, wasm_file: &str) -> WebArtifact {
+    // The loader copies strings in through the allocator
+    let shell = Shell {
+        functions: vec!["  (export \"__alloc\" (func $__alloc))\n".to_string()],
+        heap: true,
+        ..Shell::default()
+    };
+    let wat = generate_module(module, &shell);
+
+    let kinds = crate::kinds::infer_module(module);
+    let declared = declared_params(ast);
+    let functions: Vec<(&str, &[String])> = module
+        .functions
+        .iter()
+        .filter_map(|func| Some((func.name.as_str(), declared.get(&func.name)?.as_slice())))
+        .collect();
+
+    let calls = host_calls(module);
+    let ethers = calls.iter().any(|call| uses_ethers(call));
+    let mut js = format!("// Loader for {}, generated by `astrixa build --target=web`\n\n", wasm_file);
+    if ethers {
+        js.push_str("import * as ethers from \"ethers\";\n\n");
+    }
+    js.push_str(&format!(
+        "export async function load(source = new URL(\"./{}\", import.meta.url), options = {{}}) {{\n",
+        wasm_file
+    ));
+    js.push_str(PRELUDE);
+    if ethers {
+        js.push_str("  let signer;\n  const wallet = () => (signer ??= options.wallet ?? ethers.Wallet.createRandom());\n\n");
+    }
+
+    js.push_str("  const host = {\n");
+    for call in &calls {
+        js.push_str(&format!("    \"{}\": {},\n", call, default_host(call)));
+    }
+    js.push_str("    ...options.host,\n  };\n\n");
+
+    js.push_str("  const env = {\n");
+    for call in &calls {
+        js.push_str(&format!("    \"{}\": {},\n", env_name(call), import(call)));
+    }
+    js.push_str("  };\n\n");
+    js.push_str(INSTANTIATE);
+
+    js.push_str("  return {\n");
+    let mut dts = format!("// Types for the loader of {}, generated by `astrixa build --target=web`\n\n", wasm_file);
+    dts.push_str("export interface Exports {\n");
+    for (name, params) in &functions {
+        let function = &kinds[*name];
+        js.push_str(&export(name, params, &function.params, &function.results));
+        let typed: Vec<String> = params
+            .iter()
+            .zip(&function.params)
+            .map(|(param, kind)| format!("{}: {}", param, ts_type(*kind, true)))
+            .collect();
+        dts.push_str(&format!("  \"{}\"({}): {};\n", name, typed.join(", "), ts_result(&function.results)));
+    }
+    js.push_str("  };\n}\n");
+    dts.push_str("}\n\nexport interface Options {\n");
+    dts.push_str("  /** Host functions by stdlib name, replacing the defaults or filling in for ones that throw */\n");
+    dts.push_str("  host?: Record<string, (...args: any[]) => unknown>;\n");
+    if ethers {
+        dts.push_str("  /** The wallet web3 calls sign with; a random one by default */\n");
+        dts.push_str("  wallet?: import(\"ethers\").Wallet;\n");
+    }
+    dts.push_str("}\n\n/** Fetch or take the module's bytes, instantiate it and return its functions */\n");
+    dts.push_str("export function load(source?: URL | string | BufferSource, options?: Options): Promise<Exports>;\n");
+
+    WebArtifact { wat, js, dts }
+}
+
+/// Conversions between the module's values and JavaScript's
+const PRELUDE: &str = "  const decoder = new TextDecoder();
+  const encoder = new TextEncoder();
+  const refs = [];
+  let exports;
+
+  const text = (ptr, len) => decoder.decode(new Uint8Array(exports.memory.buffer, ptr, len));
+  const string = (value) => {
+    const bytes = encoder.encode(String(value));
+    const ptr = exports.__alloc(bytes.length);
+    new Uint8Array(exports.memory.buffer, ptr, bytes.length).set(bytes);
+    return [ptr, bytes.length];
+  };
+  const ref = (value) => refs.push(value) - 1;
+  const unavailable = (name, why) => () => {
+    throw new Error(`${name}() ${why}; pass options.host[\"${name}\"] to load()`);
+  };
+
+";
+
+/// A URL is fetched; anything else is taken to be the bytes
+const INSTANTIATE: &str = "  const bytes = source instanceof URL || typeof source === \"string\"
+    ? await (await fetch(source)).arrayBuffer()
+    : source;
+  const { instance } = await WebAssembly.instantiate(bytes, { env });
+  exports = instance.exports;
+
+";
+
+/// Each declared function's parameter names: top-level functions, and
+/// contract methods as `Contract.method`
+fn declared_params(ast: &[Stmt]) -> HashMap<String, Vec<String>> {
+    let mut declared = HashMap::new();
+    for stmt in ast {
+        match &stmt.kind {
+            StmtKind::Function { name, params, .. } => {
+                declared.insert(name.clone(), params.clone());
+            }
+            StmtKind::Contract { name: contract, constructor, methods, .. } => {
+                for method in constructor.as_deref().into_iter().chain(methods) {
+                    if let StmtKind::Function { name, params, .. } = &method.kind {
+                        declared.insert(format!("{}.{}", contract, name), params.clone());
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    declared
+}
+
+fn uses_ethers(call: &str) -> bool {
+    matches!(call, "hash" | "keccak" | "sha256") || call.starts_with("web3.")
+}
+
+/// The loader's own implementation of a host function, by stdlib name
+fn default_host(name: &str) -> String {
+    let implementation = match name {
+        "print" | "println" => "(line) => console.log(line)",
+        "input" => "() => prompt() ?? \"\"",
+        "len" => "(value) => [...value].length",
+        "str" | "to_string" | "str.from_float" => "(value) => String(value)",
+        "int" => "(value) => BigInt(value.trim())",
+        "exit" => "(code) => {\n      throw new Error(`exit(${code})`);\n    }",
+        "panic" => "(message) => {\n      throw new Error(`Panic: ${message}`);\n    }",
+        "revert" => "(message) => {\n      throw new Error(`Revert: ${message}`);\n    }",
+        "sqrt" => "(n) => Math.floor(Math.sqrt(Number(n)))",
+        "rand" => "(max) => Math.floor(Math.random() * Number(max))",
+        "time" => "() => Date.now()",
+        "hash" | "keccak" | "web3.keccak" => "(value) => ethers.keccak256(ethers.toUtf8Bytes(value))",
+        "sha256" => "(value) => ethers.sha256(ethers.toUtf8Bytes(value))",
+        "web3.wallet" => "() => wallet()",
+        "web3.sign" => "(message) => wallet().signMessageSync(message)",
+        "web3.verify" => "(message, signature) => ethers.verifyMessage(message, signature) === wallet().address",
+        "sleep" => return format!("unavailable(\"{}\", \"would block the page\")", name),
+        "web3.balance" | "web3.send" => return format!("unavailable(\"{}\", \"waits on the network\")", name),
+        _ if name.starts_with("ai.") => return format!("unavailable(\"{}\", \"needs a model to call\")", name),
+        _ => return format!("unavailable(\"{}\", \"has no browser default\")", name),
+    };
+    implementation.to_string()
+}
+
+/// The `env` import for a host function: the module's values to
+/// JavaScript's, the call, and its result back
+fn import(name: &str) -> String {
+    let (params, results) = import_signature(name);
+    let mut raw = Vec::new();
+    let mut args = Vec::new();
+    for (i, kind) in params.iter().enumerate() {
+        match kind {
+            ValueKind::Str => {
+                raw.extend([format!("p{}", i), format!("l{}", i)]);
+                args.push(format!("text(p{}, l{})", i, i));
+            }
+            ValueKind::Bool => {
+                raw.push(format!("a{}", i));
+                args.push(format!("a{} !== 0", i));
+            }
+            ValueKind::Ref => {
+                raw.push(format!("a{}", i));
+                args.push(format!("refs[a{}]", i));
+            }
+            ValueKind::Int | ValueKind::Float => {
+                raw.push(format!("a{}", i));
+                args.push(format!("a{}", i));
+            }
+        }
+    }
+    let call = format!("host[\"{}\"]({})", name, args.join(", "));
+    let body = match results.first() {
+        None => call,
+        Some(ValueKind::Int) => format!("BigInt({})", call),
+        Some(ValueKind::Float) => format!("Number({})", call),
+        Some(ValueKind::Bool) => format!("({} ? 1 : 0)", call),
+        Some(ValueKind::Ref) => format!("ref({})", call),
+        Some(ValueKind::Str) => format!("string({})", call),
+    };
+    format!("({}) => {}", raw.join(", "), body)
+}
+
+/// A module function as a JavaScript one
+fn export(name: &str, params: &[String], kinds: &[ValueKind], results: &[ValueKind]) -> String {
+    let args: Vec<String> = params
+        .iter()
+        .zip(kinds)
+        .map(|(param, kind)| match kind {
+            ValueKind::Int => format!("BigInt({})", param),
+            ValueKind::Float => param.clone(),
+            ValueKind::Bool => format!("{} ? 1 : 0", param),
+            ValueKind::Ref => format!("ref({})", param),
+            ValueKind::Str => format!("...string({})", param),
+        })
+        .collect();
+    let call = format!("exports[\"{}\"]({})", name, args.join(", "));
+    let head = format!("    \"{}\": ({}) => ", name, params.join(", "));
+    if results.is_empty() {
+        return format!("{}{{\n      {};\n    }},\n", head, call);
+    }
+
+    // One value comes back as it is, several as an array
+    let count = value_types(results).len();
+    let mut values = (0..count).map(|i| if count == 1 { "result".to_string() } else { format!("result[{}]", i) });
+    let decoded: Vec<String> = results
+        .iter()
+        .map(|kind| {
+            let value = values.next().unwrap_or_default();
+            match kind {
+                ValueKind::Int | ValueKind::Float => value,
+                ValueKind::Bool => format!("{} !== 0", value),
+                ValueKind::Ref => format!("refs[{}]", value),
+                ValueKind::Str => format!("text({}, {})", value, values.next().unwrap_or_default()),
+            }
+        })
+        .collect();
+    let returned = match decoded.as_slice() {
+        [one] => one.clone(),
+        many => format!("[{}]", many.join(", ")),
+    };
+    format!("{}{{\n      const result = {};\n      return {};\n    }},\n", head, call, returned)
+}
+
+/// The TypeScript type of a value of `kind`; as a parameter, an Int takes
+/// a plain number too
+fn ts_type(kind: ValueKind, param: bool) -> &'static str {
+    match kind {
+        ValueKind::Int if param => "bigint | number",
+        ValueKind::Int => "bigint",
+        ValueKind::Float => "number",
+        ValueKind::Bool => "boolean",
+        ValueKind::Str => "string",
+        ValueKind::Ref => "unknown",
+    }
+}
+
+fn ts_result(results: &[ValueKind]) -> String {
+    match results {
+        [] => "void".to_string(),
+        [one] => ts_type(*one, false).to_string(),
+        many => format!("[{}]", many.iter().map(|kind| ts_type(*kind, false)).collect::<Vec<_>>().join(", ")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_web_loader_wraps_imports_and_exports() {
+        let source = r#"
+            fn greet(name: String) {
+                let line = "Hello, " + name
+                print(line)
+                return line
+            }
+            fn add(a: Int, b: Int) {
+                return a + b
+            }
+        "#;
+        let ast = crate::parser::Parser::new(crate::lexer::Lexer::new(source)).parse().unwrap();
+        let artifact = lower(&ast, &crate::lowering::lower(&ast), "demo.wasm");
+
+        assert!(artifact.wat.contains("(export \"__alloc\" (func $__alloc))"));
+        wasmparser::Validator::new()
+            .validate_all(&crate::codegen::binary::assemble(&artifact.wat).unwrap())
+            .unwrap();
+        assert!(artifact.js.contains("\"print_str\": (p0, l0) => host[\"print\"](text(p0, l0)),"));
+        assert!(artifact.js.contains("const result = exports[\"greet\"](...string(name));\n      return text(result[0], result[1]);"));
+        assert!(!artifact.js.contains("ethers"), "only imported when used");
+        assert!(artifact.dts.contains("  \"greet\"(name: string): string;\n  \"add\"(a: bigint | number, b: bigint | number): bigint;\n"));
+    }
+}
@@ -44,7 +44,7 @@ impl Compiler {
         for stmt in program {
             match stmt {
                 Stmt::Function { .. } => {} // Already compiled
-                Stmt::Import(_module) => {
+                Stmt::Import { .. } => {
                     // Imports are handled at runtime
                 }
                 _ => {
@@ -162,7 +162,7 @@ impl Compiler {
                 // Functions are compiled separately
                 Ok(())
             }
-            Stmt::Import(_) => {
+            Stmt::Import { .. } => {
                 // Imports are handled at runtime
                 Ok(())
             }
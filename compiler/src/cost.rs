@@ -0,0 +1,218 @@
+use crate::ir::{IRFunction, IRInstr, IRModule};
+use crate::opt::{self, Passes};
+
+// Static cost model for lowered code.
+//
+// Each IR instruction has an estimated size, in bytes of WASM it encodes to,
+// and a gas cost on the scale of the VM's gas table, with contract storage
+// priced well above locals as it is on chain. A function's gas is one pass
+// through its body: a loop counts once and a branch counts both ways, so
+// the numbers compare builds rather than predict a call.
+// `astrixa build --compare-opt` uses this to show what each pass saves.
+
+/// Encoded size and gas of one function
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Cost {
+    pub size: usize,
+    pub gas: u64,
+}
+
+/// One optimization configuration's cost for every function
+#[derive(Debug, Clone)]
+pub struct Column {
+    pub name: &'static str,
+    pub costs: Vec<Cost>, // In the order of Comparison::functions
+}
+
+impl Column {
+    pub fn total(&self) -> Cost {
+        self.costs.iter().fold(Cost::default(), |total, cost| Cost {
+            size: total.size + cost.size,
+            gas: total.gas + cost.gas,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Comparison {
+    pub functions: Vec<String>,
+    pub columns: Vec<Column>,
+}
+
+/// Configurations compared: nothing, each pass on its own, then everything
+pub const CONFIGURATIONS: &[(&str, Passes)] = &[
+    ("none", Passes::NONE),
+    ("const-fold", Passes { const_fold: true, ..Passes::NONE }),
+    ("dce", Passes { dce: true, ..Passes::NONE }),
+    ("inline", Passes { inline: true, ..Passes::NONE }),
+    ("cse", Passes { cse: true, ..Passes::NONE }),
+    ("licm", Passes { licm: true, ..Passes::NONE }),
+    ("peephole", Passes { peephole: true, ..Passes::NONE }),
+    ("dead-fn", Passes { dead_functions: true, ..Passes::NONE }),
+    ("all", Passes::ALL),
+];
+
+/// Optimize `module` under every configuration and cost the functions that
+/// `include` selects. `finish` runs on each optimized module first, for
+/// passes every build applies (plugins).
+pub fn compare(
+    module: &IRModule,
+    include: impl Fn(&IRFunction) -> bool,
+    finish: impl Fn(&mut IRModule),
+) -> Comparison {
+    let functions: Vec<String> = module
+        .functions
+        .iter()
+        .filter(|f| include(f))
+        .map(|f| f.name.clone())
+        .collect();
+
+    let columns = CONFIGURATIONS
+        .iter()
+        .map(|(name, passes)| {
+            let mut optimized = opt::optimize_module_with(module, *passes);
+            finish(&mut optimized);
+            let costs = functions
+                .iter()
+                .map(|name| {
+                    optimized
+                        .functions
+                        .iter()
+                        .find(|f| &f.name == name)
+                        .map_or(Cost::default(), function_cost)
+                })
+                .collect();
+            Column { name, costs }
+        })
+        .collect();
+
+    Comparison { functions, columns }
+}
+
+pub fn function_cost(function: &IRFunction) -> Cost {
+    function.instructions.iter().fold(Cost::default(), |total, instr| Cost {
+        size: total.size + instr_size(instr),
+        gas: total.gas + instr_gas(instr),
+    })
+}
+
+/// Bytes of WASM the instruction encodes to: an opcode byte plus LEB128
+/// immediates, as the WASM backend emits it
+pub fn instr_size(instr: &IRInstr) -> usize {
+    match instr {
+        IRInstr::LoadConstInt(n) => 1 + sleb128_len(*n),
+        IRInstr::LoadConstFloat(_) => 5,
+        IRInstr::LoadConstBool(_) => 2,
+        // Pointer and length; data segments start low, so the pointer is short
+        IRInstr::LoadConstString(s) => 3 + 1 + sleb128_len(s.len() as i64),
+        IRInstr::LoadLocal(slot) | IRInstr::StoreLocal(slot) => 1 + sleb128_len(*slot as i64),
+        IRInstr::LoadVar(_) | IRInstr::StoreVar(_) => 2,
+        IRInstr::LoadGlobal(_) | IRInstr::StoreGlobal(_) => 2,
+        IRInstr::LoadFunction(_) => 2, // Its index in the table
+        IRInstr::Add | IRInstr::Sub | IRInstr::Mul | IRInstr::Div | IRInstr::Mod => 1,
+        IRInstr::CheckedAdd(_) | IRInstr::CheckedSub(_) | IRInstr::CheckedMul(_) => 6, // The message's pointer and length, and a call to the helper
+        IRInstr::FloatToInt => 2, // Saturating conversions take a prefix byte
+        IRInstr::IntToFloat => 1,
+        IRInstr::Eq | IRInstr::Ne | IRInstr::Lt | IRInstr::Le | IRInstr::Gt | IRInstr::Ge => 1,
+        IRInstr::And | IRInstr::Or => 1,
+        IRInstr::Not => 3, // i32.const 1; i32.xor
+        // A branch names a nearby block or loop by its depth, and brings
+        // about one of them: two bytes to open it and one to end it
+        IRInstr::Jump(_) => 2 + 3,
+        IRInstr::JumpIfFalse(_) => 3 + 3, // i32.eqz; br_if
+        IRInstr::Call(..) | IRInstr::CallStd(_) | IRInstr::CallAI(_) | IRInstr::CallWeb3(_)
+        | IRInstr::CallFS(_) | IRInstr::LoadProperty(_) => 2,
+        IRInstr::CallIndirect(_) => 3, // Its type and the table
+        IRInstr::Return => 1,
+        IRInstr::Panic | IRInstr::Revert => 3, // call; unreachable
+        IRInstr::Pop => 1,
+        IRInstr::Dup => 4, // local.tee; local.get
+        IRInstr::Nop => 1,
+        IRInstr::Line(..) => 0, // An annotation, not an instruction
+    }
+}
+
+/// Estimated gas to execute the instruction once
+pub fn instr_gas(instr: &IRInstr) -> u64 {
+    match instr {
+        IRInstr::LoadConstInt(_) | IRInstr::LoadConstFloat(_) | IRInstr::LoadConstBool(_) => 1,
+        IRInstr::LoadConstString(_) => 2,
+        IRInstr::LoadVar(_) | IRInstr::LoadLocal(_) | IRInstr::LoadFunction(_) => 1,
+        IRInstr::StoreVar(_) | IRInstr::StoreLocal(_) => 2,
+        IRInstr::LoadGlobal(_) => 50,
+        IRInstr::StoreGlobal(_) => 200,
+        IRInstr::Add | IRInstr::Sub => 2,
+        IRInstr::Mul => 5,
+        // The helper widens, operates and compares
+        IRInstr::CheckedAdd(_) | IRInstr::CheckedSub(_) => 8,
+        IRInstr::CheckedMul(_) => 11,
+        IRInstr::Div | IRInstr::Mod => 8,
+        IRInstr::FloatToInt | IRInstr::IntToFloat => 3,
+        IRInstr::Eq | IRInstr::Ne | IRInstr::Lt | IRInstr::Le | IRInstr::Gt | IRInstr::Ge => 2,
+        IRInstr::And | IRInstr::Or | IRInstr::Not => 2,
+        IRInstr::Jump(_) => 1,
+        IRInstr::JumpIfFalse(_) => 2,
+        IRInstr::Call(..) => 10,
+        // The table's bounds and the callee's type are checked first
+        IRInstr::CallIndirect(_) => 13,
+        IRInstr::CallStd(_) | IRInstr::CallFS(_) | IRInstr::LoadProperty(_) => 10,
+        IRInstr::CallAI(_) | IRInstr::CallWeb3(_) => 100,
+        IRInstr::Return | IRInstr::Panic | IRInstr::Revert => 3,
+        IRInstr::Pop | IRInstr::Dup => 1,
+        IRInstr::Nop | IRInstr::Line(..) => 0,
+    }
+}
+
+/// Bytes in the signed LEB128 encoding of `n`
+fn sleb128_len(mut n: i64) -> usize {
+    let mut len = 1;
+    while !(-64..64).contains(&n) {
+        n >>= 7;
+        len += 1;
+    }
+    len
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    #[test]
+    fn test_compare_costs_each_configuration() {
+        let source = r#"
+            contract Counter {
+                state count
+                fn bump() {
+                    count = count + 2 * 3
+                    return count
+                    count = 0
+                }
+            }
+            fn main() {
+                return 1
+            }
+        "#;
+        let program = Parser::new(Lexer::new(source)).parse().unwrap();
+        let module = crate::lowering::lower(&program);
+        let comparison = compare(&module, |f| f.name.contains('.'), |_| {});
+
+        assert_eq!(comparison.functions, vec!["Counter.bump"]);
+        let names: Vec<&str> = comparison.columns.iter().map(|c| c.name).collect();
+        assert_eq!(names, vec!["none", "const-fold", "dce", "inline", "cse", "licm", "peephole", "dead-fn", "all"]);
+
+        let cost = |name: &str| comparison.columns.iter().find(|c| c.name == name).unwrap().total();
+        let none = cost("none");
+        assert!(cost("const-fold").size < none.size && cost("const-fold").gas < none.gas);
+        // Dead code after the return: `count = 0` (1 + 200) and the implicit
+        // `return 0` lowering adds at the end (1 + 3)
+        assert_eq!(none.gas - cost("dce").gas, 205);
+        assert_eq!(cost("inline"), none);
+        assert!(cost("all").gas < cost("dce").gas);
+
+        assert_eq!(sleb128_len(63), 1);
+        assert_eq!(sleb128_len(64), 2);
+        assert_eq!(sleb128_len(-65), 2);
+    }
+}
@@ -0,0 +1,96 @@
+use serde_json::Value as Json;
+
+// The protocols the defi bindings cover, each a script module generated from
+// the ABI the protocol publishes. Only functions are bound; a tuple parameter
+// becomes its canonical type, `(address,address,uint24)`, taking a tuple.
+
+/// Each protocol's module name and ABI
+const PROTOCOLS: [(&str, &str); 4] = [
+    ("erc20", include_str!("abis/erc20.json")),
+    ("uniswap", include_str!("abis/uniswap_v2_router.json")),
+    ("uniswap_v3", include_str!("abis/uniswap_v3_router.json")),
+    ("chainlink", include_str!("abis/chainlink_aggregator.json")),
+];
+
+/// A function of a protocol's ABI
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    pub name: String,        // As scripts call it, `balance_of`
+    pub abi_name: String,    // As the ABI declares it, `balanceOf`
+    pub params: Vec<String>, // Parameter names, where the ABI gives them
+    pub inputs: Vec<String>, // Canonical types
+    pub outputs: Vec<String>,
+    pub view: bool,          // Reads the chain rather than changing it
+}
+
+/// Every protocol's module name
+pub fn protocols() -> impl Iterator<Item = &'static str> {
+    PROTOCOLS.iter().map(|(name, _)| *name)
+}
+
+pub fn is_protocol(module: &str) -> bool {
+    protocols().any(|name| name == module)
+}
+
+/// Every function `module`'s ABI declares
+pub fn functions(module: &str) -> Vec<Function> {
+    let Some((_, abi)) = PROTOCOLS.iter().find(|(name, _)| *name == module) else {
+        return Vec::new();
+    };
+    abi_functions(&serde_json::from_str(abi).expect("bundled ABIs are valid JSON"))
+}
+
+/// Every function a JSON ABI declares, whether bundled or a contract's own
+pub fn abi_functions(abi: &Json) -> Vec<Function> {
+    abi.as_array()
+        .into_iter()
+        .flatten()
+        .filter(|entry| entry["type"] == "function")
+        .map(|entry| {
+            let abi_name = entry["name"].as_str().unwrap_or_default().to_string();
+            let types = |params: &Json| params.as_array().map(|params| params.iter().map(param_type).collect()).unwrap_or_default();
+            Function {
+                name: snake_case(&abi_name),
+                params: entry["inputs"].as_array().into_iter().flatten().map(|param| param["name"].as_str().unwrap_or_default().to_string()).collect(),
+                inputs: types(&entry["inputs"]),
+                outputs: types(&entry["outputs"]),
+                view: matches!(entry["stateMutability"].as_str(), Some("view" | "pure")),
+                abi_name,
+            }
+        })
+        .collect()
+}
+
+/// The function scripts call as `module.name`
+pub fn function(module: &str, name: &str) -> Option<Function> {
+    functions(module).into_iter().find(|function| function.name == name)
+}
+
+/// A parameter's canonical type, spelling tuples out from their components
+fn param_type(param: &Json) -> String {
+    let kind = param["type"].as_str().unwrap_or_default();
+    match kind.strip_prefix("tuple") {
+        Some(suffix) => {
+            let components: Vec<String> = param["components"].as_array().into_iter().flatten().map(param_type).collect();
+            format!("({}){}", components.join(","), suffix)
+        }
+        None => kind.to_string(),
+    }
+}
+
+/// `swapExactETHForTokens` as `swap_exact_eth_for_tokens`
+fn snake_case(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut snake = String::new();
+    for (i, c) in chars.iter().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            let after_lower = chars[i - 1].is_lowercase() || chars[i - 1].is_ascii_digit();
+            let ends_acronym = chars[i - 1].is_uppercase() && chars.get(i + 1).is_some_and(|next| next.is_lowercase());
+            if after_lower || ends_acronym {
+                snake.push('_');
+            }
+        }
+        snake.extend(c.to_lowercase());
+    }
+    snake
+}
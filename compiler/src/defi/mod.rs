@@ -0,0 +1,127 @@
+use crate::interpreter::Value;
+use crate::logs::{hex, unhex};
+use crate::messages;
+use crate::multicall::{CallSource, FunctionSignature};
+
+pub mod abi;
+
+// Typed bindings for common protocols, the standard library's defi package:
+// `erc20`, `uniswap` (the V2 router), `uniswap_v3` (SwapRouter02) and
+// `chainlink` (price feeds). Every function of the protocol's ABI is bound
+// under its name in snake case, taking the contract's address first:
+//
+//   let decimals = erc20.decimals(usdc)?
+//   let (round, answer, started, updated, answered) = chainlink.latest_round_data(eth_usd)?
+//   let amounts = uniswap.get_amounts_out(router, 1000000, [usdc, weth])?
+//   let calldata = uniswap.swap_exact_tokens_for_tokens(router, 1000000, 0, [usdc, weth], me, deadline)
+//
+// Functions that only read the chain are called with `eth_call` on the node
+// web3.multicall reads (ASTRIXA_RPC_URL), giving an Ok with the decoded
+// value or an Err with why the call failed. Scripts cannot send
+// transactions, so functions that change state give the transaction's
+// calldata as hex instead, for a wallet to sign and send.
+
+/// Call `module.name` of the contract at `args[0]` with the rest of `args`
+pub fn call(source: Option<&dyn CallSource>, module: &str, name: &str, args: &[Value]) -> Result<Value, String> {
+    let qualified = format!("{}.{}", module, name);
+    let function = abi::function(module, name).ok_or_else(|| messages::render("E0406", &[&qualified]))?;
+    let [Value::Address(target) | Value::String(target), args @ ..] = args else {
+        return Err(messages::render("E0474", &[&qualified, &function.inputs.len()]));
+    };
+    if args.len() != function.inputs.len() {
+        return Err(messages::render("E0474", &[&qualified, &function.inputs.len()]));
+    }
+
+    let signature = FunctionSignature { name: function.abi_name, inputs: function.inputs, outputs: function.outputs };
+    let data = signature.encode(args).map_err(|e| messages::render("E0454", &[&qualified, &e]))?;
+    if !function.view {
+        return Ok(Value::String(format!("0x{}", hex(&data))));
+    }
+
+    // As with web3.logs, an unreachable node is the script's to handle
+    let result = source
+        .ok_or_else(|| messages::render("E0475", &[&qualified]))
+        .and_then(|source| source.call(target, &format!("0x{}", hex(&data))))
+        .and_then(|returned| signature.decode(&unhex(&returned)?));
+    Ok(Value::Result(result.map(Box::new).map_err(|e| Box::new(Value::String(e)))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use crate::u256::U256;
+
+    const TOKEN: &str = "0x00000000000000000000000000000000000000c0";
+    const WETH: &str = "0x00000000000000000000000000000000000000e0";
+    const HOLDER: &str = "0x00000000000000000000000000000000000000aa";
+
+    /// A node answering every call with `returned`
+    struct Fixture {
+        returned: String,
+        calls: RefCell<Vec<(String, String)>>,
+    }
+
+    impl CallSource for Fixture {
+        fn call(&self, to: &str, data: &str) -> Result<String, String> {
+            self.calls.borrow_mut().push((to.to_string(), data.to_string()));
+            Ok(self.returned.clone())
+        }
+
+        fn batch(&self, _calls: &[(String, String)]) -> Result<Vec<Result<String, String>>, String> {
+            Err("batch requests are not supported".to_string())
+        }
+    }
+
+    fn word(tail: &str) -> String {
+        format!("{:0>64}", tail)
+    }
+
+    fn address(text: &str) -> Value {
+        Value::Address(text.to_string())
+    }
+
+    #[test]
+    fn test_bindings_follow_the_abis() {
+        let names: Vec<String> = abi::functions("uniswap").into_iter().map(|f| f.name).collect();
+        assert!(names.contains(&"swap_exact_eth_for_tokens".to_string()) && names.contains(&"weth".to_string()), "{:?}", names);
+        let exact = abi::function("uniswap_v3", "exact_input_single").unwrap();
+        assert_eq!(exact.inputs, vec!["(address,address,uint24,address,uint256,uint256,uint160)"]);
+        assert!(!exact.view && abi::function("chainlink", "latest_round_data").unwrap().view);
+        assert_eq!(abi::function("erc20", "mint"), None);
+    }
+
+    #[test]
+    fn test_views_are_called_and_decoded() {
+        let node = Fixture { returned: format!("0x{}", word("64")), calls: RefCell::new(Vec::new()) };
+        let balance = call(Some(&node), "erc20", "balance_of", &[address(TOKEN), address(HOLDER)]);
+        assert_eq!(balance, Ok(Value::Result(Ok(Box::new(Value::U256(U256::from(100)))))));
+        assert_eq!(node.calls.borrow()[0], (TOKEN.to_string(), format!("0x70a08231{}", word("aa"))));
+
+        // An array comes back at the offset in its head word
+        let node = Fixture { returned: format!("0x{}{}{}{}", word("20"), word("2"), word("64"), word("c8")), calls: RefCell::new(Vec::new()) };
+        let amounts = call(Some(&node), "uniswap", "get_amounts_out", &[address(TOKEN), Value::Number(100), Value::array(vec![address(TOKEN), address(WETH)])]);
+        let expected = Value::array(vec![Value::U256(U256::from(100)), Value::U256(U256::from(200))]);
+        assert_eq!(amounts, Ok(Value::Result(Ok(Box::new(expected)))));
+        assert_eq!(node.calls.borrow()[0].1, format!("0xd06ca61f{}{}{}{}{}", word("64"), word("40"), word("2"), word("c0"), word("e0")));
+
+        let offline = call(None, "chainlink", "latest_round_data", &[address(TOKEN)]).unwrap();
+        assert!(matches!(offline, Value::Result(Err(reason)) if *reason == Value::String(messages::render("E0475", &[&"chainlink.latest_round_data"]))));
+    }
+
+    #[test]
+    fn test_state_changes_give_calldata() {
+        let path = Value::array(vec![address(TOKEN), address(WETH)]);
+        let swap = call(None, "uniswap", "swap_exact_tokens_for_tokens", &[address(TOKEN), Value::Number(100), Value::Number(90), path, address(HOLDER), Value::Number(1)]);
+        let expected = format!("0x38ed1739{}{}{}{}{}{}{}{}", word("64"), word("5a"), word("a0"), word("aa"), word("1"), word("2"), word("c0"), word("e0"));
+        assert_eq!(swap, Ok(Value::String(expected)));
+
+        // A struct parameter is a tuple, written in place
+        let params = Value::Tuple(vec![address(TOKEN), address(WETH), Value::Number(3000), address(HOLDER), Value::Number(100), Value::Number(90), Value::Number(0)]);
+        let Ok(Value::String(data)) = call(None, "uniswap_v3", "exact_input_single", &[address(TOKEN), params]) else { panic!() };
+        assert_eq!(&data[..10], "0x04e45aaf");
+        assert_eq!(&data[10..], format!("{}{}{}{}{}{}{}", word("c0"), word("e0"), word("bb8"), word("aa"), word("64"), word("5a"), word("0")));
+
+        assert!(call(None, "erc20", "transfer", &[address(TOKEN), address(HOLDER)]).unwrap_err().contains("erc20.transfer"));
+    }
+}
@@ -1,15 +1,37 @@
 /// Pretty-print compilation errors with helpful formatting
+use crate::edition::Warning;
 use crate::error::CompileError;
 
 pub fn display_error(err: CompileError) {
-    eprintln!("Error: {}", err.message);
-    eprintln!(" → line {}, column {}", err.line, err.column);
+    match err.code {
+        Some(code) => eprintln!("Error[{}]: {}", code, err.message),
+        None => eprintln!("Error: {}", err.message),
+    }
+    // Line 0 is no line of the program, as in the standard library
+    if err.line > 0 {
+        eprintln!(" → line {}, column {}", err.line, err.column);
+    }
 
     if let Some(help) = err.help {
         eprintln!(" Help: {}", help);
     }
 }
 
+pub fn display_warning(warning: &Warning) {
+    eprintln!("Warning[{}]: {}", warning.code, warning.message);
+    eprintln!(" → line {}, column {}", warning.span.start_line, warning.span.start_column);
+
+    if let Some(help) = &warning.help {
+        eprintln!(" Help: {}", help);
+    }
+}
+
+pub fn display_warnings(warnings: &[Warning]) {
+    for warning in warnings {
+        display_warning(warning);
+    }
+}
+
 pub fn display_errors(errors: &[CompileError]) {
     if errors.is_empty() {
         return;
@@ -0,0 +1,124 @@
+use crate::interpreter::Value;
+use crate::messages;
+
+// ed25519 signatures, the `ed25519` module of scripts, for chains such as
+// Solana, Near and Aptos that do not sign with secp256k1.
+//
+//   let key = ed25519.public_key(secret)
+//   let signature = ed25519.sign("hello", secret)
+//   ed25519.verify("hello", signature, key)   // true
+//
+// Keys and signatures are hex: a 32-byte secret or public key, a 64-byte
+// signature. A message is the bytes a 0x-prefixed hex string spells, or any
+// other text's UTF-8 bytes. Verification is strict, refusing the malleable
+// signatures other implementations let through, and a malformed signature
+// or key verifies nothing rather than failing.
+//
+// Signing needs the `ed25519` cargo feature; WASM builds import the three
+// functions from the host instead.
+
+/// Call `ed25519.<function>` with `args`
+pub fn call(function: &str, args: &[Value]) -> Result<Value, String> {
+    let usage = |expected: &str| messages::render("E0471", &[&function, &expected]);
+    match (function, args) {
+        ("public_key", [Value::String(secret)]) => imp::public_key(secret).map(Value::String),
+        ("sign", [Value::String(message), Value::String(secret)]) => imp::sign(message, secret).map(Value::String),
+        ("verify", [Value::String(message), Value::String(signature), Value::String(key)]) => {
+            imp::verify(message, signature, key).map(Value::Bool)
+        }
+        ("public_key", _) => Err(usage("(secret_key)")),
+        ("sign", _) => Err(usage("(message, secret_key)")),
+        ("verify", _) => Err(usage("(message, signature, public_key)")),
+        _ => Err(messages::render("E0406", &[&format!("ed25519.{}", function)])),
+    }
+}
+
+#[cfg(feature = "ed25519")]
+mod imp {
+    use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+    use crate::logs::{hex, message_bytes, unhex};
+    use crate::messages;
+
+    /// `text` as exactly `N` bytes of hex
+    fn bytes<const N: usize>(text: &str) -> Option<[u8; N]> {
+        unhex(text).ok().and_then(|bytes| bytes.try_into().ok())
+    }
+
+    fn signing_key(secret: &str) -> Result<SigningKey, String> {
+        let secret = bytes::<32>(secret).ok_or_else(|| messages::render("E0471", &[&"sign", &"a 32-byte hex secret key"]))?;
+        Ok(SigningKey::from_bytes(&secret))
+    }
+
+    pub fn public_key(secret: &str) -> Result<String, String> {
+        Ok(format!("0x{}", hex(signing_key(secret)?.verifying_key().as_bytes())))
+    }
+
+    pub fn sign(message: &str, secret: &str) -> Result<String, String> {
+        let signature = signing_key(secret)?.sign(&message_bytes(message));
+        Ok(format!("0x{}", hex(&signature.to_bytes())))
+    }
+
+    pub fn verify(message: &str, signature: &str, key: &str) -> Result<bool, String> {
+        let (Some(signature), Some(key)) = (bytes::<64>(signature), bytes::<32>(key)) else {
+            return Ok(false);
+        };
+        let Ok(key) = VerifyingKey::from_bytes(&key) else {
+            return Ok(false);
+        };
+        Ok(key.verify_strict(&message_bytes(message), &Signature::from_bytes(&signature)).is_ok())
+    }
+}
+
+#[cfg(not(feature = "ed25519"))]
+mod imp {
+    use crate::messages;
+
+    fn disabled<T>(function: &str) -> Result<T, String> {
+        Err(messages::render("E0473", &[&format!("ed25519.{}", function), &"ed25519"]))
+    }
+
+    pub fn public_key(_secret: &str) -> Result<String, String> {
+        disabled("public_key")
+    }
+
+    pub fn sign(_message: &str, _secret: &str) -> Result<String, String> {
+        disabled("sign")
+    }
+
+    pub fn verify(_message: &str, _signature: &str, _key: &str) -> Result<bool, String> {
+        disabled("verify")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 8032, section 7.1, test 2
+    #[cfg(feature = "ed25519")]
+    const SECRET: &str = "0x4ccd089b28ff96da9db6c346ec114e0f5b8a319f35aba624da8cf6ed4fb8a6fb";
+    const PUBLIC: &str = "0x3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c";
+    const SIGNATURE: &str = "0x92a009a9f0d4cab8720e820b5f642540a2b27b5416503f8fb3762223ebdb69da085ac1e43e15996e458f3613d0f11d8c387b2eaeb4302aeeb00d291612bb0c00";
+
+    fn string(text: &str) -> Value {
+        Value::String(text.to_string())
+    }
+
+    #[cfg(feature = "ed25519")]
+    #[test]
+    fn test_signatures_match_the_rfc_vectors() {
+        assert_eq!(call("public_key", &[string(SECRET)]), Ok(string(PUBLIC)));
+        assert_eq!(call("sign", &[string("0x72"), string(SECRET)]), Ok(string(SIGNATURE)));
+        assert_eq!(call("verify", &[string("0x72"), string(SIGNATURE), string(PUBLIC)]), Ok(Value::Bool(true)));
+        assert_eq!(call("verify", &[string("0x73"), string(SIGNATURE), string(PUBLIC)]), Ok(Value::Bool(false)));
+        assert_eq!(call("verify", &[string("0x72"), string("0x12"), string(PUBLIC)]), Ok(Value::Bool(false)));
+        assert!(call("sign", &[string("0x72"), string("0x12")]).is_err());
+    }
+
+    #[cfg(not(feature = "ed25519"))]
+    #[test]
+    fn test_signatures_need_the_feature() {
+        let error = call("verify", &[string("0x72"), string(SIGNATURE), string(PUBLIC)]).unwrap_err();
+        assert!(error.contains("--features ed25519"), "{}", error);
+    }
+}
@@ -0,0 +1,193 @@
+use std::fmt;
+use crate::ast::Span;
+use crate::messages;
+
+// Language editions.
+//
+// Breaking changes to syntax or semantics ship behind a newer edition, so a
+// project keeps compiling the way it always did until it opts in through the
+// `edition` field in astrixa.toml. Constructs an edition retires are still
+// accepted there, but reported as deprecation warnings with a mechanical fix
+// where one exists (`astrixa check`, and quick fixes in the LSP).
+
+/// A language edition, oldest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Edition {
+    /// The original language; projects without an `edition` field use it
+    #[default]
+    E2024,
+    E2025,
+    E2026,
+}
+
+impl Edition {
+    /// Every edition, oldest first
+    pub const ALL: &'static [Edition] = &[Edition::E2024, Edition::E2025, Edition::E2026];
+
+    /// The edition new projects are created with
+    pub const LATEST: Edition = Edition::E2026;
+
+    pub fn parse(text: &str) -> Option<Edition> {
+        Self::ALL.iter().copied().find(|e| e.as_str() == text.trim())
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Edition::E2024 => "2024",
+            Edition::E2025 => "2025",
+            Edition::E2026 => "2026",
+        }
+    }
+
+    /// Read the edition from astrixa.toml source (`[package] edition = "2025"`)
+    pub fn from_manifest(manifest: &str) -> Result<Edition, String> {
+        let value: toml::Value = toml::from_str(manifest)
+            .map_err(|e| format!("Failed to parse astrixa.toml: {}", e))?;
+
+        match value.get("package").and_then(|p| p.get("edition")) {
+            None => Ok(Edition::default()),
+            Some(toml::Value::String(text)) => Edition::parse(text).ok_or_else(|| unknown(text)),
+            Some(other) => Err(unknown(&other.to_string())),
+        }
+    }
+}
+
+fn unknown(text: &str) -> String {
+    let supported: Vec<&str> = Edition::ALL.iter().map(|e| e.as_str()).collect();
+    format!("Unknown edition '{}' (supported: {})", text, supported.join(", "))
+}
+
+impl fmt::Display for Edition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// A construct retired by an edition
+pub struct Deprecation {
+    pub code: &'static str, // Message code, e.g. "W0001"
+    pub since: Edition,     // First edition that warns about it
+}
+
+/// Every deprecation, by code. Add new entries here rather than checking
+/// editions ad hoc, so tooling can list what each edition changes.
+pub const DEPRECATIONS: &[Deprecation] = &[
+    Deprecation { code: "W0001", since: Edition::E2025 }, // println() -> print()
+    Deprecation { code: "W0002", since: Edition::E2025 }, // state: [...] list form
+    Deprecation { code: "W0003", since: Edition::E2024 }, // Reserved word as a name
+];
+
+/// The edition from which a reserved word (`token::is_reserved`) used as a
+/// name is error E0169; earlier ones warn with W0003 and rename it
+pub const RESERVED_NAMES: Edition = Edition::E2026;
+
+/// Whether the construct with this code is deprecated in `edition`
+pub fn is_deprecated(code: &str, edition: Edition) -> bool {
+    DEPRECATIONS
+        .iter()
+        .any(|d| d.code == code && edition >= d.since)
+}
+
+/// A deprecation warning, with the text that should replace `span` if the
+/// construct has a mechanical upgrade
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+    pub code: &'static str,
+    pub message: String,
+    pub help: Option<String>,
+    pub span: Span,
+    pub replacement: Option<String>,
+}
+
+impl Warning {
+    pub fn new(
+        code: &'static str,
+        args: &[&dyn fmt::Display],
+        span: Span,
+        replacement: Option<String>,
+    ) -> Self {
+        Warning {
+            code,
+            message: messages::render(code, args),
+            help: messages::help(code, args),
+            span,
+            replacement,
+        }
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Warning[{}]: {}", self.code, self.message)?;
+        write!(f, "\n → line {}, column {}", self.span.start_line, self.span.start_column)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    #[test]
+    fn test_edition_from_manifest() {
+        let old = "[package]\nname = \"demo\"\nversion = \"0.1.0\"\n";
+        assert_eq!(Edition::from_manifest(old), Ok(Edition::E2024));
+
+        let new = "[package]\nname = \"demo\"\nversion = \"0.1.0\"\nedition = \"2025\"\n";
+        assert_eq!(Edition::from_manifest(new), Ok(Edition::E2025));
+
+        let bad = "[package]\nname = \"demo\"\nversion = \"0.1.0\"\nedition = \"2030\"\n";
+        assert!(Edition::from_manifest(bad).unwrap_err().contains("2024, 2025"));
+
+        assert!(!is_deprecated("W0001", Edition::E2024));
+        assert!(is_deprecated("W0001", Edition::E2025));
+    }
+
+    #[test]
+    fn test_state_list_warning_suggests_one_declaration_per_line() {
+        let source = "contract Token {\n    state: [\"balances\", \"owner\"]\n}\n";
+
+        let mut parser = Parser::new(Lexer::new(source));
+        parser.parse().unwrap();
+        assert!(parser.warnings().is_empty());
+
+        let mut parser = Parser::new(Lexer::new(source));
+        parser.set_edition(Edition::E2025);
+        parser.parse().unwrap();
+        let warning = &parser.warnings()[0];
+        assert_eq!(warning.code, "W0002");
+        assert_eq!((warning.span.start_line, warning.span.start_column), (2, 5));
+        assert_eq!((warning.span.end_line, warning.span.end_column), (2, 33));
+        assert_eq!(warning.replacement.as_deref(), Some("state balances\n    state owner"));
+
+        // Any name the lexer reads as an identifier can go unquoted
+        let mut parser = Parser::new(Lexer::new("contract Konto {\n    state: [\"größe\", \"名前\"]\n}\n"));
+        parser.set_edition(Edition::E2025);
+        parser.parse().unwrap();
+        assert_eq!(parser.warnings()[0].replacement.as_deref(), Some("state größe\n    state 名前"));
+    }
+
+    #[test]
+    fn test_reserved_words_as_names_warn_then_fail() {
+        let source = "fn main() {\n    let match = 2\n    let mut = 1\n    print(type(match))\n    return match + mut\n}\n";
+
+        let mut parser = Parser::new(Lexer::new(source));
+        parser.set_edition(Edition::E2025);
+        parser.parse().unwrap();
+        let renames: Vec<_> = parser.warnings().iter().map(|w| w.replacement.as_deref().unwrap()).collect();
+        assert_eq!(renames, ["match_", "mut_", "match_", "match_", "mut_"], "the builtin call keeps its name");
+        assert_eq!(parser.warnings()[1].span, Span { start_line: 3, start_column: 9, end_line: 3, end_column: 12 });
+
+        let mut parser = Parser::new(Lexer::new(source));
+        parser.set_edition(RESERVED_NAMES);
+        assert_eq!(parser.parse().unwrap_err().code, Some("E0169"));
+        assert_eq!(parser.errors().len(), 5);
+
+        let migrated = crate::migrate::migrate(source, RESERVED_NAMES).unwrap().source;
+        let mut parser = Parser::new(Lexer::new(&migrated));
+        parser.set_edition(RESERVED_NAMES);
+        parser.parse().unwrap();
+        assert!(migrated.contains("return match_ + mut_"));
+    }
+}
@@ -0,0 +1,270 @@
+use k256::ecdsa::{RecoveryId, Signature, SigningKey, VerifyingKey};
+use crate::interpreter::Value;
+use crate::logs::{hex, keccak256, unhex};
+
+pub mod types;
+pub use types::{TypedData, TypedField};
+use types::split_array;
+
+// EIP-712 typed structured data.
+//
+// Off-chain orders and permits are signed as typed data rather than raw
+// bytes, so wallets can show what is being signed:
+//
+//   let domain = ("Exchange", "1", 1, exchange)
+//   let types = "Order(address maker,uint256 amount,Asset asset)Asset(address token,uint256 id)"
+//   let signature = web3.sign_typed(domain, types, (maker, 100, token, 7))
+//   web3.verify_typed(domain, types, (maker, 100, token, 7), signature, maker)
+//
+// Types are written the way EIP-712 encodes them, primary type first. The
+// language has no records, so a struct value is a tuple of its fields in
+// declaration order, or a Map keyed by field name. Scripts cannot nest
+// tuples, so a nested struct's fields may continue inline, as `token, 7`
+// does above; values from the serde bridge nest instead. A domain tuple
+// holds a prefix of name, version, chainId, verifyingContract and salt; a
+// Map may hold any of them.
+
+/// Fields an EIP712Domain may declare, in the order the standard lists them
+const DOMAIN_FIELDS: [(&str, &str); 5] = [
+    ("name", "string"),
+    ("version", "string"),
+    ("chainId", "uint256"),
+    ("verifyingContract", "address"),
+    ("salt", "bytes32"),
+];
+
+impl TypedData {
+    /// hashStruct: keccak256 of the type hash followed by each field's word
+    pub fn hash_struct(&self, name: &str, value: &Value) -> Result<[u8; 32], String> {
+        match value {
+            Value::Tuple(items) => {
+                let mut items = items.iter();
+                let hash = self.hash_fields(name, &mut items)?;
+                match items.len() {
+                    0 => Ok(hash),
+                    extra => Err(format!("'{}' got {} values too many", name, extra)),
+                }
+            }
+            Value::Map(entries) => {
+                let entries = entries.borrow();
+                let fields = self.fields(name).ok_or_else(|| format!("unknown struct '{}'", name))?;
+                let mut encoded = keccak256(self.encode_type(name).as_bytes()).to_vec();
+                for field in fields {
+                    let value = entries
+                        .iter()
+                        .find(|(key, _)| *key == field.name)
+                        .map(|(_, v)| v)
+                        .ok_or_else(|| format!("'{}' is missing field '{}'", name, field.name))?;
+                    let word = self
+                        .encode_value(&field.kind, value)
+                        .map_err(|e| format!("{}.{}: {}", name, field.name, e))?;
+                    encoded.extend_from_slice(&word);
+                }
+                Ok(keccak256(&encoded))
+            }
+            other => Err(format!("'{}' needs a tuple or map, got {}", name, describe(other))),
+        }
+    }
+
+    /// hashStruct over values taken in order, reading a nested struct's
+    /// fields inline unless its value is a tuple or map of its own
+    fn hash_fields(&self, name: &str, items: &mut std::slice::Iter<Value>) -> Result<[u8; 32], String> {
+        let fields = self.fields(name).ok_or_else(|| format!("unknown struct '{}'", name))?;
+        let mut encoded = keccak256(self.encode_type(name).as_bytes()).to_vec();
+        for field in fields {
+            let nested = matches!(items.as_slice().first(), Some(Value::Tuple(_) | Value::Map(_)));
+            let word = if self.fields(&field.kind).is_some() && !nested {
+                self.hash_fields(&field.kind, items)
+            } else {
+                let value = items.next().ok_or_else(|| format!("'{}' is missing field '{}'", name, field.name))?;
+                self.encode_value(&field.kind, value)
+            };
+            encoded.extend_from_slice(&word.map_err(|e| format!("{}.{}: {}", name, field.name, e))?);
+        }
+        Ok(keccak256(&encoded))
+    }
+
+    /// The 32-byte word a field contributes to its struct's encoding
+    fn encode_value(&self, kind: &str, value: &Value) -> Result<[u8; 32], String> {
+        if let Some((element, size)) = split_array(kind) {
+            let Value::Array(items) = value else {
+                return Err(format!("'{}' needs an array, got {}", kind, describe(value)));
+            };
+            let items = items.borrow();
+            if size.is_some_and(|size| size != items.len()) {
+                return Err(format!("'{}' needs {} elements, got {}", kind, size.unwrap_or_default(), items.len()));
+            }
+            let mut encoded = Vec::with_capacity(items.len() * 32);
+            for item in items.iter() {
+                encoded.extend_from_slice(&self.encode_value(element, item)?);
+            }
+            return Ok(keccak256(&encoded));
+        }
+        if self.fields(kind).is_some() {
+            return self.hash_struct(kind, value);
+        }
+        encode_atomic(kind, value)
+    }
+}
+
+/// The EIP712Domain separator for a domain tuple or map
+pub fn domain_separator(domain: &Value) -> Result<[u8; 32], String> {
+    let present: Vec<(TypedField, Value)> = match domain {
+        Value::Tuple(items) if items.len() <= DOMAIN_FIELDS.len() => DOMAIN_FIELDS
+            .iter()
+            .zip(items)
+            .map(|((name, kind), value)| (field(name, kind), value.clone()))
+            .collect(),
+        Value::Map(entries) => DOMAIN_FIELDS
+            .iter()
+            .filter_map(|(name, kind)| {
+                let value = entries.borrow().iter().find(|(key, _)| key == name).cloned()?;
+                Some((field(name, kind), value.1.clone()))
+            })
+            .collect(),
+        other => return Err(format!("the domain needs a tuple of up to 5 fields or a map, got {}", describe(other))),
+    };
+    let (fields, values): (Vec<TypedField>, Vec<Value>) = present.into_iter().unzip();
+    let domain_type = TypedData { structs: vec![("EIP712Domain".to_string(), fields)] };
+    domain_type.hash_struct("EIP712Domain", &Value::Tuple(values))
+}
+
+/// The hash that is signed: keccak256(0x19 0x01 ‖ domainSeparator ‖ hashStruct(value))
+pub fn digest(domain: &Value, types: &TypedData, value: &Value) -> Result<[u8; 32], String> {
+    let mut encoded = vec![0x19, 0x01];
+    encoded.extend_from_slice(&domain_separator(domain)?);
+    encoded.extend_from_slice(&types.hash_struct(types.primary(), value)?);
+    Ok(keccak256(&encoded))
+}
+
+/// Sign a digest with a hex private key: `0x` + r ‖ s ‖ v, v being 27 or 28
+pub fn sign(private_key: &str, digest: &[u8; 32]) -> Result<String, String> {
+    let key = SigningKey::from_slice(&unhex(private_key)?).map_err(|_| "not a secp256k1 private key".to_string())?;
+    let (signature, recovery) = key.sign_prehash_recoverable(digest).map_err(|e| e.to_string())?;
+    Ok(format!("0x{}{:02x}", hex(&signature.to_bytes()), 27 + recovery.to_byte()))
+}
+
+/// The address whose key produced `signature` over `digest`
+pub fn recover(digest: &[u8; 32], signature: &str) -> Result<String, String> {
+    let bytes = unhex(signature)?;
+    let (rs, v) = match bytes.as_slice() {
+        [rs @ .., v] if rs.len() == 64 => (rs, *v),
+        _ => return Err("a signature is 65 bytes".to_string()),
+    };
+    let signature = Signature::from_slice(rs).map_err(|e| e.to_string())?;
+    let recovery = RecoveryId::from_byte(v.wrapping_sub(27)).ok_or_else(|| format!("bad recovery byte {}", v))?;
+    let key = VerifyingKey::recover_from_prehash(digest, &signature, recovery).map_err(|e| e.to_string())?;
+    Ok(address_of(&key))
+}
+
+/// The lowercase Ethereum address of a public key
+pub fn address_of(key: &VerifyingKey) -> String {
+    let point = key.to_encoded_point(false);
+    format!("0x{}", hex(&keccak256(&point.as_bytes()[1..])[12..]))
+}
+
+fn field(name: &str, kind: &str) -> TypedField {
+    TypedField { name: name.to_string(), kind: kind.to_string() }
+}
+
+pub(crate) fn encode_atomic(kind: &str, value: &Value) -> Result<[u8; 32], String> {
+    let mismatch = || format!("cannot encode {} as {}", describe(value), kind);
+    let mut word = [0u8; 32];
+    match (kind, value) {
+        ("string", Value::String(s)) => return Ok(keccak256(s.as_bytes())),
+        ("bytes", Value::String(s)) => return Ok(keccak256(&unhex(s)?)),
+        ("bool", Value::Bool(b)) => word[31] = u8::from(*b),
+        ("address", Value::Address(s) | Value::String(s)) => {
+            let bytes = unhex(s)?;
+            if bytes.len() != 20 {
+                return Err(format!("'{}' is not a 20-byte address", s));
+            }
+            word[12..].copy_from_slice(&bytes);
+        }
+        (kind, Value::String(s)) if kind.starts_with("bytes") => {
+            let bytes = unhex(s)?;
+            let size: usize = kind["bytes".len()..].parse().map_err(|_| mismatch())?;
+            if bytes.len() > size {
+                return Err(format!("'{}' is longer than {} bytes", s, size));
+            }
+            word[..bytes.len()].copy_from_slice(&bytes);
+        }
+        (kind, Value::Number(n)) if kind.starts_with("uint") && *n >= 0 => word[24..].copy_from_slice(&n.to_be_bytes()),
+        (kind, Value::U256(n)) if kind.starts_with("uint") => n.to_big_endian(&mut word),
+        (kind, Value::String(s)) if kind.starts_with("uint") => {
+            // Amounts past 128 bits are written as hex strings
+            let bytes = match s.strip_prefix("0x") {
+                Some(_) => unhex(s)?,
+                None => s.parse::<u128>().map_err(|_| mismatch())?.to_be_bytes().to_vec(),
+            };
+            if bytes.len() > 32 {
+                return Err(format!("'{}' does not fit in 256 bits", s));
+            }
+            word[32 - bytes.len()..].copy_from_slice(&bytes);
+        }
+        (kind, Value::Number(n)) if kind.starts_with("int") => {
+            word = [if *n < 0 { 0xff } else { 0 }; 32];
+            word[24..].copy_from_slice(&n.to_be_bytes());
+        }
+        _ => return Err(mismatch()),
+    }
+    Ok(word)
+}
+
+fn describe(value: &Value) -> &'static str {
+    match value {
+        Value::String(_) => "a string",
+        Value::Number(_) | Value::U256(_) => "a number",
+        Value::Bool(_) => "a bool",
+        Value::Address(_) => "an address",
+        Value::Array(_) => "an array",
+        Value::Tuple(_) => "a tuple",
+        Value::Map(_) => "a map",
+        _ => "an unsupported value",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAIL: &str = "Mail(Person from,Person to,string contents)Person(string name,address wallet)";
+
+    fn string(s: &str) -> Value {
+        Value::String(s.to_string())
+    }
+
+    /// The example from the EIP-712 specification
+    fn mail() -> (Value, TypedData, Value) {
+        let domain = Value::Tuple(vec![
+            string("Ether Mail"),
+            string("1"),
+            Value::Number(1),
+            string("0xCcCCccccCCCCcCCCCCCcCcCccCcCCCcCcccccccC"),
+        ]);
+        let value = Value::Tuple(vec![
+            Value::Tuple(vec![string("Cow"), string("0xCD2a3d9F938E13CD947Ec05AbC7FE734Df8DD826")]),
+            Value::Tuple(vec![string("Bob"), string("0xbBbBBBBbbBBBbbbBbbBbbbbBBbBbbbbBbBbbBBbB")]),
+            string("Hello, Bob!"),
+        ]);
+        (domain, TypedData::parse(MAIL).unwrap(), value)
+    }
+
+    #[test]
+    fn test_specification_example_hashes_and_signs() {
+        let (domain, types, value) = mail();
+        assert_eq!(types.encode_type("Mail"), "Mail(Person from,Person to,string contents)Person(string name,address wallet)");
+        assert_eq!(hex(&domain_separator(&domain).unwrap()), "f2cee375fa42b42143804025fc449deafd50cc031ca257e0b194a650a912090f");
+        let digest = digest(&domain, &types, &value).unwrap();
+        assert_eq!(hex(&digest), "be609aee343fb3c4b28e1df9e632fca64fcfaede20f02e86244efddf30957bd2");
+
+        let private_key = format!("0x{}", hex(&keccak256(b"cow")));
+        let signature = sign(&private_key, &digest).unwrap();
+        assert_eq!(
+            signature,
+            "0x4355c47d63924e8a72e509b65029052eb6c299d53a04e167c5775fd466751c9d\
+             07299936d304c153f6443dfa05f40ff007d72911b6f72307f996231605b915621c"
+        );
+        assert_eq!(recover(&digest, &signature).unwrap(), "0xcd2a3d9f938e13cd947ec05abc7fe734df8dd826");
+    }
+}
@@ -0,0 +1,167 @@
+// EIP-712 type descriptions, checked at compile time and hashed at run time
+// (see the parent module).
+
+/// One field of a struct type: `uint256 amount`
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypedField {
+    pub name: String,
+    pub kind: String,
+}
+
+/// The struct types of a description such as
+/// `Mail(Person from,Person to,string contents)Person(string name,address wallet)`
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypedData {
+    pub structs: Vec<(String, Vec<TypedField>)>, // The first is the primary type
+}
+
+impl TypedData {
+    /// Parse and check a description: every field type must be atomic or a
+    /// declared struct, optionally as an array
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut structs: Vec<(String, Vec<TypedField>)> = Vec::new();
+        let mut rest = text.trim();
+        if rest.is_empty() {
+            return Err("no struct types".to_string());
+        }
+        while !rest.is_empty() {
+            let (name, after) = rest.split_once('(').ok_or_else(|| format!("expected '(' after '{}'", rest))?;
+            let (inside, after) = after.split_once(')').ok_or_else(|| format!("'{}(' is never closed", name.trim()))?;
+            let name = name.trim();
+            if !is_identifier(name) {
+                return Err(format!("'{}' is not a struct name", name));
+            }
+            if structs.iter().any(|(existing, _)| existing == name) {
+                return Err(format!("struct '{}' is declared twice", name));
+            }
+            if is_atomic(name) {
+                return Err(format!("struct '{}' shadows an atomic type", name));
+            }
+
+            let mut fields: Vec<TypedField> = Vec::new();
+            for field in inside.split(',').map(str::trim).filter(|f| !f.is_empty()) {
+                let (kind, field_name) = match field.split_whitespace().collect::<Vec<_>>().as_slice() {
+                    [kind, field_name] => (kind.to_string(), field_name.to_string()),
+                    _ => return Err(format!("field '{}' of '{}' must be 'type name'", field, name)),
+                };
+                if !is_identifier(&field_name) {
+                    return Err(format!("'{}' is not a field name", field_name));
+                }
+                if fields.iter().any(|f| f.name == field_name) {
+                    return Err(format!("field '{}' of '{}' is declared twice", field_name, name));
+                }
+                fields.push(TypedField { name: field_name, kind });
+            }
+            structs.push((name.to_string(), fields));
+            rest = after.trim_start();
+        }
+
+        let data = TypedData { structs };
+        for (name, fields) in &data.structs {
+            for field in fields {
+                let base = element_base(&field.kind).ok_or_else(|| format!("bad array type '{}'", field.kind))?;
+                if !is_atomic(base) && data.fields(base).is_none() {
+                    return Err(format!("field '{}' of '{}' has unknown type '{}'", field.name, name, base));
+                }
+            }
+        }
+        Ok(data)
+    }
+
+    pub fn primary(&self) -> &str {
+        &self.structs[0].0
+    }
+
+    pub(super) fn fields(&self, name: &str) -> Option<&[TypedField]> {
+        self.structs.iter().find(|(n, _)| n == name).map(|(_, fields)| fields.as_slice())
+    }
+
+    /// `Name(type name,...)` followed by every struct it refers to, sorted
+    /// by name
+    pub fn encode_type(&self, name: &str) -> String {
+        let mut deps = Vec::new();
+        self.dependencies(name, &mut deps);
+        deps.retain(|dep| dep != name);
+        deps.sort();
+        std::iter::once(name)
+            .chain(deps.iter().map(String::as_str))
+            .map(|dep| {
+                let fields: Vec<String> = self
+                    .fields(dep)
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|f| format!("{} {}", f.kind, f.name))
+                    .collect();
+                format!("{}({})", dep, fields.join(","))
+            })
+            .collect()
+    }
+
+    fn dependencies(&self, name: &str, out: &mut Vec<String>) {
+        if out.iter().any(|dep| dep == name) {
+            return;
+        }
+        let Some(fields) = self.fields(name) else { return };
+        out.push(name.to_string());
+        for field in fields {
+            if let Some(base) = element_base(&field.kind) {
+                self.dependencies(base, out);
+            }
+        }
+    }
+}
+
+fn is_identifier(text: &str) -> bool {
+    text.chars().next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && text.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// `address`, `bool`, `string`, `bytes`, `bytes1`..`bytes32`, and `uint8`..`uint256` / `int8`..`int256` by 8
+fn is_atomic(kind: &str) -> bool {
+    let sized = |prefix: &str, valid: fn(usize) -> bool| {
+        kind.strip_prefix(prefix).and_then(|n| n.parse::<usize>().ok()).is_some_and(valid)
+    };
+    matches!(kind, "address" | "bool" | "string" | "bytes")
+        || sized("bytes", |n| (1..=32).contains(&n))
+        || sized("uint", |n| n % 8 == 0 && (8..=256).contains(&n))
+        || sized("int", |n| n % 8 == 0 && (8..=256).contains(&n))
+}
+
+/// `T[]` is (T, None) and `T[3]` is (T, Some(3))
+pub(super) fn split_array(kind: &str) -> Option<(&str, Option<usize>)> {
+    let (element, size) = kind.strip_suffix(']')?.rsplit_once('[')?;
+    Some((element, if size.is_empty() { None } else { Some(size.parse().ok()?) }))
+}
+
+/// The element type under any array suffixes, or None if one is malformed
+fn element_base(kind: &str) -> Option<&str> {
+    match split_array(kind) {
+        Some((element, _)) => element_base(element),
+        None if kind.ends_with(']') => None,
+        None => Some(kind),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_malformed_type_descriptions_are_rejected() {
+        let cases = [
+            ("", "no struct types"),
+            ("Mail(Person from)", "unknown type 'Person'"),
+            ("Mail(string a,string a)", "declared twice"),
+            ("Mail(string a)Mail(string b)", "declared twice"),
+            ("Mail(uint7 amount)", "unknown type 'uint7'"),
+            ("Mail(string)", "must be 'type name'"),
+            ("Mail(string a", "never closed"),
+            ("Mail(uint256[x] amounts)", "bad array type"),
+        ];
+        for (text, expected) in cases {
+            let err = TypedData::parse(text).unwrap_err();
+            assert!(err.contains(expected), "{}: {}", text, err);
+        }
+        assert!(TypedData::parse("Batch(Order[] orders,bytes32 salt)Order(uint256[2] ids)").is_ok());
+    }
+}
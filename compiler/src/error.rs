@@ -1,3 +1,5 @@
+use crate::ast::Span;
+
 /// Represents a compilation error with location and helpful information
 #[derive(Debug, Clone)]
 pub struct CompileError {
@@ -5,6 +7,16 @@ pub struct CompileError {
     pub line: usize,
     pub column: usize,
     pub help: Option<String>,
+    pub code: Option<&'static str>, // Catalog code, e.g. "E0101"
+    pub fix: Option<Box<Fix>>, // Boxed: most errors have none, and errors travel in Results
+}
+
+/// A mechanical fix for an error: the source in `span` (which may be
+/// elsewhere than the error itself) becomes `replacement`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fix {
+    pub span: Span,
+    pub replacement: String,
 }
 
 impl CompileError {
@@ -15,6 +27,21 @@ impl CompileError {
             line,
             column,
             help: None,
+            code: None,
+            fix: None,
+        }
+    }
+
+    /// Create an error whose message (and help, if any) comes from the
+    /// diagnostic catalog, so it follows the selected locale and overrides
+    pub fn coded(code: &'static str, args: &[&dyn std::fmt::Display], line: usize, column: usize) -> Self {
+        Self {
+            message: crate::messages::render(code, args),
+            line,
+            column,
+            help: crate::messages::help(code, args),
+            code: Some(code),
+            fix: None,
         }
     }
 
@@ -23,14 +50,20 @@ impl CompileError {
         self.help = Some(text.to_string());
         self
     }
+
+    /// Attach a mechanical fix
+    pub fn with_fix(mut self, span: Span, replacement: &str) -> Self {
+        self.fix = Some(Box::new(Fix { span, replacement: replacement.to_string() }));
+        self
+    }
 }
 
 impl std::fmt::Display for CompileError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "Error: {}\n → line {}, column {}",
-            self.message, self.line, self.column
-        )
+        match self.code {
+            Some(code) => write!(f, "Error[{}]: {}", code, self.message)?,
+            None => write!(f, "Error: {}", self.message)?,
+        }
+        write!(f, "\n → line {}, column {}", self.line, self.column)
     }
 }
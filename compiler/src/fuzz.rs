@@ -0,0 +1,470 @@
+use std::fmt;
+use crate::ast::{Attribute, Stmt, StmtKind};
+use crate::interpreter::{Interpreter, Value};
+
+// Contract fuzzing.
+//
+// `astrixa fuzz` deploys each contract that declares `#[invariant]` methods
+// in the interpreter, sends it random sequences of calls from a few sender
+// addresses, and calls every invariant after each call. An invariant fails
+// when it panics or returns false. A call that fails is a reverted
+// transaction: its state changes are rolled back and the sequence goes on.
+//
+// A failing sequence is shrunk before it is reported: calls are dropped and
+// arguments simplified for as long as the same invariant still fails, so
+// the reproducer is usually only a handful of calls long.
+//
+// Runs are deterministic for a given seed, so a failure can be replayed.
+
+/// Callers the fuzzer sends transactions from; the first deploys the contract
+pub const SENDERS: &[&str] = &[
+    "0x0000000000000000000000000000000000000001",
+    "0x0000000000000000000000000000000000000002",
+    "0x0000000000000000000000000000000000000003",
+];
+
+const INTERESTING_INTS: &[i64] = &[0, 1, 2, -1, 7, 10, 100, 1000];
+const INTERESTING_STRINGS: &[&str] = &["", "a", "astrixa"];
+
+#[derive(Debug, Clone)]
+pub struct FuzzConfig {
+    pub runs: usize,  // Call sequences per contract
+    pub depth: usize, // Calls per sequence, after the constructor
+    pub seed: u64,
+}
+
+impl Default for FuzzConfig {
+    fn default() -> Self {
+        Self { runs: 256, depth: 16, seed: 0 }
+    }
+}
+
+/// A generated argument, written as it would appear in source
+#[derive(Debug, Clone, PartialEq)]
+pub enum Input {
+    Int(i64),
+    U256(u128),
+    Bool(bool),
+    String(String),
+    Address(String),
+}
+
+impl Input {
+    fn to_value(&self) -> Value {
+        match self {
+            Input::Int(n) => Value::Number(*n),
+            Input::U256(n) => Value::U256(crate::u256::U256::from(*n)),
+            Input::Bool(b) => Value::Bool(*b),
+            Input::String(s) => Value::String(s.clone()),
+            Input::Address(a) => Value::Address(a.clone()),
+        }
+    }
+
+    /// Simpler inputs of the same kind, simplest first
+    fn simpler(&self) -> Vec<Input> {
+        let mut out = match self {
+            Input::Int(n) => vec![Input::Int(0), Input::Int(n / 2), Input::Int(n - n.signum())],
+            Input::U256(n) => vec![Input::U256(0), Input::U256(n / 2), Input::U256(n.saturating_sub(1))],
+            Input::Bool(_) => vec![Input::Bool(false)],
+            Input::String(s) => {
+                let half: String = s.chars().take(s.chars().count() / 2).collect();
+                vec![Input::String(String::new()), Input::String(half)]
+            }
+            Input::Address(_) => vec![Input::Address(SENDERS[0].to_string())],
+        };
+        out.dedup();
+        out.retain(|input| input != self);
+        out
+    }
+}
+
+impl fmt::Display for Input {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Input::Int(n) => write!(f, "{}", n),
+            Input::U256(n) => write!(f, "{}", n),
+            Input::Bool(b) => write!(f, "{}", b),
+            Input::String(s) | Input::Address(s) => write!(f, "{:?}", s),
+        }
+    }
+}
+
+/// One transaction in a call sequence
+#[derive(Debug, Clone, PartialEq)]
+pub struct Call {
+    pub method: String,
+    pub args: Vec<Input>,
+    pub sender: usize, // Index into SENDERS
+    pub value: u128,   // Only ever non-zero for #[payable] methods
+}
+
+impl fmt::Display for Call {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let args: Vec<String> = self.args.iter().map(|a| a.to_string()).collect();
+        write!(f, "{}({}) from {}", self.method, args.join(", "), SENDERS[self.sender])?;
+        if self.value > 0 {
+            write!(f, " with value {}", self.value)?;
+        }
+        Ok(())
+    }
+}
+
+/// A shrunk call sequence that breaks an invariant. When the contract has a
+/// constructor, the first call deploys it.
+#[derive(Debug, Clone)]
+pub struct Failure {
+    pub invariant: String,
+    pub reason: String,
+    pub calls: Vec<Call>,
+}
+
+/// Outcome of fuzzing one contract
+#[derive(Debug, Clone)]
+pub struct FuzzReport {
+    pub contract: String,
+    pub invariants: Vec<String>,
+    pub calls: usize, // Transactions sent, not counting shrinking
+    pub failure: Option<Failure>,
+}
+
+/// A parameter's input kind, from its annotation
+#[derive(Debug, Clone, Copy)]
+enum Kind {
+    Int,
+    U256,
+    Bool,
+    String,
+    Address,
+}
+
+#[derive(Debug, Clone)]
+struct Method {
+    name: String,
+    params: Vec<Kind>,
+    payable: bool,
+}
+
+struct Target<'a> {
+    program: &'a [Stmt],
+    contract: String,
+    constructor: Option<Method>,
+    methods: Vec<Method>,
+    invariants: Vec<String>,
+}
+
+/// Fuzz every contract in a type-checked program that declares invariants
+pub fn fuzz(program: &[Stmt], config: &FuzzConfig) -> Result<Vec<FuzzReport>, String> {
+    // Surface import errors once, instead of on every replay
+    Interpreter::new().load(program.to_vec())?;
+
+    let mut reports = Vec::new();
+    let mut rng = Rng::new(config.seed);
+
+    for stmt in program {
+        let StmtKind::Contract { name, constructor, methods, .. } = &stmt.kind else { continue };
+
+        let invariants: Vec<String> = methods
+            .iter()
+            .filter_map(|m| match &m.kind {
+                StmtKind::Function { name, attributes, .. } if attributes.contains(&Attribute::Invariant) => {
+                    Some(name.clone())
+                }
+                _ => None,
+            })
+            .collect();
+        if invariants.is_empty() {
+            continue;
+        }
+
+        // Views and invariants cannot change state, so calling them is wasted
+        let callable = methods.iter().filter(|m| {
+            !matches!(&m.kind, StmtKind::Function { attributes, .. }
+                if attributes.contains(&Attribute::View) || attributes.contains(&Attribute::Invariant))
+        });
+        let target = Target {
+            program,
+            contract: name.clone(),
+            constructor: constructor.as_deref().and_then(method),
+            methods: callable.filter_map(method).collect(),
+            invariants,
+        };
+
+        reports.push(fuzz_contract(&target, config, &mut rng));
+    }
+    Ok(reports)
+}
+
+fn method(stmt: &Stmt) -> Option<Method> {
+    let StmtKind::Function { name, param_types, attributes, .. } = &stmt.kind else { return None };
+    let params = param_types
+        .iter()
+        .map(|annotation| match annotation.as_deref().map(str::to_ascii_lowercase).as_deref() {
+            Some("u256") | Some("uint") => Kind::U256,
+            Some("bool") => Kind::Bool,
+            Some("string") => Kind::String,
+            Some("address") => Kind::Address,
+            _ => Kind::Int,
+        })
+        .collect();
+    Some(Method { name: name.clone(), params, payable: attributes.contains(&Attribute::Payable) })
+}
+
+fn fuzz_contract(target: &Target, config: &FuzzConfig, rng: &mut Rng) -> FuzzReport {
+    let mut report = FuzzReport {
+        contract: target.contract.clone(),
+        invariants: target.invariants.clone(),
+        calls: 0,
+        failure: None,
+    };
+
+    for _ in 0..config.runs {
+        let mut calls = Vec::new();
+        if let Some(constructor) = &target.constructor {
+            let mut deploy = rng.call(constructor);
+            deploy.sender = 0;
+            calls.push(deploy);
+        }
+        if !target.methods.is_empty() {
+            for _ in 0..config.depth {
+                let method = &target.methods[rng.below(target.methods.len())];
+                calls.push(rng.call(method));
+            }
+        }
+
+        let (sent, violation) = replay(target, &calls);
+        report.calls += sent;
+        if let Some((invariant, reason)) = violation {
+            calls.truncate(sent);
+            let calls = shrink(target, calls, &invariant);
+            // Report the shrunk sequence's own failure message
+            let (_, violation) = replay(target, &calls);
+            let reason = violation.map_or(reason, |(_, reason)| reason);
+            report.failure = Some(Failure { invariant, reason, calls });
+            break;
+        }
+    }
+    report
+}
+
+/// Run `calls` against a freshly deployed contract, checking the invariants
+/// after each one. Returns how many calls were sent and the first broken
+/// invariant with the reason it failed.
+fn replay(target: &Target, calls: &[Call]) -> (usize, Option<(String, String)>) {
+    let mut interpreter = Interpreter::new();
+    if interpreter.load(target.program.to_vec()).is_err() {
+        return (0, None);
+    }
+
+    let deployer = SENDERS[0];
+    for (i, call) in calls.iter().enumerate() {
+        let args = call.args.iter().map(Input::to_value).collect();
+        let sent = interpreter.transact(&target.contract, &call.method, args, SENDERS[call.sender], call.value);
+        // A constructor may reject its arguments; nothing was deployed then
+        if sent.is_err() && i == 0 && target.constructor.is_some() {
+            return (1, None);
+        }
+
+        for invariant in &target.invariants {
+            let reason = match interpreter.transact(&target.contract, invariant, vec![], deployer, 0) {
+                Ok(Value::Bool(false)) | Ok(Value::Number(0)) => format!("{}() returned false", invariant),
+                Ok(_) => continue,
+                Err(e) => e,
+            };
+            return (i + 1, Some((invariant.clone(), reason)));
+        }
+    }
+    (calls.len(), None)
+}
+
+/// Whether `calls` still breaks `invariant`
+fn still_fails(target: &Target, calls: &[Call], invariant: &str) -> bool {
+    matches!(replay(target, calls), (_, Some((broken, _))) if broken == invariant)
+}
+
+/// Drop calls and simplify arguments while the invariant keeps failing
+fn shrink(target: &Target, mut calls: Vec<Call>, invariant: &str) -> Vec<Call> {
+    // The constructor call always stays first
+    let fixed = usize::from(target.constructor.is_some()).min(calls.len());
+
+    loop {
+        let mut progress = false;
+
+        let mut i = fixed;
+        while i < calls.len() {
+            let mut candidate = calls.clone();
+            candidate.remove(i);
+            if still_fails(target, &candidate, invariant) {
+                calls = candidate;
+                progress = true;
+            } else {
+                i += 1;
+            }
+        }
+
+        for i in 0..calls.len() {
+            let mut options = Vec::new();
+            for (a, arg) in calls[i].args.iter().enumerate() {
+                for simpler in arg.simpler() {
+                    let mut call = calls[i].clone();
+                    call.args[a] = simpler;
+                    options.push(call);
+                }
+            }
+            if calls[i].value > 0 {
+                for value in [0, calls[i].value / 2] {
+                    options.push(Call { value, ..calls[i].clone() });
+                }
+            }
+            if calls[i].sender > 0 {
+                options.push(Call { sender: 0, ..calls[i].clone() });
+            }
+
+            for option in options {
+                let mut candidate = calls.clone();
+                candidate[i] = option;
+                if still_fails(target, &candidate, invariant) {
+                    calls = candidate;
+                    progress = true;
+                    break;
+                }
+            }
+        }
+
+        if !progress {
+            return calls;
+        }
+    }
+}
+
+/// SplitMix64: small, fast and plenty for picking inputs
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Rng(seed)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    /// Mostly boundary values, sometimes anything in -1000..=1000
+    fn int(&mut self) -> i64 {
+        if self.below(2) == 0 {
+            INTERESTING_INTS[self.below(INTERESTING_INTS.len())]
+        } else {
+            self.below(2001) as i64 - 1000
+        }
+    }
+
+    fn input(&mut self, kind: Kind) -> Input {
+        match kind {
+            Kind::Int => Input::Int(self.int()),
+            Kind::U256 => Input::U256(self.int().unsigned_abs() as u128),
+            Kind::Bool => Input::Bool(self.below(2) == 0),
+            Kind::String => Input::String(INTERESTING_STRINGS[self.below(INTERESTING_STRINGS.len())].to_string()),
+            Kind::Address => Input::Address(SENDERS[self.below(SENDERS.len())].to_string()),
+        }
+    }
+
+    fn call(&mut self, method: &Method) -> Call {
+        Call {
+            method: method.name.clone(),
+            args: method.params.iter().map(|kind| self.input(*kind)).collect(),
+            sender: self.below(SENDERS.len()),
+            value: if method.payable { self.int().unsigned_abs() as u128 } else { 0 },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn fuzz_source(source: &str) -> Vec<FuzzReport> {
+        let program = Parser::new(Lexer::new(source)).parse().unwrap();
+        fuzz(&program, &FuzzConfig { runs: 64, depth: 8, seed: 7 }).unwrap()
+    }
+
+    #[test]
+    fn test_broken_invariant_is_shrunk_to_minimal_sequence() {
+        // withdraw forgets to check the balance, so it can go negative
+        let source = r#"
+            contract Bank {
+                state balance
+                constructor(initial) {
+                    balance = 0
+                }
+                fn deposit(amount) {
+                    if amount < 0 { panic("negative deposit") }
+                    balance = balance + amount
+                }
+                fn withdraw(amount) {
+                    balance = balance - amount
+                }
+                #[view]
+                fn get() {
+                    return balance
+                }
+                #[invariant]
+                fn solvent() {
+                    return balance >= 0
+                }
+            }
+        "#;
+
+        let reports = fuzz_source(source);
+        assert_eq!(reports.len(), 1);
+        let failure = reports[0].failure.as_ref().expect("the invariant should break");
+        assert_eq!(failure.invariant, "solvent");
+        assert_eq!(failure.reason, "solvent() returned false");
+
+        // Deploy, then one withdrawal of the smallest amount that breaks it
+        let shown: Vec<String> = failure.calls.iter().map(|c| c.to_string()).collect();
+        assert_eq!(shown, vec![
+            format!("constructor(0) from {}", SENDERS[0]),
+            format!("withdraw(1) from {}", SENDERS[0]),
+        ]);
+    }
+
+    #[test]
+    fn test_holding_invariants_and_reverted_calls() {
+        // The failed deposit reverts, so total never goes negative
+        let source = r#"
+            contract Counter {
+                state total
+                constructor() {
+                    total = 0
+                }
+                fn add(n) {
+                    total = total + n
+                    if total < 0 { panic("underflow") }
+                }
+                #[invariant]
+                fn non_negative() {
+                    if total < 0 { panic("total is negative") }
+                }
+            }
+            contract Plain {
+                state x
+                fn set(v) { x = v }
+            }
+        "#;
+
+        let reports = fuzz_source(source);
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].contract, "Counter");
+        assert!(reports[0].failure.is_none(), "{:?}", reports[0].failure);
+        assert_eq!(reports[0].calls, 64 * 9);
+    }
+}
@@ -20,10 +20,15 @@ pub fn gas_cost(op: &OpCode) -> u64 {
         OpCode::Sub => 2,            // Subtraction
         OpCode::Mul => 5,            // Multiplication (more expensive)
         OpCode::Div => 8,            // Division (most expensive)
+        OpCode::Mod => 8,            // Remainder, priced like division
         
         // Comparison operations
+        OpCode::Equal => 2,
+        OpCode::NotEqual => 2,
         OpCode::Greater => 2,
         OpCode::Less => 2,
+        OpCode::GreaterEqual => 2,
+        OpCode::LessEqual => 2,
         
         // Control flow (cheap)
         OpCode::Jump => 1,           // Unconditional jump
@@ -55,15 +60,17 @@ pub struct GasLimits {
     pub transaction: u64,     // Full transaction
 }
 
-impl GasLimits {
-    pub fn default() -> Self {
+impl Default for GasLimits {
+    fn default() -> Self {
         GasLimits {
             script: 1_000_000,      // 1 million gas for scripts
             contract: 100_000,      // 100k gas for contract calls
             transaction: 10_000_000, // 10 million gas for full transactions
         }
     }
+}
 
+impl GasLimits {
     pub fn test() -> Self {
         GasLimits {
             script: 100_000,
@@ -91,11 +98,7 @@ impl GasContext {
     }
 
     pub fn remaining(&self) -> u64 {
-        if self.gas_used > self.gas_limit {
-            0
-        } else {
-            self.gas_limit - self.gas_used
-        }
+        self.gas_limit.saturating_sub(self.gas_used)
     }
 
     pub fn is_out_of_gas(&self) -> bool {
@@ -0,0 +1,604 @@
+use serde_json::{json, Value};
+use crate::ast::{Attribute, ConditionKind};
+use crate::token::{Token, CONTEXTUAL_KEYWORDS, KEYWORDS, OPERATORS};
+
+// Editor grammars generated from the lexer's token tables, so highlighting
+// picks up new keywords and operators without hand-editing grammar files.
+
+/// TextMate scope for a keyword token
+fn keyword_scope(token: &Token) -> &'static str {
+    match token {
+        Token::If | Token::Else | Token::While | Token::For | Token::Return | Token::Await
+        | Token::Panic | Token::Require => {
+            "keyword.control.astrixa"
+        }
+        Token::Import | Token::Export => "keyword.control.import.astrixa",
+        Token::Fn | Token::Let | Token::Contract => "storage.type.astrixa",
+        Token::Async => "storage.modifier.astrixa",
+        Token::True | Token::False => "constant.language.boolean.astrixa",
+        _ => "keyword.other.astrixa",
+    }
+}
+
+/// Binding strength of a binary operator; None for non-expression operators
+fn operator_precedence(token: &Token) -> Option<u8> {
+    match token {
+        Token::OrOr => Some(1),
+        Token::AndAnd => Some(2),
+        Token::EqualEqual | Token::NotEqual | Token::Less | Token::LessEqual
+        | Token::Greater | Token::GreaterEqual => Some(3),
+        Token::Plus | Token::Minus => Some(4),
+        Token::Star | Token::Slash | Token::Percent => Some(5),
+        _ => None,
+    }
+}
+
+/// Spelling of a keyword token, as the lexer reads it
+fn kw(token: Token) -> &'static str {
+    KEYWORDS
+        .iter()
+        .find(|(_, t)| *t == token)
+        .map(|(word, _)| *word)
+        .unwrap_or_else(|| panic!("{:?} is not a keyword", token))
+}
+
+/// A contextual keyword, checked against the table
+fn contextual(word: &'static str) -> &'static str {
+    assert!(CONTEXTUAL_KEYWORDS.contains(&word), "'{}' is not a contextual keyword", word);
+    word
+}
+
+fn escape_regex(text: &str) -> String {
+    let mut out = String::new();
+    for ch in text.chars() {
+        if "\\^$.|?*+()[]{}/".contains(ch) {
+            out.push('\\');
+        }
+        out.push(ch);
+    }
+    out
+}
+
+/// TextMate grammar (`astrixa.tmLanguage.json`) for VS Code and friends
+pub fn textmate() -> Value {
+    // Group keywords by scope, keeping table order
+    let mut groups: Vec<(&str, Vec<&str>)> = Vec::new();
+    for (word, token) in KEYWORDS {
+        let scope = keyword_scope(token);
+        match groups.iter_mut().find(|(s, _)| *s == scope) {
+            Some((_, words)) => words.push(word),
+            None => groups.push((scope, vec![word])),
+        }
+    }
+
+    let mut keyword_patterns: Vec<Value> = groups
+        .iter()
+        .map(|(scope, words)| json!({ "name": scope, "match": format!("\\b({})\\b", words.join("|")) }))
+        .collect();
+    // Contract members start a line; `as` in an import is covered by the
+    // import pattern
+    keyword_patterns.push(json!({
+        "name": "keyword.other.astrixa",
+        "match": format!("^\\s*({}|{})\\b", contextual("state"), contextual("constructor")),
+    }));
+    // A cast names its type: `count as Float`
+    keyword_patterns.push(json!({
+        "match": format!("\\b({})\\s+([A-Z][A-Za-z0-9_]*)", contextual("as")),
+        "captures": {
+            "1": { "name": "keyword.operator.cast.astrixa" },
+            "2": { "name": "entity.name.type.astrixa" }
+        }
+    }));
+    // `match` and `defer` start a statement, and are plain names when
+    // assigned to
+    keyword_patterns.push(json!({
+        "name": keyword_scope(&Token::If),
+        "match": format!("^\\s*({}|{})\\b(?!\\s*=[^=>])", contextual("match"), contextual("defer")),
+    }));
+
+    let operators: Vec<String> = OPERATORS.iter().map(|(op, _)| escape_regex(op)).collect();
+    let attributes: Vec<&str> = Attribute::ALL.iter().map(|a| a.name()).collect();
+    let conditions = [ConditionKind::Requires.name(), ConditionKind::Ensures.name()];
+
+    json!({
+        "$schema": "https://raw.githubusercontent.com/martinring/tmlanguage/master/tmlanguage.json",
+        "name": "ASTRIXA",
+        "scopeName": "source.astrixa",
+        "fileTypes": ["ax"],
+        "patterns": [
+            { "include": "#comments" },
+            { "include": "#strings" },
+            { "include": "#numbers" },
+            { "include": "#attributes" },
+            { "include": "#declarations" },
+            { "include": "#keywords" },
+            { "include": "#calls" },
+            { "include": "#operators" },
+        ],
+        "repository": {
+            "comments": {
+                "patterns": [{ "name": "comment.line.double-slash.astrixa", "match": "//.*$" }]
+            },
+            "strings": {
+                "patterns": [
+                    {
+                        "name": "string.quoted.other.raw.astrixa",
+                        "begin": "\\br(#*)\"",
+                        "end": "\"\\1"
+                    },
+                    {
+                        "name": "string.quoted.double.astrixa",
+                        "begin": "\"",
+                        "end": "\"",
+                        "patterns": [{
+                            "name": "constant.character.escape.astrixa",
+                            "match": "\\\\(x[0-9A-Fa-f]{2}|u\\{[0-9A-Fa-f]{1,6}\\}|.)"
+                        }]
+                    }
+                ]
+            },
+            "numbers": {
+                "patterns": [{ "name": "constant.numeric.astrixa", "match": "\\b[0-9]+(\\.[0-9]+)?\\b" }]
+            },
+            "attributes": {
+                "patterns": [
+                    {
+                        "match": format!("(#\\[)\\s*({})\\s*(\\])", attributes.join("|")),
+                        "captures": {
+                            "1": { "name": "punctuation.definition.attribute.astrixa" },
+                            "2": { "name": "entity.other.attribute-name.astrixa" },
+                            "3": { "name": "punctuation.definition.attribute.astrixa" }
+                        }
+                    },
+                    {
+                        // `#[requires(...)]`: the condition is highlighted as code
+                        "match": format!("(#\\[)\\s*({})\\s*(?=\\()", conditions.join("|")),
+                        "captures": {
+                            "1": { "name": "punctuation.definition.attribute.astrixa" },
+                            "2": { "name": "entity.other.attribute-name.astrixa" }
+                        }
+                    }
+                ]
+            },
+            "declarations": {
+                "patterns": [
+                    {
+                        "match": format!(
+                            "\\b({})\\s+([A-Za-z_][A-Za-z0-9_]*)(?:\\s+({})\\s+([A-Za-z_][A-Za-z0-9_]*))?",
+                            kw(Token::Import),
+                            contextual("as")
+                        ),
+                        "captures": {
+                            "1": { "name": keyword_scope(&Token::Import) },
+                            "2": { "name": "entity.name.namespace.astrixa" },
+                            "3": { "name": keyword_scope(&Token::Import) },
+                            "4": { "name": "entity.name.namespace.astrixa" }
+                        }
+                    },
+                    {
+                        // `type Wei = U256`; `type(x)` is a call
+                        "match": format!("^\\s*({})\\s+([A-Za-z_][A-Za-z0-9_]*)\\s*=", contextual("type")),
+                        "captures": {
+                            "1": { "name": "keyword.other.astrixa" },
+                            "2": { "name": "entity.name.type.astrixa" }
+                        }
+                    },
+                    {
+                        "match": format!("\\b({})\\s+([A-Za-z_][A-Za-z0-9_]*)", kw(Token::Fn)),
+                        "captures": {
+                            "1": { "name": keyword_scope(&Token::Fn) },
+                            "2": { "name": "entity.name.function.astrixa" }
+                        }
+                    },
+                    {
+                        "match": format!(
+                            "\\b({})\\s+([A-Za-z_][A-Za-z0-9_]*)\\s+({})\\b",
+                            kw(Token::For),
+                            contextual("in")
+                        ),
+                        "captures": {
+                            "1": { "name": keyword_scope(&Token::For) },
+                            "2": { "name": "variable.other.loop.astrixa" },
+                            "3": { "name": keyword_scope(&Token::For) }
+                        }
+                    },
+                    {
+                        "match": format!("\\b({})\\s+({})\\b", kw(Token::Let), contextual("mut")),
+                        "captures": {
+                            "1": { "name": keyword_scope(&Token::Let) },
+                            "2": { "name": keyword_scope(&Token::Let) }
+                        }
+                    },
+                    {
+                        "match": format!("\\b({})\\s+([A-Za-z_][A-Za-z0-9_]*)", kw(Token::Contract)),
+                        "captures": {
+                            "1": { "name": keyword_scope(&Token::Contract) },
+                            "2": { "name": "entity.name.type.contract.astrixa" }
+                        }
+                    }
+                ]
+            },
+            "keywords": { "patterns": keyword_patterns },
+            "calls": {
+                "patterns": [{
+                    "match": "\\b([A-Za-z_][A-Za-z0-9_]*)\\s*(?=\\()",
+                    "captures": { "1": { "name": "entity.name.function.call.astrixa" } }
+                }]
+            },
+            "operators": {
+                "patterns": [{ "name": "keyword.operator.astrixa", "match": operators.join("|") }]
+            }
+        }
+    })
+}
+
+/// tree-sitter grammar skeleton: `grammar.js` and `queries/highlights.scm`
+pub fn tree_sitter() -> Vec<(&'static str, String)> {
+    vec![
+        ("grammar.js", tree_sitter_grammar()),
+        ("queries/highlights.scm", tree_sitter_highlights()),
+    ]
+}
+
+fn tree_sitter_grammar() -> String {
+    // One choice per precedence level, e.g. prec.left(2, seq(a, choice('+', '-'), b))
+    let mut levels: Vec<(u8, Vec<&str>)> = Vec::new();
+    for (op, token) in OPERATORS {
+        if let Some(prec) = operator_precedence(token) {
+            match levels.iter_mut().find(|(p, _)| *p == prec) {
+                Some((_, ops)) => ops.push(op),
+                None => levels.push((prec, vec![op])),
+            }
+        }
+    }
+    levels.sort_by_key(|(prec, _)| *prec);
+    let binary = levels
+        .iter()
+        .map(|(prec, ops)| {
+            let ops: Vec<String> = ops.iter().map(|op| format!("'{}'", op)).collect();
+            format!(
+                "      prec.left({}, seq(field('left', $._expression), field('operator', choice({})), field('right', $._expression))),",
+                prec,
+                ops.join(", ")
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    format!(
+        r##"// Generated by `astrixa emit-grammar --format=treesitter` from the compiler's
+// token tables. Regenerate instead of editing keywords and operators by hand.
+
+module.exports = grammar({{
+  name: 'astrixa',
+
+  extras: $ => [/\s/, $.comment],
+
+  word: $ => $.identifier,
+
+  rules: {{
+    source_file: $ => repeat($._item),
+
+    _item: $ => choice(
+      $.import_declaration,
+      $.function_declaration,
+      $.contract_declaration,
+      $.trait_declaration,
+      $.impl_declaration,
+      $.type_alias_declaration,
+    ),
+
+    import_declaration: $ => seq(
+      '{import}',
+      field('module', $.identifier),
+      optional(choice(
+        seq('.', '{{', commaSep(field('item', $.identifier)), '}}'),
+        seq('{as_}', field('alias', $.identifier)),
+      )),
+    ),
+
+    attribute: $ => seq('#', '[', field('name', $.identifier), optional(seq('(', field('condition', $._expression), ')')), ']'),
+
+    function_declaration: $ => seq(
+      repeat($.attribute),
+      optional('{export}'),
+      optional('{async_}'),
+      '{fn_}',
+      field('name', $.identifier),
+      optional($.type_parameters),
+      $.parameters,
+      optional(seq('->', field('return_type', $.type))),
+      field('body', $.block),
+    ),
+
+    type_parameters: $ => seq('<', commaSep($.identifier), '>'),
+
+    parameters: $ => seq('(', commaSep($.parameter), ')'),
+
+    parameter: $ => seq(
+      field('name', $.identifier),
+      optional(seq(':', $.type)),
+      optional(seq('=', field('default', $._expression))),
+    ),
+
+    type: $ => choice(
+      seq($.identifier, optional(seq('<', commaSep($.type), '>'))),
+      seq('{fn_}', '(', commaSep($.type), ')', optional(seq('->', $.type))),
+    ),
+
+    contract_declaration: $ => seq(
+      '{contract}',
+      field('name', $.identifier),
+      '{{',
+      repeat(choice($.state_declaration, $.constructor_declaration, $.function_declaration, ';')),
+      '}}',
+    ),
+
+    state_declaration: $ => seq(
+      '{state}',
+      choice(
+        seq(':', '[', commaSep(choice($.string, $.identifier)), ']'),
+        seq($.identifier, optional(seq(':', $.type))),
+      ),
+    ),
+
+    trait_declaration: $ => seq(
+      '{trait_}',
+      field('name', $.identifier),
+      '{{',
+      repeat(choice($.method_signature, ';')),
+      '}}',
+    ),
+
+    method_signature: $ => seq(
+      '{fn_}',
+      field('name', $.identifier),
+      optional($.type_parameters),
+      $.parameters,
+      optional(seq('->', field('return_type', $.type))),
+    ),
+
+    impl_declaration: $ => seq(
+      '{impl_}',
+      field('trait', $.identifier),
+      '{for_}',
+      field('type', $.type),
+      '{{',
+      repeat(choice($.function_declaration, ';')),
+      '}}',
+    ),
+
+    type_alias_declaration: $ => seq('{type_}', field('name', $.identifier), '=', field('type', $.type)),
+
+    constructor_declaration: $ => seq('{constructor}', $.parameters, field('body', $.block)),
+
+    // Semicolons are optional statement terminators
+    block: $ => seq('{{', repeat(choice($._statement, ';')), '}}'),
+
+    _statement: $ => choice(
+      $.function_declaration,
+      $.let_statement,
+      $.assignment,
+      $.if_statement,
+      $.while_statement,
+      $.for_statement,
+      $.match_statement,
+      $.defer_statement,
+      $.return_statement,
+      $.panic_statement,
+      $.require_statement,
+      $.expression_statement,
+    ),
+
+    let_statement: $ => seq(
+      '{let_}',
+      optional('{mut_}'),
+      field('name', $.identifier),
+      '=',
+      field('value', $._expression),
+    ),
+
+    assignment: $ => seq(field('name', $.identifier), '=', field('value', $._expression)),
+
+    if_statement: $ => seq(
+      '{if_}',
+      field('condition', $._expression),
+      field('consequence', $.block),
+      optional(seq('{else_}', field('alternative', choice($.block, $.if_statement)))),
+    ),
+
+    while_statement: $ => seq('{while_}', field('condition', $._expression), field('body', $.block)),
+
+    for_statement: $ => seq(
+      '{for_}',
+      field('variable', $.identifier),
+      '{in_}',
+      field('iterable', $._expression),
+      field('body', $.block),
+    ),
+
+    match_statement: $ => seq(
+      '{match_}',
+      field('value', $._expression),
+      '{{',
+      repeat(seq($.match_arm, optional(','))),
+      '}}',
+    ),
+
+    match_arm: $ => seq(
+      field('pattern', $.pattern),
+      '=>',
+      field('body', choice($.block, $._statement)),
+    ),
+
+    // Some(x), Ok(x), Err(e), None or _
+    pattern: $ => seq($.identifier, optional(seq('(', $.identifier, ')'))),
+
+    defer_statement: $ => seq('{defer_}', $._expression),
+
+    return_statement: $ => seq('{return_}', $._expression),
+
+    panic_statement: $ => seq('{panic}', '(', $._expression, ')'),
+
+    require_statement: $ => seq(
+      '{require}',
+      '(',
+      field('condition', $._expression),
+      ',',
+      field('message', $._expression),
+      ')',
+    ),
+
+    expression_statement: $ => $._expression,
+
+    _expression: $ => choice(
+      $.range_expression,
+      $.binary_expression,
+      $.slice_expression,
+      $.try_expression,
+      $.cast_expression,
+      $.await_expression,
+      $.call_expression,
+      $.member_expression,
+      $.path_expression,
+      $.array_expression,
+      $.identifier,
+      $.number,
+      $.string,
+      $.boolean,
+      seq('(', $._expression, ')'),
+    ),
+
+    binary_expression: $ => choice(
+{binary}
+    ),
+
+    range_expression: $ => prec.left(0, seq(field('start', $._expression), '..', field('end', $._expression))),
+
+    slice_expression: $ => prec({call_prec}, seq(field('value', $._expression), '[', field('range', $._expression), ']')),
+
+    try_expression: $ => prec({call_prec}, seq(field('value', $._expression), '?')),
+
+    cast_expression: $ => prec({call_prec}, seq(field('value', $._expression), '{as_}', field('type', $.type))),
+
+    await_expression: $ => prec({await_prec}, seq('{await_}', $._expression)),
+
+    call_expression: $ => prec({call_prec}, seq(
+      field('function', choice($.identifier, $.member_expression, $.path_expression)),
+      $.arguments,
+    )),
+
+    member_expression: $ => seq(field('object', $.identifier), '.', field('property', $.identifier)),
+
+    path_expression: $ => seq($.identifier, repeat1(seq('::', $.identifier))),
+
+    arguments: $ => seq('(', commaSep(choice($.named_argument, $._expression)), ')'),
+
+    named_argument: $ => seq(field('name', $.identifier), ':', field('value', $._expression)),
+
+    array_expression: $ => seq('[', commaSep($._expression), optional(','), ']'),
+
+    boolean: $ => choice('{true_}', '{false_}'),
+
+    number: $ => /\d+(\.\d+)?/,
+
+    string: $ => choice(
+      /"([^"\\]|\\.)*"/,
+      /r"[^"]*"/,
+      /r#"([^"]|"[^#])*"#/,
+    ),
+
+    identifier: $ => /[A-Za-z_][A-Za-z0-9_]*/,
+
+    comment: $ => token(seq('//', /.*/)),
+  }},
+}});
+
+function commaSep(rule) {{
+  return optional(seq(rule, repeat(seq(',', rule)), optional(',')));
+}}
+"##,
+        import = kw(Token::Import),
+        export = kw(Token::Export),
+        async_ = kw(Token::Async),
+        fn_ = kw(Token::Fn),
+        contract = kw(Token::Contract),
+        state = contextual("state"),
+        constructor = contextual("constructor"),
+        as_ = contextual("as"),
+        trait_ = contextual("trait"),
+        impl_ = contextual("impl"),
+        type_ = contextual("type"),
+        let_ = kw(Token::Let),
+        if_ = kw(Token::If),
+        else_ = kw(Token::Else),
+        while_ = kw(Token::While),
+        for_ = kw(Token::For),
+        in_ = contextual("in"),
+        match_ = contextual("match"),
+        mut_ = contextual("mut"),
+        defer_ = contextual("defer"),
+        return_ = kw(Token::Return),
+        panic = kw(Token::Panic),
+        require = kw(Token::Require),
+        await_ = kw(Token::Await),
+        true_ = kw(Token::True),
+        false_ = kw(Token::False),
+        await_prec = levels.len() + 1,
+        call_prec = levels.len() + 2,
+        binary = binary,
+    )
+}
+
+fn tree_sitter_highlights() -> String {
+    let mut out = String::from(
+        ";; Generated by `astrixa emit-grammar --format=treesitter`\n\n",
+    );
+
+    let keywords: Vec<String> = KEYWORDS
+        .iter()
+        .filter(|(_, token)| !matches!(token, Token::True | Token::False))
+        .map(|(word, _)| format!("\"{}\"", word))
+        .chain(CONTEXTUAL_KEYWORDS.iter().map(|word| format!("\"{}\"", word)))
+        .collect();
+    out.push_str(&format!("[\n  {}\n] @keyword\n\n", keywords.join("\n  ")));
+
+    let operators: Vec<String> = OPERATORS.iter().map(|(op, _)| format!("\"{}\"", op)).collect();
+    out.push_str(&format!("[\n  {}\n] @operator\n\n", operators.join("\n  ")));
+
+    out.push_str("(function_declaration name: (identifier) @function)\n");
+    out.push_str("(call_expression function: (identifier) @function.call)\n");
+    out.push_str("(contract_declaration name: (identifier) @type)\n");
+    out.push_str("(trait_declaration name: (identifier) @type)\n");
+    out.push_str("(method_signature name: (identifier) @function)\n");
+    out.push_str("(impl_declaration trait: (identifier) @type)\n");
+    out.push_str("(type_alias_declaration name: (identifier) @type)\n");
+    out.push_str("(import_declaration module: (identifier) @module)\n");
+    out.push_str("(import_declaration alias: (identifier) @module)\n");
+    out.push_str("(attribute name: (identifier) @attribute)\n");
+    out.push_str("(boolean) @boolean\n");
+    out.push_str("(number) @number\n");
+    out.push_str("(string) @string\n");
+    out.push_str("(comment) @comment\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grammars_cover_every_keyword() {
+        let textmate = textmate().to_string();
+        let files = tree_sitter();
+        let highlights = &files[1].1;
+
+        for (word, _) in KEYWORDS {
+            assert!(textmate.contains(word), "textmate grammar misses '{}'", word);
+            assert!(files[0].1.contains(&format!("'{}'", word)), "grammar.js misses '{}'", word);
+            if !matches!(word, &"true" | &"false") {
+                assert!(highlights.contains(&format!("\"{}\"", word)), "highlights miss '{}'", word);
+            }
+        }
+    }
+}
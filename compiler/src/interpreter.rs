@@ -1,9 +1,31 @@
-use std::collections::{HashMap, HashSet};
-use crate::ast::{Expr, Stmt};
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::rc::Rc;
+use crate::ast::{Attribute, Expr, ExprKind, Pattern, Span, Stmt, StmtKind};
 use crate::lexer::Lexer;
+use crate::logs::{self, EventSignature, LogFilter, LogSource};
+use crate::eip712::{self, TypedData};
+use crate::multicall::{self, Call, CallSource, FunctionSignature};
+use crate::ipfs::{self, Cid, IpfsStore};
+use crate::fork::{ChainState, StateSource};
+use crate::arguments;
+use crate::types::{self, Aliases};
+use crate::u256::{self, U256};
+use crate::merkle;
+use crate::{bls, defi, ed25519};
+use crate::messages;
 use crate::parser::Parser;
+use crate::shutdown::{self, Signals, Stop};
+use crate::timeline::{Record, Step, Timeline};
 use crate::ai_runtime::{AIRuntime, LocalAIRuntime};
 use crate::package_manager::PackageManager;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::logs::RpcLogSource;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::multicall::RpcCallSource;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::ipfs::HttpIpfsStore;
 
 #[derive(Clone)]
 pub struct BlockchainContext {
@@ -16,37 +38,277 @@ pub struct BlockchainContext {
     pub tx_timestamp: i64,
 }
 
-#[derive(Clone)]
+// Arrays and maps are shared rather than copied: assigning one, passing it
+// to a function or putting it in another value gives another reference to
+// the same items, so a change made through one is seen through them all:
+//
+//   fn add_fee(items) {
+//       items.push(1)
+//   }
+//   let cart = [5, 10]
+//   add_fee(cart)
+//   print(cart)                // [5,10,1]
+//   let saved = cart.copy()    // independent of cart from here on
+//
+// Arrays change with `push(value)`, `pop()` (an Option) and `set(index,
+// value)`, maps with `set(key, value)`, which adds a missing key. Slices and
+// `copy()` are new arrays, and a `for` loop walks the items the array had
+// when the loop started. No array may contain itself. A `StringBuilder()`
+// is shared the same way and grows with `append(text)`. Everything else,
+// tuples included, is copied on assignment. The VM shares arrays the same
+// way; WASM builds have no arrays yet.
+
+/// The items of an array or map, shared by every value referring to them
+pub type Shared<T> = Rc<RefCell<T>>;
+
+/// Shared items as owned ones, copied only when something else refers to
+/// them too
+pub fn unshare<T: Clone>(shared: Shared<T>) -> T {
+    Rc::try_unwrap(shared).map_or_else(|shared| shared.borrow().clone(), RefCell::into_inner)
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum Value {
     String(String),
     Number(i64),
+    Float(f64),
     Bool(bool),
-    Array(Vec<Value>),
+    Array(Shared<Vec<Value>>),
+    Tuple(Vec<Value>),    // (a, b): fixed-size group, e.g. multiple return values
+    Map(Shared<Vec<(String, Value)>>), // Named fields in order, read with value["name"]
+    Option(Option<Box<Value>>), // Some(x) or None
+    Result(Result<Box<Value>, Box<Value>>), // Ok(x) or Err(e)
+    Range(i64, i64),      // start..end, counted lazily rather than stored as an array
+    Iter(Rc<Lazy>),       // map() or filter() of a sequence, run an item at a time
     Address(String),      // Web3: Blockchain address
-    U256(u128),           // Web3: 256-bit unsigned integer
+    U256(U256),           // Web3: 256-bit unsigned integer
     AIResult {            // AI: Inference result
         label: String,
         score: f64,
     },
+    Task(usize),          // Async: handle to a scheduled task
+    StringBuilder(Shared<String>), // Text appended in place; shared like an array
+    Function(String),     // A declared function used as a value, by its full name
     Null,
 }
 
+impl Value {
+    /// A new array of `items`
+    pub fn array(items: Vec<Value>) -> Value {
+        Value::Array(Rc::new(RefCell::new(items)))
+    }
+
+    /// A new map of `entries`
+    pub fn map(entries: Vec<(String, Value)>) -> Value {
+        Value::Map(Rc::new(RefCell::new(entries)))
+    }
+
+    /// An independent copy, sharing no arrays or maps with this value
+    pub fn deep_copy(&self) -> Value {
+        match self {
+            Value::Array(items) => Value::array(items.borrow().iter().map(Value::deep_copy).collect()),
+            Value::Map(entries) => {
+                Value::map(entries.borrow().iter().map(|(key, value)| (key.clone(), value.deep_copy())).collect())
+            }
+            Value::Tuple(items) => Value::Tuple(items.iter().map(Value::deep_copy).collect()),
+            Value::StringBuilder(text) => Value::StringBuilder(Rc::new(RefCell::new(text.borrow().clone()))),
+            Value::Option(Some(value)) => Value::Option(Some(Box::new(value.deep_copy()))),
+            Value::Result(Ok(value)) => Value::Result(Ok(Box::new(value.deep_copy()))),
+            Value::Result(Err(error)) => Value::Result(Err(Box::new(error.deep_copy()))),
+            other => other.clone(),
+        }
+    }
+
+    /// Whether this value is, or holds, the array or map at `items`
+    fn refers_to(&self, items: *const ()) -> bool {
+        match self {
+            Value::Array(shared) => {
+                Rc::as_ptr(shared) as *const () == items || shared.borrow().iter().any(|v| v.refers_to(items))
+            }
+            Value::Map(shared) => {
+                Rc::as_ptr(shared) as *const () == items || shared.borrow().iter().any(|(_, v)| v.refers_to(items))
+            }
+            Value::Tuple(values) => values.iter().any(|v| v.refers_to(items)),
+            Value::Option(Some(value)) | Value::Result(Ok(value)) | Value::Result(Err(value)) => value.refers_to(items),
+            Value::Iter(lazy) => lazy.refers_to(items),
+            _ => false,
+        }
+    }
+}
+
+/// A sequence whose items are only worked out as a loop reaches them:
+/// `range(0, 10_000_000)` is its two bounds, and `map` and `filter` wrap a
+/// sequence in the function they apply. Looping over one again starts
+/// over. `collect()` makes an array of the items.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Lazy {
+    Range(i64, i64),
+    Items(Shared<Vec<Value>>), // An array's, as they are when a loop starts
+    Map(Rc<Lazy>, String),    // Each item passed through the named function
+    Filter(Rc<Lazy>, String), // The items the named function is true for
+}
+
+impl Lazy {
+    /// The sequence a range, an array or an iterator value stands for
+    fn of(value: &Value) -> Option<Rc<Lazy>> {
+        match value {
+            Value::Range(start, end) => Some(Rc::new(Lazy::Range(*start, *end))),
+            Value::Array(items) => Some(Rc::new(Lazy::Items(items.clone()))),
+            Value::Iter(lazy) => Some(lazy.clone()),
+            _ => None,
+        }
+    }
+
+    fn refers_to(&self, items: *const ()) -> bool {
+        match self {
+            Lazy::Range(..) => false,
+            Lazy::Items(shared) => Value::Array(shared.clone()).refers_to(items),
+            Lazy::Map(source, _) | Lazy::Filter(source, _) => source.refers_to(items),
+        }
+    }
+}
+
 #[derive(Clone)]
 enum Control {
-    Value(Value),
+    Next,
     Return(Value),
 }
 
 type ExecResult = Result<Control, String>;
+type Visit<'v> = dyn FnMut(&mut Interpreter, Value) -> Result<Option<Control>, String> + 'v;
 type EvalResult = Result<Value, String>;
 
+/// Outcome of each `#[test]` function, by name
+pub type TestResults = Vec<(String, Result<(), String>)>;
+
+/// A change `reload` made that calls written against the old script may
+/// not expect
+#[derive(Debug, Clone, PartialEq)]
+pub enum Incompatible {
+    /// The function's parameters, their annotations or its async-ness changed
+    SignatureChanged(String),
+    /// The function is gone; calls to it now fail
+    Removed(String),
+    /// The contract's state variables changed: `removed` lost their values
+    /// and `added` start out null
+    StateChanged { contract: String, added: Vec<String>, removed: Vec<String> },
+}
+
+impl std::fmt::Display for Incompatible {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Incompatible::SignatureChanged(name) => write!(f, "{}", messages::render("W0101", &[name])),
+            Incompatible::Removed(name) => write!(f, "{}", messages::render("W0102", &[name])),
+            Incompatible::StateChanged { contract, added, removed } => {
+                let list = |vars: &[String]| if vars.is_empty() { "-".to_string() } else { vars.join(", ") };
+                write!(f, "{}", messages::render("W0103", &[contract, &list(added), &list(removed)]))
+            }
+        }
+    }
+}
+
+/// A `panic` that ended a run: its message, the statement that raised it,
+/// and the functions it unwound through, innermost first
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuntimePanic {
+    pub message: String,
+    pub span: Span,
+    pub backtrace: Vec<Frame>,
+    pub reverted: bool, // It left a contract method, which reverts
+}
+
+/// A function a panic unwound through, with the statement in it that was
+/// running
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub function: String,
+    pub span: Span,
+}
+
+impl RuntimePanic {
+    /// The reason data a chain reverts with: the message ABI-encoded as
+    /// `Error(string)`, as Solidity's `revert("...")` leaves it
+    pub fn revert_data(&self) -> Vec<u8> {
+        // The selector, the string's offset and length as words, then its
+        // bytes padded to a word
+        let message = self.message.as_bytes();
+        let mut data = vec![0x08, 0xc3, 0x79, 0xa0];
+        for word in [0x20, message.len()] {
+            data.extend([0; 24]);
+            data.extend((word as u64).to_be_bytes());
+        }
+        data.extend(message);
+        data.resize(4 + 64 + message.len().next_multiple_of(32), 0);
+        data
+    }
+}
+
+impl std::fmt::Display for RuntimePanic {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", messages::render("E0403", &[&self.message]))?;
+        for frame in &self.backtrace {
+            write!(f, "\n    at {} ({})", frame.function, frame.span)?;
+        }
+        Ok(())
+    }
+}
+
+/// A user-defined function or contract method
+#[derive(Clone)]
+struct Function {
+    params: Vec<String>,
+    param_types: Vec<Option<String>>, // Annotations, part of the signature
+    defaults: Vec<Option<Expr>>,      // Filled in for arguments a call leaves out
+    body: Vec<Stmt>,
+    is_async: bool,
+    contract: Option<String>, // Owning contract for methods
+    module: Option<String>,   // Module the function was imported from
+    exported: bool,           // Callable from outside its module
+}
+
+/// Lifecycle of a scheduled async task
+#[derive(Clone)]
+enum TaskState {
+    Pending { func: String, args: Vec<Value> },
+    Running,
+    Done(Value),
+}
+
 pub struct Interpreter {
     variables: HashMap<String, Value>,
     functions: HashMap<String, Function>,
-    contracts: HashMap<String, Contract>,
     contract_state: HashMap<String, HashMap<String, Value>>, // contract_name -> state vars
-    loaded_modules: HashSet<String>,
+    current_contract: Option<String>,
+    current_module: Option<String>,
+    loaded_modules: HashMap<String, Vec<String>>, // module -> its function names
+    module_names: HashMap<String, String>,        // name usable as `name.f()` -> module
+    aliases: Aliases,                             // `type Wei = U256` declarations
     blockchain_context: BlockchainContext,
+    tasks: Vec<TaskState>,
+    run_queue: VecDeque<usize>, // Pending tasks in scheduling order
+    keypairs_issued: u64,       // Seeds generate_keypair() deterministically
+    timeline: Option<Timeline>, // Storage writes and events, while recording
+    transaction: Option<usize>, // Timeline transaction in progress
+    error_span: Option<Span>,   // Where the last run or transaction failed
+    panic: Option<RuntimePanic>, // The panic unwinding, or that ended the last run
+    panic_site: Option<Span>,    // Where it is in the function it is unwinding from
+    log_source: Option<Box<dyn LogSource>>, // Node web3.logs reads from
+    log_cache: Option<PathBuf>,             // Where fetched log pages are kept
+    call_source: Option<Box<dyn CallSource>>, // Node web3.multicall reads from
+    ipfs_store: Option<Box<dyn IpfsStore>>,   // Where ipfs.add and ipfs.get go
+    chain: ChainState,                        // Accounts web3.balance and friends read
+    early_return: Option<Value>,            // None or Err a `?` is returning from the current function
+    stop: Stop,                             // Where signals to stop are noted
+    signals: Option<Signals>,               // Caught into `stop` while a program runs
+    shutdown_handlers: Vec<String>,         // Functions on_shutdown registered, in order
+    interrupted: Option<i32>,               // The signal that stopped the last run
+    output: Option<Vec<String>>,            // Printed lines, while capturing them
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Interpreter {
@@ -54,9 +316,12 @@ impl Interpreter {
         Interpreter {
             variables: HashMap::new(),
             functions: HashMap::new(),
-            contracts: HashMap::new(),
             contract_state: HashMap::new(),
-            loaded_modules: HashSet::new(),
+            current_contract: None,
+            current_module: None,
+            loaded_modules: HashMap::new(),
+            module_names: HashMap::new(),
+            aliases: Aliases::new(),
             blockchain_context: BlockchainContext {
                 chain_id: 1,
                 chain_name: "ethereum".to_string(),
@@ -66,6 +331,25 @@ impl Interpreter {
                 tx_hash: "0x0".to_string(),
                 tx_timestamp: 0,
             },
+            tasks: Vec::new(),
+            run_queue: VecDeque::new(),
+            keypairs_issued: 0,
+            timeline: None,
+            transaction: None,
+            error_span: None,
+            panic: None,
+            panic_site: None,
+            log_source: None,
+            log_cache: None,
+            call_source: None,
+            ipfs_store: None,
+            chain: ChainState::default(),
+            early_return: None,
+            stop: Stop::default(),
+            signals: None,
+            shutdown_handlers: Vec::new(),
+            interrupted: None,
+            output: None,
         }
     }
 
@@ -73,318 +357,1306 @@ impl Interpreter {
         self.blockchain_context = context;
     }
 
+    /// Read `web3.logs` from `source`, caching pages in `cache` if given,
+    /// those the source says are final. Without a source, the node at
+    /// ASTRIXA_RPC_URL is used.
+    pub fn set_log_source(&mut self, source: Box<dyn LogSource>, cache: Option<PathBuf>) {
+        self.log_source = Some(source);
+        self.log_cache = cache;
+    }
+
+    /// Answer `web3.multicall` from `source`. Without one, the node at
+    /// ASTRIXA_RPC_URL is used.
+    pub fn set_call_source(&mut self, source: Box<dyn CallSource>) {
+        self.call_source = Some(source);
+    }
+
+    /// Store and fetch `ipfs` content through `store`. Without one, the
+    /// services named by the ASTRIXA_IPFS_* variables are used.
+    pub fn set_ipfs_store(&mut self, store: Box<dyn IpfsStore>) {
+        self.ipfs_store = Some(store);
+    }
+
+    /// Read accounts from a forked chain, lazily, and take on its chain ID
+    pub fn set_fork(&mut self, source: Box<dyn StateSource>) -> Result<(), String> {
+        self.blockchain_context.chain_id = source.chain_id().map_err(|e| messages::render("E0463", &[&"the chain ID", &e]))?;
+        self.chain = ChainState::forked(source);
+        Ok(())
+    }
+
+    /// Run `main`. SIGINT and SIGTERM stop it gracefully (see `shutdown`):
+    /// the run fails as interrupted once its `on_shutdown` handlers have run.
     pub fn run(&mut self, program: Vec<Stmt>) -> Result<(), String> {
-        for stmt in program {
-            match stmt {
-                Stmt::Function(func) => {
-                    self.functions.insert(func.name.clone(), func);
-                }
-                Stmt::Contract(contract) => {
-                    // Initialize contract state storage
-                    let mut state = HashMap::new();
-                    for var in &contract.state {
-                        state.insert(var.clone(), Value::Null);
-                    }
-                    self.contract_state.insert(contract.name.clone(), state);
-                    
-                    // Store contract definition
-                    self.contracts.insert(contract.name.clone(), contract);
+        self.error_span = None;
+        self.panic = None;
+        self.interrupted = None;
+        self.shutdown_handlers.clear();
+        self.signals = Some(Signals::catch(self.stop.clone()));
+
+        let result = self.load(program).and_then(|_| {
+            if self.functions.contains_key("main") {
+                self.call_function("main", vec![]).map(|_| ())
+            } else {
+                Err(messages::render("E0401", &[]))
+            }
+        });
+        // Tasks that were spawned but never awaited still run to completion
+        let result = result.and_then(|_| self.run_pending_tasks());
+
+        // The handlers run with signals still caught, so another one exits
+        let signals = self.signals.take();
+        let result = match signals.as_ref().and_then(Signals::received) {
+            Some(signal) => self.shut_down(signal, result),
+            None => result,
+        };
+        self.stop.clear();
+        result
+    }
+
+    /// Where runs note the signal to stop on; raising one on it from
+    /// another thread stops the current run as that signal would
+    pub fn stop_handle(&self) -> Stop {
+        self.stop.clone()
+    }
+
+    /// Run the `on_shutdown` handlers, the last registered first as defers
+    /// are, then flush output. A handler failing is reported over the
+    /// interruption, and the handlers after it still run.
+    fn shut_down(&mut self, signal: i32, interrupted: Result<(), String>) -> Result<(), String> {
+        self.interrupted = Some(signal);
+        let mut failed = None;
+        for handler in std::mem::take(&mut self.shutdown_handlers).into_iter().rev() {
+            if let Err(e) = self.call_function(&handler, vec![]) {
+                failed.get_or_insert(e);
+            }
+        }
+        shutdown::flush();
+        failed.map_or(interrupted, Err)
+    }
+
+    /// The signal that stopped the last `run`, if one did
+    pub fn interrupted(&self) -> Option<i32> {
+        self.interrupted
+    }
+
+    /// An error once a signal has asked the run to stop
+    fn check_signals(&self) -> Result<(), String> {
+        match self.signals.as_ref().and_then(Signals::received) {
+            Some(signal) => Err(messages::render("E0488", &[&shutdown::name(signal)])),
+            None => Ok(()),
+        }
+    }
+
+    /// Run every top-level `#[test]` function in declaration order. A test
+    /// passes when it (and any task it spawns) finishes without an error.
+    pub fn run_tests(&mut self, program: Vec<Stmt>) -> Result<TestResults, String> {
+        let tests: Vec<String> = program
+            .iter()
+            .filter_map(|stmt| match &stmt.kind {
+                StmtKind::Function { name, attributes, .. } if attributes.contains(&Attribute::Test) => {
+                    Some(name.clone())
                 }
-                Stmt::Import(module) => {
-                    self.load_module(&module)?;
+                _ => None,
+            })
+            .collect();
+
+        self.load(program)?;
+
+        let mut results = Vec::new();
+        for name in tests {
+            self.error_span = None;
+            self.panic = None;
+            let result = self
+                .call_function(&name, vec![])
+                .map(|_| ())
+                .and_then(|_| self.run_pending_tasks())
+                .map_err(|e| match self.error_span {
+                    Some(span) => format!("{} ({})", e, span),
+                    None => e,
+                });
+            // A failed test must not leave its tasks behind for the next one
+            self.run_queue.clear();
+            results.push((name, result));
+        }
+        Ok(results)
+    }
+
+    /// The innermost statement at which the last `run` or `transact` failed
+    pub fn error_span(&self) -> Option<Span> {
+        self.error_span
+    }
+
+    /// The panic the last `run`, `transact` or call failed with, if it
+    /// failed by panicking
+    pub fn take_panic(&mut self) -> Option<RuntimePanic> {
+        self.panic.take()
+    }
+
+    /// Record storage writes and events from now on, for time-travel
+    /// inspection; contracts declared afterwards start the timeline
+    pub fn record(&mut self) {
+        self.timeline = Some(Timeline::default());
+    }
+
+    /// The recorded timeline, if `record` was called
+    pub fn take_timeline(&mut self) -> Option<Timeline> {
+        self.timeline.take()
+    }
+
+    /// Keep printed lines from now on instead of writing them to stdout
+    pub fn capture_output(&mut self) {
+        self.output = Some(Vec::new());
+    }
+
+    /// The lines printed since `capture_output`, which keeps capturing
+    pub fn take_output(&mut self) -> Vec<String> {
+        self.output.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    /// Declare a program's functions, contracts and imports without running
+    /// anything, so its contracts can be driven with `transact`
+    pub fn load(&mut self, program: Vec<Stmt>) -> Result<(), String> {
+        self.aliases.extend(types::aliases(&program));
+        // The program's own declarations replace these
+        for function in crate::bootstrap::functions() {
+            if !self.functions.contains_key(function.name()) {
+                self.declare(function.declaration.clone())?;
+            }
+        }
+        for stmt in crate::nested::lift(&program) {
+            let span = stmt.span;
+            if let Err(e) = self.declare(stmt) {
+                self.error_span = Some(span);
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Call `Contract.method` as a transaction from `sender` carrying
+    /// `value`. If the call fails, the contract's state is rolled back, as a
+    /// reverted transaction would leave it.
+    pub fn transact(&mut self, contract: &str, method: &str, args: Vec<Value>, sender: &str, value: u128) -> EvalResult {
+        self.blockchain_context.sender = sender.to_string();
+        self.blockchain_context.msg_value = value;
+
+        self.error_span = None;
+        self.panic = None;
+        // Arrays and maps are copied, as a rollback must undo their changes
+        // too
+        let snapshot: HashMap<_, HashMap<_, _>> = self
+            .contract_state
+            .iter()
+            .map(|(contract, storage)| (contract.clone(), storage.iter().map(|(k, v)| (k.clone(), v.deep_copy())).collect()))
+            .collect();
+        let result = self
+            .call_function(&format!("{}.{}", contract, method), args)
+            .and_then(|v| self.run_pending_tasks().map(|_| v));
+        if result.is_err() {
+            self.contract_state = snapshot;
+            self.run_queue.clear();
+        }
+        result
+    }
+
+    /// Call a loaded function from the host, running any tasks it spawns
+    pub fn call_values(&mut self, function: &str, args: Vec<Value>) -> EvalResult {
+        self.error_span = None;
+        self.panic = None;
+        let result = self
+            .invoke(function, args)
+            .and_then(|v| self.run_pending_tasks().map(|_| v));
+        if result.is_err() {
+            self.run_queue.clear();
+        }
+        result
+    }
+
+    /// `call_values` with Rust arguments and result, converted through serde (see
+    /// `bridge`). `args` is a tuple with one element per parameter, e.g.
+    /// `&(user, 5)`, `&(user,)` or `&()`.
+    pub fn call_serde<A, R>(&mut self, function: &str, args: &A) -> Result<R, String>
+    where
+        A: serde::Serialize + ?Sized,
+        R: serde::de::DeserializeOwned,
+    {
+        let args = match Value::from_serde(args)? {
+            Value::Tuple(items) => items,
+            Value::Null => Vec::new(),
+            single => vec![single],
+        };
+        self.call_values(function, args)?.to_serde()
+    }
+
+    /// Swap `program` in for the script loaded so far without restarting:
+    /// contract storage is kept wherever a contract's state variables are
+    /// unchanged, and imported modules stay loaded. Returns the changes
+    /// that calls written against the old script may not expect. If the new
+    /// program cannot be declared, the interpreter is left as it was.
+    pub fn reload(&mut self, program: Vec<Stmt>) -> Result<Vec<Incompatible>, String> {
+        let old_functions = self.functions.clone();
+        let old_state = std::mem::take(&mut self.contract_state);
+        let old_module_names = self.module_names.clone();
+        // Storage carried over is not a step, nor part of version 0
+        let timeline = self.timeline.take();
+
+        self.functions.retain(|_, f| f.module.is_some());
+        self.aliases = types::aliases(&program);
+        let declared = crate::nested::lift(&program).into_iter().try_for_each(|stmt| self.declare(stmt));
+        self.timeline = timeline;
+        if let Err(e) = declared {
+            self.functions = old_functions;
+            self.contract_state = old_state;
+            self.module_names = old_module_names;
+            return Err(e);
+        }
+
+        let mut changes = Vec::new();
+        for (name, old) in old_functions.iter().filter(|(_, f)| f.module.is_none()) {
+            match self.functions.get(name) {
+                None => changes.push(Incompatible::Removed(name.clone())),
+                Some(new)
+                    if new.params.len() != old.params.len()
+                        || new.param_types != old.param_types
+                        || new.is_async != old.is_async =>
+                {
+                    changes.push(Incompatible::SignatureChanged(name.clone()))
                 }
-                _ => {}
+                Some(_) => {}
             }
         }
 
-        if let Some(main) = self.functions.get("main").cloned() {
-            self.call_function(main, vec![])?;
-            Ok(())
-        } else {
-            Err("Error: main function not found".to_string())
+        for (contract, storage) in &mut self.contract_state {
+            let Some(old) = old_state.get(contract) else { continue };
+            let mut added: Vec<String> = storage.keys().filter(|var| !old.contains_key(*var)).cloned().collect();
+            let mut removed: Vec<String> = old.keys().filter(|var| !storage.contains_key(*var)).cloned().collect();
+            for (var, value) in storage.iter_mut() {
+                if let Some(previous) = old.get(var) {
+                    *value = previous.clone();
+                }
+            }
+            if !added.is_empty() || !removed.is_empty() {
+                added.sort();
+                removed.sort();
+                changes.push(Incompatible::StateChanged { contract: contract.clone(), added, removed });
+            }
         }
+
+        changes.sort_by_key(|change| match change {
+            Incompatible::SignatureChanged(name) | Incompatible::Removed(name) => name.clone(),
+            Incompatible::StateChanged { contract, .. } => contract.clone(),
+        });
+        Ok(changes)
+    }
+
+    /// Register a top-level declaration (function, contract, import)
+    fn declare(&mut self, stmt: Stmt) -> Result<(), String> {
+        match stmt.kind {
+            StmtKind::Function { name, params, param_types, defaults, body, is_async, exported, .. } => {
+                if let Some(module) = self.functions.get(&name).and_then(|f| f.module.clone()) {
+                    return Err(messages::render("E0429", &[&name, &module]));
+                }
+                self.functions.insert(
+                    name,
+                    Function { params, param_types, defaults, body, is_async, contract: None, module: None, exported },
+                );
+            }
+            StmtKind::Contract { name, state, constructor, methods, .. } => {
+                // Initialize contract state storage
+                let mut storage = HashMap::new();
+                for var in &state {
+                    storage.insert(var.clone(), Value::Null);
+                    if let Some(timeline) = &mut self.timeline {
+                        timeline.initial.insert(format!("{}.{}", name, var), "null".to_string());
+                    }
+                }
+                self.contract_state.insert(name.clone(), storage);
+
+                // Methods are callable as Contract.method(...)
+                for method in constructor.map(|c| *c).into_iter().chain(methods) {
+                    if let StmtKind::Function { name: method_name, params, param_types, defaults, body, is_async, .. } = method.kind {
+                        self.functions.insert(
+                            format!("{}.{}", name, method_name),
+                            Function {
+                                params,
+                                param_types,
+                                defaults,
+                                body,
+                                is_async,
+                                contract: Some(name.clone()),
+                                module: None,
+                                exported: true,
+                            },
+                        );
+                    }
+                }
+            }
+            StmtKind::Impl { target, methods, .. } => {
+                // Methods are called on a value and resolve to Type.method
+                let target = types::expand_aliases(&target, &self.aliases).unwrap_or(target);
+                for method in methods {
+                    if let StmtKind::Function { name: method_name, params, param_types, defaults, body, is_async, .. } = method.kind {
+                        self.functions.insert(
+                            format!("{}.{}", target, method_name),
+                            Function { params, param_types, defaults, body, is_async, contract: None, module: None, exported: true },
+                        );
+                    }
+                }
+            }
+            StmtKind::Import { module, alias, items } => {
+                self.import(&module, alias, items)?;
+            }
+            _ => {}
+        }
+        Ok(())
     }
 
+    /// Run one statement. The innermost statement that fails is recorded as
+    /// the error's location.
     fn execute(&mut self, stmt: Stmt) -> ExecResult {
+        let span = stmt.span;
+        let result = self.check_signals().and_then(|_| self.execute_kind(stmt.kind));
+        if result.is_err() && self.error_span.is_none() {
+            self.error_span = Some(span);
+        }
+        // A panic passing through: the first statement it leaves in each
+        // function is where that function's frame was
+        if let (Err(_), Some(panic), None) = (&result, &mut self.panic, self.panic_site) {
+            if panic.backtrace.is_empty() {
+                panic.span = span;
+            }
+            self.panic_site = Some(span);
+        }
+        result
+    }
+
+    fn execute_kind(&mut self, stmt: StmtKind) -> ExecResult {
         match stmt {
-            Stmt::Let { name, value } => {
+            StmtKind::Let { name, value, .. } => {
                 let val = self.eval_expr(value)?;
                 self.variables.insert(name, val);
-                Ok(Control::Value(Value::Null))
+                Ok(Control::Next)
+            }
+            StmtKind::LetTuple { names, value, .. } => {
+                let items = match self.eval_expr(value)? {
+                    Value::Tuple(items) => items,
+                    other => {
+                        return Err(messages::render("E0432", &[&type_name(&other), &names.len()]));
+                    }
+                };
+                if items.len() != names.len() {
+                    return Err(messages::render("E0433", &[&items.len(), &names.len()]));
+                }
+                for (name, item) in names.into_iter().zip(items) {
+                    if name != "_" {
+                        self.variables.insert(name, item);
+                    }
+                }
+                Ok(Control::Next)
             }
-            Stmt::Expression(expr) => {
+            StmtKind::Expression(expr) => {
                 let _ = self.eval_expr(expr)?;
-                Ok(Control::Value(Value::Null))
+                Ok(Control::Next)
             }
-            Stmt::If { condition, then_branch, else_branch } => {
+            StmtKind::If { condition, then_body, else_body } => {
                 let cond = self.eval_expr(condition)?;
 
-                match cond {
-                    Value::Bool(true) => {
-                        for stmt in then_branch {
-                            match self.execute(stmt)? {
-                                Control::Return(v) => return Ok(Control::Return(v)),
-                                Control::Value(_) => {}
-                            }
-                        }
+                let branch = if self.is_truthy(&cond, "E0404")? {
+                    then_body
+                } else {
+                    else_body.unwrap_or_default()
+                };
+                self.run_block(branch)
+            }
+            StmtKind::While { condition, body } => {
+                loop {
+                    self.check_signals()?;
+                    let cond = self.eval_expr(condition.clone())?;
+                    if !self.is_truthy(&cond, "E0405")? {
+                        break;
                     }
-                    Value::Bool(false) => {
-                        for stmt in else_branch {
-                            match self.execute(stmt)? {
-                                Control::Return(v) => return Ok(Control::Return(v)),
-                                Control::Value(_) => {}
-                            }
-                        }
+
+                    if let Control::Return(v) = self.run_block(body.clone())? {
+                        return Ok(Control::Return(v));
                     }
-                    _ => return Err("Error: condition must be boolean".to_string()),
                 }
 
-                Ok(Control::Value(Value::Null))
+                Ok(Control::Next)
             }
-            Stmt::While { condition, body } => {
-                loop {
-                    let cond = self.eval_expr(condition.clone())?;
-
-                    match cond {
-                        Value::Bool(true) => {
-                            for stmt in body.clone() {
-                                match self.execute(stmt)? {
-                                    Control::Return(v) => return Ok(Control::Return(v)),
-                                    Control::Value(_) => {}
-                                }
+            StmtKind::For { var, iterable, body } => {
+                let shadowed = self.variables.get(&var).cloned();
+                let control = match self.eval_expr(iterable)? {
+                    Value::Range(start, end) => self.run_for(&var, (start..end).map(Value::Number), &body)?,
+                    Value::Array(items) => self.run_for(&var, unshare(items).into_iter(), &body)?,
+                    Value::Iter(lazy) => {
+                        let mut visit = |this: &mut Self, item| {
+                            this.variables.insert(var.clone(), item);
+                            match this.run_block(body.clone())? {
+                                Control::Return(v) => Ok(Some(Control::Return(v))),
+                                Control::Next => Ok(None),
                             }
-                        }
-                        Value::Bool(false) => break,
-                        _ => return Err("Error: while condition must be boolean".to_string()),
+                        };
+                        self.drive(&lazy, &mut visit)?.unwrap_or(Control::Next)
                     }
-                }
+                    other => return Err(messages::render("E0435", &[&type_name(&other)])),
+                };
 
-                Ok(Control::Value(Value::Null))
+                // The loop variable is scoped to the body
+                match shadowed {
+                    Some(previous) => self.variables.insert(var, previous),
+                    None => self.variables.remove(&var),
+                };
+                Ok(control)
             }
-            Stmt::Assign { name, value } => {
+            StmtKind::Assign { name, value } => {
+                // `text = text + more` grows the string where it is rather
+                // than copying it for every `+`, so building a long string
+                // in a loop stays linear
+                if let Some(parts) = self.appended(&name, &value) {
+                    let mut tail = Vec::new();
+                    for part in parts {
+                        tail.push(self.eval_expr(part.clone())?);
+                    }
+                    let Some(Value::String(text)) = self.variables.get_mut(&name) else { unreachable!() };
+                    for part in tail {
+                        let Value::String(more) = part else {
+                            return Err(messages::render("E0408", &[]));
+                        };
+                        text.push_str(&more);
+                    }
+                    return Ok(Control::Next);
+                }
                 let val = self.eval_expr(value)?;
-                if self.variables.contains_key(&name) {
-                    self.variables.insert(name.clone(), val);
+                if let Some(slot) = self.variables.get_mut(&name) {
+                    *slot = val;
+                } else if self.current_storage_mut(&name).is_some() {
+                    self.record_write(&name, &val);
+                    if let Some(storage) = self.current_storage_mut(&name) {
+                        storage.insert(name, val);
+                    }
                 } else {
-                    return Err(format!("Error: variable '{}' not defined", name));
+                    return Err(messages::render("E0402", &[&name]));
+                }
+                Ok(Control::Next)
+            }
+            StmtKind::Match { value, arms } => {
+                let value = self.eval_expr(value)?;
+                for arm in arms {
+                    let binding = match (&arm.pattern, &value) {
+                        (Pattern::Wildcard, _) | (Pattern::None, Value::Option(None)) => None,
+                        (Pattern::Some(name), Value::Option(Some(payload)))
+                        | (Pattern::Ok(name), Value::Result(Ok(payload)))
+                        | (Pattern::Err(name), Value::Result(Err(payload))) => Some((name.clone(), *payload.clone())),
+                        _ => continue,
+                    };
+                    return self.run_arm(binding, arm.body);
                 }
-                Ok(Control::Value(Value::Null))
+                Err(messages::render("E0447", &[&self.render_value(&value)]))
             }
-            Stmt::Return(expr) => {
+            StmtKind::Return(expr) | StmtKind::Value(expr) => {
                 let val = self.eval_expr(expr)?;
                 Ok(Control::Return(val))
             }
-            Stmt::Function(_) => Ok(Control::Value(Value::Null)),
-            Stmt::Contract(_) => Ok(Control::Value(Value::Null)),
-            Stmt::Import(module) => {
-                self.load_module(&module)?;
-                Ok(Control::Value(Value::Null))
+            StmtKind::Panic(expr) => {
+                let msg = match self.eval_expr(expr)? {
+                    Value::String(s) => s,
+                    other => self.render_value(&other),
+                };
+                Err(self.raise(msg))
+            }
+            StmtKind::Function { .. } | StmtKind::Contract { .. } | StmtKind::Trait { .. } | StmtKind::Impl { .. }
+            | StmtKind::TypeAlias { .. } => Ok(Control::Next),
+            StmtKind::Import { module, alias, items } => {
+                self.import(&module, alias, items)?;
+                Ok(Control::Next)
             }
+            // Registered with its block by run_block
+            StmtKind::Defer(_) => Ok(Control::Next),
         }
     }
 
-    fn eval_expr(&mut self, expr: Expr) -> EvalResult {
-        match expr {
-            Expr::StringLiteral(v) => Ok(Value::String(v)),
-            Expr::NumberLiteral(n) => Ok(Value::Number(n)),
-            Expr::BoolLiteral(b) => Ok(Value::Bool(b)),
-            Expr::ArrayLiteral(items) => {
-                let mut values = Vec::new();
-                for item in items {
-                    values.push(self.eval_expr(item)?);
-                }
-                Ok(Value::Array(values))
-            }
-            Expr::Identifier(name) => match self.variables.get(&name) {
-                Some(v) => Ok(v.clone()),
-                None => Err(format!("Error: variable '{}' not defined", name)),
-            },
-            Expr::Property { object, property } => {
-                self.resolve_property(&object, &property)
+    /// Run a block's statements until one returns or fails. Code it defers
+    /// runs as it exits, last deferred first. A failure unwinds through it
+    /// too, outside contracts (which revert instead), and stands even if
+    /// deferred code fails in turn.
+    fn run_block(&mut self, body: Vec<Stmt>) -> ExecResult {
+        let mut deferred = Vec::new();
+        let mut control = Ok(Control::Next);
+        for stmt in body {
+            if let StmtKind::Defer(body) = stmt.kind {
+                deferred.push(body);
+                continue;
             }
-            Expr::AICall { method, args } => {
-                self.call_ai(&method, args)
+            control = self.execute(stmt);
+            if !matches!(control, Ok(Control::Next)) {
+                break;
             }
-            Expr::Binary { left, operator, right } => {
-                let l = self.eval_expr(*left)?;
-                let r = self.eval_expr(*right)?;
+        }
 
-                match (l, r, operator.as_str()) {
-                    (Value::Number(a), Value::Number(b), "+") => Ok(Value::Number(a + b)),
-                    (Value::Number(a), Value::Number(b), "<") => Ok(Value::Bool(a < b)),
-                    (Value::Number(a), Value::Number(b), ">") => Ok(Value::Bool(a > b)),
-                    (Value::Number(_), Value::Number(0), "/") => Err("Error: division by zero".to_string()),
-                    (Value::Number(a), Value::Number(b), "/") => Ok(Value::Number(a / b)),
-                    _ => Err("Error: invalid operation".to_string()),
-                }
+        if control.is_ok() || self.early_return.is_some() {
+            for body in deferred.into_iter().rev() {
+                self.run_block(body)?;
+            }
+        } else if self.current_contract.is_none() && !deferred.is_empty() {
+            let failure = (self.panic.take(), self.panic_site.take(), self.error_span.take());
+            for body in deferred.into_iter().rev() {
+                let _ = self.run_block(body);
             }
-            Expr::Call { name, args } => self.call(name, args),
+            (self.panic, self.panic_site, self.error_span) = failure;
         }
+        control
     }
 
-    fn call(&mut self, name: String, args: Vec<Expr>) -> EvalResult {
-        let mut arg_values = Vec::new();
-        for arg in args {
-            arg_values.push(self.eval_expr(arg)?);
-        }
-
-        // Handle built-in contract functions
-        if name == "panic" {
-            let msg = match arg_values.get(0) {
-                Some(Value::String(s)) => s.clone(),
-                _ => "Contract panic".to_string(),
-            };
-            return Err(format!("Panic: {}", msg));
-        }
-
-        if name == "transfer" {
-            // Built-in transfer function (no-op for now, would interact with blockchain)
-            return Ok(Value::Null);
-        }
-
-        if name == "emit" {
-            // Built-in emit for contract events (no-op for now)
-            return Ok(Value::Null);
-        }
-
-        if let Ok(val) = self.stdlib(&name, &arg_values) {
-            return Ok(val);
+    /// Run a for loop's body once per item, bound to `var`
+    fn run_for(&mut self, var: &str, items: impl Iterator<Item = Value>, body: &[Stmt]) -> ExecResult {
+        for item in items {
+            self.variables.insert(var.to_string(), item);
+            if let Control::Return(v) = self.run_block(body.to_vec())? {
+                return Ok(Control::Return(v));
+            }
         }
+        Ok(Control::Next)
+    }
 
-        if name == "print" {
-            match arg_values.get(0) {
-                Some(Value::String(s)) => println!("{}", s),
-                Some(Value::Number(n)) => println!("{}", n),
-                Some(Value::Bool(b)) => println!("{}", b),
-                Some(Value::Array(arr)) => {
-                    let rendered: Vec<String> = arr.iter().map(|v| self.render_value(v)).collect();
-                    println!("[{}]", rendered.join(","));
+    /// Hand each item of `lazy` to `visit` in turn, working it out only
+    /// then, until `visit` gives back a result to stop with
+    fn drive(&mut self, lazy: &Lazy, visit: &mut Visit) -> Result<Option<Control>, String> {
+        match lazy {
+            Lazy::Range(start, end) => {
+                for n in *start..*end {
+                    if let Some(stop) = visit(self, Value::Number(n))? {
+                        return Ok(Some(stop));
+                    }
                 }
-                Some(Value::Address(addr)) => println!("{}", addr),
-                Some(Value::U256(n)) => println!("{}", n),
-                Some(Value::AIResult { label, score }) => println!("{}: {:.2}", label, score),
-                Some(Value::Null) | None => println!("null"),
             }
-            return Ok(Value::Null);
+            Lazy::Items(items) => {
+                let items = items.borrow().clone();
+                for item in items {
+                    if let Some(stop) = visit(self, item)? {
+                        return Ok(Some(stop));
+                    }
+                }
+            }
+            Lazy::Map(source, function) => {
+                return self.drive(source, &mut |this: &mut Self, item| {
+                    let mapped = this.call_function(function, vec![item])?;
+                    visit(this, mapped)
+                });
+            }
+            Lazy::Filter(source, function) => {
+                return self.drive(source, &mut |this: &mut Self, item: Value| {
+                    let keep = this.call_function(function, vec![item.clone()])?;
+                    match this.is_truthy(&keep, "E0486")? {
+                        true => visit(this, item),
+                        false => Ok(None),
+                    }
+                });
+            }
         }
+        Ok(None)
+    }
 
-        let func = match self.functions.get(&name) {
-            Some(f) => f.clone(),
-            None => return Err(format!("Error: function '{}' not defined", name)),
-        };
+    /// Run a match arm's body with its payload bound, if it has one
+    fn run_arm(&mut self, binding: Option<(String, Value)>, body: Vec<Stmt>) -> ExecResult {
+        let bound = binding.filter(|(name, _)| name != "_").map(|(name, payload)| {
+            let shadowed = self.variables.insert(name.clone(), payload);
+            (name, shadowed)
+        });
+        let control = self.run_block(body);
 
-        self.call_function(func, arg_values)
+        // The binding is scoped to the arm
+        match bound {
+            Some((name, Some(previous))) => self.variables.insert(name, previous),
+            Some((name, None)) => self.variables.remove(&name),
+            None => None,
+        };
+        control
     }
 
-    fn call_function(&mut self, func: Function, args: Vec<Value>) -> EvalResult {
-        let mut new_scope = HashMap::new();
-        for (i, param) in func.params.iter().enumerate() {
-            let arg_val = args.get(i).cloned().ok_or_else(|| "Error: argument missing".to_string())?;
-            new_scope.insert(param.clone(), arg_val);
+    /// `error` is the message code reported for a non-boolean condition
+    fn is_truthy(&self, value: &Value, error: &str) -> Result<bool, String> {
+        match value {
+            Value::Bool(b) => Ok(*b),
+            _ => Err(messages::render(error, &[])),
         }
+    }
 
-        let old_scope = self.variables.clone();
-        self.variables = new_scope;
-
-        let mut ret = Value::Null;
-        for stmt in func.body.clone() {
-            match self.execute(stmt)? {
-                Control::Return(v) => {
-                    ret = v;
-                    break;
+    fn eval_expr(&mut self, expr: Expr) -> EvalResult {
+        match expr.kind {
+            ExprKind::String(v) => Ok(Value::String(v)),
+            ExprKind::Number(n) => Ok(Value::Number(n)),
+            ExprKind::Float(f) => Ok(Value::Float(f)),
+            ExprKind::Bool(b) => Ok(Value::Bool(b)),
+            ExprKind::Identifier(name) if name == "None" => Ok(Value::Option(None)),
+            ExprKind::Identifier(name) => {
+                if let Some(v) = self.variables.get(&name) {
+                    return Ok(v.clone());
+                }
+                let contract = self.current_contract.clone().unwrap_or_default();
+                if let Some(v) = self.contract_state.get(&contract).and_then(|s| s.get(&name)) {
+                    return Ok(v.clone());
+                }
+                // A function named without calling it is a value to call later
+                match self.current_module.as_ref().map(|module| format!("{}.{}", module, name)) {
+                    Some(qualified) if self.functions.contains_key(&qualified) => Ok(Value::Function(qualified)),
+                    _ if self.functions.contains_key(&name) => Ok(Value::Function(name)),
+                    _ => Err(messages::render("E0402", &[&name])),
                 }
-                Control::Value(_) => {}
             }
-        }
-
-        self.variables = old_scope;
-        Ok(ret)
-    }
+            ExprKind::Call(name, args) => self.call(name, args),
+            // A Float becomes an Int by truncating toward zero, saturating
+            // at the Int range (NaN is 0)
+            ExprKind::Cast(inner, target) => match (self.eval_expr(*inner)?, self.resolve_type(&target).as_str()) {
+                (Value::Float(f), "Int") => Ok(Value::Number(f as i64)),
+                (Value::Number(n), "Float") => Ok(Value::Float(n as f64)),
+                (value @ Value::Number(_), "Int") | (value @ Value::Float(_), "Float") => Ok(value),
+                (value, _) => Err(messages::render("E0483", &[&type_name(&value), &target])),
+            },
+            ExprKind::Try(inner) => match self.eval_expr(*inner)? {
+                Value::Option(Some(payload)) | Value::Result(Ok(payload)) => Ok(*payload),
+                // The function returns the None or Err; see call_function
+                failure @ (Value::Option(None) | Value::Result(Err(_))) => {
+                    let rendered = self.render_value(&failure);
+                    self.early_return = Some(failure);
+                    Err(messages::render("E0445", &[&rendered]))
+                }
+                other => Err(messages::render("E0446", &[&type_name(&other)])),
+            },
+            ExprKind::ModuleCall(module, func, args) => {
+                if module == "ai" {
+                    return self.call_ai(&func, args);
+                }
+                if module == "ipfs" {
+                    return self.call_ipfs(&func, args);
+                }
+                if module == "u256" {
+                    let values = args.into_iter().map(|arg| self.eval_expr(arg)).collect::<Result<Vec<_>, _>>()?;
+                    return u256::call(&func, &values);
+                }
+                if module == "merkle" {
+                    let values = args.into_iter().map(|arg| self.eval_expr(arg)).collect::<Result<Vec<_>, _>>()?;
+                    return merkle::call(&func, &values);
+                }
+                if defi::abi::is_protocol(&module) {
+                    let values = args.into_iter().map(|arg| self.eval_expr(arg)).collect::<Result<Vec<_>, _>>()?;
+                    self.connect_call_source();
+                    return defi::call(self.call_source.as_deref(), &module, &func, &values);
+                }
+                if module == "ed25519" || module == "bls" {
+                    let values = args.into_iter().map(|arg| self.eval_expr(arg)).collect::<Result<Vec<_>, _>>()?;
+                    return if module == "bls" { bls::call(&func, &values) } else { ed25519::call(&func, &values) };
+                }
+                if module == "web3" && func == "logs" {
+                    return self.web3_logs(args);
+                }
+                if module == "web3" && matches!(func.as_str(), "balance" | "code" | "storage" | "deal" | "store") {
+                    return self.web3_account(&func, args);
+                }
+                if module == "web3" && func == "multicall" {
+                    return self.web3_multicall(args);
+                }
+                if module == "web3" && (func == "sign_typed" || func == "verify_typed") {
+                    return self.web3_typed(&func, args);
+                }
 
-    fn render_value(&self, v: &Value) -> String {
-        match v {
+                // Arrays and maps change in place (see Value); a change to
+                // contract state is a storage write like an assignment
+                let local = self.variables.get(&module).cloned();
+                let state = || {
+                    let contract = self.current_contract.clone().unwrap_or_default();
+                    self.contract_state.get(&contract).and_then(|s| s.get(&module)).cloned()
+                };
+                if let Some(collection) = local.clone().or_else(state).filter(|v| has_collection_method(v, &func)) {
+                    let mut values = Vec::new();
+                    for arg in args {
+                        values.push(self.eval_expr(arg)?);
+                    }
+                    let old = (local.is_none() && self.timeline.is_some()).then(|| self.render_value(&collection));
+                    let result = collection_method(&collection, &func, values)?;
+                    if let Some(old) = old.filter(|_| func != "copy") {
+                        let record = Record::Write {
+                            slot: format!("{}.{}", self.current_contract.clone().unwrap_or_default(), module),
+                            old,
+                            new: self.render_value(&collection),
+                        };
+                        self.record_step(record);
+                    }
+                    return Ok(result);
+                }
+
+                // A method call on a variable goes to the impl for its type
+                if let Some(value) = self.variables.get(&module) {
+                    let method = impl_type(value).map(|t| format!("{}.{}", t, func));
+                    if let Some(method) = method.filter(|m| self.functions.contains_key(m)) {
+                        let mut arg_values = vec![value.clone()];
+                        for arg in args {
+                            arg_values.push(self.eval_expr(arg)?);
+                        }
+                        return self.invoke(&method, arg_values);
+                    }
+                }
+
+                // Module names and aliases resolve to the module; anything
+                // else (e.g. a contract) is looked up as written
+                let owner = self.module_names.get(&module).cloned().unwrap_or(module);
+                let target = format!("{}.{}", owner, func);
+                match self.functions.get(&target) {
+                    None => return Err(messages::render("E0406", &[&target])),
+                    Some(f) if !self.is_visible(f) => {
+                        return Err(messages::render("E0431", &[&owner, &func]));
+                    }
+                    Some(_) => {}
+                }
+
+                let args = self.bind_arguments(&target, args)?;
+                let mut arg_values = Vec::new();
+                for arg in args {
+                    arg_values.push(self.eval_expr(arg)?);
+                }
+                self.invoke(&target, arg_values)
+            }
+            ExprKind::Await(inner) => {
+                match self.eval_expr(*inner)? {
+                    Value::Task(id) => self.await_task(id),
+                    // Awaiting a plain value is a no-op
+                    other => Ok(other),
+                }
+            }
+            ExprKind::Property(object, name) => self.resolve_property(&object, &name),
+            // Its call put it in place (see `bind_arguments`)
+            ExprKind::Named(_, value) => self.eval_expr(*value),
+            ExprKind::Tuple(items) => {
+                let mut values = Vec::new();
+                for item in items {
+                    values.push(self.eval_expr(item)?);
+                }
+                Ok(Value::Tuple(values))
+            }
+            ExprKind::Array(items) => {
+                let mut values = Vec::new();
+                for item in items {
+                    values.push(self.eval_expr(item)?);
+                }
+                Ok(Value::array(values))
+            }
+            ExprKind::Range(start, end) => {
+                match (self.eval_expr(*start)?, self.eval_expr(*end)?) {
+                    (Value::Number(start), Value::Number(end)) => Ok(Value::Range(start, end)),
+                    _ => Err(messages::render("E0434", &[])),
+                }
+            }
+            ExprKind::Slice(value, range) => {
+                let value = self.eval_expr(*value)?;
+                let range = self.eval_expr(*range)?;
+                slice(value, range)
+            }
+            ExprKind::Add(l, r) => self.binary(*l, *r, "+"),
+            ExprKind::Sub(l, r) => self.binary(*l, *r, "-"),
+            ExprKind::Mul(l, r) => self.binary(*l, *r, "*"),
+            ExprKind::Div(l, r) => self.binary(*l, *r, "/"),
+            ExprKind::Mod(l, r) => self.binary(*l, *r, "%"),
+            ExprKind::Eq(l, r) => self.binary(*l, *r, "=="),
+            ExprKind::Ne(l, r) => self.binary(*l, *r, "!="),
+            ExprKind::Lt(l, r) => self.binary(*l, *r, "<"),
+            ExprKind::Le(l, r) => self.binary(*l, *r, "<="),
+            ExprKind::Gt(l, r) => self.binary(*l, *r, ">"),
+            ExprKind::Ge(l, r) => self.binary(*l, *r, ">="),
+            ExprKind::And(l, r) => self.logical(*l, *r, false),
+            ExprKind::Or(l, r) => self.logical(*l, *r, true),
+        }
+    }
+
+    /// `&&` (decided by a false left side) or `||` (by a true one): the
+    /// right side only runs when the left leaves the answer open
+    fn logical(&mut self, left: Expr, right: Expr, decided_by: bool) -> EvalResult {
+        let left = self.eval_expr(left)?;
+        if self.is_truthy(&left, "E0490")? == decided_by {
+            return Ok(Value::Bool(decided_by));
+        }
+        let right = self.eval_expr(right)?;
+        Ok(Value::Bool(self.is_truthy(&right, "E0490")?))
+    }
+
+    fn binary(&mut self, left: Expr, right: Expr, operator: &str) -> EvalResult {
+        let l = self.eval_expr(left)?;
+        let r = self.eval_expr(right)?;
+
+        match (l, r, operator) {
+            // Int arithmetic wraps on overflow; see crate::overflow
+            (Value::Number(a), Value::Number(b), "+") => Ok(Value::Number(a.wrapping_add(b))),
+            (Value::Number(a), Value::Number(b), "-") => Ok(Value::Number(a.wrapping_sub(b))),
+            (Value::Number(a), Value::Number(b), "*") => Ok(Value::Number(a.wrapping_mul(b))),
+            (Value::Number(_), Value::Number(0), "/") => Err(messages::render("E0407", &[])),
+            (Value::Number(a), Value::Number(b), "/") => Ok(Value::Number(a.wrapping_div(b))),
+            (Value::Number(_), Value::Number(0), "%") => Err(messages::render("E0407", &[])),
+            (Value::Number(a), Value::Number(b), "%") => Ok(Value::Number(a.wrapping_rem(b))),
+            (Value::Number(a), Value::Number(b), "<") => Ok(Value::Bool(a < b)),
+            (Value::Number(a), Value::Number(b), "<=") => Ok(Value::Bool(a <= b)),
+            (Value::Number(a), Value::Number(b), ">") => Ok(Value::Bool(a > b)),
+            (Value::Number(a), Value::Number(b), ">=") => Ok(Value::Bool(a >= b)),
+            // Float arithmetic is IEEE 754's, as f64 in WASM: dividing by
+            // zero gives an infinity or NaN rather than an error
+            (Value::Float(a), Value::Float(b), "+") => Ok(Value::Float(a + b)),
+            (Value::Float(a), Value::Float(b), "-") => Ok(Value::Float(a - b)),
+            (Value::Float(a), Value::Float(b), "*") => Ok(Value::Float(a * b)),
+            (Value::Float(a), Value::Float(b), "/") => Ok(Value::Float(a / b)),
+            (Value::Float(a), Value::Float(b), "<") => Ok(Value::Bool(a < b)),
+            (Value::Float(a), Value::Float(b), "<=") => Ok(Value::Bool(a <= b)),
+            (Value::Float(a), Value::Float(b), ">") => Ok(Value::Bool(a > b)),
+            (Value::Float(a), Value::Float(b), ">=") => Ok(Value::Bool(a >= b)),
+            (Value::String(a), Value::String(b), "+") => Ok(Value::String(a + &b)),
+            (a @ (Value::U256(_) | Value::Number(_)), b @ (Value::U256(_) | Value::Number(_)), "<" | "<=" | ">" | ">=") => {
+                let order = amount(&a).cmp(&amount(&b));
+                Ok(Value::Bool(match operator {
+                    "<" => order.is_lt(),
+                    "<=" => order.is_le(),
+                    ">" => order.is_gt(),
+                    _ => order.is_ge(),
+                }))
+            }
+            (a, b, "==") => Ok(Value::Bool(values_equal(&a, &b))),
+            (a, b, "!=") => Ok(Value::Bool(!values_equal(&a, &b))),
+            _ => Err(messages::render("E0408", &[])),
+        }
+    }
+
+    fn call(&mut self, name: String, args: Vec<Expr>) -> EvalResult {
+        // A variable holding a function calls it; inside a module, its own
+        // functions come first
+        let target = match (self.variables.get(&name), &self.current_module) {
+            (Some(Value::Function(function)), _) => function.clone(),
+            (_, Some(module)) if self.functions.contains_key(&format!("{}.{}", module, name)) => {
+                format!("{}.{}", module, name)
+            }
+            _ => name.clone(),
+        };
+
+        let args = self.bind_arguments(&target, args)?;
+        let mut arg_values = Vec::new();
+        for arg in args {
+            arg_values.push(self.eval_expr(arg)?);
+        }
+
+        // Option and Result constructors are built in
+        match (name.as_str(), arg_values.len()) {
+            ("Some", 1) => return Ok(Value::Option(Some(Box::new(arg_values.remove(0))))),
+            ("Ok", 1) => return Ok(Value::Result(Ok(Box::new(arg_values.remove(0))))),
+            ("Err", 1) => return Ok(Value::Result(Err(Box::new(arg_values.remove(0))))),
+            ("Some" | "Ok" | "Err", count) => return Err(messages::render("E0448", &[&name, &count])),
+            _ => {}
+        }
+
+        // Handle built-in contract functions
+        if name == "panic" {
+            let msg = match arg_values.first() {
+                Some(Value::String(s)) => s.clone(),
+                _ => "Contract panic".to_string(),
+            };
+            return Err(self.raise(msg));
+        }
+
+        if name == "transfer" {
+            // Built-in transfer function (no-op for now, would interact with blockchain)
+            return Ok(Value::Null);
+        }
+
+        if name == "emit" {
+            // Built-in emit for contract events: emit("Name", args...). Only
+            // a recording simulation keeps them.
+            if self.timeline.is_some() {
+                let mut values = arg_values.iter();
+                let name = match values.next() {
+                    Some(Value::String(s)) => s.clone(),
+                    Some(other) => self.render_value(other),
+                    None => "event".to_string(),
+                };
+                let args = values.map(|v| self.render_value(v)).collect();
+                let contract = self.current_contract.clone();
+                self.record_step(Record::Event { contract, name, args });
+            }
+            return Ok(Value::Null);
+        }
+
+        if name == "spawn" {
+            // Calling an async function already schedules it; spawn just hands back the task
+            return match arg_values.into_iter().next() {
+                Some(Value::Task(id)) => Ok(Value::Task(id)),
+                _ => Err(messages::render("E0409", &[])),
+            };
+        }
+
+        if crate::prelude::prints(&name) {
+            let rendered = match arg_values.first() {
+                Some(Value::String(s)) => s.clone(),
+                Some(Value::StringBuilder(text)) => text.borrow().clone(),
+                Some(v) => self.render_value(v),
+                None => "null".to_string(),
+            };
+            match &mut self.output {
+                Some(lines) => lines.push(rendered),
+                None => println!("{}", rendered),
+            }
+            return Ok(Value::Null);
+        }
+
+        let name = target;
+        if !self.functions.contains_key(&name) {
+            return self.stdlib(&name, &arg_values);
+        }
+
+        self.invoke(&name, arg_values)
+    }
+
+    /// A call's arguments in parameter order, with defaults filled in (see
+    /// `arguments`). Only declared functions take arguments by name.
+    fn bind_arguments(&self, function: &str, args: Vec<Expr>) -> Result<Vec<Expr>, String> {
+        let Some(func) = self.functions.get(function) else {
+            return match args.iter().any(arguments::is_named) {
+                true => Err(messages::render("E0252", &[&function])),
+                false => Ok(args),
+            };
+        };
+        if !arguments::needs_binding(&func.defaults, &args) {
+            return Ok(args);
+        }
+        arguments::bind(&func.params, &func.defaults, &args).map_err(|e| {
+            let (code, argument) = e.code();
+            messages::render(code, &[&function, &argument])
+        })
+    }
+
+    /// Call a user function; async functions are scheduled instead of run
+    fn invoke(&mut self, name: &str, args: Vec<Value>) -> EvalResult {
+        let is_async = match self.functions.get(name) {
+            Some(f) => f.is_async,
+            None => return Err(messages::render("E0406", &[&name])),
+        };
+
+        if is_async {
+            let id = self.tasks.len();
+            self.tasks.push(TaskState::Pending { func: name.to_string(), args });
+            self.run_queue.push_back(id);
+            Ok(Value::Task(id))
+        } else {
+            self.call_function(name, args)
+        }
+    }
+
+    fn call_function(&mut self, name: &str, args: Vec<Value>) -> EvalResult {
+        let func = match self.functions.get(name) {
+            Some(f) => f.clone(),
+            None => return Err(messages::render("E0406", &[&name])),
+        };
+
+        let mut new_scope = HashMap::new();
+        for (i, param) in func.params.iter().enumerate() {
+            let arg_val = args.get(i).cloned().ok_or_else(|| messages::render("E0410", &[]))?;
+            new_scope.insert(param.clone(), arg_val);
+        }
+
+        // Calling into a contract from outside one starts a transaction
+        let starts_transaction = self.timeline.is_some() && func.contract.is_some() && self.current_contract.is_none();
+        if starts_transaction {
+            let rendered: Vec<String> = args.iter().map(|v| self.render_value(v)).collect();
+            let call = format!("{}({})", name, rendered.join(", "));
+            self.transaction = self.timeline.as_mut().map(|t| t.begin_transaction(call));
+        }
+
+        let old_scope = std::mem::replace(&mut self.variables, new_scope);
+        let old_contract = std::mem::replace(&mut self.current_contract, func.contract.clone());
+        let old_module = std::mem::replace(&mut self.current_module, func.module.clone());
+
+        let ret = match self.run_block(func.body) {
+            Ok(Control::Return(v)) => Ok(v),
+            Ok(Control::Next) => Ok(Value::Null),
+            // A `?` returning early from this function
+            Err(_) if self.early_return.is_some() => {
+                self.error_span = None;
+                Ok(self.early_return.take().unwrap_or(Value::Null))
+            }
+            Err(e) => {
+                if let (Some(panic), Some(span)) = (&mut self.panic, self.panic_site.take()) {
+                    panic.backtrace.push(Frame { function: name.to_string(), span });
+                    panic.reverted |= func.contract.is_some();
+                }
+                Err(e)
+            }
+        };
+
+        self.variables = old_scope;
+        self.current_contract = old_contract;
+        self.current_module = old_module;
+        if starts_transaction {
+            self.transaction = None;
+        }
+        ret
+    }
+
+    /// Start a panic with `message`, returning the error it unwinds as
+    fn raise(&mut self, message: String) -> String {
+        let error = messages::render("E0403", &[&message]);
+        self.panic = Some(RuntimePanic { message, span: Span::default(), backtrace: Vec::new(), reverted: false });
+        self.panic_site = None;
+        error
+    }
+
+    /// The operands `name = name + a + b` appends to a String variable,
+    /// in order
+    fn appended<'e>(&self, name: &str, value: &'e Expr) -> Option<Vec<&'e Expr>> {
+        if !matches!(self.variables.get(name), Some(Value::String(_))) {
+            return None;
+        }
+        let mut parts = Vec::new();
+        let mut left = value;
+        while let ExprKind::Add(l, r) = &left.kind {
+            parts.push(r.as_ref());
+            left = l;
+        }
+        match &left.kind {
+            ExprKind::Identifier(id) if id == name && !parts.is_empty() => {
+                parts.reverse();
+                Some(parts)
+            }
+            _ => None,
+        }
+    }
+
+    /// Record a write to the current contract's state variable `name`
+    fn record_write(&mut self, name: &str, new: &Value) {
+        if self.timeline.is_none() {
+            return;
+        }
+        let old = self.current_storage_mut(name).and_then(|s| s.get(name).cloned()).unwrap_or(Value::Null);
+        let record = Record::Write {
+            slot: format!("{}.{}", self.current_contract.clone().unwrap_or_default(), name),
+            old: self.render_value(&old),
+            new: self.render_value(new),
+        };
+        self.record_step(record);
+    }
+
+    fn record_step(&mut self, record: Record) {
+        let transaction = self.transaction;
+        if let Some(timeline) = &mut self.timeline {
+            timeline.steps.push(Step { transaction, record });
+        }
+    }
+
+    /// Drive the scheduler until the given task has finished.
+    ///
+    /// Tasks are cooperative and run to completion once started: awaiting a
+    /// task first lets every task scheduled before it run (in FIFO order),
+    /// so spawned work makes progress whenever any task awaits.
+    fn await_task(&mut self, id: usize) -> EvalResult {
+        loop {
+            match self.tasks.get(id) {
+                Some(TaskState::Done(v)) => return Ok(v.clone()),
+                Some(TaskState::Running) => {
+                    return Err(messages::render("E0411", &[]));
+                }
+                Some(TaskState::Pending { .. }) => {}
+                None => return Err(messages::render("E0412", &[&id])),
+            }
+
+            match self.run_queue.pop_front() {
+                Some(next) => self.run_task(next)?,
+                None => return Err(messages::render("E0413", &[&id])),
+            }
+        }
+    }
+
+    fn run_task(&mut self, id: usize) -> Result<(), String> {
+        let (func, args) = match std::mem::replace(&mut self.tasks[id], TaskState::Running) {
+            TaskState::Pending { func, args } => (func, args),
+            other => {
+                // Already started or finished elsewhere
+                self.tasks[id] = other;
+                return Ok(());
+            }
+        };
+
+        let result = self.call_function(&func, args)?;
+        self.tasks[id] = TaskState::Done(result);
+        Ok(())
+    }
+
+    fn run_pending_tasks(&mut self) -> Result<(), String> {
+        while let Some(next) = self.run_queue.pop_front() {
+            self.run_task(next)?;
+        }
+        Ok(())
+    }
+
+    fn current_storage_mut(&mut self, name: &str) -> Option<&mut HashMap<String, Value>> {
+        let contract = self.current_contract.clone()?;
+        self.contract_state
+            .get_mut(&contract)
+            .filter(|storage| storage.contains_key(name))
+    }
+
+    fn render_value(&self, v: &Value) -> String {
+        match v {
             Value::String(s) => format!("\"{}\"", s),
+            Value::StringBuilder(text) => format!("\"{}\"", text.borrow()),
             Value::Number(n) => n.to_string(),
+            Value::Float(f) => f.to_string(),
             Value::Bool(b) => b.to_string(),
             Value::Array(arr) => {
-                let rendered: Vec<String> = arr.iter().map(|x| self.render_value(x)).collect();
+                let rendered: Vec<String> = arr.borrow().iter().map(|x| self.render_value(x)).collect();
                 format!("[{}]", rendered.join(","))
             }
+            Value::Tuple(items) => {
+                let rendered: Vec<String> = items.iter().map(|x| self.render_value(x)).collect();
+                format!("({})", rendered.join(", "))
+            }
+            Value::Map(entries) => {
+                let rendered: Vec<String> = entries
+                    .borrow()
+                    .iter()
+                    .map(|(key, value)| format!("{}: {}", key, self.render_value(value)))
+                    .collect();
+                format!("{{{}}}", rendered.join(", "))
+            }
+            Value::Option(Some(payload)) => format!("Some({})", self.render_value(payload)),
+            Value::Option(None) => "None".to_string(),
+            Value::Result(Ok(payload)) => format!("Ok({})", self.render_value(payload)),
+            Value::Result(Err(payload)) => format!("Err({})", self.render_value(payload)),
             Value::Address(addr) => addr.clone(),
             Value::U256(n) => n.to_string(),
             Value::AIResult { label, score } => format!("{}: {:.2}", label, score),
+            Value::Task(id) => format!("<task #{}>", id),
+            Value::Range(start, end) => format!("{}..{}", start, end),
+            Value::Iter(lazy) => self.render_lazy(lazy),
+            Value::Function(name) => format!("<fn {}>", name),
             Value::Null => "null".to_string(),
         }
     }
 
+    /// An iterator as the calls that made it, without running them
+    fn render_lazy(&self, lazy: &Lazy) -> String {
+        match lazy {
+            Lazy::Range(start, end) => format!("{}..{}", start, end),
+            Lazy::Items(items) => self.render_value(&Value::Array(items.clone())),
+            Lazy::Map(source, function) => format!("map({}, {})", self.render_lazy(source), function),
+            Lazy::Filter(source, function) => format!("filter({}, {})", self.render_lazy(source), function),
+        }
+    }
+
+    /// A type annotation with its aliases spelled out
+    fn resolve_type(&self, annotation: &str) -> String {
+        types::expand_aliases(annotation, &self.aliases).unwrap_or_else(|_| annotation.to_string())
+    }
+
     fn resolve_property(&self, object: &str, property: &str) -> EvalResult {
+        if let Some(Value::AIResult { label, score }) = self.variables.get(object) {
+            return match property {
+                "label" => Ok(Value::String(label.clone())),
+                "score" => Ok(Value::Float(*score)),
+                _ => Err(messages::render("E0414", &[&object, &property])),
+            };
+        }
         match (object, property) {
             // chain properties
             ("chain", "id") => Ok(Value::Number(self.blockchain_context.chain_id)),
             ("chain", "name") => Ok(Value::String(self.blockchain_context.chain_name.clone())),
-            
+
             // msg properties
             ("msg", "sender") => Ok(Value::Address(self.blockchain_context.sender.clone())),
-            ("msg", "value") => Ok(Value::U256(self.blockchain_context.msg_value)),
+            ("msg", "value") => Ok(Value::U256(U256::from(self.blockchain_context.msg_value))),
             ("msg", "data") => Ok(Value::String(self.blockchain_context.msg_data.clone())),
-            
+
             // tx properties
             ("tx", "hash") => Ok(Value::String(self.blockchain_context.tx_hash.clone())),
-            ("tx", "value") => Ok(Value::U256(self.blockchain_context.msg_value)),
+            ("tx", "value") => Ok(Value::U256(U256::from(self.blockchain_context.msg_value))),
             ("tx", "timestamp") => Ok(Value::Number(self.blockchain_context.tx_timestamp)),
-            
-            // AI result properties
-            _ => Err(format!("Error: unknown property '{}.{}'", object, property)),
+
+            _ => Err(messages::render("E0414", &[&object, &property])),
+        }
+    }
+
+    /// `ipfs.add(data)` and `ipfs.get(cid)`, as Results; `ipfs.cid(data)`
+    /// and `ipfs.valid(text)`, which need no store
+    fn call_ipfs(&mut self, func: &str, args: Vec<Expr>) -> EvalResult {
+        if !matches!(func, "add" | "get" | "cid" | "valid") {
+            return Err(messages::render("E0457", &[&func]));
+        }
+        let mut values = Vec::new();
+        for arg in args {
+            values.push(self.eval_expr(arg)?);
+        }
+        let [Value::String(text)] = values.as_slice() else {
+            return Err(messages::render("E0458", &[&func]));
+        };
+        match func {
+            "cid" => return Ok(Value::String(Cid::raw(text.as_bytes()).to_string())),
+            "valid" => return Ok(Value::Bool(Cid::parse(text).is_ok())),
+            _ => {}
         }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.ipfs_store.is_none() {
+            self.set_ipfs_store(Box::new(HttpIpfsStore::from_env()));
+        }
+
+        // The store being unreachable is the script's to handle
+        let store = self.ipfs_store.as_deref().ok_or_else(|| messages::render("E0460", &[]));
+        let done = match func {
+            "add" => store.and_then(|store| ipfs::add(store, text.as_bytes())),
+            // Content that is not text comes back as hex
+            _ => store.and_then(|store| ipfs::get(store, text)).map(|content| {
+                String::from_utf8(content).unwrap_or_else(|e| format!("0x{}", logs::hex(e.as_bytes())))
+            }),
+        };
+        Ok(Value::Result(done.map(|s| Box::new(Value::String(s))).map_err(|e| Box::new(Value::String(e)))))
     }
 
     fn call_ai(&mut self, method: &str, args: Vec<Expr>) -> EvalResult {
         let ai_runtime = LocalAIRuntime;
-        
+
         match method {
             "model" => {
                 if args.is_empty() {
-                    return Err("ai.model() requires at least one argument".to_string());
+                    return Err(messages::render("E0415", &[]));
                 }
                 let model_name_expr = &args[0];
                 let model_name = match self.eval_expr(model_name_expr.clone())? {
                     Value::String(s) => s,
-                    _ => return Err("ai.model() requires a string argument".to_string()),
+                    _ => return Err(messages::render("E0416", &[&"ai.model"])),
                 };
-                
+
                 let _model = ai_runtime.model(&model_name)?;
                 // Store model name as string for now (simplified)
                 Ok(Value::String(model_name))
             }
             "infer" => {
                 if args.len() < 2 {
-                    return Err("ai.infer() requires two arguments: model and input".to_string());
+                    return Err(messages::render("E0417", &[]));
                 }
-                
+
                 let _model_val = self.eval_expr(args[0].clone())?;
                 let input_val = self.eval_expr(args[1].clone())?;
-                
+
                 let input_str = match input_val {
                     Value::String(s) => s,
-                    _ => return Err("ai.infer() input must be a string".to_string()),
+                    _ => return Err(messages::render("E0418", &[])),
                 };
-                
+
                 let model = ai_runtime.model("sentiment")?;
                 ai_runtime.infer(&model, &input_str)
             }
             "embed" => {
                 if args.is_empty() {
-                    return Err("ai.embed() requires a text argument".to_string());
+                    return Err(messages::render("E0419", &[&"ai.embed"]));
                 }
-                
+
                 let text_val = self.eval_expr(args[0].clone())?;
                 let text_str = match text_val {
                     Value::String(s) => s,
-                    _ => return Err("ai.embed() requires a string argument".to_string()),
+                    _ => return Err(messages::render("E0416", &[&"ai.embed"])),
                 };
-                
+
                 let embeddings = ai_runtime.embed(&text_str)?;
-                Ok(Value::Array(
+                Ok(Value::array(
                     embeddings
                         .iter()
                         .map(|&f| Value::Number((f * 100.0) as i64))
@@ -393,65 +1665,312 @@ impl Interpreter {
             }
             "tokenize" => {
                 if args.is_empty() {
-                    return Err("ai.tokenize() requires a text argument".to_string());
+                    return Err(messages::render("E0419", &[&"ai.tokenize"]));
                 }
-                
+
                 let text_val = self.eval_expr(args[0].clone())?;
                 let text_str = match text_val {
                     Value::String(s) => s,
-                    _ => return Err("ai.tokenize() requires a string argument".to_string()),
+                    _ => return Err(messages::render("E0416", &[&"ai.tokenize"])),
                 };
-                
+
                 let tokens = ai_runtime.tokenize(&text_str)?;
-                Ok(Value::Array(
+                Ok(Value::array(
                     tokens.into_iter().map(Value::String).collect(),
                 ))
             }
-            _ => Err(format!("Unknown AI method: ai.{}", method)),
+            _ => Err(messages::render("E0420", &[&method])),
+        }
+    }
+
+    /// `web3.logs(contract, event, from_block, to_block)`: the contract's
+    /// logs of one event, decoded into maps
+    fn web3_logs(&mut self, args: Vec<Expr>) -> EvalResult {
+        let mut values = Vec::new();
+        for arg in args {
+            values.push(self.eval_expr(arg)?);
+        }
+        let (address, event, from_block, to_block) = match values.as_slice() {
+            [Value::Address(a) | Value::String(a), Value::String(e), Value::Number(from), Value::Number(to)]
+                if *from >= 0 && from <= to =>
+            {
+                (a.clone(), e.clone(), *from as u64, *to as u64)
+            }
+            _ => return Err(messages::render("E0440", &[])),
+        };
+        let event = EventSignature::parse(&event)?;
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.log_source.is_none() {
+            if let Some(source) = RpcLogSource::from_env() {
+                let cache = dirs::home_dir().map(|home| home.join(".astrixa").join("cache").join("logs"));
+                self.set_log_source(Box::new(source), cache);
+            }
+        }
+
+        // The node being unreachable or refusing is the script's to handle
+        let fetched = self.log_source.as_deref().ok_or_else(|| messages::render("E0442", &[])).and_then(|source| {
+            let filter = LogFilter { address, topic: event.topic(), from_block, to_block };
+            let logs = logs::fetch(source, self.log_cache.as_ref(), &filter)?;
+            logs.iter().map(|log| event.decode(log)).collect::<Result<Vec<_>, _>>()
+        });
+        Ok(Value::Result(match fetched {
+            Ok(decoded) => Ok(Box::new(Value::array(decoded))),
+            Err(message) => Err(Box::new(Value::String(message))),
+        }))
+    }
+
+    /// Reach the node ASTRIXA_RPC_URL names for contract calls, unless a
+    /// call source is already set
+    fn connect_call_source(&mut self) {
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.call_source.is_none() {
+            if let Some(source) = RpcCallSource::from_env() {
+                self.set_call_source(Box::new(source));
+            }
+        }
+    }
+
+    /// `web3.multicall([(contract, function, args...)])`: every call's
+    /// decoded result, or why it failed, in one round trip
+    fn web3_multicall(&mut self, args: Vec<Expr>) -> EvalResult {
+        let mut values = Vec::new();
+        for arg in args {
+            values.push(self.eval_expr(arg)?);
+        }
+        let [Value::Array(items)] = values.as_slice() else {
+            return Err(messages::render("E0452", &[]));
+        };
+        let mut calls = Vec::new();
+        for item in items.borrow().iter() {
+            let Value::Tuple(fields) = item else {
+                return Err(messages::render("E0452", &[]));
+            };
+            let [Value::Address(target) | Value::String(target), Value::String(signature), args @ ..] = fields.as_slice() else {
+                return Err(messages::render("E0452", &[]));
+            };
+            let function = FunctionSignature::parse(signature)?;
+            let data = function.encode(args).map_err(|e| messages::render("E0454", &[&function.name, &e]))?;
+            calls.push(Call { target: target.clone(), function, data });
+        }
+
+        self.connect_call_source();
+
+        // As with web3.logs, an unreachable node is the script's to handle
+        let executed = self
+            .call_source
+            .as_deref()
+            .ok_or_else(|| messages::render("E0455", &[]))
+            .and_then(|source| multicall::execute(source, &calls));
+        Ok(Value::Result(match executed {
+            Ok(results) => Ok(Box::new(Value::array(
+                results
+                    .into_iter()
+                    .map(|result| Value::Result(result.map(Box::new).map_err(|e| Box::new(Value::String(e)))))
+                    .collect(),
+            ))),
+            Err(message) => Err(Box::new(Value::String(message))),
+        }))
+    }
+
+    /// `web3.balance`, `web3.code` and `web3.storage` read an account of the
+    /// simulated chain; `web3.deal` and `web3.store` write one
+    fn web3_account(&mut self, func: &str, args: Vec<Expr>) -> EvalResult {
+        let mut values = Vec::new();
+        for arg in args {
+            values.push(self.eval_expr(arg)?);
+        }
+        // Slots and words may be written as numbers or hex
+        let hex_of = |value: &Value| match value {
+            Value::Number(n) if *n >= 0 => Some(format!("{:x}", n)),
+            Value::U256(n) => Some(format!("{:x}", n)),
+            Value::String(s) => Some(s.clone()),
+            _ => None,
+        };
+        let usage = |expected: &str| messages::render("E0466", &[&func, &expected]);
+        match (func, values.as_slice()) {
+            ("balance", [Value::Address(a) | Value::String(a)]) => Ok(Value::U256(U256::from(self.chain.balance(a)?))),
+            ("code", [Value::Address(a) | Value::String(a)]) => Ok(Value::String(self.chain.code(a)?)),
+            ("storage", [Value::Address(a) | Value::String(a), slot]) => {
+                let slot = hex_of(slot).ok_or_else(|| usage("(address, slot)"))?;
+                Ok(Value::String(self.chain.storage(a, &slot)?))
+            }
+            ("deal", [Value::Address(a) | Value::String(a), amount]) => {
+                let amount = match amount {
+                    Value::Number(n) if *n >= 0 => *n as u128,
+                    Value::U256(n) => u128::try_from(*n).map_err(|_| usage("an amount below 2^128"))?,
+                    _ => return Err(usage("(address, amount)")),
+                };
+                self.chain.set_balance(a, amount)?;
+                Ok(Value::Null)
+            }
+            ("store", [Value::Address(a) | Value::String(a), slot, value]) => {
+                let (Some(slot), Some(value)) = (hex_of(slot), hex_of(value)) else {
+                    return Err(usage("(address, slot, value)"));
+                };
+                self.chain.set_storage(a, &slot, &value)?;
+                Ok(Value::Null)
+            }
+            ("storage", _) => Err(usage("(address, slot)")),
+            ("deal", _) => Err(usage("(address, amount)")),
+            ("store", _) => Err(usage("(address, slot, value)")),
+            _ => Err(usage("(address)")),
+        }
+    }
+
+    /// `web3.sign_typed(domain, types, value)` signs EIP-712 typed data with
+    /// the key passed last or ASTRIXA_PRIVATE_KEY;
+    /// `web3.verify_typed(domain, types, value, signature, signer)` checks
+    /// that `signer` produced the signature
+    fn web3_typed(&mut self, func: &str, args: Vec<Expr>) -> EvalResult {
+        let mut values = Vec::new();
+        for arg in args {
+            values.push(self.eval_expr(arg)?);
+        }
+        let usage = || messages::render("E0449", &[&func]);
+        let [domain, Value::String(types), value, rest @ ..] = values.as_slice() else {
+            return Err(usage());
+        };
+        let failed = |e: String| messages::render("E0450", &[&e]);
+        let types = TypedData::parse(types).map_err(failed)?;
+        let digest = eip712::digest(domain, &types, value).map_err(failed)?;
+
+        match (func, rest) {
+            ("sign_typed", []) => {
+                let key = std::env::var("ASTRIXA_PRIVATE_KEY").map_err(|_| messages::render("E0451", &[]))?;
+                eip712::sign(&key, &digest).map(Value::String).map_err(failed)
+            }
+            ("sign_typed", [Value::String(key)]) => eip712::sign(key, &digest).map(Value::String).map_err(failed),
+            ("verify_typed", [Value::String(signature), Value::Address(signer) | Value::String(signer)]) => {
+                // A malformed signature verifies nothing rather than failing
+                let recovered = eip712::recover(&digest, signature);
+                Ok(Value::Bool(recovered.is_ok_and(|address| address.eq_ignore_ascii_case(signer))))
+            }
+            _ => Err(usage()),
+        }
+    }
+
+    /// Functions without `export` are private to the module declaring them
+    fn is_visible(&self, func: &Function) -> bool {
+        func.exported || func.module.is_none() || func.module == self.current_module
+    }
+
+    /// Bring a module's functions into scope.
+    ///
+    /// `import m` binds every exported function of `m` and the name `m` itself;
+    /// `import m as a` binds only `a`; `import m.{f, g}` binds only `f` and
+    /// `g`. Binding a name that already refers to something else is an error.
+    fn import(
+        &mut self,
+        module: &str,
+        alias: Option<String>,
+        items: Option<Vec<String>>,
+    ) -> Result<(), String> {
+        // The prelude's functions are called where they are named already
+        if module == crate::prelude::MODULE {
+            return match items.into_iter().flatten().find(|name| crate::prelude::get(name).is_none()) {
+                Some(name) => Err(messages::render("E0428", &[&module, &name])),
+                None => Ok(()),
+            };
+        }
+
+        self.load_module(module)?;
+        let functions = self.loaded_modules.get(module).cloned().unwrap_or_default();
+
+        let qualifier = match (&alias, &items) {
+            (Some(alias), _) => Some(alias.clone()),
+            (None, None) => Some(module.to_string()),
+            (None, Some(_)) => None,
+        };
+        if let Some(qualifier) = qualifier {
+            if let Some(other) = self.module_names.get(&qualifier).filter(|other| *other != module) {
+                return Err(messages::render("E0430", &[&qualifier, other]));
+            }
+            self.module_names.insert(qualifier, module.to_string());
+        }
+
+        let qualified = |name: &String| format!("{}.{}", module, name);
+        let names = match (items, alias) {
+            (Some(items), _) => items,
+            (None, Some(_)) => Vec::new(),
+            (None, None) => functions
+                .iter()
+                .filter(|name| self.functions.get(&qualified(name)).is_some_and(|f| f.exported))
+                .cloned()
+                .collect(),
+        };
+        for name in names {
+            if !functions.contains(&name) {
+                return Err(messages::render("E0428", &[&module, &name]));
+            }
+            if !self.functions[&qualified(&name)].exported {
+                return Err(messages::render("E0431", &[&module, &name]));
+            }
+            if let Some(existing) = self.functions.get(&name) {
+                if existing.module.as_deref() != Some(module) {
+                    return Err(messages::render("E0429", &[&name, &module]));
+                }
+            }
+            let func = self.functions[&qualified(&name)].clone();
+            self.functions.insert(name, func);
         }
+
+        Ok(())
     }
 
+    /// Load a module once, registering its functions as `module.name`
     fn load_module(&mut self, name: &str) -> Result<(), String> {
-        if self.loaded_modules.contains(name) {
+        if self.loaded_modules.contains_key(name) {
             return Ok(());
         }
 
-        self.loaded_modules.insert(name.to_string());
+        self.loaded_modules.insert(name.to_string(), Vec::new());
 
         // First, try to load from installed packages
         let source = if let Ok(pm) = PackageManager::new() {
             if let Some(package_path) = pm.resolve_import(name) {
                 std::fs::read_to_string(&package_path)
-                    .map_err(|_| format!("Error: Cannot read package module '{}'", name))?
+                    .map_err(|_| messages::render("E0421", &[&name]))?
             } else {
                 // Fall back to local file
                 let filename = format!("{}.ax", name);
                 std::fs::read_to_string(&filename)
-                    .map_err(|_| format!("Error: Cannot find module '{}' (tried package and local file)", name))?
+                    .map_err(|_| messages::render("E0422", &[&name]))?
             }
         } else {
             // Package manager not available, try local file
             let filename = format!("{}.ax", name);
             std::fs::read_to_string(&filename)
-                .map_err(|_| format!("Error: Cannot find module '{}'", name))?
+                .map_err(|_| messages::render("E0423", &[&name]))?
         };
 
-        let mut lexer = Lexer::new(&source);
-        let tokens = lexer.tokenize();
-        let mut parser = Parser::new(tokens);
-        let ast = parser.parse();
+        let mut parser = Parser::new(Lexer::new(&source));
+        let ast = parser.parse().map_err(|e| messages::render("E0424", &[&name, &e]))?;
 
-        for stmt in ast {
-            match stmt {
-                Stmt::Function(func) => {
-                    self.functions.insert(func.name.clone(), func);
-                }
-                Stmt::Import(module) => {
-                    self.load_module(&module)?;
+        let mut names = Vec::new();
+        for stmt in crate::nested::lift(&ast) {
+            match stmt.kind {
+                StmtKind::Function { name: func, params, param_types, defaults, body, is_async, exported, .. } => {
+                    self.functions.insert(
+                        format!("{}.{}", name, func),
+                        Function {
+                            params,
+                            param_types,
+                            defaults,
+                            body,
+                            is_async,
+                            contract: None,
+                            module: Some(name.to_string()),
+                            exported,
+                        },
+                    );
+                    names.push(func);
                 }
+                kind @ StmtKind::Import { .. } => self.declare(Stmt::new(kind, stmt.span))?,
                 _ => {}
             }
         }
+        self.loaded_modules.insert(name.to_string(), names);
 
         Ok(())
     }
@@ -459,29 +1978,51 @@ impl Interpreter {
     fn stdlib(&mut self, name: &str, args: &[Value]) -> Result<Value, String> {
         match name {
             "len" => {
-                match args.get(0) {
-                    Some(Value::Array(arr)) => Ok(Value::Number(arr.len() as i64)),
-                    Some(Value::String(s)) => Ok(Value::Number(s.len() as i64)),
-                    _ => Err("Error: len() expects array or string".to_string()),
+                match args.first() {
+                    Some(Value::Array(arr)) => Ok(Value::Number(arr.borrow().len() as i64)),
+                    Some(Value::String(s)) => Ok(Value::Number(s.chars().count() as i64)),
+                    Some(Value::StringBuilder(text)) => Ok(Value::Number(text.borrow().chars().count() as i64)),
+                    Some(Value::Range(start, end)) => Ok(Value::Number(end.saturating_sub(*start).max(0))),
+                    _ => Err(messages::render("E0425", &[])),
                 }
             }
-            "type" => {
-                let t = match args.get(0) {
-                    Some(Value::Number(_)) => "number",
-                    Some(Value::String(_)) => "string",
-                    Some(Value::Bool(_)) => "bool",
-                    Some(Value::Array(_)) => "array",
-                    Some(Value::Address(_)) => "address",
-                    Some(Value::U256(_)) => "u256",
-                    Some(Value::AIResult { .. }) => "ai_result",
-                    Some(Value::Null) => "null",
-                    None => "unknown",
+            "to_string" | "str" => {
+                let text = match args.first() {
+                    Some(Value::String(s)) => s.clone(),
+                    Some(Value::StringBuilder(text)) => text.borrow().clone(),
+                    Some(v) => self.render_value(v),
+                    None => "null".to_string(),
                 };
+                Ok(Value::String(text))
+            }
+            "StringBuilder" => Ok(Value::StringBuilder(Rc::new(RefCell::new(String::new())))),
+            "int" => match args.first() {
+                Some(Value::String(text)) => {
+                    text.trim().parse().map(Value::Number).map_err(|_| messages::render("E0484", &[text]))
+                }
+                Some(Value::Float(f)) => Ok(Value::Number(*f as i64)),
+                Some(n @ Value::Number(_)) => Ok(n.clone()),
+                other => Err(messages::render("E0483", &[&other.map_or("null", type_name), &"Int"])),
+            },
+            "type" => {
+                let t = args.first().map(type_name).unwrap_or("unknown");
                 Ok(Value::String(t.to_string()))
             }
+            "generate_keypair" => {
+                // Deterministic development keys, so test runs are
+                // reproducible; never use them to hold real funds
+                use sha2::{Digest, Sha256};
+                self.keypairs_issued += 1;
+                let private = Sha256::digest(format!("astrixa-dev-key-{}", self.keypairs_issued));
+                let public = Sha256::digest(private);
+                Ok(Value::Tuple(vec![
+                    Value::String(format!("0x{:x}", public)),
+                    Value::String(format!("0x{:x}", private)),
+                ]))
+            }
             "input" => {
                 use std::io::{self, Write};
-                if let Some(Value::String(prompt)) = args.get(0) {
+                if let Some(Value::String(prompt)) = args.first() {
                     print!("{}", prompt);
                 }
                 io::stdout().flush().map_err(|e| e.to_string())?;
@@ -490,14 +2031,1665 @@ impl Interpreter {
                 Ok(Value::String(buffer.trim().to_string()))
             }
             "range" => {
-                if let (Some(Value::Number(start)), Some(Value::Number(end))) = (args.get(0), args.get(1)) {
-                    let v: Vec<Value> = (*start..*end).map(Value::Number).collect();
-                    Ok(Value::Array(v))
+                if let (Some(Value::Number(start)), Some(Value::Number(end))) = (args.first(), args.get(1)) {
+                    Ok(Value::Range(*start, *end))
                 } else {
-                    Err("Error: range() expects two numbers".to_string())
+                    Err(messages::render("E0426", &[]))
                 }
             }
-            _ => Err(format!("Error: unknown stdlib function '{}'", name)),
-        }
+            "map" | "filter" => {
+                let source = args.first().and_then(Lazy::of);
+                match (source, args.get(1)) {
+                    (Some(source), Some(Value::Function(function))) => {
+                        let function = function.clone();
+                        Ok(Value::Iter(Rc::new(match name {
+                            "map" => Lazy::Map(source, function),
+                            _ => Lazy::Filter(source, function),
+                        })))
+                    }
+                    _ => Err(messages::render("E0485", &[&name])),
+                }
+            }
+            "collect" => {
+                let Some(lazy) = args.first().and_then(Lazy::of) else {
+                    let given = args.first().map_or("nothing", type_name);
+                    return Err(messages::render("E0487", &[&given]));
+                };
+                let mut items = Vec::new();
+                self.drive(&lazy, &mut |_, item| {
+                    items.push(item);
+                    Ok(None)
+                })?;
+                Ok(Value::array(items))
+            }
+            "on_shutdown" => match args.first() {
+                Some(Value::Function(function)) => {
+                    self.shutdown_handlers.push(function.clone());
+                    Ok(Value::Null)
+                }
+                other => Err(messages::render("E0489", &[&other.map_or("nothing", type_name)])),
+            },
+            "sleep" => {
+                // Deterministic runtime: sleeping only yields, it never blocks
+                match args.first() {
+                    Some(Value::Number(_)) => Ok(Value::Null),
+                    _ => Err(messages::render("E0427", &[])),
+                }
+            }
+            // A private function of an imported module deserves a better hint
+            _ => match self.private_owner(name) {
+                Some(module) => Err(messages::render("E0431", &[&module, &name])),
+                None => Err(messages::render("E0406", &[&name])),
+            },
+        }
+    }
+
+    /// The loaded module declaring `name` without `export`, if any
+    fn private_owner(&self, name: &str) -> Option<String> {
+        self.loaded_modules
+            .iter()
+            .filter(|(_, functions)| functions.iter().any(|f| f == name))
+            .map(|(module, _)| module)
+            .find(|module| {
+                self.functions
+                    .get(&format!("{}.{}", module, name))
+                    .is_some_and(|f| !f.exported)
+            })
+            .cloned()
+    }
+}
+
+/// The type name trait impls for a value are declared under
+fn impl_type(value: &Value) -> Option<&'static str> {
+    match value {
+        Value::Number(_) => Some("Int"),
+        Value::Float(_) => Some("Float"),
+        Value::Bool(_) => Some("Bool"),
+        Value::String(_) => Some("String"),
+        _ => None,
+    }
+}
+
+/// `value[start..end]` for strings (by character) and arrays, and
+/// `value["name"]` for maps
+pub(crate) fn slice(value: Value, range: Value) -> EvalResult {
+    if let (Value::Map(entries), Value::String(key)) = (&value, &range) {
+        return entries
+            .borrow()
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, field)| field.clone())
+            .ok_or_else(|| messages::render("E0439", &[key]));
+    }
+    let Value::Range(start, end) = range else {
+        return Err(messages::render("E0436", &[&type_name(&value), &type_name(&range)]));
+    };
+    let len = match &value {
+        Value::Range(from, to) => to.saturating_sub(*from).max(0) as usize,
+        Value::String(s) => s.chars().count(),
+        Value::Array(items) => items.borrow().len(),
+        _ => return Err(messages::render("E0436", &[&type_name(&value), &type_name(&range)])),
+    };
+    if start < 0 || start > end || end as usize > len {
+        return Err(messages::render("E0437", &[&start, &end, &len]));
+    }
+
+    let (start, end) = (start as usize, end as usize);
+    match value {
+        // Still counted lazily
+        Value::Range(from, _) => Ok(Value::Range(from + start as i64, from + end as i64)),
+        Value::String(s) => Ok(Value::String(s.chars().skip(start).take(end - start).collect())),
+        Value::Array(items) => Ok(Value::array(items.borrow()[start..end].to_vec())),
+        _ => unreachable!(),
+    }
+}
+
+/// `==`: by value, items included, with an address equal to its text in
+/// any case and a U256 to the same non-negative Int
+pub(crate) fn values_equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => x == y,
+        (Value::Float(x), Value::Float(y)) => x == y,
+        (Value::String(x), Value::String(y)) => x == y,
+        (Value::Bool(x), Value::Bool(y)) => x == y,
+        (Value::Address(x), Value::Address(y) | Value::String(y)) | (Value::String(y), Value::Address(x)) => {
+            x.eq_ignore_ascii_case(y)
+        }
+        (Value::U256(x), Value::U256(y)) => x == y,
+        (Value::U256(x), Value::Number(n)) | (Value::Number(n), Value::U256(x)) => {
+            u64::try_from(*n).is_ok_and(|n| U256::from(n) == *x)
+        }
+        (Value::Null, Value::Null) => true,
+        (Value::Range(a, b), Value::Range(c, d)) => a == c && b == d,
+        (Value::Tuple(x), Value::Tuple(y)) => {
+            x.len() == y.len() && x.iter().zip(y).all(|(a, b)| values_equal(a, b))
+        }
+        // By their items, shared or not
+        (Value::Array(x), Value::Array(y)) => {
+            let (x, y) = (x.borrow(), y.borrow());
+            x.len() == y.len() && x.iter().zip(y.iter()).all(|(a, b)| values_equal(a, b))
+        }
+        (Value::Map(x), Value::Map(y)) => {
+            let (x, y) = (x.borrow(), y.borrow());
+            x.len() == y.len() && x.iter().zip(y.iter()).all(|((k, a), (l, b))| k == l && values_equal(a, b))
+        }
+        (Value::Option(x), Value::Option(y)) => match (x, y) {
+            (Some(a), Some(b)) => values_equal(a, b),
+            (x, y) => x.is_none() && y.is_none(),
+        },
+        (Value::Result(Ok(a)), Value::Result(Ok(b))) | (Value::Result(Err(a)), Value::Result(Err(b))) => {
+            values_equal(a, b)
+        }
+        (Value::Function(a), Value::Function(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// An amount as a U256 for ordering, with negative numbers (None) below
+/// every U256
+pub(crate) fn amount(value: &Value) -> Option<U256> {
+    match value {
+        Value::U256(x) => Some(*x),
+        Value::Number(n) => u64::try_from(*n).ok().map(U256::from),
+        _ => None,
+    }
+}
+
+/// Whether `value` is an array, map or string builder with a method named
+/// `method`
+pub(crate) fn has_collection_method(value: &Value, method: &str) -> bool {
+    match value {
+        Value::Array(_) => matches!(method, "push" | "pop" | "set" | "copy"),
+        Value::Map(_) => matches!(method, "set" | "copy"),
+        Value::StringBuilder(_) => matches!(method, "append" | "to_string" | "copy"),
+        _ => false,
+    }
+}
+
+/// Call one of an array's, map's or string builder's methods (see
+/// `has_collection_method`), changing it in place
+pub(crate) fn collection_method(collection: &Value, method: &str, args: Vec<Value>) -> EvalResult {
+    let expects = |what: &str| Err(messages::render("E0480", &[&type_name(collection), &method, &what]));
+    // Checked before anything is stored, so no array or map holds itself
+    let stored = |value: &Value, items: *const ()| {
+        if value.refers_to(items) {
+            Err(messages::render("E0482", &[]))
+        } else {
+            Ok(value.clone())
+        }
+    };
+    match (collection, method, args.as_slice()) {
+        (Value::Array(_) | Value::Map(_) | Value::StringBuilder(_), "copy", []) => Ok(collection.deep_copy()),
+        (Value::StringBuilder(text), "append", [Value::String(more)]) => {
+            text.borrow_mut().push_str(more);
+            Ok(Value::Null)
+        }
+        (Value::StringBuilder(_), "append", _) => expects("one string"),
+        (Value::StringBuilder(text), "to_string", []) => Ok(Value::String(text.borrow().clone())),
+        (_, "copy" | "to_string", _) => expects("no arguments"),
+        (Value::Array(items), "push", [value]) => stored(value, Rc::as_ptr(items) as *const ()).map(|value| {
+            items.borrow_mut().push(value);
+            Value::Null
+        }),
+        (Value::Array(_), "push", _) => expects("one value"),
+        (Value::Array(items), "pop", []) => Ok(Value::Option(items.borrow_mut().pop().map(Box::new))),
+        (Value::Array(_), "pop", _) => expects("no arguments"),
+        (Value::Array(items), "set", [Value::Number(index), value]) => {
+            let value = stored(value, Rc::as_ptr(items) as *const ())?;
+            let len = items.borrow().len();
+            match usize::try_from(*index).ok().filter(|&i| i < len) {
+                Some(i) => {
+                    items.borrow_mut()[i] = value;
+                    Ok(Value::Null)
+                }
+                None => Err(messages::render("E0481", &[index, &len])),
+            }
+        }
+        (Value::Array(_), "set", _) => expects("an index and a value"),
+        (Value::Map(entries), "set", [Value::String(key), value]) => stored(value, Rc::as_ptr(entries) as *const ()).map(|value| {
+            let mut entries = entries.borrow_mut();
+            match entries.iter_mut().find(|(name, _)| name == key) {
+                Some((_, slot)) => *slot = value,
+                None => entries.push((key.clone(), value)),
+            }
+            Value::Null
+        }),
+        (Value::Map(_), "set", _) => expects("a string key and a value"),
+        _ => Err(messages::render("E0406", &[&format!("{}.{}", type_name(collection), method)])),
+    }
+}
+
+/// Name of a value's runtime type, as `type()` reports it
+pub(crate) fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Number(_) => "number",
+        Value::Float(_) => "float",
+        Value::String(_) => "string",
+        Value::Bool(_) => "bool",
+        Value::Array(_) => "array",
+        Value::Tuple(_) => "tuple",
+        Value::Map(_) => "map",
+        Value::Option(_) => "option",
+        Value::Result(_) => "result",
+        Value::Address(_) => "address",
+        Value::U256(_) => "u256",
+        Value::AIResult { .. } => "ai_result",
+        Value::Task(_) => "task",
+        Value::StringBuilder(_) => "string_builder",
+        Value::Range(..) => "range",
+        Value::Iter(_) => "iterator",
+        Value::Function(_) => "function",
+        Value::Null => "null",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_source(source: &str) -> Result<(), String> {
+        let program = Parser::new(Lexer::new(source)).parse().map_err(|e| e.message)?;
+        Interpreter::new().run(program)
+    }
+
+    #[test]
+    fn test_conditions_must_be_booleans() {
+        let error = run_source("fn main() {\n    if 1 {\n        print(\"ran\")\n    }\n}\n").unwrap_err();
+        assert_eq!(error, messages::render("E0404", &[]));
+    }
+
+    #[test]
+    fn test_await_async_function_result() {
+        let source = r#"
+            async fn double(x) {
+                return x * 2
+            }
+            fn main() {
+                let v = await double(21)
+                if v != 42 {
+                    panic("wrong result")
+                }
+            }
+        "#;
+        assert!(run_source(source).is_ok());
+    }
+
+    #[test]
+    fn test_spawned_tasks_run_in_order_before_awaited_task() {
+        let source = r#"
+            contract Log {
+                state: ["last"]
+                fn set(v) { last = v }
+                fn get() { return last }
+            }
+            async fn record(v) {
+                Log.set(v)
+            }
+            async fn read() {
+                return Log.get()
+            }
+            fn main() {
+                spawn(record(1))
+                spawn(record(2))
+                let seen = await read()
+                if seen != 2 {
+                    panic("spawned tasks did not run first")
+                }
+            }
+        "#;
+        assert!(run_source(source).is_ok());
+    }
+
+    #[test]
+    fn test_else_if_chain_picks_first_matching_branch() {
+        let source = r#"
+            fn classify(n) {
+                if n < 0 {
+                    return "negative"
+                } else if n == 0 {
+                    return "zero"
+                } else if n < 10 {
+                    return "small"
+                } else {
+                    return "large"
+                }
+            }
+            fn main() {
+                if classify(0) != "zero" { panic("0") }
+                if classify(5) != "small" { panic("5") }
+                if classify(50) != "large" { panic("50") }
+                if classify(0 - 1) != "negative" { panic("-1") }
+            }
+        "#;
+        assert!(run_source(source).is_ok());
+    }
+
+    #[test]
+    fn test_panic_in_task_propagates_to_awaiter() {
+        let source = r#"
+            async fn fail() {
+                panic("boom")
+            }
+            fn main() {
+                await fail()
+            }
+        "#;
+        assert_eq!(run_source(source), Err("Panic: boom".to_string()));
+    }
+
+    #[test]
+    fn test_require_fails_with_its_message() {
+        let source = r#"
+            fn withdraw(balance, amount) {
+                require(amount <= balance, "insufficient balance")
+                return balance - amount
+            }
+            fn main() {
+                if withdraw(10, 4) != 6 { panic("wrong balance") }
+                withdraw(3, 4)
+            }
+        "#;
+        assert_eq!(run_source(source), Err("Panic: insufficient balance".to_string()));
+
+        // The failure is located in `withdraw`, at the require's message,
+        // not at the call into it
+        let program = Parser::new(Lexer::new(source)).parse().unwrap();
+        let mut interpreter = Interpreter::new();
+        assert!(interpreter.run(program).is_err());
+        let span = interpreter.error_span().unwrap();
+        assert_eq!((span.start_line, span.start_column), (3, 44));
+    }
+
+    #[test]
+    fn test_panics_record_where_they_unwound() {
+        let source = r#"
+            fn check(n) {
+                if n > 2 {
+                    panic("too big")
+                }
+                return n
+            }
+            fn main() {
+                print("start")
+                check(5)
+            }
+        "#;
+        let mut interpreter = Interpreter::new();
+        assert!(interpreter.run(Parser::new(Lexer::new(source)).parse().unwrap()).is_err());
+        let panic = interpreter.take_panic().unwrap();
+        assert_eq!((panic.message.as_str(), panic.span.start_line, panic.reverted), ("too big", 4, false));
+        assert_eq!(panic.to_string(), "Panic: too big\n    at check (line 4, column 21)\n    at main (line 10, column 17)");
+
+        // Out of a contract method it reverts, with the message as reason
+        let source = r#"
+            contract Vault {
+                fn withdraw() {
+                    panic("empty")
+                }
+            }
+        "#;
+        interpreter.load(Parser::new(Lexer::new(source)).parse().unwrap()).unwrap();
+        assert!(interpreter.transact("Vault", "withdraw", vec![], "0xaa", 0).is_err());
+        let panic = interpreter.take_panic().unwrap();
+        assert!(panic.reverted);
+        let data = panic.revert_data();
+        assert_eq!((data.len(), &data[..4], data[35], data[67]), (100, &[0x08, 0xc3, 0x79, 0xa0][..], 0x20, 5));
+        assert_eq!(&data[68..73], b"empty");
+        assert!(interpreter.take_panic().is_none());
+    }
+
+    #[test]
+    fn test_properties_read_the_blockchain_context() {
+        use crate::typechecker::TypeChecker;
+
+        let source = r#"
+            contract Vault {
+                state owner
+                constructor() {
+                    owner = msg.sender
+                }
+                fn paid() {
+                    return (owner, msg.value, chain.id)
+                }
+            }
+        "#;
+        let program = Parser::new(Lexer::new(source)).parse().unwrap();
+        assert!(TypeChecker::new().check(&program).is_ok());
+
+        let mut interpreter = Interpreter::new();
+        interpreter.load(program).unwrap();
+        interpreter.transact("Vault", "constructor", vec![], "0xaa", 0).unwrap();
+        assert_eq!(
+            interpreter.transact("Vault", "paid", vec![], "0xbb", 5),
+            Ok(Value::Tuple(vec![Value::Address("0xaa".to_string()), Value::U256(U256::from(5)), Value::Number(1)]))
+        );
+
+        let program = Parser::new(Lexer::new("fn main() {\n    let origin = msg.origin\n}\n")).parse().unwrap();
+        let errors = TypeChecker::new().check(&program).unwrap_err();
+        assert_eq!(errors[0].code, Some("E0245"));
+    }
+
+    #[test]
+    fn test_named_and_default_arguments() {
+        use crate::typechecker::TypeChecker;
+
+        let source = r#"
+            fn greet(name = "friend", greeting = "Hello", punctuation = "!") {
+                return greeting + ", " + name + punctuation
+            }
+            fn main() {
+                print(greet())
+                print(greet("Ana", "Hi"))
+                print(greet(punctuation: "?", name: "Ana"))
+                return greet("Ana", punctuation: ".")
+            }
+        "#;
+        let program = Parser::new(Lexer::new(source)).parse().unwrap();
+        assert!(TypeChecker::new().check(&program).is_ok());
+
+        let mut interpreter = Interpreter::new();
+        interpreter.load(program).unwrap();
+        assert_eq!(interpreter.call_values("main", vec![]), Ok(Value::String("Hello, Ana.".to_string())));
+
+        // An unannotated parameter without a default takes any type, and
+        // one with a default takes the default's
+        let source = r#"
+            fn greet(name, greeting = "Hello") {
+                return greeting + ", " + name
+            }
+            fn main() {
+                greet(7, 8)
+                return greet("Ada")
+            }
+        "#;
+        let program = Parser::new(Lexer::new(source)).parse().unwrap();
+        let errors = TypeChecker::new().check(&program).unwrap_err();
+        assert_eq!(errors.iter().map(|e| (e.code, e.line)).collect::<Vec<_>>(), vec![(Some("E0208"), 6)]);
+
+        let program = Parser::new(Lexer::new("fn f(a, b = 1) { }\nfn main() {\n    f(1, c: 2)\n}\n")).parse().unwrap();
+        let errors = TypeChecker::new().check(&program).unwrap_err();
+        assert_eq!(errors[0].code, Some("E0248"));
+        let mut interpreter = Interpreter::new();
+        let error = interpreter.run(program).unwrap_err();
+        assert!(error.contains("'f' has no parameter named 'c'"), "{}", error);
+
+        let error = Parser::new(Lexer::new("fn f(a = 1, b) { }")).parse().unwrap_err();
+        assert!(format!("{:?}", error).contains("E0166"));
+    }
+
+    #[test]
+    fn test_u256_module() {
+        use crate::typechecker::TypeChecker;
+
+        let source = r#"
+            fn main() {
+                let max = u256.from("0xffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff")
+                print(u256.mulmod(max, max, 1000))
+                return u256.saturating_add(max, u256.shl(1, 8))
+            }
+        "#;
+        let program = Parser::new(Lexer::new(source)).parse().unwrap();
+        assert!(TypeChecker::new().check(&program).is_ok());
+
+        let mut interpreter = Interpreter::new();
+        interpreter.load(program).unwrap();
+        assert_eq!(interpreter.call_values("main", vec![]), Ok(Value::U256(U256::MAX)));
+
+        let program = Parser::new(Lexer::new("fn main() {\n    let a = u256.div(1, 2)\n    let b = u256.shr(1)\n}\n")).parse().unwrap();
+        let errors = TypeChecker::new().check(&program).unwrap_err();
+        let codes: Vec<_> = errors.iter().map(|e| e.code).collect();
+        assert_eq!(codes, vec![Some("E0253"), Some("E0207")]);
+    }
+
+    #[test]
+    fn test_merkle_proofs_verify_in_contracts() {
+        use crate::typechecker::TypeChecker;
+
+        let source = r#"
+            contract Airdrop {
+                state root
+                constructor(r) {
+                    root = r
+                }
+                fn claim(leaf, proof) {
+                    return merkle.verify(root, leaf, proof)
+                }
+            }
+            fn main() {
+                let leaves = ["0xaa:100", "0xbb:250", "0xcc:75"]
+                return (merkle.root(leaves), merkle.proof(leaves, 2))
+            }
+        "#;
+        let program = Parser::new(Lexer::new(source)).parse().unwrap();
+        assert!(TypeChecker::new().check(&program).is_ok());
+
+        let mut interpreter = Interpreter::new();
+        interpreter.load(program).unwrap();
+        let Ok(Value::Tuple(tree)) = interpreter.call_values("main", vec![]) else { panic!("no tree") };
+        interpreter.transact("Airdrop", "constructor", vec![tree[0].clone()], "0xaa", 0).unwrap();
+        let claim = |interpreter: &mut Interpreter, leaf: &str| {
+            interpreter.transact("Airdrop", "claim", vec![Value::String(leaf.to_string()), tree[1].clone()], "0xcc", 0)
+        };
+        assert_eq!(claim(&mut interpreter, "0xcc:75"), Ok(Value::Bool(true)));
+        assert_eq!(claim(&mut interpreter, "0xcc:7500"), Ok(Value::Bool(false)));
+
+        let source = "contract Airdrop {\n    fn build(leaves) {\n        return merkle.root(leaves)\n    }\n}\n";
+        let errors = TypeChecker::new().check(&Parser::new(Lexer::new(source)).parse().unwrap()).unwrap_err();
+        assert_eq!(errors[0].code, Some("E0254"));
+    }
+
+    #[test]
+    fn test_defer_runs_as_blocks_exit() {
+        use crate::typechecker::TypeChecker;
+
+        let source = r#"
+            contract Log {
+                state entries
+                constructor() {
+                    entries = ""
+                }
+                fn note(text) {
+                    entries = entries + text
+                }
+                fn work(n) {
+                    defer Log.note("a")
+                    for i in 0..2 {
+                        defer Log.note("b")
+                        Log.note("c")
+                    }
+                    if n > 0 {
+                        defer Log.note("d")
+                        return entries
+                    }
+                    defer Log.note("e")
+                    Log.note("f")
+                }
+                fn read() {
+                    return entries
+                }
+            }
+        "#;
+        let program = Parser::new(Lexer::new(source)).parse().unwrap();
+        assert!(TypeChecker::new().check(&program).is_ok());
+
+        let mut interpreter = Interpreter::new();
+        interpreter.load(program).unwrap();
+        interpreter.transact("Log", "constructor", vec![], "0xaa", 0).unwrap();
+        // The value returned is read before the deferred calls run
+        assert_eq!(interpreter.transact("Log", "work", vec![Value::Number(1)], "0xaa", 0), Ok(Value::String("cbcb".to_string())));
+        assert_eq!(interpreter.transact("Log", "read", vec![], "0xaa", 0), Ok(Value::String("cbcbda".to_string())));
+        interpreter.transact("Log", "work", vec![Value::Number(0)], "0xaa", 0).unwrap();
+        assert_eq!(interpreter.transact("Log", "read", vec![], "0xaa", 0), Ok(Value::String("cbcbdacbcbfea".to_string())));
+
+        let program = Parser::new(Lexer::new("fn main() {\n    defer Some(1)?\n}\n")).parse().unwrap();
+        let errors = TypeChecker::new().check(&program).unwrap_err();
+        assert_eq!(errors[0].code, Some("E0255"));
+    }
+
+    #[test]
+    fn test_defer_blocks_run_as_a_panic_unwinds() {
+        use crate::typechecker::TypeChecker;
+
+        let source = r#"
+            contract Log {
+                state entries
+                constructor() {
+                    entries = ""
+                }
+                fn note(text) {
+                    entries = entries + text
+                }
+                fn read() {
+                    return entries
+                }
+            }
+            fn work(n) {
+                defer {
+                    Log.note("closed ")
+                    Log.note(str(n))
+                }
+                if n > 1 {
+                    defer {
+                        panic("cleanup failed")
+                    }
+                    panic("boom")
+                }
+                Log.note("worked ")
+            }
+        "#;
+        let program = Parser::new(Lexer::new(source)).parse().unwrap();
+        assert!(TypeChecker::new().check(&program).is_ok());
+
+        let mut interpreter = Interpreter::new();
+        interpreter.load(program).unwrap();
+        interpreter.transact("Log", "constructor", vec![], "0xaa", 0).unwrap();
+        interpreter.call_values("work", vec![Value::Number(0)]).unwrap();
+        // Outside a contract a panic runs the deferred code too; the first
+        // failure is what the call fails with
+        let failure = interpreter.call_values("work", vec![Value::Number(2)]).unwrap_err();
+        assert!(failure.contains("boom"), "{}", failure);
+        assert_eq!(interpreter.take_panic().map(|panic| panic.message), Some("boom".to_string()));
+        assert_eq!(interpreter.call_values("Log.read", vec![]), Ok(Value::String("worked closed 0closed 2".to_string())));
+
+        let program = Parser::new(Lexer::new("fn main() {\n    defer {\n        return 1\n    }\n}\n")).parse().unwrap();
+        let errors = TypeChecker::new().check(&program).unwrap_err();
+        assert_eq!((errors[0].code, errors[0].line), (Some("E0269"), 3));
+    }
+
+    #[test]
+    fn test_nested_functions_are_local_helpers() {
+        use crate::typechecker::TypeChecker;
+
+        let source = r#"
+            fn square(x) {
+                return x * x
+            }
+            fn total(n) {
+                fn square(x) {
+                    return x * x * x
+                }
+                fn sum(a, b) {
+                    return square(a) + square(b)
+                }
+                return sum(n, 1)
+            }
+        "#;
+        let program = Parser::new(Lexer::new(source)).parse().unwrap();
+        assert!(TypeChecker::new().check(&program).is_ok());
+
+        let mut interpreter = Interpreter::new();
+        interpreter.load(program).unwrap();
+        assert_eq!(interpreter.call_values("total", vec![Value::Number(2)]), Ok(Value::Number(9)));
+        assert_eq!(interpreter.call_values("square", vec![Value::Number(2)]), Ok(Value::Number(4)));
+
+        let source = "fn total(n) {\n    let rate = 3\n    fn scaled(x) {\n        return x * rate\n    }\n    return scaled(n)\n}\n";
+        let program = Parser::new(Lexer::new(source)).parse().unwrap();
+        let errors = TypeChecker::new().check(&program).unwrap_err();
+        assert_eq!(errors[0].code, Some("E0247"));
+        assert_eq!(errors[0].line, 3);
+    }
+
+    #[test]
+    fn test_final_expression_is_the_return_value() {
+        let source = r#"
+            fn double(x) { x * 2 }
+            fn sign(x) {
+                if x < 0 { 0 - 1 } else { 1 }
+            }
+            fn main() {
+                let a = double(2); let b = sign(0 - 5);
+                if a != 4 { panic("double") }
+                if b != 0 - 1 { panic("sign") }
+            }
+        "#;
+        assert_eq!(run_source(source), Ok(()));
+
+        // A `;` discards the value instead
+        let program = Parser::new(Lexer::new("fn f(x) {\n    x * 2;\n}\n")).parse().unwrap();
+        let StmtKind::Function { body, .. } = &program[0].kind else { panic!("not a function") };
+        assert!(matches!(body[0].kind, StmtKind::Expression(_)));
+    }
+
+    #[test]
+    fn test_run_tests_reports_each_test_function() {
+        let source = r#"
+            fn double(x) {
+                return x * 2
+            }
+            #[test]
+            fn doubles() {
+                if double(2) != 4 { panic("2") }
+            }
+            #[test]
+            fn fails() {
+                panic("expected failure")
+            }
+            fn not_a_test() {
+                panic("should not run")
+            }
+        "#;
+        let program = Parser::new(Lexer::new(source)).parse().unwrap();
+        let results = Interpreter::new().run_tests(program).unwrap();
+
+        assert_eq!(results, vec![
+            ("doubles".to_string(), Ok(())),
+            ("fails".to_string(), Err("Panic: expected failure (line 11, column 17)".to_string())),
+        ]);
+    }
+
+    #[test]
+    fn test_attribute_placement_is_checked() {
+        use crate::typechecker::TypeChecker;
+
+        let source = r#"
+            #[view]
+            fn total() { return 1 }
+            contract Vault {
+                state balance
+                #[view]
+                fn peek() { balance = 0 }
+                #[payable]
+                #[view]
+                fn deposit() { return 0 }
+                #[test]
+                fn check() { return 0 }
+            }
+        "#;
+        let program = Parser::new(Lexer::new(source)).parse().unwrap();
+        let errors = TypeChecker::new().check(&program).unwrap_err();
+        let expected = ["#[view] on 'total'", "'peek' assigns state variable 'balance'", "'deposit' cannot be both", "#[test] on 'check'"];
+
+        for text in expected {
+            assert!(errors.iter().any(|e| e.message.contains(text)), "missing '{}' in {:?}", text, errors);
+        }
+    }
+
+    #[test]
+    fn test_tuples_destructure_and_compare() {
+        let source = r#"
+            fn divmod(a, b) {
+                return (a / b, a % b)
+            }
+            fn main() {
+                let (q, r) = divmod(17, 5)
+                if q != 3 { panic("quotient") }
+                if r != 2 { panic("remainder") }
+                if divmod(9, 3) != (3, 0) { panic("tuple equality") }
+                let (public, _) = generate_keypair()
+                if type(public) != "string" { panic("keypair") }
+            }
+        "#;
+        assert!(run_source(source).is_ok());
+
+        let source = r#"
+            fn main() {
+                let (a, b, c) = (1, 2)
+            }
+        "#;
+        assert_eq!(run_source(source), Err("tuple has 2 elements but the pattern binds 3".to_string()));
+    }
+
+    #[test]
+    fn test_tuple_patterns_are_type_checked() {
+        use crate::typechecker::TypeChecker;
+
+        let source = r#"
+            fn main() {
+                let (a, b) = 5
+                let (c, d) = (1, 2, 3)
+                let (e, f) = generate_keypair()
+                let g = e * 2
+                let h = ((1, 2), 3)
+            }
+        "#;
+        let program = Parser::new(Lexer::new(source)).parse().unwrap();
+        let errors = TypeChecker::new().check(&program).unwrap_err();
+        let expected = [
+            "cannot destructure Int into 2 variables",
+            "tuple has 3 elements but the pattern binds 2",
+            "requires two Int or two Float operands (% takes Int only), got String and Int",
+            "tuples cannot contain other tuples",
+        ];
+
+        assert_eq!(errors.len(), expected.len(), "{:?}", errors);
+        for text in expected {
+            assert!(errors.iter().any(|e| e.message.contains(text)), "missing '{}' in {:?}", text, errors);
+        }
+    }
+
+    #[test]
+    fn test_for_loops_over_ranges_and_slices() {
+        let source = r#"
+            fn main() {
+                let mut total = 0
+                for i in 1..5 {
+                    total = total + i
+                }
+                if total != 10 { panic("range sum") }
+
+                let n = 3
+                let r = 0..n * 2
+                let mut count = 0
+                for i in r {
+                    count = count + 1
+                }
+                if count != 6 { panic("range value") }
+
+                let word = "astrixa"
+                if word[0..5] != "astri" { panic("string slice") }
+                if len(range(0, 10)[2..5]) != 3 { panic("array slice") }
+                for x in range(0, 2) {
+                    total = total + x
+                }
+                if total != 11 { panic("array loop") }
+            }
+        "#;
+        assert!(run_source(source).is_ok());
+
+        let source = r#"
+            fn main() {
+                let word = "ax"
+                print(word[1..4])
+            }
+        "#;
+        assert_eq!(run_source(source), Err("slice 1..4 is out of bounds for length 2".to_string()));
+
+        use crate::typechecker::TypeChecker;
+        let source = r#"
+            fn main() {
+                for c in "abc" {
+                }
+                let r = 0..true
+                let s = 5[0..1]
+                let t = "abc"[1]
+            }
+        "#;
+        let program = Parser::new(Lexer::new(source)).parse().unwrap();
+        let errors = TypeChecker::new().check(&program).unwrap_err();
+        let expected = [
+            "cannot iterate over String",
+            "range bounds must be Int, got Int and Bool",
+            "cannot slice Int",
+            "slices take a range, got Int",
+        ];
+        assert_eq!(errors.len(), expected.len(), "{:?}", errors);
+        for text in expected {
+            assert!(errors.iter().any(|e| e.message.contains(text)), "missing '{}' in {:?}", text, errors);
+        }
+    }
+
+    #[test]
+    fn test_generic_functions_check_and_run() {
+        use crate::typechecker::TypeChecker;
+
+        let source = r#"
+            fn max<T>(a: T, b: T) -> T {
+                if a > b {
+                    return a
+                }
+                return b
+            }
+            fn same<T>(a: T, b: T) -> Bool {
+                return a == b
+            }
+            fn main() {
+                if max(3, 7) != 7 { panic("max") }
+                if same("ax", "bx") { panic("same") }
+            }
+        "#;
+        let program = Parser::new(Lexer::new(source)).parse().unwrap();
+        let checked = TypeChecker::new().check(&program);
+        assert!(checked.is_ok(), "{:?}", checked);
+        assert!(run_source(source).is_ok());
+
+        let source = r#"
+            fn max<T>(a: T, b: T) -> T {
+                return a
+            }
+            fn name() -> String {
+                return 1
+            }
+            contract Box {
+                fn put<T>(item: T) {
+                }
+            }
+            fn main() {
+                let m = max(1, "one")
+                let s = max("a", "b") - 1
+            }
+        "#;
+        let program = Parser::new(Lexer::new(source)).parse().unwrap();
+        let errors = TypeChecker::new().check(&program).unwrap_err();
+        let expected = [
+            "'name' is declared to return String but returns Int",
+            "contract method 'put' cannot have type parameters",
+            "type parameter 'T' of 'max' is String here but Int in an earlier argument",
+            "arithmetic operator requires two Int or two Float operands (% takes Int only), got String and Int",
+        ];
+        assert_eq!(errors.len(), expected.len(), "{:?}", errors);
+        for text in expected {
+            assert!(errors.iter().any(|e| e.message.contains(text)), "missing '{}' in {:?}", text, errors);
+        }
+    }
+
+    #[test]
+    fn test_trait_methods_dispatch_on_the_receiver_type() {
+        use crate::typechecker::TypeChecker;
+
+        let source = r#"
+            trait Describe {
+                fn describe(self) -> String
+                fn scaled(self, by) -> Int
+            }
+            impl Describe for Int {
+                fn describe(self) -> String {
+                    return "number"
+                }
+                fn scaled(self, by) -> Int {
+                    return self * by
+                }
+            }
+            impl Describe for String {
+                fn describe(self) -> String {
+                    return "text: " + self
+                }
+                fn scaled(self, by) -> Int {
+                    return by
+                }
+            }
+            fn main() {
+                let n = 6
+                let s = "hi"
+                return (n.describe(), s.describe(), n.scaled(7))
+            }
+        "#;
+        let program = Parser::new(Lexer::new(source)).parse().unwrap();
+        let checked = TypeChecker::new().check(&program);
+        assert!(checked.is_ok(), "{:?}", checked);
+        let mut interpreter = Interpreter::new();
+        interpreter.load(program).unwrap();
+        let described: (String, String, i64) = interpreter.call_serde("main", &()).unwrap();
+        assert_eq!(described, ("number".to_string(), "text: hi".to_string(), 42));
+
+        let source = r#"
+            trait Describe {
+                fn describe(self) -> String
+                fn twice(x)
+            }
+            impl Describe for Bool {
+                fn describe(self, extra) -> String {
+                    return "bool"
+                }
+                fn other(self) {
+                }
+            }
+            impl Missing for Int {
+            }
+            impl Describe for Contract {
+            }
+            fn main() {
+                let n = 1
+                let d = n.describe()
+            }
+        "#;
+        let program = Parser::new(Lexer::new(source)).parse().unwrap();
+        let errors = TypeChecker::new().check(&program).unwrap_err();
+        let expected = [
+            "method 'twice' of trait 'Describe' must take self",
+            "method 'describe' takes 1 parameters in trait 'Describe' but 2 here",
+            "method 'other' is not part of trait 'Describe'",
+            "impl of 'Describe' for Bool is missing method 'twice'",
+            "unknown trait 'Missing'",
+            "cannot implement 'Describe' for 'Contract'",
+            "no method 'describe' for Int",
+        ];
+        assert_eq!(errors.len(), expected.len(), "{:?}", errors);
+        for text in expected {
+            assert!(errors.iter().any(|e| e.message.contains(text)), "missing '{}' in {:?}", text, errors);
+        }
+
+        let mut parser = Parser::new(Lexer::new("trait T {\n    fn f(self) {\n    }\n}\n"));
+        assert!(parser.parse().unwrap_err().message.contains("Trait method 'f' cannot have a body"));
+    }
+
+    #[test]
+    fn test_option_and_result_with_match_and_try() {
+        use crate::typechecker::TypeChecker;
+
+        let source = r#"
+            fn half(n) {
+                if n % 2 == 0 { return Some(n / 2) }
+                return None
+            }
+            fn quarter(n) {
+                let h = half(n)?
+                return half(h)
+            }
+            fn checked(n) {
+                if n < 0 { return Err("negative") }
+                return Ok(n)
+            }
+            fn describe(n) {
+                let v = checked(n)?
+                match quarter(v) {
+                    Some(q) => return Ok(q)
+                    None => return Ok(0 - 1)
+                }
+            }
+            fn main() {
+                return (describe(8), describe(6), describe(0 - 3), quarter(8))
+            }
+        "#;
+        let program = Parser::new(Lexer::new(source)).parse().unwrap();
+        let checked = TypeChecker::new().check(&program);
+        assert!(checked.is_ok(), "{:?}", checked);
+        let mut interpreter = Interpreter::new();
+        interpreter.load(program).unwrap();
+        type Described = (Result<i64, String>, Result<i64, String>, Result<i64, String>, Option<i64>);
+        let described: Described = interpreter.call_serde("main", &()).unwrap();
+        assert_eq!(described, (Ok(2), Ok(-1), Err("negative".to_string()), Some(2)));
+
+        let source = r#"
+            fn plain(n: Int) {
+                let x = n?
+                return x
+            }
+            fn mixed() {
+                let x = Some(1)?
+                return Ok(x)
+            }
+            fn partial() {
+                match Some(1) {
+                    Some(x) => print(x)
+                }
+            }
+            fn mismatched() {
+                match Ok(1) {
+                    Some(x) => print(x),
+                    _ => print(0),
+                }
+            }
+        "#;
+        let program = Parser::new(Lexer::new(source)).parse().unwrap();
+        let errors = TypeChecker::new().check(&program).unwrap_err();
+        let expected = [
+            "'?' needs an Option or Result, got Int",
+            "'?' in function 'mixed' returning Result<Int, Unknown> cannot propagate Option<Int>",
+            "match does not cover None",
+            "pattern 'Some(x)' never matches Result<Int, Unknown>",
+        ];
+        assert_eq!(errors.len(), expected.len(), "{:?}", errors);
+        for text in expected {
+            assert!(errors.iter().any(|e| e.message.contains(text)), "missing '{}' in {:?}", text, errors);
+        }
+    }
+
+    #[test]
+    fn test_web3_sign_and_verify_typed_data() {
+        use crate::typechecker::TypeChecker;
+
+        let key = format!("0x{}", crate::logs::hex(&crate::logs::keccak256(b"cow")));
+        let source = r#"
+            fn main() {
+                let domain = ("Ether Mail", "1", 1, "0xCcCCccccCCCCcCCCCCCcCcCccCcCCCcCcccccccC")
+                let types = "Mail(Person from,Person to,string contents)Person(string name,address wallet)"
+                let cow = "0xCD2a3d9F938E13CD947Ec05AbC7FE734Df8DD826"
+                let mail = ("Cow", cow, "Bob", "0xbBbBBBBbbBBBbbbBbbBbbbbBBbBbbbbBbBbbBBbB", "Hello, Bob!")
+                let signature = web3.sign_typed(domain, types, mail, "KEY")
+                let forged = ("Cow", cow, "Bob", "0xbBbBBBBbbBBBbbbBbbBbbbbBBbBbbbbBbBbbBBbB", "Pay Eve")
+                return (signature, web3.verify_typed(domain, types, mail, signature, cow), web3.verify_typed(domain, types, forged, signature, cow))
+            }
+        "#
+        .replace("KEY", &key);
+        let program = Parser::new(Lexer::new(&source)).parse().unwrap();
+        let checked = TypeChecker::new().check(&program);
+        assert!(checked.is_ok(), "{:?}", checked);
+        let mut interpreter = Interpreter::new();
+        interpreter.load(program).unwrap();
+        let (signature, genuine, forged): (String, bool, bool) = interpreter.call_serde("main", &()).unwrap();
+        assert!(signature.starts_with("0x4355c47d63924e8a72e509b65029052eb6c299d53a04e167c5775fd466751c9d"));
+        assert!(genuine && !forged);
+
+        let source = r#"
+            fn main() {
+                return web3.sign_typed(("App", "1"), "Order(Asset asset)", (1, 2))
+            }
+        "#;
+        let program = Parser::new(Lexer::new(source)).parse().unwrap();
+        let errors = TypeChecker::new().check(&program).unwrap_err();
+        assert!(errors[0].message.contains("field 'asset' of 'Order' has unknown type 'Asset'"), "{:?}", errors);
+    }
+
+    #[test]
+    fn test_web3_logs_decode_into_maps() {
+        use crate::logs::{PageError, RawLog};
+
+        struct OneTransfer;
+        impl LogSource for OneTransfer {
+            fn id(&self) -> String {
+                "one-transfer".to_string()
+            }
+            fn get_logs(&self, filter: &LogFilter) -> Result<Vec<RawLog>, PageError> {
+                Ok(vec![RawLog {
+                    block_number: 7,
+                    transaction_hash: "0x01".to_string(),
+                    log_index: 0,
+                    topics: vec![filter.topic.clone(), format!("0x{:0>64}", "aa"), format!("0x{:0>64}", "bb")],
+                    data: format!("0x{:0>64}", "64"),
+                }])
+            }
+        }
+
+        let source = r#"
+            fn transfers() {
+                let transfers = web3.logs("0x00000000000000000000000000000000000000c0", "Transfer(address indexed from, address indexed to, uint256 value)", 0, 100)?
+                return Ok(transfers)
+            }
+
+            fn main() {
+                match transfers() {
+                    Ok(transfers) => {
+                        if len(transfers) != 1 { panic("count") }
+                        for t in transfers {
+                            if t["block_number"] != 7 { panic("block") }
+                            print(t["missing"])
+                        }
+                    }
+                    Err(message) => panic(message)
+                }
+            }
+        "#;
+        let program = Parser::new(Lexer::new(source)).parse().unwrap();
+        let checked = crate::typechecker::TypeChecker::new().check(&program);
+        assert!(checked.is_ok(), "{:?}", checked);
+
+        let mut interpreter = Interpreter::new();
+        interpreter.set_log_source(Box::new(OneTransfer), None);
+        assert_eq!(interpreter.run(program), Err("no field 'missing' in map".to_string()));
+    }
+
+    #[test]
+    fn test_web3_multicall_reads_in_order() {
+        // A node without Multicall3, answering each balanceOf with its holder
+        struct Balances;
+        impl CallSource for Balances {
+            fn call(&self, _to: &str, _data: &str) -> Result<String, String> {
+                Ok("0x".to_string())
+            }
+            fn batch(&self, calls: &[(String, String)]) -> Result<Vec<Result<String, String>>, String> {
+                Ok(calls.iter().map(|(_, data)| Ok(format!("0x{}", &data[10..]))).collect())
+            }
+        }
+
+        let source = r#"
+            fn main() {
+                let token = "0x00000000000000000000000000000000000000c0"
+                let results = web3.multicall([
+                    (token, "balanceOf(address) returns (uint256)", "0x0000000000000000000000000000000000000007"),
+                    (token, "balanceOf(address) returns (uint256)", "0x0000000000000000000000000000000000000009"),
+                ])
+                match results {
+                    Ok(results) => {
+                        for result in results {
+                            match result {
+                                Ok(balance) => print(balance),
+                                Err(reason) => panic(reason),
+                            }
+                        }
+                    }
+                    Err(message) => panic(message),
+                }
+                web3.multicall([(token, "balanceOf(address)")])
+            }
+        "#;
+        let program = Parser::new(Lexer::new(source)).parse().unwrap();
+        let checked = crate::typechecker::TypeChecker::new().check(&program);
+        assert!(checked.is_ok(), "{:?}", checked);
+
+        let mut interpreter = Interpreter::new();
+        interpreter.set_call_source(Box::new(Balances));
+        assert_eq!(
+            interpreter.run(program),
+            Err("cannot encode the arguments of balanceOf: expected 1 arguments, got 0".to_string())
+        );
+    }
+
+    #[test]
+    fn test_ipfs_add_get_and_cid_validation() {
+        use crate::typechecker::TypeChecker;
+        use std::cell::RefCell;
+
+        // One block kept in memory under its raw CID
+        #[derive(Default)]
+        struct Memory(RefCell<Vec<(String, Vec<u8>)>>);
+        impl IpfsStore for Memory {
+            fn add(&self, data: &[u8]) -> Result<String, String> {
+                let cid = Cid::raw(data).to_string();
+                self.0.borrow_mut().push((cid.clone(), data.to_vec()));
+                Ok(cid)
+            }
+            fn get(&self, cid: &Cid) -> Result<Vec<u8>, String> {
+                let blocks = self.0.borrow();
+                blocks.iter().find(|(c, _)| *c == cid.to_string()).map(|(_, d)| d.clone()).ok_or("not pinned".to_string())
+            }
+        }
+
+        let source = r#"
+            fn publish() {
+                let metadata = "{\"name\": \"Astro #1\"}"
+                let cid = ipfs.add(metadata)?
+                if ipfs.valid(cid) {} else { panic("invalid") }
+                let stored = ipfs.get(cid)?
+                return Ok(stored)
+            }
+
+            fn main() {
+                match publish() {
+                    Ok(stored) => print(stored),
+                    Err(message) => panic(message),
+                }
+                if ipfs.valid("Qm123") { panic("valid") }
+                match ipfs.get(ipfs.cid("never added")) {
+                    Ok(_) => panic("found"),
+                    Err(message) => panic(message),
+                }
+            }
+        "#;
+        let program = Parser::new(Lexer::new(source)).parse().unwrap();
+        let checked = TypeChecker::new().check(&program);
+        assert!(checked.is_ok(), "{:?}", checked);
+
+        let mut interpreter = Interpreter::new();
+        interpreter.set_ipfs_store(Box::new(Memory::default()));
+        assert_eq!(interpreter.run(program), Err("Panic: not pinned".to_string()));
+
+        let program = Parser::new(Lexer::new("fn main() {\n    ipfs.get(\"bafy!\")\n}\n")).parse().unwrap();
+        let errors = TypeChecker::new().check(&program).unwrap_err();
+        assert_eq!(errors[0].code, Some("E0246"));
+    }
+
+    #[test]
+    fn test_forked_accounts_in_tests() {
+        // A chain where every account holds 5 and has code
+        struct Mainnet;
+        impl StateSource for Mainnet {
+            fn chain_id(&self) -> Result<i64, String> {
+                Ok(10)
+            }
+            fn balance(&self, _address: &str) -> Result<u128, String> {
+                Ok(5)
+            }
+            fn code(&self, _address: &str) -> Result<String, String> {
+                Ok("0x6080".to_string())
+            }
+            fn storage(&self, _address: &str, _slot: &str) -> Result<String, String> {
+                Ok("0x1".to_string())
+            }
+        }
+
+        let source = r#"
+            #[test]
+            fn reads_the_fork() {
+                let pool = "0x00000000000000000000000000000000000000aa"
+                if chain.id != 10 { panic("chain") }
+                if len(web3.code(pool)) != 6 { panic("code") }
+                web3.store(pool, 0, 2)
+                print(web3.storage(pool, 0))
+                web3.deal(pool, 1)
+            }
+
+            #[test]
+            fn rejects_bad_slots() {
+                let pool = "0x00000000000000000000000000000000000000aa"
+                print(web3.balance(pool))
+                web3.storage(pool, "0xzz")
+            }
+        "#;
+        let program = Parser::new(Lexer::new(source)).parse().unwrap();
+        let checked = crate::typechecker::TypeChecker::new().check(&program);
+        assert!(checked.is_ok(), "{:?}", checked);
+
+        let mut interpreter = Interpreter::new();
+        interpreter.set_fork(Box::new(Mainnet)).unwrap();
+        let results = interpreter.run_tests(program).unwrap();
+        assert_eq!(results[0], ("reads_the_fork".to_string(), Ok(())));
+        let failure = results[1].1.as_ref().unwrap_err();
+        assert!(failure.starts_with("'0xzz' is not a hex word"), "{}", failure);
+    }
+
+    #[test]
+    fn test_to_string_concatenates_mixed_values() {
+        use crate::typechecker::TypeChecker;
+
+        let source = "fn main() {\n    let count = 3\n    let ready = true\n    return \"count: \" + to_string(count) + \", \" + to_string(ready)\n}\n";
+        let program = Parser::new(Lexer::new(source)).parse().unwrap();
+        assert!(TypeChecker::new().check(&program).is_ok());
+        let mut interpreter = Interpreter::new();
+        interpreter.load(program).unwrap();
+        assert_eq!(interpreter.call_values("main", vec![]), Ok(Value::String("count: 3, true".to_string())));
+
+        let program = Parser::new(Lexer::new("fn main() {\n    let count = 3\n    let label = \"count: \" + count\n}\n")).parse().unwrap();
+        let errors = TypeChecker::new().check(&program).unwrap_err();
+        assert_eq!(errors[0].code, Some("E0257"));
+        assert!(errors[0].message.contains("convert the Int to a String"), "{}", errors[0].message);
+    }
+
+    #[test]
+    fn test_address_u256_and_ai_result_types() {
+        use crate::typechecker::TypeChecker;
+
+        let source = "fn main() {\n    let owner = \"0x0000000000000000000000000000000000000000\"\n    let is_owner = msg.sender == owner\n    let paid = msg.value >= 0\n    let result = ai.infer(\"sentiment\", \"great\")\n    let label = result.label\n    return (is_owner, paid, label, result.score)\n}\n";
+        let program = Parser::new(Lexer::new(source)).parse().unwrap();
+        assert!(TypeChecker::new().check(&program).is_ok());
+        let mut interpreter = Interpreter::new();
+        interpreter.load(program).unwrap();
+        let Ok(Value::Tuple(values)) = interpreter.call_values("main", vec![]) else { panic!() };
+        assert_eq!(values[..3], [Value::Bool(true), Value::Bool(true), Value::String("positive".to_string())]);
+        assert!(matches!(values[3], Value::Float(score) if score > 0.5));
+
+        let source = "fn main() {\n    let same = msg.sender == 1\n    let sent = transfer(msg.sender, \"all\")\n    let result = ai.infer(\"sentiment\", \"ok\")\n    let confidence = result.confidence\n}\n";
+        let program = Parser::new(Lexer::new(source)).parse().unwrap();
+        let codes: Vec<_> = TypeChecker::new().check(&program).unwrap_err().iter().map(|e| e.code).collect();
+        assert_eq!(codes, vec![Some("E0258"), Some("E0260"), Some("E0261")]);
+    }
+
+    #[test]
+    fn test_arrays_are_shared_and_change_in_place() {
+        let source = r#"
+            fn add_fee(items) {
+                items.push(1)
+            }
+            fn main() {
+                let cart = [5, 10]
+                add_fee(cart)
+                if len(cart) != 3 { panic("push not seen by the caller") }
+                let saved = cart.copy()
+                cart.set(0, 7)
+                if saved != [5, 10, 1] { panic("copy shares items") }
+                if cart != [7, 10, 1] { panic("set not applied") }
+                if cart.pop() != Some(1) { panic("wrong pop") }
+                let inner = [cart]
+                cart.push(inner)
+            }
+        "#;
+        assert_eq!(run_source(source), Err(messages::render("E0482", &[])));
+
+        // A reverted transaction undoes in-place changes to state too
+        let source = r#"
+            contract Queue {
+                state items
+                constructor() {
+                    items = []
+                }
+                fn add(v) {
+                    items.push(v)
+                    require(v > 0, "empty")
+                    return len(items)
+                }
+            }
+        "#;
+        let mut interpreter = Interpreter::new();
+        interpreter.load(Parser::new(Lexer::new(source)).parse().unwrap()).unwrap();
+        interpreter.transact("Queue", "constructor", vec![], "0xaa", 0).unwrap();
+        assert!(interpreter.transact("Queue", "add", vec![Value::Number(0)], "0xaa", 0).is_err());
+        assert_eq!(interpreter.transact("Queue", "add", vec![Value::Number(3)], "0xaa", 0), Ok(Value::Number(1)));
+    }
+
+    #[test]
+    fn test_conversions_between_numbers_and_text() {
+        use crate::typechecker::TypeChecker;
+
+        let source = r#"
+            fn main() {
+                let count = 7
+                if count as Float != 7.0 { panic("widen") }
+                if 3.9 as Int + 1 != 4 { panic("truncate") }
+                if int(" 42 ") + 1 != 43 { panic("parse") }
+                if str(12) + "!" != "12!" { panic("format") }
+                int("4x")
+            }
+        "#;
+        let program = Parser::new(Lexer::new(source)).parse().unwrap();
+        assert!(TypeChecker::new().check(&program).is_ok());
+        assert_eq!(run_source(source), Err(messages::render("E0484", &[&"4x"])));
+
+        let program = Parser::new(Lexer::new("fn main() {\n    let a = \"5\" as Int\n    let b = 5 as String\n    let c = int(true)\n}\n")).parse().unwrap();
+        let codes: Vec<_> = TypeChecker::new().check(&program).unwrap_err().iter().map(|e| e.code).collect();
+        assert_eq!(codes, vec![Some("E0263"), Some("E0263"), Some("E0264")]);
+    }
+
+    #[test]
+    fn test_float_arithmetic_and_ordering() {
+        use crate::typechecker::TypeChecker;
+
+        let source = r#"
+            fn area(radius: Float) {
+                return radius * radius * 3.5
+            }
+            fn main() {
+                let total = area(2.0) + 1.5 - 0.5
+                if total != 15.0 { panic("arithmetic") }
+                if total / 4.0 < 3.75 || 0.2 <= 0.1 || 2.0 > 2.5 || 1.0 >= 1.5 { panic("ordering") }
+                if 1.0 / 0.0 < total { panic("infinity") }
+                panic("done")
+            }
+        "#;
+        let program = Parser::new(Lexer::new(source)).parse().unwrap();
+        assert!(TypeChecker::new().check(&program).is_ok());
+        assert_eq!(run_source(source), Err("Panic: done".to_string()));
+
+        // Nothing mixes Int with Float, and % is for Ints alone
+        let program = Parser::new(Lexer::new("fn main() {\n    let a = 1 + 2.5\n    let b = 2.5 % 1.0\n    let c = 2.5 < 3\n}\n")).parse().unwrap();
+        let codes: Vec<_> = TypeChecker::new().check(&program).unwrap_err().iter().map(|e| e.code).collect();
+        assert_eq!(codes, vec![Some("E0209"), Some("E0210"), Some("E0211")]);
+    }
+
+    #[test]
+    fn test_type_aliases_stand_for_their_types() {
+        use crate::typechecker::TypeChecker;
+
+        let source = r#"
+            type Count = Int
+            type Ratio = Float
+            type Pair = (Count, String)
+            type Handler = fn(String) -> String
+            trait Describe {
+                fn describe(self) -> String
+            }
+            impl Describe for Count {
+                fn describe(self) -> String {
+                    return "count"
+                }
+            }
+            fn tally(items: Count) -> Pair {
+                return (items, "items")
+            }
+            contract Vault {
+                fn deposit(amount: Wei) {
+                }
+            }
+            type Wei = U256
+            fn main() {
+                let n = 3
+                let (count, label) = tally(n)
+                if count as Ratio != 3.0 { panic("cast") }
+                return n.describe() + " of " + label
+            }
+        "#;
+        let program = Parser::new(Lexer::new(source)).parse().unwrap();
+        let mut checker = TypeChecker::new();
+        assert!(checker.check(&program).is_ok());
+        let abi = crate::abi::contract_abis(&program, checker.functions()).unwrap();
+        assert_eq!(abi[0].entries[0]["inputs"][0]["type"], "uint256");
+        let mut interpreter = Interpreter::new();
+        interpreter.load(program).unwrap();
+        let described: String = interpreter.call_serde("main", &()).unwrap();
+        assert_eq!(described, "count of items");
+
+        let source = "type Int = U256\ntype Ring = Option<Ring>\ntype Count = Int\nfn half(n: Int) -> Count {\n    return \"half\"\n}\n";
+        let program = Parser::new(Lexer::new(source)).parse().unwrap();
+        let codes: Vec<_> = TypeChecker::new().check(&program).unwrap_err().iter().map(|e| e.code).collect();
+        assert_eq!(codes, vec![Some("E0266"), Some("E0267"), Some("E0229")]);
+    }
+
+    #[test]
+    fn test_contextual_words_are_told_apart_by_lookahead() {
+        // `match` and `defer` start statements only when a value follows;
+        // otherwise they are names like any other
+        let source = r#"
+            fn match(n) {
+                return n + 1
+            }
+            fn main() {
+                let mut defer = 1
+                defer = defer + match(1)
+                let mut picked = 0
+                match (Some(defer)) {
+                    Some(n) => picked = n,
+                    None => picked = 0 - 1,
+                }
+                if picked != 3 { panic("match") }
+                match(defer)
+            }
+        "#;
+        let mut interpreter = Interpreter::new();
+        interpreter.load(Parser::new(Lexer::new(source)).parse().unwrap()).unwrap();
+        let value: i64 = interpreter.call_serde("main", &()).unwrap();
+        assert_eq!(value, 4);
+    }
+
+    #[test]
+    fn test_functions_are_values_of_function_type() {
+        use crate::typechecker::TypeChecker;
+
+        let source = r#"
+            type Step = fn(Int) -> Int
+            fn double(n: Int) -> Int {
+                return n * 2
+            }
+            fn twice(f: Step, x: Int) -> Int {
+                return f(f(x))
+            }
+            fn odd(n: Int) -> Bool {
+                return n % 2 == 1
+            }
+            fn main() {
+                let step = double
+                if step(5) != 10 { panic("local") }
+                if twice(double, 3) != 12 { panic("callback") }
+                if collect(filter(range(0, 6), odd)) != [1, 3, 5] { panic("filter") }
+                if type(step) != "function" { panic("type") }
+            }
+        "#;
+        let program = Parser::new(Lexer::new(source)).parse().unwrap();
+        assert!(TypeChecker::new().check(&program).is_ok());
+        assert!(run_source(source).is_ok());
+
+        let source = "fn odd(n: Int) -> Bool {\n    return n % 2 == 1\n}\nfn apply(f: fn(Int) -> Int, x: Int) -> Int {\n    return f(x)\n}\nfn main() {\n    apply(odd, 1)\n    let f = odd\n    f(\"one\")\n    map(range(0, 3), apply)\n}\n";
+        let program = Parser::new(Lexer::new(source)).parse().unwrap();
+        let errors = TypeChecker::new().check(&program).unwrap_err();
+        let codes: Vec<_> = errors.iter().map(|e| e.code).collect();
+        assert_eq!(codes, vec![Some("E0208"), Some("E0208"), Some("E0265")]);
+        assert!(errors[0].message.contains("fn(Int) -> Bool"), "{}", errors[0].message);
+    }
+
+    #[test]
+    fn test_iterators_work_out_items_as_loops_reach_them() {
+        use crate::typechecker::TypeChecker;
+
+        // Materializing any of these ranges would run out of memory
+        let source = r#"
+            fn square(n) {
+                return n * n
+            }
+            fn is_odd(n) {
+                return n % 2 == 1
+            }
+            fn first_odd_square_over(limit) {
+                for x in filter(map(range(0, 1000000000000), square), is_odd) {
+                    if x > limit {
+                        return x
+                    }
+                }
+                return 0
+            }
+            fn main() {
+                if first_odd_square_over(50) != 81 { panic("pipeline") }
+                if len(range(0, 1000000000000)[5..10]) != 5 { panic("range slice") }
+
+                let squares = map(range(1, 4), square)
+                if collect(squares) != [1, 4, 9] { panic("collect") }
+                let mut total = 0
+                for x in squares {
+                    total = total + x
+                }
+                for x in squares {
+                    total = total + x
+                }
+                if total != 28 { panic("looping again starts over") }
+                if collect(filter([1, 2, 3], is_odd)) != [1, 3] { panic("array source") }
+            }
+        "#;
+        let program = Parser::new(Lexer::new(source)).parse().unwrap();
+        assert!(TypeChecker::new().check(&program).is_ok());
+        assert!(run_source(source).is_ok());
+
+        let source = "fn label(n) {\n    return \"x\"\n}\nfn main() {\n    collect(filter(range(0, 3), label))\n}\n";
+        assert_eq!(run_source(source), Err(messages::render("E0486", &[])));
+
+        let program = Parser::new(Lexer::new("fn main() {\n    let a = map(range(0, 3), 2)\n    let b = range(\"a\", 3)\n}\n")).parse().unwrap();
+        let codes: Vec<_> = TypeChecker::new().check(&program).unwrap_err().iter().map(|e| e.code).collect();
+        assert_eq!(codes, vec![Some("E0265"), Some("E0222")]);
+    }
+
+    #[test]
+    fn test_strings_grow_in_place() {
+        use crate::typechecker::TypeChecker;
+
+        let source = r#"
+            fn sign(out) {
+                out.append(" -- end")
+            }
+            fn main() {
+                let mut report = "rows:"
+                let copy = report
+                let mut i = 0
+                while i < 3 {
+                    report = report + " " + str(i)
+                    i = i + 1
+                }
+                if report != "rows: 0 1 2" { panic("append") }
+                if copy != "rows:" { panic("copy changed") }
+
+                let builder = StringBuilder()
+                builder.append(report)
+                sign(builder)
+                if builder.to_string() != "rows: 0 1 2 -- end" { panic("builder") }
+                if len(builder) != 18 { panic("length") }
+                report = report + 1
+            }
+        "#;
+        let program = Parser::new(Lexer::new(source)).parse().unwrap();
+        let codes: Vec<_> = TypeChecker::new().check(&program).unwrap_err().iter().map(|e| e.code).collect();
+        assert_eq!(codes, vec![Some("E0257")]); // The final `+ 1`
+        assert_eq!(run_source(source), Err(messages::render("E0408", &[])));
+    }
+
+    #[test]
+    fn test_signals_stop_the_run_and_call_shutdown_handlers() {
+        let source = r#"
+            fn flush_log() {
+                panic("flushed")
+            }
+            fn close() {
+                panic("closed")
+            }
+            fn main() {
+                on_shutdown(flush_log)
+                on_shutdown(close)
+                let mut ticks = 0
+                while true {
+                    ticks = ticks + 1
+                }
+            }
+        "#;
+        let program = Parser::new(Lexer::new(source)).parse().unwrap();
+        let mut interpreter = Interpreter::new();
+        let stop = interpreter.stop_handle();
+        let raiser = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            stop.raise(shutdown::SIGINT);
+        });
+
+        // Handlers run last-registered first; the first to fail is reported
+        let result = interpreter.run(program);
+        raiser.join().unwrap();
+        assert!(result.as_ref().is_err_and(|e| e.contains("closed")), "{:?}", result);
+        assert_eq!(interpreter.interrupted(), Some(shutdown::SIGINT));
+        assert_eq!(interpreter.stop_handle().received(), None, "cleared for the next run");
+
+        let program = Parser::new(Lexer::new("fn main() {\n    on_shutdown(1)\n}\n")).parse().unwrap();
+        let errors = crate::typechecker::TypeChecker::new().check(&program).unwrap_err();
+        assert_eq!(errors[0].code, Some("E0268"));
     }
 }
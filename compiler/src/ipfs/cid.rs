@@ -0,0 +1,232 @@
+// Content identifiers, checked at compile time and verified against fetched
+// content at run time (see the parent module).
+
+use std::fmt;
+
+/// Multicodec of a raw block: the content's bytes, unwrapped
+pub const RAW: u64 = 0x55;
+
+/// Multicodec of a UnixFS node, which `ipfs add` makes by default
+pub const DAG_PB: u64 = 0x70;
+
+/// Multihash code of sha2-256
+pub const SHA2_256: u64 = 0x12;
+
+const BASE32: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+const BASE58: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// A parsed CID: `Qm...` (version 0) or a multibase string such as
+/// `bafy...` (version 1)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cid {
+    pub version: u64,
+    pub codec: u64,     // What the content is: RAW, DAG_PB, ...
+    pub hash_code: u64, // How it was hashed: SHA2_256, ...
+    pub digest: Vec<u8>,
+}
+
+impl Cid {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        if text.len() == 46 && text.starts_with("Qm") {
+            // Version 0: a bare base58 sha2-256 multihash of a UnixFS node
+            let bytes = base58_decode(text)?;
+            let (hash_code, digest) = multihash(&bytes)?;
+            return Ok(Cid { version: 0, codec: DAG_PB, hash_code, digest });
+        }
+
+        let mut chars = text.chars();
+        let bytes = match chars.next() {
+            Some('b') => base32_decode(chars.as_str())?,
+            Some('B') => base32_decode(&chars.as_str().to_lowercase())?,
+            Some('z') => base58_decode(chars.as_str())?,
+            Some('f') => hex_decode(chars.as_str())?,
+            Some(other) => return Err(format!("unsupported multibase prefix '{}'", other)),
+            None => return Err("empty CID".to_string()),
+        };
+        let mut rest = bytes.as_slice();
+        let version = varint(&mut rest)?;
+        if version != 1 {
+            return Err(format!("unsupported CID version {}", version));
+        }
+        let codec = varint(&mut rest)?;
+        let (hash_code, digest) = multihash(rest)?;
+        Ok(Cid { version, codec, hash_code, digest })
+    }
+
+    /// The version 1 CID of `bytes` stored as one raw block
+    pub fn raw(bytes: &[u8]) -> Self {
+        use sha2::{Digest, Sha256};
+        Cid { version: 1, codec: RAW, hash_code: SHA2_256, digest: Sha256::digest(bytes).to_vec() }
+    }
+
+    /// Whether `content` is what this CID names. Only raw sha2-256 blocks
+    /// can be checked without decoding UnixFS; others answer None.
+    pub fn verify(&self, content: &[u8]) -> Option<bool> {
+        (self.codec == RAW && self.hash_code == SHA2_256).then(|| *self == Cid::raw(content))
+    }
+}
+
+impl fmt::Display for Cid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut multihash = Vec::new();
+        put_varint(&mut multihash, self.hash_code);
+        put_varint(&mut multihash, self.digest.len() as u64);
+        multihash.extend(&self.digest);
+        if self.version == 0 {
+            return write!(f, "{}", base58_encode(&multihash));
+        }
+        let mut bytes = Vec::new();
+        put_varint(&mut bytes, self.version);
+        put_varint(&mut bytes, self.codec);
+        bytes.extend(multihash);
+        write!(f, "b{}", base32_encode(&bytes))
+    }
+}
+
+/// A multihash's code and digest, which must fill the rest of the bytes
+fn multihash(bytes: &[u8]) -> Result<(u64, Vec<u8>), String> {
+    let mut rest = bytes;
+    let code = varint(&mut rest)?;
+    let length = varint(&mut rest)?;
+    if rest.len() as u64 != length {
+        return Err(format!("digest is {} bytes, expected {}", rest.len(), length));
+    }
+    if code == SHA2_256 && length != 32 {
+        return Err("sha2-256 digest must be 32 bytes".to_string());
+    }
+    Ok((code, rest.to_vec()))
+}
+
+/// An unsigned LEB128 varint, advancing past it
+fn varint(bytes: &mut &[u8]) -> Result<u64, String> {
+    let mut value = 0u64;
+    for (i, byte) in bytes.iter().enumerate().take(9) {
+        value |= u64::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            *bytes = &bytes[i + 1..];
+            return Ok(value);
+        }
+    }
+    Err("truncated varint".to_string())
+}
+
+fn put_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn base32_decode(text: &str) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    let (mut buffer, mut bits) = (0u32, 0);
+    for c in text.bytes() {
+        let digit = BASE32.iter().position(|d| *d == c).ok_or_else(|| format!("'{}' is not base32", c as char))?;
+        buffer = (buffer << 5) | digit as u32;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    Ok(out)
+}
+
+fn base32_encode(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    let (mut buffer, mut bits) = (0u32, 0);
+    for byte in bytes {
+        buffer = (buffer << 8) | u32::from(*byte);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(BASE32[(buffer >> bits) as usize & 31] as char);
+        }
+        buffer &= (1 << bits) - 1;
+    }
+    if bits > 0 {
+        out.push(BASE32[(buffer << (5 - bits)) as usize & 31] as char);
+    }
+    out
+}
+
+fn base58_decode(text: &str) -> Result<Vec<u8>, String> {
+    // Little-endian base-256 digits of the number, then one zero byte per
+    // leading '1'
+    let mut number: Vec<u8> = Vec::new();
+    for c in text.bytes() {
+        let mut carry = BASE58.iter().position(|d| *d == c).ok_or_else(|| format!("'{}' is not base58", c as char))? as u32;
+        for digit in number.iter_mut() {
+            carry += u32::from(*digit) * 58;
+            *digit = carry as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            number.push(carry as u8);
+            carry >>= 8;
+        }
+    }
+    let zeros = text.bytes().take_while(|c| *c == b'1').count();
+    Ok(std::iter::repeat_n(0, zeros).chain(number.into_iter().rev()).collect())
+}
+
+fn base58_encode(bytes: &[u8]) -> String {
+    let mut number: Vec<u8> = Vec::new(); // Little-endian base-58 digits
+    for byte in bytes {
+        let mut carry = u32::from(*byte);
+        for digit in number.iter_mut() {
+            carry += u32::from(*digit) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            number.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+    let zeros = bytes.iter().take_while(|b| **b == 0).count();
+    std::iter::repeat_n('1', zeros).chain(number.iter().rev().map(|d| BASE58[*d as usize] as char)).collect()
+}
+
+fn hex_decode(text: &str) -> Result<Vec<u8>, String> {
+    if !text.len().is_multiple_of(2) {
+        return Err("odd-length base16".to_string());
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&text[i..i + 2], 16).map_err(|_| format!("'{}' is not base16", text)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cids_parse_and_print_back() {
+        let empty = Cid::raw(b"");
+        assert_eq!(empty.to_string(), "bafkreihdwdcefgh4dqkjv67uzcmw7ojee6xedzdetojuzjevtenxquvyku");
+        assert_eq!(Cid::parse(&empty.to_string()), Ok(empty.clone()));
+        assert_eq!(empty.verify(b""), Some(true));
+        assert_eq!(empty.verify(b"tampered"), Some(false));
+
+        let v0 = Cid::parse("QmPZ9gcCEpqKTo6aq61g2nXGUhM4iCL3ewB6LDXZCtioEB").unwrap();
+        assert_eq!((v0.version, v0.codec, v0.hash_code), (0, DAG_PB, SHA2_256));
+        assert_eq!(v0.to_string(), "QmPZ9gcCEpqKTo6aq61g2nXGUhM4iCL3ewB6LDXZCtioEB");
+        assert_eq!(v0.verify(b"anything"), None);
+
+        // The same CID in base16 and uppercase base32
+        let hex: String = std::iter::once("f01551220".to_string())
+            .chain(empty.digest.iter().map(|b| format!("{:02x}", b)))
+            .collect();
+        assert_eq!(Cid::parse(&hex), Ok(empty.clone()));
+        assert_eq!(Cid::parse(&empty.to_string().to_uppercase()), Ok(empty));
+
+        assert!(Cid::parse("Qm123").is_err());
+        assert!(Cid::parse("bafy!").is_err());
+        assert!(Cid::parse("bafkreihdwdcefgh4dqkjv67uzcmw7ojee6xedzdetojuzjevtenxquv").is_err());
+        assert!(Cid::parse("").is_err());
+    }
+}
@@ -0,0 +1,165 @@
+use crate::messages;
+
+pub mod cid;
+pub use cid::Cid;
+
+// Content-addressed storage for off-chain scripts.
+//
+// `ipfs.add(data)` stores a string and returns its CID; `ipfs.get(cid)`
+// fetches it back. Both return a Result, Err holding why the store could
+// not be reached, so NFT metadata can be pinned and read back in one
+// script:
+//
+//   let cid = ipfs.add(metadata)?
+//   print(ipfs.get(cid)?)
+//
+// `ipfs.valid(text)` tells whether a string is a CID, and `ipfs.cid(data)`
+// computes the CID of a string without storing it.
+//
+// Content is added to the Kubo RPC API at ASTRIXA_IPFS_API (a local node,
+// or a pinning service that speaks it), as CIDv1 raw blocks so that small
+// content's CID is `ipfs.cid(data)`. ASTRIXA_IPFS_TOKEN, when set, is sent
+// as the Authorization header. Content is read from the gateway at
+// ASTRIXA_IPFS_GATEWAY, https://ipfs.io by default. A gateway is trusted to
+// serve what it is asked for, so content named by a raw sha2-256 CID is
+// checked against it before it is returned.
+
+/// Gateway content is read from when ASTRIXA_IPFS_GATEWAY is unset
+pub const DEFAULT_GATEWAY: &str = "https://ipfs.io";
+
+/// Largest content `get` reads, so a bad gateway cannot exhaust memory
+pub const MAX_CONTENT: u64 = 16 * 1024 * 1024;
+
+/// Where content is stored: an IPFS node or service, or a fixture in tests
+pub trait IpfsStore {
+    /// Store `data`, returning the CID the store gave it. Errors are
+    /// messages for the script.
+    fn add(&self, data: &[u8]) -> Result<String, String>;
+
+    /// The content named by `cid`
+    fn get(&self, cid: &Cid) -> Result<Vec<u8>, String>;
+}
+
+/// A Kubo RPC endpoint to add through and an HTTP gateway to read from
+#[cfg(not(target_arch = "wasm32"))]
+pub struct HttpIpfsStore {
+    pub api: Option<String>,
+    pub gateway: String,
+    pub token: Option<String>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl HttpIpfsStore {
+    /// The store named by ASTRIXA_IPFS_API, ASTRIXA_IPFS_GATEWAY and
+    /// ASTRIXA_IPFS_TOKEN
+    pub fn from_env() -> Self {
+        HttpIpfsStore {
+            api: std::env::var("ASTRIXA_IPFS_API").ok(),
+            gateway: std::env::var("ASTRIXA_IPFS_GATEWAY").unwrap_or_else(|_| DEFAULT_GATEWAY.to_string()),
+            token: std::env::var("ASTRIXA_IPFS_TOKEN").ok(),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl IpfsStore for HttpIpfsStore {
+    fn add(&self, data: &[u8]) -> Result<String, String> {
+        const BOUNDARY: &str = "astrixa-ipfs-boundary";
+        let failed = |e: &dyn std::fmt::Display| messages::render("E0461", &[&e]);
+        let api = self.api.as_deref().ok_or_else(|| messages::render("E0460", &[]))?;
+        let url = format!("{}/api/v0/add?cid-version=1&raw-leaves=true&pin=true", api.trim_end_matches('/'));
+
+        let mut body = format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"data\"\r\nContent-Type: application/octet-stream\r\n\r\n",
+            BOUNDARY
+        )
+        .into_bytes();
+        body.extend(data);
+        body.extend(format!("\r\n--{}--\r\n", BOUNDARY).into_bytes());
+
+        let mut request = ureq::post(&url).set("Content-Type", &format!("multipart/form-data; boundary={}", BOUNDARY));
+        if let Some(token) = &self.token {
+            request = request.set("Authorization", token);
+        }
+        let text = request.send_bytes(&body).map_err(|e| failed(&e))?.into_string().map_err(|e| failed(&e))?;
+        let response: serde_json::Value = serde_json::from_str(&text).map_err(|e| failed(&e))?;
+        response["Hash"].as_str().map(str::to_string).ok_or_else(|| failed(&"no Hash in response"))
+    }
+
+    fn get(&self, cid: &Cid) -> Result<Vec<u8>, String> {
+        use std::io::Read;
+        let url = format!("{}/ipfs/{}", self.gateway.trim_end_matches('/'), cid);
+        let failed = |e: &dyn std::fmt::Display| messages::render("E0461", &[&e]);
+        let mut content = Vec::new();
+        ureq::get(&url)
+            .call()
+            .map_err(|e| failed(&e))?
+            .into_reader()
+            .take(MAX_CONTENT + 1)
+            .read_to_end(&mut content)
+            .map_err(|e| failed(&e))?;
+        if content.len() as u64 > MAX_CONTENT {
+            return Err(failed(&format!("content is larger than {} bytes", MAX_CONTENT)));
+        }
+        Ok(content)
+    }
+}
+
+/// Add `data`, checking that the store answered with a CID
+pub fn add(store: &dyn IpfsStore, data: &[u8]) -> Result<String, String> {
+    let cid = store.add(data)?;
+    Cid::parse(&cid).map_err(|e| messages::render("E0461", &[&format!("store returned '{}': {}", cid, e)]))?;
+    Ok(cid)
+}
+
+/// The content named by `cid`, refused if it is not what the CID names
+pub fn get(store: &dyn IpfsStore, cid: &str) -> Result<Vec<u8>, String> {
+    let parsed = Cid::parse(cid).map_err(|e| messages::render("E0459", &[&cid, &e]))?;
+    let content = store.get(&parsed)?;
+    if parsed.verify(&content) == Some(false) {
+        return Err(messages::render("E0461", &[&format!("content does not match {}", cid)]));
+    }
+    Ok(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+
+    /// Content kept in memory under raw CIDs, optionally corrupted on read
+    struct Fixture {
+        blocks: RefCell<HashMap<String, Vec<u8>>>,
+        tamper: bool,
+    }
+
+    impl IpfsStore for Fixture {
+        fn add(&self, data: &[u8]) -> Result<String, String> {
+            let cid = Cid::raw(data).to_string();
+            self.blocks.borrow_mut().insert(cid.clone(), data.to_vec());
+            Ok(cid)
+        }
+
+        fn get(&self, cid: &Cid) -> Result<Vec<u8>, String> {
+            let mut content = self.blocks.borrow().get(&cid.to_string()).cloned().ok_or("not found")?;
+            if self.tamper {
+                content.push(b'!');
+            }
+            Ok(content)
+        }
+    }
+
+    #[test]
+    fn test_add_then_get_verifies_content() {
+        let mut store = Fixture { blocks: RefCell::new(HashMap::new()), tamper: false };
+        let cid = add(&store, b"{\"name\": \"Astro #1\"}").unwrap();
+        assert_eq!(cid, Cid::raw(b"{\"name\": \"Astro #1\"}").to_string());
+        assert_eq!(get(&store, &cid).unwrap(), b"{\"name\": \"Astro #1\"}");
+
+        assert!(get(&store, "not-a-cid").unwrap_err().contains("not-a-cid"));
+
+        store.tamper = true;
+        assert!(get(&store, &cid).unwrap_err().contains("does not match"));
+    }
+}
@@ -1,3 +1,5 @@
+use crate::ast::{Attribute, Span};
+
 /// ASTRIXA Intermediate Representation (IR)
 /// 
 /// The IR is a simplified, linear representation of the program that is:
@@ -8,7 +10,8 @@
 /// Design principles:
 /// - Stack-based (easy to translate to bytecode/WASM)
 /// - Explicit operations (no hidden conversions)
-/// - Type-erased (types are checked before lowering)
+/// - Untyped instructions: types are checked before lowering, and what
+///   kind of value each instruction works on is inferred (see `kinds`)
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum IRInstr {
@@ -23,6 +26,10 @@ pub enum IRInstr {
     StoreVar(String),     // Store variable by name (legacy)
     LoadLocal(u32),       // Load from local slot (new for Step 42)
     StoreLocal(u32),      // Store to local slot (new for Step 42)
+    LoadGlobal(String),   // Load module-level global (contract state)
+    LoadProperty(String), // Load a host-provided value: "msg.sender"
+    LoadFunction(String), // Load a reference to a module function, to call later
+    StoreGlobal(String),  // Store module-level global (contract state)
     
     // Arithmetic
     Add,
@@ -30,6 +37,13 @@ pub enum IRInstr {
     Mul,
     Div,
     Mod,
+    CheckedAdd(Span),      // Add, Sub and Mul trapping on Int overflow rather than wrapping,
+    CheckedSub(Span),      // with where the expression was written for the message
+    CheckedMul(Span),
+    
+    // Conversion
+    FloatToInt,            // Truncated toward zero, saturating at the Int range
+    IntToFloat,
     
     // Comparison
     Eq,
@@ -52,8 +66,10 @@ pub enum IRInstr {
     CallAI(String),        // STEP 52: Call AI function (runtime-provided AI)
     CallWeb3(String),      // STEP 53: Call Web3 function (runtime-provided Web3)
     CallFS(String),        // STEP 54: Call file system function (runtime-provided FS)
+    CallIndirect(usize),   // Call the function reference on top of the stack; arg count, the args below it
     Return,
     Panic,                 // STEP 48: Panic - abort execution with error
+    Revert,                // Contract failure: undo the transaction's state changes, with a reason
     
     // Stack manipulation
     Pop,
@@ -61,14 +77,49 @@ pub enum IRInstr {
     
     // Special
     Nop,                   // No operation
+    Line(usize, usize),    // Where the code after it comes from: a source line and column, for debug info
+}
+
+/// What a value is at run time, which each backend maps to its own types
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueKind {
+    Int,   // 64-bit, signed
+    Float, // 64-bit
+    Bool,
+    Str,   // A pointer and a length: two values on a WASM stack
+    Ref,   // Anything else the host hands out, such as an address
 }
 
 #[derive(Debug, Clone)]
 pub struct IRFunction {
     pub name: String,
     pub param_count: usize,  // STEP 46: Number of parameters
+    pub param_kinds: Vec<Option<ValueKind>>, // From the annotations or defaults; None takes what calls pass
     pub instructions: Vec<IRInstr>,
     pub local_count: usize,  // Number of local variables
+    pub local_names: Vec<String>, // Each slot's name in the source, params first; slots added by passes have none
+    pub result_count: usize, // Values returned: more than 1 for tuples
+    pub attributes: Vec<Attribute>, // From the source declaration, e.g. #[inline]
+    pub span: Span,                 // The source declaration
+    pub unsupported: Vec<Unsupported>, // What lowering could not express, trapping in its place
+    pub is_async: bool,             // Declared `async fn`; WASM runs it eagerly, the VM not at all
+    pub arithmetic: Vec<Span>,      // Where each Add, Sub and Mul was written, in order, for checked builds
+}
+
+/// A construct lowering has no IR for yet, and where it was written.
+/// Lowering traps in its place so the IR stays well-formed, but a build
+/// refuses a module that has any (see `lowering::lower_for_build`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unsupported {
+    pub construct: &'static str, // Its `E0501.<construct>` catalog entry
+    pub span: Span,
+}
+
+impl Unsupported {
+    /// What the construct is called, for E0501 to name it
+    pub fn name(&self) -> String {
+        crate::messages::render(&format!("E0501.{}", self.construct), &[])
+    }
 }
 
 impl IRFunction {
@@ -76,25 +127,72 @@ impl IRFunction {
         Self {
             name,
             param_count: 0,  // STEP 46: Initialize to 0
+            param_kinds: Vec::new(),
             instructions: Vec::new(),
             local_count: 0,
+            local_names: Vec::new(),
+            result_count: 1,
+            attributes: Vec::new(),
+            span: Span::default(),
+            unsupported: Vec::new(),
+            is_async: false,
+            arithmetic: Vec::new(),
         }
     }
+
+    pub fn has_attribute(&self, attribute: Attribute) -> bool {
+        self.attributes.contains(&attribute)
+    }
     
     pub fn add_instruction(&mut self, instr: IRInstr) {
         self.instructions.push(instr);
     }
 }
 
+/// A contract's entry points, which contract backends export: the
+/// functions lowered from its constructor and methods, and its state
+#[derive(Debug, Clone)]
+pub struct IRContract {
+    pub name: String,
+    pub state: Vec<String>,          // Globals, named `Contract.var`
+    pub constructor: Option<String>, // Functions, named `Contract.method`
+    pub methods: Vec<String>,
+}
+
+/// A module's linear memory, in 64 KiB pages: what it starts with, which
+/// its static data has to fit, and how far the string heap may grow it
+/// (as far as the runtime allows without a maximum)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Memory {
+    pub initial: u32,
+    pub maximum: Option<u32>,
+}
+
+impl Memory {
+    pub const PAGE: usize = 65536;
+}
+
+impl Default for Memory {
+    fn default() -> Self {
+        Memory { initial: 1, maximum: None }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct IRModule {
     pub functions: Vec<IRFunction>,
+    pub globals: Vec<String>,  // Module-level mutable globals (contract state)
+    pub contracts: Vec<IRContract>,
+    pub memory: Memory,
 }
 
 impl IRModule {
     pub fn new() -> Self {
         Self {
             functions: Vec::new(),
+            globals: Vec::new(),
+            contracts: Vec::new(),
+            memory: Memory::default(),
         }
     }
     
@@ -102,7 +200,32 @@ impl IRModule {
         self.functions.push(function);
     }
     
+    pub fn add_global(&mut self, name: String) {
+        if !self.globals.contains(&name) {
+            self.globals.push(name);
+        }
+    }
+    
     pub fn find_function(&self, name: &str) -> Option<&IRFunction> {
         self.functions.iter().find(|f| f.name == name)
     }
+    
+    /// What its functions could not express, with the function each is in
+    pub fn unsupported(&self) -> impl Iterator<Item = (&IRFunction, &Unsupported)> {
+        self.functions.iter().flat_map(|f| f.unsupported.iter().map(move |u| (f, u)))
+    }
+    
+    /// The functions loaded as values, in the order first loaded: a
+    /// reference to one is its index here
+    pub fn function_table(&self) -> Vec<String> {
+        let mut table: Vec<String> = Vec::new();
+        for instr in self.functions.iter().flat_map(|f| &f.instructions) {
+            if let IRInstr::LoadFunction(name) = instr {
+                if !table.contains(name) {
+                    table.push(name.clone());
+                }
+            }
+        }
+        table
+    }
 }
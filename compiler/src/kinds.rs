@@ -0,0 +1,318 @@
+use std::collections::HashMap;
+
+use crate::ir::{IRFunction, IRInstr, IRModule, ValueKind};
+use crate::types::Type;
+
+// Value kinds: what each local, result and stack value of a function is.
+//
+// IR instructions are untyped (`Add` adds whatever is on the stack), but a
+// backend needs to know: an Int is an i64 in WASM, a Float an f64 and a
+// String a pointer and a length. The kinds come from where values start,
+// constants, parameter annotations and host functions, and follow them
+// through the stack, locals and calls. A parameter with neither an
+// annotation nor a default takes what the first call to its function
+// passes.
+
+/// The kinds of one function's values
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FunctionKinds {
+    pub params: Vec<ValueKind>,
+    pub results: Vec<ValueKind>,
+    pub locals: Vec<ValueKind>,      // One per slot, parameters first
+    pub stacks: Vec<Vec<ValueKind>>, // The stack before each instruction, bottom first
+    pub indirect: HashMap<usize, Signature>, // What each indirect call, by instruction, passes and gets back
+}
+
+/// The kinds a function takes and returns
+pub type Signature = (Vec<ValueKind>, Vec<ValueKind>);
+
+/// The kinds in each of `module`'s functions, by name. A function returns
+/// what its first `return` does, and an untyped parameter takes what its
+/// first call passes; calls see both, so this repeats until neither
+/// changes. Indirect calls see the signatures of the functions in the
+/// module's table.
+pub fn infer_module(module: &IRModule) -> HashMap<String, FunctionKinds> {
+    let mut results: HashMap<String, Vec<ValueKind>> = HashMap::new();
+    let mut passed: HashMap<String, Vec<ValueKind>> = HashMap::new();
+    let mut kinds: HashMap<String, FunctionKinds> = HashMap::new();
+    let table: Vec<&IRFunction> = module.function_table().iter().filter_map(|name| module.find_function(name)).collect();
+    // Each round settles at least one more function's parameters or results
+    for _ in 0..=2 * module.functions.len() {
+        let signatures: Vec<Signature> = table
+            .iter()
+            .map(|func| (params(func, passed.get(&func.name)), results.get(&func.name).cloned().unwrap_or(vec![ValueKind::Int])))
+            .collect();
+        kinds = module
+            .functions
+            .iter()
+            .map(|func| (func.name.clone(), infer_with(func, params(func, passed.get(&func.name)), &results, &signatures)))
+            .collect();
+        let settled: HashMap<String, Vec<ValueKind>> =
+            kinds.iter().map(|(name, kinds)| (name.clone(), kinds.results.clone())).collect();
+        let calls = first_calls(module, &kinds);
+        if settled == results && calls == passed {
+            break;
+        }
+        results = settled;
+        passed = calls;
+    }
+    kinds
+}
+
+/// What the first direct call to each function passes it
+fn first_calls(module: &IRModule, kinds: &HashMap<String, FunctionKinds>) -> HashMap<String, Vec<ValueKind>> {
+    let mut passed = HashMap::new();
+    for func in &module.functions {
+        for (instr, stack) in func.instructions.iter().zip(&kinds[&func.name].stacks) {
+            if let IRInstr::Call(name, arg_count) = instr {
+                let args = stack[stack.len().saturating_sub(*arg_count)..].to_vec();
+                passed.entry(name.clone()).or_insert(args);
+            }
+        }
+    }
+    passed
+}
+
+/// The kinds in `func`, given what the functions it calls return and the
+/// signatures of those in the table
+pub fn infer(func: &IRFunction, results: &HashMap<String, Vec<ValueKind>>, table: &[Signature]) -> FunctionKinds {
+    infer_with(func, params(func, None), results, table)
+}
+
+fn infer_with(func: &IRFunction, params: Vec<ValueKind>, results: &HashMap<String, Vec<ValueKind>>, table: &[Signature]) -> FunctionKinds {
+    let mut locals: Vec<Option<ValueKind>> = params.iter().copied().map(Some).collect();
+    locals.resize(func.local_count.max(func.param_count), None);
+
+    // Twice, so that a loop's loads see the stores after them
+    let mut kinds = FunctionKinds { params, ..FunctionKinds::default() };
+    for _ in 0..2 {
+        kinds.stacks.clear();
+        let mut returned = None;
+        let mut stack: Vec<ValueKind> = Vec::new();
+        let mut reachable = true;
+        let mut ahead: HashMap<usize, Vec<ValueKind>> = HashMap::new(); // Stacks at jump targets
+        for (i, instr) in func.instructions.iter().enumerate() {
+            if let Some(at_target) = ahead.get(&i).filter(|_| !reachable) {
+                stack = at_target.clone();
+            } else if !reachable {
+                stack.clear();
+            }
+            reachable = true;
+            kinds.stacks.push(stack.clone());
+
+            let pop = |stack: &mut Vec<ValueKind>, n: usize| {
+                let mut popped: Vec<ValueKind> = (0..n).map(|_| stack.pop().unwrap_or(ValueKind::Int)).collect();
+                popped.reverse();
+                popped
+            };
+            match instr {
+                IRInstr::LoadConstInt(_) => stack.push(ValueKind::Int),
+                IRInstr::LoadConstFloat(_) => stack.push(ValueKind::Float),
+                IRInstr::LoadConstBool(_) => stack.push(ValueKind::Bool),
+                IRInstr::LoadConstString(_) => stack.push(ValueKind::Str),
+                IRInstr::LoadVar(_) | IRInstr::LoadGlobal(_) => stack.push(ValueKind::Int),
+                IRInstr::LoadFunction(_) => stack.push(ValueKind::Ref),
+                IRInstr::StoreVar(_) | IRInstr::StoreGlobal(_) | IRInstr::Pop => {
+                    pop(&mut stack, 1);
+                }
+                IRInstr::LoadLocal(slot) => {
+                    stack.push(locals.get(*slot as usize).copied().flatten().unwrap_or(ValueKind::Int));
+                }
+                IRInstr::StoreLocal(slot) => {
+                    let kind = pop(&mut stack, 1)[0];
+                    let slot = *slot as usize;
+                    if slot >= locals.len() {
+                        locals.resize(slot + 1, None);
+                    }
+                    locals[slot].get_or_insert(kind);
+                }
+                IRInstr::LoadProperty(name) => {
+                    let property = name.split_once('.').and_then(|(object, name)| crate::stdlib::get_property_info(object, name));
+                    stack.extend(property.map(|p| of_type(&p.returns)).unwrap_or(vec![ValueKind::Ref]));
+                }
+                IRInstr::Add | IRInstr::Sub | IRInstr::Mul | IRInstr::Div | IRInstr::Mod
+                | IRInstr::CheckedAdd(_) | IRInstr::CheckedSub(_) | IRInstr::CheckedMul(_) => {
+                    let operands = pop(&mut stack, 2);
+                    stack.push(arithmetic(operands[0], operands[1]));
+                }
+                IRInstr::Eq | IRInstr::Ne | IRInstr::Lt | IRInstr::Le | IRInstr::Gt | IRInstr::Ge
+                | IRInstr::And | IRInstr::Or => {
+                    pop(&mut stack, 2);
+                    stack.push(ValueKind::Bool);
+                }
+                IRInstr::Not => {
+                    pop(&mut stack, 1);
+                    stack.push(ValueKind::Bool);
+                }
+                IRInstr::FloatToInt => {
+                    pop(&mut stack, 1);
+                    stack.push(ValueKind::Int);
+                }
+                IRInstr::IntToFloat => {
+                    pop(&mut stack, 1);
+                    stack.push(ValueKind::Float);
+                }
+                IRInstr::Jump(target) => {
+                    if *target > i {
+                        ahead.entry(*target).or_insert_with(|| stack.clone());
+                    }
+                    reachable = false;
+                }
+                IRInstr::JumpIfFalse(target) => {
+                    pop(&mut stack, 1);
+                    if *target > i {
+                        ahead.entry(*target).or_insert_with(|| stack.clone());
+                    }
+                }
+                IRInstr::Call(name, arg_count) => {
+                    pop(&mut stack, *arg_count);
+                    stack.extend(results.get(name).cloned().unwrap_or(vec![ValueKind::Int]));
+                }
+                IRInstr::CallIndirect(arg_count) => {
+                    pop(&mut stack, 1);
+                    let signature = callee(table, &pop(&mut stack, *arg_count));
+                    stack.extend(&signature.1);
+                    kinds.indirect.insert(i, signature);
+                }
+                IRInstr::CallStd(name) | IRInstr::CallAI(name) | IRInstr::CallWeb3(name) | IRInstr::CallFS(name) => {
+                    let (params, results) = host_signature(name);
+                    pop(&mut stack, params.len());
+                    stack.extend(results);
+                }
+                IRInstr::Return => {
+                    let count = func.result_count.min(stack.len());
+                    returned.get_or_insert_with(|| stack[stack.len() - count..].to_vec());
+                    reachable = false;
+                }
+                IRInstr::Panic | IRInstr::Revert => {
+                    pop(&mut stack, 1);
+                    reachable = false;
+                }
+                IRInstr::Dup => {
+                    let kind = pop(&mut stack, 1)[0];
+                    stack.extend([kind, kind]);
+                }
+                IRInstr::Nop | IRInstr::Line(..) => {}
+            }
+        }
+        let mut returned = returned.unwrap_or_default();
+        returned.resize(func.result_count, ValueKind::Int);
+        kinds.results = returned;
+    }
+    kinds.locals = locals.into_iter().map(|kind| kind.unwrap_or(ValueKind::Int)).collect();
+    kinds
+}
+
+/// The kinds `func` takes: as annotated or defaulted, else as `passed`
+/// by a call, else Int
+fn params(func: &IRFunction, passed: Option<&Vec<ValueKind>>) -> Vec<ValueKind> {
+    (0..func.param_count)
+        .map(|i| {
+            let typed = func.param_kinds.get(i).copied().flatten();
+            typed.or_else(|| passed?.get(i).copied()).unwrap_or(ValueKind::Int)
+        })
+        .collect()
+}
+
+/// The signature an indirect call passing `args` is made with: that of the
+/// first function in the table taking them, or failing that as many
+/// values. With neither, nothing in the table can be called with them;
+/// the call traps, and is typed as returning an Int.
+fn callee(table: &[Signature], args: &[ValueKind]) -> Signature {
+    table
+        .iter()
+        .find(|(params, _)| params == args)
+        .or_else(|| table.iter().find(|(params, _)| params.len() == args.len()))
+        .cloned()
+        .unwrap_or_else(|| (args.to_vec(), vec![ValueKind::Int]))
+}
+
+/// The kind arithmetic on `a` and `b` gives: Float if either is, a String
+/// when adding two (concatenation), else Int
+pub fn arithmetic(a: ValueKind, b: ValueKind) -> ValueKind {
+    match (a, b) {
+        (ValueKind::Float, _) | (_, ValueKind::Float) => ValueKind::Float,
+        (ValueKind::Str, ValueKind::Str) => ValueKind::Str,
+        _ => ValueKind::Int,
+    }
+}
+
+/// The kinds a value of type `ty` is made of: none for Void, one each for
+/// a tuple's items and two for a range's bounds
+pub fn of_type(ty: &Type) -> Vec<ValueKind> {
+    match ty {
+        Type::Int | Type::U256 => vec![ValueKind::Int],
+        Type::Float => vec![ValueKind::Float],
+        Type::Bool => vec![ValueKind::Bool],
+        Type::String => vec![ValueKind::Str],
+        Type::Void => Vec::new(),
+        Type::Tuple(items) => items.iter().flat_map(of_type).collect(),
+        Type::Range => vec![ValueKind::Int, ValueKind::Int],
+        _ => vec![ValueKind::Ref],
+    }
+}
+
+/// What a host function takes and returns. Standard library functions
+/// have their registry signatures, with a value of any type printed or
+/// measured as a String. The host writes out only the host values `str`
+/// is called on; the rest convert in the module (see `strings`).
+pub fn host_signature(name: &str) -> (Vec<ValueKind>, Vec<ValueKind>) {
+    use ValueKind::*;
+    let info = crate::stdlib::get_stdlib_info(name);
+    let results = info.map(|info| of_type(&info.returns)).unwrap_or_default();
+    match name {
+        "print" | "println" | "len" | "int" | "panic" | "revert" | "on_shutdown" => (vec![Str], results),
+        "to_string" | "str" => (vec![Ref], results),
+        "str.concat" => (vec![Str, Str], vec![Str]),
+        "str.eq" => (vec![Str, Str], vec![Bool]),
+        "str.substr" => (vec![Str, Int, Int], vec![Str]),
+        "str.from_float" => (vec![Float], vec![Str]),
+        "web3.wallet" => (vec![], vec![Ref]),
+        "web3.sign" | "web3.keccak" => (vec![Str], vec![Str]),
+        "web3.verify" => (vec![Str, Str], vec![Bool]),
+        "web3.balance" => (vec![Ref], vec![Int]),
+        "web3.send" => (vec![Ref, Int], vec![Bool]),
+        _ => match info {
+            Some(info) => (info.params.iter().map(|ty| of_type(ty).first().copied().unwrap_or(Ref)).collect(), results),
+            None => (vec![Ref], Vec::new()), // Unknown: one value in, nothing out
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kinds_follow_values_through_locals_and_calls() {
+        let source = r#"
+            fn scale(x: Float) {
+                return x * 2.5
+            }
+            fn shout(word, mark = "!") {
+                return word + mark
+            }
+            fn main() {
+                let big = 5000000000
+                let label = "total"
+                print(shout(label))
+                return scale(2.0) > 1.0
+            }
+        "#;
+        let stmts = crate::parser::Parser::new(crate::lexer::Lexer::new(source))
+            .parse()
+            .unwrap();
+        // What the compiler would lower: a program that type-checks
+        crate::typechecker::TypeChecker::new().check(&stmts).unwrap();
+        let kinds = infer_module(&crate::lowering::lower(&stmts));
+
+        assert_eq!(kinds["scale"].params, [ValueKind::Float]);
+        assert_eq!(kinds["scale"].results, [ValueKind::Float]);
+        assert_eq!(kinds["main"].locals, [ValueKind::Int, ValueKind::Str]);
+        assert_eq!(kinds["main"].results, [ValueKind::Bool]);
+        // An untyped parameter takes what its call passes, and one with a
+        // default its default's kind
+        assert_eq!(kinds["shout"].params, [ValueKind::Str, ValueKind::Str]);
+        assert_eq!(kinds["shout"].results, [ValueKind::Str]);
+    }
+}
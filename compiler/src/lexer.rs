@@ -1,10 +1,15 @@
-use crate::token::Token;
+use crate::error::CompileError;
+use crate::text::{is_ident_continue, is_ident_start};
+use crate::token::{Token, KEYWORDS};
 
 pub struct Lexer {
     input: Vec<char>,
     position: usize,
     pub line: usize,
     pub column: usize,
+    pub token_line: usize,   // Where the most recent token started
+    pub token_column: usize,
+    errors: Vec<CompileError>, // Malformed literals, reported by the parser
 }
 
 impl Lexer {
@@ -14,11 +19,30 @@ impl Lexer {
             position: 0,
             line: 1,
             column: 1,
+            token_line: 1,
+            token_column: 1,
+            errors: Vec::new(),
         }
     }
 
+    /// The malformed literals seen so far, in source order. The lexer
+    /// keeps going after one so every one of them is reported.
+    pub fn take_errors(&mut self) -> Vec<CompileError> {
+        std::mem::take(&mut self.errors)
+    }
+
+    fn report(&mut self, error: CompileError) {
+        self.errors.push(error);
+    }
+
+    fn peek(&self, offset: usize) -> Option<char> {
+        self.input.get(self.position + offset).copied()
+    }
+
     pub fn next_token(&mut self) -> Token {
         self.skip_whitespace();
+        self.token_line = self.line;
+        self.token_column = self.column;
 
         if self.position >= self.input.len() {
             return Token::EOF;
@@ -31,21 +55,53 @@ impl Lexer {
             ')' => self.simple(Token::RParen),
             '{' => self.simple(Token::LBrace),
             '}' => self.simple(Token::RBrace),
+            '[' => self.simple(Token::LBracket),
+            ']' => self.simple(Token::RBracket),
             '+' => self.simple(Token::Plus),
+            '-' if self.peek(1) == Some('>') => {
+                self.advance();
+                self.simple(Token::Arrow)
+            }
             '-' => self.simple(Token::Minus),
             '*' => self.simple(Token::Star),
             '/' => self.simple(Token::Slash),
             '%' => self.simple(Token::Percent),
+            ':' if self.peek(1) == Some(':') => {
+                self.advance();
+                self.simple(Token::PathSep)
+            }
             ':' => self.simple(Token::Colon),
+            ';' => self.simple(Token::Semicolon),
             ',' => self.simple(Token::Comma),
+            '?' => self.simple(Token::Question),
+            '.' if self.peek(1) == Some('.') => {
+                self.advance();
+                self.simple(Token::DotDot)
+            }
             '.' => self.simple(Token::Dot),  // STEP 49: Module access
+            '#' => self.simple(Token::Hash),
             '=' => self.peek_two_char_op(),
             '!' => self.peek_not_equal(),
+            '&' if self.peek(1) == Some('&') => {
+                self.advance();
+                self.simple(Token::AndAnd)
+            }
+            '|' if self.peek(1) == Some('|') => {
+                self.advance();
+                self.simple(Token::OrOr)
+            }
             '<' => self.peek_less(),
             '>' => self.peek_greater(),
             '"' => self.read_string(),
-            _ if ch.is_numeric() => self.read_number(),
-            _ => self.read_identifier(),
+            'r' if self.is_raw_string_start() => self.read_raw_string(),
+            _ if ch.is_ascii_digit() => self.read_number(),
+            _ if is_ident_start(ch) => self.read_identifier(),
+            _ => {
+                // Skip the character so lexing can go on past it
+                self.report(CompileError::coded("E0152", &[&ch], self.line, self.column));
+                self.advance();
+                self.next_token()
+            }
         }
     }
 
@@ -89,28 +145,17 @@ impl Lexer {
     fn read_identifier(&mut self) -> Token {
         let start = self.position;
 
-        while self.position < self.input.len()
-            && (self.input[self.position].is_alphanumeric() || self.input[self.position] == '_')
-        {
+        while self.peek(0).is_some_and(is_ident_continue) {
             self.advance();
         }
 
         let text: String = self.input[start..self.position].iter().collect();
 
-        match text.as_str() {
-            "fn" => Token::Fn,
-            "let" => Token::Let,
-            "return" => Token::Return,
-            "if" => Token::If,
-            "else" => Token::Else,
-            "while" => Token::While,
-            "panic" => Token::Panic,  // STEP 48: Panic keyword
-            "import" => Token::Import, // STEP 49: Import keyword
-            "export" => Token::Export, // STEP 49: Export keyword
-            "true" => Token::True,
-            "false" => Token::False,
-            _ => Token::Identifier(text),
-        }
+        KEYWORDS
+            .iter()
+            .find(|(keyword, _)| *keyword == text)
+            .map(|(_, token)| token.clone())
+            .unwrap_or(Token::Identifier(text))
     }
 
     fn peek_two_char_op(&mut self) -> Token {
@@ -118,6 +163,9 @@ impl Lexer {
             self.advance();
             self.advance();
             Token::EqualEqual
+        } else if self.peek(1) == Some('>') {
+            self.advance();
+            self.simple(Token::FatArrow)
         } else {
             self.simple(Token::Assign)
         }
@@ -159,69 +207,287 @@ impl Lexer {
         let mut is_float = false;
 
         // Read integer part
-        while self.position < self.input.len() && self.input[self.position].is_numeric() {
+        while self.position < self.input.len() && self.input[self.position].is_ascii_digit() {
             self.advance();
         }
 
         // Check for decimal point
         if self.position < self.input.len() && self.input[self.position] == '.' {
             // Look ahead to ensure it's a float, not module access
-            if self.position + 1 < self.input.len() && self.input[self.position + 1].is_numeric() {
+            if self.position + 1 < self.input.len() && self.input[self.position + 1].is_ascii_digit() {
                 is_float = true;
                 self.advance(); // consume '.'
                 
                 // Read fractional part
-                while self.position < self.input.len() && self.input[self.position].is_numeric() {
+                while self.position < self.input.len() && self.input[self.position].is_ascii_digit() {
                     self.advance();
                 }
             }
         }
 
+        // A name can't start with a digit: `1abc` is one malformed token,
+        // not a number and a name
+        if self.peek(0).is_some_and(is_ident_start) {
+            while self.peek(0).is_some_and(is_ident_continue) {
+                self.advance();
+            }
+            let text: String = self.input[start..self.position].iter().collect();
+            self.report(CompileError::coded("E0170", &[&text], self.token_line, self.token_column));
+            return Token::Identifier(text);
+        }
+
         let text: String = self.input[start..self.position].iter().collect();
-        
+
         if is_float {
             if let Ok(num) = text.parse::<f64>() {
                 Token::Float(num)
             } else {
                 Token::Identifier(text)
             }
+        } else if let Ok(num) = text.parse::<i64>() {
+            Token::Number(num)
         } else {
-            if let Ok(num) = text.parse::<i64>() {
-                Token::Number(num)
-            } else {
-                Token::Identifier(text)
-            }
+            self.report(CompileError::coded("E0171", &[&text, &i64::MAX], self.token_line, self.token_column));
+            Token::Number(0)
         }
     }
 
     fn read_string(&mut self) -> Token {
+        let (start_line, start_column) = (self.line, self.column);
         self.advance(); // consume opening quote
         let mut result = String::new();
 
-        while self.position < self.input.len() && self.input[self.position] != '"' {
-            if self.input[self.position] == '\\' && self.position + 1 < self.input.len() {
-                self.advance();
-                match self.input[self.position] {
-                    'n' => result.push('\n'),
-                    'r' => result.push('\r'),
-                    't' => result.push('\t'),
-                    '\\' => result.push('\\'),
-                    '"' => result.push('"'),
-                    _ => {
-                        result.push('\\');
-                        result.push(self.input[self.position]);
+        loop {
+            match self.peek(0) {
+                None => {
+                    self.report(CompileError::coded("E0143", &[], start_line, start_column));
+                    break;
+                }
+                Some('"') => {
+                    self.advance(); // consume closing quote
+                    break;
+                }
+                Some('\\') => {
+                    if let Some(ch) = self.read_escape() {
+                        result.push(ch);
                     }
                 }
-            } else {
-                result.push(self.input[self.position]);
+                Some(ch) => {
+                    result.push(ch);
+                    self.advance();
+                }
+            }
+        }
+
+        Token::String(result)
+    }
+
+    /// Read one escape sequence starting at the backslash. Malformed
+    /// escapes are reported and skipped.
+    fn read_escape(&mut self) -> Option<char> {
+        let (line, column) = (self.line, self.column);
+        self.advance(); // consume '\'
+        let ch = self.peek(0)?;
+        self.advance();
+
+        match ch {
+            'n' => Some('\n'),
+            'r' => Some('\r'),
+            't' => Some('\t'),
+            '0' => Some('\0'),
+            '\\' => Some('\\'),
+            '"' => Some('"'),
+            '\'' => Some('\''),
+            'x' => self.read_hex_escape(line, column),
+            'u' => self.read_unicode_escape(line, column),
+            _ => {
+                self.report(CompileError::coded("E0140", &[&ch], line, column));
+                None
+            }
+        }
+    }
+
+    /// The digits of `\xHH`: an ASCII byte
+    fn read_hex_escape(&mut self, line: usize, column: usize) -> Option<char> {
+        let digits: String = (0..2).filter_map(|i| self.peek(i)).collect();
+        match u8::from_str_radix(&digits, 16) {
+            Ok(byte) if digits.len() == 2 && byte.is_ascii() => {
+                self.advance();
+                self.advance();
+                Some(byte as char)
             }
+            _ => {
+                self.report(CompileError::coded("E0141", &[&digits], line, column));
+                None
+            }
+        }
+    }
+
+    /// The braces of `\u{H..H}`: a Unicode scalar value, 1 to 6 hex digits
+    fn read_unicode_escape(&mut self, line: usize, column: usize) -> Option<char> {
+        let mut digits = String::new();
+        let mut closed = false;
+        if self.peek(0) == Some('{') {
+            self.advance();
+            while let Some(c) = self.peek(0).filter(|c| c.is_ascii_hexdigit()) {
+                digits.push(c);
+                self.advance();
+            }
+            closed = self.peek(0) == Some('}');
+            if closed {
+                self.advance();
+            }
+        }
+
+        let value = u32::from_str_radix(&digits, 16).ok().filter(|_| closed && digits.len() <= 6);
+        let ch = value.and_then(char::from_u32);
+        if ch.is_none() {
+            self.report(CompileError::coded("E0142", &[&digits], line, column));
+        }
+        ch
+    }
+
+    /// `r"` or `r#"`, `r##"`, ... opening a raw string
+    fn is_raw_string_start(&self) -> bool {
+        let hashes = (1..).take_while(|&i| self.peek(i) == Some('#')).count();
+        self.peek(hashes + 1) == Some('"')
+    }
+
+    /// A raw string: no escapes, and it ends at the first `"` followed by
+    /// as many `#` as it opened with, so `r#"{"a": 1}"#` holds quotes
+    fn read_raw_string(&mut self) -> Token {
+        let (start_line, start_column) = (self.line, self.column);
+        self.advance(); // consume 'r'
+        let mut hashes = 0;
+        while self.peek(0) == Some('#') {
+            hashes += 1;
             self.advance();
         }
+        self.advance(); // consume opening quote
 
-        if self.position < self.input.len() && self.input[self.position] == '"' {
-            self.advance(); // consume closing quote
+        let mut result = String::new();
+        loop {
+            match self.peek(0) {
+                None => {
+                    self.report(CompileError::coded("E0143", &[], start_line, start_column));
+                    break;
+                }
+                Some('"') if (1..=hashes).all(|i| self.peek(i) == Some('#')) => {
+                    for _ in 0..=hashes {
+                        self.advance();
+                    }
+                    break;
+                }
+                Some(ch) => {
+                    result.push(ch);
+                    self.advance();
+                }
+            }
         }
 
         Token::String(result)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lex_string(source: &str) -> (Token, Option<CompileError>) {
+        let mut lexer = Lexer::new(source);
+        let token = lexer.next_token();
+        (token, lexer.take_errors().into_iter().next())
+    }
+
+    #[test]
+    fn test_semicolons_and_unexpected_characters() {
+        let mut lexer = Lexer::new("x; @y");
+        assert_eq!(lexer.next_token(), Token::Identifier("x".to_string()));
+        assert_eq!(lexer.next_token(), Token::Semicolon);
+        // '@' is reported and skipped, not lexed as an empty identifier
+        assert_eq!(lexer.next_token(), Token::Identifier("y".to_string()));
+        assert_eq!(lexer.next_token(), Token::EOF);
+        let error = lexer.take_errors().remove(0);
+        assert_eq!((error.code, error.column), (Some("E0152"), 4));
+    }
+
+    #[test]
+    fn test_unicode_identifiers() {
+        // Columns count chars, so `=` is at 7 whatever `größe` takes in bytes
+        let mut lexer = Lexer::new("größe = 名前 + 😀");
+        assert_eq!(lexer.next_token(), Token::Identifier("größe".to_string()));
+        lexer.next_token();
+        assert_eq!(lexer.token_column, 7);
+        assert_eq!(lexer.next_token(), Token::Identifier("名前".to_string()));
+        lexer.next_token();
+        // An emoji is no letter: reported like any stray character
+        assert_eq!(lexer.next_token(), Token::EOF);
+        assert_eq!(lexer.take_errors()[0].code, Some("E0152"));
+
+        // Only ASCII digits make numbers
+        let (_, error) = lex_string("٣");
+        assert_eq!(error.unwrap().code, Some("E0152"));
+    }
+
+    #[test]
+    fn test_string_escapes() {
+        let (token, error) = lex_string(r#""a\tb\0\x41\u{1F600}\u{e9}\'\"\\""#);
+        assert_eq!(token, Token::String("a\tb\0A\u{1F600}\u{e9}'\"\\".to_string()));
+        assert!(error.is_none());
+
+        for (source, code) in [
+            (r#""\d""#, "E0140"),
+            (r#""\x4""#, "E0141"),
+            (r#""\xff""#, "E0141"),
+            (r#""\u{110000}""#, "E0142"),
+            (r#""\u{41""#, "E0142"),
+            (r#""abc\"#, "E0143"),
+        ] {
+            let (_, error) = lex_string(source);
+            assert_eq!(error.map(|e| e.code), Some(Some(code)), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_raw_strings() {
+        let (token, error) = lex_string(r#"r"^\d+\.\d*$""#);
+        assert_eq!(token, Token::String(r"^\d+\.\d*$".to_string()));
+        assert!(error.is_none());
+
+        let (token, _) = lex_string(r##"r#"{"amount": 10}"#"##);
+        assert_eq!(token, Token::String(r#"{"amount": 10}"#.to_string()));
+
+        // `r` alone is still an identifier
+        let mut lexer = Lexer::new("r + raw");
+        assert_eq!(lexer.next_token(), Token::Identifier("r".to_string()));
+        lexer.next_token();
+        assert_eq!(lexer.next_token(), Token::Identifier("raw".to_string()));
+
+        let (_, error) = lex_string(r##"r#"open"##);
+        assert_eq!(error.unwrap().code, Some("E0143"));
+    }
+
+    #[test]
+    fn test_malformed_numbers() {
+        let (token, error) = lex_string("9223372036854775807");
+        assert_eq!(token, Token::Number(i64::MAX));
+        assert!(error.is_none());
+
+        // One token, not a number followed by a name
+        let mut lexer = Lexer::new("1abc_2 = 3");
+        assert_eq!(lexer.next_token(), Token::Identifier("1abc_2".to_string()));
+        assert_eq!(lexer.next_token(), Token::Assign);
+        assert_eq!(lexer.take_errors()[0].code, Some("E0170"));
+
+        let (token, error) = lex_string("99999999999999999999");
+        assert_eq!((token, error.unwrap().code), (Token::Number(0), Some("E0171")));
+    }
+
+    #[test]
+    fn test_every_malformed_literal_is_reported() {
+        let mut lexer = Lexer::new("let a = \"\\d\"\nlet b = 1abc\nlet c = 99999999999999999999\n");
+        while lexer.next_token() != Token::EOF {}
+        let found: Vec<(Option<&str>, usize)> = lexer.take_errors().iter().map(|e| (e.code, e.line)).collect();
+        assert_eq!(found, vec![(Some("E0140"), 1), (Some("E0170"), 2), (Some("E0171"), 3)]);
+    }
+}
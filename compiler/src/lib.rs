@@ -1,16 +1,68 @@
+/// Compiler version, as shown in crash reports
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
 pub mod lexer;
 pub mod parser;
 pub mod token;
+pub mod text;
 pub mod ast;
+pub mod ast_json;
+pub mod abi;
+pub mod bindgen;
 pub mod types;
 pub mod typechecker;
 pub mod error;
+pub mod messages;
+pub mod edition;
+pub mod migrate;
 pub mod diagnostics;
+pub mod bytecode;
 pub mod ir;
+pub mod kinds;
 pub mod lowering;
+pub mod monomorphize;
+pub mod nested;
+pub mod arguments;
+pub mod translate;
 pub mod opt;
-pub mod codegen {
-    pub mod wasm;
-}
+pub mod profile;
+pub mod cost;
+pub mod stats;
+pub mod bench;
+pub mod loops;
+pub mod verify;
+pub mod unused;
+pub mod storage;
+pub mod overflow;
+pub mod order;
+pub mod spec;
+pub mod txdecode;
+pub mod sourcemap;
+pub mod codegen;
 pub mod stdlib;
+pub mod bootstrap;
+pub mod prelude;
 pub mod loader;
+pub mod grammar;
+pub mod session;
+pub mod plugin;
+pub mod interpreter;
+pub mod shutdown;
+pub mod bridge;
+pub mod logs;
+pub mod eip712;
+pub mod multicall;
+pub mod ipfs;
+pub mod fork;
+pub mod u256;
+pub mod merkle;
+pub mod ed25519;
+pub mod bls;
+pub mod defi;
+pub mod gas;
+pub mod vm;
+pub mod module_cache;
+pub mod fuzz;
+pub mod timeline;
+pub mod ai_runtime;
+pub mod package_manager;
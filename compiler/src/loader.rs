@@ -1,7 +1,7 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::PathBuf;
-use crate::ast::{Module, Stmt};
+use crate::ast::{Module, Stmt, StmtKind};
 use crate::error::CompileError;
 use crate::lexer::Lexer;
 use crate::parser::Parser;
@@ -35,10 +35,7 @@ impl ModuleLoader {
         let module_file = self.find_module_file(name)?;
         
         let source = fs::read_to_string(&module_file)
-            .map_err(|e| CompileError::new(
-                &format!("Failed to read module '{}': {}", name, e),
-                0, 0
-            ))?;
+            .map_err(|e| CompileError::coded("E0301", &[&name, &e], 0, 0))?;
         
         let module = self.parse_module(name, &source)?;
         
@@ -58,10 +55,8 @@ impl ModuleLoader {
             }
         }
         
-        Err(CompileError::new(
-            &format!("Module '{}' not found in search paths", name),
-            0, 0
-        ).help(&format!("Searched for '{}' in: {:?}", filename, self.search_paths)))
+        let searched = format!("{:?}", self.search_paths);
+        Err(CompileError::coded("E0302", &[&name, &filename, &searched], 0, 0))
     }
     
     /// Parse module source into Module AST
@@ -73,8 +68,8 @@ impl ModuleLoader {
         
         let mut imports = Vec::new();
         for stmt in &statements {
-            if let Stmt::Import(module_name) = stmt {
-                imports.push(module_name.clone());
+            if let StmtKind::Import { module, .. } = &stmt.kind {
+                imports.push(module.clone());
             }
         }
         
@@ -85,6 +80,82 @@ impl ModuleLoader {
         })
     }
     
+    /// Load every module a program imports and check the names the imports
+    /// bring into scope: selected functions must exist and be exported, and
+    /// no two imports (or an import and a function of the program itself)
+    /// may bind one name.
+    pub fn check_imports(&mut self, program: &[Stmt]) -> Result<(), CompileError> {
+        let local: HashSet<&str> = program
+            .iter()
+            .filter_map(|stmt| match &stmt.kind {
+                StmtKind::Function { name, .. } => Some(name.as_str()),
+                _ => None,
+            })
+            .collect();
+        let mut bound: HashMap<String, String> = HashMap::new(); // name -> module
+        let mut aliases: HashMap<String, String> = HashMap::new(); // alias -> module
+
+        for stmt in program {
+            let StmtKind::Import { module, alias, items } = &stmt.kind else { continue };
+
+            // (name, exported) for each function of the module; the
+            // prelude is always there, without a file
+            let functions: Vec<(String, bool)> = if module == crate::prelude::MODULE {
+                crate::prelude::functions().iter().map(|f| (f.name.to_string(), true)).collect()
+            } else {
+                self.load_module(module)?
+                    .statements
+                    .iter()
+                    .filter_map(|stmt| match &stmt.kind {
+                        StmtKind::Function { name, exported, .. } => Some((name.clone(), *exported)),
+                        _ => None,
+                    })
+                    .collect()
+            };
+
+            if let Some(alias) = alias {
+                if let Some(other) = aliases.get(alias).filter(|other| *other != module) {
+                    return Err(CompileError::coded("E0306", &[alias, other], 0, 0));
+                }
+                aliases.insert(alias.clone(), module.clone());
+            }
+
+            // A plain import binds every exported function; an aliased one binds none
+            let names = match (items, alias) {
+                (Some(items), _) => {
+                    for item in items {
+                        match functions.iter().find(|(name, _)| name == item) {
+                            None => return Err(CompileError::coded("E0303", &[module, item], 0, 0)),
+                            Some((_, false)) => {
+                                return Err(CompileError::coded("E0307", &[module, item], 0, 0));
+                            }
+                            Some((_, true)) => {}
+                        }
+                    }
+                    items.clone()
+                }
+                (None, Some(_)) => Vec::new(),
+                (None, None) => functions
+                    .into_iter()
+                    .filter(|(_, exported)| *exported)
+                    .map(|(name, _)| name)
+                    .collect(),
+            };
+
+            for name in names {
+                if local.contains(name.as_str()) {
+                    return Err(CompileError::coded("E0305", &[&name, module], 0, 0));
+                }
+                if let Some(other) = bound.get(&name).filter(|other| *other != module) {
+                    return Err(CompileError::coded("E0304", &[&name, module, other], 0, 0));
+                }
+                bound.insert(name, module.clone());
+            }
+        }
+
+        Ok(())
+    }
+
     /// Get a loaded module
     pub fn get_module(&self, name: &str) -> Option<&Module> {
         self.loaded_modules.get(name)
@@ -106,4 +177,53 @@ mod tests {
         assert_eq!(loader.loaded_modules.len(), 0);
         assert!(loader.search_paths.len() >= 2);
     }
+
+    fn check(source: &str, dir: &str) -> Result<(), CompileError> {
+        let dir = std::env::temp_dir().join(dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("mathx.ax"),
+            "export fn add(a, b) {\n    return a + b\n}\nexport fn sub(a, b) {\n    return a - b\n}\nfn helper() {\n    return 0\n}\n",
+        ).unwrap();
+        fs::write(dir.join("mathy.ax"), "export fn add(a, b) {\n    return b + a\n}\n").unwrap();
+
+        let mut loader = ModuleLoader::new();
+        loader.add_search_path(dir);
+        let program = Parser::new(Lexer::new(source)).parse()?;
+        loader.check_imports(&program)
+    }
+
+    #[test]
+    fn test_selective_and_aliased_imports() {
+        let dir = "astrixa-loader-test-ok";
+        assert!(check("import mathx.{add, sub}\nimport mathy as y\nfn main() {\n}\n", dir).is_ok());
+        assert!(check("import mathx.{sub}\nfn add(a, b) {\n    return 0\n}\n", dir).is_ok());
+
+        let err = check("import mathx.{mul}\n", dir).unwrap_err();
+        assert_eq!(err.code, Some("E0303"));
+    }
+
+    #[test]
+    fn test_private_functions_stay_in_their_module() {
+        let dir = "astrixa-loader-test-private";
+        // A plain import skips private functions, so a local helper is fine
+        assert!(check("import mathx\nfn helper() {\n    return 1\n}\n", dir).is_ok());
+
+        let err = check("import mathx.{helper}\n", dir).unwrap_err();
+        assert_eq!(err.code, Some("E0307"));
+        assert!(err.help.unwrap().contains("export fn helper"));
+    }
+
+    #[test]
+    fn test_import_collisions() {
+        let dir = "astrixa-loader-test-collide";
+        let err = check("import mathx\nimport mathy\n", dir).unwrap_err();
+        assert_eq!(err.code, Some("E0304"));
+
+        let err = check("import mathx.{add}\nfn add(a, b) {\n    return 0\n}\n", dir).unwrap_err();
+        assert_eq!(err.code, Some("E0305"));
+
+        let err = check("import mathx as m\nimport mathy as m\n", dir).unwrap_err();
+        assert_eq!(err.code, Some("E0306"));
+    }
 }
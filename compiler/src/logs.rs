@@ -0,0 +1,532 @@
+use std::fs;
+use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use crate::interpreter::Value;
+use crate::u256::U256;
+use crate::messages;
+
+// Historical contract events for off-chain scripts.
+//
+// `web3.logs(contract, event, from_block, to_block)` fetches a contract's
+// logs for one event with `eth_getLogs` and decodes each into a Map. It
+// returns a Result, Err holding why the node could not be read:
+//
+//   let transfers = web3.logs(token, "Transfer(address indexed from, address indexed to, uint256 value)", 17000000, 17010000)?
+//   for t in transfers {
+//       print(t["to"])
+//   }
+//
+// The block range is fetched in pages of PAGE_BLOCKS blocks, halving a page
+// whenever the node refuses it for holding too many logs. Each page is
+// cached under ~/.astrixa/cache/logs, keyed by endpoint, contract, event and
+// range, so rerunning an analysis only fetches what it has not seen. Blocks
+// near the chain head can still be reorganized, so only pages ending at or
+// below the source's finalized block are cached; the rest are fetched
+// again every run.
+
+/// Blocks asked for in one `eth_getLogs` call, before any halving
+pub const PAGE_BLOCKS: u64 = 2_000;
+
+/// Blocks below the head taken as final on a node that cannot name its
+/// finalized block
+pub const CONFIRMATIONS: u64 = 64;
+
+/// One event's logs from one contract, over an inclusive block range
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogFilter {
+    pub address: String,
+    pub topic: String, // topic0: keccak256 of the event's canonical signature
+    pub from_block: u64,
+    pub to_block: u64,
+}
+
+/// A log as the node returns it, before decoding
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RawLog {
+    pub block_number: u64,
+    pub transaction_hash: String,
+    pub log_index: u64,
+    pub topics: Vec<String>,
+    pub data: String,
+}
+
+/// Why a page of logs could not be fetched
+#[derive(Debug, Clone, PartialEq)]
+pub enum PageError {
+    /// The range holds more logs than the node returns at once
+    TooLarge,
+    Failed(String),
+}
+
+/// Where logs come from: a JSON-RPC node, or a fixture in tests
+pub trait LogSource {
+    /// Identifies the chain endpoint, keying the cache
+    fn id(&self) -> String;
+
+    /// Every log matching the filter, in one request
+    fn get_logs(&self, filter: &LogFilter) -> Result<Vec<RawLog>, PageError>;
+
+    /// The newest block no reorganization can undo, if the source knows;
+    /// pages past it are never cached
+    fn finalized_block(&self) -> Option<u64> {
+        None
+    }
+}
+
+/// A JSON-RPC node reached over HTTP
+#[cfg(not(target_arch = "wasm32"))]
+pub struct RpcLogSource {
+    pub url: String,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl RpcLogSource {
+    /// The node named by the ASTRIXA_RPC_URL environment variable
+    pub fn from_env() -> Option<Self> {
+        std::env::var("ASTRIXA_RPC_URL").ok().map(|url| RpcLogSource { url })
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl RpcLogSource {
+    /// The node's whole response to one JSON-RPC call
+    fn request(&self, method: &str, params: serde_json::Value) -> Result<serde_json::Value, PageError> {
+        let request = serde_json::json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+        let body = ureq::post(&self.url)
+            .set("Content-Type", "application/json")
+            .send_string(&request.to_string())
+            .map_err(|e| PageError::Failed(e.to_string()))?
+            .into_string()
+            .map_err(|e| PageError::Failed(e.to_string()))?;
+        serde_json::from_str(&body).map_err(|e| PageError::Failed(e.to_string()))
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl LogSource for RpcLogSource {
+    fn id(&self) -> String {
+        self.url.clone()
+    }
+
+    fn get_logs(&self, filter: &LogFilter) -> Result<Vec<RawLog>, PageError> {
+        let response = self.request(
+            "eth_getLogs",
+            serde_json::json!([{
+                "address": filter.address,
+                "topics": [filter.topic],
+                "fromBlock": format!("0x{:x}", filter.from_block),
+                "toBlock": format!("0x{:x}", filter.to_block),
+            }]),
+        )?;
+
+        if let Some(error) = response.get("error") {
+            let message = error["message"].as_str().unwrap_or_default().to_string();
+            // Nodes disagree on how to say it; -32005 is the common code
+            let too_large = error["code"] == -32005
+                || ["more than", "too many", "limit exceeded", "range is too large"]
+                    .iter()
+                    .any(|phrase| message.to_lowercase().contains(phrase));
+            return Err(if too_large { PageError::TooLarge } else { PageError::Failed(message) });
+        }
+
+        let logs = response["result"].as_array().ok_or_else(|| PageError::Failed("no result in response".to_string()))?;
+        logs.iter().map(raw_log).collect()
+    }
+
+    /// The "finalized" block where the node has one, else CONFIRMATIONS
+    /// below the head
+    fn finalized_block(&self) -> Option<u64> {
+        let number = |value: &serde_json::Value| u64::from_str_radix(value.as_str()?.trim_start_matches("0x"), 16).ok();
+        let finalized = self.request("eth_getBlockByNumber", serde_json::json!(["finalized", false])).ok();
+        if let Some(block) = finalized.as_ref().and_then(|response| number(&response["result"]["number"])) {
+            return Some(block);
+        }
+        let head = self.request("eth_blockNumber", serde_json::json!([])).ok()?;
+        number(&head["result"])?.checked_sub(CONFIRMATIONS)
+    }
+}
+
+/// Read one log object of an `eth_getLogs` result
+#[cfg(not(target_arch = "wasm32"))]
+fn raw_log(log: &serde_json::Value) -> Result<RawLog, PageError> {
+    let text = |field: &str| {
+        log[field].as_str().map(str::to_string).ok_or_else(|| PageError::Failed(format!("log without {}", field)))
+    };
+    let number = |field: &str| {
+        text(field).and_then(|hex| {
+            u64::from_str_radix(hex.trim_start_matches("0x"), 16).map_err(|_| PageError::Failed(format!("bad {} '{}'", field, hex)))
+        })
+    };
+    Ok(RawLog {
+        block_number: number("blockNumber")?,
+        transaction_hash: text("transactionHash")?,
+        log_index: number("logIndex")?,
+        topics: log["topics"]
+            .as_array()
+            .map(|topics| topics.iter().filter_map(|t| t.as_str().map(str::to_string)).collect())
+            .unwrap_or_default(),
+        data: text("data")?,
+    })
+}
+
+/// Every log of `filter`'s range, fetched page by page through the cache
+/// for the pages that are final
+pub fn fetch(source: &dyn LogSource, cache: Option<&PathBuf>, filter: &LogFilter) -> Result<Vec<RawLog>, String> {
+    let finalized = cache.and_then(|_| source.finalized_block());
+    let mut logs = Vec::new();
+    let mut from = filter.from_block;
+    let mut page = PAGE_BLOCKS;
+    while from <= filter.to_block {
+        let to = filter.to_block.min(from.saturating_add(page - 1));
+        let range = LogFilter { from_block: from, to_block: to, ..filter.clone() };
+        let cache = cache.filter(|_| finalized.is_some_and(|block| to <= block));
+        match fetch_page(source, cache, &range) {
+            Ok(found) => {
+                logs.extend(found);
+                from = to + 1;
+            }
+            Err(PageError::TooLarge) if page > 1 => page /= 2,
+            Err(PageError::TooLarge) => return Err(messages::render("E0443", &[&format!("block {} has too many logs", from)])),
+            Err(PageError::Failed(message)) => return Err(messages::render("E0443", &[&message])),
+        }
+    }
+    Ok(logs)
+}
+
+fn fetch_page(source: &dyn LogSource, cache: Option<&PathBuf>, filter: &LogFilter) -> Result<Vec<RawLog>, PageError> {
+    let path = cache.map(|dir| dir.join(cache_key(&source.id(), filter)));
+    if let Some(cached) = path.as_ref().and_then(|p| fs::read_to_string(p).ok()) {
+        if let Ok(logs) = serde_json::from_str(&cached) {
+            return Ok(logs);
+        }
+    }
+
+    let logs = source.get_logs(filter)?;
+    // A cache that cannot be written only costs a refetch next time
+    if let Some(path) = path {
+        if let Some(dir) = path.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        if let Ok(text) = serde_json::to_string(&logs) {
+            let _ = fs::write(path, text);
+        }
+    }
+    Ok(logs)
+}
+
+/// File name of a cached page: a digest of what was asked, and where
+fn cache_key(source: &str, filter: &LogFilter) -> String {
+    use sha2::{Digest, Sha256};
+    let key = format!(
+        "{}|{}|{}|{}-{}",
+        source,
+        filter.address.to_lowercase(),
+        filter.topic,
+        filter.from_block,
+        filter.to_block
+    );
+    format!("{:x}.json", Sha256::digest(key))
+}
+
+/// An event parameter: `address indexed from`
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventParam {
+    pub name: String,
+    pub kind: String,
+    pub indexed: bool,
+}
+
+/// A parsed event declaration: `Transfer(address indexed from, address indexed to, uint256 value)`
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventSignature {
+    pub name: String,
+    pub params: Vec<EventParam>,
+}
+
+impl EventSignature {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let invalid = || messages::render("E0441", &[&text]);
+        let (name, rest) = text.trim().split_once('(').ok_or_else(invalid)?;
+        let inside = rest.strip_suffix(')').ok_or_else(invalid)?;
+        let name = name.trim();
+        if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+            return Err(invalid());
+        }
+
+        let mut params = Vec::new();
+        for (i, param) in inside.split(',').map(str::trim).filter(|p| !p.is_empty()).enumerate() {
+            let words: Vec<&str> = param.split_whitespace().collect();
+            let (kind, indexed, name) = match words.as_slice() {
+                [kind] => (kind, false, None),
+                [kind, "indexed"] => (kind, true, None),
+                [kind, "indexed", name] => (kind, true, Some(name)),
+                [kind, name] => (kind, false, Some(name)),
+                _ => return Err(invalid()),
+            };
+            params.push(EventParam {
+                name: name.map_or_else(|| format!("arg{}", i), |n| n.to_string()),
+                kind: canonical_type(kind),
+                indexed,
+            });
+        }
+        Ok(EventSignature { name: name.to_string(), params })
+    }
+
+    /// keccak256 of `Name(type,type)`, the log's first topic
+    pub fn topic(&self) -> String {
+        let kinds: Vec<&str> = self.params.iter().map(|p| p.kind.as_str()).collect();
+        format!("0x{}", hex(&keccak256(format!("{}({})", self.name, kinds.join(",")).as_bytes())))
+    }
+
+    /// Decode a log into a Map of its parameters, then where it came from
+    pub fn decode(&self, log: &RawLog) -> Result<Value, String> {
+        let data = unhex(&log.data).map_err(|e| messages::render("E0444", &[&self.name, &e]))?;
+        let mut topics = log.topics.iter().skip(1);
+        let mut head = 0;
+        let mut entries = Vec::new();
+        for param in &self.params {
+            let failed = |e: String| messages::render("E0444", &[&self.name, &format!("{}: {}", param.name, e)]);
+            let value = if param.indexed {
+                let topic = topics.next().ok_or_else(|| failed("missing topic".to_string()))?;
+                let word = unhex(topic).map_err(failed)?;
+                if is_dynamic(&param.kind) {
+                    // Only the hash of an indexed string or array is logged
+                    Value::String(topic.clone())
+                } else {
+                    decode_word(&param.kind, &word).map_err(failed)?
+                }
+            } else {
+                let word = data.get(head..head + 32).ok_or_else(|| failed("data too short".to_string()))?;
+                head += 32;
+                if is_dynamic(&param.kind) {
+                    decode_dynamic(&param.kind, &data, word).map_err(failed)?
+                } else {
+                    decode_word(&param.kind, word).map_err(failed)?
+                }
+            };
+            entries.push((param.name.clone(), value));
+        }
+        entries.push(("block_number".to_string(), Value::Number(log.block_number as i64)));
+        entries.push(("transaction_hash".to_string(), Value::String(log.transaction_hash.clone())));
+        entries.push(("log_index".to_string(), Value::Number(log.log_index as i64)));
+        Ok(Value::map(entries))
+    }
+}
+
+/// The name a type takes in a canonical signature: `uint` is `uint256`
+pub(crate) fn canonical_type(kind: &str) -> String {
+    match kind {
+        "uint" => "uint256".to_string(),
+        "int" => "int256".to_string(),
+        other => other.to_string(),
+    }
+}
+
+pub(crate) fn is_dynamic(kind: &str) -> bool {
+    kind == "string" || kind == "bytes" || kind.ends_with(']') || kind.starts_with('(')
+}
+
+/// A static value from its 32-byte ABI word
+pub(crate) fn decode_word(kind: &str, word: &[u8]) -> Result<Value, String> {
+    if word.len() != 32 {
+        return Err("expected a 32-byte word".to_string());
+    }
+    if kind == "address" {
+        return Ok(Value::Address(format!("0x{}", hex(&word[12..]))));
+    }
+    if kind == "bool" {
+        return Ok(Value::Bool(word[31] != 0));
+    }
+    if kind.starts_with("uint") {
+        return Ok(Value::U256(U256::from_big_endian(word)));
+    }
+    if kind.starts_with("int") {
+        let sign = if word[0] & 0x80 != 0 { 0xff } else { 0 };
+        if word[..24].iter().any(|b| *b != sign) {
+            return Err("value does not fit in 64 bits".to_string());
+        }
+        let low: [u8; 8] = word[24..].try_into().map_err(|_| "bad word".to_string())?;
+        return Ok(Value::Number(i64::from_be_bytes(low)));
+    }
+    if let Some(size) = kind.strip_prefix("bytes").and_then(|n| n.parse::<usize>().ok()).filter(|n| (1..=32).contains(n)) {
+        return Ok(Value::String(format!("0x{}", hex(&word[..size]))));
+    }
+    Err(format!("type '{}' is not supported", kind))
+}
+
+/// A `string`, `bytes` or array of static values, found at the offset in
+/// its head word
+pub(crate) fn decode_dynamic(kind: &str, data: &[u8], word: &[u8]) -> Result<Value, String> {
+    let offset = word_to_usize(word)?;
+    let length = word_to_usize(data.get(offset..offset + 32).ok_or("offset out of range")?)?;
+    if let Some(element) = kind.strip_suffix("[]").filter(|element| !is_dynamic(element)) {
+        let end = length.checked_mul(32).and_then(|size| (offset + 32).checked_add(size)).ok_or("length out of range")?;
+        let words = data.get(offset + 32..end).ok_or("length out of range")?;
+        return words.chunks(32).map(|word| decode_word(element, word)).collect::<Result<_, _>>().map(Value::array);
+    }
+    let bytes = data.get(offset + 32..offset + 32 + length).ok_or("length out of range")?;
+    match kind {
+        "string" => String::from_utf8(bytes.to_vec()).map(Value::String).map_err(|_| "string is not UTF-8".to_string()),
+        "bytes" => Ok(Value::String(format!("0x{}", hex(bytes)))),
+        other => Err(format!("type '{}' is not supported", other)),
+    }
+}
+
+fn word_to_usize(word: &[u8]) -> Result<usize, String> {
+    if word.len() != 32 || word[..24].iter().any(|b| *b != 0) {
+        return Err("offset out of range".to_string());
+    }
+    let low: [u8; 8] = word[24..].try_into().map_err(|_| "bad word".to_string())?;
+    usize::try_from(u64::from_be_bytes(low)).map_err(|_| "offset out of range".to_string())
+}
+
+pub fn keccak256(bytes: &[u8]) -> [u8; 32] {
+    use tiny_keccak::{Hasher, Keccak};
+    let mut hasher = Keccak::v256();
+    let mut out = [0u8; 32];
+    hasher.update(bytes);
+    hasher.finalize(&mut out);
+    out
+}
+
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// The bytes a message stands for: those a 0x-prefixed hex string spells,
+/// and any other text's UTF-8 bytes
+pub(crate) fn message_bytes(text: &str) -> Vec<u8> {
+    match text.starts_with("0x") {
+        true => unhex(text).unwrap_or_else(|_| text.as_bytes().to_vec()),
+        false => text.as_bytes().to_vec(),
+    }
+}
+
+pub(crate) fn unhex(text: &str) -> Result<Vec<u8>, String> {
+    let digits = text.strip_prefix("0x").unwrap_or(text);
+    if !digits.len().is_multiple_of(2) {
+        return Err(format!("odd-length hex '{}'", text));
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).map_err(|_| format!("bad hex '{}'", text)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    const TRANSFER: &str = "Transfer(address indexed from, address indexed to, uint256 value)";
+
+    /// Logs at fixed blocks; refuses ranges over `max_blocks`
+    struct Fixture {
+        logs: Vec<RawLog>,
+        max_blocks: u64,
+        finalized: u64,
+        requests: RefCell<Vec<(u64, u64)>>,
+    }
+
+    impl LogSource for Fixture {
+        fn id(&self) -> String {
+            "fixture".to_string()
+        }
+
+        fn get_logs(&self, filter: &LogFilter) -> Result<Vec<RawLog>, PageError> {
+            self.requests.borrow_mut().push((filter.from_block, filter.to_block));
+            if filter.to_block - filter.from_block + 1 > self.max_blocks {
+                return Err(PageError::TooLarge);
+            }
+            Ok(self
+                .logs
+                .iter()
+                .filter(|log| (filter.from_block..=filter.to_block).contains(&log.block_number))
+                .cloned()
+                .collect())
+        }
+
+        fn finalized_block(&self) -> Option<u64> {
+            Some(self.finalized)
+        }
+    }
+
+    fn word(tail: &str) -> String {
+        format!("{:0>64}", tail)
+    }
+
+    fn transfer(block: u64, value: &str) -> RawLog {
+        RawLog {
+            block_number: block,
+            transaction_hash: format!("0x{:064x}", block),
+            log_index: 0,
+            topics: vec![
+                EventSignature::parse(TRANSFER).unwrap().topic(),
+                format!("0x{}", word("aa")),
+                format!("0x{}", word("bb")),
+            ],
+            data: format!("0x{}", word(value)),
+        }
+    }
+
+    #[test]
+    fn test_event_signature_topic_and_decoding() {
+        let event = EventSignature::parse(TRANSFER).unwrap();
+        assert_eq!(event.topic(), "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef");
+
+        let decoded = event.decode(&transfer(7, "64")).unwrap();
+        let Value::Map(entries) = decoded else { panic!("expected a map") };
+        let entries = entries.borrow();
+        assert_eq!(entries[0], ("from".to_string(), Value::Address(format!("0x{:0>40}", "aa"))));
+        assert_eq!(entries[2], ("value".to_string(), Value::U256(U256::from(100))));
+        assert_eq!(entries[3], ("block_number".to_string(), Value::Number(7)));
+
+        // A dynamic string is found through its offset
+        let note = EventSignature::parse("Note(string text)").unwrap();
+        let log = RawLog { data: format!("0x{}{}{:0<64}", word("20"), word("2"), "6869"), ..transfer(1, "0") };
+        let Value::Map(entries) = note.decode(&log).unwrap() else { panic!("expected a map") };
+        let entries = entries.borrow();
+        assert_eq!(entries[0].1, Value::String("hi".to_string()));
+
+        assert!(EventSignature::parse("Transfer address").is_err());
+    }
+
+    #[test]
+    fn test_fetch_pages_halves_on_refusal_and_caches() {
+        let cache = std::env::temp_dir().join(format!("astrixa-logs-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&cache);
+        let fixture = Fixture {
+            logs: vec![transfer(10, "1"), transfer(1_500, "2"), transfer(2_500, "3")],
+            max_blocks: 1_000,
+            finalized: 5_000,
+            requests: RefCell::new(Vec::new()),
+        };
+        let filter = LogFilter {
+            address: "0x00000000000000000000000000000000000000c0".to_string(),
+            topic: EventSignature::parse(TRANSFER).unwrap().topic(),
+            from_block: 0,
+            to_block: 2_999,
+        };
+
+        let logs = fetch(&fixture, Some(&cache), &filter).unwrap();
+        let blocks: Vec<u64> = logs.iter().map(|log| log.block_number).collect();
+        assert_eq!(blocks, vec![10, 1_500, 2_500]);
+        assert_eq!(fixture.requests.borrow()[..2], [(0, 1_999), (0, 999)]);
+
+        // The second run is served from the cache
+        fixture.requests.borrow_mut().clear();
+        assert_eq!(fetch(&fixture, Some(&cache), &filter).unwrap(), logs);
+        assert_eq!(fixture.requests.borrow().len(), 1, "only the refused first page is asked again");
+
+        // Pages reaching past the finalized block may yet change, so every
+        // run asks for them again
+        let _ = fs::remove_dir_all(&cache);
+        let fixture = Fixture { finalized: 2_499, ..fixture };
+        assert_eq!(fetch(&fixture, Some(&cache), &filter).unwrap(), logs);
+        fixture.requests.borrow_mut().clear();
+        assert_eq!(fetch(&fixture, Some(&cache), &filter).unwrap(), logs);
+        assert_eq!(fixture.requests.borrow()[..], [(0, 1_999), (2_000, 2_999)]);
+
+        let _ = fs::remove_dir_all(&cache);
+    }
+}
@@ -0,0 +1,277 @@
+use std::collections::HashSet;
+use crate::ast::{Attribute, Expr, ExprKind, Stmt, StmtKind};
+use crate::cost;
+use crate::edition::Warning;
+
+// Loop bounds in contract methods.
+//
+// A contract call has to fit in a block, so a loop whose iteration count the
+// caller or the chain controls can make a method impossible to call once
+// the data grows:
+//
+//   fn airdrop(recipients) {
+//       let mut i = 0
+//       while i < len(recipients) {   // W0201
+//           ...
+//
+// A loop is user-controlled when its bound reads a parameter, contract
+// state, a host property (`msg.value`) or a module call, directly or
+// through a local assigned from one. Those always warn, with the number of
+// iterations at which the method outgrows the block. A loop bounded by
+// constants (`for i in 0..1000`, `while i < 1000`) warns only when that
+// many iterations exceed the block. Per-iteration gas is the cost model's
+// estimate for one pass through the loop. A method marked #[unbounded]
+// keeps its loops on purpose and is skipped.
+
+/// Gas a block holds, on the cost model's scale
+pub const BLOCK_GAS_LIMIT: u64 = 30_000_000;
+
+/// Warnings for every contract method in `stmts` whose loops may not fit
+/// in a block
+pub fn analyze(stmts: &[Stmt]) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+    for stmt in stmts {
+        let StmtKind::Contract { name, state, constructor, methods, .. } = &stmt.kind else { continue };
+        for method in constructor.as_deref().into_iter().chain(methods) {
+            let StmtKind::Function { name: method_name, params, body, attributes, .. } = &method.kind else { continue };
+            if attributes.contains(&Attribute::Unbounded) {
+                continue;
+            }
+            let mut analysis = Analysis {
+                contract: stmt,
+                method,
+                qualified: format!("{}.{}", name, method_name),
+                tainted: params.iter().chain(state).cloned().collect(),
+                warnings: &mut warnings,
+            };
+            analysis.block(body);
+        }
+    }
+    warnings
+}
+
+struct Analysis<'a> {
+    contract: &'a Stmt,
+    method: &'a Stmt,
+    qualified: String,
+    tainted: HashSet<String>, // Names holding user-controlled data
+    warnings: &'a mut Vec<Warning>,
+}
+
+impl Analysis<'_> {
+    fn block(&mut self, body: &[Stmt]) {
+        for stmt in body {
+            self.stmt(stmt);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match &stmt.kind {
+            StmtKind::Let { name, value, .. } | StmtKind::Assign { name, value } if self.source(value).is_some() => {
+                self.tainted.insert(name.clone());
+            }
+            StmtKind::LetTuple { names, value, .. } if self.source(value).is_some() => {
+                self.tainted.extend(names.iter().cloned());
+            }
+            StmtKind::If { then_body, else_body, .. } => {
+                self.block(then_body);
+                if let Some(else_body) = else_body {
+                    self.block(else_body);
+                }
+            }
+            StmtKind::Match { arms, .. } => {
+                for arm in arms {
+                    self.block(&arm.body);
+                }
+            }
+            StmtKind::While { condition, body } => {
+                // Assignments in the body feed the next test of the condition
+                self.block(body);
+                let bound = match self.source(condition) {
+                    Some(source) => Bound::User(source),
+                    None => constant_count(condition).map_or(Bound::Unknown, Bound::Constant),
+                };
+                self.report(stmt, bound, std::slice::from_ref(stmt));
+            }
+            StmtKind::For { var, iterable, body } => {
+                let bound = match self.source(iterable) {
+                    Some(source) => {
+                        self.tainted.insert(var.clone());
+                        Bound::User(source)
+                    }
+                    None => match &iterable.kind {
+                        ExprKind::Range(start, end) => match (&start.kind, &end.kind) {
+                            (ExprKind::Number(start), ExprKind::Number(end)) => {
+                                Bound::Constant(end.saturating_sub(*start).max(0) as u64)
+                            }
+                            _ => Bound::Unknown,
+                        },
+                        ExprKind::Array(items) => Bound::Constant(items.len() as u64),
+                        _ => Bound::Unknown,
+                    },
+                };
+                self.block(body);
+                self.report(stmt, bound, body);
+            }
+            StmtKind::Defer(body) => self.block(body),
+            _ => {}
+        }
+    }
+
+    fn report(&mut self, stmt: &Stmt, bound: Bound, pass: &[Stmt]) {
+        let per_iteration = self.pass_gas(pass).max(1);
+        let warning = match bound {
+            Bound::User(source) => {
+                let iterations = BLOCK_GAS_LIMIT / per_iteration + 1;
+                Warning::new(
+                    "W0201",
+                    &[&self.qualified, &source, &per_iteration, &iterations, &BLOCK_GAS_LIMIT],
+                    stmt.span,
+                    None,
+                )
+            }
+            Bound::Constant(iterations) if iterations.saturating_mul(per_iteration) > BLOCK_GAS_LIMIT => Warning::new(
+                "W0202",
+                &[&self.qualified, &iterations, &per_iteration, &iterations.saturating_mul(per_iteration), &BLOCK_GAS_LIMIT],
+                stmt.span,
+                None,
+            ),
+            _ => return,
+        };
+        self.warnings.push(warning);
+    }
+
+    /// Estimated gas of one pass through `pass`: the cost of the method with
+    /// `pass` as its body, less that of the method with an empty one
+    fn pass_gas(&self, pass: &[Stmt]) -> u64 {
+        let gas = |body: &[Stmt]| {
+            let mut method = self.method.clone();
+            if let StmtKind::Function { body: slot, .. } = &mut method.kind {
+                *slot = body.to_vec();
+            }
+            let mut contract = self.contract.clone();
+            if let StmtKind::Contract { constructor, methods, .. } = &mut contract.kind {
+                *constructor = None;
+                *methods = vec![method];
+            }
+            let module = crate::lowering::lower(std::slice::from_ref(&contract));
+            module.find_function(&self.qualified).map_or(0, |f| cost::function_cost(f).gas)
+        };
+        gas(pass).saturating_sub(gas(&[]))
+    }
+
+    /// The first user-controlled value `expr` reads, as written
+    fn source(&self, expr: &Expr) -> Option<String> {
+        match &expr.kind {
+            ExprKind::Identifier(name) => self.tainted.contains(name).then(|| name.clone()),
+            ExprKind::Property(object, name) => Some(format!("{}.{}", object, name)),
+            ExprKind::ModuleCall(receiver, method, _) => Some(format!("{}.{}()", receiver, method)),
+            ExprKind::Call(_, args) | ExprKind::Tuple(args) | ExprKind::Array(args) => {
+                args.iter().find_map(|arg| self.source(arg))
+            }
+            ExprKind::Await(inner) | ExprKind::Try(inner) | ExprKind::Named(_, inner) | ExprKind::Cast(inner, _) => self.source(inner),
+            ExprKind::Range(l, r) | ExprKind::Slice(l, r)
+            | ExprKind::Add(l, r) | ExprKind::Sub(l, r) | ExprKind::Mul(l, r) | ExprKind::Div(l, r) | ExprKind::Mod(l, r)
+            | ExprKind::Eq(l, r) | ExprKind::Ne(l, r) | ExprKind::Lt(l, r) | ExprKind::Le(l, r)
+            | ExprKind::Gt(l, r) | ExprKind::Ge(l, r)
+            | ExprKind::And(l, r) | ExprKind::Or(l, r) => self.source(l).or_else(|| self.source(r)),
+            ExprKind::Number(_) | ExprKind::Float(_) | ExprKind::Bool(_) | ExprKind::String(_) => None,
+        }
+    }
+}
+
+/// What limits a loop's iterations
+enum Bound {
+    User(String),  // A user-controlled value, as written
+    Constant(u64), // A known number of iterations
+    Unknown,       // Local state the analysis does not follow
+}
+
+/// Iterations of `while i < N` (or `N > i`) with a literal N, counting from
+/// zero
+fn constant_count(condition: &Expr) -> Option<u64> {
+    let (counter, limit, inclusive) = match &condition.kind {
+        ExprKind::Lt(counter, limit) | ExprKind::Gt(limit, counter) => (counter, limit, false),
+        ExprKind::Le(counter, limit) | ExprKind::Ge(limit, counter) => (counter, limit, true),
+        _ => return None,
+    };
+    match (&counter.kind, &limit.kind) {
+        (ExprKind::Identifier(_), ExprKind::Number(n)) if *n >= 0 => Some(*n as u64 + inclusive as u64),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn analyze_source(source: &str) -> Vec<Warning> {
+        analyze(&Parser::new(Lexer::new(source)).parse().unwrap())
+    }
+
+    #[test]
+    fn test_user_controlled_loops_warn() {
+        let warnings = analyze_source(r#"
+            contract Token {
+                state holders
+                fn airdrop(count) {
+                    let mut i = 0
+                    let n = count * 2
+                    while i < n {
+                        holders = holders + 1
+                        i = i + 1
+                    }
+                }
+                fn refund() {
+                    for i in 0..msg.value {
+                        holders = holders - 1
+                    }
+                }
+                fn settle() {
+                    let mut i = 0
+                    while i < 10 {
+                        i = i + 1
+                    }
+                }
+                #[unbounded]
+                fn sweep(count) {
+                    let mut i = 0
+                    while i < count {
+                        i = i + 1
+                    }
+                }
+            }
+        "#);
+
+        let codes: Vec<&str> = warnings.iter().map(|w| w.code).collect();
+        assert_eq!(codes, vec!["W0201", "W0201"]);
+        assert!(warnings[0].message.contains("Token.airdrop") && warnings[0].message.contains("'n'"), "{}", warnings[0].message);
+        assert!(warnings[1].message.contains("'msg.value'"), "{}", warnings[1].message);
+        assert_eq!(warnings[0].span.start_line, 7);
+    }
+
+    #[test]
+    fn test_constant_loops_warn_past_the_block_limit() {
+        let warnings = analyze_source(r#"
+            contract Ledger {
+                state total
+                fn fill() {
+                    for i in 0..1000000 {
+                        total = total + i
+                    }
+                }
+                fn small() {
+                    for i in 0..100 {
+                        total = total + i
+                    }
+                }
+            }
+        "#);
+
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].code, "W0202");
+        assert!(warnings[0].message.contains("Ledger.fill runs 1000000 times"), "{}", warnings[0].message);
+    }
+}
@@ -10,28 +10,70 @@
 /// 4. Produces optimizable IR
 /// 5. Maps stdlib calls to CallStd instructions
 
-use crate::ast::{Expr, Stmt};
-use crate::ir::{IRFunction, IRInstr, IRModule};
-use std::collections::HashMap;
+use crate::ast::{Condition, ConditionKind, Expr, ExprKind, Span, Stmt, StmtKind};
+use crate::error::CompileError;
+use crate::ir::{IRContract, IRFunction, IRInstr, IRModule, Unsupported, ValueKind};
+use crate::types::Type;
+use std::collections::{HashMap, HashSet};
+
+/// Stands in for a function's scratch slot for `&&` and `||` until the
+/// function is lowered and the slot allocated after its other locals
+const LOGIC_SLOT: u32 = u32::MAX;
 
 /// Context for lowering - tracks variables and their stack slots
 #[derive(Debug, Clone)]
 pub struct LowerCtx {
     locals: HashMap<String, u32>,
+    names: Vec<String>, // each slot's name, for debug info
     next_slot: u32,
+    globals: HashMap<String, String>, // contract state: variable -> global name
+    tuples: HashMap<String, Vec<u32>>, // tuple variable -> one slot per element
+    result_counts: HashMap<String, usize>, // functions returning more than one value
+    functions: HashSet<String>, // the module's functions, which a bare name loads a reference to
+    in_contract: bool, // lowering a contract method, where panics revert
+    deferred: Vec<Vec<Vec<Stmt>>>, // code each enclosing block defers, innermost last
+    assertions: bool, // check #[requires] on entry and #[ensures] on return
+    ensures: Vec<Expr>, // the function's #[ensures], checked at each return when assertions are on
+    lines: bool, // mark where each statement's code starts, for debug info
 }
 
 impl LowerCtx {
     pub fn new() -> Self {
         Self {
             locals: HashMap::new(),
+            names: Vec::new(),
             next_slot: 0,
+            globals: HashMap::new(),
+            tuples: HashMap::new(),
+            result_counts: HashMap::new(),
+            functions: HashSet::new(),
+            in_contract: false,
+            deferred: Vec::new(),
+            assertions: false,
+            ensures: Vec::new(),
+            lines: false,
+        }
+    }
+    
+    /// Make a module-level global visible under a local name
+    pub fn bind_global(&mut self, name: String, global: String) {
+        self.globals.insert(name, global);
+    }
+    
+    /// Get the global backing a name, unless shadowed by a local
+    pub fn get_global(&self, name: &str) -> Option<&String> {
+        if self.locals.contains_key(name) {
+            None
+        } else {
+            self.globals.get(name)
         }
     }
     
     /// Allocate a new local variable slot
     pub fn alloc(&mut self, name: String) -> u32 {
         let slot = self.next_slot;
+        self.tuples.remove(&name);
+        self.names.push(name.clone());
         self.locals.insert(name, slot);
         self.next_slot += 1;
         slot
@@ -42,6 +84,33 @@ impl LowerCtx {
         self.locals.get(name).copied()
     }
     
+    /// Allocate consecutive slots for a tuple variable, one per element
+    pub fn alloc_tuple(&mut self, name: String, arity: usize) -> Vec<u32> {
+        let slots: Vec<u32> = (0..arity).map(|_| self.alloc(name.clone())).collect();
+        self.tuples.insert(name, slots.clone());
+        slots
+    }
+    
+    /// Get the slots of a tuple variable
+    pub fn get_tuple(&self, name: &str) -> Option<&[u32]> {
+        self.tuples.get(name).map(|slots| slots.as_slice())
+    }
+    
+    /// Number of values an expression leaves on the stack
+    pub fn arity(&self, expr: &Expr) -> usize {
+        match &expr.kind {
+            ExprKind::Tuple(items) => items.len(),
+            ExprKind::Range(..) => 2,
+            ExprKind::Identifier(name) => self.get_tuple(name).map_or(1, |slots| slots.len()),
+            ExprKind::Call(name, _) => match self.result_counts.get(name) {
+                Some(count) => *count,
+                None => crate::stdlib::get_stdlib_info(name).map_or(1, |f| f.result_count()),
+            },
+            ExprKind::Await(inner) => self.arity(inner),
+            _ => 1,
+        }
+    }
+    
     /// Get the number of local slots used
     pub fn num_locals(&self) -> u32 {
         self.next_slot
@@ -50,18 +119,86 @@ impl LowerCtx {
 
 /// Lower an AST into IR
 pub fn lower(stmts: &[Stmt]) -> IRModule {
+    lower_with(stmts, false, false)
+}
+
+/// Lower an AST into IR; with `assertions`, functions check their
+/// #[requires] conditions on entry and #[ensures] on return, trapping (or
+/// reverting, in a contract) when one fails. With `lines`, each statement's
+/// code starts with an `IRInstr::Line`, for source maps.
+pub fn lower_with(stmts: &[Stmt], assertions: bool, lines: bool) -> IRModule {
+    let mut module = lower_unlinked(stmts, assertions, lines);
+    crate::bootstrap::link(&mut module);
+    module
+}
+
+/// Lower an AST into IR for a build, as `lower_with` does; a construct
+/// compiled builds cannot express yet is an error where it was written,
+/// rather than a trap the artifact would carry
+pub fn lower_for_build(stmts: &[Stmt], assertions: bool, lines: bool) -> Result<IRModule, Vec<CompileError>> {
+    let module = lower_with(stmts, assertions, lines);
+    let errors: Vec<CompileError> = module
+        .unsupported()
+        .map(|(function, unsupported)| {
+            let construct = unsupported.name();
+            // A standard library function's spans are in std/, not the
+            // program: the error names the function instead, at line 0
+            if crate::bootstrap::defines(&function.name) && function.span == Span::default() {
+                let mut error = CompileError::coded("E0501", &[&construct], 0, 0);
+                error.message = crate::messages::render("E0501.std", &[&error.message, &function.name]);
+                return error;
+            }
+            CompileError::coded("E0501", &[&construct], unsupported.span.start_line, unsupported.span.start_column)
+        })
+        .collect();
+    if errors.is_empty() {
+        Ok(module)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Lower an AST into IR, without the standard library functions written
+/// in ASTRIXA that it calls
+pub(crate) fn lower_unlinked(stmts: &[Stmt], assertions: bool, lines: bool) -> IRModule {
+    let stmts = &crate::monomorphize::monomorphize(&crate::arguments::resolve(&crate::nested::lift(stmts)));
     let mut module = IRModule::new();
+    let result_counts = result_counts(stmts);
+    let functions: HashSet<String> = stmts
+        .iter()
+        .filter_map(|stmt| match &stmt.kind {
+            StmtKind::Function { name, .. } => Some(name.clone()),
+            _ => None,
+        })
+        .collect();
 
     for stmt in stmts {
-        match stmt {
-            Stmt::Import(_) => {
+        match &stmt.kind {
+            StmtKind::Import { .. } => {
                 // Imports are handled at compilation level, not lowered to IR
             }
-            Stmt::Function { name, params, body, exported: _, .. } => {
-                let function = lower_function(name, params, body);
+            StmtKind::Function { name, params, param_types, defaults, body, exported: _, is_async, attributes, conditions, .. } => {
+                let mut ctx = LowerCtx::new();
+                ctx.result_counts = result_counts.clone();
+                ctx.functions = functions.clone();
+                ctx.assertions = assertions;
+                ctx.lines = lines;
+                let mut function = lower_function(name, params, body, conditions, ctx);
+                function.param_kinds = param_kinds(param_types, defaults);
+                function.attributes = attributes.clone();
+                function.span = stmt.span;
+                function.is_async = *is_async;
                 module.add_function(function);
                 // Note: `exported` flag is tracked in AST but doesn't affect IR
             }
+            StmtKind::Contract { name, state, constructor, methods, .. } => {
+                let mut ctx = LowerCtx::new();
+                ctx.result_counts = result_counts.clone();
+                ctx.functions = functions.clone();
+                ctx.assertions = assertions;
+                ctx.lines = lines;
+                lower_contract(name, state, constructor.as_deref(), methods, ctx, &mut module);
+            }
             _ => {
                 // Other statements not allowed at module level
             }
@@ -71,10 +208,147 @@ pub fn lower(stmts: &[Stmt]) -> IRModule {
     module
 }
 
+/// Lower a contract: state variables become module globals named
+/// `Contract.var`, and every method (including the constructor) becomes
+/// a function named `Contract.method` that reads/writes those globals.
+fn lower_contract(
+    name: &str,
+    state: &[String],
+    constructor: Option<&Stmt>,
+    methods: &[Stmt],
+    mut ctx: LowerCtx,
+    module: &mut IRModule,
+) {
+    let mut contract = IRContract { name: name.to_string(), state: Vec::new(), constructor: None, methods: Vec::new() };
+    for var in state {
+        let global = format!("{}.{}", name, var);
+        module.add_global(global.clone());
+        ctx.bind_global(var.clone(), global.clone());
+        contract.state.push(global);
+    }
+    ctx.in_contract = true;
+    
+    for method in constructor.into_iter().chain(methods.iter()) {
+        if let StmtKind::Function { name: method_name, params, param_types, defaults, body, attributes, conditions, .. } = &method.kind {
+            let qualified = format!("{}.{}", name, method_name);
+            let mut function = lower_function(&qualified, params, body, conditions, ctx.clone());
+            function.param_kinds = param_kinds(param_types, defaults);
+            function.attributes = attributes.clone();
+            function.span = method.span;
+            module.add_function(function);
+            if method_name == "constructor" {
+                contract.constructor = Some(qualified);
+            } else {
+                contract.methods.push(qualified);
+            }
+        }
+    }
+    module.contracts.push(contract);
+}
+
+/// The kind of each parameter: what its annotation names, or that of its
+/// default; without either, calls decide (see `kinds`)
+fn param_kinds(param_types: &[Option<String>], defaults: &[Option<Expr>]) -> Vec<Option<ValueKind>> {
+    param_types
+        .iter()
+        .enumerate()
+        .map(|(i, annotation)| match annotation.as_deref() {
+            Some(annotation) => match Type::from_annotation(annotation).map(|ty| crate::kinds::of_type(&ty)).as_deref() {
+                Some([kind]) => Some(*kind),
+                _ => Some(ValueKind::Int),
+            },
+            None => match defaults.get(i).and_then(Option::as_ref).map(|default| &default.kind) {
+                Some(ExprKind::Number(_)) => Some(ValueKind::Int),
+                Some(ExprKind::Float(_)) => Some(ValueKind::Float),
+                Some(ExprKind::Bool(_)) => Some(ValueKind::Bool),
+                Some(ExprKind::String(_)) => Some(ValueKind::Str),
+                _ => None,
+            },
+        })
+        .collect()
+}
+
+/// How many values each function returns, for those returning a tuple.
+/// Repeated until stable, since `return other()` takes on other's count.
+fn result_counts(stmts: &[Stmt]) -> HashMap<String, usize> {
+    let mut functions: Vec<(String, &[Stmt])> = Vec::new();
+    for stmt in stmts {
+        match &stmt.kind {
+            StmtKind::Function { name, body, .. } => functions.push((name.clone(), body)),
+            StmtKind::Contract { name, constructor, methods, .. } => {
+                for method in constructor.iter().map(|c| c.as_ref()).chain(methods.iter()) {
+                    if let StmtKind::Function { name: method_name, body, .. } = &method.kind {
+                        functions.push((format!("{}.{}", name, method_name), body));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut counts = HashMap::new();
+    loop {
+        let mut changed = false;
+        for (name, body) in &functions {
+            let mut ctx = LowerCtx::new();
+            ctx.result_counts = counts.clone();
+            if let Some(count) = returned_arity(body, &mut ctx).filter(|count| *count > 1) {
+                changed |= counts.insert(name.clone(), count) != Some(count);
+            }
+        }
+        if !changed {
+            return counts;
+        }
+    }
+}
+
+/// Arity of the first `return` in a body, tracking tuple variables on the way
+fn returned_arity(body: &[Stmt], ctx: &mut LowerCtx) -> Option<usize> {
+    for stmt in body {
+        match &stmt.kind {
+            StmtKind::Return(expr) => return Some(ctx.arity(expr)),
+            StmtKind::Value(expr) if ctx.arity(expr) > 0 => return Some(ctx.arity(expr)),
+            StmtKind::Let { name, value, .. } => {
+                let arity = ctx.arity(value);
+                if arity > 1 {
+                    ctx.alloc_tuple(name.clone(), arity);
+                } else {
+                    ctx.alloc(name.clone());
+                }
+            }
+            StmtKind::LetTuple { names, .. } => {
+                for name in names {
+                    ctx.alloc(name.clone());
+                }
+            }
+            StmtKind::If { then_body, else_body, .. } => {
+                let found = returned_arity(then_body, ctx)
+                    .or_else(|| returned_arity(else_body.as_deref().unwrap_or_default(), ctx));
+                if found.is_some() {
+                    return found;
+                }
+            }
+            StmtKind::While { body, .. } => {
+                if let Some(arity) = returned_arity(body, ctx) {
+                    return Some(arity);
+                }
+            }
+            StmtKind::For { var, body, .. } => {
+                ctx.alloc(var.clone());
+                if let Some(arity) = returned_arity(body, ctx) {
+                    return Some(arity);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
 /// Lower a single function to IR
-fn lower_function(name: &str, params: &[String], body: &[Stmt]) -> IRFunction {
+fn lower_function(name: &str, params: &[String], body: &[Stmt], conditions: &[Condition], mut ctx: LowerCtx) -> IRFunction {
     let mut function = IRFunction::new(name.to_string());
-    let mut ctx = LowerCtx::new();
+    function.result_count = ctx.result_counts.get(name).copied().unwrap_or(1);
     
     // STEP 46: Allocate slots for parameters first
     for param in params {
@@ -82,36 +356,104 @@ fn lower_function(name: &str, params: &[String], body: &[Stmt]) -> IRFunction {
     }
     function.param_count = params.len();
     
-    // Lower function body
-    for stmt in body {
-        lower_statement(stmt, &mut function, &mut ctx);
+    // Conditions checked at run time; #[ensures] sees the returned value as
+    // `result`, which a tuple has no one slot for
+    if ctx.assertions {
+        for condition in conditions.iter().filter(|c| c.kind == ConditionKind::Requires) {
+            lower_check(&condition.expr, ConditionKind::Requires, &mut function, &ctx);
+        }
+        let ensures = conditions.iter().filter(|c| c.kind == ConditionKind::Ensures);
+        ctx.ensures = ensures.map(|c| c.expr.clone()).collect();
+        if !ctx.ensures.is_empty() && function.result_count == 1 {
+            ctx.alloc("#result".to_string());
+        } else {
+            ctx.ensures.clear();
+        }
     }
     
+    // Lower function body
+    lower_block(body, &mut function, &mut ctx);
+    
     // Ensure function ends with return
     if function.instructions.is_empty() 
         || !matches!(function.instructions.last(), Some(IRInstr::Return)) {
-        function.add_instruction(IRInstr::LoadConstInt(0));
-        function.add_instruction(IRInstr::Return);
+        for _ in 0..function.result_count {
+            function.add_instruction(IRInstr::LoadConstInt(0));
+        }
+        lower_return(&mut function, &ctx);
+    }
+    
+    // The scratch slot `&&` and `||` keep their answer in, if used
+    let uses_logic = |instr: &IRInstr| matches!(instr, IRInstr::StoreLocal(LOGIC_SLOT) | IRInstr::LoadLocal(LOGIC_SLOT));
+    if function.instructions.iter().any(uses_logic) {
+        let slot = ctx.alloc("#logic".to_string());
+        for instr in &mut function.instructions {
+            match instr {
+                IRInstr::StoreLocal(s) | IRInstr::LoadLocal(s) if *s == LOGIC_SLOT => *s = slot,
+                _ => {}
+            }
+        }
     }
     
     // Store the number of locals in the function
     function.local_count = ctx.num_locals() as usize;
+    function.local_names = ctx.names;
     
     function
 }
 
+/// A for loop's iterable as the sequence it starts from and the `map` and
+/// `filter` stages applied to each item, innermost first, each with the
+/// function it names
+fn stages<'e>(iterable: &'e Expr, ctx: &LowerCtx) -> (&'e Expr, Vec<(&'e str, &'e str)>) {
+    match &iterable.kind {
+        ExprKind::Call(stage, args) if matches!(stage.as_str(), "map" | "filter") => match args.as_slice() {
+            [items, Expr { kind: ExprKind::Identifier(applied), .. }] if ctx.get(applied).is_none() => {
+                let (source, mut stages) = stages(items, ctx);
+                stages.push((stage.as_str(), applied.as_str()));
+                (source, stages)
+            }
+            _ => (iterable, Vec::new()),
+        },
+        _ => (iterable, Vec::new()),
+    }
+}
+
 /// Lower a single statement
 fn lower_statement(stmt: &Stmt, function: &mut IRFunction, ctx: &mut LowerCtx) {
-    match stmt {
-        Stmt::Import(_) => {
+    if ctx.lines {
+        function.add_instruction(IRInstr::Line(stmt.span.start_line, stmt.span.start_column));
+    }
+    match &stmt.kind {
+        StmtKind::Import { .. } => {
             // Imports are handled at module level, not lowered to IR
         }
-        Stmt::Expression(expr) => {
+        StmtKind::Expression(expr) => {
             lower_expression(expr, function, ctx);
             // Don't emit Pop - let the WASM codegen decide whether to drop based on context
             // In most cases, expression statements don't have their result used
         }
-        Stmt::Let { name, value } => {
+        StmtKind::Let { name, value, .. } if ctx.arity(value) > 1 => {
+            // A tuple lives in one slot per element; the last element is on
+            // top of the stack, so store in reverse
+            let arity = ctx.arity(value);
+            lower_expression(value, function, ctx);
+            for slot in ctx.alloc_tuple(name.clone(), arity).into_iter().rev() {
+                function.add_instruction(IRInstr::StoreLocal(slot));
+            }
+        }
+        StmtKind::LetTuple { names, value, .. } => {
+            lower_expression(value, function, ctx);
+            for name in names.iter().rev() {
+                if name == "_" {
+                    function.add_instruction(IRInstr::Pop);
+                } else {
+                    let slot = ctx.alloc(name.clone());
+                    function.add_instruction(IRInstr::StoreLocal(slot));
+                }
+            }
+        }
+        StmtKind::Let { name, value, .. } => {
             // Allocate a new local variable slot
             let slot = ctx.alloc(name.clone());
             
@@ -121,10 +463,16 @@ fn lower_statement(stmt: &Stmt, function: &mut IRFunction, ctx: &mut LowerCtx) {
             // Store to the local slot
             function.add_instruction(IRInstr::StoreLocal(slot));
         }
-        Stmt::Function { .. } => {
-            // Nested functions not supported yet
+        StmtKind::Function { .. } => {
+            // Lifted to module scope by `nested::lift` before lowering
+        }
+        StmtKind::Contract { .. } | StmtKind::Trait { .. } | StmtKind::Impl { .. } | StmtKind::TypeAlias { .. } => {
+            // Nested declarations not supported yet
+        }
+        StmtKind::Match { .. } => {
+            lower_unsupported("match", stmt.span, function);
         }
-        Stmt::If { condition, then_body, else_body } => {
+        StmtKind::If { condition, then_body, else_body } => {
             // Lower the condition expression
             lower_expression(condition, function, ctx);
             
@@ -133,9 +481,7 @@ fn lower_statement(stmt: &Stmt, function: &mut IRFunction, ctx: &mut LowerCtx) {
             function.add_instruction(IRInstr::JumpIfFalse(0));
             
             // Lower then body
-            for stmt in then_body {
-                lower_statement(stmt, function, ctx);
-            }
+            lower_block(then_body, function, ctx);
             
             // Check if we have an else body
             if let Some(else_body) = else_body {
@@ -150,9 +496,7 @@ fn lower_statement(stmt: &Stmt, function: &mut IRFunction, ctx: &mut LowerCtx) {
                 }
                 
                 // Lower else body
-                for stmt in else_body {
-                    lower_statement(stmt, function, ctx);
-                }
+                lower_block(else_body, function, ctx);
                 
                 // Patch Jump to jump here (after else body)
                 let end = function.instructions.len();
@@ -167,18 +511,27 @@ fn lower_statement(stmt: &Stmt, function: &mut IRFunction, ctx: &mut LowerCtx) {
                 }
             }
         }
-        Stmt::Assign { name, value } => {
+        StmtKind::Assign { name, value } => {
             // Get the slot for the variable (must be already allocated)
-            if let Some(slot) = ctx.get(name) {
+            if let Some(slots) = ctx.get_tuple(name).map(|slots| slots.to_vec()) {
+                lower_expression(value, function, ctx);
+                for slot in slots.into_iter().rev() {
+                    function.add_instruction(IRInstr::StoreLocal(slot));
+                }
+            } else if let Some(slot) = ctx.get(name) {
                 // Lower the right-hand side expression
                 lower_expression(value, function, ctx);
                 
                 // Store to the local slot
                 function.add_instruction(IRInstr::StoreLocal(slot));
+            } else if let Some(global) = ctx.get_global(name).cloned() {
+                // Contract state variable
+                lower_expression(value, function, ctx);
+                function.add_instruction(IRInstr::StoreGlobal(global));
             }
             // If variable not found, the type checker should have caught this
         }
-        Stmt::While { condition, body } => {
+        StmtKind::While { condition, body } => {
             // Mark the start of the loop
             let loop_start = function.instructions.len();
             
@@ -190,9 +543,7 @@ fn lower_statement(stmt: &Stmt, function: &mut IRFunction, ctx: &mut LowerCtx) {
             function.add_instruction(IRInstr::JumpIfFalse(0));
             
             // Lower loop body
-            for stmt in body {
-                lower_statement(stmt, function, ctx);
-            }
+            lower_block(body, function, ctx);
             
             // Add Jump back to loop start
             function.add_instruction(IRInstr::Jump(loop_start));
@@ -203,69 +554,361 @@ fn lower_statement(stmt: &Stmt, function: &mut IRFunction, ctx: &mut LowerCtx) {
                 *target = loop_end;
             }
         }
-        Stmt::Return(expr) => {
+        StmtKind::For { var, iterable, body } => {
+            let (source, stages) = stages(iterable, ctx);
+            if ctx.arity(source) != 2 {
+                // Only ranges lower to a counter; arrays have no WASM
+                // representation yet
+                lower_unsupported("array_loop", iterable.span, function);
+                return;
+            }
+            
+            // A range is its (start, end) pair: the loop variable is the
+            // counter, compared against a hidden copy of the end each time.
+            // Through map or filter, the counter is hidden too and the
+            // variable holds each item the stages let through.
+            let shadowed = ctx.get(var);
+            let end_slot = ctx.alloc(format!("{}.end", var));
+            let counter = match stages.is_empty() {
+                true => ctx.alloc(var.clone()),
+                false => ctx.alloc(format!("{}.index", var)),
+            };
+            lower_expression(source, function, ctx);
+            function.add_instruction(IRInstr::StoreLocal(end_slot));
+            function.add_instruction(IRInstr::StoreLocal(counter));
+            
+            let loop_start = function.instructions.len();
+            function.add_instruction(IRInstr::LoadLocal(counter));
+            function.add_instruction(IRInstr::LoadLocal(end_slot));
+            function.add_instruction(IRInstr::Lt);
+            let jump_if_false_index = function.instructions.len();
+            function.add_instruction(IRInstr::JumpIfFalse(0));
+            
+            let mut skips = Vec::new();
+            if !stages.is_empty() {
+                let item = ctx.alloc(var.clone());
+                function.add_instruction(IRInstr::LoadLocal(counter));
+                function.add_instruction(IRInstr::StoreLocal(item));
+                for (stage, applied) in stages {
+                    function.add_instruction(IRInstr::LoadLocal(item));
+                    function.add_instruction(call_instruction(applied, 1));
+                    if stage == "map" {
+                        function.add_instruction(IRInstr::StoreLocal(item));
+                    } else {
+                        skips.push(function.instructions.len());
+                        function.add_instruction(IRInstr::JumpIfFalse(0));
+                    }
+                }
+            }
+            
+            lower_block(body, function, ctx);
+            
+            // A filtered-out item skips straight to the next count
+            let next = function.instructions.len();
+            for skip in skips {
+                function.instructions[skip] = IRInstr::JumpIfFalse(next);
+            }
+            function.add_instruction(IRInstr::LoadLocal(counter));
+            function.add_instruction(IRInstr::LoadConstInt(1));
+            lower_arithmetic(IRInstr::Add, iterable.span, function);
+            function.add_instruction(IRInstr::StoreLocal(counter));
+            function.add_instruction(IRInstr::Jump(loop_start));
+            
+            let loop_end = function.instructions.len();
+            if let IRInstr::JumpIfFalse(ref mut target) = &mut function.instructions[jump_if_false_index] {
+                *target = loop_end;
+            }
+            
+            // The loop variable is scoped to the body
+            match shadowed {
+                Some(slot) => ctx.locals.insert(var.clone(), slot),
+                None => ctx.locals.remove(var),
+            };
+        }
+        StmtKind::Value(expr) if ctx.arity(expr) == 0 => {
+            // A final print(..) has no value to return; the function's
+            // default is returned after it
+            lower_expression(expr, function, ctx);
+        }
+        StmtKind::Return(expr) | StmtKind::Value(expr) if ctx.deferred.iter().any(|bodies| !bodies.is_empty()) => {
+            // Hold the returned values while every enclosing block's
+            // deferred code runs
+            let arity = ctx.arity(expr);
+            lower_expression(expr, function, ctx);
+            let slots = ctx.alloc_tuple("return".to_string(), arity);
+            for slot in slots.iter().rev() {
+                function.add_instruction(IRInstr::StoreLocal(*slot));
+            }
+            lower_deferred(function, ctx);
+            for slot in slots {
+                function.add_instruction(IRInstr::LoadLocal(slot));
+            }
+            lower_return(function, ctx);
+        }
+        StmtKind::Return(expr) | StmtKind::Value(expr) => {
             // STEP 46: Lower return statement
             lower_expression(expr, function, ctx);
-            function.add_instruction(IRInstr::Return);
+            lower_return(function, ctx);
+        }
+        StmtKind::Defer(body) => {
+            if let Some(bodies) = ctx.deferred.last_mut() {
+                bodies.push(body.clone());
+            }
+        }
+        StmtKind::Panic(expr) if !ctx.in_contract && ctx.deferred.iter().any(|bodies| !bodies.is_empty()) => {
+            // Likewise the message. A panic in a function this one calls
+            // ends the program without running them: WASM cannot unwind.
+            lower_expression(expr, function, ctx);
+            let slot = ctx.alloc("panic".to_string());
+            function.add_instruction(IRInstr::StoreLocal(slot));
+            lower_deferred(function, ctx);
+            function.add_instruction(IRInstr::LoadLocal(slot));
+            function.add_instruction(IRInstr::Panic);
         }
-        Stmt::Panic(expr) => {
+        StmtKind::Panic(expr) => {
             // STEP 48: Lower panic statement
             // Evaluate the error message expression
             lower_expression(expr, function, ctx);
-            // Emit panic instruction (message is on stack)
-            function.add_instruction(IRInstr::Panic);
+            // Emit panic instruction (message is on stack). In a contract
+            // the failure reverts the transaction, with the message as reason
+            function.add_instruction(if ctx.in_contract { IRInstr::Revert } else { IRInstr::Panic });
+        }
+    }
+}
+
+/// Lower a block, then the code it deferred, last first. A block ending
+/// in `return` ran it before returning.
+fn lower_block(body: &[Stmt], function: &mut IRFunction, ctx: &mut LowerCtx) {
+    ctx.deferred.push(Vec::new());
+    for stmt in body {
+        lower_statement(stmt, function, ctx);
+    }
+    let deferred = ctx.deferred.pop().unwrap_or_default();
+    let returned = match body.last().map(|stmt| &stmt.kind) {
+        Some(StmtKind::Return(_)) => true,
+        Some(StmtKind::Value(expr)) => ctx.arity(expr) > 0,
+        _ => false,
+    };
+    if !returned {
+        for body in deferred.iter().rev() {
+            lower_block(body, function, ctx);
+        }
+    }
+}
+
+/// Lower the code every enclosing block has deferred, innermost and last
+/// deferred first, for the function to exit here
+fn lower_deferred(function: &mut IRFunction, ctx: &mut LowerCtx) {
+    let deferred: Vec<Vec<Stmt>> = ctx.deferred.iter().rev().flat_map(|bodies| bodies.iter().rev()).cloned().collect();
+    for body in &deferred {
+        lower_block(body, function, ctx);
+    }
+}
+
+/// Return the value on the stack, first checking the function's #[ensures]
+/// with it as `result`
+fn lower_return(function: &mut IRFunction, ctx: &LowerCtx) {
+    if let Some(slot) = ctx.get("#result").filter(|_| !ctx.ensures.is_empty()) {
+        function.add_instruction(IRInstr::StoreLocal(slot));
+        let mut checking = ctx.clone();
+        checking.locals.insert("result".to_string(), slot);
+        for condition in &ctx.ensures {
+            lower_check(condition, ConditionKind::Ensures, function, &checking);
         }
+        function.add_instruction(IRInstr::LoadLocal(slot));
     }
+    function.add_instruction(IRInstr::Return);
+}
+
+/// Trap unless `condition` holds, saying which condition failed and where
+/// it was written; in a contract the failure reverts
+fn lower_check(condition: &Expr, kind: ConditionKind, function: &mut IRFunction, ctx: &LowerCtx) {
+    lower_expression(condition, function, ctx);
+    let holds = function.instructions.len() + 4;
+    function.add_instruction(IRInstr::Not);
+    function.add_instruction(IRInstr::JumpIfFalse(holds));
+    function.add_instruction(IRInstr::LoadConstString(format!("#[{}] failed ({})", kind.name(), condition.span)));
+    function.add_instruction(if ctx.in_contract { IRInstr::Revert } else { IRInstr::Panic });
+}
+
+/// Record a construct compiled builds cannot express yet, by its
+/// `E0501.<construct>` catalog entry, trapping in its place with its
+/// message and where it was written
+fn lower_unsupported(construct: &'static str, span: Span, function: &mut IRFunction) {
+    let unsupported = Unsupported { construct, span };
+    let message = crate::messages::render("E0501", &[&unsupported.name()]);
+    function.add_instruction(IRInstr::LoadConstString(format!("{} ({})", message, span)));
+    function.add_instruction(IRInstr::Panic);
+    function.unsupported.push(unsupported);
+}
+
+/// `value[range]` on a string: the string, the range's bounds, then the
+/// runtime's `str.substr`
+fn lower_substr(value: &Expr, range: &Expr, function: &mut IRFunction, ctx: &LowerCtx) {
+    lower_expression(value, function, ctx);
+    lower_expression(range, function, ctx);
+    function.add_instruction(IRInstr::CallStd("str.substr".to_string()));
 }
 
 /// Lower an expression
+/// Add `instr`, an Add, Sub or Mul, remembering where it was written so a
+/// checked build can say where it overflowed
+fn lower_arithmetic(instr: IRInstr, span: Span, function: &mut IRFunction) {
+    function.arithmetic.push(span);
+    function.add_instruction(instr);
+}
+
 fn lower_expression(expr: &Expr, function: &mut IRFunction, ctx: &LowerCtx) {
-    match expr {
-        Expr::Number(n) => {
+    match &expr.kind {
+        ExprKind::Number(n) => {
             function.add_instruction(IRInstr::LoadConstInt(*n));
         }
-        Expr::Float(f) => {
+        ExprKind::Float(f) => {
             function.add_instruction(IRInstr::LoadConstFloat(*f));
         }
-        Expr::Bool(b) => {
+        ExprKind::Bool(b) => {
             function.add_instruction(IRInstr::LoadConstBool(*b));
         }
-        Expr::String(s) => {
+        ExprKind::String(s) => {
             function.add_instruction(IRInstr::LoadConstString(s.clone()));
         }
-        Expr::Identifier(name) => {
+        ExprKind::Tuple(items) => {
+            // Each element stays on the stack: a multi-value
+            for item in items {
+                lower_expression(item, function, ctx);
+            }
+        }
+        // The checker only lets `as` convert between Int and Float
+        ExprKind::Cast(inner, target) => {
+            lower_expression(inner, function, ctx);
+            function.add_instruction(if target == "Float" { IRInstr::IntToFloat } else { IRInstr::FloatToInt });
+        }
+        ExprKind::Array(_) => {
+            lower_unsupported("array_literal", expr.span, function);
+        }
+        ExprKind::Range(start, end) => {
+            // Never materialized: a range is its two bounds on the stack
+            lower_expression(start, function, ctx);
+            lower_expression(end, function, ctx);
+        }
+        ExprKind::Call(name, args) if name == "range" && args.len() == 2 => {
+            for bound in args {
+                lower_expression(bound, function, ctx);
+            }
+        }
+        // A for loop fuses these into its counter (see `stages`); anywhere
+        // else they would need arrays
+        ExprKind::Call(name, _) if matches!(name.as_str(), "map" | "filter" | "collect") => {
+            lower_unsupported("iterator", expr.span, function);
+        }
+        ExprKind::Slice(value, range) => {
+            // Constant slices of string literals fold at compile time, the
+            // rest of a string's slices take its bytes at run time
+            match (&value.kind, &range.kind) {
+                (ExprKind::String(s), ExprKind::Range(start, end)) => match (&start.kind, &end.kind) {
+                    (ExprKind::Number(start), ExprKind::Number(end))
+                        if 0 <= *start && start <= end && (*end as usize) <= s.chars().count() =>
+                    {
+                        let (start, end) = (*start as usize, *end as usize);
+                        let sliced = s.chars().skip(start).take(end - start).collect();
+                        function.add_instruction(IRInstr::LoadConstString(sliced));
+                    }
+                    _ => lower_substr(value, range, function, ctx),
+                },
+                // A map's fields and a range's sub-ranges need their runtimes
+                (_, ExprKind::String(_)) | (ExprKind::Range(..), _) => {
+                    lower_unsupported("slice", expr.span, function)
+                }
+                _ => lower_substr(value, range, function, ctx),
+            }
+        }
+        ExprKind::Identifier(name) if name == "None" => {
+            lower_unsupported("option", expr.span, function);
+        }
+        ExprKind::Call(name, _) if matches!(name.as_str(), "Some" | "Ok" | "Err") => {
+            lower_unsupported("option", expr.span, function);
+        }
+        ExprKind::Try(_) => {
+            lower_unsupported("try", expr.span, function);
+        }
+        ExprKind::Identifier(name) => {
             // Check if it's a local variable first
-            if let Some(slot) = ctx.get(name) {
+            if let Some(slots) = ctx.get_tuple(name) {
+                for slot in slots {
+                    function.add_instruction(IRInstr::LoadLocal(*slot));
+                }
+            } else if let Some(slot) = ctx.get(name) {
                 function.add_instruction(IRInstr::LoadLocal(slot));
+            } else if let Some(global) = ctx.get_global(name) {
+                function.add_instruction(IRInstr::LoadGlobal(global.clone()));
+            } else if ctx.functions.contains(name) {
+                // A function named without calling it, to call later
+                function.add_instruction(IRInstr::LoadFunction(name.clone()));
             } else {
                 // Fallback to named variable (for backward compatibility)
                 function.add_instruction(IRInstr::LoadVar(name.clone()));
             }
         }
-        Expr::Call(name, args) => {
-            // Lower arguments first (left to right)
+        ExprKind::Named(_, value) => {
+            // `arguments::resolve` passed it by position; one left here was
+            // reported by the checker
+            lower_expression(value, function, ctx);
+        }
+        // A local's properties are an AIResult's label and score
+        ExprKind::Property(object, _) if ctx.get(object).is_some() => {
+            lower_unsupported("ai_result", expr.span, function);
+        }
+        ExprKind::Property(object, name) => {
+            function.add_instruction(IRInstr::LoadProperty(format!("{}.{}", object, name)));
+        }
+        // A local holding a function: the arguments, then the reference
+        ExprKind::Call(name, args) if ctx.get(name).is_some() => {
             for arg in args {
                 lower_expression(arg, function, ctx);
             }
-            
-            // STEP 53: Check if this is a Web3 function
-            if is_web3_function(name) {
-                function.add_instruction(IRInstr::CallWeb3(name.clone()));
-            }
-            // STEP 52: Check if this is an AI function
-            else if is_ai_function(name) {
-                function.add_instruction(IRInstr::CallAI(name.clone()));
-            }
-            // Check if this is a stdlib function
-            else if is_stdlib_function(name) {
+            lower_expression(&Expr::new(ExprKind::Identifier(name.clone()), expr.span), function, ctx);
+            function.add_instruction(IRInstr::CallIndirect(args.len()));
+        }
+        // The handler goes by name, as the VM calls functions; WASM
+        // builds drop it, their hosts delivering no signals
+        ExprKind::Call(name, args) if name == "on_shutdown" => {
+            if let Some(Expr { kind: ExprKind::Identifier(handler), .. }) = args.first() {
+                function.add_instruction(IRInstr::LoadConstString(handler.clone()));
                 function.add_instruction(IRInstr::CallStd(name.clone()));
-            } else {
-                function.add_instruction(IRInstr::Call(name.clone(), args.len()));
             }
         }
+        ExprKind::Call(name, _) if name == "StringBuilder" => {
+            lower_unsupported("string_builder", expr.span, function);
+        }
+        ExprKind::Call(name, args) => {
+            // Lower arguments first (left to right)
+            for arg in args {
+                lower_expression(arg, function, ctx);
+            }
+            
+            function.add_instruction(call_instruction(name, args.len()));
+        }
         
+        // WASM values are 64-bit, too narrow for 256-bit arithmetic
+        ExprKind::ModuleCall(module_name, _, _) if module_name == "u256" => {
+            lower_unsupported("u256", expr.span, function);
+        }
+        // Proofs are arrays, which WASM builds cannot hold yet
+        ExprKind::ModuleCall(module_name, _, _) if crate::defi::abi::is_protocol(module_name) => {
+            lower_unsupported("defi", expr.span, function);
+        }
+        ExprKind::ModuleCall(module_name, _, _) if module_name == "merkle" => {
+            lower_unsupported("merkle", expr.span, function);
+        }
+        // A local's array or map methods; WASM builds have neither
+        ExprKind::ModuleCall(receiver, method, _)
+            if ctx.get(receiver).is_some() && matches!(method.as_str(), "push" | "pop" | "set" | "copy") =>
+        {
+            lower_unsupported("array_method", expr.span, function);
+        }
+
         // STEP 49: Module-qualified function call: module.function(args)
-        Expr::ModuleCall(module_name, func_name, args) => {
+        ExprKind::ModuleCall(module_name, func_name, args) => {
             // Lower arguments first (left to right)
             for arg in args {
                 lower_expression(arg, function, ctx);
@@ -274,80 +917,110 @@ fn lower_expression(expr: &Expr, function: &mut IRFunction, ctx: &LowerCtx) {
             // Generate fully qualified function name: module.func
             let qualified_name = format!("{}.{}", module_name, func_name);
             
-            // STEP 52: Check if this is an AI function
-            if is_ai_function(&qualified_name) {
-                function.add_instruction(IRInstr::CallAI(qualified_name));
-            }
-            // STEP 53: Check if this is a Web3 function
-            else if is_web3_function(&qualified_name) {
-                function.add_instruction(IRInstr::CallWeb3(qualified_name));
-            }
-            // STEP 54: Check if this is a file system function
-            else if is_fs_function(&qualified_name) {
-                function.add_instruction(IRInstr::CallFS(qualified_name));
-            } else {
-                function.add_instruction(IRInstr::Call(qualified_name, args.len()));
-            }
+            function.add_instruction(call_instruction(&qualified_name, args.len()));
+        }
+        
+        // WASM has no task runtime: async functions run eagerly, so awaiting
+        // a call is the call itself
+        ExprKind::Await(inner) => {
+            lower_expression(inner, function, ctx);
         }
         
         // Binary arithmetic operators (STEP 43)
-        Expr::Add(left, right) => {
+        ExprKind::Add(left, right) => {
             lower_expression(left, function, ctx);
             lower_expression(right, function, ctx);
-            function.add_instruction(IRInstr::Add);
+            lower_arithmetic(IRInstr::Add, expr.span, function);
         }
-        Expr::Sub(left, right) => {
+        ExprKind::Sub(left, right) => {
             lower_expression(left, function, ctx);
             lower_expression(right, function, ctx);
-            function.add_instruction(IRInstr::Sub);
+            lower_arithmetic(IRInstr::Sub, expr.span, function);
         }
-        Expr::Mul(left, right) => {
+        ExprKind::Mul(left, right) => {
             lower_expression(left, function, ctx);
             lower_expression(right, function, ctx);
-            function.add_instruction(IRInstr::Mul);
+            lower_arithmetic(IRInstr::Mul, expr.span, function);
         }
-        Expr::Div(left, right) => {
+        ExprKind::Div(left, right) => {
             lower_expression(left, function, ctx);
             lower_expression(right, function, ctx);
             function.add_instruction(IRInstr::Div);
         }
-        Expr::Mod(left, right) => {
+        ExprKind::Mod(left, right) => {
             lower_expression(left, function, ctx);
             lower_expression(right, function, ctx);
             function.add_instruction(IRInstr::Mod);
         }
         
         // Binary comparison operators (STEP 43)
-        Expr::Eq(left, right) => {
+        ExprKind::Eq(left, right) => {
             lower_expression(left, function, ctx);
             lower_expression(right, function, ctx);
             function.add_instruction(IRInstr::Eq);
         }
-        Expr::Ne(left, right) => {
+        ExprKind::Ne(left, right) => {
             lower_expression(left, function, ctx);
             lower_expression(right, function, ctx);
             function.add_instruction(IRInstr::Ne);
         }
-        Expr::Lt(left, right) => {
+        ExprKind::Lt(left, right) => {
             lower_expression(left, function, ctx);
             lower_expression(right, function, ctx);
             function.add_instruction(IRInstr::Lt);
         }
-        Expr::Le(left, right) => {
+        ExprKind::Le(left, right) => {
             lower_expression(left, function, ctx);
             lower_expression(right, function, ctx);
             function.add_instruction(IRInstr::Le);
         }
-        Expr::Gt(left, right) => {
+        ExprKind::Gt(left, right) => {
             lower_expression(left, function, ctx);
             lower_expression(right, function, ctx);
             function.add_instruction(IRInstr::Gt);
         }
-        Expr::Ge(left, right) => {
+        ExprKind::Ge(left, right) => {
             lower_expression(left, function, ctx);
             lower_expression(right, function, ctx);
             function.add_instruction(IRInstr::Ge);
         }
+        
+        // The left side is the answer unless it leaves it open: false for
+        // `&&`, true for `||`. Only then does the right side run and
+        // replace it. Nothing stays on the stack across the jump.
+        ExprKind::And(left, right) | ExprKind::Or(left, right) => {
+            lower_expression(left, function, ctx);
+            function.add_instruction(IRInstr::StoreLocal(LOGIC_SLOT));
+            function.add_instruction(IRInstr::LoadLocal(LOGIC_SLOT));
+            if matches!(expr.kind, ExprKind::Or(..)) {
+                function.add_instruction(IRInstr::Not);
+            }
+            let decided = function.instructions.len();
+            function.add_instruction(IRInstr::JumpIfFalse(0));
+            lower_expression(right, function, ctx);
+            function.add_instruction(IRInstr::StoreLocal(LOGIC_SLOT));
+            let end = function.instructions.len();
+            if let IRInstr::JumpIfFalse(ref mut target) = &mut function.instructions[decided] {
+                *target = end;
+            }
+            function.add_instruction(IRInstr::LoadLocal(LOGIC_SLOT));
+        }
+    }
+}
+
+/// The call instruction for `name`: runtime-provided functions get their
+/// own instruction, everything else is a user function
+pub(crate) fn call_instruction(name: &str, arg_count: usize) -> IRInstr {
+    if is_web3_function(name) {
+        IRInstr::CallWeb3(name.to_string()) // STEP 53
+    } else if is_ai_function(name) {
+        IRInstr::CallAI(name.to_string()) // STEP 52
+    } else if is_fs_function(name) {
+        IRInstr::CallFS(name.to_string()) // STEP 54
+    } else if is_stdlib_function(name) && !crate::bootstrap::defines(name) {
+        IRInstr::CallStd(name.to_string())
+    } else {
+        IRInstr::Call(name.to_string(), arg_count)
     }
 }
 
@@ -378,13 +1051,20 @@ mod tests {
     
     #[test]
     fn test_lower_empty_function() {
-        let stmts = vec![Stmt::Function {
+        let stmts = vec![Stmt::new(StmtKind::Function {
             name: "test".to_string(),
             params: vec![],
+            param_types: vec![],
+            defaults: vec![],
+            type_params: vec![],
+            return_annotation: None,
             return_type: Type::Void,
             body: vec![],
             exported: false,
-        }];
+            is_async: false,
+            attributes: vec![],
+            conditions: vec![],
+        }, Span::default())];
         
         let module = lower(&stmts);
         assert_eq!(module.functions.len(), 1);
@@ -399,20 +1079,34 @@ mod tests {
     #[test]
     fn test_lower_multiple_functions() {
         let stmts = vec![
-            Stmt::Function {
+            Stmt::new(StmtKind::Function {
                 name: "foo".to_string(),
                 params: vec![],
+                param_types: vec![],
+                defaults: vec![],
+                type_params: vec![],
+                return_annotation: None,
                 return_type: Type::Void,
                 body: vec![],
                 exported: false,
-            },
-            Stmt::Function {
+                is_async: false,
+                attributes: vec![],
+                conditions: vec![],
+            }, Span::default()),
+            Stmt::new(StmtKind::Function {
                 name: "bar".to_string(),
                 params: vec![],
+                param_types: vec![],
+                defaults: vec![],
+                type_params: vec![],
+                return_annotation: None,
                 return_type: Type::Void,
                 body: vec![],
                 exported: false,
-            },
+                is_async: false,
+                attributes: vec![],
+                conditions: vec![],
+            }, Span::default()),
         ];
         
         let module = lower(&stmts);
@@ -420,4 +1114,313 @@ mod tests {
         assert_eq!(module.functions[0].name, "foo");
         assert_eq!(module.functions[1].name, "bar");
     }
+    
+    #[test]
+    fn test_lower_contract_state_and_methods() {
+        let source = r#"
+            contract Token {
+                state: ["total_supply"]
+                state owner: Address
+                constructor(supply) {
+                    total_supply = supply
+                }
+                fn supply() {
+                    return total_supply
+                }
+            }
+        "#;
+        let stmts = crate::parser::Parser::new(crate::lexer::Lexer::new(source))
+            .parse()
+            .unwrap();
+        
+        let module = lower(&stmts);
+        assert_eq!(module.globals, vec!["Token.total_supply", "Token.owner"]);
+        assert_eq!(module.functions[0].name, "Token.constructor");
+        assert_eq!(module.functions[1].name, "Token.supply");
+        assert!(module.functions[0]
+            .instructions
+            .contains(&IRInstr::StoreGlobal("Token.total_supply".to_string())));
+        assert!(module.functions[1]
+            .instructions
+            .contains(&IRInstr::LoadGlobal("Token.total_supply".to_string())));
+    }
+    
+    #[test]
+    fn test_lower_property_to_host_import() {
+        let source = "fn main() {\n    let who = msg.sender\n    return who\n}\n";
+        let stmts = crate::parser::Parser::new(crate::lexer::Lexer::new(source))
+            .parse()
+            .unwrap();
+        
+        let module = lower(&stmts);
+        assert!(module.functions[0]
+            .instructions
+            .contains(&IRInstr::LoadProperty("msg.sender".to_string())));
+        
+        let wat = crate::codegen::wasm::generate_wasm_module(&module);
+        assert!(wat.contains("(import \"env\" \"msg_sender\" (func $msg_sender (result i32)))"));
+        assert!(wat.contains("call $msg_sender"));
+    }
+    
+    #[test]
+    fn test_lower_tuple_return_and_destructuring() {
+        let source = r#"
+            fn minmax(a, b) {
+                if a < b {
+                    return (a, b)
+                }
+                return (b, a)
+            }
+            fn forward() {
+                let pair = minmax(3, 1)
+                return pair
+            }
+            fn main() {
+                let (low, _) = forward()
+                return low
+            }
+        "#;
+        let stmts = crate::parser::Parser::new(crate::lexer::Lexer::new(source))
+            .parse()
+            .unwrap();
+        
+        let module = lower(&stmts);
+        let counts: Vec<usize> = module.functions.iter().map(|f| f.result_count).collect();
+        assert_eq!(counts, vec![2, 2, 1]);
+        
+        // `pair` takes two slots, loaded back in order
+        let forward = &module.functions[1].instructions;
+        assert!(forward.ends_with(&[IRInstr::LoadLocal(0), IRInstr::LoadLocal(1), IRInstr::Return]));
+        
+        // Destructuring pops the skipped last element, then stores the first
+        let main = &module.functions[2].instructions;
+        assert_eq!(&main[..3], &[
+            IRInstr::Call("forward".to_string(), 0),
+            IRInstr::Pop,
+            IRInstr::StoreLocal(0),
+        ]);
+    }
+    
+    #[test]
+    fn test_lower_for_range_to_counter() {
+        let source = r#"
+            fn sum(n) {
+                let mut total = 0
+                for i in 0..n {
+                    total = total + i
+                }
+                return total
+            }
+        "#;
+        let stmts = crate::parser::Parser::new(crate::lexer::Lexer::new(source))
+            .parse()
+            .unwrap();
+        
+        // n, total, the hidden end and i: no array is ever built
+        let sum = &lower(&stmts).functions[0];
+        assert_eq!(sum.local_count, 4);
+        assert_eq!(&sum.instructions[2..], &[
+            IRInstr::LoadConstInt(0),
+            IRInstr::LoadLocal(0),
+            IRInstr::StoreLocal(2),
+            IRInstr::StoreLocal(3),
+            IRInstr::LoadLocal(3),
+            IRInstr::LoadLocal(2),
+            IRInstr::Lt,
+            IRInstr::JumpIfFalse(19),
+            IRInstr::LoadLocal(1),
+            IRInstr::LoadLocal(3),
+            IRInstr::Add,
+            IRInstr::StoreLocal(1),
+            IRInstr::LoadLocal(3),
+            IRInstr::LoadConstInt(1),
+            IRInstr::Add,
+            IRInstr::StoreLocal(3),
+            IRInstr::Jump(6),
+            IRInstr::LoadLocal(1),
+            IRInstr::Return,
+        ]);
+    }
+    
+    #[test]
+    fn test_lower_conditions_only_with_assertions() {
+        let source = r#"
+            #[requires(n > 0)]
+            #[ensures(result < n)]
+            fn half(n) {
+                return n / 2
+            }
+        "#;
+        let stmts = crate::parser::Parser::new(crate::lexer::Lexer::new(source))
+            .parse()
+            .unwrap();
+        
+        let messages = |ir: &IRModule| -> Vec<String> {
+            ir.functions[0].instructions.iter().filter_map(|instr| match instr {
+                IRInstr::LoadConstString(message) => Some(message.clone()),
+                _ => None,
+            }).collect()
+        };
+        assert!(messages(&lower(&stmts)).is_empty());
+        
+        // The check on entry, then the one before returning
+        let checked = lower_with(&stmts, true, false);
+        let messages = messages(&checked);
+        assert_eq!(messages.len(), 2);
+        assert!(messages[0].starts_with("#[requires] failed (line 2"));
+        assert!(messages[1].starts_with("#[ensures] failed (line 3"));
+        assert_eq!(checked.functions[0].instructions.iter().filter(|i| **i == IRInstr::Panic).count(), 2);
+    }
+    
+    #[test]
+    fn test_lower_map_and_filter_into_the_counter() {
+        let source = r#"
+            fn square(n) {
+                return n * n
+            }
+            fn is_odd(n) {
+                return n % 2 == 1
+            }
+            fn total(n) {
+                let mut total = 0
+                for x in filter(map(range(0, n), square), is_odd) {
+                    total = total + x
+                }
+                return total
+            }
+        "#;
+        let stmts = crate::parser::Parser::new(crate::lexer::Lexer::new(source))
+            .parse()
+            .unwrap();
+        
+        // n, total, the hidden end and index, and x: still no array
+        let module = lower(&stmts);
+        let total = module.functions.iter().find(|f| f.name == "total").unwrap();
+        assert_eq!(total.local_count, 5);
+        assert_eq!(&total.instructions[6..22], &[
+            IRInstr::LoadLocal(3),
+            IRInstr::LoadLocal(2),
+            IRInstr::Lt,
+            IRInstr::JumpIfFalse(27),
+            IRInstr::LoadLocal(3),
+            IRInstr::StoreLocal(4),
+            IRInstr::LoadLocal(4),
+            IRInstr::Call("square".to_string(), 1),
+            IRInstr::StoreLocal(4),
+            IRInstr::LoadLocal(4),
+            IRInstr::Call("is_odd".to_string(), 1),
+            IRInstr::JumpIfFalse(22),
+            IRInstr::LoadLocal(1),
+            IRInstr::LoadLocal(4),
+            IRInstr::Add,
+            IRInstr::StoreLocal(1),
+        ]);
+        assert_eq!(total.instructions[22], IRInstr::LoadLocal(3));
+    }
+    
+    #[test]
+    fn test_lower_require_reverts_in_contracts() {
+        let source = r#"
+            contract Vault {
+                state balance
+                fn withdraw(amount) {
+                    require(amount <= balance, "insufficient balance")
+                    balance = balance - amount
+                }
+            }
+            fn check(n) {
+                require(n > 0, "n must be positive")
+                return n
+            }
+        "#;
+        let stmts = crate::parser::Parser::new(crate::lexer::Lexer::new(source))
+            .parse()
+            .unwrap();
+        let module = crate::opt::optimize_module(&lower(&stmts));
+        let function = |name: &str| module.functions.iter().find(|f| f.name == name).unwrap();
+        
+        // The failing branch ends the call but not the function: the write
+        // after the check survives dead code elimination
+        let withdraw = &function("Vault.withdraw").instructions;
+        assert!(withdraw.contains(&IRInstr::Revert) && !withdraw.contains(&IRInstr::Panic));
+        assert!(withdraw.contains(&IRInstr::StoreGlobal("Vault.balance".to_string())));
+        
+        let check = &function("check").instructions;
+        assert!(check.contains(&IRInstr::Panic) && !check.contains(&IRInstr::Revert));
+        assert_eq!(check[check.len() - 2..], [IRInstr::LoadLocal(0), IRInstr::Return]);
+    }
+    
+    #[test]
+    fn test_lower_defer_before_each_exit() {
+        let source = r#"
+            fn close(n) {
+                return n
+            }
+            fn work(n) {
+                defer close(1)
+                if n > 0 {
+                    defer close(2)
+                    return n
+                }
+                defer close(3)
+            }
+        "#;
+        let stmts = crate::parser::Parser::new(crate::lexer::Lexer::new(source))
+            .parse()
+            .unwrap();
+        let module = lower(&stmts);
+        let close = |n| [IRInstr::LoadConstInt(n), IRInstr::Call("close".to_string(), 1)];
+        
+        // The early return holds n while both blocks' deferred calls run,
+        // innermost first; falling off the end runs the outer block's
+        let work = &module.functions[1].instructions;
+        assert_eq!(work[4..6], [IRInstr::LoadLocal(0), IRInstr::StoreLocal(1)]);
+        assert_eq!(work[6..10], [close(2), close(1)].concat());
+        assert_eq!(work[10..12], [IRInstr::LoadLocal(1), IRInstr::Return]);
+        assert_eq!(work[12..16], [close(3), close(1)].concat());
+        assert_eq!(work[16..], [IRInstr::LoadConstInt(0), IRInstr::Return]);
+
+        // A panic holds its message while deferred blocks run
+        let source = "fn close(n) {\n    return n\n}\nfn fail(n) {\n    defer {\n        close(n)\n    }\n    panic(\"no\")\n}\n";
+        let module = lower(&crate::parser::Parser::new(crate::lexer::Lexer::new(source)).parse().unwrap());
+        assert_eq!(
+            module.functions[1].instructions[..6],
+            [
+                IRInstr::LoadConstString("no".to_string()),
+                IRInstr::StoreLocal(1),
+                IRInstr::LoadLocal(0),
+                IRInstr::Call("close".to_string(), 1),
+                IRInstr::LoadLocal(1),
+                IRInstr::Panic,
+            ]
+        );
+
+        // A final print(..) under a defer runs before it, and the function
+        // still returns a value of each of its result kinds
+        let source = "fn pick(x) {\n    defer {\n        print(\"bye\")\n    }\n    if x > 0 {\n        return \"big\"\n    }\n    print(\"small\")\n}\nfn main() {\n    defer {\n        print(\"bye\")\n    }\n    print(pick(1))\n    print(\"end\")\n}\n";
+        let module = lower(&crate::parser::Parser::new(crate::lexer::Lexer::new(source)).parse().unwrap());
+        let wat = crate::codegen::wasm::generate_wasm_module(&module);
+        crate::codegen::binary::validate(&crate::codegen::binary::assemble(&wat).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_unsupported_constructs_fail_the_build() {
+        let source = "fn main() {\n    let items = [1, 2]\n    return sum(items)\n}\n";
+        let stmts = crate::parser::Parser::new(crate::lexer::Lexer::new(source)).parse().unwrap();
+        let errors = lower_for_build(&stmts, false, false).unwrap_err();
+        let reported: Vec<(&str, usize, usize)> = errors.iter().map(|e| (e.message.as_str(), e.line, e.column)).collect();
+        assert_eq!(
+            reported,
+            [
+                ("an array literal is not supported in compiled builds yet", 2, 17),
+                ("a for loop over an array is not supported in compiled builds yet, in the standard library's 'sum'", 0, 0),
+            ]
+        );
+        assert!(errors.iter().all(|e| e.code == Some("E0501")));
+
+        // What compiled builds can express lowers as before
+        let source = "fn main() {\n    return min(1, 2)\n}\n";
+        let stmts = crate::parser::Parser::new(crate::lexer::Lexer::new(source)).parse().unwrap();
+        assert!(lower_for_build(&stmts, false, false).is_ok());
+    }
 }
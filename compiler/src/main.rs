@@ -1,38 +1,30 @@
 use std::env;
 use std::fs;
 
-mod lexer;
-mod parser;
-mod token;
-mod ast;
-mod types;
-mod typechecker;
-mod error;
-mod diagnostics;
-mod ir;
-mod lowering;
-mod opt;
-mod codegen;
-mod stdlib;
-mod loader;  // STEP 49: Module loader
-
-use lexer::Lexer;
-use parser::Parser;
-use typechecker::TypeChecker;
-use diagnostics::display_error;
-use lowering::lower;
-use opt::optimize_module;
-use codegen::wasm;
+use astrixa::lexer::Lexer;
+use astrixa::parser::Parser;
+use astrixa::typechecker::TypeChecker;
+use astrixa::diagnostics::{display_error, display_errors, display_warnings};
+use astrixa::lowering::lower_for_build;
+use astrixa::opt::{self, optimize_module_with, Level};
+use astrixa::profile::Profile;
+use astrixa::codegen::{binary, wasm};
+use astrixa::{overflow, unused};
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let args: Vec<String> = env::args().skip(1).collect();
+    // --release builds as `astrixa build --release` does, then shrinks the
+    // module as far as it goes
+    let release = args.iter().any(|arg| arg == "--release");
+    let profile = Profile::for_mode(release);
+    let file = args.iter().find(|arg| !arg.starts_with("--"));
     
-    let (source, input_file) = if args.len() > 1 {
+    let (source, input_file) = if let Some(file) = file {
         // Read from file if provided
-        match fs::read_to_string(&args[1]) {
-            Ok(content) => (content, Some(args[1].clone())),
+        match fs::read_to_string(file) {
+            Ok(content) => (content, Some(file.clone())),
             Err(e) => {
-                eprintln!("Error reading file '{}': {}", args[1], e);
+                eprintln!("Error reading file '{}': {}", file, e);
                 std::process::exit(1);
             }
         }
@@ -56,17 +48,34 @@ fn main() {
             match checker.check(&ast) {
                 Ok(()) => {
                     println!("✅ Type check passed");
+                    display_warnings(&unused::analyze(&ast));
                     
                     // Lowering phase: AST → IR
-                    let ir = lower(&ast);
+                    let mut ir = match lower_for_build(&ast, profile.assertions, false) {
+                        Ok(ir) => ir,
+                        Err(errors) => {
+                            eprintln!("❌ Cannot compile:");
+                            display_errors(&errors);
+                            std::process::exit(1);
+                        }
+                    };
+                    if profile.overflow_checks {
+                        overflow::check_arithmetic(&mut ir);
+                    }
                     println!("\n📊 IR Module (before optimization):");
                     println!("  Functions: {}", ir.functions.len());
                     for func in &ir.functions {
                         println!("  - {} ({} instructions)", func.name, func.instructions.len());
                     }
                     
-                    // Optimization phase: Apply optimization passes
-                    let optimized_ir = optimize_module(&ir);
+                    // Optimization phase: the profile's passes, none at -O0
+                    let mut optimized_ir = match profile.opt_level {
+                        Level::O0 => ir.clone(),
+                        level => optimize_module_with(&ir, level.passes()),
+                    };
+                    if release {
+                        opt::remove_dead_functions(&mut optimized_ir);
+                    }
                     println!("\n🚀 IR Module (after optimization):");
                     println!("  Functions: {}", optimized_ir.functions.len());
                     for func in &optimized_ir.functions {
@@ -75,9 +84,25 @@ fn main() {
                     
                     // Code generation phase: IR → WASM
                     println!("\n🧬 WASM Code Generation:");
-                    let wasm_module = wasm::generate_wasm_module(&optimized_ir);
+                    let wasm_module = if release {
+                        wasm::generate_release_module(&optimized_ir)
+                    } else {
+                        wasm::generate_wasm_module(&optimized_ir)
+                    };
                     println!("  Generated WebAssembly (WAT format):\n");
                     println!("{}", wasm_module);
+
+                    // What the release path saved, against the module as lowered
+                    if release {
+                        let size = |wat: &str| binary::assemble(wat).map(|bytes| bytes.len());
+                        match (size(&wasm::generate_wasm_module(&ir)), size(&wasm_module)) {
+                            (Ok(before), Ok(after)) => {
+                                let saved = 100.0 * (before as f64 - after as f64) / before.max(1) as f64;
+                                println!("\n📦 Module size: {} → {} bytes ({:.1}% smaller)", before, after, saved);
+                            }
+                            (Err(e), _) | (_, Err(e)) => eprintln!("Could not measure the module: {}", e),
+                        }
+                    }
                     
                     // Save output file if input was provided
                     if let Some(input) = input_file {
@@ -93,10 +118,9 @@ fn main() {
                     }
                 }
                 Err(errors) => {
-                    println!("❌ Type check failed:");
-                    for error in errors {
-                        println!("  - {}", error);
-                    }
+                    eprintln!("❌ Type check failed:");
+                    display_errors(&errors);
+                    std::process::exit(1);
                 }
             }
         }
@@ -0,0 +1,165 @@
+use crate::interpreter::Value;
+use crate::logs::{hex, keccak256, message_bytes, unhex};
+use crate::messages;
+
+// Merkle trees over keccak256, the `merkle` module of scripts.
+//
+//   let leaves = ["0xaa...01:100", "0xaa...02:250"]
+//   let root = merkle.root(leaves)
+//   let proof = merkle.proof(leaves, 1)
+//   merkle.verify(root, "0xaa...02:250", proof)   // true
+//
+// A leaf is hashed before it enters the tree: a 0x-prefixed hex string as
+// the bytes it spells, any other string as its UTF-8 bytes, and a number as
+// its 32-byte big-endian word. Pairs of nodes are sorted before they are
+// hashed together, as OpenZeppelin's MerkleProof expects, so a proof is
+// just the sibling hashes from the leaf up and a root computed here can be
+// checked on-chain. A level with an odd node carries it up unhashed.
+//
+// Building trees and proofs is off-chain work: contracts may only call
+// `merkle.verify`, which reads nothing but its arguments.
+
+/// Hash a leaf into the tree, if it is a string or a non-negative number
+pub fn leaf(value: &Value) -> Option<[u8; 32]> {
+    let word = |n: crate::u256::U256| {
+        let mut word = [0u8; 32];
+        n.to_big_endian(&mut word);
+        word
+    };
+    let bytes = match value {
+        Value::String(s) | Value::Address(s) => message_bytes(s),
+        Value::Number(n) if *n >= 0 => word((*n as u64).into()).to_vec(),
+        Value::U256(n) => word(*n).to_vec(),
+        _ => return None,
+    };
+    Some(keccak256(&bytes))
+}
+
+/// The parent of two nodes, which does not depend on their order
+pub fn parent(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    let (low, high) = if a <= b { (a, b) } else { (b, a) };
+    let mut pair = [0u8; 64];
+    pair[..32].copy_from_slice(low);
+    pair[32..].copy_from_slice(high);
+    keccak256(&pair)
+}
+
+/// Every level of the tree, from the hashed leaves up to the root
+fn levels(leaves: &[[u8; 32]]) -> Vec<Vec<[u8; 32]>> {
+    let mut levels = vec![leaves.to_vec()];
+    while levels.last().is_some_and(|level| level.len() > 1) {
+        let level = levels.last().expect("at least one level");
+        let next = level
+            .chunks(2)
+            .map(|pair| match pair {
+                [a, b] => parent(a, b),
+                [odd] => *odd,
+                _ => unreachable!("chunks of two"),
+            })
+            .collect();
+        levels.push(next);
+    }
+    levels
+}
+
+pub fn root(leaves: &[[u8; 32]]) -> Option<[u8; 32]> {
+    levels(leaves).last().and_then(|level| level.first()).copied()
+}
+
+/// The siblings on the path from leaf `index` to the root
+pub fn proof(leaves: &[[u8; 32]], index: usize) -> Option<Vec<[u8; 32]>> {
+    if index >= leaves.len() {
+        return None;
+    }
+    let mut index = index;
+    let mut siblings = Vec::new();
+    for level in levels(leaves).iter().filter(|level| level.len() > 1) {
+        if let Some(sibling) = level.get(index ^ 1) {
+            siblings.push(*sibling);
+        }
+        index /= 2;
+    }
+    Some(siblings)
+}
+
+pub fn verify(root: &[u8; 32], leaf: &[u8; 32], proof: &[[u8; 32]]) -> bool {
+    proof.iter().fold(*leaf, |node, sibling| parent(&node, sibling)) == *root
+}
+
+/// Call `merkle.<function>` with `args`
+pub fn call(function: &str, args: &[Value]) -> Result<Value, String> {
+    let usage = |expected: &str| messages::render("E0469", &[&function, &expected]);
+    let hashed = |leaves: &[Value]| {
+        let hashes: Option<Vec<_>> = leaves.iter().map(leaf).collect();
+        hashes.ok_or_else(|| usage("leaves that are strings or non-negative numbers"))
+    };
+    match (function, args) {
+        ("root", [Value::Array(leaves)]) => {
+            let root = root(&hashed(&leaves.borrow())?).ok_or_else(|| usage("at least one leaf"))?;
+            Ok(Value::String(format!("0x{}", hex(&root))))
+        }
+        ("proof", [Value::Array(leaves), Value::Number(index)]) => {
+            let hashes = hashed(&leaves.borrow())?;
+            let proof = usize::try_from(*index)
+                .ok()
+                .and_then(|i| proof(&hashes, i))
+                .ok_or_else(|| messages::render("E0470", &[index, &leaves.borrow().len()]))?;
+            Ok(Value::array(proof.iter().map(|node| Value::String(format!("0x{}", hex(node)))).collect()))
+        }
+        ("verify", [root @ Value::String(_), value, Value::Array(siblings)]) => {
+            // A malformed root or proof proves nothing rather than failing
+            let node = |value: &Value| match value {
+                Value::String(s) => unhex(s).ok().and_then(|bytes| <[u8; 32]>::try_from(bytes).ok()),
+                _ => None,
+            };
+            let siblings: Option<Vec<[u8; 32]>> = siblings.borrow().iter().map(node).collect();
+            let valid = match (node(root), siblings) {
+                (Some(root), Some(siblings)) => {
+                    let leaf = leaf(value).ok_or_else(|| usage("a leaf that is a string or a non-negative number"))?;
+                    verify(&root, &leaf, &siblings)
+                }
+                _ => false,
+            };
+            Ok(Value::Bool(valid))
+        }
+        ("root", _) => Err(usage("an array of leaves")),
+        ("proof", _) => Err(usage("(leaves, index)")),
+        ("verify", _) => Err(usage("(root, leaf, proof)")),
+        _ => Err(messages::render("E0406", &[&format!("merkle.{}", function)])),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaves(n: usize) -> Vec<Value> {
+        (0..n).map(|i| Value::String(format!("account-{}:{}", i, i * 100))).collect()
+    }
+
+    #[test]
+    fn test_every_leaf_proves_against_the_root() {
+        // Odd sizes carry a node up unhashed at some level
+        for n in 1..=7 {
+            let leaves = leaves(n);
+            let root = call("root", &[Value::array(leaves.clone())]).unwrap();
+            for (i, leaf) in leaves.iter().enumerate() {
+                let proof = call("proof", &[Value::array(leaves.clone()), Value::Number(i as i64)]).unwrap();
+                let valid = call("verify", &[root.clone(), leaf.clone(), proof.clone()]).unwrap();
+                assert_eq!(valid, Value::Bool(true), "leaf {} of {}", i, n);
+                let forged = call("verify", &[root.clone(), Value::String("account-9:900".to_string()), proof]).unwrap();
+                assert_eq!(forged, Value::Bool(false));
+            }
+        }
+
+        // Two leaves: the root is the sorted pair of their hashes
+        let (a, b) = (keccak256(b"a"), keccak256(b"b"));
+        assert_eq!(root(&[b, a]), Some(parent(&a, &b)));
+        assert_eq!(proof(&[a, b], 0), Some(vec![b]));
+
+        assert!(call("root", &[Value::array(vec![])]).is_err());
+        assert!(call("proof", &[Value::array(leaves(2)), Value::Number(2)]).is_err());
+        let malformed = call("verify", &[Value::String("0x12".to_string()), Value::String("a".to_string()), Value::array(vec![])]);
+        assert_eq!(malformed, Ok(Value::Bool(false)));
+    }
+}
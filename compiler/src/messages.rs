@@ -0,0 +1,861 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::sync::RwLock;
+
+// Diagnostic message catalog.
+//
+// Every user-facing diagnostic has a stable code; its text is looked up here
+// so messages can be translated and embedders can replace them. Codes:
+//   E01xx parser, E02xx type checker, E03xx module loader, E04xx runtime,
+//   E05xx code generation,
+//   W00xx edition deprecation warnings, W01xx hot reload, W02xx contract
+//   gas analysis, W03xx unused code
+// A `<code>.help` entry, when present, is attached as the error's help text;
+// a `<code>.suggestion` entry is help the reporter attaches when it has one.
+// Templates use positional placeholders: {0}, {1}, ...
+
+const EN: &[(&str, &str)] = &[
+    // Parser
+    ("E0101", "Expected module name after 'import'"),
+    ("E0101.help", "Example: import math"),
+    ("E0102", "Expected 'fn' after 'export'"),
+    ("E0102.help", "Only functions can be exported. Example: export fn add(a, b) { }"),
+    ("E0103", "Expected 'fn' after 'async'"),
+    ("E0103.help", "Example: async fn fetch_data() { }"),
+    ("E0104", "Expected function name"),
+    ("E0104.help", "Function names must be valid identifiers"),
+    ("E0105", "Expected parameter name"),
+    ("E0106", "Expected contract name after 'contract'"),
+    ("E0106.help", "Example: contract Token { }"),
+    ("E0107", "Expected '{' after contract name"),
+    ("E0108", "State variable '{0}' is declared more than once in contract '{1}'"),
+    ("E0109", "Contract '{0}' already has a constructor"),
+    ("E0109.help", "A contract may declare at most one constructor"),
+    ("E0110", "Expected 'state', 'constructor' or 'fn' inside contract body"),
+    ("E0111", "Expected '}' after contract body"),
+    ("E0112", "Expected '[' after 'state:'"),
+    ("E0112.help", "Example: state: [\"balances\", \"total_supply\"]"),
+    ("E0113", "Expected state variable name"),
+    ("E0114", "Expected ']' after state variable list"),
+    ("E0115", "Expected state variable name after 'state'"),
+    ("E0115.help", "Example: state owner: Address"),
+    ("E0116", "Expected type name after ':'"),
+    ("E0117", "Unterminated type arguments, expected '>'"),
+    ("E0118", "Expected variable name after 'let'"),
+    ("E0119", "Expected '=' after variable name"),
+    ("E0120", "Expected '{' after if condition"),
+    ("E0121", "Expected '}' after if body"),
+    ("E0122", "Expected '{' after else"),
+    ("E0122.help", "Use 'else { ... }' or chain another condition with 'else if'"),
+    ("E0123", "Expected '}' after else body"),
+    ("E0124", "Expected '(' after 'panic'"),
+    ("E0125", "Expected ')' after panic message"),
+    ("E0126", "Expected '{' after while condition"),
+    ("E0127", "Expected '}' after while body"),
+    ("E0128", "Expected function name after '.'"),
+    ("E0129", "Expected '(' after module function name"),
+    ("E0130", "Unexpected token in expression"),
+    ("E0131", "Expected '{' after '.' in import"),
+    ("E0131.help", "Example: import math.{add, sub}"),
+    ("E0132", "Expected function name in import list"),
+    ("E0133", "Expected alias name after 'as'"),
+    ("E0133.help", "Example: import math as m"),
+    ("E0134", "Expected '[' and an attribute name after '#'"),
+    ("E0134.help", "Example: #[test]"),
+    ("E0135", "Unknown attribute '#[{0}]'"),
+    ("E0135.help", "Supported attributes: {1}"),
+    ("E0136", "Expected ']' after attribute name"),
+    ("E0137", "Attributes must be followed by a function declaration"),
+    ("E0138", "Expected variable name or '_' in tuple pattern"),
+    ("E0138.help", "Example: let (public, private) = generate_keypair()"),
+    ("E0139", "Expected ',' or ')' in tuple"),
+    ("E0140", "Unknown escape sequence '\\{0}'"),
+    ("E0140.help", "Supported escapes: \\n \\r \\t \\0 \\\\ \\\" \\' \\xHH \\u{...}. For regexes, use a raw string: r\"\\d+\""),
+    ("E0141", "Invalid hex escape '\\x{0}'"),
+    ("E0141.help", "\\x takes exactly two hex digits, from \\x00 to \\x7F; use \\u{...} for other characters"),
+    ("E0142", "Invalid unicode escape '\\u{{0}}'"),
+    ("E0142.help", "Write 1 to 6 hex digits in braces naming a Unicode character, e.g. \\u{1F600}"),
+    ("E0143", "Unterminated string literal"),
+    ("E0143.help", "Close the string with '\"' (a raw string r#\"...\"# ends with '\"#')"),
+    ("E0144", "Expected loop variable name after 'for'"),
+    ("E0145", "Expected 'in' after the loop variable"),
+    ("E0145.help", "Example: for i in 0..10 { print(i) }"),
+    ("E0146", "Expected '{' after for range"),
+    ("E0147", "Expected '}' after for body"),
+    ("E0148", "Expected ']' after slice range"),
+    ("E0149", "Expected '(' after 'require'"),
+    ("E0149.help", "Example: require(amount > 0, \"amount must be positive\")"),
+    ("E0150", "Expected ',' and a message after the require condition"),
+    ("E0150.help", "require takes a condition and the message to fail with: require(amount > 0, \"amount must be positive\")"),
+    ("E0151", "Expected ')' after require message"),
+    ("E0152", "Unexpected character '{0}'"),
+    ("E0153", "Expected a type parameter name"),
+    ("E0153.help", "Type parameters are names between '<' and '>': fn max<T>(a: T, b: T) -> T"),
+    ("E0154", "Trait method '{0}' cannot have a body"),
+    ("E0154.help", "A trait lists signatures; write the body in each impl"),
+    ("E0155", "Expected a name after '{0}'"),
+    ("E0156", "Expected 'for' and a type after the trait name"),
+    ("E0156.help", "Example: impl Printable for Int { ... }"),
+    ("E0157", "Expected '{' to open the body of '{0}'"),
+    ("E0158", "Expected 'fn' inside the body of '{0}'"),
+    ("E0159", "Expected '}' after the body of '{0}'"),
+    ("E0160", "Expected '{' after the match value"),
+    ("E0161", "Expected a pattern: Some(name), None, Ok(name), Err(name) or _"),
+    ("E0161.help", "Each arm is a pattern, '=>' and a body: Some(x) => x,"),
+    ("E0162", "Expected '=>' after the pattern '{0}'"),
+    ("E0163", "Expected '}' to close the match"),
+    ("E0164", "Expected ',' or ']' in array"),
+    ("E0165", "Default value of parameter '{0}' must be a literal"),
+    ("E0165.help", "Use a number, string or bool; compute other defaults in the body"),
+    ("E0166", "Parameter '{0}' needs a default, since a parameter before it has one"),
+    ("E0166.help", "Give '{0}' a default, or move the parameters with defaults to the end"),
+    ("E0167", "Expected a condition in parentheses after #[{0}]"),
+    ("E0167.help", "Example: #[{0}(amount > 0)]"),
+    ("E0168", "Expected '=' and a type after 'type {0}'"),
+    ("E0169", "'{0}' is a reserved word and cannot be a name since edition {1}"),
+    ("E0169.help", "Rename it, e.g. to '{0}_'; 'astrixa migrate' renames every use"),
+    ("E0170", "'{0}' is not a valid name: names cannot start with a digit"),
+    ("E0170.help", "Start it with a letter or '_', e.g. '_{0}'"),
+    ("E0171", "Integer literal {0} is too large (the largest Int is {1})"),
+    ("E0172", "Expected '}' after defer body"),
+    ("E0173", "Unknown path '{0}'"),
+    ("E0173.help", "Standard library functions have paths like std::io::print, the same as print"),
+    ("E0174", "Struct declarations are not supported yet"),
+    ("E0174.help", "Group the values in a tuple or a map for now; generic structs will follow struct declarations"),
+    // Type checker
+    ("E0201", "Type error: inconsistent return types in function '{0}' (found {1})"),
+    ("E0202", "Type error: if condition must be Bool, got {0}"),
+    ("E0203", "Type error: variable '{0}' not defined"),
+    ("E0203.suggestion", "Did you mean '{0}'?"),
+    ("E0204", "Type mismatch: cannot assign {0} to variable of type {1}"),
+    ("E0205", "Type error: while condition must be Bool, got {0}"),
+    ("E0206", "Type error: panic() requires a string message, got {0}"),
+    ("E0207", "Type error: function '{0}' expects {1} arguments, got {2}"),
+    ("E0208", "Type error: argument {0} of function '{1}' expects {2}, got {3}"),
+    ("E0209", "Type error: cannot add {0} and {1} (both operands must be Int, both Float or both String)"),
+    ("E0210", "Type error: arithmetic operator requires two Int or two Float operands (% takes Int only), got {0} and {1}"),
+    ("E0211", "Type error: comparison operator requires Int or U256 operands, or two Float ones, got {0} and {1}"),
+    ("E0212", "Type error: #[{0}] on '{1}' only applies to contract methods"),
+    ("E0213", "Type error: '{0}' cannot be both #[payable] and #[view]"),
+    ("E0214", "Type error: #[view] method '{0}' assigns state variable '{1}'"),
+    ("E0215", "Type error: #[test] function '{0}' must not take parameters"),
+    ("E0216", "Type error: #[{0}] on '{1}' only applies to top-level functions"),
+    ("E0217", "Type error: cannot destructure {0} into {1} variables"),
+    ("E0218", "Type error: tuple has {0} elements but the pattern binds {1}"),
+    ("E0219", "Type error: tuples cannot contain other tuples"),
+    ("E0220", "Parameter '{0}' of '{1}' has type '{2}', which has no ABI encoding"),
+    ("E0220.help", "Contract method parameters can be Int, U256, Address, Bool, String or Bytes"),
+    ("E0221", "'{0}' returns {1}, which has no ABI encoding"),
+    ("E0222", "Type error: range bounds must be Int, got {0} and {1}"),
+    ("E0223", "Type error: cannot iterate over {0}"),
+    ("E0223.help", "for loops count through a range, e.g. for i in 0..10"),
+    ("E0224", "Type error: cannot slice {0}"),
+    ("E0225", "Type error: slices take a range, got {0}"),
+    ("E0225.help", "Example: name[0..3]"),
+    ("E0226", "Type error: #[invariant] method '{0}' must not take parameters"),
+    ("E0227", "Type error: #[invariant] method '{0}' assigns state variable '{1}'"),
+    ("E0227.help", "Invariants only check state; `astrixa fuzz` calls them after every transaction"),
+    ("E0228", "Type error: type parameter '{0}' of '{1}' is {2} here but {3} in an earlier argument"),
+    ("E0229", "Type error: '{0}' is declared to return {1} but returns {2}"),
+    ("E0230", "Type error: contract method '{0}' cannot have type parameters"),
+    ("E0230.help", "ABI entries need concrete types; move the generic code into a function the method calls"),
+    ("E0231", "Type error: method '{0}' of trait '{1}' must take self as its first parameter"),
+    ("E0232", "Type error: cannot implement '{0}' for '{1}'"),
+    ("E0232.help", "Traits can be implemented for Int, Float, Bool and String"),
+    ("E0233", "Type error: unknown trait '{0}'"),
+    ("E0233.help", "Declare the trait before implementing it"),
+    ("E0234", "Type error: impl of '{0}' for {1} is missing method '{2}'"),
+    ("E0235", "Type error: method '{0}' is not part of trait '{1}'"),
+    ("E0236", "Type error: method '{0}' takes {1} parameters in trait '{2}' but {3} here"),
+    ("E0237", "Type error: method '{0}' is already implemented for {1}"),
+    ("E0238", "Type error: no method '{0}' for {1}"),
+    ("E0238.help", "Implement a trait declaring '{0}' for {1}"),
+    ("E0239", "Type error: '?' needs an Option or Result, got {0}"),
+    ("E0239.help", "Wrap the value in Some(..) or Ok(..), or drop the '?'"),
+    ("E0240", "Type error: '?' in function '{0}' returning {1} cannot propagate {2}"),
+    ("E0240.help", "Return an Option to use '?' on an Option, or a Result to use it on a Result"),
+    ("E0241", "Type error: pattern '{0}' never matches {1}"),
+    ("E0242", "Type error: match does not cover {0}"),
+    ("E0242.help", "Add an arm for {0}, or a '_' arm"),
+    ("E0243", "Type error: cannot assign twice to immutable variable '{0}'"),
+    ("E0243.help", "Declare it with 'let mut {0}' to allow reassignment"),
+    ("E0244", "Type error: malformed EIP-712 types '{0}': {1}"),
+    ("E0244.help", "Write each struct as Name(type field,type field), primary type first, e.g. Mail(Person from,string contents)Person(string name,address wallet)"),
+    ("E0245", "Type error: unknown property '{0}.{1}'"),
+    ("E0245.help", "Properties are chain.id, chain.name, msg.sender, msg.value, msg.data, tx.hash, tx.value and tx.timestamp; call a module function with parentheses"),
+    ("E0246", "Type error: invalid CID '{0}': {1}"),
+    ("E0247", "Type error: nested function '{0}' uses '{1}' from the enclosing function '{2}'"),
+    ("E0247.help", "Nested functions do not capture variables; pass '{1}' to '{0}' as a parameter"),
+    ("E0248", "Type error: '{0}' has no parameter named '{1}'"),
+    ("E0249", "Type error: argument '{1}' is passed to '{0}' more than once"),
+    ("E0250", "Type error: missing argument '{1}' in call to '{0}'"),
+    ("E0250.help", "Pass it by position or as {1}: value, or give the parameter a default"),
+    ("E0251", "Type error: positional argument after a named argument in call to '{0}'"),
+    ("E0251.help", "Pass arguments by position first, then by name"),
+    ("E0252", "Type error: '{0}' does not take arguments by name"),
+    ("E0252.help", "Only functions declared in the program take named arguments; pass these by position"),
+    ("E0253", "Type error: the u256 module has no function '{0}'"),
+    ("E0253.help", "Available: from, addmod, mulmod, shl, shr, saturating_add, saturating_sub, saturating_mul"),
+    ("E0254", "Type error: contracts cannot call merkle.{0}"),
+    ("E0254.help", "Build trees and proofs off-chain; contracts can only check them with merkle.verify(root, leaf, proof)"),
+    ("E0255", "Type error: '?' cannot be used in deferred code"),
+    ("E0255.help", "Deferred code runs as its block exits and cannot return early; handle the None or Err with match"),
+    ("E0256", "Type error: module '{0}' has no function '{1}'"),
+    ("E0257", "Type error: cannot add {0} and {1}; convert the {2} to a String with to_string() first"),
+    ("E0257.help", "Example: \"total: \" + to_string(total)"),
+    ("E0258", "Type error: cannot compare {0} with {1}"),
+    ("E0258.help", "Both sides of == and != have one type; Int compares with U256 and Address with String"),
+    ("E0259", "Type error: transfer sends to an Address, not {0}"),
+    ("E0260", "Type error: transfer sends an Int or U256 amount, not {0}"),
+    ("E0261", "Type error: an AIResult has a label and a score, not '{0}'"),
+    ("E0262", "Type error: #[{0}] on '{1}' must be a Bool condition, got {2}"),
+    ("E0263", "Type error: 'as' converts between Int and Float, not {0} to {1}"),
+    ("E0263.help", "Parse a number from text with int(text), and format any value with str(value)"),
+    ("E0264", "Type error: int() reads a String, Int or Float, not {0}"),
+    ("E0265", "Type error: {0}() takes a function of one argument, as in {0}(items, double), not {1}"),
+    ("E0266", "Type error: '{0}' already names a type"),
+    ("E0267", "Type error: type alias '{0}' stands for itself"),
+    ("E0268", "Type error: on_shutdown() takes a function of no arguments, as in on_shutdown(cleanup), not {0}"),
+    ("E0269", "Type error: 'return' cannot be used in deferred code"),
+    ("E0269.help", "Deferred code runs as its block exits, once what the function returns is settled"),
+    ("E0270", "Type error: '{0}' is in the prelude, which #[no_prelude] leaves out"),
+    ("E0270.help", "Import it by name with 'import prelude.{{0}}', or declare it"),
+    ("E0271", "Type error: '{0}' requires Bool operands, got {1} and {2}"),
+    ("E0271.help", "Compare an Int to get a Bool, as in 'count > 0 && ready'"),
+    // Module loader
+    ("E0301", "Failed to read module '{0}': {1}"),
+    ("E0302", "Module '{0}' not found in search paths"),
+    ("E0302.help", "Searched for '{1}' in: {2}"),
+    ("E0303", "Module '{0}' has no function '{1}'"),
+    ("E0304", "'{0}' imported from module '{1}' collides with '{0}' from module '{2}'"),
+    ("E0304.help", "Import one of them selectively, or use an alias: import {1} as ..."),
+    ("E0305", "'{0}' imported from module '{1}' collides with a function defined in this file"),
+    ("E0305.help", "Rename the local function, or import the module with an alias: import {1} as ..."),
+    ("E0306", "Import alias '{0}' is already used for module '{1}'"),
+    ("E0307", "'{1}' is private to module '{0}'"),
+    ("E0307.help", "Declare it as 'export fn {1}' in {0}.ax to use it from other modules"),
+    // Runtime
+    ("E0401", "main function not found"),
+    ("E0402", "variable '{0}' not defined"),
+    ("E0403", "Panic: {0}"),
+    ("E0404", "condition must be boolean"),
+    ("E0405", "while condition must be boolean"),
+    ("E0406", "function '{0}' not defined"),
+    ("E0407", "division by zero"),
+    ("E0408", "invalid operation"),
+    ("E0409", "spawn() expects a call to an async function"),
+    ("E0410", "argument missing"),
+    ("E0411", "task awaited itself (cyclic await)"),
+    ("E0412", "unknown task #{0}"),
+    ("E0413", "task #{0} was never scheduled"),
+    ("E0414", "unknown property '{0}.{1}'"),
+    ("E0415", "ai.model() requires at least one argument"),
+    ("E0416", "{0}() requires a string argument"),
+    ("E0417", "ai.infer() requires two arguments: model and input"),
+    ("E0418", "ai.infer() input must be a string"),
+    ("E0419", "{0}() requires a text argument"),
+    ("E0420", "Unknown AI method: ai.{0}"),
+    ("E0421", "Cannot read package module '{0}'"),
+    ("E0422", "Cannot find module '{0}' (tried package and local file)"),
+    ("E0423", "Cannot find module '{0}'"),
+    ("E0424", "Error in module '{0}': {1}"),
+    ("E0425", "len() expects array or string"),
+    ("E0426", "range() expects two numbers"),
+    ("E0427", "sleep() expects a number of milliseconds"),
+    ("E0428", "module '{0}' has no function '{1}'"),
+    ("E0429", "'{0}' imported from module '{1}' collides with an existing '{0}'"),
+    ("E0430", "import alias '{0}' is already used for module '{1}'"),
+    ("E0431", "'{1}' is private to module '{0}'; declare it with 'export fn' to use it outside the module"),
+    ("E0432", "cannot destructure {0} into {1} variables"),
+    ("E0433", "tuple has {0} elements but the pattern binds {1}"),
+    ("E0434", "range bounds must be numbers"),
+    ("E0435", "cannot iterate over {0}"),
+    ("E0436", "cannot slice {0} with {1}"),
+    ("E0437", "slice {0}..{1} is out of bounds for length {2}"),
+    ("E0438", "cannot convert between a Rust value and a script value: {0}"),
+    ("E0439", "no field '{0}' in map"),
+    ("E0440", "web3.logs(contract, event, from_block, to_block) expects an address, an event signature and a block range"),
+    ("E0441", "cannot parse event signature '{0}'; write it as 'Name(type indexed name, type name)'"),
+    ("E0442", "web3.logs needs an RPC node; set ASTRIXA_RPC_URL"),
+    ("E0443", "fetching logs failed: {0}"),
+    ("E0444", "cannot decode {0} log: {1}"),
+    ("E0445", "'?' returned {0} outside a function"),
+    ("E0446", "'?' needs an Option or Result, got {0}"),
+    ("E0447", "no match arm for {0}"),
+    ("E0448", "{0}() takes 1 argument, got {1}"),
+    ("E0449", "web3.{0} expects (domain, types, value, key?) to sign or (domain, types, value, signature, signer) to verify, with types as a string"),
+    ("E0450", "cannot encode typed data: {0}"),
+    ("E0451", "web3.sign_typed needs a private key; pass one or set ASTRIXA_PRIVATE_KEY"),
+    ("E0452", "web3.multicall expects an array of (contract, function, args...) tuples"),
+    ("E0453", "cannot parse function signature '{0}'; write it as 'name(type, type) returns (type)'"),
+    ("E0454", "cannot encode the arguments of {0}: {1}"),
+    ("E0455", "web3.multicall needs an RPC node; set ASTRIXA_RPC_URL"),
+    ("E0456", "multicall failed: {0}"),
+    ("E0457", "unknown ipfs function '{0}'; use add, get, cid or valid"),
+    ("E0458", "ipfs.{0} expects one string"),
+    ("E0459", "invalid CID '{0}': {1}"),
+    ("E0460", "ipfs.add needs an IPFS API; set ASTRIXA_IPFS_API"),
+    ("E0461", "IPFS request failed: {0}"),
+    ("E0462", "invalid fork '{0}'; write it as <rpc_url>@<block>"),
+    ("E0463", "reading {0} from the fork failed: {1}"),
+    ("E0464", "'{0}' is not a 20-byte address"),
+    ("E0465", "'{0}' is not a hex word of at most 32 bytes"),
+    ("E0466", "web3.{0} expects {1}"),
+    ("E0467", "u256.{0} expects {1}"),
+    ("E0468", "{0} is not a 256-bit unsigned integer"),
+    ("E0469", "merkle.{0} expects {1}"),
+    ("E0470", "leaf {0} is out of range for a tree of {1} leaves"),
+    ("E0471", "ed25519.{0} expects {1}"),
+    ("E0472", "bls.{0} expects {1}"),
+    ("E0473", "{0} is not enabled in this build; rebuild with --features {1}"),
+    ("E0474", "{0} expects the contract's address, then {1} arguments"),
+    ("E0475", "{0} reads the chain and needs an RPC node; set ASTRIXA_RPC_URL"),
+    ("E0476", "'{0}' is neither calldata nor a transaction hash"),
+    ("E0477", "no known ABI has a function with selector 0x{0}"),
+    ("E0478", "the arguments of {0} could not be decoded: {1}"),
+    ("E0479", "transaction {0} could not be fetched: {1}"),
+    ("E0480", "{0}.{1}() expects {2}"),
+    ("E0481", "index {0} is out of bounds for length {1}"),
+    ("E0482", "an array or map cannot contain itself"),
+    ("E0483", "cannot convert {0} to {1}"),
+    ("E0484", "'{0}' is not an integer"),
+    ("E0485", "{0}(items, function) expects a range, an array or an iterator, then a declared function"),
+    ("E0486", "filter() expects its function to return a bool"),
+    ("E0487", "collect() expects a range, an array or an iterator, not {0}"),
+    ("E0488", "Interrupted by {0}"),
+    ("E0489", "on_shutdown() expects a declared function, not {0}"),
+    ("E0490", "'&&' and '||' require boolean operands"),
+    ("E0491", "stack underflow; the bytecode is malformed"),
+    ("E0492", "function '{0}' takes {1} arguments; it cannot be the entry point"),
+    ("E0493", "out of gas: used {0} gas, limit was {1} gas"),
+    ("E0494", "cannot index {0} with {1}"),
+    ("E0495", "malformed bytecode: {0} has the invalid operand '{1}'"),
+    // Code generation; each construct E0501 names is an `E0501.<construct>` entry
+    ("E0501", "{0} is not supported in compiled builds yet"),
+    ("E0501.help", "The interpreter runs it; compiled builds do not support it yet"),
+    ("E0501.std", "{0}, in the standard library's '{1}'"),
+    ("E0501.match", "match"),
+    ("E0501.array_loop", "a for loop over an array"),
+    ("E0501.array_literal", "an array literal"),
+    ("E0501.iterator", "an iterator outside a for loop's iterable"),
+    ("E0501.slice", "slicing a map or a range"),
+    ("E0501.option", "an Option or Result value"),
+    ("E0501.try", "the '?' operator"),
+    ("E0501.ai_result", "an AI result's property"),
+    ("E0501.string_builder", "a string builder"),
+    ("E0501.u256", "u256 arithmetic"),
+    ("E0501.defi", "a defi binding"),
+    ("E0501.merkle", "a merkle proof"),
+    ("E0501.array_method", "an array or map method"),
+    ("E0502", "'{0}' ({1}) is async, and the VM has no task scheduler; async functions run only in the interpreter"),
+    // Edition deprecations
+    ("W0001", "println() is deprecated since edition {0}; use print()"),
+    ("W0002", "The list form of 'state' is deprecated since edition {0}; declare one variable per 'state'"),
+    ("W0002.help", "Example: state balances"),
+    ("W0003", "'{0}' is a reserved word; as a name it is an error from edition {1}"),
+    ("W0003.help", "Rename it to '{0}_' ('astrixa migrate' does this everywhere)"),
+    // Hot reload
+    ("W0101", "'{0}' changed its signature; calls using the old one will fail"),
+    ("W0102", "'{0}' was removed; calls to it will fail"),
+    ("W0103", "state of contract '{0}' changed (added: {1}; removed: {2}); removed variables lost their values"),
+    // Gas analysis
+    ("W0201", "loop in {0} is bounded by user-controlled '{1}'; at about {2} gas per iteration, {3} iterations exceed the block gas limit of {4}"),
+    ("W0201.help", "Bound the loop by a constant or process the data in batches; mark the method #[unbounded] if the pattern is intended"),
+    ("W0202", "loop in {0} runs {1} times at about {2} gas each, {3} gas in all, over the block gas limit of {4}"),
+    ("W0202.help", "Split the work across several calls"),
+    // Unused code
+    ("W0301", "unused variable '{0}'"),
+    ("W0301.help", "Remove it, or name it _{0} if it is unused on purpose"),
+    ("W0302", "unused parameter '{0}'"),
+    ("W0302.help", "Remove it, or name it _{0} if it is unused on purpose"),
+    ("W0303", "function '{0}' is never called"),
+    ("W0303.help", "Remove it, or name it _{0} if it is unused on purpose"),
+];
+
+const ES: &[(&str, &str)] = &[
+    // Parser
+    ("E0101", "Se esperaba un nombre de módulo después de 'import'"),
+    ("E0101.help", "Ejemplo: import math"),
+    ("E0102", "Se esperaba 'fn' después de 'export'"),
+    ("E0102.help", "Solo se pueden exportar funciones. Ejemplo: export fn add(a, b) { }"),
+    ("E0103", "Se esperaba 'fn' después de 'async'"),
+    ("E0103.help", "Ejemplo: async fn fetch_data() { }"),
+    ("E0104", "Se esperaba un nombre de función"),
+    ("E0104.help", "Los nombres de función deben ser identificadores válidos"),
+    ("E0105", "Se esperaba un nombre de parámetro"),
+    ("E0106", "Se esperaba un nombre de contrato después de 'contract'"),
+    ("E0106.help", "Ejemplo: contract Token { }"),
+    ("E0107", "Se esperaba '{' después del nombre del contrato"),
+    ("E0108", "La variable de estado '{0}' se declara más de una vez en el contrato '{1}'"),
+    ("E0109", "El contrato '{0}' ya tiene un constructor"),
+    ("E0109.help", "Un contrato puede declarar como máximo un constructor"),
+    ("E0110", "Se esperaba 'state', 'constructor' o 'fn' dentro del cuerpo del contrato"),
+    ("E0111", "Se esperaba '}' después del cuerpo del contrato"),
+    ("E0112", "Se esperaba '[' después de 'state:'"),
+    ("E0112.help", "Ejemplo: state: [\"balances\", \"total_supply\"]"),
+    ("E0113", "Se esperaba un nombre de variable de estado"),
+    ("E0114", "Se esperaba ']' después de la lista de variables de estado"),
+    ("E0115", "Se esperaba un nombre de variable de estado después de 'state'"),
+    ("E0115.help", "Ejemplo: state owner: Address"),
+    ("E0116", "Se esperaba un nombre de tipo después de ':'"),
+    ("E0117", "Argumentos de tipo sin cerrar, se esperaba '>'"),
+    ("E0118", "Se esperaba un nombre de variable después de 'let'"),
+    ("E0119", "Se esperaba '=' después del nombre de la variable"),
+    ("E0120", "Se esperaba '{' después de la condición del if"),
+    ("E0121", "Se esperaba '}' después del cuerpo del if"),
+    ("E0122", "Se esperaba '{' después de else"),
+    ("E0122.help", "Usa 'else { ... }' o encadena otra condición con 'else if'"),
+    ("E0123", "Se esperaba '}' después del cuerpo del else"),
+    ("E0124", "Se esperaba '(' después de 'panic'"),
+    ("E0125", "Se esperaba ')' después del mensaje de panic"),
+    ("E0126", "Se esperaba '{' después de la condición del while"),
+    ("E0127", "Se esperaba '}' después del cuerpo del while"),
+    ("E0128", "Se esperaba un nombre de función después de '.'"),
+    ("E0129", "Se esperaba '(' después del nombre de la función del módulo"),
+    ("E0130", "Token inesperado en la expresión"),
+    ("E0131", "Se esperaba '{' después de '.' en el import"),
+    ("E0131.help", "Ejemplo: import math.{add, sub}"),
+    ("E0132", "Se esperaba un nombre de función en la lista del import"),
+    ("E0133", "Se esperaba un alias después de 'as'"),
+    ("E0133.help", "Ejemplo: import math as m"),
+    ("E0134", "Se esperaba '[' y un nombre de atributo después de '#'"),
+    ("E0134.help", "Ejemplo: #[test]"),
+    ("E0135", "Atributo desconocido '#[{0}]'"),
+    ("E0135.help", "Atributos admitidos: {1}"),
+    ("E0136", "Se esperaba ']' después del nombre del atributo"),
+    ("E0137", "Los atributos deben ir seguidos de una declaración de función"),
+    ("E0138", "Se esperaba un nombre de variable o '_' en el patrón de tupla"),
+    ("E0138.help", "Ejemplo: let (public, private) = generate_keypair()"),
+    ("E0139", "Se esperaba ',' o ')' en la tupla"),
+    ("E0140", "Secuencia de escape desconocida '\\{0}'"),
+    ("E0140.help", "Escapes admitidos: \\n \\r \\t \\0 \\\\ \\\" \\' \\xHH \\u{...}. Para expresiones regulares, usa una cadena sin procesar: r\"\\d+\""),
+    ("E0141", "Escape hexadecimal no válido '\\x{0}'"),
+    ("E0141.help", "\\x admite exactamente dos dígitos hexadecimales, de \\x00 a \\x7F; usa \\u{...} para otros caracteres"),
+    ("E0142", "Escape unicode no válido '\\u{{0}}'"),
+    ("E0142.help", "Escribe de 1 a 6 dígitos hexadecimales entre llaves que nombren un carácter Unicode, p. ej. \\u{1F600}"),
+    ("E0143", "Cadena sin cerrar"),
+    ("E0143.help", "Cierra la cadena con '\"' (una cadena sin procesar r#\"...\"# termina con '\"#')"),
+    ("E0144", "Se esperaba el nombre de la variable del bucle después de 'for'"),
+    ("E0145", "Se esperaba 'in' después de la variable del bucle"),
+    ("E0145.help", "Ejemplo: for i in 0..10 { print(i) }"),
+    ("E0146", "Se esperaba '{' después del rango del for"),
+    ("E0147", "Se esperaba '}' después del cuerpo del for"),
+    ("E0148", "Se esperaba ']' después del rango de la porción"),
+    ("E0149", "Se esperaba '(' después de 'require'"),
+    ("E0149.help", "Ejemplo: require(amount > 0, \"amount must be positive\")"),
+    ("E0150", "Se esperaba ',' y un mensaje después de la condición de require"),
+    ("E0150.help", "require recibe una condición y el mensaje con el que fallar: require(amount > 0, \"amount must be positive\")"),
+    ("E0151", "Se esperaba ')' después del mensaje de require"),
+    ("E0152", "Carácter inesperado '{0}'"),
+    ("E0153", "Se esperaba el nombre de un parámetro de tipo"),
+    ("E0153.help", "Los parámetros de tipo son nombres entre '<' y '>': fn max<T>(a: T, b: T) -> T"),
+    ("E0154", "El método de trait '{0}' no puede tener cuerpo"),
+    ("E0154.help", "Un trait enumera firmas; escribe el cuerpo en cada impl"),
+    ("E0155", "Se esperaba un nombre después de '{0}'"),
+    ("E0156", "Se esperaba 'for' y un tipo después del nombre del trait"),
+    ("E0156.help", "Ejemplo: impl Printable for Int { ... }"),
+    ("E0157", "Se esperaba '{' para abrir el cuerpo de '{0}'"),
+    ("E0158", "Se esperaba 'fn' dentro del cuerpo de '{0}'"),
+    ("E0159", "Se esperaba '}' después del cuerpo de '{0}'"),
+    ("E0160", "Se esperaba '{' después del valor del match"),
+    ("E0161", "Se esperaba un patrón: Some(nombre), None, Ok(nombre), Err(nombre) o _"),
+    ("E0161.help", "Cada rama es un patrón, '=>' y un cuerpo: Some(x) => x,"),
+    ("E0162", "Se esperaba '=>' después del patrón '{0}'"),
+    ("E0163", "Se esperaba '}' para cerrar el match"),
+    ("E0164", "Se esperaba ',' o ']' en el arreglo"),
+    ("E0165", "El valor por defecto del parámetro '{0}' debe ser un literal"),
+    ("E0165.help", "Usa un número, texto o bool; calcula otros valores por defecto en el cuerpo"),
+    ("E0166", "El parámetro '{0}' necesita un valor por defecto, porque un parámetro anterior lo tiene"),
+    ("E0166.help", "Dale a '{0}' un valor por defecto, o mueve los parámetros con valor por defecto al final"),
+    ("E0167", "Se esperaba una condición entre paréntesis después de #[{0}]"),
+    ("E0167.help", "Ejemplo: #[{0}(amount > 0)]"),
+    ("E0168", "Se esperaba '=' y un tipo después de 'type {0}'"),
+    ("E0169", "'{0}' es una palabra reservada y no puede ser un nombre desde la edición {1}"),
+    ("E0169.help", "Cámbiale el nombre, p. ej. a '{0}_'; 'astrixa migrate' renombra cada uso"),
+    ("E0170", "'{0}' no es un nombre válido: los nombres no pueden empezar por un dígito"),
+    ("E0170.help", "Empiézalo por una letra o '_', p. ej. '_{0}'"),
+    ("E0171", "El literal entero {0} es demasiado grande (el mayor Int es {1})"),
+    ("E0172", "Se esperaba '}' después del cuerpo de defer"),
+    ("E0173", "Ruta desconocida '{0}'"),
+    ("E0173.help", "Las funciones de la biblioteca estándar tienen rutas como std::io::print, igual que print"),
+    ("E0174", "Las declaraciones de struct aún no están soportadas"),
+    ("E0174.help", "Agrupa los valores en una tupla o un mapa por ahora; los structs genéricos llegarán con las declaraciones de struct"),
+    // Type checker
+    ("E0201", "Error de tipos: tipos de retorno inconsistentes en la función '{0}' (encontrados {1})"),
+    ("E0202", "Error de tipos: la condición del if debe ser Bool, se obtuvo {0}"),
+    ("E0203", "Error de tipos: la variable '{0}' no está definida"),
+    ("E0203.suggestion", "¿Quisiste decir '{0}'?"),
+    ("E0204", "Tipos incompatibles: no se puede asignar {0} a una variable de tipo {1}"),
+    ("E0205", "Error de tipos: la condición del while debe ser Bool, se obtuvo {0}"),
+    ("E0206", "Error de tipos: panic() requiere un mensaje de texto, se obtuvo {0}"),
+    ("E0207", "Error de tipos: la función '{0}' espera {1} argumentos, se recibieron {2}"),
+    ("E0208", "Error de tipos: el argumento {0} de la función '{1}' espera {2}, se obtuvo {3}"),
+    ("E0209", "Error de tipos: no se puede sumar {0} y {1} (ambos operandos deben ser Int, ambos Float o ambos String)"),
+    ("E0210", "Error de tipos: el operador aritmético requiere dos operandos Int o dos Float (% solo admite Int), se obtuvieron {0} y {1}"),
+    ("E0211", "Error de tipos: el operador de comparación requiere operandos Int o U256, o dos Float, se obtuvieron {0} y {1}"),
+    ("E0212", "Error de tipos: #[{0}] en '{1}' solo se aplica a métodos de contrato"),
+    ("E0213", "Error de tipos: '{0}' no puede ser #[payable] y #[view] a la vez"),
+    ("E0214", "Error de tipos: el método #[view] '{0}' asigna la variable de estado '{1}'"),
+    ("E0215", "Error de tipos: la función #[test] '{0}' no debe recibir parámetros"),
+    ("E0216", "Error de tipos: #[{0}] en '{1}' solo se aplica a funciones de nivel superior"),
+    ("E0217", "Error de tipos: no se puede desestructurar {0} en {1} variables"),
+    ("E0218", "Error de tipos: la tupla tiene {0} elementos pero el patrón enlaza {1}"),
+    ("E0219", "Error de tipos: una tupla no puede contener otras tuplas"),
+    ("E0220", "El parámetro '{0}' de '{1}' tiene el tipo '{2}', que no tiene codificación ABI"),
+    ("E0220.help", "Los parámetros de los métodos de contrato pueden ser Int, U256, Address, Bool, String o Bytes"),
+    ("E0221", "'{0}' devuelve {1}, que no tiene codificación ABI"),
+    ("E0222", "Error de tipos: los límites de un rango deben ser Int, se obtuvieron {0} y {1}"),
+    ("E0223", "Error de tipos: no se puede iterar sobre {0}"),
+    ("E0223.help", "Los bucles for recorren un rango, p. ej. for i in 0..10"),
+    ("E0224", "Error de tipos: no se puede tomar una porción de {0}"),
+    ("E0225", "Error de tipos: las porciones toman un rango, se obtuvo {0}"),
+    ("E0225.help", "Ejemplo: name[0..3]"),
+    ("E0226", "Error de tipos: el método #[invariant] '{0}' no debe recibir parámetros"),
+    ("E0227", "Error de tipos: el método #[invariant] '{0}' asigna la variable de estado '{1}'"),
+    ("E0227.help", "Los invariantes solo comprueban el estado; `astrixa fuzz` los llama después de cada transacción"),
+    ("E0228", "Error de tipos: el parámetro de tipo '{0}' de '{1}' es {2} aquí pero {3} en un argumento anterior"),
+    ("E0229", "Error de tipos: '{0}' declara devolver {1} pero devuelve {2}"),
+    ("E0230", "Error de tipos: el método de contrato '{0}' no puede tener parámetros de tipo"),
+    ("E0230.help", "Las entradas ABI necesitan tipos concretos; mueve el código genérico a una función que llame el método"),
+    ("E0231", "Error de tipos: el método '{0}' del trait '{1}' debe recibir self como primer parámetro"),
+    ("E0232", "Error de tipos: no se puede implementar '{0}' para '{1}'"),
+    ("E0232.help", "Los traits se pueden implementar para Int, Float, Bool y String"),
+    ("E0233", "Error de tipos: trait desconocido '{0}'"),
+    ("E0233.help", "Declara el trait antes de implementarlo"),
+    ("E0234", "Error de tipos: al impl de '{0}' para {1} le falta el método '{2}'"),
+    ("E0235", "Error de tipos: el método '{0}' no forma parte del trait '{1}'"),
+    ("E0236", "Error de tipos: el método '{0}' recibe {1} parámetros en el trait '{2}' pero {3} aquí"),
+    ("E0237", "Error de tipos: el método '{0}' ya está implementado para {1}"),
+    ("E0238", "Error de tipos: no hay método '{0}' para {1}"),
+    ("E0238.help", "Implementa un trait que declare '{0}' para {1}"),
+    ("E0239", "Error de tipos: '?' necesita un Option o Result, se obtuvo {0}"),
+    ("E0239.help", "Envuelve el valor en Some(..) u Ok(..), o quita el '?'"),
+    ("E0240", "Error de tipos: '?' en la función '{0}' que devuelve {1} no puede propagar {2}"),
+    ("E0240.help", "Devuelve un Option para usar '?' sobre un Option, o un Result para usarlo sobre un Result"),
+    ("E0241", "Error de tipos: el patrón '{0}' nunca coincide con {1}"),
+    ("E0242", "Error de tipos: match no cubre {0}"),
+    ("E0242.help", "Añade un brazo para {0}, o un brazo '_'"),
+    ("E0243", "Error de tipos: no se puede asignar dos veces a la variable inmutable '{0}'"),
+    ("E0243.help", "Decláralo con 'let mut {0}' para permitir reasignarlo"),
+    ("E0244", "Error de tipos: tipos EIP-712 mal formados '{0}': {1}"),
+    ("E0244.help", "Escribe cada struct como Nombre(tipo campo,tipo campo), el tipo principal primero, p. ej. Mail(Person from,string contents)Person(string name,address wallet)"),
+    ("E0245", "Error de tipos: propiedad desconocida '{0}.{1}'"),
+    ("E0245.help", "Las propiedades son chain.id, chain.name, msg.sender, msg.value, msg.data, tx.hash, tx.value y tx.timestamp; llama a una función de módulo con paréntesis"),
+    ("E0246", "Error de tipos: CID inválido '{0}': {1}"),
+    ("E0247", "Error de tipos: la función anidada '{0}' usa '{1}' de la función contenedora '{2}'"),
+    ("E0247.help", "Las funciones anidadas no capturan variables; pasa '{1}' a '{0}' como parámetro"),
+    ("E0248", "Error de tipos: '{0}' no tiene un parámetro llamado '{1}'"),
+    ("E0249", "Error de tipos: el argumento '{1}' se pasa a '{0}' más de una vez"),
+    ("E0250", "Error de tipos: falta el argumento '{1}' en la llamada a '{0}'"),
+    ("E0250.help", "Pásalo por posición o como {1}: valor, o dale un valor por defecto al parámetro"),
+    ("E0251", "Error de tipos: argumento posicional después de un argumento con nombre en la llamada a '{0}'"),
+    ("E0251.help", "Pasa primero los argumentos por posición y después por nombre"),
+    ("E0252", "Error de tipos: '{0}' no acepta argumentos por nombre"),
+    ("E0252.help", "Solo las funciones declaradas en el programa aceptan argumentos con nombre; pásalos por posición"),
+    ("E0253", "Error de tipos: el módulo u256 no tiene la función '{0}'"),
+    ("E0253.help", "Disponibles: from, addmod, mulmod, shl, shr, saturating_add, saturating_sub, saturating_mul"),
+    ("E0254", "Error de tipos: los contratos no pueden llamar a merkle.{0}"),
+    ("E0254.help", "Construye los árboles y las pruebas fuera de la cadena; los contratos solo pueden comprobarlas con merkle.verify(root, leaf, proof)"),
+    ("E0255", "Error de tipos: no se puede usar '?' en código diferido"),
+    ("E0255.help", "El código diferido se ejecuta al salir de su bloque y no puede retornar antes; trata el None o el Err con match"),
+    ("E0256", "Error de tipos: el módulo '{0}' no tiene la función '{1}'"),
+    ("E0257", "Error de tipos: no se puede sumar {0} y {1}; convierte el {2} en String con to_string() primero"),
+    ("E0257.help", "Ejemplo: \"total: \" + to_string(total)"),
+    ("E0258", "Error de tipos: no se puede comparar {0} con {1}"),
+    ("E0258.help", "Ambos lados de == y != tienen un mismo tipo; Int se compara con U256 y Address con String"),
+    ("E0259", "Error de tipos: transfer envía a una Address, no a {0}"),
+    ("E0260", "Error de tipos: transfer envía una cantidad Int o U256, no {0}"),
+    ("E0261", "Error de tipos: un AIResult tiene label y score, no '{0}'"),
+    ("E0262", "Error de tipos: #[{0}] en '{1}' debe ser una condición Bool, se obtuvo {2}"),
+    ("E0263", "Error de tipos: 'as' convierte entre Int y Float, no {0} a {1}"),
+    ("E0263.help", "Lee un número de un texto con int(texto) y da formato a cualquier valor con str(valor)"),
+    ("E0264", "Error de tipos: int() lee un String, Int o Float, no {0}"),
+    ("E0265", "Error de tipos: {0}() recibe una función de un argumento, como en {0}(items, double), no {1}"),
+    ("E0266", "Error de tipos: '{0}' ya es el nombre de un tipo"),
+    ("E0267", "Error de tipos: el alias de tipo '{0}' se refiere a sí mismo"),
+    ("E0268", "Error de tipos: on_shutdown() recibe una función sin argumentos, como en on_shutdown(cleanup), no {0}"),
+    ("E0269", "Error de tipos: no se puede usar 'return' en código diferido"),
+    ("E0269.help", "El código diferido se ejecuta al salir de su bloque, cuando ya está decidido lo que retorna la función"),
+    ("E0270", "Error de tipos: '{0}' está en el preludio, que #[no_prelude] deja fuera"),
+    ("E0270.help", "Impórtala por nombre con 'import prelude.{{0}}', o declárala"),
+    ("E0271", "Error de tipos: '{0}' requiere operandos Bool, se obtuvieron {1} y {2}"),
+    ("E0271.help", "Compara un Int para obtener un Bool, como en 'count > 0 && ready'"),
+    // Module loader
+    ("E0301", "No se pudo leer el módulo '{0}': {1}"),
+    ("E0302", "No se encontró el módulo '{0}' en las rutas de búsqueda"),
+    ("E0302.help", "Se buscó '{1}' en: {2}"),
+    ("E0303", "El módulo '{0}' no tiene la función '{1}'"),
+    ("E0304", "'{0}' importado del módulo '{1}' choca con '{0}' del módulo '{2}'"),
+    ("E0304.help", "Importa uno de ellos de forma selectiva o usa un alias: import {1} as ..."),
+    ("E0305", "'{0}' importado del módulo '{1}' choca con una función definida en este archivo"),
+    ("E0305.help", "Renombra la función local o importa el módulo con un alias: import {1} as ..."),
+    ("E0306", "El alias de import '{0}' ya se usa para el módulo '{1}'"),
+    ("E0307", "'{1}' es privada del módulo '{0}'"),
+    ("E0307.help", "Declárala como 'export fn {1}' en {0}.ax para usarla desde otros módulos"),
+    // Runtime
+    ("E0401", "no se encontró la función main"),
+    ("E0402", "la variable '{0}' no está definida"),
+    ("E0403", "Pánico: {0}"),
+    ("E0404", "la condición debe ser booleana"),
+    ("E0405", "la condición del while debe ser booleana"),
+    ("E0406", "la función '{0}' no está definida"),
+    ("E0407", "división por cero"),
+    ("E0408", "operación no válida"),
+    ("E0409", "spawn() espera una llamada a una función async"),
+    ("E0410", "falta un argumento"),
+    ("E0411", "la tarea se espera a sí misma (await cíclico)"),
+    ("E0412", "tarea desconocida #{0}"),
+    ("E0413", "la tarea #{0} nunca se programó"),
+    ("E0414", "propiedad desconocida '{0}.{1}'"),
+    ("E0415", "ai.model() requiere al menos un argumento"),
+    ("E0416", "{0}() requiere un argumento de texto"),
+    ("E0417", "ai.infer() requiere dos argumentos: modelo y entrada"),
+    ("E0418", "la entrada de ai.infer() debe ser un texto"),
+    ("E0419", "{0}() requiere un argumento de texto"),
+    ("E0420", "Método de IA desconocido: ai.{0}"),
+    ("E0421", "no se puede leer el módulo del paquete '{0}'"),
+    ("E0422", "no se encuentra el módulo '{0}' (se probó el paquete y el archivo local)"),
+    ("E0423", "no se encuentra el módulo '{0}'"),
+    ("E0424", "Error en el módulo '{0}': {1}"),
+    ("E0425", "len() espera un arreglo o un texto"),
+    ("E0426", "range() espera dos números"),
+    ("E0427", "sleep() espera un número de milisegundos"),
+    ("E0428", "el módulo '{0}' no tiene la función '{1}'"),
+    ("E0429", "'{0}' importado del módulo '{1}' choca con un '{0}' existente"),
+    ("E0430", "el alias de import '{0}' ya se usa para el módulo '{1}'"),
+    ("E0431", "'{1}' es privada del módulo '{0}'; declárala con 'export fn' para usarla fuera del módulo"),
+    ("E0432", "no se puede desestructurar {0} en {1} variables"),
+    ("E0433", "la tupla tiene {0} elementos pero el patrón enlaza {1}"),
+    ("E0434", "los límites de un rango deben ser números"),
+    ("E0435", "no se puede iterar sobre {0}"),
+    ("E0436", "no se puede tomar una porción de {0} con {1}"),
+    ("E0437", "la porción {0}..{1} está fuera de los límites para la longitud {2}"),
+    ("E0438", "no se puede convertir entre un valor de Rust y un valor del script: {0}"),
+    ("E0439", "el mapa no tiene el campo '{0}'"),
+    ("E0440", "web3.logs(contract, event, from_block, to_block) espera una dirección, una firma de evento y un rango de bloques"),
+    ("E0441", "no se puede analizar la firma de evento '{0}'; escríbela como 'Nombre(tipo indexed nombre, tipo nombre)'"),
+    ("E0442", "web3.logs necesita un nodo RPC; define ASTRIXA_RPC_URL"),
+    ("E0443", "falló la obtención de logs: {0}"),
+    ("E0444", "no se puede decodificar el log de {0}: {1}"),
+    ("E0445", "'?' devolvió {0} fuera de una función"),
+    ("E0446", "'?' necesita un Option o Result, se obtuvo {0}"),
+    ("E0447", "ningún brazo de match para {0}"),
+    ("E0448", "{0}() recibe 1 argumento, se obtuvieron {1}"),
+    ("E0449", "web3.{0} espera (domain, types, value, key?) para firmar o (domain, types, value, signature, signer) para verificar, con types como cadena"),
+    ("E0450", "no se pueden codificar los datos tipados: {0}"),
+    ("E0451", "web3.sign_typed necesita una clave privada; pásala o define ASTRIXA_PRIVATE_KEY"),
+    ("E0452", "web3.multicall espera un arreglo de tuplas (contract, function, args...)"),
+    ("E0453", "no se puede analizar la firma de función '{0}'; escríbela como 'nombre(tipo, tipo) returns (tipo)'"),
+    ("E0454", "no se pueden codificar los argumentos de {0}: {1}"),
+    ("E0455", "web3.multicall necesita un nodo RPC; define ASTRIXA_RPC_URL"),
+    ("E0456", "falló el multicall: {0}"),
+    ("E0457", "función de ipfs desconocida '{0}'; usa add, get, cid o valid"),
+    ("E0458", "ipfs.{0} espera una cadena"),
+    ("E0459", "CID inválido '{0}': {1}"),
+    ("E0460", "ipfs.add necesita una API de IPFS; define ASTRIXA_IPFS_API"),
+    ("E0461", "falló la solicitud a IPFS: {0}"),
+    ("E0462", "fork inválido '{0}'; escríbelo como <rpc_url>@<bloque>"),
+    ("E0463", "falló la lectura de {0} desde el fork: {1}"),
+    ("E0464", "'{0}' no es una dirección de 20 bytes"),
+    ("E0465", "'{0}' no es una palabra hexadecimal de hasta 32 bytes"),
+    ("E0466", "web3.{0} espera {1}"),
+    ("E0467", "u256.{0} espera {1}"),
+    ("E0468", "{0} no es un entero sin signo de 256 bits"),
+    ("E0469", "merkle.{0} espera {1}"),
+    ("E0470", "la hoja {0} está fuera del rango de un árbol de {1} hojas"),
+    ("E0471", "ed25519.{0} espera {1}"),
+    ("E0472", "bls.{0} espera {1}"),
+    ("E0473", "{0} no está habilitado en esta compilación; recompila con --features {1}"),
+    ("E0474", "{0} espera la dirección del contrato y luego {1} argumentos"),
+    ("E0475", "{0} lee la cadena y necesita un nodo RPC; define ASTRIXA_RPC_URL"),
+    ("E0476", "'{0}' no es calldata ni el hash de una transacción"),
+    ("E0477", "ninguna ABI conocida tiene una función con el selector 0x{0}"),
+    ("E0478", "no se pudieron decodificar los argumentos de {0}: {1}"),
+    ("E0479", "no se pudo obtener la transacción {0}: {1}"),
+    ("E0480", "{0}.{1}() espera {2}"),
+    ("E0481", "el índice {0} está fuera de rango para la longitud {1}"),
+    ("E0482", "un arreglo o mapa no puede contenerse a sí mismo"),
+    ("E0483", "no se puede convertir {0} a {1}"),
+    ("E0484", "'{0}' no es un entero"),
+    ("E0485", "{0}(items, function) espera un rango, un arreglo o un iterador, y luego una función declarada"),
+    ("E0486", "filter() espera que su función devuelva un bool"),
+    ("E0487", "collect() espera un rango, un arreglo o un iterador, no {0}"),
+    ("E0488", "Interrumpido por {0}"),
+    ("E0489", "on_shutdown() espera una función declarada, no {0}"),
+    ("E0490", "'&&' y '||' requieren operandos booleanos"),
+    ("E0491", "desbordamiento inferior de la pila; el bytecode está mal formado"),
+    ("E0492", "la función '{0}' recibe {1} argumentos; no puede ser el punto de entrada"),
+    ("E0493", "sin gas: se usaron {0} de gas, el límite era {1}"),
+    ("E0494", "no se puede indexar {0} con {1}"),
+    ("E0495", "bytecode mal formado: {0} tiene el operando no válido '{1}'"),
+    // Generación de código; cada construcción que nombra E0501 es una entrada `E0501.<construcción>`
+    ("E0501", "{0}: las compilaciones aún no lo admiten"),
+    ("E0501.help", "El intérprete lo ejecuta; las compilaciones aún no lo admiten"),
+    ("E0501.std", "{0}, en '{1}' de la biblioteca estándar"),
+    ("E0501.match", "match"),
+    ("E0501.array_loop", "un bucle for sobre un array"),
+    ("E0501.array_literal", "un literal de array"),
+    ("E0501.iterator", "un iterador fuera del iterable de un bucle for"),
+    ("E0501.slice", "el corte de un mapa o un rango"),
+    ("E0501.option", "un valor Option o Result"),
+    ("E0501.try", "el operador '?'"),
+    ("E0501.ai_result", "una propiedad de un resultado de IA"),
+    ("E0501.string_builder", "un StringBuilder"),
+    ("E0501.u256", "la aritmética u256"),
+    ("E0501.defi", "un enlace defi"),
+    ("E0501.merkle", "una prueba merkle"),
+    ("E0501.array_method", "un método de array o mapa"),
+    ("E0502", "'{0}' ({1}) es async y la VM no tiene planificador de tareas; las funciones async solo se ejecutan en el intérprete"),
+    // Deprecaciones por edición
+    ("W0001", "println() está obsoleto desde la edición {0}; usa print()"),
+    ("W0002", "La forma de lista de 'state' está obsoleta desde la edición {0}; declara una variable por cada 'state'"),
+    ("W0002.help", "Ejemplo: state balances"),
+    ("W0003", "'{0}' es una palabra reservada; como nombre es un error desde la edición {1}"),
+    ("W0003.help", "Cámbiale el nombre a '{0}_' ('astrixa migrate' lo hace en todas partes)"),
+    // Recarga en caliente
+    ("W0101", "'{0}' cambió su firma; las llamadas que usen la anterior fallarán"),
+    ("W0102", "'{0}' fue eliminada; las llamadas a ella fallarán"),
+    ("W0103", "el estado del contrato '{0}' cambió (añadidas: {1}; eliminadas: {2}); las variables eliminadas perdieron sus valores"),
+    // Análisis de gas
+    ("W0201", "el bucle de {0} está acotado por '{1}', que controla el usuario; a unos {2} de gas por iteración, {3} iteraciones superan el límite de gas del bloque de {4}"),
+    ("W0201.help", "Acota el bucle con una constante o procesa los datos por lotes; marca el método con #[unbounded] si el patrón es intencionado"),
+    ("W0202", "el bucle de {0} se ejecuta {1} veces a unos {2} de gas cada una, {3} de gas en total, por encima del límite de gas del bloque de {4}"),
+    ("W0202.help", "Reparte el trabajo entre varias llamadas"),
+    // Código sin usar
+    ("W0301", "variable '{0}' sin usar"),
+    ("W0301.help", "Elimínala, o llámala _{0} si no se usa a propósito"),
+    ("W0302", "parámetro '{0}' sin usar"),
+    ("W0302.help", "Elimínalo, o llámalo _{0} si no se usa a propósito"),
+    ("W0303", "la función '{0}' nunca se llama"),
+    ("W0303.help", "Elimínala, o llámala _{0} si no se usa a propósito"),
+];
+
+/// Locales with a built-in catalog; anything missing falls back to English
+pub const LOCALES: &[&str] = &["en", "es"];
+
+struct Catalog {
+    locale: Option<String>, // None: not chosen yet, resolve from the environment
+    overrides: BTreeMap<String, String>,
+}
+
+static CATALOG: RwLock<Catalog> = RwLock::new(Catalog {
+    locale: None,
+    overrides: BTreeMap::new(),
+});
+
+/// Select the message locale (e.g. "es", "es_ES.UTF-8"); unknown locales use English
+pub fn set_locale(locale: &str) {
+    if let Ok(mut catalog) = CATALOG.write() {
+        catalog.locale = Some(normalize_locale(locale));
+    }
+}
+
+/// The active locale: set explicitly, else the ASTRIXA_LOCALE variable, else "en"
+pub fn locale() -> String {
+    let chosen = CATALOG.read().ok().and_then(|c| c.locale.clone());
+    chosen.unwrap_or_else(|| {
+        std::env::var("ASTRIXA_LOCALE")
+            .map(|l| normalize_locale(&l))
+            .unwrap_or_else(|_| "en".to_string())
+    })
+}
+
+/// Replace the template for a code (or `<code>.help`), for embedders
+pub fn set_override(code: &str, template: &str) {
+    if let Ok(mut catalog) = CATALOG.write() {
+        catalog.overrides.insert(code.to_string(), template.to_string());
+    }
+}
+
+/// Drop every override installed with `set_override`
+pub fn clear_overrides() {
+    if let Ok(mut catalog) = CATALOG.write() {
+        catalog.overrides.clear();
+    }
+}
+
+/// Render the message for a code with positional arguments
+pub fn render(code: &str, args: &[&dyn Display]) -> String {
+    let template = lookup(code).unwrap_or_else(|| code.to_string());
+    fill(&template, args)
+}
+
+/// Render the help text for a code, if the catalog has one
+pub fn help(code: &str, args: &[&dyn Display]) -> Option<String> {
+    lookup(&format!("{}.help", code)).map(|template| fill(&template, args))
+}
+
+fn lookup(key: &str) -> Option<String> {
+    if let Some(template) = CATALOG.read().ok().and_then(|c| c.overrides.get(key).cloned()) {
+        return Some(template);
+    }
+
+    let locale = locale();
+    find(table(&locale), key)
+        .or_else(|| find(EN, key))
+        .map(|template| template.to_string())
+}
+
+fn table(locale: &str) -> &'static [(&'static str, &'static str)] {
+    match locale {
+        "es" => ES,
+        _ => EN,
+    }
+}
+
+fn find(table: &'static [(&'static str, &'static str)], key: &str) -> Option<&'static str> {
+    table.iter().find(|(k, _)| *k == key).map(|(_, v)| *v)
+}
+
+/// "es_ES.UTF-8" -> "es"
+fn normalize_locale(locale: &str) -> String {
+    locale
+        .split(['_', '-', '.'])
+        .next()
+        .unwrap_or("en")
+        .to_lowercase()
+}
+
+fn fill(template: &str, args: &[&dyn Display]) -> String {
+    let mut out = template.to_string();
+    for (i, arg) in args.iter().enumerate() {
+        out = out.replace(&format!("{{{}}}", i), &arg.to_string());
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_translation_has_an_english_original() {
+        for (key, _) in ES {
+            assert!(find(EN, key).is_some(), "'{}' is translated but missing from EN", key);
+        }
+    }
+
+    #[test]
+    fn test_runtime_errors_leave_the_label_to_their_printer() {
+        // The CLI prints "Error:" before them, the VM runner "Runtime error:"
+        for (key, message) in EN.iter().chain(ES) {
+            if key.starts_with("E04") {
+                assert!(!message.starts_with("Error:"), "{} repeats the label: {}", key, message);
+            }
+        }
+    }
+
+    #[test]
+    fn test_render_fills_arguments_and_honours_overrides() {
+        // Locale is process-wide, so read the Spanish table directly
+        let spanish = fill(find(table("es"), "E0207").unwrap(), &[&"f", &1, &2]);
+        assert_eq!(spanish, "Error de tipos: la función 'f' espera 1 argumentos, se recibieron 2");
+        assert_eq!(normalize_locale("es_ES.UTF-8"), "es");
+
+        // A code no real diagnostic uses, so parallel tests are unaffected
+        assert_eq!(render("E9999", &[]), "E9999");
+        set_override("E9999", "custom {0}");
+        assert_eq!(render("E9999", &[&"text"]), "custom text");
+        clear_overrides();
+    }
+}
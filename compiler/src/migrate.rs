@@ -0,0 +1,227 @@
+use crate::edition::{Edition, Warning};
+use crate::error::CompileError;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+
+// Automated upgrades between editions.
+//
+// Every deprecation with a mechanical fix carries its replacement text (see
+// `edition::Warning`), so migrating is parsing the file as the target edition
+// and splicing those replacements into the original source. Edits are
+// textual, which keeps comments and the rest of the layout untouched.
+
+/// Upper bound on parse-and-fix rounds, in case one fix exposes another
+const MAX_ROUNDS: usize = 8;
+
+/// The result of migrating one source file
+#[derive(Debug)]
+pub struct Migration {
+    pub source: String,
+    pub applied: Vec<Warning>,   // Deprecations rewritten in `source`
+    pub remaining: Vec<Warning>, // Deprecations that need a manual fix
+}
+
+impl Migration {
+    pub fn changed(&self) -> bool {
+        !self.applied.is_empty()
+    }
+}
+
+/// Rewrite `source` so it no longer uses anything `edition` deprecates
+pub fn migrate(source: &str, edition: Edition) -> Result<Migration, CompileError> {
+    let mut current = source.to_string();
+    let mut applied = Vec::new();
+
+    for _ in 0..MAX_ROUNDS {
+        let mut parser = Parser::new(Lexer::new(&current));
+        parser.set_edition(edition);
+        parser.set_migrating(true);
+        parser.parse()?;
+
+        let (fixable, remaining): (Vec<Warning>, Vec<Warning>) = parser
+            .warnings()
+            .iter()
+            .cloned()
+            .partition(|w| w.replacement.is_some());
+
+        if fixable.is_empty() {
+            return Ok(Migration { source: current, applied, remaining });
+        }
+
+        let (next, fixed) = apply_fixes(&current, &fixable);
+        current = next;
+        applied.extend(fixed);
+    }
+
+    Ok(Migration { source: current, applied, remaining: Vec::new() })
+}
+
+/// Splice each warning's replacement over its span. Overlapping fixes are
+/// left for the next round; returns the new text and the fixes applied.
+pub fn apply_fixes(source: &str, warnings: &[Warning]) -> (String, Vec<Warning>) {
+    let mut chars: Vec<char> = source.chars().collect();
+    let line_starts = line_starts(&chars);
+    let offset = |line: usize, column: usize| line_starts.get(line - 1).map(|start| start + column - 1);
+
+    let mut edits: Vec<(usize, usize, &Warning)> = warnings
+        .iter()
+        .filter(|w| w.replacement.is_some())
+        .filter_map(|w| {
+            let start = offset(w.span.start_line, w.span.start_column)?;
+            let end = offset(w.span.end_line, w.span.end_column)?;
+            (start <= end && end <= chars.len()).then_some((start, end, w))
+        })
+        .collect();
+    edits.sort_by_key(|(start, _, _)| *start);
+
+    // Keep non-overlapping edits, then apply back to front so earlier
+    // offsets stay valid
+    let mut kept: Vec<(usize, usize, &Warning)> = Vec::new();
+    for edit in edits {
+        if kept.last().is_none_or(|last| last.1 <= edit.0) {
+            kept.push(edit);
+        }
+    }
+
+    for (start, end, warning) in kept.iter().rev() {
+        let replacement = warning.replacement.as_deref().unwrap_or_default();
+        chars.splice(*start..*end, replacement.chars());
+    }
+
+    (chars.into_iter().collect(), kept.into_iter().map(|(_, _, w)| w.clone()).collect())
+}
+
+/// Char offset of the first character of each line
+fn line_starts(chars: &[char]) -> Vec<usize> {
+    let mut starts = vec![0];
+    for (i, ch) in chars.iter().enumerate() {
+        if *ch == '\n' {
+            starts.push(i + 1);
+        }
+    }
+    starts
+}
+
+/// A unified diff between two versions of `path`, with three lines of
+/// context around each change. Empty when the texts are identical.
+pub fn unified_diff(path: &str, before: &str, after: &str) -> String {
+    const CONTEXT: usize = 3;
+
+    let old: Vec<&str> = before.lines().collect();
+    let new: Vec<&str> = after.lines().collect();
+    let ops = diff_lines(&old, &new);
+    if ops.iter().all(|op| matches!(op, DiffOp::Same(..))) {
+        return String::new();
+    }
+
+    let mut out = format!("--- a/{}\n+++ b/{}\n", path, path);
+    let changed: Vec<usize> = (0..ops.len()).filter(|&i| !matches!(ops[i], DiffOp::Same(..))).collect();
+
+    // Group changes whose context windows touch into one hunk
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for &i in &changed {
+        let start = i.saturating_sub(CONTEXT);
+        let end = (i + CONTEXT + 1).min(ops.len());
+        match hunks.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    for (start, end) in hunks {
+        let (old_start, new_start) = ops[start].positions();
+        let old_len = ops[start..end].iter().filter(|op| !matches!(op, DiffOp::Insert(..))).count();
+        let new_len = ops[start..end].iter().filter(|op| !matches!(op, DiffOp::Delete(..))).count();
+        out.push_str(&format!("@@ -{},{} +{},{} @@\n", old_start + 1, old_len, new_start + 1, new_len));
+
+        for op in &ops[start..end] {
+            match op {
+                DiffOp::Same(i, _) => out.push_str(&format!(" {}\n", old[*i])),
+                DiffOp::Delete(i, _) => out.push_str(&format!("-{}\n", old[*i])),
+                DiffOp::Insert(_, j) => out.push_str(&format!("+{}\n", new[*j])),
+            }
+        }
+    }
+
+    out
+}
+
+/// One line of an edit script, with the old and new line indices it sits at
+enum DiffOp {
+    Same(usize, usize),
+    Delete(usize, usize),
+    Insert(usize, usize),
+}
+
+impl DiffOp {
+    fn positions(&self) -> (usize, usize) {
+        match self {
+            DiffOp::Same(i, j) | DiffOp::Delete(i, j) | DiffOp::Insert(i, j) => (*i, *j),
+        }
+    }
+}
+
+/// Line edit script from a longest-common-subsequence table. Source files
+/// are small enough that the quadratic table is not a concern.
+fn diff_lines(old: &[&str], new: &[&str]) -> Vec<DiffOp> {
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut ops = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            ops.push(DiffOp::Same(i, j));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            ops.push(DiffOp::Delete(i, j));
+            i += 1;
+        } else {
+            ops.push(DiffOp::Insert(i, j));
+            j += 1;
+        }
+    }
+    ops
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrate_rewrites_deprecated_constructs() {
+        let source = "contract Token {\n    state: [\"balances\", \"owner\"]\n}\n\nfn main() {\n    // greet\n    println(\"hi\")\n}\n";
+
+        let migration = migrate(source, Edition::E2025).unwrap();
+        assert_eq!(migration.applied.len(), 2);
+        assert!(migration.remaining.is_empty());
+        assert_eq!(
+            migration.source,
+            "contract Token {\n    state balances\n    state owner\n}\n\nfn main() {\n    // greet\n    print(\"hi\")\n}\n"
+        );
+
+        // Already-migrated code is left alone
+        let again = migrate(&migration.source, Edition::E2025).unwrap();
+        assert!(!again.changed());
+        assert!(!migrate(source, Edition::E2024).unwrap().changed());
+    }
+
+    #[test]
+    fn test_unified_diff_shows_changed_lines_with_context() {
+        let before = "a\nb\nc\nd\ne\nf\ng\nh\n";
+        let after = "a\nb\nc\nd\nE\nf\ng\nh\n";
+
+        let diff = unified_diff("src/main.ax", before, after);
+        assert_eq!(diff, "--- a/src/main.ax\n+++ b/src/main.ax\n@@ -2,7 +2,7 @@\n b\n c\n d\n-e\n+E\n f\n g\n h\n");
+        assert_eq!(unified_diff("src/main.ax", before, before), "");
+    }
+}
@@ -0,0 +1,185 @@
+// Compiled programs shared between VMs.
+//
+// A server running a script per HTTP request, or a chain running a
+// contract per call, wants a fresh VM each time: nothing one run leaves in
+// its variables, stack or gas may reach the next. Compiling the program
+// again each time would be the slow part, so the cache compiles it once
+// per key and keeps it immutable behind an `Arc`; every VM it starts
+// shares that copy and owns only its own state. The cache is `Sync`, so
+// one `Arc<ModuleCache>` serves every thread. Each key has a lock of its
+// own, so compiling one program holds up only those waiting for it.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+use crate::bytecode::Instruction;
+use crate::vm::VM;
+
+/// A key's program, None until it has been compiled; its lock is held
+/// while compiling it
+type Slot = Arc<Mutex<Option<Arc<[Instruction]>>>>;
+
+#[derive(Default)]
+pub struct ModuleCache {
+    programs: RwLock<HashMap<String, Slot>>,
+}
+
+impl ModuleCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The program cached under `key`, compiled by `compile` the first time.
+    /// Other threads asking meanwhile wait for it rather than compiling it
+    /// again; a compile error caches nothing.
+    pub fn get_or_compile(
+        &self,
+        key: &str,
+        compile: impl FnOnce() -> Result<Vec<Instruction>, String>,
+    ) -> Result<Arc<[Instruction]>, String> {
+        // The cache only ever holds whole programs, so a poisoned lock is
+        // still consistent
+        let slot = self.programs.write().unwrap_or_else(PoisonError::into_inner)
+            .entry(key.to_string())
+            .or_default()
+            .clone();
+        let mut program = slot.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(program) = program.as_ref() {
+            return Ok(program.clone());
+        }
+        match compile() {
+            Ok(compiled) => Ok(program.insert(compiled.into()).clone()),
+            Err(e) => {
+                // Unless invalidated meanwhile, the key is still this slot
+                let mut programs = self.programs.write().unwrap_or_else(PoisonError::into_inner);
+                if programs.get(key).is_some_and(|current| Arc::ptr_eq(current, &slot)) {
+                    programs.remove(key);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// The program cached under `key`, waiting for it if it is being compiled
+    pub fn get(&self, key: &str) -> Option<Arc<[Instruction]>> {
+        let slot = self.programs.read().unwrap_or_else(PoisonError::into_inner).get(key)?.clone();
+        let program = slot.lock().unwrap_or_else(PoisonError::into_inner).clone();
+        program
+    }
+
+    /// A new VM with the program cached under `key` loaded, sharing it and
+    /// nothing else
+    pub fn instance(&self, key: &str) -> Option<VM> {
+        let mut vm = VM::new();
+        vm.load_shared(self.get(key)?);
+        Some(vm)
+    }
+
+    /// Drop the program cached under `key`, as when its source changes.
+    /// VMs already running it keep it until they finish.
+    pub fn invalidate(&self, key: &str) -> bool {
+        self.programs.write().unwrap_or_else(PoisonError::into_inner).remove(key).is_some()
+    }
+
+    /// How many programs are cached, waiting for any being compiled
+    pub fn len(&self) -> usize {
+        let slots: Vec<Slot> = self.programs.read().unwrap_or_else(PoisonError::into_inner).values().cloned().collect();
+        slots.iter().filter(|slot| slot.lock().unwrap_or_else(PoisonError::into_inner).is_some()).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use crate::bytecode::OpCode;
+    use crate::interpreter::Value;
+
+    /// Sets `count` and returns it
+    fn counter() -> Vec<Instruction> {
+        vec![
+            Instruction::const_instr("1".to_string()),
+            Instruction::store_var("count".to_string()),
+            Instruction::new(OpCode::Pop, None),
+            Instruction::var_instr("count".to_string()),
+            Instruction::new(OpCode::Return, None),
+        ]
+    }
+
+    #[test]
+    fn test_vms_share_one_compiled_program_and_nothing_else() {
+        let cache = Arc::new(ModuleCache::new());
+        let compiles = Arc::new(AtomicUsize::new(0));
+
+        let threads: Vec<_> = (0..8)
+            .map(|_| {
+                let (cache, compiles) = (cache.clone(), compiles.clone());
+                std::thread::spawn(move || {
+                    cache
+                        .get_or_compile("counter", || {
+                            compiles.fetch_add(1, Ordering::SeqCst);
+                            Ok(counter())
+                        })
+                        .unwrap();
+                    let mut vm = cache.instance("counter").unwrap();
+                    matches!(vm.run_for(usize::MAX), Ok(crate::vm::Tick::Finished(Value::Number(1))))
+                })
+            })
+            .collect();
+        assert!(threads.into_iter().all(|thread| thread.join().unwrap()));
+        assert_eq!((compiles.load(Ordering::SeqCst), cache.len()), (1, 1));
+
+        // Nor does one VM see another's variables, or gas
+        let reader = cache.get_or_compile("reader", || Ok(vec![Instruction::var_instr("count".to_string())])).unwrap();
+        let mut vm = VM::new();
+        vm.load_shared(reader);
+        assert!(matches!(vm.run_for(usize::MAX), Err(e) if e == crate::messages::render("E0402", &[&"count"])));
+        let mut starved = cache.instance("counter").unwrap().with_gas(2, 1);
+        assert!(starved.run_for(usize::MAX).is_err());
+        let mut fed = cache.instance("counter").unwrap();
+        assert!(fed.run_for(usize::MAX).is_ok() && fed.gas_used() > 0);
+
+        // Starting a VM copies nothing, so it stays well under a millisecond
+        let start = std::time::Instant::now();
+        for _ in 0..1000 {
+            cache.instance("counter").unwrap();
+        }
+        assert!(start.elapsed() < std::time::Duration::from_secs(1));
+
+        assert!(cache.get_or_compile("broken", || Err("syntax error".to_string())).is_err());
+        assert!(cache.invalidate("counter") && cache.instance("counter").is_none());
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_compiling_one_program_does_not_hold_up_another() {
+        let cache = Arc::new(ModuleCache::new());
+        let (started, wait_started) = std::sync::mpsc::channel();
+        let (release, wait_release) = std::sync::mpsc::channel::<()>();
+
+        let slow = {
+            let cache = cache.clone();
+            std::thread::spawn(move || {
+                cache
+                    .get_or_compile("slow", || {
+                        started.send(()).unwrap();
+                        wait_release.recv().unwrap();
+                        Ok(counter())
+                    })
+                    .is_ok()
+            })
+        };
+        wait_started.recv().unwrap();
+
+        // "slow" is still compiling; a whole-cache lock would wait forever
+        assert!(cache.get_or_compile("fast", || Ok(counter())).is_ok());
+        assert!(cache.instance("fast").is_some());
+
+        release.send(()).unwrap();
+        assert!(slow.join().unwrap());
+        assert_eq!(cache.len(), 2);
+    }
+}
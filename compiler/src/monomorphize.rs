@@ -0,0 +1,430 @@
+use std::collections::HashMap;
+use crate::ast::{Expr, ExprKind, Pattern, Stmt, StmtKind};
+use crate::types::{self, Aliases, Type};
+
+// Monomorphization.
+//
+// Lowering runs this first, so generic functions never reach the IR: each
+// distinct set of type arguments a program calls a generic function with
+// becomes a copy named for them, e.g. `max<Int>` or `pair<Int:String>` (a
+// valid WASM identifier), and every call is renamed to its copy. Type
+// arguments come from the argument types where lowering can see them:
+// literals, annotated parameters, `let` bindings and calls to annotated
+// functions. A parameter no argument pins down is Int, as unannotated
+// values are in V1. The generic declarations themselves are dropped.
+//
+// Trait methods are dispatched statically the same way: the methods of
+// `impl Printable for Int` become functions named `Int.to_string`, and
+// `n.to_string()` on a variable of known type becomes a direct call
+// `Int.to_string(n)`. Trait declarations are dropped.
+//
+// Type aliases are spelled out on the way, so an impl for `Wei` names its
+// methods `U256.method` and `x as Real` reaches lowering as `x as Float`.
+//
+// Only functions are generic: the language has no struct declarations
+// yet, so the parser reports a `struct` (E0174), and generic structs will
+// be instantiated here the same way once it has them.
+
+/// `stmts` with every generic function replaced by its instantiations, in
+/// its place, and every impl by its methods
+pub fn monomorphize(stmts: &[Stmt]) -> Vec<Stmt> {
+    let mut mono = Monomorphizer { aliases: types::aliases(stmts), ..Default::default() };
+    let mut program: Vec<Stmt> = Vec::new();
+    for stmt in stmts {
+        match &stmt.kind {
+            StmtKind::Function { name, type_params, .. } if !type_params.is_empty() => {
+                mono.generics.insert(name.clone(), stmt.clone());
+            }
+            StmtKind::Impl { target, methods, .. } => {
+                let target = mono.resolve(target);
+                for method in methods {
+                    let mut method = method.clone();
+                    if let StmtKind::Function { name, param_types, .. } = &mut method.kind {
+                        *name = format!("{}.{}", target, name);
+                        // `self` is the implementing type
+                        if let Some(first) = param_types.first_mut() {
+                            first.get_or_insert_with(|| target.clone());
+                        }
+                        mono.methods.push(name.clone());
+                    }
+                    program.push(method);
+                }
+                continue;
+            }
+            StmtKind::Trait { .. } => continue,
+            _ => {}
+        }
+        program.push(stmt.clone());
+    }
+    for stmt in &program {
+        if let StmtKind::Function { name, return_annotation: Some(annotation), .. } = &stmt.kind {
+            if let Some(returns) = mono.annotated(annotation) {
+                mono.returns.insert(name.clone(), returns);
+            }
+        }
+    }
+    if mono.generics.is_empty() && mono.methods.is_empty() && mono.aliases.is_empty() {
+        return program;
+    }
+
+    for stmt in &mut program {
+        match &mut stmt.kind {
+            StmtKind::Function { type_params, .. } if !type_params.is_empty() => {}
+            StmtKind::Function { .. } => mono.function(stmt),
+            StmtKind::Contract { constructor, methods, .. } => {
+                for method in constructor.iter_mut().map(|c| c.as_mut()).chain(methods.iter_mut()) {
+                    mono.function(method);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Instantiating a body can ask for further instantiations
+    let mut done = 0;
+    while done < mono.queue.len() {
+        let (generic, args) = mono.queue[done].clone();
+        let mut instance = mono.instantiate(&generic, &args);
+        mono.function(&mut instance);
+        mono.instances.entry(generic).or_default().push(instance);
+        done += 1;
+    }
+
+    let mut output = Vec::new();
+    for stmt in program {
+        match &stmt.kind {
+            StmtKind::Function { name, type_params, .. } if !type_params.is_empty() => {
+                output.extend(mono.instances.remove(name).unwrap_or_default());
+            }
+            _ => output.push(stmt),
+        }
+    }
+    output
+}
+
+#[derive(Default)]
+struct Monomorphizer {
+    generics: HashMap<String, Stmt>,          // Generic function declarations by name
+    returns: HashMap<String, Type>,           // Concrete return annotations, e.g. `-> Int`
+    queue: Vec<(String, Vec<Type>)>,          // Instantiations asked for, in order
+    instances: HashMap<String, Vec<Stmt>>,    // Generic name -> its instantiated copies
+    methods: Vec<String>,                     // Impl methods, as `Type.method`
+    aliases: Aliases,
+}
+
+/// The name of a generic function's copy for `args`
+fn instance_name(generic: &str, args: &[Type]) -> String {
+    format!("{}{}", generic, type_list(args))
+}
+
+/// `<Int:String>`; a tuple nests as another list
+fn type_list(types: &[Type]) -> String {
+    let names: Vec<String> = types
+        .iter()
+        .map(|t| match t {
+            Type::Tuple(items) => type_list(items),
+            other => other.to_string(),
+        })
+        .collect();
+    format!("<{}>", names.join(":"))
+}
+
+impl Monomorphizer {
+    /// An annotation with its type aliases spelled out
+    fn resolve(&self, annotation: &str) -> String {
+        types::expand_aliases(annotation, &self.aliases).unwrap_or_else(|_| annotation.to_string())
+    }
+
+    fn annotated(&self, annotation: &str) -> Option<Type> {
+        Type::from_annotation(&self.resolve(annotation))
+    }
+
+    /// Rename the generic calls in a concrete function's body
+    fn function(&mut self, stmt: &mut Stmt) {
+        let StmtKind::Function { params, param_types, body, .. } = &mut stmt.kind else { return };
+        let mut locals = HashMap::new();
+        for (param, annotation) in params.iter().zip(param_types.iter()) {
+            let param_type = annotation.as_deref().and_then(|annotation| self.annotated(annotation)).unwrap_or(Type::Int);
+            locals.insert(param.clone(), param_type);
+        }
+        self.block(body, &mut locals);
+    }
+
+    fn block(&mut self, body: &mut [Stmt], locals: &mut HashMap<String, Type>) {
+        for stmt in body {
+            match &mut stmt.kind {
+                StmtKind::Let { name, value, .. } => {
+                    self.expr(value, locals);
+                    match self.type_of(value, locals) {
+                        Some(value_type) => locals.insert(name.clone(), value_type),
+                        None => locals.remove(name),
+                    };
+                }
+                StmtKind::LetTuple { names, value, .. } => {
+                    self.expr(value, locals);
+                    for name in names.iter() {
+                        locals.remove(name);
+                    }
+                }
+                StmtKind::Expression(expr) | StmtKind::Return(expr) | StmtKind::Value(expr) | StmtKind::Panic(expr) => self.expr(expr, locals),
+                StmtKind::Defer(body) => self.block(body, &mut locals.clone()),
+                StmtKind::Assign { value, .. } => self.expr(value, locals),
+                StmtKind::If { condition, then_body, else_body } => {
+                    self.expr(condition, locals);
+                    self.block(then_body, &mut locals.clone());
+                    if let Some(else_body) = else_body {
+                        self.block(else_body, &mut locals.clone());
+                    }
+                }
+                StmtKind::While { condition, body } => {
+                    self.expr(condition, locals);
+                    self.block(body, &mut locals.clone());
+                }
+                StmtKind::For { var, iterable, body } => {
+                    self.expr(iterable, locals);
+                    let mut inner = locals.clone();
+                    inner.insert(var.clone(), Type::Int);
+                    self.block(body, &mut inner);
+                }
+                StmtKind::Match { value, arms } => {
+                    self.expr(value, locals);
+                    let value_type = self.type_of(value, locals);
+                    for arm in arms {
+                        let mut inner = locals.clone();
+                        let bound = match (&arm.pattern, &value_type) {
+                            (Pattern::Some(name), Some(Type::Option(payload)))
+                            | (Pattern::Ok(name), Some(Type::Result(payload, _)))
+                            | (Pattern::Err(name), Some(Type::Result(_, payload))) => Some((name, Some(*payload.clone()))),
+                            (Pattern::Some(name) | Pattern::Ok(name) | Pattern::Err(name), _) => Some((name, None)),
+                            _ => None,
+                        };
+                        match bound {
+                            Some((name, Some(payload))) => inner.insert(name.clone(), payload),
+                            Some((name, None)) => inner.remove(name),
+                            None => None,
+                        };
+                        self.block(&mut arm.body, &mut inner);
+                    }
+                }
+                StmtKind::Import { .. } | StmtKind::Function { .. } | StmtKind::Contract { .. }
+                | StmtKind::Trait { .. } | StmtKind::Impl { .. } | StmtKind::TypeAlias { .. } => {}
+            }
+        }
+    }
+
+    /// Rename generic calls in an expression, innermost first so argument
+    /// types are known
+    fn expr(&mut self, expr: &mut Expr, locals: &HashMap<String, Type>) {
+        match &mut expr.kind {
+            ExprKind::Call(name, args) => {
+                for arg in args.iter_mut() {
+                    self.expr(arg, locals);
+                }
+                if let Some(generic) = self.generics.get(name.as_str()) {
+                    let type_args = self.type_args(generic, args, locals);
+                    let instance = instance_name(name, &type_args);
+                    if !self.queue.iter().any(|(g, a)| g == name && *a == type_args) {
+                        self.queue.push((name.clone(), type_args));
+                    }
+                    *name = instance;
+                }
+            }
+            ExprKind::ModuleCall(receiver, method, args) => {
+                for arg in args.iter_mut() {
+                    self.expr(arg, locals);
+                }
+                if let Some(target) = self.method_target(receiver, method, locals) {
+                    let mut call_args = vec![Expr::new(ExprKind::Identifier(receiver.clone()), expr.span)];
+                    call_args.append(args);
+                    expr.kind = ExprKind::Call(target, call_args);
+                }
+            }
+            ExprKind::Tuple(args) | ExprKind::Array(args) => {
+                for arg in args {
+                    self.expr(arg, locals);
+                }
+            }
+            ExprKind::Cast(inner, target) => {
+                *target = self.resolve(target);
+                self.expr(inner, locals)
+            }
+            ExprKind::Await(inner) | ExprKind::Try(inner) | ExprKind::Named(_, inner) => self.expr(inner, locals),
+            ExprKind::Range(l, r) | ExprKind::Slice(l, r)
+            | ExprKind::Add(l, r) | ExprKind::Sub(l, r) | ExprKind::Mul(l, r) | ExprKind::Div(l, r) | ExprKind::Mod(l, r)
+            | ExprKind::Eq(l, r) | ExprKind::Ne(l, r) | ExprKind::Lt(l, r) | ExprKind::Le(l, r)
+            | ExprKind::Gt(l, r) | ExprKind::Ge(l, r)
+            | ExprKind::And(l, r) | ExprKind::Or(l, r) => {
+                self.expr(l, locals);
+                self.expr(r, locals);
+            }
+            ExprKind::Number(_) | ExprKind::Float(_) | ExprKind::Bool(_) | ExprKind::String(_)
+            | ExprKind::Identifier(_) | ExprKind::Property(..) => {}
+        }
+    }
+
+    /// Each type parameter of `generic`, bound by the first argument whose
+    /// type is known, else Int
+    fn type_args(&self, generic: &Stmt, args: &[Expr], locals: &HashMap<String, Type>) -> Vec<Type> {
+        let StmtKind::Function { type_params, param_types, .. } = &generic.kind else { return Vec::new() };
+        type_params
+            .iter()
+            .map(|param| {
+                param_types
+                    .iter()
+                    .zip(args)
+                    .filter(|(annotation, _)| annotation.as_deref() == Some(param.as_str()))
+                    .find_map(|(_, arg)| self.type_of(arg, locals))
+                    .unwrap_or(Type::Int)
+            })
+            .collect()
+    }
+
+    /// An expression's type, if it can be seen without the type checker
+    fn type_of(&self, expr: &Expr, locals: &HashMap<String, Type>) -> Option<Type> {
+        match &expr.kind {
+            ExprKind::Number(_) => Some(Type::Int),
+            ExprKind::Float(_) => Some(Type::Float),
+            ExprKind::Bool(_) => Some(Type::Bool),
+            ExprKind::String(_) => Some(Type::String),
+            ExprKind::Identifier(name) => locals.get(name).cloned(),
+            ExprKind::Tuple(items) => items.iter().map(|item| self.type_of(item, locals)).collect::<Option<_>>().map(Type::Tuple),
+            ExprKind::Range(..) => Some(Type::Range),
+            ExprKind::Add(l, _) | ExprKind::Sub(l, _) | ExprKind::Mul(l, _) | ExprKind::Div(l, _) | ExprKind::Mod(l, _) => {
+                self.type_of(l, locals)
+            }
+            ExprKind::Eq(..) | ExprKind::Ne(..) | ExprKind::Lt(..) | ExprKind::Le(..) | ExprKind::Gt(..) | ExprKind::Ge(..)
+            | ExprKind::And(..) | ExprKind::Or(..) => {
+                Some(Type::Bool)
+            }
+            // Already renamed: `max<Int>` returns what its copy's annotation says
+            ExprKind::Call(name, _) => match self.instance_return(name) {
+                Some(returns) => returns,
+                None => self.returns.get(name).cloned(),
+            },
+            ExprKind::ModuleCall(receiver, method, _) => {
+                self.method_target(receiver, method, locals).and_then(|target| self.returns.get(&target).cloned())
+            }
+            ExprKind::Try(inner) => match self.type_of(inner, locals)? {
+                Type::Option(payload) | Type::Result(payload, _) => Some(*payload),
+                _ => None,
+            },
+            ExprKind::Named(_, value) => self.type_of(value, locals),
+            ExprKind::Cast(_, target) => self.annotated(target),
+            ExprKind::Await(_) | ExprKind::Slice(..) | ExprKind::Array(_) | ExprKind::Property(..) => None,
+        }
+    }
+
+    /// The impl method `receiver.method(..)` calls, when the receiver is a
+    /// variable whose type has one
+    fn method_target(&self, receiver: &str, method: &str, locals: &HashMap<String, Type>) -> Option<String> {
+        let target = format!("{}.{}", locals.get(receiver)?.to_string(), method);
+        self.methods.contains(&target).then_some(target)
+    }
+
+    /// For the name of an instantiation, its return type: `Some(None)` when
+    /// the generic function's return type is not annotated
+    fn instance_return(&self, name: &str) -> Option<Option<Type>> {
+        let (generic, type_args) = self.queue.iter().find(|(g, a)| instance_name(g, a) == name)?;
+        let StmtKind::Function { type_params, return_annotation, .. } = &self.generics[generic].kind else { return None };
+        let annotation = return_annotation.as_deref()?;
+        Some(match type_params.iter().position(|p| p == annotation) {
+            Some(i) => Some(type_args[i].clone()),
+            None => self.annotated(annotation),
+        })
+    }
+
+    /// A copy of a generic function with its type parameters replaced by
+    /// `args` in every annotation
+    fn instantiate(&self, generic: &str, args: &[Type]) -> Stmt {
+        let mut instance = self.generics[generic].clone();
+        if let StmtKind::Function { name, param_types, type_params, return_annotation, .. } = &mut instance.kind {
+            let substitute = |annotation: &mut Option<String>| {
+                if let Some(i) = type_params.iter().position(|p| Some(p) == annotation.as_ref()) {
+                    *annotation = Some(args[i].to_string());
+                }
+            };
+            for annotation in param_types.iter_mut() {
+                substitute(annotation);
+            }
+            substitute(return_annotation);
+            *name = instance_name(generic, args);
+            type_params.clear();
+        }
+        instance
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::IRInstr;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    #[test]
+    fn test_generic_functions_are_instantiated_per_type() {
+        let source = r#"
+            fn max<T>(a: T, b: T) -> T {
+                if a > b {
+                    return a
+                }
+                return b
+            }
+            fn pick<T>(a: T, b: T) -> T {
+                return max(a, b)
+            }
+            fn main() {
+                let n = max(1, 2)
+                let s = pick("a", "b")
+                return max(n, 3)
+            }
+        "#;
+        let program = Parser::new(Lexer::new(source)).parse().unwrap();
+        let module = crate::lowering::lower(&program);
+
+        let names: Vec<&str> = module.functions.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, vec!["max<Int>", "max<String>", "pick<String>", "main"]);
+        let main = module.functions.iter().find(|f| f.name == "main").unwrap();
+        let calls: Vec<&IRInstr> = main.instructions.iter().filter(|i| matches!(i, IRInstr::Call(..))).collect();
+        assert_eq!(calls, vec![
+            &IRInstr::Call("max<Int>".to_string(), 2),
+            &IRInstr::Call("pick<String>".to_string(), 2),
+            &IRInstr::Call("max<Int>".to_string(), 2),
+        ]);
+        assert_eq!(instance_name("pair", &[Type::Int, Type::Tuple(vec![Type::Bool, Type::String])]), "pair<Int:<Bool:String>>");
+    }
+
+    #[test]
+    fn test_generic_structs_are_reported_not_skipped() {
+        let source = "struct Pair<T> {\n    first: T\n}\nfn main() {\n    print(\"x\")\n}\n";
+        let error = Parser::new(Lexer::new(source)).parse().unwrap_err();
+        assert_eq!((error.code, error.line, error.column), (Some("E0174"), 1, 1));
+    }
+
+    #[test]
+    fn test_trait_method_calls_become_direct_calls() {
+        let source = r#"
+            trait Double {
+                fn double(self) -> Int
+            }
+            impl Double for Int {
+                fn double(self) -> Int {
+                    return self * 2
+                }
+            }
+            fn main() {
+                let n = 4
+                let d = n.double()
+                return d.double()
+            }
+        "#;
+        let program = Parser::new(Lexer::new(source)).parse().unwrap();
+        let module = crate::lowering::lower(&program);
+
+        let names: Vec<&str> = module.functions.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, vec!["Int.double", "main"]);
+        let main = &module.functions[1];
+        let calls = main.instructions.iter().filter(|i| **i == IRInstr::Call("Int.double".to_string(), 1)).count();
+        assert_eq!(calls, 2);
+    }
+}
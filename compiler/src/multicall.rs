@@ -0,0 +1,442 @@
+use crate::eip712::encode_atomic;
+use crate::interpreter::Value;
+use crate::logs::{canonical_type, decode_dynamic, decode_word, hex, is_dynamic, keccak256, unhex};
+use crate::messages;
+
+// Batched contract reads for off-chain scripts.
+//
+// `web3.multicall(calls)` runs many `eth_call`s in one round trip. Each call
+// is a tuple of the contract, the function with what it returns, and its
+// arguments:
+//
+//   let results = web3.multicall([
+//       (token, "balanceOf(address) returns (uint256)", holder),
+//       (token, "decimals() returns (uint8)"),
+//   ])?
+//   for result in results {
+//       match result {
+//           Ok(value) => print(value),
+//           Err(reason) => print(reason),
+//       }
+//   }
+//
+// The calls go to the Multicall3 contract's `aggregate3`, which every major
+// chain has at the same address. Where it is not deployed, they are sent as
+// one JSON-RPC batch instead. Results come back in the order of the calls,
+// each an Ok holding the decoded value (a tuple when the function returns
+// several) or an Err when that call reverted. The outer Err holds why the
+// node could not be read at all.
+
+/// Multicall3, deployed at this address on every chain it supports
+pub const MULTICALL3: &str = "0xcA11bde05977b3631167028862bE2a173976CA11";
+
+/// Selector of `aggregate3((address,bool,bytes)[])`
+const AGGREGATE3: [u8; 4] = [0x82, 0xad, 0x56, 0xcb];
+
+/// Where calls are answered: a JSON-RPC node, or a fixture in tests
+pub trait CallSource {
+    /// `eth_call` of `data` against `to` at the latest block, as hex
+    fn call(&self, to: &str, data: &str) -> Result<String, String>;
+
+    /// Several `eth_call`s in one JSON-RPC batch, answered in order
+    fn batch(&self, calls: &[(String, String)]) -> Result<Vec<Result<String, String>>, String>;
+}
+
+/// A JSON-RPC node reached over HTTP
+#[cfg(not(target_arch = "wasm32"))]
+pub struct RpcCallSource {
+    pub url: String,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl RpcCallSource {
+    /// The node named by the ASTRIXA_RPC_URL environment variable
+    pub fn from_env() -> Option<Self> {
+        std::env::var("ASTRIXA_RPC_URL").ok().map(|url| RpcCallSource { url })
+    }
+
+    pub(crate) fn post(&self, request: &serde_json::Value) -> Result<serde_json::Value, String> {
+        let body = ureq::post(&self.url)
+            .set("Content-Type", "application/json")
+            .send_string(&request.to_string())
+            .map_err(|e| e.to_string())?
+            .into_string()
+            .map_err(|e| e.to_string())?;
+        serde_json::from_str(&body).map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn eth_call(id: usize, to: &str, data: &str) -> serde_json::Value {
+    serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "method": "eth_call",
+        "params": [{ "to": to, "data": data }, "latest"],
+    })
+}
+
+/// The hex result of one JSON-RPC response, or its error message
+#[cfg(not(target_arch = "wasm32"))]
+fn call_result(response: &serde_json::Value) -> Result<String, String> {
+    if let Some(error) = response.get("error") {
+        return Err(error["message"].as_str().unwrap_or("call failed").to_string());
+    }
+    response["result"].as_str().map(str::to_string).ok_or_else(|| "no result in response".to_string())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl CallSource for RpcCallSource {
+    fn call(&self, to: &str, data: &str) -> Result<String, String> {
+        call_result(&self.post(&eth_call(1, to, data))?)
+    }
+
+    fn batch(&self, calls: &[(String, String)]) -> Result<Vec<Result<String, String>>, String> {
+        let request: Vec<_> = calls.iter().enumerate().map(|(id, (to, data))| eth_call(id, to, data)).collect();
+        let response = self.post(&serde_json::Value::Array(request))?;
+        let responses = response
+            .as_array()
+            .ok_or_else(|| call_result(&response).err().unwrap_or_else(|| "batch requests are not supported".to_string()))?;
+
+        // Batch responses may come back in any order
+        let mut results = vec![Err("no response".to_string()); calls.len()];
+        for response in responses {
+            if let Some(slot) = response["id"].as_u64().and_then(|id| results.get_mut(id as usize)) {
+                *slot = call_result(response);
+            }
+        }
+        Ok(results)
+    }
+}
+
+/// A parsed function declaration: `balanceOf(address owner) returns (uint256)`
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionSignature {
+    pub name: String,
+    pub inputs: Vec<String>,
+    pub outputs: Vec<String>,
+}
+
+impl FunctionSignature {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let invalid = || messages::render("E0453", &[&text]);
+        let (declaration, returns) = match text.split_once(" returns") {
+            Some((declaration, returns)) => (declaration, Some(returns.trim())),
+            None => (text, None),
+        };
+        let (name, inputs) = split_params(declaration).ok_or_else(invalid)?;
+        if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+            return Err(invalid());
+        }
+        let outputs = match returns {
+            Some(returns) => match split_params(returns) {
+                Some(("", outputs)) => outputs,
+                _ => return Err(invalid()),
+            },
+            None => Vec::new(),
+        };
+        Ok(FunctionSignature { name: name.to_string(), inputs, outputs })
+    }
+
+    /// The first four bytes of keccak256 of `name(type,type)`
+    pub fn selector(&self) -> [u8; 4] {
+        let hash = keccak256(format!("{}({})", self.name, self.inputs.join(",")).as_bytes());
+        [hash[0], hash[1], hash[2], hash[3]]
+    }
+
+    /// Calldata: the selector, then the arguments ABI-encoded
+    pub fn encode(&self, args: &[Value]) -> Result<Vec<u8>, String> {
+        if args.len() != self.inputs.len() {
+            return Err(format!("expected {} arguments, got {}", self.inputs.len(), args.len()));
+        }
+        // A static tuple takes a word per field of the head, the rest one each
+        let head_size: usize = self.inputs.iter().map(|kind| 32 * static_tuple(kind).map_or(1, |fields| fields.len())).sum();
+        let mut head = Vec::new();
+        let mut tail = Vec::new();
+        for (kind, arg) in self.inputs.iter().zip(args) {
+            if kind == "string" || kind == "bytes" {
+                let bytes = match arg {
+                    Value::String(s) if kind == "string" => s.as_bytes().to_vec(),
+                    Value::String(s) => unhex(s)?,
+                    _ => return Err(format!("expected a string for {}", kind)),
+                };
+                head.extend(usize_word(head_size + tail.len()));
+                tail.extend(padded(&bytes));
+            } else if let Some(element) = kind.strip_suffix("[]").filter(|element| !is_dynamic(element)) {
+                // Its length, then a word per item
+                let Value::Array(items) = arg else {
+                    return Err(format!("expected an array for {}", kind));
+                };
+                head.extend(usize_word(head_size + tail.len()));
+                tail.extend(usize_word(items.borrow().len()));
+                for item in items.borrow().iter() {
+                    tail.extend(encode_atomic(element, item)?);
+                }
+            } else if let Some(fields) = static_tuple(kind) {
+                // Written in place, field by field
+                let Value::Tuple(values) = arg else {
+                    return Err(format!("expected a tuple for {}", kind));
+                };
+                if values.len() != fields.len() {
+                    return Err(format!("expected {} fields for {}, got {}", fields.len(), kind, values.len()));
+                }
+                for (field, value) in fields.iter().zip(values) {
+                    head.extend(encode_atomic(field, value)?);
+                }
+            } else if is_dynamic(kind) {
+                return Err(format!("type '{}' is not supported", kind));
+            } else {
+                head.extend(encode_atomic(kind, arg)?);
+            }
+        }
+        let mut data = self.selector().to_vec();
+        data.extend(head);
+        data.extend(tail);
+        Ok(data)
+    }
+
+    /// What a call returned: nothing, one value, or a tuple of several
+    pub fn decode(&self, data: &[u8]) -> Result<Value, String> {
+        let mut values = decode_params(&self.outputs, data)?;
+        Ok(match values.len() {
+            0 => Value::Null,
+            1 => values.remove(0),
+            _ => Value::Tuple(values),
+        })
+    }
+
+    /// The arguments of a call of this function, from its calldata
+    pub fn decode_inputs(&self, calldata: &[u8]) -> Result<Vec<Value>, String> {
+        match calldata.split_at_checked(4) {
+            Some((selector, data)) if selector == self.selector() => decode_params(&self.inputs, data),
+            _ => Err(format!("calldata does not call {}", self.name)),
+        }
+    }
+}
+
+/// Values of `kinds` ABI-encoded in `data`, as `encode` writes them
+fn decode_params(kinds: &[String], data: &[u8]) -> Result<Vec<Value>, String> {
+    let mut values = Vec::new();
+    let mut head = 0;
+    for kind in kinds {
+        let size = 32 * static_tuple(kind).map_or(1, |fields| fields.len());
+        let words = data.get(head..head + size).ok_or("data too short")?;
+        values.push(match static_tuple(kind) {
+            Some(fields) => {
+                let fields = fields.iter().zip(words.chunks(32)).map(|(field, word)| decode_word(field, word));
+                Value::Tuple(fields.collect::<Result<_, _>>()?)
+            }
+            None if is_dynamic(kind) => decode_dynamic(kind, data, words)?,
+            None => decode_word(kind, words)?,
+        });
+        head += size;
+    }
+    Ok(values)
+}
+
+/// The fields of a tuple type whose fields are all static
+fn static_tuple(kind: &str) -> Option<Vec<&str>> {
+    let fields: Vec<&str> = kind.strip_prefix('(')?.strip_suffix(')')?.split(',').collect();
+    fields.iter().all(|field| !is_dynamic(field)).then_some(fields)
+}
+
+/// `name(type a, type b)` into its name and canonical parameter types
+fn split_params(text: &str) -> Option<(&str, Vec<String>)> {
+    let (name, rest) = text.trim().split_once('(')?;
+    let inside = rest.strip_suffix(')')?;
+    let mut kinds = Vec::new();
+    for param in inside.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let words: Vec<&str> = param.split_whitespace().collect();
+        match words.as_slice() {
+            [kind] | [kind, _] => kinds.push(canonical_type(kind)),
+            _ => return None,
+        }
+    }
+    Some((name.trim(), kinds))
+}
+
+/// One call of a batch: the contract, the function and its calldata
+#[derive(Debug, Clone, PartialEq)]
+pub struct Call {
+    pub target: String,
+    pub function: FunctionSignature,
+    pub data: Vec<u8>,
+}
+
+/// Run `calls` through Multicall3, or as a JSON-RPC batch where it is not
+/// deployed, each result decoded or the reason that call failed
+pub fn execute(source: &dyn CallSource, calls: &[Call]) -> Result<Vec<Result<Value, String>>, String> {
+    if calls.is_empty() {
+        return Ok(Vec::new());
+    }
+    let returned = match source.call(MULTICALL3, &format!("0x{}", hex(&aggregate3(calls)?))) {
+        // An address without code answers every call with nothing
+        Ok(result) if result != "0x" => decode_aggregate3(&unhex(&result)?, calls.len())?,
+        _ => {
+            let batch: Vec<_> = calls.iter().map(|c| (c.target.clone(), format!("0x{}", hex(&c.data)))).collect();
+            let results = source.batch(&batch).map_err(|e| messages::render("E0456", &[&e]))?;
+            results.into_iter().map(|result| result.and_then(|data| unhex(&data))).collect()
+        }
+    };
+    Ok(calls
+        .iter()
+        .zip(returned)
+        .map(|(call, result)| result.and_then(|data| call.function.decode(&data)))
+        .collect())
+}
+
+/// Calldata for `aggregate3`, letting every call fail on its own
+fn aggregate3(calls: &[Call]) -> Result<Vec<u8>, String> {
+    // Each (target, allowFailure, callData) is dynamic, so the array holds
+    // offsets to them, counted from just after its length
+    let mut offsets = Vec::new();
+    let mut tuples = Vec::new();
+    for call in calls {
+        offsets.extend(usize_word(32 * calls.len() + tuples.len()));
+        tuples.extend(encode_atomic("address", &Value::String(call.target.clone()))?);
+        tuples.extend(usize_word(1));
+        tuples.extend(usize_word(96));
+        tuples.extend(padded(&call.data));
+    }
+    let mut data = AGGREGATE3.to_vec();
+    data.extend(usize_word(32));
+    data.extend(usize_word(calls.len()));
+    data.extend(offsets);
+    data.extend(tuples);
+    Ok(data)
+}
+
+/// The `(bool success, bytes returnData)[]` that `aggregate3` returns
+fn decode_aggregate3(data: &[u8], count: usize) -> Result<Vec<Result<Vec<u8>, String>>, String> {
+    let failed = |e: &str| messages::render("E0456", &[&format!("malformed aggregate3 result: {}", e)]);
+    let word_at = |at: usize| data.get(at..at + 32).ok_or_else(|| failed("too short")).and_then(|w| word_usize(w).map_err(|e| failed(&e)));
+
+    let array = word_at(0)?;
+    if word_at(array)? != count {
+        return Err(failed("wrong number of results"));
+    }
+    let base = array + 32;
+    (0..count)
+        .map(|i| {
+            let tuple = base + word_at(base + 32 * i)?;
+            let success = word_at(tuple)? != 0;
+            let bytes = tuple + word_at(tuple + 32)?;
+            let length = word_at(bytes)?;
+            let returned = data.get(bytes + 32..bytes + 32 + length).ok_or_else(|| failed("too short"))?;
+            Ok(if success { Ok(returned.to_vec()) } else { Err("call reverted".to_string()) })
+        })
+        .collect()
+}
+
+fn usize_word(n: usize) -> [u8; 32] {
+    let mut word = [0u8; 32];
+    word[24..].copy_from_slice(&(n as u64).to_be_bytes());
+    word
+}
+
+fn word_usize(word: &[u8]) -> Result<usize, String> {
+    match decode_word("uint256", word)? {
+        Value::U256(n) => usize::try_from(n).map_err(|_| "offset out of range".to_string()),
+        _ => unreachable!(),
+    }
+}
+
+/// `bytes` as the ABI writes them: the length, then the bytes padded to words
+fn padded(bytes: &[u8]) -> Vec<u8> {
+    let mut out = usize_word(bytes.len()).to_vec();
+    out.extend(bytes);
+    out.resize(32 + bytes.len().div_ceil(32) * 32, 0);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use crate::u256::U256;
+
+    const HOLDER: &str = "0x00000000000000000000000000000000000000aa";
+    const TOKEN: &str = "0x00000000000000000000000000000000000000c0";
+
+    /// A node that either has Multicall3 and answers with `aggregated`, or
+    /// answers batches from `batched`
+    struct Fixture {
+        aggregated: Option<String>,
+        batched: Vec<Result<String, String>>,
+        calls: RefCell<Vec<(String, String)>>,
+    }
+
+    impl CallSource for Fixture {
+        fn call(&self, to: &str, data: &str) -> Result<String, String> {
+            self.calls.borrow_mut().push((to.to_string(), data.to_string()));
+            Ok(self.aggregated.clone().unwrap_or_else(|| "0x".to_string()))
+        }
+
+        fn batch(&self, calls: &[(String, String)]) -> Result<Vec<Result<String, String>>, String> {
+            self.calls.borrow_mut().extend(calls.iter().cloned());
+            Ok(self.batched.clone())
+        }
+    }
+
+    fn word(tail: &str) -> String {
+        format!("{:0>64}", tail)
+    }
+
+    fn calls() -> Vec<Call> {
+        let balance = FunctionSignature::parse("balanceOf(address owner) returns (uint256)").unwrap();
+        let name = FunctionSignature::parse("name() returns (string)").unwrap();
+        vec![
+            Call { target: TOKEN.to_string(), data: balance.encode(&[Value::Address(HOLDER.to_string())]).unwrap(), function: balance },
+            Call { target: TOKEN.to_string(), data: name.encode(&[]).unwrap(), function: name },
+        ]
+    }
+
+    #[test]
+    fn test_function_signature_encoding() {
+        let balance = FunctionSignature::parse("balanceOf(address) returns (uint256)").unwrap();
+        assert_eq!(hex(&balance.selector()), "70a08231");
+        assert_eq!(hex(&balance.encode(&[Value::Address(HOLDER.to_string())]).unwrap()), format!("70a08231{}", word("aa")));
+        assert_eq!(balance.decode(&unhex(&word("64")).unwrap()).unwrap(), Value::U256(U256::from(100)));
+
+        // A string argument is written after the head, at its offset
+        let greet = FunctionSignature::parse("greet(string, uint)").unwrap();
+        assert_eq!(greet.inputs, vec!["string", "uint256"]);
+        let data = greet.encode(&[Value::String("hi".to_string()), Value::Number(1)]).unwrap();
+        assert_eq!(hex(&data[4..]), format!("{}{}{}{:0<64}", word("40"), word("1"), word("2"), "6869"));
+
+        assert!(FunctionSignature::parse("balanceOf address").is_err());
+        assert!(FunctionSignature::parse("balanceOf(address) returns uint256").is_err());
+        assert!(balance.encode(&[]).is_err());
+    }
+
+    #[test]
+    fn test_execute_through_multicall3_and_batch_fallback() {
+        // (true, balance) and (false, nothing): tuples at 0x40 and 0xc0
+        let aggregated = format!(
+            "0x{}{}{}{}{}{}{}{}{}{}{}",
+            word("20"), word("2"), word("40"), word("c0"),
+            word("1"), word("40"), word("20"), word("64"),
+            word("0"), word("40"), word("0"),
+        );
+        let node = Fixture { aggregated: Some(aggregated), batched: Vec::new(), calls: RefCell::new(Vec::new()) };
+        let results = execute(&node, &calls()).unwrap();
+        assert_eq!(results, vec![Ok(Value::U256(U256::from(100))), Err("call reverted".to_string())]);
+
+        let sent = node.calls.borrow();
+        assert_eq!(sent.len(), 1, "one eth_call for the whole batch");
+        assert_eq!(sent[0].0, MULTICALL3);
+        assert!(sent[0].1.starts_with("0x82ad56cb"));
+        assert!(sent[0].1.contains(&format!("70a08231{}", word("aa"))));
+
+        // Without Multicall3, the same calls go out as a JSON-RPC batch
+        let name = format!("0x{}{}{:0<64}", word("20"), word("2"), "6869");
+        let node = Fixture {
+            aggregated: None,
+            batched: vec![Err("execution reverted".to_string()), Ok(name)],
+            calls: RefCell::new(Vec::new()),
+        };
+        let results = execute(&node, &calls()).unwrap();
+        assert_eq!(results, vec![Err("execution reverted".to_string()), Ok(Value::String("hi".to_string()))]);
+        assert_eq!(node.calls.borrow().len(), 3);
+    }
+}
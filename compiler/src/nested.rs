@@ -0,0 +1,361 @@
+use std::collections::{HashMap, HashSet};
+use crate::ast::{Expr, ExprKind, Pattern, Stmt, StmtKind};
+
+// Nested functions.
+//
+// A function declared inside another one is a helper local to it:
+//
+//   fn total(prices) {
+//       fn with_tax(price) {
+//           return price * 120 / 100
+//       }
+//       return with_tax(prices)
+//   }
+//
+// Helpers do not capture the enclosing function's variables (the type
+// checker reports one that tries), so each can be lifted to module scope
+// under its qualified name, `total.with_tax`, with the calls made from its
+// scope renamed to match. Lowering and the interpreter run `lift` before
+// they see a program, so neither handles nesting itself. A helper is in
+// scope in the whole body of the function declaring it, including its own
+// body and its siblings', and shadows a top-level function of the same name.
+
+/// `stmts` with every nested function moved to module scope, just before
+/// the declaration it was nested in
+pub fn lift(stmts: &[Stmt]) -> Vec<Stmt> {
+    let mut output = Vec::new();
+    for stmt in stmts {
+        let mut stmt = stmt.clone();
+        match &mut stmt.kind {
+            StmtKind::Function { name, body, .. } => {
+                let prefix = name.clone();
+                lift_body(&prefix, body, &HashMap::new(), &mut output);
+            }
+            StmtKind::Contract { name, constructor, methods, .. } => {
+                for method in constructor.iter_mut().map(|c| c.as_mut()).chain(methods.iter_mut()) {
+                    if let StmtKind::Function { name: method_name, body, .. } = &mut method.kind {
+                        let prefix = format!("{}.{}", name, method_name);
+                        lift_body(&prefix, body, &HashMap::new(), &mut output);
+                    }
+                }
+            }
+            _ => {}
+        }
+        output.push(stmt);
+    }
+    output
+}
+
+/// For each helper nested in a function with parameters `bound` and body
+/// `body`, the first enclosing variable it uses: (helper, variable, span)
+pub fn captures(bound: &[String], body: &[Stmt]) -> Vec<(String, String, crate::ast::Span)> {
+    let mut enclosing: HashSet<String> = bound.iter().cloned().collect();
+    let mut helpers = Vec::new();
+    walk(body, &mut |stmt| match &stmt.kind {
+        StmtKind::Function { .. } => helpers.push(stmt),
+        kind => enclosing.extend(bindings(kind)),
+    });
+
+    let mut found = Vec::new();
+    for helper in helpers {
+        let StmtKind::Function { name, params, body, .. } = &helper.kind else { continue };
+        let mut own: HashSet<String> = params.iter().cloned().collect();
+        let mut used = Vec::new();
+        walk_all(body, &mut |stmt| {
+            own.extend(bindings(&stmt.kind));
+            if let StmtKind::Function { params, .. } = &stmt.kind {
+                own.extend(params.iter().cloned());
+            }
+            if let StmtKind::Assign { name, .. } = &stmt.kind {
+                used.push(name.clone());
+            }
+            for expr in stmt_exprs(&stmt.kind) {
+                identifiers(expr, &mut used);
+            }
+        });
+        if let Some(variable) = used.into_iter().find(|v| enclosing.contains(v) && !own.contains(v)) {
+            found.push((name.clone(), variable, helper.span));
+        }
+    }
+    found
+}
+
+/// The helpers declared directly in `body`, at any block depth but not
+/// inside another helper
+pub fn helpers(body: &[Stmt]) -> Vec<String> {
+    let mut names = Vec::new();
+    walk(body, &mut |stmt| {
+        if let StmtKind::Function { name, .. } = &stmt.kind {
+            names.push(name.clone());
+        }
+    });
+    names
+}
+
+/// Move the helpers out of a body named `prefix` into `output`, renaming
+/// the calls the body makes to them and to the helpers already in scope
+fn lift_body(prefix: &str, body: &mut Vec<Stmt>, scope: &HashMap<String, String>, output: &mut Vec<Stmt>) {
+    let mut lifted = Vec::new();
+    take_helpers(body, &mut lifted);
+    let mut scope = scope.clone();
+    for helper in &lifted {
+        if let StmtKind::Function { name, .. } = &helper.kind {
+            scope.insert(name.clone(), format!("{}.{}", prefix, name));
+        }
+    }
+    rename_calls(body, &scope);
+
+    for mut helper in lifted {
+        if let StmtKind::Function { name, body, .. } = &mut helper.kind {
+            *name = scope[name.as_str()].clone();
+            let prefix = name.clone();
+            lift_body(&prefix, body, &scope, output);
+        }
+        output.push(helper);
+    }
+}
+
+/// Remove the helpers from `body` and its blocks, in order
+fn take_helpers(body: &mut Vec<Stmt>, lifted: &mut Vec<Stmt>) {
+    let mut kept = Vec::new();
+    for mut stmt in body.drain(..) {
+        if matches!(stmt.kind, StmtKind::Function { .. }) {
+            lifted.push(stmt);
+            continue;
+        }
+        for block in blocks_mut(&mut stmt.kind) {
+            take_helpers(block, lifted);
+        }
+        kept.push(stmt);
+    }
+    *body = kept;
+}
+
+fn rename_calls(body: &mut [Stmt], scope: &HashMap<String, String>) {
+    for stmt in body {
+        match &mut stmt.kind {
+            StmtKind::Let { value, .. } | StmtKind::LetTuple { value, .. } | StmtKind::Assign { value, .. }
+            | StmtKind::Expression(value) | StmtKind::Return(value) | StmtKind::Value(value) | StmtKind::Panic(value) => {
+                rename_expr(value, scope)
+            }
+            StmtKind::If { condition: value, .. } | StmtKind::While { condition: value, .. }
+            | StmtKind::For { iterable: value, .. } | StmtKind::Match { value, .. } => rename_expr(value, scope),
+            _ => {}
+        }
+        for block in blocks_mut(&mut stmt.kind) {
+            rename_calls(block, scope);
+        }
+    }
+}
+
+fn rename_expr(expr: &mut Expr, scope: &HashMap<String, String>) {
+    match &mut expr.kind {
+        ExprKind::Call(name, args) => {
+            if let Some(qualified) = scope.get(name.as_str()) {
+                *name = qualified.clone();
+            }
+            for arg in args {
+                rename_expr(arg, scope);
+            }
+        }
+        ExprKind::ModuleCall(_, _, args) | ExprKind::Tuple(args) | ExprKind::Array(args) => {
+            for arg in args {
+                rename_expr(arg, scope);
+            }
+        }
+        ExprKind::Await(inner) | ExprKind::Try(inner) | ExprKind::Named(_, inner) | ExprKind::Cast(inner, _) => rename_expr(inner, scope),
+        ExprKind::Range(l, r) | ExprKind::Slice(l, r)
+        | ExprKind::Add(l, r) | ExprKind::Sub(l, r) | ExprKind::Mul(l, r) | ExprKind::Div(l, r) | ExprKind::Mod(l, r)
+        | ExprKind::Eq(l, r) | ExprKind::Ne(l, r) | ExprKind::Lt(l, r) | ExprKind::Le(l, r)
+        | ExprKind::Gt(l, r) | ExprKind::Ge(l, r)
+            | ExprKind::And(l, r) | ExprKind::Or(l, r) => {
+            rename_expr(l, scope);
+            rename_expr(r, scope);
+        }
+        ExprKind::Number(_) | ExprKind::Float(_) | ExprKind::Bool(_) | ExprKind::String(_)
+        | ExprKind::Identifier(_) | ExprKind::Property(..) => {}
+    }
+}
+
+/// Every variable name read in `expr`
+fn identifiers(expr: &Expr, out: &mut Vec<String>) {
+    match &expr.kind {
+        ExprKind::Identifier(name) => out.push(name.clone()),
+        ExprKind::ModuleCall(receiver, _, args) => {
+            out.push(receiver.clone());
+            for arg in args {
+                identifiers(arg, out);
+            }
+        }
+        ExprKind::Call(_, args) | ExprKind::Tuple(args) | ExprKind::Array(args) => {
+            for arg in args {
+                identifiers(arg, out);
+            }
+        }
+        ExprKind::Await(inner) | ExprKind::Try(inner) | ExprKind::Named(_, inner) | ExprKind::Cast(inner, _) => identifiers(inner, out),
+        ExprKind::Range(l, r) | ExprKind::Slice(l, r)
+        | ExprKind::Add(l, r) | ExprKind::Sub(l, r) | ExprKind::Mul(l, r) | ExprKind::Div(l, r) | ExprKind::Mod(l, r)
+        | ExprKind::Eq(l, r) | ExprKind::Ne(l, r) | ExprKind::Lt(l, r) | ExprKind::Le(l, r)
+        | ExprKind::Gt(l, r) | ExprKind::Ge(l, r)
+            | ExprKind::And(l, r) | ExprKind::Or(l, r) => {
+            identifiers(l, out);
+            identifiers(r, out);
+        }
+        ExprKind::Number(_) | ExprKind::Float(_) | ExprKind::Bool(_) | ExprKind::String(_) | ExprKind::Property(..) => {}
+    }
+}
+
+/// The names a statement binds for the rest of its function
+fn bindings(kind: &StmtKind) -> Vec<String> {
+    match kind {
+        StmtKind::Let { name, .. } => vec![name.clone()],
+        StmtKind::LetTuple { names, .. } => names.clone(),
+        StmtKind::For { var, .. } => vec![var.clone()],
+        StmtKind::Match { arms, .. } => arms
+            .iter()
+            .filter_map(|arm| match &arm.pattern {
+                Pattern::Some(name) | Pattern::Ok(name) | Pattern::Err(name) => Some(name.clone()),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// The expressions a statement evaluates itself, outside its blocks
+fn stmt_exprs(kind: &StmtKind) -> Vec<&Expr> {
+    match kind {
+        StmtKind::Let { value, .. } | StmtKind::LetTuple { value, .. } | StmtKind::Assign { value, .. }
+        | StmtKind::Expression(value) | StmtKind::Return(value) | StmtKind::Value(value) | StmtKind::Panic(value)
+        | StmtKind::If { condition: value, .. } | StmtKind::While { condition: value, .. }
+        | StmtKind::For { iterable: value, .. } | StmtKind::Match { value, .. } => vec![value],
+        _ => Vec::new(),
+    }
+}
+
+/// Visit each statement of `body` and its blocks, not entering helpers
+fn walk<'a>(body: &'a [Stmt], visit: &mut impl FnMut(&'a Stmt)) {
+    for stmt in body {
+        visit(stmt);
+        if !matches!(stmt.kind, StmtKind::Function { .. }) {
+            for block in blocks(&stmt.kind) {
+                walk(block, visit);
+            }
+        }
+    }
+}
+
+/// Visit each statement of `body`, its blocks and its helpers
+fn walk_all<'a>(body: &'a [Stmt], visit: &mut impl FnMut(&'a Stmt)) {
+    for stmt in body {
+        visit(stmt);
+        match &stmt.kind {
+            StmtKind::Function { body, .. } => walk_all(body, visit),
+            kind => {
+                for block in blocks(kind) {
+                    walk_all(block, visit);
+                }
+            }
+        }
+    }
+}
+
+fn blocks(kind: &StmtKind) -> Vec<&Vec<Stmt>> {
+    match kind {
+        StmtKind::If { then_body, else_body, .. } => std::iter::once(then_body).chain(else_body.as_ref()).collect(),
+        StmtKind::While { body, .. } | StmtKind::For { body, .. } | StmtKind::Defer(body) => vec![body],
+        StmtKind::Match { arms, .. } => arms.iter().map(|arm| &arm.body).collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn blocks_mut(kind: &mut StmtKind) -> Vec<&mut Vec<Stmt>> {
+    match kind {
+        StmtKind::If { then_body, else_body, .. } => std::iter::once(then_body).chain(else_body.as_mut()).collect(),
+        StmtKind::While { body, .. } | StmtKind::For { body, .. } | StmtKind::Defer(body) => vec![body],
+        StmtKind::Match { arms, .. } => arms.iter_mut().map(|arm| &mut arm.body).collect(),
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    #[test]
+    fn test_helpers_are_lifted_and_their_calls_renamed() {
+        let source = r#"
+            fn double(x) {
+                return x * 2
+            }
+            fn outer(n) {
+                fn double(x) {
+                    return x + x
+                }
+                if n > 0 {
+                    fn step(x) {
+                        return double(x) - 1
+                    }
+                    return step(n)
+                }
+                return double(n)
+            }
+            fn other(n) {
+                return double(n)
+            }
+        "#;
+        let program = Parser::new(Lexer::new(source)).parse().unwrap();
+        let lifted = lift(&program);
+
+        let names: Vec<&str> = lifted
+            .iter()
+            .filter_map(|stmt| match &stmt.kind {
+                StmtKind::Function { name, .. } => Some(name.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(names, vec!["double", "outer.double", "outer.step", "outer", "other"]);
+
+        let module = crate::lowering::lower(&program);
+        let calls = |function: &str| -> Vec<String> {
+            let function = module.functions.iter().find(|f| f.name == function).unwrap();
+            function
+                .instructions
+                .iter()
+                .filter_map(|i| match i {
+                    crate::ir::IRInstr::Call(name, _) => Some(name.clone()),
+                    _ => None,
+                })
+                .collect()
+        };
+        assert_eq!(calls("outer"), vec!["outer.step", "outer.double"]);
+        assert_eq!(calls("outer.step"), vec!["outer.double"]);
+        assert_eq!(calls("other"), vec!["double"]);
+    }
+
+    #[test]
+    fn test_helpers_using_enclosing_variables_are_captures() {
+        let source = r#"
+            fn outer(rate) {
+                let base = 10
+                fn scaled(x) {
+                    let y = x * 2
+                    return y + base
+                }
+                fn pure(x) {
+                    let rate = x
+                    return rate
+                }
+                return scaled(rate) + pure(1)
+            }
+        "#;
+        let program = Parser::new(Lexer::new(source)).parse().unwrap();
+        let StmtKind::Function { params, body, .. } = &program[0].kind else { panic!("not a function") };
+
+        let found: Vec<(String, String)> = captures(params, body).into_iter().map(|(h, v, _)| (h, v)).collect();
+        assert_eq!(found, vec![("scaled".to_string(), "base".to_string())]);
+        assert_eq!(helpers(body), vec!["scaled", "pure"]);
+    }
+}
@@ -1,48 +1,76 @@
 use crate::ir::IRInstr;
 
+use super::{lands_inside, splice};
+
 /// Fold constant arithmetic and comparison patterns inside a linear IR stream.
 pub fn const_fold(ir: &mut Vec<IRInstr>) {
+    // A converted constant is the constant of the other type
+    let mut i = 0;
+    while i + 1 < ir.len() {
+        let converted = match (&ir[i], &ir[i + 1]) {
+            (IRInstr::LoadConstFloat(f), IRInstr::FloatToInt) => Some(IRInstr::LoadConstInt(*f as i64)),
+            (IRInstr::LoadConstInt(n), IRInstr::IntToFloat) => Some(IRInstr::LoadConstFloat(*n as f64)),
+            _ => None,
+        };
+        // A jump landing on the conversion keeps it
+        if let Some(replacement) = converted.filter(|_| !lands_inside(ir, i..i + 2)) {
+            splice(ir, i..i + 2, vec![replacement]);
+        } else {
+            i += 1;
+        }
+    }
+
     let mut i = 0;
     while i + 2 < ir.len() {
         let replaced = match (&ir[i], &ir[i + 1], &ir[i + 2]) {
             (IRInstr::LoadConstInt(a), IRInstr::LoadConstInt(b), IRInstr::Add) => {
-                Some(IRInstr::LoadConstInt(a + b))
+                Some(IRInstr::LoadConstInt(a.wrapping_add(*b)))
             }
             (IRInstr::LoadConstInt(a), IRInstr::LoadConstInt(b), IRInstr::Sub) => {
-                Some(IRInstr::LoadConstInt(a - b))
+                Some(IRInstr::LoadConstInt(a.wrapping_sub(*b)))
             }
             (IRInstr::LoadConstInt(a), IRInstr::LoadConstInt(b), IRInstr::Mul) => {
-                Some(IRInstr::LoadConstInt(a * b))
+                Some(IRInstr::LoadConstInt(a.wrapping_mul(*b)))
+            }
+            // Checked arithmetic that overflows is left to trap at run time
+            (IRInstr::LoadConstInt(a), IRInstr::LoadConstInt(b), IRInstr::CheckedAdd(_)) => {
+                a.checked_add(*b).map(IRInstr::LoadConstInt)
+            }
+            (IRInstr::LoadConstInt(a), IRInstr::LoadConstInt(b), IRInstr::CheckedSub(_)) => {
+                a.checked_sub(*b).map(IRInstr::LoadConstInt)
+            }
+            (IRInstr::LoadConstInt(a), IRInstr::LoadConstInt(b), IRInstr::CheckedMul(_)) => {
+                a.checked_mul(*b).map(IRInstr::LoadConstInt)
             }
             (IRInstr::LoadConstInt(a), IRInstr::LoadConstInt(b), IRInstr::Div) if *b != 0 => {
-                Some(IRInstr::LoadConstInt(a / b))
+                Some(IRInstr::LoadConstInt(a.wrapping_div(*b)))
             }
             (IRInstr::LoadConstInt(a), IRInstr::LoadConstInt(b), IRInstr::Mod) if *b != 0 => {
-                Some(IRInstr::LoadConstInt(a % b))
+                Some(IRInstr::LoadConstInt(a.wrapping_rem(*b)))
             }
             (IRInstr::LoadConstInt(a), IRInstr::LoadConstInt(b), IRInstr::Eq) => {
-                Some(IRInstr::LoadConstInt(if a == b { 1 } else { 0 }))
+                Some(IRInstr::LoadConstBool(a == b))
             }
             (IRInstr::LoadConstInt(a), IRInstr::LoadConstInt(b), IRInstr::Ne) => {
-                Some(IRInstr::LoadConstInt(if a != b { 1 } else { 0 }))
+                Some(IRInstr::LoadConstBool(a != b))
             }
             (IRInstr::LoadConstInt(a), IRInstr::LoadConstInt(b), IRInstr::Lt) => {
-                Some(IRInstr::LoadConstInt(if a < b { 1 } else { 0 }))
+                Some(IRInstr::LoadConstBool(a < b))
             }
             (IRInstr::LoadConstInt(a), IRInstr::LoadConstInt(b), IRInstr::Le) => {
-                Some(IRInstr::LoadConstInt(if a <= b { 1 } else { 0 }))
+                Some(IRInstr::LoadConstBool(a <= b))
             }
             (IRInstr::LoadConstInt(a), IRInstr::LoadConstInt(b), IRInstr::Gt) => {
-                Some(IRInstr::LoadConstInt(if a > b { 1 } else { 0 }))
+                Some(IRInstr::LoadConstBool(a > b))
             }
             (IRInstr::LoadConstInt(a), IRInstr::LoadConstInt(b), IRInstr::Ge) => {
-                Some(IRInstr::LoadConstInt(if a >= b { 1 } else { 0 }))
+                Some(IRInstr::LoadConstBool(a >= b))
             }
             _ => None,
         };
 
-        if let Some(replacement) = replaced {
-            ir.splice(i..i + 3, [replacement]);
+        if let Some(replacement) = replaced.filter(|_| !lands_inside(ir, i..i + 3)) {
+            splice(ir, i..i + 3, vec![replacement]);
         } else {
             i += 1;
         }
@@ -0,0 +1,62 @@
+use crate::ir::{IRFunction, IRInstr};
+
+use super::{clobbers, is_jump_target, is_pure_operator, is_stable_operand, lands_inside, splice};
+
+/// Compute a repeated expression once per basic block. The expressions
+/// considered are two stable operands and a pure operator, such as
+/// `price * qty`: the first keeps its result in a new local and later
+/// ones in the same block load it, until a store or call changes an
+/// operand. A block ends at any jump, exit or jump target.
+pub fn common_subexpressions(func: &mut IRFunction) {
+    let mut i = 0;
+    while i + 3 <= func.instructions.len() {
+        let repeats = repeats_of(&func.instructions, i);
+        if repeats.is_empty() {
+            i += 1;
+            continue;
+        }
+
+        let temp = func.local_count as u32;
+        func.local_count += 1;
+        for &at in repeats.iter().rev() {
+            splice(&mut func.instructions, at..at + 3, vec![IRInstr::LoadLocal(temp)]);
+        }
+        splice(&mut func.instructions, i + 3..i + 3, vec![IRInstr::StoreLocal(temp), IRInstr::LoadLocal(temp)]);
+        i += 5;
+    }
+}
+
+/// Where the expression at `at` is computed again in its block with the
+/// same operand values
+fn repeats_of(ir: &[IRInstr], at: usize) -> Vec<usize> {
+    let expr = &ir[at..at + 3];
+    if !is_stable_operand(&expr[0]) || !is_stable_operand(&expr[1]) || !is_pure_operator(&expr[2]) {
+        return Vec::new();
+    }
+    if lands_inside(ir, at..at + 3) {
+        return Vec::new();
+    }
+
+    let mut repeats = Vec::new();
+    let mut j = at + 3;
+    while j < ir.len() {
+        if is_jump_target(ir, j) {
+            break;
+        }
+        if j + 3 <= ir.len() && ir[j..j + 3] == *expr && !lands_inside(ir, j..j + 3) {
+            repeats.push(j);
+            j += 3;
+            continue;
+        }
+        let instr = &ir[j];
+        let ends_block = matches!(
+            instr,
+            IRInstr::Jump(_) | IRInstr::JumpIfFalse(_) | IRInstr::Return | IRInstr::Panic | IRInstr::Revert
+        );
+        if ends_block || clobbers(instr, &expr[0]) || clobbers(instr, &expr[1]) {
+            break;
+        }
+        j += 1;
+    }
+    repeats
+}
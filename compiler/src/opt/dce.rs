@@ -1,11 +1,20 @@
 use crate::ir::IRInstr;
 
-/// Truncate instructions that are unreachable after an early exit.
+/// Truncate instructions that are unreachable after an early exit. An exit
+/// inside a branch is not the end: code that some jump lands past it stays.
 pub fn dead_code_elim(ir: &mut Vec<IRInstr>) {
-    if let Some(pos) = ir
+    let last_target = ir
         .iter()
-        .position(|i| matches!(i, IRInstr::Return | IRInstr::Panic))
-    {
+        .filter_map(|i| match i {
+            IRInstr::Jump(target) | IRInstr::JumpIfFalse(target) => Some(*target),
+            _ => None,
+        })
+        .max();
+    
+    if let Some(pos) = ir.iter().enumerate().position(|(pos, i)| {
+        matches!(i, IRInstr::Return | IRInstr::Panic | IRInstr::Revert)
+            && last_target.is_none_or(|target| target <= pos)
+    }) {
         ir.truncate(pos + 1);
     }
 }
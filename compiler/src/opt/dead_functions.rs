@@ -0,0 +1,37 @@
+use std::collections::HashSet;
+
+use crate::ast::Attribute;
+use crate::ir::{IRInstr, IRModule};
+
+/// Remove functions no entry point can reach. Entry points are `main`,
+/// every contract's constructor and methods, and `#[test]` functions;
+/// anything they call or load as a value, directly or through other
+/// functions, stays.
+pub fn remove_dead_functions(module: &mut IRModule) {
+    let mut reached: HashSet<String> = HashSet::new();
+    let mut pending: Vec<String> = module
+        .functions
+        .iter()
+        .filter(|f| f.name == "main" || f.has_attribute(Attribute::Test))
+        .map(|f| f.name.clone())
+        .collect();
+    for contract in &module.contracts {
+        pending.extend(contract.constructor.iter().chain(&contract.methods).cloned());
+    }
+
+    while let Some(name) = pending.pop() {
+        if !reached.insert(name.clone()) {
+            continue;
+        }
+        let Some(function) = module.find_function(&name) else { continue };
+        for instr in &function.instructions {
+            if let IRInstr::Call(callee, _) | IRInstr::LoadFunction(callee) = instr {
+                if !reached.contains(callee) {
+                    pending.push(callee.clone());
+                }
+            }
+        }
+    }
+
+    module.functions.retain(|f| reached.contains(&f.name));
+}
@@ -1,7 +1,10 @@
 use std::collections::HashMap;
 
+use crate::ast::Attribute;
 use crate::ir::{IRFunction, IRInstr, IRModule};
 
+use super::retarget;
+
 /// Inline small, branch-free functions directly at call sites.
 pub fn inline_small_functions(module: &mut IRModule) {
     let candidates = collect_candidates(module);
@@ -38,7 +41,8 @@ fn collect_candidates(module: &IRModule) -> HashMap<String, InlineCandidate> {
 }
 
 fn is_inline_candidate(func: &IRFunction) -> bool {
-    if func.instructions.len() > 5 {
+    // #[inline] lifts the size limit; the shape checks below still apply
+    if func.instructions.len() > 5 && !func.has_attribute(Attribute::Inline) {
         return false;
     }
 
@@ -50,7 +54,7 @@ fn is_inline_candidate(func: &IRFunction) -> bool {
     for instr in &func.instructions {
         match instr {
             IRInstr::Jump(_) | IRInstr::JumpIfFalse(_) => return false,
-            IRInstr::Call(_, _) | IRInstr::CallStd(_) | IRInstr::CallAI(_) => return false,
+            IRInstr::Call(_, _) | IRInstr::CallIndirect(_) | IRInstr::CallStd(_) | IRInstr::CallAI(_) => return false,
             IRInstr::Return => return_count += 1,
             _ => {}
         }
@@ -61,9 +65,13 @@ fn is_inline_candidate(func: &IRFunction) -> bool {
 
 fn inline_in_function(function: &mut IRFunction, candidates: &HashMap<String, InlineCandidate>) {
     let mut new_instrs = Vec::new();
+    // Where each instruction, and the end, moved to; inlined bodies have
+    // no jumps of their own, so only the caller's need moving
+    let mut moved = Vec::with_capacity(function.instructions.len() + 1);
     let mut i = 0;
 
     while i < function.instructions.len() {
+        moved.push(new_instrs.len());
         match &function.instructions[i] {
             IRInstr::Call(name, arg_count) => {
                 if let Some(callee) = candidates.get(name) {
@@ -106,5 +114,7 @@ fn inline_in_function(function: &mut IRFunction, candidates: &HashMap<String, In
         }
     }
 
+    moved.push(new_instrs.len());
+    retarget(&mut new_instrs, |_, target| moved[target.min(moved.len() - 1)]);
     function.instructions = new_instrs;
 }
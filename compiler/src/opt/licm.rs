@@ -0,0 +1,70 @@
+use crate::ir::{IRFunction, IRInstr};
+
+use super::{clobbers, is_pure_operator, is_stable_operand, lands_inside, retarget, splice};
+
+/// Compute what a loop never changes once, before it. A loop is the code
+/// from a backward jump's target to the jump; an expression of two stable
+/// operands and a pure operator that nothing in the loop clobbers moves
+/// into a new local just before the loop, and each computation of it in
+/// the loop loads that local. Jumps into the loop from outside land on the
+/// hoisted code, so every way in computes it. Inner loops go first, so an
+/// expression can move out through several.
+pub fn hoist_loop_invariants(func: &mut IRFunction) {
+    while let Some((header, end, at)) = find_invariant(&func.instructions) {
+        let expr = func.instructions[at..at + 3].to_vec();
+        let temp = func.local_count as u32;
+        func.local_count += 1;
+
+        let mut repeats = Vec::new();
+        let mut j = header;
+        while j + 3 <= end {
+            if func.instructions[j..j + 3] == expr[..] && !lands_inside(&func.instructions, j..j + 3) {
+                repeats.push(j);
+                j += 3;
+            } else {
+                j += 1;
+            }
+        }
+        for &r in repeats.iter().rev() {
+            splice(&mut func.instructions, r..r + 3, vec![IRInstr::LoadLocal(temp)]);
+        }
+
+        let end = end - 2 * repeats.len();
+        let mut hoisted = expr;
+        hoisted.push(IRInstr::StoreLocal(temp));
+        let len = hoisted.len();
+        retarget(&mut func.instructions, |from, target| {
+            let from_inside = (header..end).contains(&from);
+            if target > header || (target == header && from_inside) { target + len } else { target }
+        });
+        func.instructions.splice(header..header, hoisted);
+    }
+}
+
+/// The first loop, by where it ends, with an invariant expression: its
+/// header, its end and where the expression is
+fn find_invariant(ir: &[IRInstr]) -> Option<(usize, usize, usize)> {
+    for (pos, instr) in ir.iter().enumerate() {
+        let IRInstr::Jump(header) = *instr else { continue };
+        if header > pos {
+            continue;
+        }
+        let body = &ir[header..=pos];
+        let invariant = |operand: &IRInstr| body.iter().all(|instr| !clobbers(instr, operand));
+        let constant = |operand: &IRInstr| matches!(operand, IRInstr::LoadConstInt(_) | IRInstr::LoadConstBool(_));
+        for at in header..pos.saturating_sub(2) {
+            let expr = &ir[at..at + 3];
+            let hoistable = is_stable_operand(&expr[0])
+                && is_stable_operand(&expr[1])
+                && is_pure_operator(&expr[2])
+                && !(constant(&expr[0]) && constant(&expr[1]))
+                && invariant(&expr[0])
+                && invariant(&expr[1])
+                && !lands_inside(ir, at..at + 3);
+            if hoistable {
+                return Some((header, pos + 1, at));
+            }
+        }
+    }
+    None
+}
@@ -4,44 +4,243 @@
 //! - Constant folding: shrink pure expressions
 //! - Dead code elimination: strip unreachable tails
 //! - Basic inlining: inline tiny branch-free functions
+//! - Common subexpressions: compute a repeated expression once per block
+//! - Loop-invariant code motion: compute what a loop never changes before it
+//! - Peephole: drop instructions that do nothing
+//! - Dead functions: drop functions no entry point reaches
+//!
+//! A `Level` picks the passes, as `-O0` to `-O2` and `-Os` do. Passes that
+//! add or remove instructions go through `splice`, so jumps keep landing
+//! on the instructions they targeted.
 
 mod const_fold;
+mod cse;
 mod dce;
+mod dead_functions;
 mod inline;
+mod licm;
+mod peephole;
+
+use std::ops::Range;
 
-use crate::ir::{IRInstr, IRModule};
+use crate::ir::{IRFunction, IRInstr, IRModule};
 
 pub use const_fold::const_fold;
+pub use cse::common_subexpressions;
 pub use dce::dead_code_elim;
+pub use dead_functions::remove_dead_functions;
 pub use inline::inline_small_functions;
+pub use licm::hoist_loop_invariants;
+pub use peephole::peephole;
+
+/// Which passes to run; `astrixa build --compare-opt` toggles them one by one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Passes {
+    pub const_fold: bool,
+    pub dce: bool,
+    pub inline: bool,
+    pub cse: bool,
+    pub licm: bool,
+    pub peephole: bool,
+    pub dead_functions: bool,
+}
+
+impl Passes {
+    pub const ALL: Passes = Passes {
+        const_fold: true,
+        dce: true,
+        inline: true,
+        cse: true,
+        licm: true,
+        peephole: true,
+        dead_functions: true,
+    };
+    pub const NONE: Passes = Passes {
+        const_fold: false,
+        dce: false,
+        inline: false,
+        cse: false,
+        licm: false,
+        peephole: false,
+        dead_functions: false,
+    };
+}
+
+/// How hard a build optimizes: `-O0` runs nothing, for the fastest compile
+/// and IR that maps straight back to the source; `-O1` folds and strips
+/// dead code; `-O2` adds inlining, common subexpressions and loop-invariant
+/// code motion for speed; `-Os` trades those for the smallest module
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    O0,
+    O1,
+    O2,
+    Os,
+}
+
+impl Level {
+    /// As written after `-O`
+    pub const NAMES: &'static [&'static str] = &["0", "1", "2", "s"];
+
+    /// A level from `2`, `O2` or `-O2`
+    pub fn parse(text: &str) -> Option<Level> {
+        match text.trim_start_matches('-').trim_start_matches('O') {
+            "0" => Some(Level::O0),
+            "1" => Some(Level::O1),
+            "2" => Some(Level::O2),
+            "s" => Some(Level::Os),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Level::O0 => "0",
+            Level::O1 => "1",
+            Level::O2 => "2",
+            Level::Os => "s",
+        }
+    }
+
+    pub fn passes(&self) -> Passes {
+        match self {
+            Level::O0 => Passes::NONE,
+            Level::O1 => Passes { const_fold: true, dce: true, ..Passes::NONE },
+            Level::O2 => Passes { const_fold: true, dce: true, inline: true, cse: true, licm: true, ..Passes::NONE },
+            Level::Os => Passes { const_fold: true, dce: true, peephole: true, dead_functions: true, ..Passes::NONE },
+        }
+    }
+}
 
 /// Run all instruction-level passes on a single function body.
 pub fn optimize(ir: &mut Vec<IRInstr>) {
-    const_fold(ir);
-    dead_code_elim(ir);
+    optimize_with(ir, Passes::ALL);
 }
 
-/// Run optimization passes across an entire module.
+fn optimize_with(ir: &mut Vec<IRInstr>, passes: Passes) {
+    if passes.const_fold {
+        const_fold(ir);
+    }
+    if passes.dce {
+        dead_code_elim(ir);
+    }
+    if passes.peephole {
+        peephole(ir);
+    }
+}
+
+/// The instruction-level passes, then those that add locals
+fn optimize_function(func: &mut IRFunction, passes: Passes) {
+    optimize_with(&mut func.instructions, passes);
+    if passes.licm {
+        hoist_loop_invariants(func);
+    }
+    if passes.cse {
+        common_subexpressions(func);
+    }
+}
+
+/// Run the release build's passes (`-O2`) across an entire module.
 pub fn optimize_module(module: &IRModule) -> IRModule {
+    optimize_module_with(module, Level::O2.passes())
+}
+
+/// Run the selected passes across an entire module.
+pub fn optimize_module_with(module: &IRModule, passes: Passes) -> IRModule {
     let mut optimized = module.clone();
 
     for func in optimized.functions.iter_mut() {
-        optimize(&mut func.instructions);
+        optimize_function(func, passes);
     }
 
-    inline_small_functions(&mut optimized);
+    if passes.inline {
+        inline_small_functions(&mut optimized);
 
-    // Re-run lightweight passes to clean up any new opportunities exposed by inlining.
-    for func in optimized.functions.iter_mut() {
-        optimize(&mut func.instructions);
+        // Re-run lightweight passes to clean up any new opportunities exposed by inlining.
+        for func in optimized.functions.iter_mut() {
+            optimize_function(func, passes);
+        }
+    }
+
+    if passes.dead_functions {
+        remove_dead_functions(&mut optimized);
     }
 
     optimized
 }
 
+/// Replace `ir[range]` with `replacement`, keeping every jump on the
+/// instruction it targeted. A jump to the start of `range` lands on the
+/// replacement, or on what follows if the replacement is empty.
+pub(crate) fn splice(ir: &mut Vec<IRInstr>, range: Range<usize>, replacement: Vec<IRInstr>) {
+    let (start, end, added) = (range.start, range.end, replacement.len());
+    retarget(ir, |_, target| match target {
+        t if t >= end => t - (end - start) + added,
+        t => t.min(start),
+    });
+    ir.splice(range, replacement);
+}
+
+/// Whether a jump lands inside `range` past its first instruction, so the
+/// range cannot be rewritten as a whole
+pub(crate) fn lands_inside(ir: &[IRInstr], range: Range<usize>) -> bool {
+    ir.iter().any(|instr| match instr {
+        IRInstr::Jump(target) | IRInstr::JumpIfFalse(target) => *target > range.start && *target < range.end,
+        _ => false,
+    })
+}
+
+/// Whether some jump lands on `at`, which starts a basic block
+pub(crate) fn is_jump_target(ir: &[IRInstr], at: usize) -> bool {
+    ir.iter().any(|instr| matches!(instr, IRInstr::Jump(t) | IRInstr::JumpIfFalse(t) if *t == at))
+}
+
+/// Point each jump, given its index, at a new target
+pub(crate) fn retarget(ir: &mut [IRInstr], target: impl Fn(usize, usize) -> usize) {
+    for (at, instr) in ir.iter_mut().enumerate() {
+        if let IRInstr::Jump(t) | IRInstr::JumpIfFalse(t) = instr {
+            *t = target(at, *t);
+        }
+    }
+}
+
+/// An instruction that pushes a value without side effects or reading
+/// anything a call could change: an operand CSE and LICM may reuse
+pub(crate) fn is_stable_operand(instr: &IRInstr) -> bool {
+    matches!(instr, IRInstr::LoadConstInt(_) | IRInstr::LoadConstBool(_) | IRInstr::LoadLocal(_) | IRInstr::LoadGlobal(_))
+}
+
+/// A binary operator with no side effects that never traps, so computing it
+/// early or once changes nothing
+pub(crate) fn is_pure_operator(instr: &IRInstr) -> bool {
+    matches!(
+        instr,
+        IRInstr::Add | IRInstr::Sub | IRInstr::Mul
+            | IRInstr::Eq | IRInstr::Ne | IRInstr::Lt | IRInstr::Le | IRInstr::Gt | IRInstr::Ge
+            | IRInstr::And | IRInstr::Or
+    )
+}
+
+/// Whether running `instr` may change the value `operand` pushes: a store
+/// to its local or global, or any call for a global, since a call may
+/// write contract state
+pub(crate) fn clobbers(instr: &IRInstr, operand: &IRInstr) -> bool {
+    match (operand, instr) {
+        (IRInstr::LoadLocal(slot), IRInstr::StoreLocal(stored)) => slot == stored,
+        (IRInstr::LoadGlobal(name), IRInstr::StoreGlobal(stored)) => name == stored,
+        (IRInstr::LoadGlobal(_), other) => matches!(
+            other,
+            IRInstr::StoreVar(_) | IRInstr::Call(..) | IRInstr::CallIndirect(_) | IRInstr::CallStd(_)
+                | IRInstr::CallAI(_) | IRInstr::CallWeb3(_) | IRInstr::CallFS(_)
+        ),
+        _ => false,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ast::{Attribute, Span};
     use crate::ir::IRFunction;
 
     #[test]
@@ -75,11 +274,12 @@ mod tests {
 
     #[test]
     fn inlines_small_function_body() {
-        let mut module = IRModule {
+        let module = IRModule {
             functions: vec![
                 IRFunction {
                     name: "add".to_string(),
                     param_count: 2,
+                    param_kinds: vec![],
                     instructions: vec![
                         IRInstr::LoadLocal(0),
                         IRInstr::LoadLocal(1),
@@ -87,10 +287,18 @@ mod tests {
                         IRInstr::Return,
                     ],
                     local_count: 2,
+                    local_names: Vec::new(),
+                    result_count: 1,
+                    attributes: vec![],
+                    span: Span::default(),
+                    unsupported: Vec::new(),
+                    is_async: false,
+                    arithmetic: Vec::new(),
                 },
                 IRFunction {
                     name: "main".to_string(),
                     param_count: 0,
+                    param_kinds: vec![],
                     instructions: vec![
                         IRInstr::LoadConstInt(2),
                         IRInstr::LoadConstInt(3),
@@ -98,8 +306,18 @@ mod tests {
                         IRInstr::Return,
                     ],
                     local_count: 0,
+                    local_names: Vec::new(),
+                    result_count: 1,
+                    attributes: vec![],
+                    span: Span::default(),
+                    unsupported: Vec::new(),
+                    is_async: false,
+                    arithmetic: Vec::new(),
                 },
             ],
+            globals: vec![],
+            contracts: vec![],
+            memory: Default::default(),
         };
 
         let optimized = optimize_module(&module);
@@ -109,4 +327,154 @@ mod tests {
         assert_eq!(main.local_count, 2);
         assert!(matches!(main.instructions.last(), Some(IRInstr::Return)));
     }
+
+    #[test]
+    fn inline_attribute_lifts_size_limit() {
+        let helper = |attributes| IRFunction {
+            name: "scale".to_string(),
+            param_count: 1,
+            param_kinds: vec![],
+            instructions: vec![
+                IRInstr::LoadLocal(0),
+                IRInstr::LoadConstInt(1),
+                IRInstr::Add,
+                IRInstr::LoadConstInt(2),
+                IRInstr::Mul,
+                IRInstr::LoadConstInt(3),
+                IRInstr::Add,
+                IRInstr::Return,
+            ],
+            local_count: 1,
+            local_names: Vec::new(),
+            result_count: 1,
+            attributes,
+            span: Span::default(),
+            unsupported: Vec::new(),
+            is_async: false,
+            arithmetic: Vec::new(),
+        };
+        let main = IRFunction {
+            name: "main".to_string(),
+            param_count: 0,
+            param_kinds: vec![],
+            instructions: vec![
+                IRInstr::LoadConstInt(4),
+                IRInstr::Call("scale".to_string(), 1),
+                IRInstr::Return,
+            ],
+            local_count: 0,
+            local_names: Vec::new(),
+            result_count: 1,
+            attributes: vec![],
+            span: Span::default(),
+            unsupported: Vec::new(),
+            is_async: false,
+            arithmetic: Vec::new(),
+        };
+        let calls_scale = |module: &IRModule| {
+            module.functions.iter().find(|f| f.name == "main").unwrap()
+                .instructions.iter().any(|i| matches!(i, IRInstr::Call(_, _)))
+        };
+
+        let plain = IRModule { functions: vec![helper(vec![]), main.clone()], globals: vec![], contracts: vec![], memory: Default::default() };
+        assert!(calls_scale(&optimize_module(&plain)));
+
+        let marked = IRModule { functions: vec![helper(vec![Attribute::Inline]), main], globals: vec![], contracts: vec![], memory: Default::default() };
+        assert!(!calls_scale(&optimize_module(&marked)));
+    }
+
+    fn lowered(source: &str) -> IRModule {
+        let program = crate::parser::Parser::new(crate::lexer::Lexer::new(source)).parse().unwrap();
+        crate::lowering::lower(&program)
+    }
+
+    /// What each jump lands on, which passes must not change
+    fn landings(function: &IRFunction) -> Vec<Option<IRInstr>> {
+        let ir = &function.instructions;
+        ir.iter()
+            .filter_map(|instr| match instr {
+                IRInstr::Jump(t) | IRInstr::JumpIfFalse(t) => Some(ir.get(*t).cloned()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn levels_select_passes_and_keep_jumps() {
+        assert_eq!(Level::parse("-O2"), Some(Level::O2));
+        assert_eq!(Level::parse("s"), Some(Level::Os));
+        assert_eq!(Level::parse("3"), None);
+        assert!(!Level::Os.passes().inline && Level::O2.passes().licm);
+
+        let module = lowered(
+            r#"
+            fn unused() {
+                return 1
+            }
+            fn main() {
+                let price = 3
+                let qty = 4
+                let mut total = 0
+                let mut i = 0
+                while i < 10 {
+                    if i > 2 + 3 {
+                        total = total + price * qty
+                    }
+                    i = i + 1
+                }
+                return total + price * qty + price * qty
+            }
+            "#,
+        );
+        let main = |module: &IRModule| module.find_function("main").unwrap().clone();
+        let before = main(&module);
+
+        // -O0 leaves the IR as lowered
+        assert_eq!(main(&optimize_module_with(&module, Level::O0.passes())).instructions, before.instructions);
+
+        // -O1 folds 2 + 3 inside the loop; every jump still lands where it did
+        let o1 = main(&optimize_module_with(&module, Level::O1.passes()));
+        assert!(o1.instructions.len() < before.instructions.len());
+        assert_eq!(landings(&o1), landings(&before));
+
+        // -O2 computes price * qty before the loop, and once for the return
+        let o2 = main(&optimize_module_with(&module, Level::O2.passes()));
+        let (header, end) = loop_span(&o2);
+        assert!(!o2.instructions[header..end].contains(&IRInstr::Mul));
+        assert_eq!(o2.instructions.iter().filter(|i| **i == IRInstr::Mul).count(), 2);
+        assert_eq!(o2.local_count, before.local_count + 2);
+        assert_eq!(o2.instructions[header], before.instructions[loop_span(&before).0]);
+
+        // -Os drops the function nothing calls
+        let os = optimize_module_with(&module, Level::Os.passes());
+        assert!(os.find_function("unused").is_none() && os.find_function("main").is_some());
+    }
+
+    /// Where `function`'s loop starts, and its backward jump
+    fn loop_span(function: &IRFunction) -> (usize, usize) {
+        function.instructions.iter().enumerate()
+            .find_map(|(at, instr)| match instr {
+                IRInstr::Jump(t) if *t < at => Some((*t, at)),
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn peephole_drops_instructions_that_do_nothing() {
+        let mut ir = vec![
+            IRInstr::LoadLocal(0),
+            IRInstr::LoadConstInt(0),
+            IRInstr::Add,
+            IRInstr::JumpIfFalse(7),
+            IRInstr::LoadLocal(1),
+            IRInstr::Pop,
+            IRInstr::Jump(7),
+            IRInstr::Nop,
+            IRInstr::LoadConstInt(1),
+            IRInstr::Return,
+        ];
+        peephole(&mut ir);
+        assert_eq!(ir, vec![IRInstr::LoadLocal(0), IRInstr::JumpIfFalse(2), IRInstr::LoadConstInt(1), IRInstr::Return]);
+    }
 }
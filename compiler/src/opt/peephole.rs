@@ -0,0 +1,38 @@
+use crate::ir::IRInstr;
+
+use super::{lands_inside, splice};
+
+/// Drop instructions that do nothing: `nop`, a value pushed only to be
+/// popped, adding or subtracting 0, multiplying or dividing by 1, and a
+/// jump to the next instruction. Repeats until nothing changes, as one
+/// removal can expose another.
+pub fn peephole(ir: &mut Vec<IRInstr>) {
+    let mut changed = true;
+    while changed {
+        changed = false;
+        let mut i = 0;
+        while i < ir.len() {
+            let width = match (&ir[i], ir.get(i + 1)) {
+                (IRInstr::Nop, _) => 1,
+                (IRInstr::Jump(target), _) if *target == i + 1 => 1,
+                (
+                    IRInstr::LoadConstInt(_) | IRInstr::LoadConstFloat(_) | IRInstr::LoadConstBool(_)
+                    | IRInstr::LoadConstString(_) | IRInstr::LoadLocal(_) | IRInstr::LoadGlobal(_),
+                    Some(IRInstr::Pop),
+                ) => 2,
+                (
+                    IRInstr::LoadConstInt(0),
+                    Some(IRInstr::Add | IRInstr::Sub | IRInstr::CheckedAdd(_) | IRInstr::CheckedSub(_)),
+                ) => 2,
+                (IRInstr::LoadConstInt(1), Some(IRInstr::Mul | IRInstr::CheckedMul(_) | IRInstr::Div)) => 2,
+                _ => 0,
+            };
+            if width > 0 && !lands_inside(ir, i..i + width) {
+                splice(ir, i..i + width, Vec::new());
+                changed = true;
+            } else {
+                i += 1;
+            }
+        }
+    }
+}
@@ -0,0 +1,124 @@
+// The order expressions evaluate in, which every backend keeps.
+//
+// Within an expression, operands run left to right: `a() + b()` calls `a`
+// first, and so do comparisons, indexes and slices. A call evaluates all
+// of its arguments, left to right, before the function starts; arguments
+// passed by name run in the order of the parameters they bind to, since
+// `arguments::bind` puts them there before anything runs.
+//
+// `&&` and `||` short-circuit. The left side always runs; the right side
+// runs only when the left leaves the answer open, false for `&&` and true
+// for `||`:
+//
+//   if count > 0 && total / count > 10 { ... }   // no division by zero
+//
+// `&&` binds tighter than `||`, and both looser than comparisons, so
+// `a == 1 || b == 2 && c` is `(a == 1) || ((b == 2) && c)`. Both need
+// Bool operands and give a Bool.
+//
+// The interpreter walks the AST in this order. Lowering emits the IR in
+// it, and keeps the answer of `&&` and `||` in a scratch local so the jump
+// past the right side carries nothing on the stack; the WASM backend and
+// the VM (through `translate::to_bytecode`) run that IR as it is. The
+// tests below are the specification: each program has to give the same
+// outcome on all three.
+
+#[cfg(test)]
+mod tests {
+    use crate::codegen::{binary, wasm};
+    use crate::interpreter::{Interpreter, Value};
+    use crate::ir::IRModule;
+    use crate::lexer::Lexer;
+    use crate::lowering::lower;
+    use crate::opt::optimize_module;
+    use crate::parser::Parser;
+    use crate::translate::to_bytecode;
+    use crate::vm::VM;
+    use std::process::Command;
+
+    #[derive(Debug, PartialEq)]
+    enum Outcome {
+        Returned(i64),
+        Trapped,
+    }
+
+    fn outcome(result: Result<Value, String>) -> Outcome {
+        match result {
+            Ok(Value::Number(n)) => Outcome::Returned(n),
+            Ok(other) => panic!("main returned {:?}", other),
+            Err(_) => Outcome::Trapped,
+        }
+    }
+
+    fn lower_source(source: &str) -> IRModule {
+        lower(&Parser::new(Lexer::new(source)).parse().unwrap())
+    }
+
+    fn interpret(source: &str) -> Outcome {
+        let mut interpreter = Interpreter::new();
+        interpreter.load(Parser::new(Lexer::new(source)).parse().unwrap()).unwrap();
+        outcome(interpreter.call_values("main", vec![]))
+    }
+
+    fn on_vm(source: &str) -> Outcome {
+        let module = lower_source(source);
+        let main = to_bytecode(module.find_function("main").unwrap()).unwrap();
+        outcome(VM::new().run(main.instructions))
+    }
+
+    /// The optimized module, as a build ships it, run by Node; None where
+    /// Node is not installed
+    fn on_wasm(source: &str, case: usize) -> Option<Outcome> {
+        let wat = wasm::generate_wasm_module(&optimize_module(&lower_source(source)));
+        let path = std::env::temp_dir().join(format!("astrixa-order-{}-{}.wasm", std::process::id(), case));
+        std::fs::write(&path, binary::assemble(&wat).unwrap()).unwrap();
+        let script = "const module = new WebAssembly.Module(require('fs').readFileSync(process.argv[1]));\n\
+            const imports = {};\n\
+            for (const i of WebAssembly.Module.imports(module)) (imports[i.module] ??= {})[i.name] = () => 0;\n\
+            try { console.log(String(new WebAssembly.Instance(module, imports).exports.main())); } catch { console.log('trapped'); }";
+        let output = Command::new("node").arg("-e").arg(script).arg(&path).output();
+        let _ = std::fs::remove_file(&path);
+        let stdout = String::from_utf8(output.ok()?.stdout).unwrap();
+        Some(match stdout.trim() {
+            "trapped" => Outcome::Trapped,
+            n => Outcome::Returned(n.parse().unwrap_or_else(|_| panic!("node printed {:?}", n))),
+        })
+    }
+
+    #[test]
+    fn test_every_backend_evaluates_in_the_same_order() {
+        let program = |condition: &str| {
+            format!("fn main() {{\n    let zero = 0\n    if {} {{\n        return 1\n    }}\n    return 2\n}}\n", condition)
+        };
+        let cases = [
+            // The right side runs only when the left leaves the answer open
+            (program("false && 10 / zero == 1"), Outcome::Returned(2)),
+            (program("true || 10 / zero == 1"), Outcome::Returned(1)),
+            (program("true && 10 / zero == 1"), Outcome::Trapped),
+            (program("false || 10 / zero == 1"), Outcome::Trapped),
+            // The left always runs, even when the right alone would decide
+            (program("10 / zero == 1 && false"), Outcome::Trapped),
+            // && binds tighter than ||
+            (program("false && false || true"), Outcome::Returned(1)),
+            (program("true || false && 10 / zero == 1"), Outcome::Returned(1)),
+            // A short-circuit nested in the right side of another
+            (program("1 < 2 && (zero > 0 && 10 / zero == 1 || zero == 0)"), Outcome::Returned(1)),
+            // Kept as a value, beside operands already on the stack
+            ("fn main() {\n    let zero = 0\n    let ready = 3 > zero && zero == 0\n    if ready {\n        return 40 + 2\n    }\n    return 0\n}\n".to_string(), Outcome::Returned(42)),
+        ];
+
+        for (case, (source, expected)) in cases.iter().enumerate() {
+            assert_eq!(&interpret(source), expected, "interpreter, on\n{}", source);
+            assert_eq!(&on_vm(source), expected, "VM, on\n{}", source);
+            if let Some(actual) = on_wasm(source, case) {
+                assert_eq!(&actual, expected, "WASM, on\n{}", source);
+            }
+        }
+
+        let errors = crate::typechecker::TypeChecker::new()
+            .check(&Parser::new(Lexer::new(&program("zero && true"))).parse().unwrap())
+            .unwrap_err();
+        assert_eq!(errors[0].code, Some("E0271"));
+        assert!(errors[0].message.contains("'&&' requires Bool operands, got Int and Bool"), "{}", errors[0].message);
+    }
+}
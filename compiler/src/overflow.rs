@@ -0,0 +1,100 @@
+use crate::ast::Span;
+use crate::ir::{IRInstr, IRModule};
+
+// What Int arithmetic does when the result does not fit.
+//
+// By default `+`, `-` and `*` wrap around in two's complement, as do `/`
+// and `%` for the one quotient that overflows (the minimum divided by -1):
+//
+//   let big = 9223372036854775807
+//   print(big + 1)   // -9223372036854775808
+//
+// The interpreter, the VM and constant folding all wrap, so a program gives
+// the same result however it runs. A build with `--checked-arith` traps
+// instead: `check_arithmetic` turns Add, Sub and Mul into their checked
+// forms, which the WASM backend emits as calls to helpers that compute the
+// result at double width and, when it does not fit the target's Int, panic
+// with "integer overflow" and where the expression was written. WASM division traps on overflow by itself. Contract builds
+// are always checked, since an unnoticed wraparound in a balance is a
+// vulnerability; the chain rolls back a trapping call like any revert.
+
+/// Make Int addition, subtraction and multiplication in `module` trap on
+/// overflow. Run on the module as lowered, before any pass moves its
+/// arithmetic away from the spans lowering recorded for it.
+pub fn check_arithmetic(module: &mut IRModule) {
+    for function in &mut module.functions {
+        let mut spans = function.arithmetic.iter().copied();
+        for instr in &mut function.instructions {
+            let checked: fn(Span) -> IRInstr = match instr {
+                IRInstr::Add => IRInstr::CheckedAdd,
+                IRInstr::Sub => IRInstr::CheckedSub,
+                IRInstr::Mul => IRInstr::CheckedMul,
+                _ => continue,
+            };
+            *instr = checked(spans.next().unwrap_or_default());
+        }
+    }
+}
+
+/// What a checked build panics with when the arithmetic at `span`
+/// overflows; the standard library's have no span in the program
+pub fn message(span: Span) -> String {
+    if span == Span::default() {
+        "integer overflow".to_string()
+    } else {
+        format!("integer overflow ({})", span)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::wasm::generate_wasm_module;
+    use crate::lexer::Lexer;
+    use crate::lowering::lower;
+    use crate::opt::optimize_module;
+    use crate::parser::Parser;
+
+    fn lower_source(source: &str) -> IRModule {
+        lower(&Parser::new(Lexer::new(source)).parse().unwrap())
+    }
+
+    #[test]
+    fn test_checked_builds_trap_on_overflow() {
+        let source = "fn scale(a, b) {\n    return a * b + 1 - a / b\n}\nfn main() {\n    return 9223372036854775807 + 1\n}\n";
+
+        let mut module = lower_source(source);
+        check_arithmetic(&mut module);
+        let scale = &module.functions.iter().find(|f| f.name == "scale").unwrap().instructions;
+        let spans: Vec<(usize, usize)> = scale
+            .iter()
+            .filter_map(|instr| match instr {
+                IRInstr::CheckedAdd(span) | IRInstr::CheckedSub(span) | IRInstr::CheckedMul(span) => {
+                    Some((span.start_line, span.start_column))
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(spans, vec![(2, 12), (2, 12), (2, 12)]);
+        assert!(scale.contains(&IRInstr::Div) && !scale.contains(&IRInstr::Add));
+
+        // An overflowing constant is left for the runtime to trap on
+        let optimized = optimize_module(&module);
+        let main = &optimized.functions.iter().find(|f| f.name == "main").unwrap().instructions;
+        let overflowing = main.iter().any(|instr| matches!(instr, IRInstr::CheckedAdd(span) if (span.start_line, span.start_column) == (5, 12)));
+        assert!(overflowing, "{:?}", main);
+
+        // Trapping with a message saying where, through the panic import
+        let wat = generate_wasm_module(&optimized);
+        assert!(wat.contains("call $__checked_mul") && wat.contains("(func $__checked_mul"));
+        assert!(wat.contains("\"integer overflow (line 5, column 12)\""), "{}", wat);
+        assert!(wat.contains("(import \"env\" \"panic\"") && wat.contains("call $panic"));
+        assert!(!wat.contains("(export \"__checked_mul\""));
+
+        // Unchecked, it wraps at compile time as it would at run time
+        let wrapped = optimize_module(&lower_source(source));
+        let main = &wrapped.functions.iter().find(|f| f.name == "main").unwrap().instructions;
+        assert!(main.contains(&IRInstr::LoadConstInt(i64::MIN)), "{:?}", main);
+        assert!(!generate_wasm_module(&wrapped).contains("__checked"));
+    }
+}
@@ -1,7 +1,6 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
-use std::io::Write;
 use std::path::{Path, PathBuf};
 use sha2::{Sha256, Digest};
 
@@ -23,6 +22,7 @@ pub struct Lockfile {
 /// Package Manager - handles installation, resolution, and storage
 pub struct PackageManager {
     packages_dir: PathBuf,
+    #[allow(dead_code)]
     registry_url: String,
 }
 
@@ -51,7 +51,7 @@ impl PackageManager {
         let manifest = PackageManifest {
             name: project_name.to_string(),
             version: "0.1.0".to_string(),
-            description: Some(format!("A new ASTRIXA project")),
+            description: Some("A new ASTRIXA project".to_string()),
             dependencies: Some(HashMap::new()),
         };
         
@@ -164,7 +164,7 @@ export fn power(base: int, exp: int) -> int {
     }
     return result;
 }
-"#,
+"#.to_string(),
             "ai-tools" => r#"// AI utilities
 export fn create_prompt(text: string) -> string {
     return "AI: " + text;
@@ -174,7 +174,7 @@ export fn analyze_sentiment(text: string) -> string {
     // Basic sentiment example; replace with real implementation when available
     return "neutral";
 }
-"#,
+"#.to_string(),
             _ => format!(r#"// {} package
 export fn hello() -> string {{
     return "Hello from {}!";
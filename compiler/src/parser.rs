@@ -1,202 +1,1190 @@
-use crate::ast::{Expr, Stmt};
+use std::collections::HashSet;
+use crate::ast::{Attribute, Condition, ConditionKind, Expr, ExprKind, MatchArm, Pattern, Span, Stmt, StmtKind};
+use crate::edition::{self, Edition, Warning};
 use crate::error::CompileError;
 use crate::lexer::Lexer;
-use crate::token::Token;
+use crate::token::{Token, TokenKind};
 use crate::types::Type;
 
+/// A token lexed ahead of the parser, with where it starts and ends
+struct Lexed {
+    token: Token,
+    start: (usize, usize),
+    end: (usize, usize),
+}
+
 pub struct Parser {
-    lexer: Lexer,
+    tokens: Vec<Lexed>,            // The whole source, lexed up front, ending in EOF
+    next: usize,                   // Index in `tokens` of the token after the current one
+    lex_errors: Vec<CompileError>, // The malformed literals in the source
     current: Token,
+    current_start: (usize, usize), // Where the current token starts
+    current_end: (usize, usize),   // Where it ends
+    prev_end: (usize, usize),      // Where the previous token ends
+    spans: Vec<Span>,              // Statement spans in pre-order
+    edition: Edition,
+    warnings: Vec<Warning>,        // Deprecations under `edition`
+    migrating: bool,               // Fixable edition errors become warnings
+    errors: Vec<CompileError>,     // Syntax errors recovered from so far
+    block_values: HashSet<(usize, usize)>, // Starts of unterminated final expressions
+    in_trait: bool,                // Parsing method signatures, which take no body
 }
 
 impl Parser {
     pub fn new(mut lexer: Lexer) -> Self {
-        let current = lexer.next_token();
-        Self { lexer, current }
+        let mut tokens = Vec::new();
+        loop {
+            let token = lexer.next_token();
+            let end = token == Token::EOF;
+            tokens.push(Lexed { token, start: (lexer.token_line, lexer.token_column), end: (lexer.line, lexer.column) });
+            if end {
+                break;
+            }
+        }
+        let mut parser = Self {
+            tokens,
+            next: 0,
+            lex_errors: lexer.take_errors(),
+            current: Token::EOF,
+            current_start: (0, 0),
+            current_end: (0, 0),
+            prev_end: (0, 0),
+            spans: Vec::new(),
+            edition: Edition::default(),
+            warnings: Vec::new(),
+            migrating: false,
+            errors: Vec::new(),
+            block_values: HashSet::new(),
+            in_trait: false,
+        };
+        parser.advance();
+        parser.prev_end = parser.current_start;
+        parser
+    }
+
+    /// Parse as the given edition (the default is the oldest)
+    pub fn set_edition(&mut self, edition: Edition) {
+        self.edition = edition;
+    }
+
+    /// Report what `edition` rejects but has a mechanical fix for as a
+    /// warning with that fix instead of an error, for `migrate`
+    pub fn set_migrating(&mut self, migrating: bool) {
+        self.migrating = migrating;
+    }
+
+    /// Deprecation warnings collected while parsing
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    /// Warn about a construct if the edition retires it; `start` is where
+    /// the construct begins and it ends at the previous token
+    fn deprecated(&mut self, code: &'static str, start: (usize, usize), replacement: Option<String>) {
+        let Some(deprecation) = edition::DEPRECATIONS.iter().find(|d| d.code == code) else {
+            return;
+        };
+        if !edition::is_deprecated(code, self.edition) {
+            return;
+        }
+        let span = self.span_from(start);
+        self.warnings.push(Warning::new(code, &[&deprecation.since], span, replacement));
+    }
+
+    /// Check a name being bound or used at the current token against the
+    /// reserved words
+    fn check_name(&mut self, name: &str) {
+        let span = Span {
+            start_line: self.current_start.0,
+            start_column: self.current_start.1,
+            end_line: self.current_end.0,
+            end_column: self.current_end.1,
+        };
+        self.check_name_at(name, span);
+    }
+
+    /// A reserved word as a name is an error from `edition::RESERVED_NAMES`
+    /// on, and before it a warning that renames it. Either way parsing goes
+    /// on as if it were any other name.
+    fn check_name_at(&mut self, name: &str, span: Span) {
+        if !crate::token::is_reserved(name) {
+            return;
+        }
+        if self.edition >= edition::RESERVED_NAMES && !self.migrating {
+            let error = CompileError::coded("E0169", &[&name, &edition::RESERVED_NAMES], span.start_line, span.start_column);
+            self.errors.push(error);
+        } else {
+            let replacement = format!("{}_", name);
+            self.warnings.push(Warning::new("W0003", &[&name, &edition::RESERVED_NAMES], span, Some(replacement)));
+        }
     }
 
+    /// Move to the next token; past the end, EOF repeats
     fn advance(&mut self) {
-        self.current = self.lexer.next_token();
+        let last = self.tokens.len() - 1;
+        let next = &mut self.tokens[self.next];
+        // Tokens behind the current one are never looked at again
+        self.current = match self.next < last {
+            true => std::mem::replace(&mut next.token, Token::EOF),
+            false => Token::EOF,
+        };
+        self.prev_end = self.current_end;
+        self.current_start = next.start;
+        self.current_end = next.end;
+        self.next = (self.next + 1).min(last);
+    }
+
+    /// The token `n` places after the current one, counting from 1
+    fn ahead(&self, n: usize) -> &Lexed {
+        &self.tokens[(self.next + n - 1).min(self.tokens.len() - 1)]
+    }
+
+    /// The kind of the token `n` places ahead; `peek(0)` is the current one
+    fn peek(&self, n: usize) -> TokenKind {
+        match n {
+            0 => self.current.kind(),
+            _ => self.ahead(n).token.kind(),
+        }
+    }
+
+    /// Whether the token `n` places ahead is on the current token's line
+    fn peek_same_line(&self, n: usize) -> bool {
+        n == 0 || self.ahead(n).start.0 == self.current_start.0
+    }
+
+    /// Spans of every parsed statement, in the same pre-order as a walk of
+    /// the returned AST (a statement before its body; a contract's
+    /// constructor before its methods)
+    pub fn spans(&self) -> &[Span] {
+        &self.spans
+    }
+
+    /// After an error in a top-level declaration that began at `start`, skip
+    /// to the next token that can begin one
+    fn synchronize_declaration(&mut self, start: (usize, usize)) {
+        if self.current_start == start {
+            self.advance();
+        }
+        while !matches!(
+            self.current,
+            Token::Fn | Token::Async | Token::Export | Token::Import | Token::Contract | Token::Hash | Token::EOF
+        ) && !self.at_line_start_word(&["trait", "impl", "type", "struct"])
+        {
+            self.advance();
+        }
+    }
+
+    /// Whether the current token is one of `words` and first on its line
+    fn at_line_start_word(&self, words: &[&str]) -> bool {
+        let starts_line = self.current_start.0 > self.prev_end.0;
+        matches!(&self.current, Token::Identifier(word) if starts_line && words.contains(&word.as_str()))
+    }
+
+    /// After an error in a statement that began at `start`, skip to the next
+    /// statement of the same block: the first token of a later line, or a
+    /// statement keyword, outside any braces the statement opened. Stops
+    /// before a `}` closing the block.
+    fn synchronize_statement(&mut self, start: (usize, usize)) {
+        if self.current_start == start && self.current != Token::RBrace {
+            self.advance();
+        }
+        let mut depth = 0;
+        loop {
+            let starts_line = self.current_start.0 > self.prev_end.0;
+            match self.current {
+                Token::EOF => return,
+                Token::RBrace | Token::Semicolon if depth == 0 => return,
+                Token::Let | Token::If | Token::While | Token::For | Token::Return | Token::Panic
+                | Token::Require if depth == 0 => return,
+                _ if depth == 0 && starts_line => return,
+                Token::LBrace => depth += 1,
+                Token::RBrace => depth -= 1,
+                _ => {}
+            }
+            self.advance();
+        }
+    }
+
+    /// Run a statement parser and record the statement's span
+    fn spanned(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<StmtKind, CompileError>,
+    ) -> Result<Stmt, CompileError> {
+        let index = self.spans.len();
+        let start = self.current_start;
+        self.spans.push(Span::default());
+
+        let kind = match parse(self) {
+            Ok(kind) => kind,
+            Err(error) => {
+                self.spans.truncate(index);
+                return Err(error);
+            }
+        };
+
+        let span = self.span_from(start);
+        self.spans[index] = span;
+        Ok(Stmt::new(kind, span))
+    }
+
+    /// From `start` to the end of the previous token
+    fn span_from(&self, start: (usize, usize)) -> Span {
+        Span {
+            start_line: start.0,
+            start_column: start.1,
+            end_line: self.prev_end.0,
+            end_column: self.prev_end.1,
+        }
     }
 
+    /// Parse the whole program. After a syntax error the parser skips to
+    /// the next statement or declaration and carries on, so `errors` holds
+    /// every error found; this returns the first.
     pub fn parse(&mut self) -> Result<Vec<Stmt>, CompileError> {
+        let stmts = self.parse_program();
+
+        // Malformed literals are reported once, however far the parser got
+        self.errors.append(&mut self.lex_errors);
+        self.errors.sort_by_key(|e| (e.line, e.column));
+
+        match self.errors.first() {
+            Some(error) => Err(error.clone()),
+            None => Ok(stmts),
+        }
+    }
+
+    /// Every syntax error of the last `parse`, in source order
+    pub fn errors(&self) -> &[CompileError] {
+        &self.errors
+    }
+
+    fn parse_program(&mut self) -> Vec<Stmt> {
         let mut stmts = Vec::new();
 
         while self.current != Token::EOF {
-            match &self.current {
-                Token::Import => {
-                    stmts.push(self.parse_import()?);
-                }
-                Token::Export => {
-                    stmts.push(self.parse_export_function()?);
-                }
-                Token::Fn => {
-                    stmts.push(self.parse_function(false)?);
-                }
+            let start = self.current_start;
+            let parsed = match &self.current {
+                Token::Import => self.spanned(Self::parse_import),
+                Token::Export => self.spanned(Self::parse_export_function),
+                Token::Fn => self.spanned(|p| p.parse_function(false, false)),
+                Token::Async => self.spanned(|p| p.parse_async_function(false)),
+                Token::Contract => self.spanned(Self::parse_contract),
+                Token::Hash => self.spanned(|p| p.parse_attributed_function(false)),
+                Token::Identifier(word) if word == "trait" => self.spanned(Self::parse_trait),
+                Token::Identifier(word) if word == "impl" => self.spanned(Self::parse_impl),
+                Token::Identifier(word) if word == "type" => self.spanned(Self::parse_type_alias),
+                Token::Identifier(word) if word == "struct" => self.spanned(Self::parse_struct),
                 _ => {
                     self.advance();
+                    continue;
+                }
+            };
+            match parsed {
+                Ok(stmt) => stmts.push(stmt),
+                Err(error) => {
+                    self.errors.push(error);
+                    self.synchronize_declaration(start);
                 }
             }
         }
 
-        Ok(stmts)
+        stmts
     }
     
-    fn parse_import(&mut self) -> Result<Stmt, CompileError> {
+    fn parse_import(&mut self) -> Result<StmtKind, CompileError> {
         self.advance();
         
         let module_name = match &self.current {
             Token::Identifier(name) => name.clone(),
             _ => {
-                return Err(CompileError::new(
-                    "Expected module name after 'import'",
-                    self.lexer.line,
-                    self.lexer.column,
-                ).help("Example: import math"));
+                return Err(CompileError::coded(
+                    "E0101",
+                    &[],
+                    self.current_end.0,
+                    self.current_end.1,
+                ));
             }
         };
         
         self.advance();
-        
-        Ok(Stmt::Import(module_name))
+
+        // import math.{add, sub}
+        let mut items = None;
+        if self.current == Token::Dot {
+            self.advance();
+            if self.current != Token::LBrace {
+                return Err(CompileError::coded(
+                    "E0131",
+                    &[],
+                    self.current_end.0,
+                    self.current_end.1,
+                ));
+            }
+            self.advance();
+
+            let mut names = Vec::new();
+            while self.current != Token::RBrace {
+                match &self.current {
+                    Token::Identifier(name) => {
+                        let name = name.clone();
+                        self.check_name(&name);
+                        names.push(name);
+                    }
+                    _ => {
+                        return Err(CompileError::coded(
+                            "E0132",
+                            &[],
+                            self.current_end.0,
+                            self.current_end.1,
+                        ));
+                    }
+                }
+                self.advance();
+                if self.current == Token::Comma {
+                    self.advance();
+                }
+            }
+            self.advance(); // consume '}'
+            items = Some(names);
+        }
+
+        // import math as m ('as' is contextual)
+        let mut alias = None;
+        if items.is_none() && self.current == Token::Identifier("as".to_string()) {
+            self.advance();
+            match &self.current {
+                Token::Identifier(name) => {
+                    let name = name.clone();
+                    self.check_name(&name);
+                    alias = Some(name);
+                }
+                _ => {
+                    return Err(CompileError::coded(
+                        "E0133",
+                        &[],
+                        self.current_end.0,
+                        self.current_end.1,
+                    ));
+                }
+            }
+            self.advance();
+        }
+
+        Ok(StmtKind::Import { module: module_name, alias, items })
     }
     
-    fn parse_export_function(&mut self) -> Result<Stmt, CompileError> {
+    fn parse_export_function(&mut self) -> Result<StmtKind, CompileError> {
         self.advance();
         
+        if self.current == Token::Async {
+            return self.parse_async_function(true);
+        }
+        
+        if self.current != Token::Fn {
+            return Err(CompileError::coded(
+                "E0102",
+                &[],
+                self.current_end.0,
+                self.current_end.1,
+            ));
+        }
+        
+        self.parse_function(true, false)
+    }
+    
+    fn parse_async_function(&mut self, exported: bool) -> Result<StmtKind, CompileError> {
+        self.advance(); // consume 'async'
+        
         if self.current != Token::Fn {
-            return Err(CompileError::new(
-                "Expected 'fn' after 'export'",
-                self.lexer.line,
-                self.lexer.column,
-            ).help("Only functions can be exported. Example: export fn add(a, b) { }"));
+            return Err(CompileError::coded(
+                "E0103",
+                &[],
+                self.current_end.0,
+                self.current_end.1,
+            ));
+        }
+        
+        self.parse_function(exported, true)
+    }
+
+    fn parse_function(&mut self, exported: bool, is_async: bool) -> Result<StmtKind, CompileError> {
+        self.advance();
+
+        let name = match &self.current {
+            Token::Identifier(name) => name.clone(),
+            _ => {
+                return Err(CompileError::coded(
+                    "E0104",
+                    &[],
+                    self.current_end.0,
+                    self.current_end.1,
+                ));
+            }
+        };
+        self.check_name(&name);
+
+        self.advance();
+
+        self.parse_function_rest(name, exported, is_async)
+    }
+
+    /// Parse the parameter list and body that follow a function name.
+    fn parse_function_rest(&mut self, name: String, exported: bool, is_async: bool) -> Result<StmtKind, CompileError> {
+        // Type parameters: `fn max<T>(a: T, b: T) -> T`
+        let mut type_params = Vec::new();
+        if let Token::Less = self.current {
+            self.advance();
+            while self.current != Token::Greater {
+                match &self.current {
+                    Token::Identifier(param) => type_params.push(param.clone()),
+                    _ => return Err(CompileError::coded("E0153", &[], self.current_end.0, self.current_end.1)),
+                }
+                self.advance();
+                if let Token::Comma = self.current {
+                    self.advance();
+                }
+            }
+            self.advance();
+        }
+
+        let mut params = Vec::new();
+        let mut param_types = Vec::new();
+        let mut defaults = Vec::new();
+        if let Token::LParen = self.current {
+            self.advance();
+            
+            while self.current != Token::RParen && self.current != Token::EOF {
+                if let Token::Identifier(param) = &self.current {
+                    let param = param.clone();
+                    self.check_name(&param);
+                    params.push(param.clone());
+                    self.advance();
+                    
+                    // Optional type annotation: `to: Address`
+                    let annotation = if let Token::Colon = self.current {
+                        self.advance();
+                        Some(self.parse_type_annotation()?)
+                    } else {
+                        None
+                    };
+                    param_types.push(annotation);
+
+                    // Optional default: `greeting = "Hello"`. Calls are
+                    // given it in place, so it is a literal; once one
+                    // parameter has a default, the rest need one too.
+                    let default = if let Token::Assign = self.current {
+                        self.advance();
+                        let value = self.parse_expression()?;
+                        if !matches!(value.kind, ExprKind::Number(_) | ExprKind::Float(_) | ExprKind::Bool(_) | ExprKind::String(_)) {
+                            return Err(CompileError::coded("E0165", &[&param], value.span.start_line, value.span.start_column));
+                        }
+                        Some(value)
+                    } else if defaults.iter().any(Option::is_some) {
+                        return Err(CompileError::coded("E0166", &[&param], self.current_end.0, self.current_end.1));
+                    } else {
+                        None
+                    };
+                    defaults.push(default);
+                    
+                    if let Token::Comma = self.current {
+                        self.advance();
+                    }
+                } else {
+                    return Err(CompileError::coded(
+                        "E0105",
+                        &[],
+                        self.current_end.0,
+                        self.current_end.1,
+                    ));
+                }
+            }
+            
+            if let Token::RParen = self.current {
+                self.advance();
+            }
+        }
+
+        let return_annotation = if let Token::Arrow = self.current {
+            self.advance();
+            Some(self.parse_type_annotation()?)
+        } else {
+            None
+        };
+        let return_type = Type::Void;
+
+        if self.in_trait && self.current == Token::LBrace {
+            return Err(CompileError::coded("E0154", &[&name], self.current_end.0, self.current_end.1));
+        }
+        
+        let body = if let Token::LBrace = self.current {
+            self.advance();
+            let mut body = self.parse_block()?;
+            self.implicit_return(&mut body);
+            if let Token::RBrace = self.current {
+                self.advance();
+            }
+            body
+        } else {
+            vec![]
+        };
+
+        Ok(StmtKind::Function {
+            name,
+            params,
+            param_types,
+            defaults,
+            type_params,
+            return_annotation,
+            return_type,
+            body,
+            exported,
+            is_async,
+            attributes: Vec::new(),
+            conditions: Vec::new(),
+        })
+    }
+
+    /// Parse `#[name]` attributes and the function they annotate. Inside a
+    /// contract only `fn` may follow; at the top level also `export`/`async`.
+    /// `#[requires(expr)]` and `#[ensures(expr)]` carry a condition.
+    fn parse_attributed_function(&mut self, in_contract: bool) -> Result<StmtKind, CompileError> {
+        let mut attributes = Vec::new();
+        let mut conditions = Vec::new();
+
+        while self.current == Token::Hash {
+            self.advance(); // consume '#'
+            if self.current != Token::LBracket {
+                return Err(CompileError::coded("E0134", &[], self.current_end.0, self.current_end.1));
+            }
+            self.advance(); // consume '['
+
+            let kind = match &self.current {
+                Token::Identifier(name) if name == "requires" => Some(ConditionKind::Requires),
+                Token::Identifier(name) if name == "ensures" => Some(ConditionKind::Ensures),
+                _ => None,
+            };
+            if let Some(kind) = kind {
+                self.advance();
+                if self.current != Token::LParen {
+                    return Err(CompileError::coded("E0167", &[&kind.name()], self.current_end.0, self.current_end.1));
+                }
+                self.advance(); // consume '('
+                let expr = self.parse_expression()?;
+                if self.current != Token::RParen {
+                    return Err(CompileError::coded("E0167", &[&kind.name()], self.current_end.0, self.current_end.1));
+                }
+                self.advance(); // consume ')'
+                if self.current != Token::RBracket {
+                    return Err(CompileError::coded("E0136", &[], self.current_end.0, self.current_end.1));
+                }
+                self.advance(); // consume ']'
+                conditions.push(Condition { kind, expr });
+                continue;
+            }
+
+            let attribute = match &self.current {
+                Token::Identifier(name) => match Attribute::from_name(name) {
+                    Some(attribute) => attribute,
+                    None => {
+                        let mut supported: Vec<&str> = Attribute::ALL.iter().map(|a| a.name()).collect();
+                        supported.extend(["requires(...)", "ensures(...)"]);
+                        return Err(CompileError::coded(
+                            "E0135",
+                            &[name, &supported.join(", ")],
+                            self.current_end.0,
+                            self.current_end.1,
+                        ));
+                    }
+                },
+                _ => {
+                    return Err(CompileError::coded("E0134", &[], self.current_end.0, self.current_end.1));
+                }
+            };
+            self.advance();
+
+            if self.current != Token::RBracket {
+                return Err(CompileError::coded("E0136", &[], self.current_end.0, self.current_end.1));
+            }
+            self.advance(); // consume ']'
+
+            if !attributes.contains(&attribute) {
+                attributes.push(attribute);
+            }
+        }
+
+        let mut function = match &self.current {
+            Token::Fn => self.parse_function(false, false)?,
+            Token::Export if !in_contract => self.parse_export_function()?,
+            Token::Async if !in_contract => self.parse_async_function(false)?,
+            _ => {
+                return Err(CompileError::coded("E0137", &[], self.current_end.0, self.current_end.1));
+            }
+        };
+
+        if let StmtKind::Function { attributes: slot, conditions: condition_slot, .. } = &mut function {
+            *slot = attributes;
+            *condition_slot = conditions;
+        }
+        Ok(function)
+    }
+    
+    /// Parse a contract declaration:
+    ///
+    /// ```text
+    /// contract Token {
+    ///     state: ["balances", "total_supply"]
+    ///     state owner: Address
+    ///     constructor(supply) { ... }
+    ///     fn transfer(to, amount) { ... }
+    /// }
+    /// ```
+    fn parse_contract(&mut self) -> Result<StmtKind, CompileError> {
+        self.advance(); // consume 'contract'
+
+        let name = match &self.current {
+            Token::Identifier(name) => name.clone(),
+            _ => {
+                return Err(CompileError::coded(
+                    "E0106",
+                    &[],
+                    self.current_end.0,
+                    self.current_end.1,
+                ));
+            }
+        };
+        self.check_name(&name);
+        self.advance();
+
+        if self.current != Token::LBrace {
+            return Err(CompileError::coded(
+                "E0107",
+                &[],
+                self.current_end.0,
+                self.current_end.1,
+            ));
+        }
+        self.advance(); // consume '{'
+
+        let mut state = Vec::new();
+        let mut state_types = Vec::new();
+        let mut constructor = None;
+        let mut methods = Vec::new();
+        let members_start = self.spans.len();
+        let mut constructor_spans = 0..0;
+
+        while self.current != Token::RBrace && self.current != Token::EOF {
+            match &self.current {
+                Token::Fn => {
+                    methods.push(self.spanned(|p| p.parse_function(false, false))?);
+                }
+                Token::Hash => {
+                    methods.push(self.spanned(|p| p.parse_attributed_function(true))?);
+                }
+                Token::Identifier(word) if word == "state" => {
+                    let start = self.current_start;
+                    self.advance(); // consume 'state'
+                    let list_form = self.current == Token::Colon;
+                    let vars = self.parse_state_declaration()?;
+                    if list_form {
+                        // One `state` line per variable, at the same indentation
+                        let indent = " ".repeat(start.1.saturating_sub(1));
+                        let fix = vars
+                            .iter()
+                            .all(|(v, _)| crate::text::is_ident(v))
+                            .then(|| {
+                                vars.iter()
+                                    .map(|(v, _)| format!("state {}", v))
+                                    .collect::<Vec<_>>()
+                                    .join(&format!("\n{}", indent))
+                            });
+                        self.deprecated("W0002", start, fix);
+                    }
+                    for (var, annotation) in vars {
+                        if state.contains(&var) {
+                            return Err(CompileError::coded(
+                                "E0108",
+                                &[&var, &name],
+                                self.current_end.0,
+                                self.current_end.1,
+                            ));
+                        }
+                        state.push(var);
+                        state_types.push(annotation);
+                    }
+                }
+                Token::Semicolon => self.advance(),
+                Token::Identifier(word) if word == "constructor" => {
+                    if constructor.is_some() {
+                        return Err(CompileError::coded(
+                            "E0109",
+                            &[&name],
+                            self.current_end.0,
+                            self.current_end.1,
+                        ));
+                    }
+                    let first = self.spans.len();
+                    let ctor = self.spanned(|p| {
+                        p.advance(); // consume 'constructor'
+                        p.parse_function_rest("constructor".to_string(), false, false)
+                    })?;
+                    constructor_spans = first..self.spans.len();
+                    constructor = Some(Box::new(ctor));
+                }
+                _ => {
+                    return Err(CompileError::coded(
+                        "E0110",
+                        &[],
+                        self.current_end.0,
+                        self.current_end.1,
+                    ));
+                }
+            }
+        }
+
+        if self.current != Token::RBrace {
+            return Err(CompileError::coded(
+                "E0111",
+                &[],
+                self.current_end.0,
+                self.current_end.1,
+            ));
+        }
+        self.advance(); // consume '}'
+
+        // The AST keeps the constructor apart from the methods, so its spans
+        // move ahead of the methods' to keep pre-order
+        if constructor_spans.start > members_start {
+            let ctor: Vec<Span> = self.spans.drain(constructor_spans).collect();
+            self.spans.splice(members_start..members_start, ctor);
+        }
+
+        Ok(StmtKind::Contract {
+            name,
+            state,
+            state_types,
+            constructor,
+            methods,
+        })
+    }
+
+    /// Parse a trait: the method signatures an `impl` must provide.
+    ///
+    /// ```text
+    /// trait Printable {
+    ///     fn to_string(self) -> String
+    /// }
+    /// ```
+    fn parse_trait(&mut self) -> Result<StmtKind, CompileError> {
+        self.advance(); // consume 'trait'
+
+        let name = match &self.current {
+            Token::Identifier(name) => name.clone(),
+            _ => return Err(CompileError::coded("E0155", &[&"trait"], self.current_end.0, self.current_end.1)),
+        };
+        self.check_name(&name);
+        self.advance();
+
+        self.in_trait = true;
+        let methods = self.parse_methods(&name);
+        self.in_trait = false;
+        Ok(StmtKind::Trait { name, methods: methods? })
+    }
+
+    /// Parse an implementation of a trait for a type:
+    ///
+    /// ```text
+    /// impl Printable for Int {
+    ///     fn to_string(self) -> String { ... }
+    /// }
+    /// ```
+    fn parse_impl(&mut self) -> Result<StmtKind, CompileError> {
+        self.advance(); // consume 'impl'
+
+        let trait_name = match &self.current {
+            Token::Identifier(name) => name.clone(),
+            _ => return Err(CompileError::coded("E0155", &[&"impl"], self.current_end.0, self.current_end.1)),
+        };
+        self.advance();
+
+        if self.current != Token::For {
+            return Err(CompileError::coded("E0156", &[], self.current_end.0, self.current_end.1));
+        }
+        self.advance(); // consume 'for'
+        let target = self.parse_type_annotation()?;
+
+        let methods = self.parse_methods(&trait_name)?;
+        Ok(StmtKind::Impl { trait_name, target, methods })
+    }
+
+    /// Parse a type alias, another name for a type:
+    ///
+    /// ```text
+    /// type Wei = U256
+    /// ```
+    fn parse_type_alias(&mut self) -> Result<StmtKind, CompileError> {
+        self.advance(); // consume 'type'
+
+        let name = match &self.current {
+            Token::Identifier(name) => name.clone(),
+            _ => return Err(CompileError::coded("E0155", &[&"type"], self.current_end.0, self.current_end.1)),
+        };
+        self.check_name(&name);
+        self.advance();
+
+        if self.current != Token::Assign {
+            return Err(CompileError::coded("E0168", &[&name], self.current_end.0, self.current_end.1));
+        }
+        self.advance(); // consume '='
+        let target = self.parse_type_annotation()?;
+        Ok(StmtKind::TypeAlias { name, target })
+    }
+
+    /// The language has no struct declarations yet, nor so generic ones;
+    /// a `struct` is reported rather than skipped with the rest it can't parse
+    fn parse_struct(&mut self) -> Result<StmtKind, CompileError> {
+        Err(CompileError::coded("E0174", &[], self.current_start.0, self.current_start.1))
+    }
+
+    /// `{ fn ... fn ... }` of a trait or impl named `owner`
+    fn parse_methods(&mut self, owner: &str) -> Result<Vec<Stmt>, CompileError> {
+        if self.current != Token::LBrace {
+            return Err(CompileError::coded("E0157", &[&owner], self.current_end.0, self.current_end.1));
+        }
+        self.advance(); // consume '{'
+
+        let mut methods = Vec::new();
+        while self.current != Token::RBrace && self.current != Token::EOF {
+            match self.current {
+                Token::Fn => methods.push(self.spanned(|p| p.parse_function(false, false))?),
+                Token::Semicolon => self.advance(),
+                _ => return Err(CompileError::coded("E0158", &[&owner], self.current_end.0, self.current_end.1)),
+            }
+        }
+
+        if self.current != Token::RBrace {
+            return Err(CompileError::coded("E0159", &[&owner], self.current_end.0, self.current_end.1));
+        }
+        self.advance(); // consume '}'
+        Ok(methods)
+    }
+
+    /// Parse the state variables following the `state` keyword.
+    /// Accepts either a list (`state: ["a", "b"]`) or a single
+    /// declaration with an optional type (`state owner: Address`).
+    fn parse_state_declaration(&mut self) -> Result<Vec<(String, Option<String>)>, CompileError> {
+        let mut vars = Vec::new();
+
+        match &self.current {
+            Token::Colon => {
+                self.advance(); // consume ':'
+                if self.current != Token::LBracket {
+                    return Err(CompileError::coded(
+                        "E0112",
+                        &[],
+                        self.current_end.0,
+                        self.current_end.1,
+                    ));
+                }
+                self.advance(); // consume '['
+
+                while self.current != Token::RBracket && self.current != Token::EOF {
+                    match &self.current {
+                        Token::String(var) | Token::Identifier(var) => {
+                            vars.push((var.clone(), None));
+                            self.advance();
+                        }
+                        _ => {
+                            return Err(CompileError::coded(
+                                "E0113",
+                                &[],
+                                self.current_end.0,
+                                self.current_end.1,
+                            ));
+                        }
+                    }
+                    if let Token::Comma = self.current {
+                        self.advance();
+                    }
+                }
+
+                if self.current != Token::RBracket {
+                    return Err(CompileError::coded(
+                        "E0114",
+                        &[],
+                        self.current_end.0,
+                        self.current_end.1,
+                    ));
+                }
+                self.advance(); // consume ']'
+            }
+            Token::Identifier(var) => {
+                let var = var.clone();
+                self.check_name(&var);
+                self.advance();
+                let annotation = match self.current {
+                    Token::Colon => {
+                        self.advance();
+                        Some(self.parse_type_annotation()?)
+                    }
+                    _ => None,
+                };
+                vars.push((var, annotation));
+            }
+            _ => {
+                return Err(CompileError::coded(
+                    "E0115",
+                    &[],
+                    self.current_end.0,
+                    self.current_end.1,
+                ));
+            }
+        }
+
+        Ok(vars)
+    }
+
+    /// Parse `= value` after `let name`
+    fn finish_let(&mut self, name: String, mutable: bool) -> Result<StmtKind, CompileError> {
+        if let Token::Assign = self.current {
+            self.advance();
+        } else {
+            return Err(CompileError::coded("E0119", &[], self.current_end.0, self.current_end.1));
+        }
+
+        let value = self.parse_expression()?;
+        Ok(StmtKind::Let { name, value, mutable })
+    }
+
+    /// Parse `(a, _, c) = value` after `let` or `let mut`
+    fn parse_let_tuple(&mut self, mutable: bool) -> Result<StmtKind, CompileError> {
+        self.advance(); // consume (
+
+        let mut names = Vec::new();
+        while self.current != Token::RParen {
+            match &self.current {
+                Token::Identifier(name) => {
+                    let name = name.clone();
+                    self.check_name(&name);
+                    names.push(name);
+                }
+                _ => {
+                    return Err(CompileError::coded("E0138", &[], self.current_end.0, self.current_end.1));
+                }
+            }
+            self.advance();
+
+            match self.current {
+                Token::Comma => self.advance(),
+                Token::RParen => {}
+                _ => return Err(CompileError::coded("E0139", &[], self.current_end.0, self.current_end.1)),
+            }
         }
-        
-        self.parse_function(true)
+        self.advance(); // consume )
+
+        if let Token::Assign = self.current {
+            self.advance();
+        } else {
+            return Err(CompileError::coded("E0119", &[], self.current_end.0, self.current_end.1));
+        }
+
+        let value = self.parse_expression()?;
+        Ok(StmtKind::LetTuple { names, value, mutable })
     }
 
-    fn parse_function(&mut self, exported: bool) -> Result<Stmt, CompileError> {
-        self.advance();
+    /// Parse a type annotation such as `Address`, `map<Address, U256>` or
+    /// `(PublicKey, PrivateKey)`, returning it as written (normalized
+    /// spacing). V1 does not check annotations yet; tooling such as the ABI
+    /// generator reads them.
+    fn parse_type_annotation(&mut self) -> Result<String, CompileError> {
+        // A function's type: `fn(String, Int) -> Bool`
+        if let Token::Fn = self.current {
+            self.advance();
+            if self.current != Token::LParen {
+                return Err(CompileError::coded("E0116", &[], self.current_end.0, self.current_end.1));
+            }
+            let params = self.parse_type_annotation()?;
+            let params = params.strip_prefix('(').and_then(|p| p.strip_suffix(')')).unwrap_or(&params);
+            if self.current != Token::Arrow {
+                return Ok(format!("fn({})", params));
+            }
+            self.advance(); // consume '->'
+            return Ok(format!("fn({}) -> {}", params, self.parse_type_annotation()?));
+        }
 
-        let name = match &self.current {
+        if let Token::LParen = self.current {
+            self.advance();
+            let mut items = Vec::new();
+            while self.current != Token::RParen {
+                items.push(self.parse_type_annotation()?);
+                match self.current {
+                    Token::Comma => self.advance(),
+                    Token::RParen => {}
+                    _ => return Err(CompileError::coded("E0139", &[], self.current_end.0, self.current_end.1)),
+                }
+            }
+            self.advance();
+            return Ok(format!("({})", items.join(", ")));
+        }
+
+        let mut text = match &self.current {
             Token::Identifier(name) => name.clone(),
             _ => {
-                return Err(
-                    CompileError::new(
-                        "Expected function name",
-                        self.lexer.line,
-                        self.lexer.column,
-                    )
-                    .help("Function names must be valid identifiers"),
-                );
+                return Err(CompileError::coded(
+                    "E0116",
+                    &[],
+                    self.current_end.0,
+                    self.current_end.1,
+                ));
             }
         };
-
         self.advance();
 
-        let mut params = Vec::new();
-        if let Token::LParen = self.current {
-            self.advance();
-            
-            while self.current != Token::RParen && self.current != Token::EOF {
-                if let Token::Identifier(param) = &self.current {
-                    params.push(param.clone());
-                    self.advance();
-                    
-                    if let Token::Comma = self.current {
-                        self.advance();
+        if let Token::Less = self.current {
+            let mut depth = 0;
+            loop {
+                match &self.current {
+                    Token::Less => {
+                        depth += 1;
+                        text.push('<');
                     }
-                } else {
-                    return Err(CompileError::new(
-                        "Expected parameter name",
-                        self.lexer.line,
-                        self.lexer.column,
-                    ));
+                    Token::Greater => {
+                        depth -= 1;
+                        text.push('>');
+                    }
+                    Token::Comma => text.push_str(", "),
+                    Token::Identifier(name) => text.push_str(name),
+                    Token::EOF => {
+                        return Err(CompileError::coded(
+                            "E0117",
+                            &[],
+                            self.current_end.0,
+                            self.current_end.1,
+                        ));
+                    }
+                    _ => {}
                 }
-            }
-            
-            if let Token::RParen = self.current {
                 self.advance();
+                if depth == 0 {
+                    break;
+                }
             }
         }
 
-        let return_type = Type::Void;
-        
-        let body = if let Token::LBrace = self.current {
-            self.advance();
-            let body = self.parse_block()?;
-            if let Token::RBrace = self.current {
-                self.advance();
-            }
-            body
-        } else {
-            vec![]
-        };
-
-        Ok(Stmt::Function {
-            name,
-            params,
-            return_type,
-            body,
-            exported,
-        })
+        Ok(text)
     }
     
+    /// Statements up to the closing `}`. Semicolons are optional: a `;`
+    /// ends the statement before it, and only matters after a block's final
+    /// expression. Without one, that expression is the block's value (see
+    /// `implicit_return`); with one, the value is discarded.
     fn parse_block(&mut self) -> Result<Vec<Stmt>, CompileError> {
         let mut stmts = Vec::new();
+        let mut terminated = false;
         
         while self.current != Token::RBrace && self.current != Token::EOF {
-            stmts.push(self.parse_statement()?);
+            if self.current == Token::Semicolon {
+                self.advance();
+                terminated = true;
+                continue;
+            }
+            let start = self.current_start;
+            match self.spanned(Self::parse_statement) {
+                Ok(stmt) => stmts.push(stmt),
+                Err(error) => {
+                    self.errors.push(error);
+                    self.synchronize_statement(start);
+                }
+            }
+            terminated = false;
         }
         
+        if let Some(last) = stmts.last().filter(|_| !terminated) {
+            if let StmtKind::Expression(_) = last.kind {
+                self.block_values.insert((last.span.start_line, last.span.start_column));
+            }
+        }
         Ok(stmts)
     }
+
+    /// Make a function body's value its return value: a final expression
+    /// without a `;` is the function's value, in both branches of a final
+    /// if/else and every arm of a final match too. Whether it has one, and
+    /// so is returned, is up to its type.
+    fn implicit_return(&self, body: &mut [Stmt]) {
+        let Some(last) = body.last_mut() else { return };
+        let is_value = self.block_values.contains(&(last.span.start_line, last.span.start_column));
+        match &mut last.kind {
+            StmtKind::Expression(expr) if is_value => {
+                let expr = expr.clone();
+                last.kind = StmtKind::Value(expr);
+            }
+            StmtKind::If { then_body, else_body: Some(else_body), .. } => {
+                self.implicit_return(then_body);
+                self.implicit_return(else_body);
+            }
+            StmtKind::Match { arms, .. } => {
+                for arm in arms {
+                    self.implicit_return(&mut arm.body);
+                }
+            }
+            _ => {}
+        }
+    }
     
-    fn parse_statement(&mut self) -> Result<Stmt, CompileError> {
+    fn parse_statement(&mut self) -> Result<StmtKind, CompileError> {
         match &self.current {
             Token::Let => {
                 self.advance();
+
+                // `mut` is contextual: `let mut = 1` binds a variable named mut
+                let mut mutable = false;
+                if self.current == Token::Identifier("mut".to_string()) {
+                    let start = self.current_start;
+                    self.advance();
+                    mutable = matches!(self.current, Token::Identifier(_) | Token::LParen);
+                    if !mutable {
+                        self.check_name_at("mut", self.span_from(start));
+                        return self.finish_let("mut".to_string(), false);
+                    }
+                }
+
+                if let Token::LParen = self.current {
+                    return self.parse_let_tuple(mutable);
+                }
                 
                 let name = match &self.current {
                     Token::Identifier(n) => n.clone(),
                     _ => {
-                        return Err(CompileError::new(
-                            "Expected variable name after 'let'",
-                            self.lexer.line,
-                            self.lexer.column,
+                        return Err(CompileError::coded(
+                            "E0118",
+                            &[],
+                            self.current_end.0,
+                            self.current_end.1,
                         ));
                     }
                 };
+                self.check_name(&name);
                 
                 self.advance();
-                
-                if let Token::Assign = self.current {
-                    self.advance();
-                } else {
-                    return Err(CompileError::new(
-                        "Expected '=' after variable name",
-                        self.lexer.line,
-                        self.lexer.column,
-                    ));
-                }
-                
-                let value = self.parse_expression()?;
-                
-                Ok(Stmt::Let { name, value })
+                self.finish_let(name, mutable)
             }
             Token::While => {
                 self.parse_while()
             }
+            Token::For => {
+                self.parse_for()
+            }
             Token::If => {
                 self.advance();
                 
                 let condition = self.parse_expression()?;
                 
                 if self.current != Token::LBrace {
-                    return Err(CompileError::new(
-                        "Expected '{' after if condition",
-                        self.lexer.line,
-                        self.lexer.column,
+                    return Err(CompileError::coded(
+                        "E0120",
+                        &[],
+                        self.current_end.0,
+                        self.current_end.1,
                     ));
                 }
                 self.advance();
@@ -204,10 +1192,11 @@ impl Parser {
                 let then_body = self.parse_block()?;
                 
                 if self.current != Token::RBrace {
-                    return Err(CompileError::new(
-                        "Expected '}' after if body",
-                        self.lexer.line,
-                        self.lexer.column,
+                    return Err(CompileError::coded(
+                        "E0121",
+                        &[],
+                        self.current_end.0,
+                        self.current_end.1,
                     ));
                 }
                 self.advance();
@@ -215,11 +1204,18 @@ impl Parser {
                 let else_body = if self.current == Token::Else {
                     self.advance();
                     
+                    // `else if` chains: the nested if is the whole else body
+                    if self.current == Token::If {
+                        let nested = self.spanned(Self::parse_statement)?;
+                        return Ok(StmtKind::If { condition, then_body, else_body: Some(vec![nested]) });
+                    }
+                    
                     if self.current != Token::LBrace {
-                        return Err(CompileError::new(
-                            "Expected '{' after else",
-                            self.lexer.line,
-                            self.lexer.column,
+                        return Err(CompileError::coded(
+                            "E0122",
+                            &[],
+                            self.current_end.0,
+                            self.current_end.1,
                         ));
                     }
                     self.advance();
@@ -227,10 +1223,11 @@ impl Parser {
                     let body = self.parse_block()?;
                     
                     if self.current != Token::RBrace {
-                        return Err(CompileError::new(
-                            "Expected '}' after else body",
-                            self.lexer.line,
-                            self.lexer.column,
+                        return Err(CompileError::coded(
+                            "E0123",
+                            &[],
+                            self.current_end.0,
+                            self.current_end.1,
                         ));
                     }
                     self.advance();
@@ -240,12 +1237,12 @@ impl Parser {
                     None
                 };
                 
-                Ok(Stmt::If { condition, then_body, else_body })
+                Ok(StmtKind::If { condition, then_body, else_body })
             }
             Token::Return => {
                 self.advance();
                 let expr = self.parse_expression()?;
-                Ok(Stmt::Return(expr))
+                Ok(StmtKind::Return(expr))
             }
             Token::Panic => {
                 // STEP 48: Parse panic statement: panic("message")
@@ -253,10 +1250,11 @@ impl Parser {
                 
                 // Expect (
                 if self.current != Token::LParen {
-                    return Err(CompileError::new(
-                        "Expected '(' after 'panic'",
-                        self.lexer.line,
-                        self.lexer.column,
+                    return Err(CompileError::coded(
+                        "E0124",
+                        &[],
+                        self.current_end.0,
+                        self.current_end.1,
                     ));
                 }
                 self.advance(); // consume '('
@@ -266,50 +1264,256 @@ impl Parser {
                 
                 // Expect )
                 if self.current != Token::RParen {
-                    return Err(CompileError::new(
-                        "Expected ')' after panic message",
-                        self.lexer.line,
-                        self.lexer.column,
+                    return Err(CompileError::coded(
+                        "E0125",
+                        &[],
+                        self.current_end.0,
+                        self.current_end.1,
                     ));
                 }
                 self.advance(); // consume ')'
                 
-                Ok(Stmt::Panic(expr))
+                Ok(StmtKind::Panic(expr))
+            }
+            Token::Require => {
+                self.parse_require()
+            }
+            // A helper local to the enclosing function (see `nested`)
+            Token::Fn => self.parse_function(false, false),
+            // `match` is contextual: the word followed by a value on the
+            // same line, or by a parenthesized value and `{`
+            Token::Identifier(word) if word == "match" && (self.value_at(1) || self.parenthesized_before_brace(1)) => {
+                self.advance(); // consume 'match'
+                let value = self.parse_expression()?;
+                self.parse_match(value)
+            }
+            // So is `defer`, followed by the expression or block it defers
+            Token::Identifier(word) if word == "defer" && self.peek_same_line(1) && self.peek(1) == TokenKind::LBrace => {
+                self.advance(); // consume 'defer'
+                self.advance(); // consume '{'
+                let body = self.parse_block()?;
+                if self.current != Token::RBrace {
+                    return Err(CompileError::coded("E0172", &[], self.current_end.0, self.current_end.1));
+                }
+                self.advance(); // consume '}'
+                Ok(StmtKind::Defer(body))
+            }
+            Token::Identifier(word) if word == "defer" && self.value_at(1) => {
+                self.advance(); // consume 'defer'
+                let expr = self.parse_expression()?;
+                let span = expr.span;
+                Ok(StmtKind::Defer(vec![Stmt::new(StmtKind::Expression(expr), span)]))
             }
             Token::Identifier(_) => {
                 // Could be assignment: name = value, or just an expression (function call, etc.)
-                // We need to peek ahead to distinguish
-                // For now, parse as expression and it will handle function calls
                 let expr = self.parse_expression()?;
-                
+
                 // Check if it's an assignment we just parsed
-                if let Expr::Identifier(name) = &expr {
+                if let ExprKind::Identifier(name) = &expr.kind {
                     if let Token::Assign = self.current {
                         self.advance(); // consume '='
                         let value = self.parse_expression()?;
-                        return Ok(Stmt::Assign { name: name.clone(), value });
+                        return Ok(StmtKind::Assign { name: name.clone(), value });
                     }
                 }
                 
-                Ok(Stmt::Expression(expr))
+                Ok(StmtKind::Expression(expr))
             }
             _ => {
                 let expr = self.parse_expression()?;
-                Ok(Stmt::Expression(expr))
+                Ok(StmtKind::Expression(expr))
+            }
+        }
+    }
+    
+    /// Parse a call argument, passed by position or as `name: value`
+    fn parse_argument(&mut self) -> Result<Expr, CompileError> {
+        let start = self.current_start;
+        let arg = self.parse_expression()?;
+        match arg.kind {
+            ExprKind::Identifier(name) if self.current == Token::Colon => {
+                self.advance(); // consume ':'
+                let value = self.parse_expression()?;
+                Ok(Expr::new(ExprKind::Named(name, Box::new(value)), self.span_from(start)))
+            }
+            _ => Ok(arg),
+        }
+    }
+
+    /// Whether the token `n` places ahead begins a value on the current
+    /// token's line; a `(` would make the current word a call instead
+    fn value_at(&self, n: usize) -> bool {
+        self.peek_same_line(n)
+            && matches!(
+                self.peek(n),
+                TokenKind::Identifier | TokenKind::Number | TokenKind::Float | TokenKind::String
+                    | TokenKind::True | TokenKind::False | TokenKind::LBracket | TokenKind::Await
+            )
+    }
+
+    /// Whether the token `n` places ahead opens parentheses that a `{`
+    /// follows once they close, as in `match (value) {`
+    fn parenthesized_before_brace(&self, n: usize) -> bool {
+        if self.peek(n) != TokenKind::LParen {
+            return false;
+        }
+        let mut depth = 0;
+        for ahead in n.. {
+            match self.peek(ahead) {
+                TokenKind::LParen => depth += 1,
+                TokenKind::RParen if depth == 1 => return self.peek(ahead + 1) == TokenKind::LBrace,
+                TokenKind::RParen => depth -= 1,
+                TokenKind::EOF => return false,
+                _ => {}
+            }
+        }
+        false
+    }
+
+    /// Parse the arms of a match on `value`:
+    ///
+    /// ```text
+    /// match lookup(id) {
+    ///     Some(user) => greet(user),
+    ///     None => { return 0 }
+    /// }
+    /// ```
+    ///
+    /// An arm's body is a block or a single statement; commas between arms
+    /// are optional.
+    fn parse_match(&mut self, value: Expr) -> Result<StmtKind, CompileError> {
+        if self.current != Token::LBrace {
+            return Err(CompileError::coded("E0160", &[], self.current_end.0, self.current_end.1));
+        }
+        self.advance(); // consume '{'
+
+        let mut arms = Vec::new();
+        while self.current != Token::RBrace {
+            if self.current == Token::EOF {
+                return Err(CompileError::coded("E0163", &[], self.current_end.0, self.current_end.1));
+            }
+            let pattern = self.parse_pattern()?;
+            if self.current != Token::FatArrow {
+                return Err(CompileError::coded("E0162", &[&pattern], self.current_end.0, self.current_end.1));
+            }
+            self.advance(); // consume '=>'
+
+            let body = if self.current == Token::LBrace {
+                self.advance();
+                let body = self.parse_block()?;
+                if self.current != Token::RBrace {
+                    return Err(CompileError::coded("E0163", &[], self.current_end.0, self.current_end.1));
+                }
+                self.advance();
+                body
+            } else {
+                // A lone expression is the arm's value
+                let stmt = self.spanned(Self::parse_statement)?;
+                if let StmtKind::Expression(_) = stmt.kind {
+                    self.block_values.insert((stmt.span.start_line, stmt.span.start_column));
+                }
+                vec![stmt]
+            };
+            if self.current == Token::Comma {
+                self.advance();
+            }
+            arms.push(MatchArm { pattern, body });
+        }
+        self.advance(); // consume '}'
+
+        Ok(StmtKind::Match { value, arms })
+    }
+
+    /// `Some(name)`, `None`, `Ok(name)`, `Err(name)` or `_`
+    fn parse_pattern(&mut self) -> Result<Pattern, CompileError> {
+        let invalid = |parser: &Self| CompileError::coded("E0161", &[], parser.current_end.0, parser.current_end.1);
+        let wrap: fn(String) -> Pattern = match &self.current {
+            Token::Identifier(word) if word == "_" => {
+                self.advance();
+                return Ok(Pattern::Wildcard);
+            }
+            Token::Identifier(word) if word == "None" => {
+                self.advance();
+                return Ok(Pattern::None);
             }
+            Token::Identifier(word) if word == "Some" => Pattern::Some,
+            Token::Identifier(word) if word == "Ok" => Pattern::Ok,
+            Token::Identifier(word) if word == "Err" => Pattern::Err,
+            _ => return Err(invalid(self)),
+        };
+        self.advance();
+
+        if self.current != Token::LParen {
+            return Err(invalid(self));
+        }
+        self.advance(); // consume '('
+        let Token::Identifier(name) = &self.current else {
+            return Err(invalid(self));
+        };
+        let name = name.clone();
+        self.check_name(&name);
+        self.advance();
+        if self.current != Token::RParen {
+            return Err(invalid(self));
+        }
+        self.advance(); // consume ')'
+        Ok(wrap(name))
+    }
+
+    /// `require(condition, message)`: an `if` whose else branch panics with
+    /// the message, so checks read as one line instead of a negated `if`
+    fn parse_require(&mut self) -> Result<StmtKind, CompileError> {
+        self.advance(); // consume 'require'
+        
+        if self.current != Token::LParen {
+            return Err(CompileError::coded(
+                "E0149",
+                &[],
+                self.current_end.0,
+                self.current_end.1,
+            ));
+        }
+        self.advance(); // consume '('
+        
+        let condition = self.parse_expression()?;
+        
+        if self.current != Token::Comma {
+            return Err(CompileError::coded(
+                "E0150",
+                &[],
+                self.current_end.0,
+                self.current_end.1,
+            ));
+        }
+        self.advance(); // consume ','
+        
+        // The synthesized panic is a statement of its own, spanning the message
+        let panic = self.spanned(|parser| Ok(StmtKind::Panic(parser.parse_expression()?)))?;
+        
+        if self.current != Token::RParen {
+            return Err(CompileError::coded(
+                "E0151",
+                &[],
+                self.current_end.0,
+                self.current_end.1,
+            ));
         }
+        self.advance(); // consume ')'
+        
+        Ok(StmtKind::If { condition, then_body: Vec::new(), else_body: Some(vec![panic]) })
     }
     
-    fn parse_while(&mut self) -> Result<Stmt, CompileError> {
+    fn parse_while(&mut self) -> Result<StmtKind, CompileError> {
         self.advance(); // consume 'while'
         
         let condition = self.parse_expression()?;
         
         if self.current != Token::LBrace {
-            return Err(CompileError::new(
-                "Expected '{' after while condition",
-                self.lexer.line,
-                self.lexer.column,
+            return Err(CompileError::coded(
+                "E0126",
+                &[],
+                self.current_end.0,
+                self.current_end.1,
             ));
         }
         self.advance(); // consume '{'
@@ -317,60 +1521,138 @@ impl Parser {
         let body = self.parse_block()?;
         
         if self.current != Token::RBrace {
-            return Err(CompileError::new(
-                "Expected '}' after while body",
-                self.lexer.line,
-                self.lexer.column,
+            return Err(CompileError::coded(
+                "E0127",
+                &[],
+                self.current_end.0,
+                self.current_end.1,
             ));
         }
         self.advance(); // consume '}'
         
-        Ok(Stmt::While { condition, body })
+        Ok(StmtKind::While { condition, body })
+    }
+    
+    /// Parse `for var in iterable { body }`; `in` is a contextual keyword
+    fn parse_for(&mut self) -> Result<StmtKind, CompileError> {
+        self.advance(); // consume 'for'
+        
+        let var = match &self.current {
+            Token::Identifier(name) => name.clone(),
+            _ => {
+                return Err(CompileError::coded("E0144", &[], self.current_end.0, self.current_end.1));
+            }
+        };
+        self.check_name(&var);
+        self.advance();
+        
+        if self.current != Token::Identifier("in".to_string()) {
+            return Err(CompileError::coded("E0145", &[], self.current_end.0, self.current_end.1));
+        }
+        self.advance(); // consume 'in'
+        
+        let iterable = self.parse_expression()?;
+        
+        if self.current != Token::LBrace {
+            return Err(CompileError::coded("E0146", &[], self.current_end.0, self.current_end.1));
+        }
+        self.advance(); // consume '{'
+        
+        let body = self.parse_block()?;
+        
+        if self.current != Token::RBrace {
+            return Err(CompileError::coded("E0147", &[], self.current_end.0, self.current_end.1));
+        }
+        self.advance(); // consume '}'
+        
+        Ok(StmtKind::For { var, iterable, body })
     }
     
+    /// Parse range expressions (lowest precedence): start..end
     fn parse_expression(&mut self) -> Result<Expr, CompileError> {
-        self.parse_comparison()
+        let from = self.current_start;
+        let start = self.parse_or()?;
+        
+        if let Token::DotDot = self.current {
+            self.advance(); // consume '..'
+            let end = self.parse_or()?;
+            return Ok(Expr::new(ExprKind::Range(Box::new(start), Box::new(end)), self.span_from(from)));
+        }
+        
+        Ok(start)
     }
     
+    /// Parse `||` (below `&&`): a || b || c
+    fn parse_or(&mut self) -> Result<Expr, CompileError> {
+        let start = self.current_start;
+        let mut left = self.parse_and()?;
+
+        while let Token::OrOr = self.current {
+            self.advance();
+            let right = self.parse_and()?;
+            left = Expr::new(ExprKind::Or(Box::new(left), Box::new(right)), self.span_from(start));
+        }
+
+        Ok(left)
+    }
+
+    /// Parse `&&` (below comparisons): a == b && c < d
+    fn parse_and(&mut self) -> Result<Expr, CompileError> {
+        let start = self.current_start;
+        let mut left = self.parse_comparison()?;
+
+        while let Token::AndAnd = self.current {
+            self.advance();
+            let right = self.parse_comparison()?;
+            left = Expr::new(ExprKind::And(Box::new(left), Box::new(right)), self.span_from(start));
+        }
+
+        Ok(left)
+    }
+
     /// Parse comparison expressions: a > b, a < b, etc.
     fn parse_comparison(&mut self) -> Result<Expr, CompileError> {
+        let start = self.current_start;
         let mut left = self.parse_additive()?;
         
         while matches!(self.current, 
             Token::Greater | Token::Less | Token::GreaterEqual | Token::LessEqual | 
             Token::EqualEqual | Token::NotEqual) {
-            let op = self.current.clone();
+            let op = self.peek(0);
             self.advance();
             let right = self.parse_additive()?;
             
-            left = match op {
-                Token::Greater => Expr::Gt(Box::new(left), Box::new(right)),
-                Token::Less => Expr::Lt(Box::new(left), Box::new(right)),
-                Token::GreaterEqual => Expr::Ge(Box::new(left), Box::new(right)),
-                Token::LessEqual => Expr::Le(Box::new(left), Box::new(right)),
-                Token::EqualEqual => Expr::Eq(Box::new(left), Box::new(right)),
-                Token::NotEqual => Expr::Ne(Box::new(left), Box::new(right)),
+            let kind = match op {
+                TokenKind::Greater => ExprKind::Gt(Box::new(left), Box::new(right)),
+                TokenKind::Less => ExprKind::Lt(Box::new(left), Box::new(right)),
+                TokenKind::GreaterEqual => ExprKind::Ge(Box::new(left), Box::new(right)),
+                TokenKind::LessEqual => ExprKind::Le(Box::new(left), Box::new(right)),
+                TokenKind::EqualEqual => ExprKind::Eq(Box::new(left), Box::new(right)),
+                TokenKind::NotEqual => ExprKind::Ne(Box::new(left), Box::new(right)),
                 _ => unreachable!(),
             };
+            left = Expr::new(kind, self.span_from(start));
         }
         
         Ok(left)
     }
     
-    /// Parse additive expressions (lowest precedence): a + b - c
+    /// Parse additive expressions: a + b - c
     fn parse_additive(&mut self) -> Result<Expr, CompileError> {
+        let start = self.current_start;
         let mut left = self.parse_multiplicative()?;
         
         while matches!(self.current, Token::Plus | Token::Minus) {
-            let op = self.current.clone();
+            let op = self.peek(0);
             self.advance();
             let right = self.parse_multiplicative()?;
             
-            left = match op {
-                Token::Plus => Expr::Add(Box::new(left), Box::new(right)),
-                Token::Minus => Expr::Sub(Box::new(left), Box::new(right)),
+            let kind = match op {
+                TokenKind::Plus => ExprKind::Add(Box::new(left), Box::new(right)),
+                TokenKind::Minus => ExprKind::Sub(Box::new(left), Box::new(right)),
                 _ => unreachable!(),
             };
+            left = Expr::new(kind, self.span_from(start));
         }
         
         Ok(left)
@@ -378,29 +1660,86 @@ impl Parser {
     
     /// Parse multiplicative expressions (higher precedence): a * b / c % d
     fn parse_multiplicative(&mut self) -> Result<Expr, CompileError> {
-        let mut left = self.parse_call()?;
+        let start = self.current_start;
+        let mut left = self.parse_slice()?;
         
         while matches!(self.current, Token::Star | Token::Slash | Token::Percent) {
-            let op = self.current.clone();
+            let op = self.peek(0);
             self.advance();
-            let right = self.parse_call()?;
+            let right = self.parse_slice()?;
             
-            left = match op {
-                Token::Star => Expr::Mul(Box::new(left), Box::new(right)),
-                Token::Slash => Expr::Div(Box::new(left), Box::new(right)),
-                Token::Percent => Expr::Mod(Box::new(left), Box::new(right)),
+            let kind = match op {
+                TokenKind::Star => ExprKind::Mul(Box::new(left), Box::new(right)),
+                TokenKind::Slash => ExprKind::Div(Box::new(left), Box::new(right)),
+                TokenKind::Percent => ExprKind::Mod(Box::new(left), Box::new(right)),
                 _ => unreachable!(),
             };
+            left = Expr::new(kind, self.span_from(start));
         }
         
         Ok(left)
     }
     
+    /// Parse slices, `?` and `as` (binding tighter than any operator):
+    /// name[1..3], lookup(id)?, count as Float
+    fn parse_slice(&mut self) -> Result<Expr, CompileError> {
+        let start = self.current_start;
+        let mut expr = self.parse_call()?;
+        
+        loop {
+            match self.current {
+                Token::LBracket => {
+                    self.advance(); // consume '['
+                    let range = self.parse_expression()?;
+                    if self.current != Token::RBracket {
+                        return Err(CompileError::coded("E0148", &[], self.current_end.0, self.current_end.1));
+                    }
+                    self.advance(); // consume ']'
+                    expr = Expr::new(ExprKind::Slice(Box::new(expr), Box::new(range)), self.span_from(start));
+                }
+                Token::Question => {
+                    self.advance(); // consume '?'
+                    expr = Expr::new(ExprKind::Try(Box::new(expr)), self.span_from(start));
+                }
+                Token::Identifier(ref word) if word == "as" => {
+                    self.advance(); // consume 'as'
+                    let target = self.parse_type_annotation()?;
+                    expr = Expr::new(ExprKind::Cast(Box::new(expr), target), self.span_from(start));
+                }
+                _ => return Ok(expr),
+            }
+        }
+    }
+    
     fn parse_call(&mut self) -> Result<Expr, CompileError> {
-        let expr = self.parse_primary()?;
+        let start = self.current_start;
+        let mut expr = self.parse_primary()?;
+
+        // A stdlib path names the same function as its bare name:
+        // std::io::print is print, std::fs::read is fs.read
+        if let (ExprKind::Identifier(first), Token::PathSep) = (&expr.kind, &self.current) {
+            let mut path = first.clone();
+            while self.current == Token::PathSep {
+                self.advance(); // consume '::'
+                match &self.current {
+                    Token::Identifier(segment) => path = format!("{}::{}", path, segment),
+                    _ => return Err(CompileError::coded("E0173", &[&format!("{}::", path)], self.current_end.0, self.current_end.1)),
+                }
+                self.advance(); // consume segment
+            }
+            let Some(function) = crate::stdlib::resolve_path(&path) else {
+                return Err(CompileError::coded("E0173", &[&path], self.current_end.0, self.current_end.1));
+            };
+            if let (Some((module_name, function_name)), Token::LParen) = (function.name.split_once('.'), &self.current) {
+                let args = self.parse_call_arguments()?;
+                let kind = ExprKind::ModuleCall(module_name.to_string(), function_name.to_string(), args);
+                return Ok(Expr::new(kind, self.span_from(start)));
+            }
+            expr = Expr::new(ExprKind::Identifier(function.name.to_string()), self.span_from(start));
+        }
         
         // Check for module-qualified call: module.function(args)
-        if let Expr::Identifier(ref name) = expr {
+        if let ExprKind::Identifier(ref name) = expr.kind {
             // Check for dot notation (module access)
             if let Token::Dot = self.current {
                 let module_name = name.clone();
@@ -409,10 +1748,11 @@ impl Parser {
                 let function_name = match &self.current {
                     Token::Identifier(func) => func.clone(),
                     _ => {
-                        return Err(CompileError::new(
-                            "Expected function name after '.'",
-                            self.lexer.line,
-                            self.lexer.column,
+                        return Err(CompileError::coded(
+                            "E0128",
+                            &[],
+                            self.current_end.0,
+                            self.current_end.1,
                         ));
                     }
                 };
@@ -420,101 +1760,140 @@ impl Parser {
                 
                 // Parse arguments
                 if let Token::LParen = self.current {
-                    self.advance(); // consume (
-                    let mut args = Vec::new();
-                    
-                    while self.current != Token::RParen && self.current != Token::EOF {
-                        args.push(self.parse_expression()?);
-                        if let Token::Comma = self.current {
-                            self.advance(); // consume ,
-                        }
-                    }
-                    
-                    if let Token::RParen = self.current {
-                        self.advance(); // consume )
-                    }
-                    
-                    return Ok(Expr::ModuleCall(module_name, function_name, args));
-                } else {
-                    return Err(CompileError::new(
-                        "Expected '(' after module function name",
-                        self.lexer.line,
-                        self.lexer.column,
-                    ));
+                    let args = self.parse_call_arguments()?;
+                    let kind = ExprKind::ModuleCall(module_name, function_name, args);
+                    return Ok(Expr::new(kind, self.span_from(start)));
                 }
+                
+                // Without arguments it reads a property: msg.sender
+                let kind = ExprKind::Property(module_name, function_name);
+                return Ok(Expr::new(kind, self.span_from(start)));
             }
             
             // Regular function call
             if let Token::LParen = self.current {
                 let name_clone = name.clone();
-                self.advance(); // consume (
-                let mut args = Vec::new();
-                
-                while self.current != Token::RParen && self.current != Token::EOF {
-                    args.push(self.parse_expression()?);
-                    if let Token::Comma = self.current {
-                        self.advance(); // consume ,
-                    }
-                }
-                
-                if let Token::RParen = self.current {
-                    self.advance(); // consume )
+                if name_clone == "println" {
+                    self.deprecated("W0001", start, Some("print".to_string()));
                 }
-                
-                return Ok(Expr::Call(name_clone, args));
+                let args = self.parse_call_arguments()?;
+                return Ok(Expr::new(ExprKind::Call(name_clone, args), self.span_from(start)));
             }
         }
         
         Ok(expr)
     }
+
+    /// A call's parenthesized arguments, from its '('
+    fn parse_call_arguments(&mut self) -> Result<Vec<Expr>, CompileError> {
+        self.advance(); // consume (
+        let mut args = Vec::new();
+        
+        while self.current != Token::RParen && self.current != Token::EOF {
+            args.push(self.parse_argument()?);
+            if let Token::Comma = self.current {
+                self.advance(); // consume ,
+            }
+        }
+        
+        if let Token::RParen = self.current {
+            self.advance(); // consume )
+        }
+        Ok(args)
+    }
     
     fn parse_primary(&mut self) -> Result<Expr, CompileError> {
-        let expr = match &self.current.clone() {
+        let start = self.current_start;
+        let kind = match &self.current {
             Token::Number(n) => {
                 let n = *n;
                 self.advance();
-                Expr::Number(n)
+                ExprKind::Number(n)
             }
             Token::Float(f) => {
                 let f = *f;
                 self.advance();
-                Expr::Float(f)
+                ExprKind::Float(f)
             }
             Token::True => {
                 self.advance();
-                Expr::Bool(true)
+                ExprKind::Bool(true)
             }
             Token::False => {
                 self.advance();
-                Expr::Bool(false)
+                ExprKind::Bool(false)
             }
             Token::String(s) => {
                 let s = s.clone();
                 self.advance();
-                Expr::String(s)
+                ExprKind::String(s)
             }
             Token::Identifier(id) => {
                 let id = id.clone();
+                let builtin_call = crate::token::RESERVED_BUILTINS.contains(&id.as_str()) && self.peek(1) == TokenKind::LParen;
+                if !builtin_call {
+                    self.check_name(&id);
+                }
                 self.advance();
-                Expr::Identifier(id)
+                ExprKind::Identifier(id)
+            }
+            Token::LBracket => {
+                // Array literal: [a, b, ...]
+                self.advance();
+                let mut items = Vec::new();
+                while self.current != Token::RBracket && self.current != Token::EOF {
+                    items.push(self.parse_expression()?);
+                    if self.current != Token::Comma {
+                        break;
+                    }
+                    self.advance(); // consume ',', or a trailing comma
+                }
+                if self.current != Token::RBracket {
+                    return Err(CompileError::coded("E0164", &[], self.current_end.0, self.current_end.1));
+                }
+                self.advance();
+                ExprKind::Array(items)
+            }
+            Token::Await => {
+                self.advance(); // consume 'await'
+                let inner = self.parse_call()?;
+                ExprKind::Await(Box::new(inner))
             }
             Token::LParen => {
                 self.advance();
                 let expr = self.parse_expression()?;
-                if let Token::RParen = self.current {
+                if let Token::Comma = self.current {
+                    // Tuple literal: (a, b, ...)
+                    let mut items = vec![expr];
+                    while let Token::Comma = self.current {
+                        self.advance();
+                        if self.current == Token::RParen {
+                            break; // trailing comma
+                        }
+                        items.push(self.parse_expression()?);
+                    }
+                    if self.current != Token::RParen {
+                        return Err(CompileError::coded("E0139", &[], self.current_end.0, self.current_end.1));
+                    }
                     self.advance();
+                    ExprKind::Tuple(items)
+                } else {
+                    if let Token::RParen = self.current {
+                        self.advance();
+                    }
+                    return Ok(expr);
                 }
-                expr
             }
             _ => {
-                return Err(CompileError::new(
-                    "Unexpected token in expression",
-                    self.lexer.line,
-                    self.lexer.column,
+                return Err(CompileError::coded(
+                    "E0130",
+                    &[],
+                    self.current_end.0,
+                    self.current_end.1,
                 ));
             }
         };
         
-        Ok(expr)
+        Ok(Expr::new(kind, self.span_from(start)))
     }
 }
@@ -0,0 +1,221 @@
+use crate::ast::{Span, Stmt, StmtKind};
+use crate::error::CompileError;
+use crate::ir::IRModule;
+
+// Plugin API: custom lints and IR passes.
+//
+// Teams register their own checks and transformations on a `Session`
+// (`register_lint`, `register_ir_pass`) instead of forking the compiler.
+// Lints run after type checking and fail the build when they report
+// anything; IR passes run after the built-in optimizer, right before codegen.
+//
+// The same registration can be loaded at run time from a shared library
+// (`astrixa build --plugin path/to/libteam_rules.so`). Such a library is a
+// `cdylib` that depends on this crate and calls `declare_plugin!`. Rust has
+// no stable ABI, so the library must be built with the same compiler and
+// the same version of this crate as the CLI loading it. PLUGIN_API_VERSION
+// guards against mismatched plugin API revisions, and ABI_FINGERPRINT
+// against a change since the plugin was built to any type a plugin is
+// handed (the AST, the IR, sessions, errors and the traits here), which no
+// one has to remember to bump for.
+
+/// Bumped whenever `Lint`, `IrPass` or the registration entry point change
+pub const PLUGIN_API_VERSION: u32 = 1;
+
+/// A digest of the source of every type crossing into a plugin, computed
+/// as this crate builds, so any change to what lints and passes see changes it
+pub const ABI_FINGERPRINT: u64 = fnv1a(&[
+    include_bytes!("ast.rs"),
+    include_bytes!("ir.rs"),
+    include_bytes!("error.rs"),
+    include_bytes!("session.rs"),
+    include_bytes!("plugin.rs"),
+]);
+
+/// Exported symbol holding the plugin's PLUGIN_API_VERSION
+pub const VERSION_SYMBOL: &[u8] = b"ASTRIXA_PLUGIN_API_VERSION\0";
+
+/// Exported symbol holding the plugin's ABI_FINGERPRINT
+pub const FINGERPRINT_SYMBOL: &[u8] = b"ASTRIXA_PLUGIN_ABI_FINGERPRINT\0";
+
+/// Exported symbol of the plugin's `RegisterFn`
+pub const REGISTER_SYMBOL: &[u8] = b"astrixa_plugin_register\0";
+
+/// Entry point a plugin library exports to register its lints and passes
+pub type RegisterFn = fn(&mut crate::session::Session);
+
+/// A custom check over a type-checked program
+pub trait Lint: Send + Sync {
+    /// Short identifier shown with each finding, e.g. "no-unguarded-transfer"
+    fn name(&self) -> &str;
+
+    /// Findings for `program`. `spans` are the parser's statement spans, in
+    /// the pre-order described on `Parser::spans`; `spanned` pairs them up.
+    fn check(&self, program: &[Stmt], spans: &[Span]) -> Vec<CompileError>;
+}
+
+/// A custom transformation over the optimized IR
+pub trait IrPass: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn run(&self, module: &mut IRModule);
+}
+
+/// 64-bit FNV-1a over `sources` one after another, which a const fn can
+/// compute
+const fn fnv1a(sources: &[&[u8]]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    let mut s = 0;
+    while s < sources.len() {
+        let bytes = sources[s];
+        let mut i = 0;
+        while i < bytes.len() {
+            hash ^= bytes[i] as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+            i += 1;
+        }
+        s += 1;
+    }
+    hash
+}
+
+/// Every statement in `program` (including nested bodies and contract
+/// methods) with its span, in the parser's pre-order
+pub fn spanned<'a>(program: &'a [Stmt], spans: &[Span]) -> Vec<(&'a Stmt, Span)> {
+    fn walk<'a>(body: &'a [Stmt], spans: &mut std::slice::Iter<Span>, out: &mut Vec<(&'a Stmt, Span)>) {
+        for stmt in body {
+            let Some(span) = spans.next() else { return };
+            out.push((stmt, *span));
+            match &stmt.kind {
+                StmtKind::Function { body, .. } | StmtKind::While { body, .. } | StmtKind::For { body, .. }
+                | StmtKind::Defer(body) => walk(body, spans, out),
+                StmtKind::If { then_body, else_body, .. } => {
+                    walk(then_body, spans, out);
+                    walk(else_body.as_deref().unwrap_or_default(), spans, out);
+                }
+                StmtKind::Contract { constructor, methods, .. } => {
+                    walk(constructor.as_deref().map(std::slice::from_ref).unwrap_or_default(), spans, out);
+                    walk(methods, spans, out);
+                }
+                StmtKind::Trait { methods, .. } | StmtKind::Impl { methods, .. } => walk(methods, spans, out),
+                StmtKind::Match { arms, .. } => {
+                    for arm in arms {
+                        walk(&arm.body, spans, out);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut out = Vec::new();
+    walk(program, &mut spans.iter(), &mut out);
+    out
+}
+
+/// Export a plugin's registration function under the names the CLI looks
+/// up with `--plugin`:
+///
+/// ```ignore
+/// fn register(session: &mut astrixa::session::Session) {
+///     session.register_lint(Box::new(NoSelfDestruct));
+/// }
+///
+/// astrixa::declare_plugin!(register);
+/// ```
+#[macro_export]
+macro_rules! declare_plugin {
+    ($register:path) => {
+        #[no_mangle]
+        pub static ASTRIXA_PLUGIN_API_VERSION: u32 = $crate::plugin::PLUGIN_API_VERSION;
+
+        #[no_mangle]
+        pub static ASTRIXA_PLUGIN_ABI_FINGERPRINT: u64 = $crate::plugin::ABI_FINGERPRINT;
+
+        #[no_mangle]
+        pub fn astrixa_plugin_register(session: &mut $crate::session::Session) {
+            let register: $crate::plugin::RegisterFn = $register;
+            register(session);
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::IRInstr;
+    use crate::lexer::Lexer;
+    use crate::lowering::lower;
+    use crate::parser::Parser;
+    use crate::session::Session;
+
+    /// Nothing may be called `destroy`
+    struct NoDestroy;
+
+    impl Lint for NoDestroy {
+        fn name(&self) -> &str {
+            "no-destroy"
+        }
+
+        fn check(&self, program: &[Stmt], spans: &[Span]) -> Vec<CompileError> {
+            spanned(program, spans)
+                .into_iter()
+                .filter(|(stmt, _)| matches!(&stmt.kind, StmtKind::Function { name, .. } if name == "destroy"))
+                .map(|(_, span)| CompileError::new("destroy() is not allowed", span.start_line, span.start_column))
+                .collect()
+        }
+    }
+
+    /// Prefixes every function with a no-op marker
+    struct MarkReturns;
+
+    impl IrPass for MarkReturns {
+        fn name(&self) -> &str {
+            "mark-returns"
+        }
+
+        fn run(&self, module: &mut IRModule) {
+            for func in module.functions.iter_mut() {
+                func.instructions.insert(0, IRInstr::LoadConstInt(7));
+                func.instructions.insert(1, IRInstr::Pop);
+            }
+        }
+    }
+
+    fn register(session: &mut Session) {
+        session.register_lint(Box::new(NoDestroy));
+        session.register_ir_pass(Box::new(MarkReturns));
+    }
+
+    declare_plugin!(register);
+
+    #[test]
+    fn test_registered_lints_and_passes_run() {
+        let mut session = Session::new();
+        astrixa_plugin_register(&mut session);
+        assert_eq!(ASTRIXA_PLUGIN_API_VERSION, PLUGIN_API_VERSION);
+        assert_eq!(ASTRIXA_PLUGIN_ABI_FINGERPRINT, ABI_FINGERPRINT);
+        assert_ne!(fnv1a(&[b"pub struct Stmt"]), fnv1a(&[b"pub struct Stmt "]));
+        // A change to any of the sources changes the whole
+        assert_ne!(fnv1a(&[b"pub struct Stmt", b"pub struct IRModule"]), fnv1a(&[b"pub struct Stmt", b"pub struct IRModule "]));
+
+        let source = "contract Vault {\n    state owner\n    fn deposit() {\n        if 1 > 0 {\n            return 1\n        }\n        return 0\n    }\n    fn destroy() {\n        return 0\n    }\n}\n";
+        let mut parser = Parser::new(Lexer::new(source));
+        let program = parser.parse().unwrap();
+
+        let findings = session.lint(&program, parser.spans());
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].message, "[no-destroy] destroy() is not allowed");
+        assert_eq!(findings[0].line, 9);
+
+        let mut module = lower(&program);
+        session.run_ir_passes(&mut module);
+        assert!(module.functions.iter().all(|f| f.instructions[0] == IRInstr::LoadConstInt(7)));
+
+        // Editors see lint findings with the document's line numbers
+        session.update("file:///vault.ax", &format!("fn main() {{\n}}\n\n{}", source));
+        let diagnostics = session.diagnostics("file:///vault.ax");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].line, 12);
+    }
+}
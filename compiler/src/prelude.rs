@@ -0,0 +1,127 @@
+// The prelude: the functions every program can call without declaring or
+// importing them
+//
+// Each is declared here once, with its signature: the standard library's
+// unqualified functions (see `stdlib`), then the few the runtime answers
+// itself. Namespaced ones like `fs.read` are reached through their module
+// instead. The typechecker checks calls against these signatures, and the
+// module system knows the prelude as a module, `prelude`, that is always
+// there without a file.
+//
+// A function marked `#[no_prelude]` (and every helper nested in it) sees
+// none of it implicitly, which keeps constrained contract code to what it
+// declares: it may still call what the file imports by name, as in
+// `import prelude.{len}`.
+
+use std::sync::OnceLock;
+use crate::types::Type;
+
+/// The name the prelude goes by as a module
+pub const MODULE: &str = "prelude";
+
+/// An implicitly available function's signature
+#[derive(Debug, Clone)]
+pub struct PreludeFunction {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub params: Vec<Type>, // Type::Unknown accepts any value
+    pub variadic: bool,    // Any number of values may follow the parameters
+    pub returns: Type,
+}
+
+/// Every function in the prelude
+pub fn functions() -> &'static [PreludeFunction] {
+    static PRELUDE: OnceLock<Vec<PreludeFunction>> = OnceLock::new();
+    PRELUDE.get_or_init(declare)
+}
+
+/// The prelude function called `name`, if there is one
+pub fn get(name: &str) -> Option<&'static PreludeFunction> {
+    functions().iter().find(|f| f.name == name)
+}
+
+/// Whether a call to `name` prints its argument on a line of its own
+pub fn prints(name: &str) -> bool {
+    matches!(name, "print" | "println")
+}
+
+fn declare() -> Vec<PreludeFunction> {
+    let stdlib = crate::stdlib::stdlib_functions()
+        .iter()
+        .filter(|f| !f.name.contains('.'))
+        .map(|f| PreludeFunction {
+            name: f.name,
+            description: f.description,
+            params: f.params.clone(),
+            variadic: false,
+            returns: f.returns.clone(),
+        });
+
+    let runtime = [
+        PreludeFunction {
+            name: "transfer",
+            description: "Send value from the running contract (to, amount)",
+            params: vec![Type::Address, Type::U256],
+            variadic: false,
+            returns: Type::Void,
+        },
+        PreludeFunction {
+            name: "emit",
+            description: "Emit a contract event: its name, then its arguments",
+            params: vec![Type::String],
+            variadic: true,
+            returns: Type::Void,
+        },
+        PreludeFunction {
+            name: "spawn",
+            description: "The task of a call to an async function",
+            params: vec![Type::Unknown],
+            variadic: false,
+            returns: Type::Unknown,
+        },
+        PreludeFunction {
+            name: "type",
+            description: "The name of a value's type",
+            params: vec![Type::Unknown],
+            variadic: false,
+            returns: Type::String,
+        },
+    ];
+
+    stdlib.chain(runtime).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::loader::ModuleLoader;
+    use crate::parser::Parser;
+    use crate::typechecker::TypeChecker;
+
+    fn check(source: &str) -> Result<(), Vec<crate::error::CompileError>> {
+        TypeChecker::new().check(&Parser::new(Lexer::new(source)).parse().unwrap())
+    }
+
+    #[test]
+    fn test_no_prelude_calls_only_what_is_declared_or_imported() {
+        assert!(get("print").is_some() && get("emit").unwrap().variadic);
+        assert!(get("fs.read").is_none(), "reached through its module");
+
+        // Runtime builtins are checked against their signatures too
+        let errors = check("fn main() {\n    emit(\"Sent\", 1, 2)\n    emit(3)\n}\n").unwrap_err();
+        let codes: Vec<_> = errors.iter().map(|e| e.code).collect();
+        assert_eq!(codes, vec![Some("E0208")]);
+
+        let constrained = "#[no_prelude]\nfn total(items) {\n    fn size(list) {\n        return len(list)\n    }\n    print(\"total\")\n    return twice(1)\n}\nfn twice(n) {\n    return len(\"ab\") * n\n}\n";
+        let errors = check(constrained).unwrap_err();
+        let lines: Vec<_> = errors.iter().map(|e| (e.code, e.line)).collect();
+        assert_eq!(lines, vec![(Some("E0270"), 4), (Some("E0270"), 6)], "nested helpers too, not twice");
+        assert!(errors[1].help.as_deref().unwrap().contains("import prelude.{print}"));
+
+        let imported = format!("import prelude.{{len, print}}\n{}", constrained);
+        assert!(check(&imported).is_ok());
+        let program = Parser::new(Lexer::new("import prelude.{len, nothing}\n")).parse().unwrap();
+        assert_eq!(ModuleLoader::new().check_imports(&program).unwrap_err().code, Some("E0303"));
+    }
+}
@@ -0,0 +1,27 @@
+use crate::opt::Level;
+
+// Build profiles, as Cargo has them.
+//
+// A debug build keeps every check and what a debugger needs: Int overflow
+// traps, #[requires] and #[ensures] are checked at run time, and the module
+// carries its function names and a source map. A release build optimizes
+// fully and drops all of that. astrixa.toml's [profile.debug] and
+// [profile.release] tables override single settings; both CLIs start from
+// these, so `--release` means the same build everywhere.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Profile {
+    pub opt_level: Level,
+    pub overflow_checks: bool, // Int overflow traps instead of wrapping around
+    pub assertions: bool,      // #[requires] and #[ensures] checked at run time
+    pub debug_info: bool,      // A name section and source map in the module
+}
+
+impl Profile {
+    pub const DEBUG: Profile = Profile { opt_level: Level::O0, overflow_checks: true, assertions: true, debug_info: true };
+    pub const RELEASE: Profile = Profile { opt_level: Level::O2, overflow_checks: false, assertions: false, debug_info: false };
+
+    pub fn for_mode(release: bool) -> Profile {
+        if release { Profile::RELEASE } else { Profile::DEBUG }
+    }
+}
@@ -0,0 +1,672 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use crate::ast::{Span, Stmt, StmtKind};
+use crate::edition::{Edition, Warning};
+use crate::error::CompileError;
+use crate::lexer::Lexer;
+use crate::interpreter::{Incompatible, Interpreter};
+use crate::ir::IRModule;
+use crate::parser::Parser;
+use crate::plugin::{IrPass, Lint};
+use crate::typechecker::{FunctionSignature, TraitMethods, TypeChecker};
+
+/// Long-lived compiler front-end for editors.
+///
+/// Documents are split into top-level items (functions, contracts, imports).
+/// Parsing is cached per item text and type checking per item plus the
+/// signatures declared before it, so an edit inside one function body only
+/// re-parses and re-checks that function. Later items are re-checked only
+/// when a signature they can see actually changes.
+///
+/// Plugins registered on a session (see `plugin`) apply to everything it
+/// checks, and to builds that go through `lint` and `run_ir_passes`.
+pub struct Session {
+    documents: HashMap<String, Vec<Item>>,
+    editions: HashMap<String, Edition>, // Per document; default when unset
+    parse_cache: HashMap<(u64, Edition), ParsedItem>,
+    check_cache: HashMap<(u64, u64), CheckedItem>,
+    lints: Vec<Box<dyn Lint>>,
+    ir_passes: Vec<Box<dyn IrPass>>,
+    pub stats: SessionStats,
+}
+
+/// Document that `reload` compiles scripts as
+const HOST_SCRIPT: &str = "host:///script.ax";
+
+/// Cache counters, useful for tests and tracing latency issues
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SessionStats {
+    pub parse_hits: usize,
+    pub parse_misses: usize,
+    pub check_hits: usize,
+    pub check_misses: usize,
+}
+
+/// A top-level item and where it starts in its document
+#[derive(Debug, Clone)]
+pub struct Item {
+    pub text: String,
+    pub start_line: usize, // 0-based line of the item's first line
+    hash: u64,
+}
+
+/// Parse result for one item, with its deprecation warnings
+#[derive(Clone)]
+struct ParsedItem {
+    result: Result<Vec<Stmt>, Vec<CompileError>>, // Every syntax error
+    warnings: Vec<Warning>,
+    spans: Vec<Span>,
+}
+
+/// Type checking result for one item in a given signature environment
+#[derive(Clone)]
+struct CheckedItem {
+    errors: Vec<CompileError>,
+    declared: Vec<(String, FunctionSignature)>,
+    traits: Vec<(String, TraitMethods)>,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Self {
+            documents: HashMap::new(),
+            editions: HashMap::new(),
+            parse_cache: HashMap::new(),
+            check_cache: HashMap::new(),
+            lints: Vec::new(),
+            ir_passes: Vec::new(),
+            stats: SessionStats::default(),
+        }
+    }
+
+    /// Replace the text of a document (open or change)
+    pub fn update(&mut self, uri: &str, text: &str) {
+        self.documents.insert(uri.to_string(), split_items(text));
+        self.collect_garbage();
+    }
+
+    /// Parse a document as the given edition (its project's astrixa.toml)
+    pub fn set_edition(&mut self, uri: &str, edition: Edition) {
+        self.editions.insert(uri.to_string(), edition);
+    }
+
+    /// Forget a document and any cache entries only it was using
+    pub fn close(&mut self, uri: &str) {
+        self.documents.remove(uri);
+        self.editions.remove(uri);
+        self.collect_garbage();
+    }
+
+    /// Syntax and type errors for a document, with document line numbers
+    pub fn diagnostics(&mut self, uri: &str) -> Vec<CompileError> {
+        let items = match self.documents.get(uri) {
+            Some(items) => items.clone(),
+            None => return Vec::new(),
+        };
+
+        let mut errors = Vec::new();
+        let mut env: HashMap<String, FunctionSignature> = HashMap::new();
+        let mut traits: HashMap<String, TraitMethods> = HashMap::new();
+        let mut env_hash = 0u64;
+
+        for item in &items {
+            let stmts = match self.parse_item(uri, item).result {
+                Ok(stmts) => stmts,
+                Err(syntax_errors) => {
+                    for mut err in syntax_errors {
+                        err.line += item.start_line;
+                        errors.push(err);
+                    }
+                    continue;
+                }
+            };
+
+            let checked = self.check_item(item, &stmts, (&env, &traits), env_hash);
+            for err in &checked.errors {
+                let mut err = err.clone();
+                err.line += item.start_line;
+                if let Some(fix) = &mut err.fix {
+                    fix.span.start_line += item.start_line;
+                    fix.span.end_line += item.start_line;
+                }
+                errors.push(err);
+            }
+
+            // Later items see this item's signatures and traits
+            for (name, sig) in checked.declared {
+                env_hash = hash_of(&(env_hash, &name, format!("{:?}", sig)));
+                env.insert(name, sig);
+            }
+            for (name, methods) in checked.traits {
+                env_hash = hash_of(&(env_hash, &name, &methods));
+                traits.insert(name, methods);
+            }
+        }
+
+        // Lints expect a program that type-checks
+        if errors.is_empty() && !self.lints.is_empty() {
+            if let Some((program, spans)) = self.spanned_program(uri) {
+                errors.extend(self.lint(&program, &spans));
+            }
+        }
+
+        errors
+    }
+
+    /// Hot reload for hosts embedding the interpreter: recompile `source`,
+    /// re-parsing and re-checking only the items changed since the last
+    /// reload, and swap it into `vm` with `Interpreter::reload`. Errors leave
+    /// `vm` untouched. Each change that calls written against the previous
+    /// script may not expect is passed to `on_incompatible`.
+    pub fn reload(
+        &mut self,
+        vm: &mut Interpreter,
+        source: &str,
+        mut on_incompatible: impl FnMut(&Incompatible),
+    ) -> Result<(), Vec<CompileError>> {
+        self.update(HOST_SCRIPT, source);
+        let errors = self.diagnostics(HOST_SCRIPT);
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        let program = self.program(HOST_SCRIPT).unwrap_or_default();
+        let changes = vm.reload(program).map_err(|e| vec![CompileError::new(&e, 1, 1)])?;
+        for change in &changes {
+            on_incompatible(change);
+        }
+        Ok(())
+    }
+
+    /// The whole document's AST, if every item parses
+    pub fn program(&mut self, uri: &str) -> Option<Vec<Stmt>> {
+        self.spanned_program(uri).map(|(program, _)| program)
+    }
+
+    /// The document's AST and statement spans, with document line numbers
+    fn spanned_program(&mut self, uri: &str) -> Option<(Vec<Stmt>, Vec<Span>)> {
+        let items = self.documents.get(uri)?.clone();
+        let mut program = Vec::new();
+        let mut spans = Vec::new();
+        for item in &items {
+            let parsed = self.parse_item(uri, item);
+            program.extend(parsed.result.ok()?);
+            spans.extend(parsed.spans.into_iter().map(|mut span| {
+                span.start_line += item.start_line;
+                span.end_line += item.start_line;
+                span
+            }));
+        }
+        Some((program, spans))
+    }
+
+    /// Add a custom lint; its findings are reported like type errors
+    pub fn register_lint(&mut self, lint: Box<dyn Lint>) {
+        self.lints.push(lint);
+    }
+
+    /// Add a custom IR pass; passes run in registration order
+    pub fn register_ir_pass(&mut self, pass: Box<dyn IrPass>) {
+        self.ir_passes.push(pass);
+    }
+
+    /// Findings of every registered lint, each message prefixed with the
+    /// lint's name
+    pub fn lint(&self, program: &[Stmt], spans: &[Span]) -> Vec<CompileError> {
+        let mut findings = Vec::new();
+        for lint in &self.lints {
+            for mut finding in lint.check(program, spans) {
+                finding.message = format!("[{}] {}", lint.name(), finding.message);
+                findings.push(finding);
+            }
+        }
+        findings
+    }
+
+    /// Run every registered IR pass over `module`
+    pub fn run_ir_passes(&self, module: &mut IRModule) {
+        for pass in &self.ir_passes {
+            pass.run(module);
+        }
+    }
+
+    /// Edition deprecation, contract gas and unused code warnings for a
+    /// document, with document line numbers
+    pub fn warnings(&mut self, uri: &str) -> Vec<Warning> {
+        let items = match self.documents.get(uri) {
+            Some(items) => items.clone(),
+            None => return Vec::new(),
+        };
+
+        let mut warnings = Vec::new();
+        let mut parts = Vec::new();
+        for item in &items {
+            let parsed = self.parse_item(uri, item);
+            let gas = parsed.result.as_deref().map(crate::loops::analyze).unwrap_or_default();
+            for mut warning in parsed.warnings.into_iter().chain(gas) {
+                warning.span.start_line += item.start_line;
+                warning.span.end_line += item.start_line;
+                warnings.push(warning);
+            }
+            parts.push(parsed.result.ok());
+        }
+
+        // Whether a function is called depends on every item, so unused
+        // code is only reported once the whole document parses
+        if let Some(parts) = parts.into_iter().collect::<Option<Vec<_>>>() {
+            let parts: Vec<&[Stmt]> = parts.iter().map(Vec::as_slice).collect();
+            for (part, mut warning) in crate::unused::analyze_parts(&parts) {
+                warning.span.start_line += items[part].start_line;
+                warning.span.end_line += items[part].start_line;
+                warnings.push(warning);
+            }
+        }
+        warnings
+    }
+
+    fn parse_item(&mut self, uri: &str, item: &Item) -> ParsedItem {
+        let edition = self.editions.get(uri).copied().unwrap_or_default();
+        let key = (item.hash, edition);
+        if let Some(cached) = self.parse_cache.get(&key) {
+            self.stats.parse_hits += 1;
+            return cached.clone();
+        }
+
+        self.stats.parse_misses += 1;
+        let mut parser = Parser::new(Lexer::new(&item.text));
+        parser.set_edition(edition);
+        let result = parser.parse().map_err(|_| parser.errors().to_vec());
+        let parsed = ParsedItem {
+            result,
+            warnings: parser.warnings().to_vec(),
+            spans: parser.spans().to_vec(),
+        };
+        self.parse_cache.insert(key, parsed.clone());
+        parsed
+    }
+
+    fn check_item(
+        &mut self,
+        item: &Item,
+        stmts: &[Stmt],
+        (env, traits): (&HashMap<String, FunctionSignature>, &HashMap<String, TraitMethods>),
+        env_hash: u64,
+    ) -> CheckedItem {
+        let key = (item.hash, env_hash);
+        if let Some(cached) = self.check_cache.get(&key) {
+            self.stats.check_hits += 1;
+            return cached.clone();
+        }
+
+        self.stats.check_misses += 1;
+        let mut checker = TypeChecker::with_functions(env.clone()).with_traits(traits.clone());
+        let errors = checker.check(stmts).err().unwrap_or_default();
+
+        let declared = declared_names(stmts)
+            .into_iter()
+            .filter_map(|name| {
+                let sig = checker.functions().get(&name)?.clone();
+                Some((name, sig))
+            })
+            .collect();
+
+        let traits = stmts
+            .iter()
+            .filter_map(|stmt| match &stmt.kind {
+                StmtKind::Trait { name, .. } => Some((name.clone(), checker.traits().get(name)?.clone())),
+                _ => None,
+            })
+            .collect();
+
+        let checked = CheckedItem { errors, declared, traits };
+        self.check_cache.insert(key, checked.clone());
+        checked
+    }
+
+    /// Drop cached results for items no open document contains
+    fn collect_garbage(&mut self) {
+        let live: HashSet<u64> = self
+            .documents
+            .values()
+            .flat_map(|items| items.iter().map(|item| item.hash))
+            .collect();
+
+        self.parse_cache.retain(|(hash, _), _| live.contains(hash));
+        self.check_cache.retain(|(hash, _), _| live.contains(hash));
+    }
+}
+
+/// Function names an item declares, as the type checker registers them
+fn declared_names(stmts: &[Stmt]) -> Vec<String> {
+    let mut names = Vec::new();
+    for stmt in stmts {
+        match &stmt.kind {
+            StmtKind::Function { name, .. } => names.push(name.clone()),
+            StmtKind::Contract { name, constructor, methods, .. } => {
+                for method in constructor.iter().map(|c| c.as_ref()).chain(methods.iter()) {
+                    if let StmtKind::Function { name: method_name, .. } = &method.kind {
+                        names.push(format!("{}.{}", name, method_name));
+                    }
+                }
+            }
+            StmtKind::Impl { target, methods, .. } => {
+                for method in methods {
+                    if let StmtKind::Function { name: method_name, .. } = &method.kind {
+                        names.push(format!("{}.{}", target, method_name));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    names
+}
+
+/// Split source into top-level items by brace depth.
+///
+/// An unterminated item is cut short when a line starts a new top-level
+/// declaration, so a half-typed function doesn't swallow the rest of the file.
+pub fn split_items(text: &str) -> Vec<Item> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut items = Vec::new();
+    let mut current: Vec<&str> = Vec::new();
+    let mut start_line = 0;
+    let mut depth = 0i32;
+    let mut opened = false;
+
+    for (i, line) in lines.iter().enumerate() {
+        if !current.is_empty() && depth > 0 && starts_declaration(line) {
+            items.push(make_item(&current, start_line));
+            current.clear();
+            depth = 0;
+            opened = false;
+        }
+
+        if current.is_empty() {
+            let code = strip_comment(line);
+            if code.trim().is_empty() {
+                continue;
+            }
+            start_line = i;
+        }
+
+        current.push(line);
+        let (opens, closes) = brace_counts(line);
+        opened |= opens > 0;
+        depth += opens - closes;
+
+        // Item ends when braces balance, unless the body opens on the next line
+        let next_opens_block = lines[i + 1..]
+            .iter()
+            .find(|l| !strip_comment(l).trim().is_empty())
+            .is_some_and(|l| l.trim_start().starts_with('{'));
+        // An attribute line belongs to the function after it
+        let is_attribute = line.trim_start().starts_with("#[");
+        if depth <= 0 && !is_attribute && (opened || !next_opens_block) {
+            items.push(make_item(&current, start_line));
+            current.clear();
+            depth = 0;
+            opened = false;
+        }
+    }
+
+    if !current.is_empty() {
+        items.push(make_item(&current, start_line));
+    }
+
+    items
+}
+
+fn make_item(lines: &[&str], start_line: usize) -> Item {
+    let text = lines.join("\n");
+    Item {
+        hash: hash_of(&text),
+        text,
+        start_line,
+    }
+}
+
+fn starts_declaration(line: &str) -> bool {
+    ["fn ", "async fn ", "export fn ", "export async fn ", "contract ", "import ", "#["]
+        .iter()
+        .any(|kw| line.starts_with(kw))
+}
+
+/// Drop a trailing `//` comment that isn't inside a string
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    let bytes = line.as_bytes();
+    for i in 0..bytes.len() {
+        match bytes[i] {
+            b'"' => in_string = !in_string,
+            b'/' if !in_string && bytes.get(i + 1) == Some(&b'/') => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// Opening and closing braces on a line, ignoring strings and comments
+fn brace_counts(line: &str) -> (i32, i32) {
+    let mut in_string = false;
+    let (mut opens, mut closes) = (0, 0);
+    for ch in strip_comment(line).chars() {
+        match ch {
+            '"' => in_string = !in_string,
+            '{' if !in_string => opens += 1,
+            '}' if !in_string => closes += 1,
+            _ => {}
+        }
+    }
+    (opens, closes)
+}
+
+fn hash_of<T: Hash>(value: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "fn add(a, b) {\n    return a + b\n}\n\nfn main() {\n    let x = add(1, 2)\n}\n";
+
+    #[test]
+    fn test_split_items_by_top_level_braces() {
+        let items = split_items(SOURCE);
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].start_line, 0);
+        assert_eq!(items[1].start_line, 4);
+
+        let items = split_items("#[test]\n#[inline]\nfn check() {\n}\n");
+        assert_eq!(items.len(), 1);
+        assert!(items[0].text.starts_with("#[test]"));
+    }
+
+    #[test]
+    fn test_body_edit_only_rechecks_that_function() {
+        let mut session = Session::new();
+        session.update("file:///a.ax", SOURCE);
+        assert!(session.diagnostics("file:///a.ax").is_empty());
+
+        // Same signature for add: main's check result is reused
+        let edited = SOURCE.replace("return a + b", "let sum = a + b\n    return sum");
+        session.update("file:///a.ax", &edited);
+        session.stats = SessionStats::default();
+        assert!(session.diagnostics("file:///a.ax").is_empty());
+
+        assert_eq!(session.stats.parse_misses, 1);
+        assert_eq!(session.stats.parse_hits, 1);
+        assert_eq!(session.stats.check_misses, 1);
+        assert_eq!(session.stats.check_hits, 1);
+    }
+
+    #[test]
+    fn test_signature_change_rechecks_callers() {
+        let mut session = Session::new();
+        session.update("file:///a.ax", SOURCE);
+        session.diagnostics("file:///a.ax");
+
+        let edited = SOURCE.replace("fn add(a, b)", "fn add(a)").replace("a + b", "a");
+        session.update("file:///a.ax", &edited);
+        let errors = session.diagnostics("file:///a.ax");
+
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].message.contains("expects 1 arguments, got 2"));
+        // Reported at the call, not the start of `main`
+        assert_eq!((errors[0].line, errors[0].column), (6, 13));
+    }
+
+    #[test]
+    fn test_parse_errors_use_document_lines() {
+        let mut session = Session::new();
+        session.update("file:///a.ax", "fn ok() {\n}\n\nfn broken( {\n}\n");
+        let errors = session.diagnostics("file:///a.ax");
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line, 4);
+        assert!(session.program("file:///a.ax").is_none());
+    }
+
+    #[test]
+    fn test_reassigning_immutable_binding_offers_let_mut() {
+        let mut session = Session::new();
+        let text = "fn ok() {\n}\n\nfn count() {\n    let n = 0\n    let mut m = 0\n    n = 1\n    m = 1\n}\n";
+        session.update("file:///a.ax", text);
+        let errors = session.diagnostics("file:///a.ax");
+
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert_eq!((errors[0].code, errors[0].line), (Some("E0243"), 7));
+        let fix = errors[0].fix.as_ref().unwrap();
+        assert_eq!((fix.span.start_line, fix.span.start_column, fix.span.end_column), (5, 5, 8));
+        assert_eq!(fix.replacement, "let mut");
+    }
+
+    #[test]
+    fn test_undefined_names_suggest_close_ones() {
+        let mut session = Session::new();
+        let text = "fn price(amount) {\n    return amount * 2\n}\n\nfn main() {\n    let total = 1\n    let amount = 3\n    print(totl)\n    print(price(amout))\n    return total + amount + speed\n}\n";
+        session.update("file:///a.ax", text);
+        let errors = session.diagnostics("file:///a.ax");
+
+        let found: Vec<(Option<&str>, usize, usize)> = errors.iter().map(|e| (e.code, e.line, e.column)).collect();
+        assert_eq!(found, vec![(Some("E0203"), 8, 11), (Some("E0203"), 9, 17), (Some("E0203"), 10, 29)], "{:?}", errors);
+        assert_eq!(errors[0].help.as_deref(), Some("Did you mean 'total'?"));
+        assert_eq!(errors[1].help.as_deref(), Some("Did you mean 'amount'?"));
+        assert_eq!(errors[2].help, None);
+    }
+
+    #[test]
+    fn test_type_errors_point_at_the_offending_expression() {
+        let mut session = Session::new();
+        let text = "fn greet(name: String) {\n    print(name)\n}\n\nfn main() {\n    let count = 1\n    if count > 0 {\n        greet(\"a\" + \"b\")\n        greet(count)\n    }\n    return count + true\n}\n";
+        session.update("file:///a.ax", text);
+        let errors = session.diagnostics("file:///a.ax");
+
+        // The argument, and the sum, not the statements or functions around them
+        let found: Vec<(Option<&str>, usize, usize)> = errors.iter().map(|e| (e.code, e.line, e.column)).collect();
+        assert_eq!(found, vec![(Some("E0208"), 9, 15), (Some("E0209"), 11, 12)], "{:?}", errors);
+    }
+
+    #[test]
+    fn test_reload_keeps_state_and_reports_incompatible_changes() {
+        use crate::interpreter::Value;
+
+        let counter = "contract Counter {\n    state count\n    state total\n    constructor() {\n        count = 0\n    }\n    fn bump(by) {\n        count = count + by\n        return count\n    }\n}\n\nfn helper() {\n}\n";
+        let bump = |vm: &mut Interpreter, args: Vec<i64>| {
+            match vm.transact("Counter", "bump", args.into_iter().map(Value::Number).collect(), "0x1", 0) {
+                Ok(Value::Number(n)) => n,
+                _ => panic!("bump failed"),
+            }
+        };
+
+        let mut session = Session::new();
+        let mut vm = Interpreter::new();
+        session.reload(&mut vm, counter, |change| panic!("unexpected {:?}", change)).unwrap();
+        assert!(vm.transact("Counter", "constructor", vec![], "0x1", 0).is_ok());
+        assert_eq!(bump(&mut vm, vec![2]), 2);
+
+        // A new body with the same signature keeps the counter's value
+        let edited = counter.replace("count + by", "count + by * 10");
+        session.reload(&mut vm, &edited, |change| panic!("unexpected {:?}", change)).unwrap();
+        assert_eq!(bump(&mut vm, vec![1]), 12);
+
+        let reshaped = edited
+            .replace("    state total\n", "")
+            .replace("fn bump(by)", "fn bump(by, times)")
+            .replace("\nfn helper() {\n}\n", "");
+        let mut changes = Vec::new();
+        session.reload(&mut vm, &reshaped, |change| changes.push(change.clone())).unwrap();
+        assert_eq!(changes, vec![
+            Incompatible::StateChanged { contract: "Counter".to_string(), added: vec![], removed: vec!["total".to_string()] },
+            Incompatible::SignatureChanged("Counter.bump".to_string()),
+            Incompatible::Removed("helper".to_string()),
+        ]);
+        assert_eq!(bump(&mut vm, vec![1, 0]), 22);
+
+        // A script that does not compile leaves the running one in place
+        let errors = session.reload(&mut vm, "fn broken( {\n}\n", |_| {}).unwrap_err();
+        assert_eq!(errors[0].line, 1);
+        assert_eq!(bump(&mut vm, vec![1, 0]), 32);
+    }
+
+    #[test]
+    fn test_every_syntax_error_in_an_item_is_reported() {
+        let source = "fn main() {\n    let = 1\n    let ok = 2\n    if ok { let y 3 }\n    let z = (1 +\n}\n";
+        let mut session = Session::new();
+        session.update("file:///a.ax", source);
+        let errors = session.diagnostics("file:///a.ax");
+
+        let lines: Vec<usize> = errors.iter().map(|e| e.line).collect();
+        assert_eq!(lines, vec![2, 4, 6], "{:?}", errors);
+        assert_eq!(errors[0].code, Some("E0118"));
+        assert_eq!(errors[1].code, Some("E0119"));
+    }
+
+    #[test]
+    fn test_every_malformed_literal_is_reported() {
+        let source = "fn main() {\n    let a = \"\\q\"\n    let b = 1abc\n    print(a)\n}\n";
+        let mut session = Session::new();
+        session.update("file:///a.ax", source);
+        let errors = session.diagnostics("file:///a.ax");
+
+        let found: Vec<(Option<&str>, usize)> = errors.iter().map(|e| (e.code, e.line)).collect();
+        assert_eq!(found, vec![(Some("E0140"), 2), (Some("E0170"), 3)], "{:?}", errors);
+    }
+
+    #[test]
+    fn test_warnings_follow_document_edition() {
+        let source = "fn helper() {\n}\n\nfn main() {\n    println(1)\n    helper()\n}\n";
+        let mut session = Session::new();
+        session.update("file:///a.ax", source);
+        assert!(session.warnings("file:///a.ax").is_empty());
+
+        session.set_edition("file:///a.ax", Edition::E2025);
+        let warnings = session.warnings("file:///a.ax");
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].code, "W0001");
+        assert_eq!(warnings[0].span.start_line, 5);
+        assert_eq!(warnings[0].span.start_column, 5);
+        assert_eq!(warnings[0].replacement.as_deref(), Some("print"));
+    }
+
+    #[test]
+    fn test_unused_code_warnings_see_every_item() {
+        let source = "fn main() {\n    print(used(1))\n}\n\nfn used(x) {\n    let y = x\n    return 1\n}\n";
+        let mut session = Session::new();
+        session.update("file:///a.ax", source);
+        let warnings = session.warnings("file:///a.ax");
+
+        let found: Vec<(&str, usize)> = warnings.iter().map(|w| (w.code, w.span.start_line)).collect();
+        assert_eq!(found, vec![("W0301", 6)]);
+    }
+}
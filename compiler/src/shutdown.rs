@@ -0,0 +1,111 @@
+// Graceful shutdown on SIGINT and SIGTERM
+//
+// While a program runs natively, Ctrl-C (SIGINT) or SIGTERM does not kill
+// the process outright. The signal is noted, and the runtime stops at a
+// point of its choosing: the interpreter before its next statement, after
+// which it runs the program's `on_shutdown` handlers; a `Scheduler` that
+// catches signals before its next round, running each unfinished VM's
+// handlers and finishing it as interrupted. Output is flushed either way. A second signal before then exits at once, with
+// the conventional status of 128 + the signal.
+//
+// Signals are only caught while a `Signals` is alive, so outside a run
+// they act as they always do. WASM builds have no signals to catch, but a
+// `Stop` raised from another thread works the same.
+
+use std::io::Write;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::Arc;
+
+pub const SIGINT: i32 = 2;
+pub const SIGTERM: i32 = 15;
+
+/// Where a received signal is noted. Clones share it, so another thread
+/// can stop a run the way a signal would.
+#[derive(Debug, Clone, Default)]
+pub struct Stop(Arc<AtomicI32>); // 0 until a signal is received
+
+impl Stop {
+    /// The signal received, if one has been
+    pub fn received(&self) -> Option<i32> {
+        Some(self.0.load(Ordering::SeqCst)).filter(|signal| *signal != 0)
+    }
+
+    /// Act as though `signal` had been received
+    pub fn raise(&self, signal: i32) {
+        self.0.store(signal, Ordering::SeqCst);
+    }
+
+    /// Forget the signal received, ready for the next run
+    pub fn clear(&self) {
+        self.0.store(0, Ordering::SeqCst);
+    }
+}
+
+/// SIGINT and SIGTERM, caught into a `Stop` while this is alive
+pub struct Signals {
+    stop: Stop,
+    #[cfg(not(target_arch = "wasm32"))]
+    ids: Vec<signal_hook_registry::SigId>,
+}
+
+impl Signals {
+    pub fn catch(stop: Stop) -> Self {
+        #[cfg(not(target_arch = "wasm32"))]
+        let ids = [SIGINT, SIGTERM]
+            .into_iter()
+            .filter_map(|signal| {
+                let received = stop.0.clone();
+                // Only an atomic swap and _exit, which are safe in a handler
+                let action = move || {
+                    if received.swap(signal, Ordering::SeqCst) != 0 {
+                        unsafe { libc::_exit(exit_code(signal)) };
+                    }
+                };
+                unsafe { signal_hook_registry::register(signal, action) }.ok()
+            })
+            .collect();
+        Signals {
+            stop,
+            #[cfg(not(target_arch = "wasm32"))]
+            ids,
+        }
+    }
+
+    /// The signal received, if one has been
+    pub fn received(&self) -> Option<i32> {
+        self.stop.received()
+    }
+
+    pub fn stop(&self) -> Stop {
+        self.stop.clone()
+    }
+}
+
+impl Drop for Signals {
+    fn drop(&mut self) {
+        #[cfg(not(target_arch = "wasm32"))]
+        for id in self.ids.drain(..) {
+            signal_hook_registry::unregister(id);
+        }
+    }
+}
+
+/// The name a signal goes by, as in "SIGINT"
+pub fn name(signal: i32) -> String {
+    match signal {
+        SIGINT => "SIGINT".to_string(),
+        SIGTERM => "SIGTERM".to_string(),
+        _ => format!("signal {}", signal),
+    }
+}
+
+/// The status a process stopped by `signal` exits with
+pub fn exit_code(signal: i32) -> i32 {
+    128 + signal
+}
+
+/// Write out whatever is still buffered for stdout and stderr
+pub fn flush() {
+    let _ = std::io::stdout().flush();
+    let _ = std::io::stderr().flush();
+}
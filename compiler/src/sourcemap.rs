@@ -0,0 +1,86 @@
+use crate::ast::Span;
+
+// Source maps (version 3) for WASM modules.
+//
+// Browsers and Node map a WASM module back to its source through a map
+// whose one generated "line" is the module itself, with byte offsets as
+// columns. IR keeps a span per function, and with debug info a line marker
+// per statement, so the map points each function's code at where it was
+// declared and each statement's code at the statement: enough for a trace
+// or a debugger to land on the right line of the source.
+
+const BASE64: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// The source map of module `file`, built from `source_name` (as the map
+/// should refer to it) and its text: code offsets in the module, each with
+/// the span its code comes from, such as a function's declaration
+pub fn wasm_source_map(file: &str, source_name: &str, source: &str, points: &[(usize, Span)]) -> String {
+    let mut points = points.to_vec();
+    points.sort_by_key(|(offset, _)| *offset);
+
+    // Each segment is relative to the one before: offset, source, line, column
+    let mut mappings = String::new();
+    let (mut offset, mut line, mut column) = (0, 0, 0);
+    for (i, (at, span)) in points.iter().enumerate() {
+        if i > 0 {
+            mappings.push(',');
+        }
+        let (at_line, at_column) = (span.start_line.saturating_sub(1) as i64, span.start_column.saturating_sub(1) as i64);
+        vlq(&mut mappings, *at as i64 - offset);
+        vlq(&mut mappings, 0);
+        vlq(&mut mappings, at_line - line);
+        vlq(&mut mappings, at_column - column);
+        (offset, line, column) = (*at as i64, at_line, at_column);
+    }
+
+    let map = serde_json::json!({
+        "version": 3,
+        "file": file,
+        "sources": [source_name],
+        "sourcesContent": [source],
+        "names": [],
+        "mappings": mappings,
+    });
+    format!("{}\n", map)
+}
+
+/// A signed number in base64 VLQ: the sign in the lowest bit, then five
+/// bits per digit, least significant first
+fn vlq(out: &mut String, n: i64) {
+    let mut value = if n < 0 { ((-n as u64) << 1) | 1 } else { (n as u64) << 1 };
+    loop {
+        let mut digit = value & 31;
+        value >>= 5;
+        if value > 0 {
+            digit |= 32; // More digits follow
+        }
+        out.push(BASE64[digit as usize] as char);
+        if value == 0 {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_source_map_points_functions_at_declarations() {
+        let at = |line, column| Span { start_line: line, start_column: column, end_line: line, end_column: column };
+        let map = wasm_source_map("demo.wasm", "../src/main.ax", "fn a() {}\n\nfn b() {}\n", &[(120, at(3, 1)), (100, at(1, 1))]);
+        let map: serde_json::Value = serde_json::from_str(&map).unwrap();
+
+        assert_eq!(map["version"], 3);
+        assert_eq!(map["sources"][0], "../src/main.ax");
+        // Offset 100 at line 0, then 20 bytes on at line 2
+        assert_eq!(map["mappings"], "oGAAA,oBAEA");
+
+        let encoded = |n| {
+            let mut out = String::new();
+            vlq(&mut out, n);
+            out
+        };
+        assert_eq!([encoded(0), encoded(1), encoded(-1), encoded(16)], ["A", "C", "D", "gB"]);
+    }
+}
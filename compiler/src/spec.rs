@@ -0,0 +1,283 @@
+use std::path::Path;
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use crate::ast::Stmt;
+use crate::codegen::{binary, web};
+use crate::interpreter::Interpreter;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use crate::typechecker::TypeChecker;
+use crate::vm::VM;
+
+// The conformance suite: programs every backend has to run alike.
+//
+// Each program in `spec/` says what it does in comment lines before its
+// code:
+//
+//   // expect-output: 3
+//   // expect-output: done
+//   // expect-error: trap
+//
+// `expect-output` is a line it prints, in order. `expect-error` is either
+// a catalog code the front end has to reject it with, as in
+// `expect-error: E0271`, or `trap`: it has to fail at run time, after
+// printing any expected lines. Without one, it has to run to the end.
+// The WASM backend prints strings only, so programs print numbers through
+// `str`.
+//
+// Every backend runs `main` from the same checked program: the interpreter
+// directly, the VM from the lowered IR translated back to bytecode (see
+// `translate::to_bytecode`), and WASM as an optimized web build, run by
+// Node through its own loader. A backend that cannot express a program
+// (the VM has no floats, and neither compiled backend has arrays) or is
+// not installed reports it
+// unsupported rather than failing, so the suite can run ahead of the
+// backends; a new backend joins by passing it.
+
+/// A program in the suite and what it has to do
+#[derive(Debug, Clone)]
+pub struct Case {
+    pub name: String,
+    pub source: String,
+    pub output: Vec<String>, // Printed lines, in order
+    pub expected: Expected,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expected {
+    Runs,
+    Traps,
+    Rejected(String), // The code the front end reports
+}
+
+/// What runs programs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Backend {
+    Interpreter,
+    Vm,
+    Wasm,
+}
+
+impl Backend {
+    pub const ALL: [Backend; 3] = [Backend::Interpreter, Backend::Vm, Backend::Wasm];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Backend::Interpreter => "interpreter",
+            Backend::Vm => "vm",
+            Backend::Wasm => "wasm",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Verdict {
+    Pass,
+    Fail(String),        // What it did instead
+    Unsupported(String), // Why the backend could not run it
+}
+
+/// What a backend's run of `main` printed, and how it failed if it did
+struct Run {
+    output: Vec<String>,
+    error: Option<String>,
+}
+
+/// Read a case's headers
+pub fn parse_case(name: &str, source: &str) -> Result<Case, String> {
+    let mut case = Case { name: name.to_string(), source: source.to_string(), output: Vec::new(), expected: Expected::Runs };
+    for line in source.lines().map(str::trim).take_while(|line| line.starts_with("//")) {
+        let Some((key, value)) = line.trim_start_matches('/').trim().split_once(':') else { continue };
+        let value = value.strip_prefix(' ').unwrap_or(value);
+        match key {
+            "expect-output" => case.output.push(value.to_string()),
+            "expect-error" if value == "trap" => case.expected = Expected::Traps,
+            "expect-error" => case.expected = Expected::Rejected(value.trim().to_string()),
+            _ if key.starts_with("expect-") => return Err(format!("{}: unknown header '{}'", name, key)),
+            _ => {}
+        }
+    }
+    Ok(case)
+}
+
+/// Every case under `dir`, by path
+pub fn load(dir: &Path) -> Result<Vec<Case>, String> {
+    let mut cases = Vec::new();
+    for entry in walkdir::WalkDir::new(dir).sort_by_file_name() {
+        let entry = entry.map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+        let path = entry.path();
+        if path.extension().is_some_and(|ext| ext == "ax") {
+            let source = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            let name = path.strip_prefix(dir).unwrap_or(path).display().to_string();
+            cases.push(parse_case(&name, &source)?);
+        }
+    }
+    Ok(cases)
+}
+
+/// Run `case` on every backend
+pub fn check(case: &Case) -> Vec<(Backend, Verdict)> {
+    // The front end is shared, so a rejection is the same for all
+    let ast = match front_end(&case.source) {
+        Ok(ast) => ast,
+        Err(codes) => {
+            let verdict = match &case.expected {
+                Expected::Rejected(code) if codes.contains(code) => Verdict::Pass,
+                _ => Verdict::Fail(format!("rejected with {}", codes.join(", "))),
+            };
+            return Backend::ALL.iter().map(|backend| (*backend, verdict.clone())).collect();
+        }
+    };
+    if let Expected::Rejected(code) = &case.expected {
+        let verdict = Verdict::Fail(format!("accepted, expected {}", code));
+        return Backend::ALL.iter().map(|backend| (*backend, verdict.clone())).collect();
+    }
+
+    Backend::ALL
+        .iter()
+        .map(|backend| {
+            let verdict = match run(*backend, &ast) {
+                Ok(run) => judge(case, run),
+                Err(verdict) => verdict,
+            };
+            (*backend, verdict)
+        })
+        .collect()
+}
+
+/// The program, or the codes of the errors rejecting it
+fn front_end(source: &str) -> Result<Vec<Stmt>, Vec<String>> {
+    let code = |e: &crate::error::CompileError| e.code.unwrap_or("error").to_string();
+    let ast = Parser::new(Lexer::new(source)).parse().map_err(|e| vec![code(&e)])?;
+    TypeChecker::new().check(&ast).map_err(|errors| errors.iter().map(code).collect::<Vec<_>>())?;
+    Ok(ast)
+}
+
+fn judge(case: &Case, run: Run) -> Verdict {
+    if run.output != case.output {
+        let failed = run.error.map(|e| format!(", then failed: {}", e)).unwrap_or_default();
+        return Verdict::Fail(format!("printed {:?}{}, expected {:?}", run.output, failed, case.output));
+    }
+    match (run.error, &case.expected) {
+        (None, Expected::Traps) => Verdict::Fail("ran to the end, expected a trap".to_string()),
+        (Some(error), Expected::Runs) => Verdict::Fail(format!("failed: {}", error)),
+        _ => Verdict::Pass,
+    }
+}
+
+fn run(backend: Backend, ast: &[Stmt]) -> Result<Run, Verdict> {
+    match backend {
+        Backend::Interpreter => {
+            let mut interpreter = Interpreter::new();
+            interpreter.capture_output();
+            let result = interpreter.load(ast.to_vec()).and_then(|_| interpreter.call_values("main", vec![]));
+            Ok(Run { output: interpreter.take_output(), error: result.err() })
+        }
+        Backend::Vm => {
+            let module = crate::translate::to_bytecode_module(&compiled(ast)?).map_err(Verdict::Unsupported)?;
+            let mut vm = VM::new();
+            vm.capture_output();
+            let result = vm.run_module(&module, "main");
+            Ok(Run { output: vm.take_output(), error: result.err() })
+        }
+        Backend::Wasm => run_wasm(ast),
+    }
+}
+
+/// The program lowered as a build lowers it; what no compiled build can
+/// express yet makes the backends that compile it unsupported
+fn compiled(ast: &[Stmt]) -> Result<crate::ir::IRModule, Verdict> {
+    crate::lowering::lower_for_build(ast, false, false)
+        .map_err(|errors| {
+            // One in the standard library has no line in the program
+            let unsupported: Vec<String> = errors
+                .iter()
+                .map(|e| match e.line {
+                    0 => e.message.clone(),
+                    line => format!("{} (line {}, column {})", e.message, line, e.column),
+                })
+                .collect();
+            Verdict::Unsupported(unsupported.join("; "))
+        })
+}
+
+/// Printed before the message of whatever stopped `main`, to tell it from
+/// the program's own output
+const TRAPPED: &str = "#spec-trapped: ";
+
+/// An optimized web build of the program, with Node running its `main`
+fn run_wasm(ast: &[Stmt]) -> Result<Run, Verdict> {
+    static RUNS: AtomicUsize = AtomicUsize::new(0);
+
+    let module = crate::opt::optimize_module(&compiled(ast)?);
+    let artifact = web::lower(ast, &module, "case.wasm");
+    let bytes = binary::assemble(&artifact.wat).map_err(|e| Verdict::Fail(format!("did not assemble: {}", e)))?;
+
+    let dir = std::env::temp_dir().join(format!("astrixa-spec-{}-{}", std::process::id(), RUNS.fetch_add(1, Ordering::Relaxed)));
+    let write = |name: &str, contents: &[u8]| std::fs::write(dir.join(name), contents);
+    let written = std::fs::create_dir_all(&dir)
+        .and_then(|_| write("case.wasm", &bytes))
+        .and_then(|_| write("case.mjs", artifact.js.as_bytes()))
+        .and_then(|_| write("run.mjs", runner().as_bytes()));
+    let output = written.and_then(|_| Command::new("node").arg(dir.join("run.mjs")).output());
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let output = match output {
+        Ok(output) => output,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Err(Verdict::Unsupported("node is not installed".to_string())),
+        Err(e) => return Err(Verdict::Fail(format!("could not run node: {}", e))),
+    };
+    if !output.status.success() {
+        return Err(Verdict::Fail(format!("node failed: {}", String::from_utf8_lossy(&output.stderr).trim())));
+    }
+    let mut run = Run { output: Vec::new(), error: None };
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        match line.strip_prefix(TRAPPED) {
+            Some(message) => run.error = Some(message.to_string()),
+            None => run.output.push(line.to_string()),
+        }
+    }
+    Ok(run)
+}
+
+fn runner() -> String {
+    format!(
+        "import {{ readFileSync }} from \"node:fs\";\n\
+         import {{ load }} from \"./case.mjs\";\n\n\
+         const program = await load(readFileSync(new URL(\"./case.wasm\", import.meta.url)));\n\
+         try {{\n  program.main();\n}} catch (e) {{\n  console.log(\"{}\" + e.message);\n}}\n",
+        TRAPPED
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_backend_passes_the_suite() {
+        let case = parse_case("headers.ax", "// expect-output: a\n// expect-output:  b\n// expect-error: trap\nfn main() {}\n").unwrap();
+        assert_eq!((case.output, case.expected), (vec!["a".to_string(), " b".to_string()], Expected::Traps));
+        assert!(parse_case("typo.ax", "// expect-ouput: 1\n").is_err());
+
+        // A wrong expectation fails on every backend that runs it
+        let wrong = parse_case("wrong.ax", "// expect-output: 2\nfn main() {\n    print(\"1\")\n}\n").unwrap();
+        assert!(check(&wrong).iter().all(|(backend, verdict)| match verdict {
+            Verdict::Fail(why) => why.contains("printed [\"1\"]"),
+            Verdict::Unsupported(_) => *backend == Backend::Wasm,
+            Verdict::Pass => false,
+        }));
+
+        let suite = load(&Path::new(env!("CARGO_MANIFEST_DIR")).join("../spec")).unwrap();
+        assert!(suite.len() >= 5);
+        let failures: Vec<String> = suite
+            .iter()
+            .flat_map(|case| check(case).into_iter().map(move |result| (case, result)))
+            .filter_map(|(case, (backend, verdict))| match verdict {
+                Verdict::Fail(why) => Some(format!("{} on {}: {}", case.name, backend.name(), why)),
+                _ => None,
+            })
+            .collect();
+        assert!(failures.is_empty(), "{:#?}", failures);
+    }
+}
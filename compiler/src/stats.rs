@@ -0,0 +1,181 @@
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+use crate::ast::{Expr, ExprKind, Stmt, StmtKind};
+use crate::ir::{IRInstr, IRModule};
+
+// Compiler statistics, for `astrixa build --stats`.
+//
+// A build records how long each phase took, how big the program is at each
+// stage (AST nodes, then IR instructions per function), how many distinct
+// string constants the WASM data segments intern, and the process's peak
+// resident memory. Collecting is cheap, so every build does it and the CLI
+// only decides whether to print; a regression in the compiler's time or
+// memory then shows up on any project.
+
+#[derive(Debug, Clone, Default)]
+pub struct Stats {
+    pub phases: Vec<(&'static str, Duration)>, // In the order they ran
+    pub statements: usize,
+    pub expressions: usize,
+    pub functions: Vec<(String, usize)>, // IR instructions per function
+    pub strings: usize,
+    current: Option<(&'static str, Instant)>,
+}
+
+impl Stats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start timing `phase`, ending the one before
+    pub fn enter(&mut self, phase: &'static str) {
+        self.finish();
+        self.current = Some((phase, Instant::now()));
+    }
+
+    /// End the phase being timed
+    pub fn finish(&mut self) {
+        if let Some((phase, start)) = self.current.take() {
+            self.phases.push((phase, start.elapsed()));
+        }
+    }
+
+    /// Count the statements and expressions of `program`, including those
+    /// in function bodies, contracts and defaults
+    pub fn count_ast(&mut self, program: &[Stmt]) {
+        for stmt in program {
+            self.count_stmt(stmt);
+        }
+    }
+
+    fn count_stmt(&mut self, stmt: &Stmt) {
+        self.statements += 1;
+        match &stmt.kind {
+            StmtKind::Function { defaults, conditions, body, .. } => {
+                defaults.iter().flatten().for_each(|e| self.count_expr(e));
+                conditions.iter().for_each(|c| self.count_expr(&c.expr));
+                self.count_ast(body);
+            }
+            StmtKind::Expression(value) | StmtKind::Let { value, .. } | StmtKind::LetTuple { value, .. }
+            | StmtKind::Assign { value, .. } | StmtKind::Return(value) | StmtKind::Value(value) | StmtKind::Panic(value) => {
+                self.count_expr(value)
+            }
+            StmtKind::Defer(body) => self.count_ast(body),
+            StmtKind::If { condition, then_body, else_body } => {
+                self.count_expr(condition);
+                self.count_ast(then_body);
+                self.count_ast(else_body.as_deref().unwrap_or_default());
+            }
+            StmtKind::While { condition: value, body } | StmtKind::For { iterable: value, body, .. } => {
+                self.count_expr(value);
+                self.count_ast(body);
+            }
+            StmtKind::Match { value, arms } => {
+                self.count_expr(value);
+                arms.iter().for_each(|arm| self.count_ast(&arm.body));
+            }
+            StmtKind::Contract { constructor, methods, .. } => {
+                constructor.iter().for_each(|c| self.count_stmt(c));
+                self.count_ast(methods);
+            }
+            StmtKind::Trait { methods, .. } | StmtKind::Impl { methods, .. } => self.count_ast(methods),
+            StmtKind::Import { .. } | StmtKind::TypeAlias { .. } => {}
+        }
+    }
+
+    fn count_expr(&mut self, expr: &Expr) {
+        self.expressions += 1;
+        match &expr.kind {
+            ExprKind::Call(_, args) | ExprKind::ModuleCall(_, _, args) | ExprKind::Tuple(args)
+            | ExprKind::Array(args) => args.iter().for_each(|a| self.count_expr(a)),
+            ExprKind::Named(_, inner) | ExprKind::Await(inner) | ExprKind::Try(inner) | ExprKind::Cast(inner, _) => {
+                self.count_expr(inner)
+            }
+            ExprKind::Range(a, b) | ExprKind::Slice(a, b)
+            | ExprKind::Add(a, b) | ExprKind::Sub(a, b) | ExprKind::Mul(a, b) | ExprKind::Div(a, b)
+            | ExprKind::Mod(a, b) | ExprKind::Eq(a, b) | ExprKind::Ne(a, b) | ExprKind::Lt(a, b)
+            | ExprKind::Le(a, b) | ExprKind::Gt(a, b) | ExprKind::Ge(a, b)
+            | ExprKind::And(a, b) | ExprKind::Or(a, b) => {
+                self.count_expr(a);
+                self.count_expr(b);
+            }
+            ExprKind::Number(_) | ExprKind::Float(_) | ExprKind::Bool(_) | ExprKind::String(_)
+            | ExprKind::Identifier(_) | ExprKind::Property(..) => {}
+        }
+    }
+
+    /// Count each function's instructions and the distinct strings, as the
+    /// module will be generated
+    pub fn count_ir(&mut self, module: &IRModule) {
+        let mut strings = HashSet::new();
+        self.functions.clear();
+        for function in &module.functions {
+            self.functions.push((function.name.clone(), function.instructions.len()));
+            for instr in &function.instructions {
+                if let IRInstr::LoadConstString(s) = instr {
+                    strings.insert(s);
+                }
+            }
+        }
+        self.strings = strings.len();
+    }
+
+    pub fn total_time(&self) -> Duration {
+        self.phases.iter().map(|(_, time)| *time).sum()
+    }
+}
+
+/// The most memory the process has held resident, in bytes; None where the
+/// platform does not say (only Linux's /proc is read)
+pub fn peak_rss() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+    let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb * 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    #[test]
+    fn test_stats_count_nodes_instructions_and_strings() {
+        let source = r#"
+            fn greet(name = "you") {
+                print("hi " + name)
+            }
+            fn main() {
+                greet()
+                print("hi ")
+                return 1
+            }
+        "#;
+        let program = Parser::new(Lexer::new(source)).parse().unwrap();
+        let mut stats = Stats::new();
+        stats.enter("parse");
+        stats.count_ast(&program);
+        stats.enter("lower");
+        let module = crate::lowering::lower(&program);
+        stats.count_ir(&module);
+        stats.finish();
+
+        // Two functions, their four statements
+        assert_eq!(stats.statements, 6);
+        // "you"; print, +, "hi ", name; greet(); print, "hi "; 1
+        assert_eq!(stats.expressions, 9);
+        let names: Vec<&str> = stats.functions.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["greet", "main"]);
+        assert!(stats.functions.iter().all(|(_, count)| *count > 0));
+        // "hi " twice is one string
+        assert_eq!(stats.strings, 2);
+        let phases: Vec<&str> = stats.phases.iter().map(|(phase, _)| *phase).collect();
+        assert_eq!(phases, vec!["parse", "lower"]);
+        assert_eq!(stats.total_time(), stats.phases[0].1 + stats.phases[1].1);
+
+        if cfg!(target_os = "linux") {
+            assert!(peak_rss().unwrap() > 0);
+        }
+    }
+}
@@ -1,7 +1,8 @@
 /// ASTRIXA Standard Library Registry
 /// 
 /// This module defines all built-in functions available in ASTRIXA.
-/// These functions are provided by the runtime and compiled as WASM imports.
+/// Most are provided by the runtime and compiled as WASM imports; the
+/// pure helpers under `std/` are written in ASTRIXA (see `bootstrap`).
 /// 
 /// Design principles:
 /// - Minimal: Only essential functions
@@ -10,6 +11,9 @@
 /// - Web3-ready: Includes crypto functions for blockchain
 /// - WASM-friendly: All signatures compatible with WASM types
 
+use std::sync::OnceLock;
+use crate::types::Type;
+
 /// Standard library function category
 #[derive(Debug, Clone, PartialEq)]
 pub enum StdlibCategory {
@@ -18,6 +22,7 @@ pub enum StdlibCategory {
     Time,      // Time functions: time, sleep
     Crypto,    // Web3 crypto: hash, keccak, sha256
     String,    // String operations: len, concat, substr
+    Array,     // Array helpers: sum, contains, index_of
     FileSystem, // File operations: fs.read, fs.write, fs.delete
     AI,        // STEP 52: AI-native functions: generate, embed, classify
 }
@@ -28,13 +33,59 @@ pub struct StdlibFunction {
     pub name: &'static str,
     pub category: StdlibCategory,
     pub description: &'static str,
-    pub param_count: usize,
-    pub return_type: &'static str,
+    pub params: Vec<Type>, // Type::Unknown accepts any value
+    pub returns: Type,
+}
+
+impl StdlibFunction {
+    /// Number of values the function returns: 2 for `(String, String)`,
+    /// and for a range, which is its two bounds; none for Void
+    pub fn result_count(&self) -> usize {
+        match &self.returns {
+            Type::Void => 0,
+            Type::Tuple(items) => items.len(),
+            Type::Range => 2,
+            _ => 1,
+        }
+    }
+
+    /// The module under `std` the function is also reachable from
+    pub fn module(&self) -> &'static str {
+        match self.name {
+            "print" | "println" | "input" => "io",
+            "exit" | "on_shutdown" => "process",
+            "range" | "map" | "filter" | "collect" => "iter",
+            "len" | "to_string" | "str" | "int" | "StringBuilder" => "string",
+            _ => match self.category {
+                StdlibCategory::Core => "core",
+                StdlibCategory::Math => "math",
+                StdlibCategory::Time => "time",
+                StdlibCategory::Crypto => "crypto",
+                StdlibCategory::String => "string",
+                StdlibCategory::Array => "array",
+                StdlibCategory::FileSystem => "fs",
+                StdlibCategory::AI => "ai",
+            },
+        }
+    }
+
+    /// Its fully-qualified path: `std::io::print` for `print`, and
+    /// `std::fs::read` for `fs.read`
+    pub fn path(&self) -> String {
+        let module = self.module();
+        let name = self.name.strip_prefix(module).and_then(|rest| rest.strip_prefix('.')).unwrap_or(self.name);
+        format!("std::{}::{}", module, name.replace('.', "::"))
+    }
+}
+
+/// The stdlib function a path like `std::io::print` names
+pub fn resolve_path(path: &str) -> Option<&'static StdlibFunction> {
+    stdlib_functions().iter().find(|f| f.path() == path)
 }
 
 /// Check if a function name is a standard library function
 pub fn is_stdlib(name: &str) -> bool {
-    STDLIB_FUNCTIONS.iter().any(|f| f.name == name)
+    stdlib_functions().iter().any(|f| f.name == name)
 }
 
 /// Check if a function is a filesystem function
@@ -67,197 +118,313 @@ pub fn is_web3(name: &str) -> bool {
     )
 }
 
+/// A value the runtime provides, read as `object.name`: `msg.sender`
+#[derive(Debug, Clone)]
+pub struct HostProperty {
+    pub object: &'static str,
+    pub name: &'static str,
+    pub description: &'static str,
+    pub returns: Type,
+}
+
+/// Get information about a host property
+pub fn get_property_info(object: &str, name: &str) -> Option<&'static HostProperty> {
+    HOST_PROPERTIES.iter().find(|p| p.object == object && p.name == name)
+}
+
 /// Get information about a stdlib function
 pub fn get_stdlib_info(name: &str) -> Option<&'static StdlibFunction> {
-    STDLIB_FUNCTIONS.iter().find(|f| f.name == name)
+    stdlib_functions().iter().find(|f| f.name == name)
 }
 
 /// Get all stdlib functions by category
 pub fn get_by_category(category: StdlibCategory) -> Vec<&'static StdlibFunction> {
-    STDLIB_FUNCTIONS
+    stdlib_functions()
         .iter()
         .filter(|f| f.category == category)
         .collect()
 }
 
 /// Registry of all standard library functions
-pub static STDLIB_FUNCTIONS: &[StdlibFunction] = &[
-    // ==========================================
-    // CORE FUNCTIONS (Always available)
-    // ==========================================
-    StdlibFunction {
-        name: "print",
-        category: StdlibCategory::Core,
-        description: "Print a value to stdout (no newline)",
-        param_count: 1,
-        return_type: "void",
-    },
-    StdlibFunction {
-        name: "println",
-        category: StdlibCategory::Core,
-        description: "Print a value to stdout with newline",
-        param_count: 1,
-        return_type: "void",
-    },
-    StdlibFunction {
-        name: "input",
-        category: StdlibCategory::Core,
-        description: "Read a line from stdin",
-        param_count: 0,
-        return_type: "string",
-    },
-    StdlibFunction {
-        name: "len",
-        category: StdlibCategory::Core,
-        description: "Get length of a string or array",
-        param_count: 1,
-        return_type: "int",
-    },
-    StdlibFunction {
-        name: "exit",
-        category: StdlibCategory::Core,
-        description: "Exit program with status code",
-        param_count: 1,
-        return_type: "void",
-    },
-    
-    // ==========================================
-    // MATH FUNCTIONS
-    // ==========================================
-    StdlibFunction {
-        name: "abs",
-        category: StdlibCategory::Math,
-        description: "Absolute value of a number",
-        param_count: 1,
-        return_type: "int",
-    },
-    StdlibFunction {
-        name: "pow",
-        category: StdlibCategory::Math,
-        description: "Raise base to exponent (base^exp)",
-        param_count: 2,
-        return_type: "int",
-    },
-    StdlibFunction {
-        name: "sqrt",
-        category: StdlibCategory::Math,
-        description: "Square root of a number",
-        param_count: 1,
-        return_type: "int",
-    },
-    StdlibFunction {
-        name: "min",
-        category: StdlibCategory::Math,
-        description: "Minimum of two numbers",
-        param_count: 2,
-        return_type: "int",
-    },
-    StdlibFunction {
-        name: "max",
-        category: StdlibCategory::Math,
-        description: "Maximum of two numbers",
-        param_count: 2,
-        return_type: "int",
-    },
-    StdlibFunction {
-        name: "rand",
-        category: StdlibCategory::Math,
-        description: "Random integer in range [0, max)",
-        param_count: 1,
-        return_type: "int",
-    },
-    
-    // ==========================================
-    // TIME FUNCTIONS
-    // ==========================================
-    StdlibFunction {
-        name: "time",
-        category: StdlibCategory::Time,
-        description: "Current Unix timestamp in milliseconds",
-        param_count: 0,
-        return_type: "int",
-    },
-    StdlibFunction {
-        name: "sleep",
-        category: StdlibCategory::Time,
-        description: "Sleep for specified milliseconds",
-        param_count: 1,
-        return_type: "void",
-    },
-    
-    // ==========================================
-    // CRYPTO FUNCTIONS (Web3-ready)
-    // ==========================================
-    StdlibFunction {
-        name: "hash",
-        category: StdlibCategory::Crypto,
-        description: "Generic hash function (defaults to keccak256)",
-        param_count: 1,
-        return_type: "string",
-    },
-    StdlibFunction {
-        name: "keccak",
-        category: StdlibCategory::Crypto,
-        description: "Keccak-256 hash (Ethereum standard)",
-        param_count: 1,
-        return_type: "string",
-    },
-    StdlibFunction {
-        name: "sha256",
-        category: StdlibCategory::Crypto,
-        description: "SHA-256 hash",
-        param_count: 1,
-        return_type: "string",
-    },    
-    // ==========================================
-    // AI FUNCTIONS (STEP 52)
-    // ==========================================
-    StdlibFunction {
-        name: "ai.generate",
-        category: StdlibCategory::AI,
-        description: "Generate text using AI (prompt in, text out)",
-        param_count: 1,
-        return_type: "string",
-    },
-    StdlibFunction {
-        name: "ai.embed",
-        category: StdlibCategory::AI,
-        description: "Generate embeddings for text",
-        param_count: 1,
-        return_type: "string",
-    },
-    StdlibFunction {
-        name: "ai.classify",
-        category: StdlibCategory::AI,
-        description: "Classify text into categories",
-        param_count: 1,
-        return_type: "string",
-    },
-    
-    // ==========================================
-    // FILE SYSTEM FUNCTIONS (with security)
-    // ==========================================
-    StdlibFunction {
-        name: "fs.read",
-        category: StdlibCategory::FileSystem,
-        description: "Read file contents (requires FS_READ capability)",
-        param_count: 1,
-        return_type: "string",
-    },
-    StdlibFunction {
-        name: "fs.write",
-        category: StdlibCategory::FileSystem,
-        description: "Write file contents (requires FS_WRITE capability)",
-        param_count: 2,
-        return_type: "int",
-    },
-    StdlibFunction {
-        name: "fs.delete",
-        category: StdlibCategory::FileSystem,
-        description: "Delete file (requires FS_DELETE capability)",
-        param_count: 1,
-        return_type: "int",
-    },
+pub fn stdlib_functions() -> &'static [StdlibFunction] {
+    static REGISTRY: OnceLock<Vec<StdlibFunction>> = OnceLock::new();
+    REGISTRY.get_or_init(registry)
+}
+
+fn registry() -> Vec<StdlibFunction> {
+    let mut functions = vec![
+        // ==========================================
+        // CORE FUNCTIONS (Always available)
+        // ==========================================
+        StdlibFunction {
+            name: "print",
+            category: StdlibCategory::Core,
+            description: "Print a value to stdout (no newline)",
+            params: vec![Type::Unknown],
+            returns: Type::Void,
+        },
+        StdlibFunction {
+            name: "println",
+            category: StdlibCategory::Core,
+            description: "Print a value to stdout with newline",
+            params: vec![Type::Unknown],
+            returns: Type::Void,
+        },
+        StdlibFunction {
+            name: "input",
+            category: StdlibCategory::Core,
+            description: "Read a line from stdin",
+            params: vec![],
+            returns: Type::String,
+        },
+        StdlibFunction {
+            name: "len",
+            category: StdlibCategory::Core,
+            description: "Get length of a string or array",
+            params: vec![Type::Unknown],
+            returns: Type::Int,
+        },
+        StdlibFunction {
+            name: "to_string",
+            category: StdlibCategory::Core,
+            description: "A value as print shows it, as a string",
+            params: vec![Type::Unknown],
+            returns: Type::String,
+        },
+        StdlibFunction {
+            name: "StringBuilder",
+            category: StdlibCategory::String,
+            description: "An empty text buffer: append(text) adds to it in place and to_string() reads it",
+            params: vec![],
+            returns: Type::Unknown,
+        },
+        StdlibFunction {
+            name: "str",
+            category: StdlibCategory::Core,
+            description: "A value as text, e.g. a number in decimal; like to_string",
+            params: vec![Type::Unknown],
+            returns: Type::String,
+        },
+        StdlibFunction {
+            name: "int",
+            category: StdlibCategory::Core,
+            description: "An Int from decimal text, or a Float truncated toward zero",
+            params: vec![Type::Unknown],
+            returns: Type::Int,
+        },
+        StdlibFunction {
+            name: "range",
+            category: StdlibCategory::Core,
+            description: "The Ints from start up to end, counted as a loop reaches them",
+            params: vec![Type::Int, Type::Int],
+            returns: Type::Range,
+        },
+        StdlibFunction {
+            name: "map",
+            category: StdlibCategory::Core,
+            description: "Items passed through a named function, lazily: map(items, double)",
+            params: vec![Type::Unknown, Type::Unknown],
+            returns: Type::Unknown,
+        },
+        StdlibFunction {
+            name: "filter",
+            category: StdlibCategory::Core,
+            description: "The items a named function is true for, lazily: filter(items, is_even)",
+            params: vec![Type::Unknown, Type::Unknown],
+            returns: Type::Unknown,
+        },
+        StdlibFunction {
+            name: "collect",
+            category: StdlibCategory::Core,
+            description: "An array of the items of a range or iterator",
+            params: vec![Type::Unknown],
+            returns: Type::Unknown,
+        },
+        StdlibFunction {
+            name: "on_shutdown",
+            category: StdlibCategory::Core,
+            description: "Run a named function when Ctrl-C or SIGTERM stops the program: on_shutdown(cleanup)",
+            params: vec![Type::Unknown],
+            returns: Type::Void,
+        },
+        StdlibFunction {
+            name: "exit",
+            category: StdlibCategory::Core,
+            description: "Exit program with status code",
+            params: vec![Type::Int],
+            returns: Type::Void,
+        },
+
+        // ==========================================
+        // MATH FUNCTIONS
+        // ==========================================
+        StdlibFunction {
+            name: "sqrt",
+            category: StdlibCategory::Math,
+            description: "Square root of a number",
+            params: vec![Type::Int],
+            returns: Type::Int,
+        },
+        StdlibFunction {
+            name: "rand",
+            category: StdlibCategory::Math,
+            description: "Random integer in range [0, max)",
+            params: vec![Type::Int],
+            returns: Type::Int,
+        },
+
+        // ==========================================
+        // TIME FUNCTIONS
+        // ==========================================
+        StdlibFunction {
+            name: "time",
+            category: StdlibCategory::Time,
+            description: "Current Unix timestamp in milliseconds",
+            params: vec![],
+            returns: Type::Int,
+        },
+        StdlibFunction {
+            name: "sleep",
+            category: StdlibCategory::Time,
+            description: "Sleep for specified milliseconds",
+            params: vec![Type::Int],
+            returns: Type::Void,
+        },
+
+        // ==========================================
+        // CRYPTO FUNCTIONS (Web3-ready)
+        // ==========================================
+        StdlibFunction {
+            name: "hash",
+            category: StdlibCategory::Crypto,
+            description: "Generic hash function (defaults to keccak256)",
+            params: vec![Type::String],
+            returns: Type::String,
+        },
+        StdlibFunction {
+            name: "keccak",
+            category: StdlibCategory::Crypto,
+            description: "Keccak-256 hash (Ethereum standard)",
+            params: vec![Type::String],
+            returns: Type::String,
+        },
+        StdlibFunction {
+            name: "sha256",
+            category: StdlibCategory::Crypto,
+            description: "SHA-256 hash",
+            params: vec![Type::String],
+            returns: Type::String,
+        },
+        StdlibFunction {
+            name: "generate_keypair",
+            category: StdlibCategory::Crypto,
+            description: "Generate a key pair, returned as (public, private)",
+            params: vec![],
+            returns: Type::Tuple(vec![Type::String, Type::String]),
+        },
+        // Signature schemes beyond secp256k1, for non-EVM chains. Scripts need
+        // the `ed25519` or `bls` cargo feature; WASM builds import them
+        StdlibFunction {
+            name: "ed25519.public_key",
+            category: StdlibCategory::Crypto,
+            description: "ed25519 public key of a 32-byte secret key",
+            params: vec![Type::String],
+            returns: Type::String,
+        },
+        StdlibFunction {
+            name: "ed25519.sign",
+            category: StdlibCategory::Crypto,
+            description: "ed25519 signature of a message (message, secret_key)",
+            params: vec![Type::String, Type::String],
+            returns: Type::String,
+        },
+        StdlibFunction {
+            name: "ed25519.verify",
+            category: StdlibCategory::Crypto,
+            description: "Verify an ed25519 signature (message, signature, public_key)",
+            params: vec![Type::String, Type::String, Type::String],
+            returns: Type::Bool,
+        },
+        StdlibFunction {
+            name: "bls.verify",
+            category: StdlibCategory::Crypto,
+            description: "Verify a BLS12-381 signature (public_key, message, signature)",
+            params: vec![Type::String, Type::String, Type::String],
+            returns: Type::Bool,
+        },
+        StdlibFunction {
+            name: "bls.aggregate_verify",
+            category: StdlibCategory::Crypto,
+            description: "Verify a BLS12-381 signature aggregated over one message (public_keys, message, signature)",
+            params: vec![Type::Unknown, Type::String, Type::String],
+            returns: Type::Bool,
+        },
+        // ==========================================
+        // AI FUNCTIONS (STEP 52)
+        // ==========================================
+        StdlibFunction {
+            name: "ai.generate",
+            category: StdlibCategory::AI,
+            description: "Generate text using AI (prompt in, text out)",
+            params: vec![Type::String],
+            returns: Type::String,
+        },
+        StdlibFunction {
+            name: "ai.embed",
+            category: StdlibCategory::AI,
+            description: "Generate embeddings for text, as an array of floats",
+            params: vec![Type::String],
+            returns: Type::Unknown,
+        },
+        StdlibFunction {
+            name: "ai.classify",
+            category: StdlibCategory::AI,
+            description: "Classify text into categories",
+            params: vec![Type::String],
+            returns: Type::String,
+        },
+
+        // ==========================================
+        // FILE SYSTEM FUNCTIONS (with security)
+        // ==========================================
+        StdlibFunction {
+            name: "fs.read",
+            category: StdlibCategory::FileSystem,
+            description: "Read file contents (requires FS_READ capability)",
+            params: vec![Type::String],
+            returns: Type::String,
+        },
+        StdlibFunction {
+            name: "fs.write",
+            category: StdlibCategory::FileSystem,
+            description: "Write file contents (requires FS_WRITE capability)",
+            params: vec![Type::String, Type::String],
+            returns: Type::Int,
+        },
+        StdlibFunction {
+            name: "fs.delete",
+            category: StdlibCategory::FileSystem,
+            description: "Delete file (requires FS_DELETE capability)",
+            params: vec![Type::String],
+            returns: Type::Int,
+        },
+    ];
+    // Then those written in ASTRIXA
+    functions.extend(crate::bootstrap::functions().iter().map(|f| f.signature()));
+    functions
+}
+
+/// Registry of all host properties, compiled as WASM imports taking no
+/// arguments: `msg.sender` is `env.msg_sender`
+pub static HOST_PROPERTIES: &[HostProperty] = &[
+    HostProperty { object: "chain", name: "id", description: "Chain ID of the network", returns: Type::Int },
+    HostProperty { object: "chain", name: "name", description: "Name of the network", returns: Type::String },
+    HostProperty { object: "msg", name: "sender", description: "Address that sent the current call", returns: Type::Address },
+    HostProperty { object: "msg", name: "value", description: "Value sent with the current call", returns: Type::U256 },
+    HostProperty { object: "msg", name: "data", description: "Calldata of the current call", returns: Type::String },
+    HostProperty { object: "tx", name: "hash", description: "Hash of the current transaction", returns: Type::String },
+    HostProperty { object: "tx", name: "value", description: "Value sent with the current transaction", returns: Type::U256 },
+    HostProperty { object: "tx", name: "timestamp", description: "Timestamp of the current transaction", returns: Type::Int },
 ];
 
 /// Generate human-readable documentation for stdlib
@@ -280,14 +447,30 @@ pub fn generate_docs() -> String {
         for func in funcs {
             docs.push_str(&format!("### `{}()`\n", func.name));
             docs.push_str(&format!("{}\n\n", func.description));
-            docs.push_str(&format!("**Params:** {}\n", func.param_count));
-            docs.push_str(&format!("**Returns:** `{}`\n\n", func.return_type));
+            let params: Vec<String> = func.params.iter().map(doc_type).collect();
+            docs.push_str(&format!("**Params:** ({})\n", params.join(", ")));
+            docs.push_str(&format!("**Returns:** `{}`\n\n", doc_type(&func.returns)));
         }
     }
+
+    docs.push_str("## Host Properties\n\n");
+    for property in HOST_PROPERTIES {
+        docs.push_str(&format!("### `{}.{}`\n", property.object, property.name));
+        docs.push_str(&format!("{}\n\n", property.description));
+        docs.push_str(&format!("**Returns:** `{}`\n\n", doc_type(&property.returns)));
+    }
     
     docs
 }
 
+/// A type as the reference shows it, `Any` where any value is accepted
+fn doc_type(t: &Type) -> String {
+    match t {
+        Type::Unknown => "Any".to_string(),
+        t => t.to_string(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -324,4 +507,42 @@ mod tests {
         assert!(!get_by_category(StdlibCategory::Time).is_empty());
         assert!(!get_by_category(StdlibCategory::Crypto).is_empty());
     }
+
+    #[test]
+    fn test_calls_are_checked_against_signatures() {
+        use crate::lexer::Lexer;
+        use crate::parser::Parser;
+        use crate::typechecker::TypeChecker;
+
+        let check = |source: &str| TypeChecker::new().check(&Parser::new(Lexer::new(source)).parse().unwrap());
+
+        // Return types come from the registry, not an Int default
+        assert!(check("fn main() {\n    let digest = sha256(\"data\") + \"!\"\n    let (public, private) = generate_keypair()\n}\n").is_ok());
+
+        let errors = check("fn main() {\n    let a = pow(2)\n    let b = sqrt(\"nine\")\n    let c = ed25519.verify(\"m\", \"s\", 1)\n}\n").unwrap_err();
+        let codes: Vec<_> = errors.iter().map(|e| e.code).collect();
+        assert_eq!(codes, vec![Some("E0207"), Some("E0208"), Some("E0208")]);
+        assert!(errors[1].message.contains("'sqrt' expects Int, got String"), "{}", errors[1].message);
+    }
+
+    #[test]
+    fn test_paths_resolve_to_the_bare_functions() {
+        use crate::ast::{ExprKind, StmtKind};
+        use crate::lexer::Lexer;
+        use crate::parser::Parser;
+        use crate::typechecker::TypeChecker;
+
+        assert_eq!(get_stdlib_info("print").unwrap().path(), "std::io::print");
+        assert_eq!(resolve_path("std::fs::read").unwrap().name, "fs.read");
+        assert!(resolve_path("std::math::print").is_none());
+
+        let program = Parser::new(Lexer::new("fn main() {\n    std::io::print(\"x\")\n    let s = std::fs::read(\"a\")\n}\n")).parse().unwrap();
+        let StmtKind::Function { body, .. } = &program[0].kind else { panic!("not a function") };
+        assert!(matches!(&body[0].kind, StmtKind::Expression(e) if matches!(&e.kind, ExprKind::Call(name, _) if name == "print")), "{:?}", body[0]);
+        assert!(matches!(&body[1].kind, StmtKind::Let { value, .. } if matches!(&value.kind, ExprKind::ModuleCall(m, f, _) if m == "fs" && f == "read")), "{:?}", body[1]);
+        assert!(TypeChecker::new().check(&program).is_ok());
+
+        let error = Parser::new(Lexer::new("fn main() {\n    std::io::nope()\n}\n")).parse().unwrap_err();
+        assert_eq!(error.code, Some("E0173"));
+    }
 }
@@ -0,0 +1,203 @@
+use std::fmt;
+use crate::ast::{Stmt, StmtKind};
+
+// Storage layouts, and whether an upgrade keeps them.
+//
+// Behind an upgradeable proxy the new implementation reads the storage the
+// old one wrote, slot by slot. A contract's layout gives each state variable
+// the next slot in declaration order, with its annotated type (Int when
+// unannotated, as V1 treats it):
+//
+//   contract Vault {
+//       state owner: Address   // slot 0: Address
+//       state balance          // slot 1: Int
+//
+// An upgrade is safe when every old variable keeps its slot and type; new
+// variables may only follow the last old one. Anything else makes the new
+// code read one variable's data as another's.
+
+/// Where a state variable lives
+#[derive(Debug, Clone, PartialEq)]
+pub struct Slot {
+    pub index: usize,
+    pub name: String,
+    pub ty: String, // As annotated, e.g. "Address"
+}
+
+/// A contract's state variables, in slot order
+#[derive(Debug, Clone, PartialEq)]
+pub struct Layout {
+    pub contract: String,
+    pub slots: Vec<Slot>,
+}
+
+/// The layout of every contract in `stmts`
+pub fn layouts(stmts: &[Stmt]) -> Vec<Layout> {
+    stmts
+        .iter()
+        .filter_map(|stmt| match &stmt.kind {
+            StmtKind::Contract { name, state, state_types, .. } => Some(Layout {
+                contract: name.clone(),
+                slots: state
+                    .iter()
+                    .enumerate()
+                    .map(|(index, var)| Slot {
+                        index,
+                        name: var.clone(),
+                        ty: state_types.get(index).cloned().flatten().unwrap_or_else(|| "Int".to_string()),
+                    })
+                    .collect(),
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+/// One difference between a contract's old and new layouts
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    ContractRemoved,
+    Removed { name: String, slot: usize },
+    Moved { name: String, from: usize, to: usize },
+    Retyped { name: String, slot: usize, from: String, to: String },
+    Inserted { name: String, slot: usize, previous: String }, // Into a slot an old variable used
+    Appended { name: String, slot: usize },
+}
+
+impl Change {
+    /// Whether old data stays readable after this change
+    pub fn is_compatible(&self) -> bool {
+        matches!(self, Change::Appended { .. })
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Change::ContractRemoved => write!(f, "contract removed; its storage is orphaned"),
+            Change::Removed { name, slot } => write!(f, "'{}' (slot {}) removed", name, slot),
+            Change::Moved { name, from, to } => write!(f, "'{}' moved from slot {} to slot {}", name, from, to),
+            Change::Retyped { name, slot, from, to } => {
+                write!(f, "'{}' (slot {}) changed type from {} to {}", name, slot, from, to)
+            }
+            Change::Inserted { name, slot, previous } => {
+                write!(f, "'{}' takes slot {}, which held '{}'", name, slot, previous)
+            }
+            Change::Appended { name, slot } => write!(f, "'{}' added in slot {}", name, slot),
+        }
+    }
+}
+
+/// Every change from `old` to `new` layouts, per contract, in slot order.
+/// Contracts only `new` declares have nothing to keep and are left out.
+pub fn diff(old: &[Layout], new: &[Layout]) -> Vec<(String, Change)> {
+    let mut changes = Vec::new();
+    for before in old {
+        let Some(after) = new.iter().find(|layout| layout.contract == before.contract) else {
+            changes.push((before.contract.clone(), Change::ContractRemoved));
+            continue;
+        };
+
+        for slot in &before.slots {
+            let Some(kept) = after.slots.iter().find(|s| s.name == slot.name) else {
+                changes.push((before.contract.clone(), Change::Removed { name: slot.name.clone(), slot: slot.index }));
+                continue;
+            };
+            // A variable can both move and change type; each breaks it alone
+            if kept.index != slot.index {
+                let change = Change::Moved { name: slot.name.clone(), from: slot.index, to: kept.index };
+                changes.push((before.contract.clone(), change));
+            }
+            if kept.ty != slot.ty {
+                let change = Change::Retyped {
+                    name: slot.name.clone(),
+                    slot: kept.index,
+                    from: slot.ty.clone(),
+                    to: kept.ty.clone(),
+                };
+                changes.push((before.contract.clone(), change));
+            }
+        }
+
+        for slot in after.slots.iter().filter(|s| !before.slots.iter().any(|old| old.name == s.name)) {
+            let change = match before.slots.get(slot.index) {
+                Some(previous) => Change::Inserted { name: slot.name.clone(), slot: slot.index, previous: previous.name.clone() },
+                None => Change::Appended { name: slot.name.clone(), slot: slot.index },
+            };
+            changes.push((before.contract.clone(), change));
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn layouts_of(source: &str) -> Vec<Layout> {
+        layouts(&Parser::new(Lexer::new(source)).parse().unwrap())
+    }
+
+    #[test]
+    fn test_upgrade_changes_are_classified() {
+        let old = layouts_of(r#"
+            contract Vault {
+                state owner: Address
+                state balance
+                state fee
+                state paused: Bool
+            }
+            contract Legacy {
+                state count
+            }
+        "#);
+        let new = layouts_of(r#"
+            contract Vault {
+                state owner: Address
+                state balance: U256
+                state paused: Bool
+                state limit
+                state fee
+                state memo: String
+            }
+        "#);
+
+        assert_eq!(old[0].slots[0], Slot { index: 0, name: "owner".to_string(), ty: "Address".to_string() });
+        assert_eq!(old[0].slots[1].ty, "Int");
+
+        let changes: Vec<String> = diff(&old, &new).iter().map(|(contract, change)| format!("{}: {}", contract, change)).collect();
+        assert_eq!(changes, vec![
+            "Vault: 'balance' (slot 1) changed type from Int to U256",
+            "Vault: 'fee' moved from slot 2 to slot 4",
+            "Vault: 'paused' moved from slot 3 to slot 2",
+            "Vault: 'limit' takes slot 3, which held 'paused'",
+            "Vault: 'memo' added in slot 5",
+            "Legacy: contract removed; its storage is orphaned",
+        ]);
+    }
+
+    #[test]
+    fn test_appending_state_is_compatible() {
+        let old = layouts_of("contract Token {\n    state supply\n}\n");
+        let new = layouts_of("contract Token {\n    state supply\n    state owner: Address\n}\nfn main() {\n}\n");
+
+        let changes = diff(&old, &new);
+        assert_eq!(changes, vec![("Token".to_string(), Change::Appended { name: "owner".to_string(), slot: 1 })]);
+        assert!(changes.iter().all(|(_, change)| change.is_compatible()));
+    }
+
+    #[test]
+    fn test_moving_and_retyping_reports_both() {
+        let old = layouts_of("contract Vault {\n    state owner: Address\n    state fee\n}\n");
+        let new = layouts_of("contract Vault {\n    state fee: U256\n    state owner: Address\n}\n");
+
+        let changes: Vec<String> = diff(&old, &new).iter().map(|(_, change)| change.to_string()).collect();
+        assert_eq!(changes, vec![
+            "'owner' moved from slot 0 to slot 1",
+            "'fee' moved from slot 1 to slot 0",
+            "'fee' (slot 0) changed type from Int to U256",
+        ]);
+    }
+}
@@ -0,0 +1,114 @@
+// Positions in source text, and what counts as a name
+//
+// Spans count lines and columns from 1, a column being a char. Slicing a
+// line wants byte offsets, and the LSP counts UTF-16 code units; these
+// convert between the three, so no tool indexes a line by the wrong one.
+// Past the end of a line they all clamp to its end.
+//
+// Names follow UAX #31: an XID_Start char or `_`, then XID_Continue chars.
+
+pub fn is_ident_start(c: char) -> bool {
+    c == '_' || unicode_ident::is_xid_start(c)
+}
+
+pub fn is_ident_continue(c: char) -> bool {
+    unicode_ident::is_xid_continue(c)
+}
+
+/// Whether `word` is a name, e.g. `total` or `größe` but not `1st`
+pub fn is_ident(word: &str) -> bool {
+    let mut chars = word.chars();
+    chars.next().is_some_and(is_ident_start) && chars.all(is_ident_continue)
+}
+
+/// UTF-16 code units in `text`: its length to the LSP
+pub fn utf16_len(text: &str) -> u32 {
+    text.chars().map(|c| c.len_utf16() as u32).sum()
+}
+
+/// The LSP character of byte `offset` in `line`. An offset inside a char
+/// counts as that char's start.
+pub fn utf16_of_byte(line: &str, offset: usize) -> u32 {
+    line.char_indices()
+        .take_while(|(i, c)| i + c.len_utf8() <= offset)
+        .map(|(_, c)| c.len_utf16() as u32)
+        .sum()
+}
+
+/// The LSP character of 1-based span `column` in `line`
+pub fn utf16_of_column(line: &str, column: usize) -> u32 {
+    line.chars().take(column.saturating_sub(1)).map(|c| c.len_utf16() as u32).sum()
+}
+
+/// The byte offset of LSP `character` in `line`. One inside a surrogate
+/// pair counts as the start of its char.
+pub fn byte_of_utf16(line: &str, character: u32) -> usize {
+    let mut units = 0;
+    for (i, c) in line.char_indices() {
+        units += c.len_utf16() as u32;
+        if units > character {
+            return i;
+        }
+    }
+    line.len()
+}
+
+/// The byte offset of 1-based span `column` in `line`
+pub fn byte_of_column(line: &str, column: usize) -> usize {
+    line.char_indices().nth(column.saturating_sub(1)).map_or(line.len(), |(i, _)| i)
+}
+
+/// The byte offset of 1-based `line` and `column` in `source`, if there is
+/// such a line
+pub fn byte_offset(source: &str, line: usize, column: usize) -> Option<usize> {
+    let start = match line {
+        0 => return None,
+        1 => 0,
+        _ => source.match_indices('\n').nth(line - 2)?.0 + 1,
+    };
+    let text = source[start..].split('\n').next().unwrap_or_default();
+    Some(start + byte_of_column(text, column))
+}
+
+/// The run of `part` chars in `line` around byte `offset`
+pub fn word_at(line: &str, offset: usize, part: impl Fn(char) -> bool) -> &str {
+    let mut offset = offset.min(line.len());
+    while !line.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    let start = line[..offset]
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| part(*c))
+        .last()
+        .map_or(offset, |(i, _)| i);
+    let end = line[offset..].find(|c: char| !part(c)).map_or(line.len(), |i| offset + i);
+    &line[start..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_positions_agree_past_ascii() {
+        // 'é' is two bytes and one unit, '𝔸' four bytes and two units
+        let line = "let é = \"𝔸\" + naïve";
+        let naive = line.find("naïve").unwrap();
+        assert_eq!(naive, 18);
+        assert_eq!(utf16_of_byte(line, naive), 15);
+        assert_eq!(utf16_of_column(line, 15), 15);
+        assert_eq!(byte_of_utf16(line, 15), naive);
+        assert_eq!(byte_of_utf16(line, 10), line.find('𝔸').unwrap(), "the middle of a pair");
+        assert_eq!(utf16_len(line), 20);
+
+        assert_eq!(word_at(line, byte_of_utf16(line, 17), is_ident_continue), "naïve");
+        assert_eq!(word_at(line, line.len(), is_ident_continue), "naïve");
+        assert_eq!(word_at(line, 5, is_ident_continue), "é", "inside the char");
+        assert_eq!(byte_offset("fn f() {\n    let é = 1\n}", 2, 11), Some(20));
+        assert_eq!(byte_offset("one line", 2, 1), None);
+
+        assert!(is_ident("größe") && is_ident("_x1") && is_ident("変数"));
+        assert!(!is_ident("1st") && !is_ident("a-b") && !is_ident("") && !is_ident("😀"));
+    }
+}
@@ -0,0 +1,161 @@
+use std::collections::BTreeMap;
+
+// Time-travel state inspection.
+//
+// While recording, the interpreter logs every contract storage write and
+// every `emit(...)` as a step, tagged with the transaction it happened in: a
+// call into a contract from outside any contract. Version 0 is the state
+// right after the contracts are declared; version N is the state after the
+// first N steps. Any version can be rebuilt from the log, so `astrixa
+// simulate --inspect` can step backwards as easily as forwards and diff two
+// points in time. Values are kept as rendered text, the way they print.
+
+/// Something that happened during a transaction
+#[derive(Debug, Clone, PartialEq)]
+pub enum Record {
+    Write {
+        slot: String, // Contract.var
+        old: String,
+        new: String,
+    },
+    Event {
+        contract: Option<String>,
+        name: String,
+        args: Vec<String>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    pub transaction: Option<usize>, // Index into Timeline::transactions
+    pub record: Record,
+}
+
+/// A storage slot whose value differs between two versions
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    pub slot: String,
+    pub before: String,
+    pub after: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Timeline {
+    pub transactions: Vec<String>,           // Each transaction's call, e.g. Bank.deposit(5)
+    pub initial: BTreeMap<String, String>,   // Contract.var -> value at version 0
+    pub steps: Vec<Step>,
+}
+
+impl Timeline {
+    /// The newest version, reached after every step
+    pub fn latest(&self) -> usize {
+        self.steps.len()
+    }
+
+    /// Every storage slot's value at `version` (clamped to the latest)
+    pub fn state_at(&self, version: usize) -> BTreeMap<String, String> {
+        let mut state = self.initial.clone();
+        for step in &self.steps[..version.min(self.steps.len())] {
+            if let Record::Write { slot, new, .. } = &step.record {
+                state.insert(slot.clone(), new.clone());
+            }
+        }
+        state
+    }
+
+    /// Slots whose value at version `to` differs from version `from`. Either
+    /// may be the earlier one.
+    pub fn diff(&self, from: usize, to: usize) -> Vec<Change> {
+        let before = self.state_at(from);
+        let after = self.state_at(to);
+        after
+            .iter()
+            .filter(|(slot, value)| before.get(*slot) != Some(*value))
+            .map(|(slot, value)| Change {
+                slot: slot.clone(),
+                before: before.get(slot).cloned().unwrap_or_else(|| "null".to_string()),
+                after: value.clone(),
+            })
+            .collect()
+    }
+
+    /// Events emitted between two versions, in order
+    pub fn events_between(&self, from: usize, to: usize) -> Vec<&Step> {
+        let (start, end) = (from.min(to), from.max(to).min(self.steps.len()));
+        self.steps[start..end]
+            .iter()
+            .filter(|step| matches!(step.record, Record::Event { .. }))
+            .collect()
+    }
+
+    pub(crate) fn begin_transaction(&mut self, call: String) -> usize {
+        self.transactions.push(call);
+        self.transactions.len() - 1
+    }
+}
+
+impl std::fmt::Display for Record {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Record::Write { slot, old, new } => write!(f, "{}: {} -> {}", slot, old, new),
+            Record::Event { name, args, .. } => write!(f, "event {}({})", name, args.join(", ")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interpreter::Interpreter;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    #[test]
+    fn test_recorded_timeline_steps_and_diffs() {
+        let source = r#"
+            contract Bank {
+                state balance
+                state owner
+                constructor() {
+                    balance = 0
+                    owner = "alice"
+                }
+                fn deposit(amount) {
+                    balance = balance + amount
+                    emit("Deposit", amount)
+                }
+            }
+            fn main() {
+                Bank.constructor()
+                Bank.deposit(5)
+                Bank.deposit(7)
+            }
+        "#;
+        let program = Parser::new(Lexer::new(source)).parse().unwrap();
+        let mut interpreter = Interpreter::new();
+        interpreter.record();
+        interpreter.run(program).unwrap();
+        let timeline = interpreter.take_timeline().unwrap();
+
+        assert_eq!(timeline.transactions, vec!["Bank.constructor()", "Bank.deposit(5)", "Bank.deposit(7)"]);
+        let shown: Vec<String> = timeline.steps.iter().map(|s| s.record.to_string()).collect();
+        assert_eq!(shown, vec![
+            "Bank.balance: null -> 0",
+            "Bank.owner: null -> \"alice\"",
+            "Bank.balance: 0 -> 5",
+            "event Deposit(5)",
+            "Bank.balance: 5 -> 12",
+            "event Deposit(7)",
+        ]);
+        assert_eq!(timeline.steps[3].transaction, Some(1));
+
+        // Step back to just after the first deposit
+        assert_eq!(timeline.state_at(3)["Bank.balance"], "5");
+        assert_eq!(timeline.state_at(0)["Bank.owner"], "null");
+
+        let changes = timeline.diff(2, timeline.latest());
+        assert_eq!(changes.len(), 1);
+        assert_eq!((changes[0].before.as_str(), changes[0].after.as_str()), ("0", "12"));
+        assert_eq!(timeline.diff(6, 2)[0].after, "0");
+        assert_eq!(timeline.events_between(6, 0).len(), 2);
+    }
+}
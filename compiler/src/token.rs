@@ -6,9 +6,14 @@ pub enum Token {
     If,
     Else,
     While,
+    For,
     Panic,
+    Require,
     Import,
     Export,
+    Contract,
+    Async,
+    Await,
     True,
     False,
 
@@ -21,10 +26,18 @@ pub enum Token {
     RParen,
     LBrace,
     RBrace,
+    LBracket,
+    RBracket,
     Colon,
+    PathSep, // '::' between the segments of a path: std::io::print
+    Semicolon, // Optional statement terminator
     Comma,
     Arrow,
+    FatArrow, // '=>' between a match pattern and its body
+    Question, // '?' after a value: propagate None or Err
     Dot,
+    DotDot, // '..' between range bounds: 0..10
+    Hash, // '#' opening an attribute: #[test]
 
     Plus,
     Minus,
@@ -39,6 +52,188 @@ pub enum Token {
     LessEqual,
     EqualEqual,
     NotEqual,
+    AndAnd, // '&&': true when both sides are, skipping the right when the left is false
+    OrOr,   // '||': true when either side is, skipping the right when the left is true
 
     EOF,
 }
+
+/// A token without its payload: cheap to copy, for the parser to look
+/// ahead and compare with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Fn,
+    Let,
+    Return,
+    If,
+    Else,
+    While,
+    For,
+    Panic,
+    Require,
+    Import,
+    Export,
+    Contract,
+    Async,
+    Await,
+    True,
+    False,
+    Identifier,
+    Number,
+    Float,
+    String,
+    LParen,
+    RParen,
+    LBrace,
+    RBrace,
+    LBracket,
+    RBracket,
+    Colon,
+    PathSep,
+    Semicolon,
+    Comma,
+    Arrow,
+    FatArrow,
+    Question,
+    Dot,
+    DotDot,
+    Hash,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
+    Assign,
+    Greater,
+    Less,
+    GreaterEqual,
+    LessEqual,
+    EqualEqual,
+    NotEqual,
+    AndAnd,
+    OrOr,
+    EOF,
+}
+
+impl Token {
+    pub fn kind(&self) -> TokenKind {
+        match self {
+            Token::Fn => TokenKind::Fn,
+            Token::Let => TokenKind::Let,
+            Token::Return => TokenKind::Return,
+            Token::If => TokenKind::If,
+            Token::Else => TokenKind::Else,
+            Token::While => TokenKind::While,
+            Token::For => TokenKind::For,
+            Token::Panic => TokenKind::Panic,
+            Token::Require => TokenKind::Require,
+            Token::Import => TokenKind::Import,
+            Token::Export => TokenKind::Export,
+            Token::Contract => TokenKind::Contract,
+            Token::Async => TokenKind::Async,
+            Token::Await => TokenKind::Await,
+            Token::True => TokenKind::True,
+            Token::False => TokenKind::False,
+            Token::Identifier(_) => TokenKind::Identifier,
+            Token::Number(_) => TokenKind::Number,
+            Token::Float(_) => TokenKind::Float,
+            Token::String(_) => TokenKind::String,
+            Token::LParen => TokenKind::LParen,
+            Token::RParen => TokenKind::RParen,
+            Token::LBrace => TokenKind::LBrace,
+            Token::RBrace => TokenKind::RBrace,
+            Token::LBracket => TokenKind::LBracket,
+            Token::RBracket => TokenKind::RBracket,
+            Token::Colon => TokenKind::Colon,
+            Token::PathSep => TokenKind::PathSep,
+            Token::Semicolon => TokenKind::Semicolon,
+            Token::Comma => TokenKind::Comma,
+            Token::Arrow => TokenKind::Arrow,
+            Token::FatArrow => TokenKind::FatArrow,
+            Token::Question => TokenKind::Question,
+            Token::Dot => TokenKind::Dot,
+            Token::DotDot => TokenKind::DotDot,
+            Token::Hash => TokenKind::Hash,
+            Token::Plus => TokenKind::Plus,
+            Token::Minus => TokenKind::Minus,
+            Token::Star => TokenKind::Star,
+            Token::Slash => TokenKind::Slash,
+            Token::Percent => TokenKind::Percent,
+            Token::Assign => TokenKind::Assign,
+            Token::Greater => TokenKind::Greater,
+            Token::Less => TokenKind::Less,
+            Token::GreaterEqual => TokenKind::GreaterEqual,
+            Token::LessEqual => TokenKind::LessEqual,
+            Token::EqualEqual => TokenKind::EqualEqual,
+            Token::NotEqual => TokenKind::NotEqual,
+            Token::AndAnd => TokenKind::AndAnd,
+            Token::OrOr => TokenKind::OrOr,
+            Token::EOF => TokenKind::EOF,
+        }
+    }
+}
+
+
+/// Reserved words and the tokens the lexer produces for them.
+/// Editor grammars are generated from this table, so keep it the single source.
+pub const KEYWORDS: &[(&str, Token)] = &[
+    ("fn", Token::Fn),
+    ("let", Token::Let),
+    ("return", Token::Return),
+    ("if", Token::If),
+    ("else", Token::Else),
+    ("while", Token::While),
+    ("for", Token::For),
+    ("panic", Token::Panic),   // STEP 48: Panic keyword
+    ("require", Token::Require),
+    ("import", Token::Import), // STEP 49: Import keyword
+    ("export", Token::Export), // STEP 49: Export keyword
+    ("contract", Token::Contract),
+    ("async", Token::Async),
+    ("await", Token::Await),
+    ("true", Token::True),
+    ("false", Token::False),
+];
+
+/// Words the parser treats as keywords only in certain positions
+pub const CONTEXTUAL_KEYWORDS: &[&str] = &["state", "constructor", "as", "in", "trait", "impl", "type", "match", "mut", "defer"];
+
+/// Words set aside for syntax the language may grow into, so claiming them
+/// later breaks no one
+pub const FUTURE_KEYWORDS: &[&str] = &[
+    "break", "const", "continue", "enum", "event", "interface", "loop", "mod", "pub", "static", "struct", "use", "where",
+    "yield",
+];
+
+/// Builtin functions named by reserved words, which calls may still use
+pub const RESERVED_BUILTINS: &[&str] = &["type"];
+
+/// Whether `word` is reserved and so not usable as a name: the contextual
+/// keywords and the future ones. Editions before
+/// `edition::RESERVED_NAMES` only warn about such names.
+pub fn is_reserved(word: &str) -> bool {
+    CONTEXTUAL_KEYWORDS.contains(&word) || FUTURE_KEYWORDS.contains(&word)
+}
+
+/// Operator spellings, longest first so prefixes don't shadow them
+pub const OPERATORS: &[(&str, Token)] = &[
+    ("..", Token::DotDot),
+    ("::", Token::PathSep),
+    ("->", Token::Arrow),
+    ("=>", Token::FatArrow),
+    ("==", Token::EqualEqual),
+    ("!=", Token::NotEqual),
+    ("<=", Token::LessEqual),
+    (">=", Token::GreaterEqual),
+    ("&&", Token::AndAnd),
+    ("||", Token::OrOr),
+    ("+", Token::Plus),
+    ("-", Token::Minus),
+    ("*", Token::Star),
+    ("/", Token::Slash),
+    ("%", Token::Percent),
+    ("=", Token::Assign),
+    ("<", Token::Less),
+    (">", Token::Greater),
+    ("?", Token::Question),
+];
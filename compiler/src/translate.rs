@@ -0,0 +1,423 @@
+use std::collections::{HashMap, HashSet};
+use crate::bytecode::{BytecodeFunction, BytecodeModule, Instruction, OpCode};
+use crate::ir::{IRFunction, IRInstr, IRModule};
+use crate::lowering::call_instruction;
+use crate::messages;
+
+// Bytecode to IR translation.
+//
+// Packages can be distributed as compiled bytecode (.axb) without their
+// source. Translating that bytecode into IR lets the usual optimizer and
+// WASM backend build it, the same as if it had been lowered from the AST.
+//
+// The VM binds variables by name; the IR uses numbered local slots. Each
+// function's parameters take the first slots, in order, and every other
+// name the function stores to gets the next free slot. Names that are only
+// ever loaded fall back to `LoadVar`, as lowering does for unknown names.
+//
+// `to_bytecode` goes the other way, one lowered function at a time, so a
+// program compiled from source can also run on the VM. Slots become names
+// again, and the few IR instructions the VM has no opcode for become short
+// sequences of ones it has.
+
+/// Translate a whole bytecode module into an IR module
+pub fn translate(module: &BytecodeModule) -> Result<IRModule, String> {
+    let mut ir = IRModule::new();
+    for function in &module.functions {
+        let translated = translate_function(function, module)
+            .map_err(|e| format!("In function '{}': {}", function.name, e))?;
+        ir.add_function(translated);
+    }
+    crate::bootstrap::link(&mut ir);
+    Ok(ir)
+}
+
+fn translate_function(function: &BytecodeFunction, module: &BytecodeModule) -> Result<IRFunction, String> {
+    let instrs = &function.instructions;
+
+    let mut slots: HashMap<&str, u32> = HashMap::new();
+    for param in &function.params {
+        let next = slots.len() as u32;
+        slots.entry(param.as_str()).or_insert(next);
+    }
+    for instr in instrs {
+        if instr.opcode == OpCode::StoreVar {
+            let name = operand(instr)?;
+            let next = slots.len() as u32;
+            slots.entry(name).or_insert(next);
+        }
+    }
+
+    let targets: HashSet<usize> = instrs
+        .iter()
+        .filter(|i| matches!(i.opcode, OpCode::Jump | OpCode::JumpIfFalse))
+        .map(jump_target)
+        .collect::<Result<_, _>>()?;
+
+    // starts[i] is where bytecode instruction i begins in the IR, so jump
+    // targets can be remapped once every instruction has been emitted
+    let mut out = IRFunction::new(function.name.clone());
+    out.param_count = function.params.len();
+    out.span = function.span.unwrap_or_default();
+    let mut starts = Vec::with_capacity(instrs.len() + 1);
+    let mut i = 0;
+    while i < instrs.len() {
+        let instr = &instrs[i];
+        starts.push(out.instructions.len());
+
+        match instr.opcode {
+            OpCode::LoadConst => out.add_instruction(constant(operand(instr)?)?),
+            OpCode::LoadVar => {
+                let name = operand(instr)?;
+                out.add_instruction(match slots.get(name) {
+                    Some(slot) => IRInstr::LoadLocal(*slot),
+                    None => IRInstr::LoadVar(name.to_string()),
+                });
+            }
+            OpCode::StoreVar => {
+                // The VM stores without popping; the compiler almost always
+                // follows with a Pop, which the IR store already does
+                let slot = slots[operand(instr)?];
+                let popped = instrs.get(i + 1).is_some_and(|next| next.opcode == OpCode::Pop);
+                if popped && !targets.contains(&(i + 1)) {
+                    out.add_instruction(IRInstr::StoreLocal(slot));
+                    starts.push(out.instructions.len());
+                    i += 2;
+                    continue;
+                }
+                out.add_instruction(IRInstr::Dup);
+                out.add_instruction(IRInstr::StoreLocal(slot));
+            }
+            OpCode::Add => out.add_instruction(IRInstr::Add),
+            OpCode::Sub => out.add_instruction(IRInstr::Sub),
+            OpCode::Mul => out.add_instruction(IRInstr::Mul),
+            OpCode::Div => out.add_instruction(IRInstr::Div),
+            OpCode::Mod => out.add_instruction(IRInstr::Mod),
+            OpCode::Equal => out.add_instruction(IRInstr::Eq),
+            OpCode::NotEqual => out.add_instruction(IRInstr::Ne),
+            OpCode::Greater => out.add_instruction(IRInstr::Gt),
+            OpCode::Less => out.add_instruction(IRInstr::Lt),
+            OpCode::GreaterEqual => out.add_instruction(IRInstr::Ge),
+            OpCode::LessEqual => out.add_instruction(IRInstr::Le),
+            // Targets are bytecode indices until remapped below
+            OpCode::Jump => out.add_instruction(IRInstr::Jump(jump_target(instr)?)),
+            OpCode::JumpIfFalse => out.add_instruction(IRInstr::JumpIfFalse(jump_target(instr)?)),
+            OpCode::Call => {
+                let name = operand(instr)?;
+                let arg_count = match module.find_function(name) {
+                    Some(callee) => callee.params.len(),
+                    None => crate::prelude::get(name).map_or(0, |f| f.params.len()),
+                };
+                let call = call_instruction(name, arg_count);
+                if matches!(call, IRInstr::Call(..)) && module.find_function(name).is_none() && !crate::bootstrap::defines(name) {
+                    return Err(format!("call to '{}', which is neither in this module nor in the standard library", name));
+                }
+                out.add_instruction(call);
+            }
+            OpCode::Return => out.add_instruction(IRInstr::Return),
+            OpCode::Print => out.add_instruction(IRInstr::CallStd("print".to_string())),
+            OpCode::Pop => out.add_instruction(IRInstr::Pop),
+            OpCode::Array | OpCode::Index => {
+                return Err(format!("{:?} at instruction {} has no WASM equivalent yet", instr.opcode, i));
+            }
+        }
+        i += 1;
+    }
+    starts.push(out.instructions.len());
+
+    for instr in out.instructions.iter_mut() {
+        if let IRInstr::Jump(target) | IRInstr::JumpIfFalse(target) = instr {
+            *target = *starts
+                .get(*target)
+                .ok_or_else(|| format!("jump to {}, past the end of the function", target))?;
+        }
+    }
+
+    out.local_count = slots.len();
+    Ok(out)
+}
+
+/// Translate a lowered module back into bytecode, as `build --target=native`
+/// writes it
+pub fn to_bytecode_module(module: &IRModule) -> Result<BytecodeModule, String> {
+    // The task scheduler is the interpreter's; the VM has none to run
+    // async functions on, and running them eagerly would reorder output
+    if let Some(function) = module.functions.iter().find(|f| f.is_async) {
+        return Err(messages::render("E0502", &[&function.name, &function.span]));
+    }
+    let functions = module
+        .functions
+        .iter()
+        .map(|function| to_bytecode(function).map_err(|e| format!("In function '{}': {}", function.name, e)))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(BytecodeModule { functions })
+}
+
+/// Translate a lowered function back into bytecode
+pub fn to_bytecode(function: &IRFunction) -> Result<BytecodeFunction, String> {
+    let instrs = &function.instructions;
+    // Lowering's hidden locals, such as a for loop's `i.end`, would read
+    // as property lookups in the VM, so their dots become `#`, which no
+    // name the VM reads can contain
+    let name = |slot: &u32| -> String {
+        let named = function.local_names.get(*slot as usize);
+        let name = match named {
+            Some(n) if function.local_names.iter().filter(|other| *other == n).count() == 1 => n.clone(),
+            Some(n) => format!("{}#{}", n, slot),
+            None => format!("#{}", slot),
+        };
+        name.replace('.', "#")
+    };
+
+    // Where each IR instruction starts in the bytecode, for jump targets
+    let width = |instr: &IRInstr| match instr {
+        IRInstr::StoreLocal(_) | IRInstr::Dup => 2,
+        IRInstr::Not => 4,
+        IRInstr::Line(..) | IRInstr::Nop => 0,
+        _ => 1,
+    };
+    let mut starts = Vec::with_capacity(instrs.len() + 1);
+    let mut at = 0;
+    for instr in instrs {
+        starts.push(at);
+        at += width(instr);
+    }
+    starts.push(at);
+    let target = |i: usize| starts.get(i).copied().ok_or_else(|| format!("jump to {}, past the end of the function", i));
+
+    let mut out = Vec::with_capacity(at);
+    for (i, instr) in instrs.iter().enumerate() {
+        match instr {
+            IRInstr::LoadConstInt(n) => out.push(Instruction::const_instr(n.to_string())),
+            IRInstr::LoadConstBool(b) => out.push(Instruction::const_instr(b.to_string())),
+            IRInstr::LoadConstString(text) => out.push(Instruction::const_instr(format!("\"{}\"", text))),
+            IRInstr::LoadLocal(slot) => out.push(Instruction::var_instr(name(slot))),
+            IRInstr::LoadVar(var) => out.push(Instruction::var_instr(var.clone())),
+            // The VM stores without popping
+            IRInstr::StoreLocal(slot) => {
+                out.push(Instruction::store_var(name(slot)));
+                out.push(Instruction::new(OpCode::Pop, None));
+            }
+            IRInstr::Dup => {
+                out.push(Instruction::store_var("#dup".to_string()));
+                out.push(Instruction::var_instr("#dup".to_string()));
+            }
+            IRInstr::Add => out.push(Instruction::new(OpCode::Add, None)),
+            IRInstr::Sub => out.push(Instruction::new(OpCode::Sub, None)),
+            IRInstr::Mul => out.push(Instruction::new(OpCode::Mul, None)),
+            IRInstr::Div => out.push(Instruction::new(OpCode::Div, None)),
+            IRInstr::Mod => out.push(Instruction::new(OpCode::Mod, None)),
+            IRInstr::Eq => out.push(Instruction::new(OpCode::Equal, None)),
+            IRInstr::Ne => out.push(Instruction::new(OpCode::NotEqual, None)),
+            IRInstr::Lt => out.push(Instruction::new(OpCode::Less, None)),
+            IRInstr::Le => out.push(Instruction::new(OpCode::LessEqual, None)),
+            IRInstr::Gt => out.push(Instruction::new(OpCode::Greater, None)),
+            IRInstr::Ge => out.push(Instruction::new(OpCode::GreaterEqual, None)),
+            // Branch to push the opposite constant
+            IRInstr::Not => {
+                let here = starts[i];
+                out.push(Instruction::jump_if_false(here + 3));
+                out.push(Instruction::const_instr("false".to_string()));
+                out.push(Instruction::jump_instr(here + 4));
+                out.push(Instruction::const_instr("true".to_string()));
+            }
+            IRInstr::Jump(to) => out.push(Instruction::jump_instr(target(*to)?)),
+            IRInstr::JumpIfFalse(to) => out.push(Instruction::jump_if_false(target(*to)?)),
+            IRInstr::Call(callee, _) => out.push(Instruction::call_instr(callee.clone())),
+            IRInstr::CallStd(callee) => out.push(Instruction::call_instr(callee.clone())),
+            IRInstr::Return => out.push(Instruction::new(OpCode::Return, None)),
+            IRInstr::Pop => out.push(Instruction::new(OpCode::Pop, None)),
+            IRInstr::Line(..) | IRInstr::Nop => {}
+            other => return Err(format!("{:?} at instruction {} has no bytecode equivalent yet", other, i)),
+        }
+    }
+
+    Ok(BytecodeFunction {
+        name: function.name.clone(),
+        params: (0..function.param_count as u32).map(|slot| name(&slot)).collect(),
+        instructions: out,
+        span: Some(function.span),
+    })
+}
+
+fn operand(instr: &Instruction) -> Result<&str, String> {
+    instr
+        .operand
+        .as_deref()
+        .ok_or_else(|| format!("{:?} is missing its operand", instr.opcode))
+}
+
+fn jump_target(instr: &Instruction) -> Result<usize, String> {
+    let text = operand(instr)?;
+    text.parse().map_err(|_| format!("invalid jump target '{}'", text))
+}
+
+/// The IR constant for a `LoadConst` operand, in the VM's spelling
+fn constant(text: &str) -> Result<IRInstr, String> {
+    match text {
+        "null" => Ok(IRInstr::LoadConstInt(0)),
+        "true" => Ok(IRInstr::LoadConstBool(true)),
+        "false" => Ok(IRInstr::LoadConstBool(false)),
+        _ if text.len() >= 2 && text.starts_with('"') && text.ends_with('"') => {
+            Ok(IRInstr::LoadConstString(text[1..text.len() - 1].to_string()))
+        }
+        _ => text
+            .parse::<i64>()
+            .map(IRInstr::LoadConstInt)
+            .or_else(|_| text.parse::<f64>().map(IRInstr::LoadConstFloat))
+            .map_err(|_| format!("unknown constant {}", text)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Span;
+    use crate::codegen::wasm::generate_wasm_module;
+
+    fn instr(opcode: OpCode, operand: &str) -> Instruction {
+        Instruction::new(opcode, (!operand.is_empty()).then(|| operand.to_string()))
+    }
+
+    #[test]
+    fn test_translate_bytecode_artifact_to_wasm() {
+        // fn countdown(n) { while n > 0 { n = n - 1 } return n }
+        // fn main() { print(countdown(3)) }
+        let module = BytecodeModule {
+            functions: vec![
+                BytecodeFunction {
+                    name: "countdown".to_string(),
+                    params: vec!["n".to_string()],
+                    instructions: vec![
+                        instr(OpCode::LoadVar, "n"),
+                        instr(OpCode::LoadConst, "0"),
+                        instr(OpCode::Greater, ""),
+                        instr(OpCode::JumpIfFalse, "10"),
+                        instr(OpCode::LoadVar, "n"),
+                        instr(OpCode::LoadConst, "1"),
+                        instr(OpCode::Sub, ""),
+                        instr(OpCode::StoreVar, "n"),
+                        instr(OpCode::Pop, ""),
+                        instr(OpCode::Jump, "0"),
+                        instr(OpCode::LoadVar, "n"),
+                        instr(OpCode::Return, ""),
+                    ],
+                    span: Some(Span { start_line: 1, start_column: 1, end_line: 3, end_column: 2 }),
+                },
+                BytecodeFunction {
+                    name: "main".to_string(),
+                    params: vec![],
+                    instructions: vec![
+                        instr(OpCode::LoadConst, "3"),
+                        instr(OpCode::Call, "countdown"),
+                        instr(OpCode::Call, "print"),
+                        instr(OpCode::Pop, ""),
+                        instr(OpCode::LoadConst, "null"),
+                        instr(OpCode::Return, ""),
+                    ],
+                    span: Some(Span { start_line: 5, start_column: 1, end_line: 7, end_column: 2 }),
+                },
+            ],
+        };
+
+        // Round-trip through the .axb encoding, as a registry download would
+        let module = BytecodeModule::from_bytes(&module.to_bytes()).unwrap();
+        let ir = translate(&module).unwrap();
+
+        let countdown = ir.find_function("countdown").unwrap();
+        assert_eq!((countdown.param_count, countdown.local_count), (1, 1));
+        assert_eq!(countdown.span.start_line, 1);
+        assert_eq!(
+            countdown.instructions,
+            vec![
+                IRInstr::LoadLocal(0),
+                IRInstr::LoadConstInt(0),
+                IRInstr::Gt,
+                IRInstr::JumpIfFalse(9),
+                IRInstr::LoadLocal(0),
+                IRInstr::LoadConstInt(1),
+                IRInstr::Sub,
+                IRInstr::StoreLocal(0),
+                IRInstr::Jump(0),
+                IRInstr::LoadLocal(0),
+                IRInstr::Return,
+            ]
+        );
+
+        let main = ir.find_function("main").unwrap();
+        assert_eq!(main.instructions[1], IRInstr::Call("countdown".to_string(), 1));
+        assert_eq!(main.instructions[2], IRInstr::CallStd("print".to_string()));
+
+        let wat = generate_wasm_module(&ir);
+        assert!(wat.contains("(func $countdown (param i64) (result i64)"));
+        assert!(wat.contains("call $countdown"));
+
+        // Stripped, the spans are gone and the artifact smaller
+        let mut stripped = module.clone();
+        stripped.strip();
+        assert!(stripped.to_bytes().len() < module.to_bytes().len());
+        assert_eq!(BytecodeModule::from_bytes(&stripped.to_bytes()).unwrap().functions[1].span, None);
+    }
+
+    #[test]
+    fn test_malformed_bytecode_is_rejected() {
+        let module = BytecodeModule {
+            functions: vec![BytecodeFunction {
+                name: "main".to_string(),
+                params: vec![],
+                instructions: vec![instr(OpCode::Call, "missing"), instr(OpCode::Return, "")],
+                span: None,
+            }],
+        };
+        let err = translate(&module).unwrap_err();
+        assert!(err.starts_with("In function 'main': call to 'missing'"));
+
+        let bytes = module.to_bytes();
+        assert!(BytecodeModule::from_bytes(&bytes[..bytes.len() - 1]).unwrap_err().starts_with("Truncated"));
+        assert!(BytecodeModule::from_bytes(b"\0asm\x01\0\0\0").unwrap_err().contains("bad magic"));
+
+        // Each string is pooled once; an index past the pool is caught
+        assert_eq!(bytes.windows(4).filter(|w| w == b"main").count(), 1);
+        let mut bad = bytes.clone();
+        let operand = bad.len() - 10;
+        bad[operand] = 9;
+        assert!(BytecodeModule::from_bytes(&bad).unwrap_err().contains("out of range"));
+
+        // Only the current format version loads
+        let mut v2 = bytes.clone();
+        v2[4] = 2;
+        assert!(BytecodeModule::from_bytes(&v2).unwrap_err().contains("version 2"));
+
+        // Each opcode has the operand it needs, or none, before the VM runs it
+        let with = |instructions| BytecodeModule { functions: vec![BytecodeFunction { instructions, ..module.functions[0].clone() }] };
+        let loads = |instructions| BytecodeModule::from_bytes(&with(instructions).to_bytes());
+        assert!(loads(vec![instr(OpCode::LoadVar, "")]).unwrap_err().contains("LoadVar in function 'main' has no operand"));
+        assert!(loads(vec![instr(OpCode::Jump, "end")]).unwrap_err().contains("not 'end'"));
+        assert!(loads(vec![instr(OpCode::Return, "x")]).unwrap_err().contains("takes no operand"));
+        assert!(loads(vec![instr(OpCode::Array, "2"), instr(OpCode::Return, "")]).is_ok());
+    }
+
+    #[test]
+    fn test_native_builds_run_loops_and_slices() {
+        // As `build --target=native` writes it and `run` loads it
+        let source = "fn main() {\n    let word = \"astrixa\"\n    let mut total = 0\n    for i in 0..3 {\n        total = total + i\n    }\n    for i in 0..2 {\n        print(word[i..i + 2])\n    }\n    return total\n}\n";
+        let program = crate::parser::Parser::new(crate::lexer::Lexer::new(source)).parse().unwrap();
+        crate::typechecker::TypeChecker::new().check(&program).unwrap();
+        let module = to_bytecode_module(&crate::lowering::lower_with(&program, true, true)).unwrap();
+        let module = BytecodeModule::from_bytes(&module.to_bytes()).unwrap();
+
+        let mut vm = crate::vm::VM::new();
+        vm.capture_output();
+        let result = vm.run_module(&module, "main").unwrap();
+        assert!(matches!(result, crate::interpreter::Value::Number(3)));
+        assert_eq!(vm.take_output(), vec!["as", "st"]);
+    }
+
+    #[test]
+    fn test_async_functions_are_refused() {
+        let source = "async fn double(x) {\n    return x * 2\n}\nfn main() {\n    return await double(21)\n}\n";
+        let program = crate::parser::Parser::new(crate::lexer::Lexer::new(source)).parse().unwrap();
+        let error = to_bytecode_module(&crate::lowering::lower(&program)).unwrap_err();
+        assert!(error.starts_with("'double' (line 1, column 1) is async"), "{}", error);
+    }
+}
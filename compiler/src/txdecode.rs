@@ -0,0 +1,186 @@
+use crate::abi::ContractAbi;
+use crate::defi::abi::{self as defi_abi, Function};
+use crate::interpreter::Value;
+use crate::logs::{hex, unhex};
+use crate::messages;
+use crate::multicall::FunctionSignature;
+use crate::u256::U256;
+
+// Transactions read back as the calls they make, for `astrixa decode-tx`.
+//
+// Calldata starts with the 4-byte selector of the function it calls. The
+// selector is looked up in every function the known ABIs declare, the
+// project's contracts first and then the standard library's defi bindings,
+// and the arguments are decoded by that function's parameter types:
+//
+//   $ astrixa decode-tx 0xa9059cbb000000...
+//   erc20.transfer
+//      to: address = 0x00000000000000000000000000000000000000aa
+//      value: uint256 = 1000000
+//
+// Given a transaction hash instead, the transaction is fetched from the node
+// at ASTRIXA_RPC_URL, which also gives the contract called and the value
+// sent with the call.
+
+/// What `decode-tx` was given
+#[derive(Debug, Clone, PartialEq)]
+pub enum Input {
+    Hash(String), // A transaction to fetch
+    Calldata(Vec<u8>),
+}
+
+impl Input {
+    /// A 32-byte hex string is a transaction hash, any other hex calldata
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+        let bytes = unhex(text).map_err(|_| messages::render("E0476", &[&text]))?;
+        match bytes.len() {
+            32 => Ok(Input::Hash(format!("0x{}", hex(&bytes)))),
+            n if n >= 4 => Ok(Input::Calldata(bytes)),
+            _ => Err(messages::render("E0476", &[&text])),
+        }
+    }
+}
+
+/// What a transaction sent: the contract, the value in wei and the calldata
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transaction {
+    pub to: Option<String>, // None for a deployment
+    pub value: U256,
+    pub input: Vec<u8>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl crate::multicall::RpcCallSource {
+    /// The transaction with `hash`, by `eth_getTransactionByHash`
+    pub fn transaction(&self, hash: &str) -> Result<Transaction, String> {
+        let request = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "eth_getTransactionByHash",
+            "params": [hash],
+        });
+        let not_found = |reason: &str| messages::render("E0479", &[&hash, &reason]);
+        let response = self.post(&request).map_err(|e| not_found(&e))?;
+        if let Some(error) = response.get("error") {
+            return Err(not_found(error["message"].as_str().unwrap_or("request failed")));
+        }
+        let tx = &response["result"];
+        if tx.is_null() {
+            return Err(not_found("the node does not know it"));
+        }
+        let value = tx["value"].as_str().unwrap_or("0x0");
+        Ok(Transaction {
+            to: tx["to"].as_str().map(str::to_string),
+            value: crate::u256::arith::parse(value).ok_or_else(|| not_found("its value is not a number"))?,
+            input: unhex(tx["input"].as_str().unwrap_or("0x")).map_err(|e| not_found(&e))?,
+        })
+    }
+}
+
+/// Every function a selector may call, each with the name of the contract
+/// or defi module declaring it: `contracts` first, then the bindings
+pub fn known_functions(contracts: &[ContractAbi]) -> Vec<(String, Function)> {
+    let own = contracts
+        .iter()
+        .flat_map(|contract| defi_abi::abi_functions(&contract.entries).into_iter().map(|f| (contract.name.clone(), f)));
+    let bindings = defi_abi::protocols().flat_map(|module| defi_abi::functions(module).into_iter().map(move |f| (module.to_string(), f)));
+    own.chain(bindings).collect()
+}
+
+/// A call decoded against the ABI declaring its function
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedCall {
+    pub abi: String,      // The contract or defi module, `erc20`
+    pub function: String, // As the ABI declares it, `transfer`
+    pub args: Vec<(String, String, Value)>, // Each argument's name, type and value
+}
+
+/// Decode `calldata` with the first of `known` its selector matches
+pub fn decode_call(calldata: &[u8], known: &[(String, Function)]) -> Result<DecodedCall, String> {
+    let selector = calldata.get(..4).ok_or_else(|| messages::render("E0476", &[&format!("0x{}", hex(calldata))]))?;
+    let (abi, function) = known
+        .iter()
+        .find(|(_, function)| signature(function).selector() == selector)
+        .ok_or_else(|| messages::render("E0477", &[&hex(selector)]))?;
+
+    let qualified = format!("{}.{}", abi, function.abi_name);
+    let values = signature(function).decode_inputs(calldata).map_err(|e| messages::render("E0478", &[&qualified, &e]))?;
+    let args = values
+        .into_iter()
+        .enumerate()
+        .map(|(i, value)| {
+            let name = function.params.get(i).filter(|name| !name.is_empty()).cloned().unwrap_or_else(|| format!("arg{}", i));
+            (name, function.inputs[i].clone(), value)
+        })
+        .collect();
+    Ok(DecodedCall { abi: abi.clone(), function: function.abi_name.clone(), args })
+}
+
+fn signature(function: &Function) -> FunctionSignature {
+    FunctionSignature { name: function.abi_name.clone(), inputs: function.inputs.clone(), outputs: Vec::new() }
+}
+
+/// A decoded argument as it would be written in a script
+pub fn render(value: &Value) -> String {
+    match value {
+        Value::String(s) if s.starts_with("0x") => s.clone(),
+        Value::String(s) => format!("\"{}\"", s),
+        Value::Address(address) => address.clone(),
+        Value::U256(n) => n.to_string(),
+        Value::Number(n) => n.to_string(),
+        Value::Bool(b) => b.to_string(),
+        Value::Array(items) => format!("[{}]", items.borrow().iter().map(render).collect::<Vec<_>>().join(", ")),
+        Value::Tuple(items) => format!("({})", items.iter().map(render).collect::<Vec<_>>().join(", ")),
+        other => format!("{:?}", other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    use crate::typechecker::TypeChecker;
+
+    fn word(tail: &str) -> String {
+        format!("{:0>64}", tail)
+    }
+
+    #[test]
+    fn test_calldata_decodes_against_known_abis() {
+        let source = r#"
+            contract Vault {
+                fn deposit(owner: Address, amount: U256) {
+                    return amount
+                }
+            }
+        "#;
+        let ast = Parser::new(Lexer::new(source)).parse().unwrap();
+        let mut checker = TypeChecker::new();
+        checker.check(&ast).unwrap();
+        let known = known_functions(&crate::abi::contract_abis(&ast, checker.functions()).unwrap());
+
+        // A project contract's method
+        let deposit = FunctionSignature { name: "deposit".to_string(), inputs: vec!["address".to_string(), "uint256".to_string()], outputs: Vec::new() };
+        let data = format!("0x{}{}{}", hex(&deposit.selector()), word("aa"), word("64"));
+        let Input::Calldata(calldata) = Input::parse(&data).unwrap() else { panic!() };
+        let call = decode_call(&calldata, &known).unwrap();
+        assert_eq!((call.abi.as_str(), call.function.as_str()), ("Vault", "deposit"));
+        assert_eq!(call.args[0], ("owner".to_string(), "address".to_string(), Value::Address(format!("0x{:0>40}", "aa"))));
+        assert_eq!(render(&call.args[1].2), "100");
+
+        // A binding's, with a dynamic array after the head
+        let data = format!("0x38ed1739{}{}{}{}{}{}{}{}", word("64"), word("5a"), word("a0"), word("aa"), word("1"), word("2"), word("c0"), word("e0"));
+        let call = decode_call(&unhex(&data).unwrap(), &known).unwrap();
+        assert_eq!(format!("{}.{}", call.abi, call.function), "uniswap.swapExactTokensForTokens");
+        let names: Vec<&str> = call.args.iter().map(|(name, _, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["amountIn", "amountOutMin", "path", "to", "deadline"]);
+        assert_eq!(render(&call.args[2].2), format!("[0x{:0>40}, 0x{:0>40}]", "c0", "e0"));
+
+        assert_eq!(decode_call(&[0xde, 0xad, 0xbe, 0xef], &known), Err(messages::render("E0477", &[&"deadbeef"])));
+        assert!(decode_call(&unhex("0xa9059cbb").unwrap(), &known).unwrap_err().contains("erc20.transfer"));
+        assert!(matches!(Input::parse(&format!("0x{}", word("1"))), Ok(Input::Hash(_))));
+        assert!(Input::parse("0x12").is_err() && Input::parse("transfer").is_err());
+    }
+}
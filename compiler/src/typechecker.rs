@@ -1,30 +1,94 @@
-use std::collections::HashMap;
-use crate::types::Type;
-use crate::ast::{Stmt, Expr};
+use std::collections::{HashMap, HashSet};
+use crate::types::{self, Aliases, Type};
+use crate::ast::{Attribute, Condition, ConditionKind, Expr, ExprKind, MatchArm, Pattern, Span, Stmt, StmtKind};
+use crate::error::CompileError;
+use crate::eip712::types::TypedData;
+use crate::ipfs::cid::Cid;
+use crate::u256::arith as u256;
+use crate::defi::abi as defi;
 
 // STEP 46: Function signature for type checking
 #[derive(Debug, Clone)]
 pub struct FunctionSignature {
     pub params: Vec<Type>,
     pub return_type: Type,
+    pub names: Vec<String>,           // Parameter names, for arguments passed by name
+    pub defaults: Vec<Option<Expr>>,  // Each parameter's default, if it has one
+}
+
+/// A trait's methods: each one's name and parameter count, `self` included
+pub type TraitMethods = Vec<(String, usize)>;
+
+/// The annotations of a function with type parameters, to bind them per call
+#[derive(Debug, Clone)]
+struct GenericFunction {
+    type_params: Vec<String>,
+    param_types: Vec<Option<String>>,
+    return_annotation: Option<String>,
 }
 
 pub struct TypeChecker {
-    symbols: HashMap<String, Type>,
+    symbols: Vec<HashMap<String, Type>>, // Bindings by scope: the top level, then functions and blocks, innermost last
     functions: HashMap<String, FunctionSignature>,  // STEP 46: Function signatures
-    errors: Vec<String>,
+    generics: HashMap<String, GenericFunction>,
+    traits: HashMap<String, TraitMethods>,
+    impl_target: Option<Type>, // The type `self` has while checking an impl's methods
+    tries: Vec<(Type, Span)>,  // Each `?` operand in the function being checked
+    returns: Vec<Type>,        // Each returned value's type in the function being checked
+    immutable: HashMap<String, Span>, // Bindings declared without `mut`, by their `let`
+    errors: Vec<CompileError>,
+    contract_state: Option<Vec<String>>, // State variables while checking a contract's methods
+    aliases: Aliases,
+    no_prelude: bool,        // Checking a `#[no_prelude]` function
+    imported: HashSet<String>, // Prelude functions imported by name
 }
 
 impl TypeChecker {
     pub fn new() -> Self {
         Self {
-            symbols: HashMap::new(),
+            symbols: vec![HashMap::new()],
             functions: HashMap::new(),  // STEP 46: Initialize function table
+            generics: HashMap::new(),
+            traits: HashMap::new(),
+            impl_target: None,
+            tries: Vec::new(),
+            returns: Vec::new(),
+            immutable: HashMap::new(),
             errors: Vec::new(),
+            contract_state: None,
+            aliases: Aliases::new(),
+            no_prelude: false,
+            imported: HashSet::new(),
+        }
+    }
+
+    /// Start from function signatures declared outside the checked statements
+    pub fn with_functions(functions: HashMap<String, FunctionSignature>) -> Self {
+        Self {
+            functions,
+            ..Self::new()
         }
     }
 
-    pub fn check(&mut self, stmts: &[Stmt]) -> Result<(), Vec<String>> {
+    /// Also know traits declared outside the checked statements
+    pub fn with_traits(mut self, traits: HashMap<String, TraitMethods>) -> Self {
+        self.traits = traits;
+        self
+    }
+
+    /// Traits known after checking
+    pub fn traits(&self) -> &HashMap<String, TraitMethods> {
+        &self.traits
+    }
+
+    /// Function signatures known after checking (declared and inferred)
+    pub fn functions(&self) -> &HashMap<String, FunctionSignature> {
+        &self.functions
+    }
+
+    pub fn check(&mut self, stmts: &[Stmt]) -> Result<(), Vec<CompileError>> {
+        // A type alias may be used before its declaration
+        self.aliases.extend(types::aliases(stmts));
         for stmt in stmts {
             self.check_stmt(stmt);
         }
@@ -36,304 +100,1406 @@ impl TypeChecker {
         }
     }
 
+    /// The type an annotation names, seeing through type aliases
+    fn annotated(&self, annotation: &str) -> Option<Type> {
+        Type::from_annotation(&types::expand_aliases(annotation, &self.aliases).ok()?)
+    }
+
+    /// The type `name` is bound to in the innermost scope binding it
+    fn lookup(&self, name: &str) -> Option<&Type> {
+        self.symbols.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    /// The innermost scope, where bindings are made
+    fn scope(&mut self) -> &mut HashMap<String, Type> {
+        self.symbols.last_mut().expect("the top-level scope is never closed")
+    }
+
+    /// Check a block's statements in a scope of their own; its bindings,
+    /// and whether they may be reassigned, end with it
+    fn check_block(&mut self, body: &[Stmt]) {
+        let outer_immutable = self.immutable.clone();
+        self.symbols.push(HashMap::new());
+        for stmt in body {
+            self.check_stmt(stmt);
+        }
+        self.symbols.pop();
+        self.immutable = outer_immutable;
+    }
+
+    /// Report a catalog error at `span`
+    fn error(&mut self, code: &'static str, args: &[&dyn std::fmt::Display], span: Span) {
+        self.errors.push(CompileError::coded(code, args, span.start_line, span.start_column));
+    }
+
+    /// Report `name` as not defined, suggesting the closest name in scope
+    /// if one is near enough to be a typo
+    fn undefined(&mut self, name: &str, span: Span) {
+        let mut error = CompileError::coded("E0203", &[&name], span.start_line, span.start_column);
+        let candidates = self.symbols.iter().flat_map(|scope| scope.keys()).chain(self.functions.keys()).filter(|c| !c.contains('.'));
+        if let Some(suggestion) = closest(name, candidates) {
+            error = error.help(&crate::messages::render("E0203.suggestion", &[&suggestion]));
+        }
+        self.errors.push(error);
+    }
+
     fn check_stmt(&mut self, stmt: &Stmt) {
-        match stmt {
-            Stmt::Import(_) => {
+        match &stmt.kind {
+            StmtKind::Import { module, items, .. } if module == crate::prelude::MODULE => {
+                // The loader checks the names exist; a plain import names them all
+                match items {
+                    Some(items) => self.imported.extend(items.iter().cloned()),
+                    None => self.imported.extend(crate::prelude::functions().iter().map(|f| f.name.to_string())),
+                }
+            }
+            StmtKind::Import { .. } => {
                 // Imports are handled at module level, not type-checked here
             }
-            Stmt::Function { name, params, return_type, body, .. } => {
+            StmtKind::Function {
+                name, params, param_types: annotations, defaults, type_params, return_annotation, return_type, body, attributes, conditions, ..
+            } => {
+                self.check_attributes(name, params, body, attributes, stmt.span);
+                let default_types: Vec<Option<Type>> =
+                    defaults.iter().map(|default| default.as_ref().map(|value| self.check_expr(value))).collect();
+
+                // A parameter annotated with a type parameter takes whatever
+                // each call passes, an impl method's `self` is the
+                // implementing type, and a type annotation is taken at its
+                // word; an unannotated parameter with a default has the
+                // default's type, and the rest take whatever they are given
+                let param_types: Vec<Type> = (0..params.len())
+                    .map(|i| match (annotations.get(i), &self.impl_target) {
+                        (Some(Some(annotation)), _) if type_params.contains(annotation) => Type::Unknown,
+                        (_, Some(target)) if i == 0 && params[0] == "self" => target.clone(),
+                        (Some(Some(annotation)), _) => self.annotated(annotation).unwrap_or(Type::Int),
+                        _ => default_types.get(i).cloned().flatten().unwrap_or(Type::Unknown),
+                    })
+                    .collect();
+                if type_params.is_empty() {
+                    self.generics.remove(name);
+                } else if self.contract_state.is_some() {
+                    self.error("E0230", &[&name], stmt.span);
+                } else {
+                    self.generics.insert(name.clone(), GenericFunction {
+                        type_params: type_params.clone(),
+                        param_types: annotations.clone(),
+                        return_annotation: return_annotation.clone(),
+                    });
+                }
+
                 // Register function signature with provisional return type (may be inferred)
-                let param_types = vec![Type::Int; params.len()]; // For V1, all params are Int
                 let provisional_sig = FunctionSignature {
                     params: param_types.clone(),
                     return_type: return_type.clone(),
+                    names: params.clone(),
+                    defaults: defaults.clone(),
                 };
                 self.functions.insert(name.clone(), provisional_sig);
-                self.symbols.insert(name.clone(), return_type.clone());
+                self.scope().insert(name.clone(), Type::Function(param_types.clone(), Box::new(return_type.clone())));
 
-                // Register parameters as local variables
-                for param in params {
-                    self.symbols.insert(param.clone(), Type::Int);
+                // Parameters are bound in the function's own scope; V1
+                // parameters may be reassigned
+                let outer_immutable = std::mem::take(&mut self.immutable);
+                self.symbols.push(params.iter().cloned().zip(param_types.iter().cloned()).collect());
+
+                // Helpers nested in the body are local to it, and cannot
+                // use its variables
+                let helpers: Vec<(String, Option<FunctionSignature>)> = crate::nested::helpers(body)
+                    .into_iter()
+                    .map(|helper| (helper.clone(), self.functions.get(&helper).cloned()))
+                    .collect();
+                let mut enclosing = params.clone();
+                enclosing.extend(self.contract_state.iter().flatten().cloned());
+                for (helper, variable, span) in crate::nested::captures(&enclosing, body) {
+                    self.error("E0247", &[&helper, &variable, &name], span);
                 }
 
-                // Check function body
+                // Check function body; helpers nested in a #[no_prelude]
+                // function are held to it too
+                let outer_tries = std::mem::take(&mut self.tries);
+                let outer_returns = std::mem::take(&mut self.returns);
+                let outer_no_prelude = self.no_prelude;
+                self.no_prelude |= attributes.contains(&Attribute::NoPrelude);
                 for inner_stmt in body {
                     self.check_stmt(inner_stmt);
                 }
+                self.no_prelude = outer_no_prelude;
 
                 // Infer function return type from return statements
-                let returns = self.collect_return_types_in_body(body);
+                let returns = std::mem::replace(&mut self.returns, outer_returns);
                 let inferred_return = if returns.is_empty() {
                     Type::Void
                 } else {
-                    // Ensure all return types are consistent
-                    let first = returns[0].clone();
-                    let mut consistent = true;
-                    for t in &returns {
-                        if *t != first && *t != Type::Unknown {
-                            consistent = false;
-                            break;
+                    // Ensure all return types are consistent; a `None` or
+                    // `Err` leaves part of its type to the other returns
+                    let unified = returns.iter().try_fold(Type::Unknown, |unified, t| unified.unify(t));
+                    match unified {
+                        Some(unified) => unified,
+                        None => {
+                            self.error(
+                                "E0201",
+                                &[&name, &format!("{:?}", returns)],
+                                stmt.span,
+                            );
+                            Type::Unknown
                         }
                     }
-                    if consistent { first } else {
-                        self.errors.push(format!(
-                            "Type error: inconsistent return types in function '{}' (found {:?})",
-                            name, returns
-                        ));
-                        Type::Unknown
+                };
+
+                // A concrete return annotation must agree with the body
+                let declared = return_annotation.as_deref().and_then(|annotation| self.annotated(annotation));
+                let inferred_return = match declared {
+                    Some(declared) => {
+                        if declared.unify(&inferred_return).is_none() {
+                            self.error(
+                                "E0229",
+                                &[&name, &Self::type_to_readable_name(&declared), &Self::type_to_readable_name(&inferred_return)],
+                                stmt.span,
+                            );
+                        }
+                        declared
                     }
+                    None => inferred_return,
                 };
 
+                // `?` returns its operand's None or Err from the function
+                for (operand, span) in std::mem::replace(&mut self.tries, outer_tries) {
+                    let propagates = matches!(
+                        (&operand, &inferred_return),
+                        (Type::Option(_), Type::Option(_)) | (Type::Result(..), Type::Result(..)) | (_, Type::Unknown)
+                    ) || operand == Type::Unknown;
+                    if !propagates {
+                        self.error(
+                            "E0240",
+                            &[&name, &Self::type_to_readable_name(&inferred_return), &Self::type_to_readable_name(&operand)],
+                            span,
+                        );
+                    }
+                }
+
+                self.check_conditions(name, conditions, &inferred_return, stmt.span);
+
                 // Update function signature and symbol with inferred type
+                // The function's scope ends with its parameters, locals
+                // and helpers
+                self.symbols.pop();
+                self.immutable = outer_immutable;
                 if let Some(sig) = self.functions.get_mut(name) {
                     sig.return_type = inferred_return.clone();
                 }
-                self.symbols.insert(name.clone(), inferred_return);
+                self.scope().insert(name.clone(), Type::Function(param_types, Box::new(inferred_return)));
 
-                // Clean up parameter symbols after function
-                for param in params {
-                    self.symbols.remove(param);
+                // Helpers go out of scope, uncovering what they shadowed
+                for (helper, function) in helpers {
+                    match function {
+                        Some(function) => self.functions.insert(helper, function),
+                        None => self.functions.remove(&helper),
+                    };
                 }
             }
-            Stmt::Expression(_expr) => {
-                // Check expression statement
-                // For now, just validate the expression exists
+            StmtKind::Expression(expr) => {
+                self.check_expr(expr);
             }
-            Stmt::Let { name, value } => {
+            StmtKind::Let { name, value, mutable } => {
                 // Infer the variable's type from the initializer expression
                 let value_type = self.check_expr(value);
-                self.symbols.insert(name.clone(), value_type);
+                self.scope().insert(name.clone(), value_type);
+                self.declare(name, *mutable, stmt.span);
             }
-            Stmt::If { condition, then_body, else_body } => {
-                // Check condition expression must be Bool or Int
+            StmtKind::LetTuple { names, value, mutable } => {
+                let value_type = self.check_expr(value);
+                let element_types = match value_type {
+                    Type::Tuple(types) if types.len() == names.len() => types,
+                    Type::Tuple(types) => {
+                        self.error("E0218", &[&types.len(), &names.len()], stmt.span);
+                        vec![Type::Unknown; names.len()]
+                    }
+                    Type::Unknown => vec![Type::Unknown; names.len()],
+                    other => {
+                        self.error(
+                            "E0217",
+                            &[&Self::type_to_readable_name(&other), &names.len()],
+                            stmt.span,
+                        );
+                        vec![Type::Unknown; names.len()]
+                    }
+                };
+                for (name, element_type) in names.iter().zip(element_types) {
+                    if name != "_" {
+                        self.scope().insert(name.clone(), element_type);
+                        self.declare(name, *mutable, stmt.span);
+                    }
+                }
+            }
+            StmtKind::If { condition, then_body, else_body } => {
+                // Check condition expression must be Bool
                 let cond_type = self.check_expr(condition);
-                if cond_type != Type::Int && cond_type != Type::Bool && cond_type != Type::Unknown {
-                    self.errors.push(format!(
-                        "Type error: if condition must be Bool, got {}",
-                        Self::type_to_readable_name(&cond_type)
-                    ));
+                if cond_type != Type::Bool && cond_type != Type::Unknown {
+                    self.error(
+                        "E0202",
+                        &[&Self::type_to_readable_name(&cond_type)],
+                        condition.span,
+                    );
                 }
                 
                 // Check then body
-                for stmt in then_body {
-                    self.check_stmt(stmt);
-                }
+                self.check_block(then_body);
                 
                 // Check else body if present
                 if let Some(body) = else_body {
-                    for stmt in body {
-                        self.check_stmt(stmt);
-                    }
+                    self.check_block(body);
                 }
             }
-            Stmt::Assign { name, value } => {
-                // Check that variable is already defined
-                if !self.symbols.contains_key(name) {
-                    self.errors.push(format!(
-                        "Type error: variable '{}' not defined",
-                        name
-                    ));
+            StmtKind::Assign { name, value } => {
+                // Check that variable is already defined, and may change
+                if self.lookup(name).is_none() {
+                    self.undefined(name, stmt.span);
+                }
+                if let Some(declared) = self.immutable.get(name).copied() {
+                    // The fix turns the declaring `let` into `let mut`
+                    let keyword = Span { end_line: declared.start_line, end_column: declared.start_column + 3, ..declared };
+                    let error = CompileError::coded("E0243", &[&name], stmt.span.start_line, stmt.span.start_column)
+                        .with_fix(keyword, "let mut");
+                    self.errors.push(error);
                 }
                 
                 // Check the value expression type is compatible
                 let value_type = self.check_expr(value);
-                let var_type = self.symbols.get(name).cloned().unwrap_or(Type::Unknown);
+                let var_type = self.lookup(name).cloned().unwrap_or(Type::Unknown);
                 
-                if var_type != Type::Unknown && value_type != Type::Unknown && var_type != value_type {
-                    self.errors.push(format!(
-                        "Type mismatch: cannot assign {} to variable of type {}",
-                        Self::type_to_readable_name(&value_type),
-                        Self::type_to_readable_name(&var_type)
-                    ));
+                if var_type.unify(&value_type).is_none() {
+                    self.error(
+                        "E0204",
+                        &[
+                            &Self::type_to_readable_name(&value_type),
+                            &Self::type_to_readable_name(&var_type),
+                        ],
+                        value.span,
+                    );
                 }
             }
-            Stmt::While { condition, body } => {
-                // Check condition expression must be Bool or Int
+            StmtKind::While { condition, body } => {
+                // Check condition expression must be Bool
                 let cond_type = self.check_expr(condition);
-                if cond_type != Type::Int && cond_type != Type::Bool && cond_type != Type::Unknown {
-                    self.errors.push(format!(
-                        "Type error: while condition must be Bool, got {}",
-                        Self::type_to_readable_name(&cond_type)
-                    ));
+                if cond_type != Type::Bool && cond_type != Type::Unknown {
+                    self.error(
+                        "E0205",
+                        &[&Self::type_to_readable_name(&cond_type)],
+                        condition.span,
+                    );
                 }
                 
                 // Check loop body
-                for stmt in body {
-                    self.check_stmt(stmt);
+                self.check_block(body);
+            }
+            StmtKind::For { var, iterable, body } => {
+                // Ranges count through Ints; arrays only come from stdlib
+                // calls, which the checker cannot type yet
+                let var_type = match self.check_expr(iterable) {
+                    Type::Range => Type::Int,
+                    Type::Unknown => Type::Unknown,
+                    _ if matches!(&iterable.kind, ExprKind::Call(name, _) if !self.functions.contains_key(name)) => Type::Unknown,
+                    other => {
+                        self.error("E0223", &[&Self::type_to_readable_name(&other)], iterable.span);
+                        Type::Unknown
+                    }
+                };
+                
+                // The loop variable is scoped to the body
+                self.symbols.push(HashMap::from([(var.clone(), var_type)]));
+                let shadowed_immutable = self.immutable.remove(var);
+                self.check_block(body);
+                self.symbols.pop();
+                if let Some(declared) = shadowed_immutable {
+                    self.immutable.insert(var.clone(), declared);
+                }
+            }
+            StmtKind::Match { value, arms } => {
+                let value_type = self.check_expr(value);
+                for arm in arms {
+                    // The binding is scoped to the arm
+                    self.symbols.push(HashMap::new());
+                    self.bind_pattern(arm, &value_type, stmt.span);
+                    self.check_block(&arm.body);
+                    self.symbols.pop();
                 }
+                self.check_exhaustive(arms, &value_type, stmt.span);
+            }
+            StmtKind::Return(expr) => {
+                // STEP 46: Record the returned type; the function's return
+                // type is inferred from them all
+                let return_type = self.check_expr(expr);
+                self.returns.push(return_type);
             }
-            Stmt::Return(expr) => {
-                // STEP 46: Check return expression type
-                let _return_type = self.check_expr(expr);
-                // Function-level inference happens in the function arm
+            StmtKind::Value(expr) => {
+                // A final print(..) is run, not returned
+                let value_type = self.check_expr(expr);
+                if value_type != Type::Void {
+                    self.returns.push(value_type);
+                }
             }
-            Stmt::Panic(expr) => {
+            StmtKind::Defer(body) => {
+                // Deferred code runs while its block is already exiting,
+                // too late to return from the function, early or not
+                let tries = self.tries.len();
+                let returns = self.returns.len();
+                self.check_block(body);
+                self.returns.truncate(returns);
+                if self.tries.len() > tries {
+                    self.tries.truncate(tries);
+                    self.error("E0255", &[], stmt.span);
+                }
+                if let Some(span) = first_return(body) {
+                    self.error("E0269", &[], span);
+                }
+            }
+            StmtKind::Panic(expr) => {
                 // STEP 48: Check panic expression must be a string
                 let expr_type = self.check_expr(expr);
                 if expr_type != Type::String && expr_type != Type::Unknown {
-                    self.errors.push(format!(
-                        "Type error: panic() requires a string message, got {}",
-                        Self::type_to_readable_name(&expr_type)
-                    ));
+                    self.error(
+                        "E0206",
+                        &[&Self::type_to_readable_name(&expr_type)],
+                        expr.span,
+                    );
                 }
             }
-        }
-    }
+            StmtKind::Contract { name, state, constructor, methods, .. } => {
+                // State variables are visible to every method; their types are
+                // not declared yet, so they stay Unknown.
+                for var in state {
+                    self.scope().insert(var.clone(), Type::Unknown);
+                }
+                self.contract_state = Some(state.clone());
 
-    // Collect return types from a sequence of statements (recursively)
-    fn collect_return_types_in_body(&mut self, body: &[Stmt]) -> Vec<Type> {
-        let mut returns = Vec::new();
-        for stmt in body {
-            match stmt {
-                Stmt::Return(expr) => {
-                    returns.push(self.check_expr(expr));
+                for method in constructor.iter().map(|c| c.as_ref()).chain(methods.iter()) {
+                    self.check_stmt(method);
+
+                    // Methods live in the contract namespace, not the global one
+                    if let StmtKind::Function { name: method_name, .. } = &method.kind {
+                        if let Some(sig) = self.functions.remove(method_name) {
+                            self.functions.insert(format!("{}.{}", name, method_name), sig);
+                        }
+                        if let Some(ty) = self.scope().remove(method_name) {
+                            self.scope().insert(format!("{}.{}", name, method_name), ty);
+                        }
+                    }
+                }
+
+                for var in state {
+                    self.scope().remove(var);
+                }
+                self.contract_state = None;
+            }
+            StmtKind::TypeAlias { name, target } => {
+                if Type::from_annotation(name).is_some() || self.aliases.get(name).is_some_and(|known| known != target) {
+                    self.error("E0266", &[&name], stmt.span);
+                    return;
                 }
-                Stmt::If { then_body, else_body, .. } => {
-                    returns.extend(self.collect_return_types_in_body(then_body));
-                    if let Some(else_b) = else_body {
-                        returns.extend(self.collect_return_types_in_body(else_b));
+                self.aliases.insert(name.clone(), target.clone());
+                if types::expand_aliases(target, &self.aliases).is_err() {
+                    self.error("E0267", &[&name], stmt.span);
+                }
+            }
+            StmtKind::Trait { name, methods } => {
+                let mut signatures = Vec::new();
+                for method in methods {
+                    let StmtKind::Function { name: method_name, params, .. } = &method.kind else { continue };
+                    if params.first().map(String::as_str) != Some("self") {
+                        self.error("E0231", &[&method_name, &name], method.span);
                     }
+                    signatures.push((method_name.clone(), params.len()));
                 }
-                Stmt::While { body: loop_body, .. } => {
-                    returns.extend(self.collect_return_types_in_body(loop_body));
+                self.traits.insert(name.clone(), signatures);
+            }
+            StmtKind::Impl { trait_name, target, methods } => {
+                let Some(target_type) = self.annotated(target).filter(|t| !matches!(t, Type::Void | Type::Tuple(_))) else {
+                    self.error("E0232", &[&trait_name, &target], stmt.span);
+                    return;
+                };
+                let Some(required) = self.traits.get(trait_name).cloned() else {
+                    self.error("E0233", &[&trait_name], stmt.span);
+                    return;
+                };
+
+                self.impl_target = Some(target_type.clone());
+                for method in methods {
+                    let StmtKind::Function { name: method_name, params, .. } = &method.kind else { continue };
+                    match required.iter().find(|(name, _)| name == method_name) {
+                        None => self.error("E0235", &[&method_name, &trait_name], method.span),
+                        Some((_, count)) if *count != params.len() => {
+                            self.error("E0236", &[&method_name, &count, &trait_name, &params.len()], method.span)
+                        }
+                        Some(_) => {}
+                    }
+                    if self.functions.contains_key(&format!("{}.{}", target_type.to_string(), method_name)) {
+                        self.error("E0237", &[&method_name, &target_type.to_string()], method.span);
+                    }
+
+                    self.check_stmt(method);
+
+                    // Methods are called on a value, `n.to_string()`, and
+                    // resolve to `Type.method`
+                    if let Some(sig) = self.functions.remove(method_name) {
+                        self.functions.insert(format!("{}.{}", target_type.to_string(), method_name), sig);
+                    }
+                    self.scope().remove(method_name);
+                }
+                self.impl_target = None;
+
+                for (method_name, _) in &required {
+                    let provided = methods
+                        .iter()
+                        .any(|m| matches!(&m.kind, StmtKind::Function { name, .. } if name == method_name));
+                    if !provided {
+                        self.error("E0234", &[&trait_name, &target_type.to_string(), &method_name], stmt.span);
+                    }
                 }
-                Stmt::Function { body: inner, .. } => {
-                    // Nested function: do not consider its returns for outer function
-                    let _ = inner; // explicitly ignore
+            }
+        }
+    }
+
+    /// Check that a function's attributes fit where it is declared:
+    /// #[payable], #[view] and #[unbounded] only on contract methods
+    /// (#[payable] and #[view] not both), a
+    /// #[view] method never assigns contract state, #[invariant] methods
+    /// take no arguments and never assign state, and #[test] functions are
+    /// top-level and take no arguments.
+    fn check_attributes(&mut self, name: &str, params: &[String], body: &[Stmt], attributes: &[Attribute], span: Span) {
+        let in_contract = self.contract_state.is_some();
+
+        for attribute in attributes {
+            let misplaced = match attribute {
+                Attribute::Payable | Attribute::View | Attribute::Invariant | Attribute::Unbounded => !in_contract,
+                Attribute::Test => in_contract,
+                Attribute::Inline | Attribute::NoPrelude => false,
+            };
+            if misplaced {
+                let code = if in_contract { "E0216" } else { "E0212" };
+                self.error(code, &[&attribute.name(), &name], span);
+            }
+        }
+
+        if attributes.contains(&Attribute::Payable) && attributes.contains(&Attribute::View) {
+            self.error("E0213", &[&name], span);
+        }
+
+        if attributes.contains(&Attribute::Test) && !params.is_empty() {
+            self.error("E0215", &[&name], span);
+        }
+
+        if attributes.contains(&Attribute::Invariant) && !params.is_empty() {
+            self.error("E0226", &[&name], span);
+        }
+
+        // Invariants only observe state, like views
+        for (attribute, code) in [(Attribute::View, "E0214"), (Attribute::Invariant, "E0227")] {
+            if !attributes.contains(&attribute) {
+                continue;
+            }
+            if let Some(state) = &self.contract_state {
+                let mut locals: Vec<String> = params.to_vec();
+                if let Some((var, write)) = first_state_write(body, state, &mut locals) {
+                    self.error(code, &[&name, &var], write);
                 }
-                _ => {}
             }
         }
-        returns
+    }
+
+    // Collect return types from a sequence of statements (recursively)
+    /// Check a function's #[requires] and #[ensures]: contract methods only,
+    /// and each a Bool over the parameters and state, with `result` the
+    /// returned value in an #[ensures]
+    fn check_conditions(&mut self, name: &str, conditions: &[Condition], returns: &Type, span: Span) {
+        for condition in conditions {
+            if self.contract_state.is_none() {
+                self.error("E0212", &[&condition.kind.name(), &name], span);
+            }
+            self.symbols.push(HashMap::new());
+            if condition.kind == ConditionKind::Ensures {
+                self.scope().insert("result".to_string(), returns.clone());
+            }
+            let condition_type = self.check_expr(&condition.expr);
+            self.symbols.pop();
+            if !matches!(condition_type, Type::Bool | Type::Unknown) {
+                self.error(
+                    "E0262",
+                    &[&condition.kind.name(), &name, &Self::type_to_readable_name(&condition_type)],
+                    condition.expr.span,
+                );
+            }
+        }
     }
 
     fn check_expr(&mut self, expr: &Expr) -> Type {
-        match expr {
-            Expr::Number(_) => Type::Int,
-            Expr::Float(_) => Type::Float,
-            Expr::Bool(_) => Type::Bool,
-            Expr::String(_) => Type::String,
-            Expr::Identifier(name) => {
-                self.symbols.get(name).cloned().unwrap_or(Type::Unknown)
-            }
-            Expr::Call(name, args) => {
+        if let ExprKind::Call(name, _) = &expr.kind {
+            self.check_prelude(name, expr.span);
+        }
+        match &expr.kind {
+            ExprKind::Number(_) => Type::Int,
+            ExprKind::Float(_) => Type::Float,
+            ExprKind::Bool(_) => Type::Bool,
+            ExprKind::String(_) => Type::String,
+            ExprKind::Tuple(items) => {
+                let types: Vec<Type> = items.iter().map(|item| self.check_expr(item)).collect();
+                // Tuples compile to multiple values on the WASM stack, which
+                // have no nesting
+                if types.iter().any(|t| matches!(t, Type::Tuple(_))) {
+                    self.error("E0219", &[], expr.span);
+                }
+                Type::Tuple(types)
+            }
+            ExprKind::Property(object, name) if self.lookup(object) == Some(&Type::AIResult) => match name.as_str() {
+                "label" => Type::String,
+                "score" => Type::Float,
+                _ => {
+                    self.error("E0261", &[name], expr.span);
+                    Type::Unknown
+                }
+            },
+            ExprKind::Property(object, name) => match crate::stdlib::get_property_info(object, name) {
+                Some(property) => property.returns.clone(),
+                None => {
+                    self.error("E0245", &[object, name], expr.span);
+                    Type::Unknown
+                }
+            },
+            ExprKind::Array(items) => {
+                // Arrays live on the heap, so their elements may be tuples;
+                // like the arrays stdlib returns, they are not typed yet
+                for item in items {
+                    self.check_expr(item);
+                }
+                Type::Unknown
+            }
+            ExprKind::Range(start, end) => {
+                let start_type = self.check_expr(start);
+                let end_type = self.check_expr(end);
+                let is_int = |t: &Type| matches!(t, Type::Int | Type::Unknown);
+                if !is_int(&start_type) || !is_int(&end_type) {
+                    self.error(
+                        "E0222",
+                        &[
+                            &Self::type_to_readable_name(&start_type),
+                            &Self::type_to_readable_name(&end_type),
+                        ],
+                        expr.span,
+                    );
+                }
+                Type::Range
+            }
+            ExprKind::Slice(value, range) => {
+                let value_type = self.check_expr(value);
+                let range_type = self.check_expr(range);
+                // `value["name"]` reads a field of a map, which is untyped
+                if range_type == Type::String && value_type == Type::Unknown {
+                    return Type::Unknown;
+                }
+                if range_type != Type::Range && range_type != Type::Unknown {
+                    self.error(
+                        "E0225",
+                        &[&Self::type_to_readable_name(&range_type)],
+                        range.span,
+                    );
+                }
+                match value_type {
+                    Type::String => Type::String,
+                    Type::Range => Type::Range,
+                    Type::Unknown => Type::Unknown,
+                    // Arrays come from stdlib calls, typed as Int for now
+                    _ if matches!(&value.kind, ExprKind::Call(name, _) if !self.functions.contains_key(name)) => Type::Unknown,
+                    other => {
+                        self.error(
+                            "E0224",
+                            &[&Self::type_to_readable_name(&other)],
+                            value.span,
+                        );
+                        Type::Unknown
+                    }
+                }
+            }
+            ExprKind::Identifier(name) if name == "None" => Type::Option(Box::new(Type::Unknown)),
+            ExprKind::Identifier(name) => match self.lookup(name) {
+                Some(symbol) => symbol.clone(),
+                // A function declared elsewhere, used by name
+                None => match self.functions.get(name) {
+                    Some(sig) => Type::Function(sig.params.clone(), Box::new(sig.return_type.clone())),
+                    None => {
+                        self.undefined(name, expr.span);
+                        Type::Unknown
+                    }
+                },
+            },
+            ExprKind::Call(name, args) if matches!(name.as_str(), "Some" | "Ok" | "Err") => {
+                if args.len() != 1 {
+                    self.error("E0207", &[&name, &1, &args.len()], expr.span);
+                }
+                let payload = args.iter().map(|arg| self.check_expr(arg)).last().unwrap_or(Type::Unknown);
+                match name.as_str() {
+                    "Some" => Type::Option(Box::new(payload)),
+                    "Ok" => Type::Result(Box::new(payload), Box::new(Type::Unknown)),
+                    _ => Type::Result(Box::new(Type::Unknown), Box::new(payload)),
+                }
+            }
+            ExprKind::Named(_, value) => self.check_expr(value),
+            ExprKind::Try(inner) => {
+                let operand = self.check_expr(inner);
+                self.tries.push((operand.clone(), expr.span));
+                match operand {
+                    Type::Option(payload) | Type::Result(payload, _) => *payload,
+                    Type::Unknown => Type::Unknown,
+                    other => {
+                        self.error("E0239", &[&Self::type_to_readable_name(&other)], inner.span);
+                        Type::Unknown
+                    }
+                }
+            }
+            ExprKind::Cast(inner, target) => {
+                // Only numbers convert with `as`; text parses with int()
+                // and anything formats with str()
+                let operand = self.check_expr(inner);
+                let target_type = self.annotated(target).filter(|t| matches!(t, Type::Int | Type::Float));
+                if target_type.is_none() || !matches!(operand, Type::Int | Type::Float | Type::Unknown) {
+                    self.error("E0263", &[&Self::type_to_readable_name(&operand), target], expr.span);
+                }
+                target_type.unwrap_or(Type::Unknown)
+            }
+            ExprKind::Call(name, args) if name == "range" && !self.functions.contains_key(name) => {
+                if args.len() != 2 {
+                    self.error("E0207", &[&name, &2, &args.len()], expr.span);
+                }
+                let types: Vec<Type> = args.iter().map(|arg| self.check_expr(arg)).collect();
+                if types.iter().any(|t| !matches!(t, Type::Int | Type::Unknown)) {
+                    let bound = |i: usize| Self::type_to_readable_name(types.get(i).unwrap_or(&Type::Unknown));
+                    self.error("E0222", &[&bound(0), &bound(1)], expr.span);
+                }
+                Type::Range
+            }
+            ExprKind::Call(name, args) if matches!(name.as_str(), "map" | "filter") && !self.functions.contains_key(name) => {
+                // The function is named, not called: map(items, double)
+                if args.len() != 2 {
+                    self.error("E0207", &[&name, &2, &args.len()], expr.span);
+                }
+                if let Some(items) = args.first() {
+                    self.check_expr(items);
+                }
+                if let Some(function) = args.get(1) {
+                    match self.check_expr(function) {
+                        Type::Function(params, _) if params.len() == 1 => {}
+                        Type::Unknown => {}
+                        other => self.error("E0265", &[&name, &Self::type_to_readable_name(&other)], function.span),
+                    }
+                }
+                Type::Unknown
+            }
+            ExprKind::Call(name, args) if name == "on_shutdown" && !self.functions.contains_key(name) => {
+                // Named, not called, and called later with nothing
+                if args.len() != 1 {
+                    self.error("E0207", &[&name, &1, &args.len()], expr.span);
+                }
+                for handler in args {
+                    match self.check_expr(handler) {
+                        Type::Function(params, _) if params.is_empty() => {}
+                        Type::Unknown => {}
+                        other => self.error("E0268", &[&Self::type_to_readable_name(&other)], handler.span),
+                    }
+                }
+                Type::Void
+            }
+            ExprKind::Call(name, args) if name == "int" && !self.functions.contains_key(name) => {
+                if args.len() != 1 {
+                    self.error("E0207", &[&name, &1, &args.len()], expr.span);
+                }
+                for arg in args {
+                    let arg_type = self.check_expr(arg);
+                    if !matches!(arg_type, Type::String | Type::Int | Type::Float | Type::Unknown) {
+                        self.error("E0264", &[&Self::type_to_readable_name(&arg_type)], arg.span);
+                    }
+                }
+                Type::Int
+            }
+            ExprKind::Call(name, args) if name == "transfer" && !self.functions.contains_key(name) => {
+                // transfer(to, amount)
+                if args.len() != 2 {
+                    self.error("E0207", &[&name, &2, &args.len()], expr.span);
+                }
+                let types: Vec<Type> = args.iter().map(|arg| self.check_expr(arg)).collect();
+                if let (Some(to), Some(arg)) = (types.first(), args.first()) {
+                    if !matches!(to, Type::Address | Type::String | Type::Unknown) {
+                        self.error("E0259", &[&Self::type_to_readable_name(to)], arg.span);
+                    }
+                }
+                if let (Some(amount), Some(arg)) = (types.get(1), args.get(1)) {
+                    if !matches!(amount, Type::Int | Type::U256 | Type::Unknown) {
+                        self.error("E0260", &[&Self::type_to_readable_name(amount)], arg.span);
+                    }
+                }
+                Type::Void
+            }
+            ExprKind::Call(name, args) => {
                 // STEP 46: Check function call arguments
-                // Clone the signature to avoid borrowing issues
-                let sig = self.functions.get(name).cloned();
+                // Clone the signature to avoid borrowing issues; a variable
+                // holding a function is called by its function type
+                let sig = self.functions.get(name).cloned().or_else(|| match self.lookup(name) {
+                    Some(Type::Function(params, returns)) => Some(FunctionSignature {
+                        params: params.clone(),
+                        return_type: returns.as_ref().clone(),
+                        names: Vec::new(),
+                        defaults: Vec::new(),
+                    }),
+                    _ => None,
+                });
+
+                // Arguments passed by name or left to defaults take their
+                // parameters' places (see `arguments`)
+                let bound;
+                let args = match &sig {
+                    Some(sig) if crate::arguments::needs_binding(&sig.defaults, args) => {
+                        match crate::arguments::bind(&sig.names, &sig.defaults, args) {
+                            Ok(resolved) => {
+                                bound = resolved;
+                                &bound
+                            }
+                            Err(e) => {
+                                let (code, argument) = e.code();
+                                self.error(code, &[&name, &argument], expr.span);
+                                for arg in args {
+                                    self.check_expr(arg);
+                                }
+                                return sig.return_type.clone();
+                            }
+                        }
+                    }
+                    _ => args,
+                };
                 
                 if let Some(sig) = sig {
                     // Check argument count
                     if args.len() != sig.params.len() {
-                        self.errors.push(format!(
-                            "Type error: function '{}' expects {} arguments, got {}",
-                            name, sig.params.len(), args.len()
-                        ));
+                        self.error(
+                            "E0207",
+                            &[&name, &sig.params.len(), &args.len()],
+                            expr.span,
+                        );
                     }
                     
                     // Check argument types
+                    let mut arg_types = Vec::new();
                     for (i, arg) in args.iter().enumerate() {
                         let arg_type = self.check_expr(arg);
+                        arg_types.push(arg_type.clone());
                         if i < sig.params.len() {
                             let expected_type = &sig.params[i];
-                            if arg_type != *expected_type && arg_type != Type::Unknown {
-                                self.errors.push(format!(
-                                    "Type error: argument {} of function '{}' expects {}, got {}",
-                                    i, name, 
-                                    Self::type_to_readable_name(expected_type),
-                                    Self::type_to_readable_name(&arg_type)
-                                ));
+                            if arg_type.unify(expected_type).is_none() {
+                                self.error(
+                                    "E0208",
+                                    &[
+                                        &i,
+                                        &name,
+                                        &Self::type_to_readable_name(expected_type),
+                                        &Self::type_to_readable_name(&arg_type),
+                                    ],
+                                    arg.span,
+                                );
                             }
                         }
                     }
+
+                    if let Some(generic) = self.generics.get(name).cloned() {
+                        return self.bind_type_params(name, &generic, args, &arg_types, sig.return_type);
+                    }
                     
                     sig.return_type.clone()
                 } else {
-                    // For stdlib or unknown functions, assume Int return type
-                    Type::Int
+                    if let Some(arg) = args.iter().find(|arg| crate::arguments::is_named(arg)) {
+                        self.error("E0252", &[&name], arg.span);
+                    }
+                    match crate::prelude::get(name) {
+                        Some(info) => {
+                            self.check_builtin_call(info.name, &info.params, info.variadic, &info.returns, args, expr.span)
+                        }
+                        // Nothing is known of a function declared nowhere
+                        None => {
+                            for arg in args {
+                                self.check_expr(arg);
+                            }
+                            Type::Unknown
+                        }
+                    }
                 }
             }
             
-            // Add operator: can work with Int (addition) or String (concatenation)
-            Expr::Add(left, right) => {
+            // Add operator: can work with Int or Float (addition) or String
+            // (concatenation)
+            ExprKind::Add(left, right) => {
                 let left_type = self.check_expr(left);
                 let right_type = self.check_expr(right);
                 
                 if left_type == Type::Int && right_type == Type::Int {
                     Type::Int
+                } else if left_type == Type::Float && right_type == Type::Float {
+                    Type::Float
                 } else if left_type == Type::String && right_type == Type::String {
                     Type::String
                 } else if left_type == Type::Unknown || right_type == Type::Unknown {
                     Type::Unknown
+                } else if left_type == Type::String || right_type == Type::String {
+                    // Only strings concatenate: name the operand to convert
+                    let other = if left_type == Type::String { &right_type } else { &left_type };
+                    self.error(
+                        "E0257",
+                        &[
+                            &Self::type_to_readable_name(&left_type),
+                            &Self::type_to_readable_name(&right_type),
+                            &Self::type_to_readable_name(other),
+                        ],
+                        expr.span,
+                    );
+                    Type::String
                 } else {
-                    self.errors.push(format!(
-                        "Type error: cannot add {} and {} (both operands must be Int or both must be String)",
-                        Self::type_to_readable_name(&left_type),
-                        Self::type_to_readable_name(&right_type)
-                    ));
+                    self.error(
+                        "E0209",
+                        &[
+                            &Self::type_to_readable_name(&left_type),
+                            &Self::type_to_readable_name(&right_type),
+                        ],
+                        expr.span,
+                    );
                     Type::Unknown
                 }
             }
             
-            // Other arithmetic operators: require both operands to be Int
-            Expr::Sub(left, right) | Expr::Mul(left, right) | 
-            Expr::Div(left, right) | Expr::Mod(left, right) => {
+            // Other arithmetic operators: require both operands to be Int,
+            // or both Float but for %, which WASM has no f64 instruction for
+            ExprKind::Sub(left, right) | ExprKind::Mul(left, right) | 
+            ExprKind::Div(left, right) | ExprKind::Mod(left, right) => {
                 let left_type = self.check_expr(left);
                 let right_type = self.check_expr(right);
                 
                 if left_type == Type::Int && right_type == Type::Int {
                     Type::Int
+                } else if left_type == Type::Float && right_type == Type::Float && !matches!(expr.kind, ExprKind::Mod(..)) {
+                    Type::Float
                 } else if left_type == Type::Unknown || right_type == Type::Unknown {
                     Type::Unknown
                 } else {
-                    self.errors.push(format!(
-                        "Type error: arithmetic operator requires Int operands, got {} and {}",
-                        Self::type_to_readable_name(&left_type),
-                        Self::type_to_readable_name(&right_type)
-                    ));
+                    self.error(
+                        "E0210",
+                        &[
+                            &Self::type_to_readable_name(&left_type),
+                            &Self::type_to_readable_name(&right_type),
+                        ],
+                        expr.span,
+                    );
                     Type::Unknown
                 }
             }
             
-            // Comparison operators: require Int operands, return Bool
-            Expr::Eq(left, right) | Expr::Ne(left, right) |
-            Expr::Lt(left, right) | Expr::Le(left, right) |
-            Expr::Gt(left, right) | Expr::Ge(left, right) => {
+            // Equality: both sides of one type, except that amounts compare
+            // across Int and U256 and addresses with the strings that spell
+            // them, as in `msg.value == 0` and `msg.sender == "0x…"`
+            ExprKind::Eq(left, right) | ExprKind::Ne(left, right) => {
+                let left_type = self.check_expr(left);
+                let right_type = self.check_expr(right);
+                let amount = |t: &Type| matches!(t, Type::Int | Type::U256);
+                let address = |t: &Type| matches!(t, Type::Address | Type::String);
+                let comparable = left_type.unify(&right_type).is_some()
+                    || (amount(&left_type) && amount(&right_type))
+                    || (address(&left_type) && address(&right_type));
+                if !comparable {
+                    self.error(
+                        "E0258",
+                        &[
+                            &Self::type_to_readable_name(&left_type),
+                            &Self::type_to_readable_name(&right_type),
+                        ],
+                        expr.span,
+                    );
+                }
+                Type::Bool
+            }
+            
+            // Ordering: require Int or U256 operands, or two Floats, return Bool
+            ExprKind::Lt(left, right) | ExprKind::Le(left, right) |
+            ExprKind::Gt(left, right) | ExprKind::Ge(left, right) => {
                 let left_type = self.check_expr(left);
                 let right_type = self.check_expr(right);
                 
-                if left_type == Type::Int && right_type == Type::Int {
+                let amounts = matches!(left_type, Type::Int | Type::U256) && matches!(right_type, Type::Int | Type::U256);
+                if amounts || (left_type == Type::Float && right_type == Type::Float) {
                     Type::Bool
                 } else if left_type == Type::Unknown || right_type == Type::Unknown {
                     Type::Bool
                 } else {
-                    self.errors.push(format!(
-                        "Type error: comparison operator requires Int operands, got {} and {}",
-                        Self::type_to_readable_name(&left_type),
-                        Self::type_to_readable_name(&right_type)
-                    ));
+                    self.error(
+                        "E0211",
+                        &[
+                            &Self::type_to_readable_name(&left_type),
+                            &Self::type_to_readable_name(&right_type),
+                        ],
+                        expr.span,
+                    );
                     Type::Bool
                 }
             }
             
-            Expr::ModuleCall(..) => {
-                // Module calls are handled at runtime, assume Int for now
-                Type::Int
+            // Logical: Bool on both sides, since the right may never run
+            ExprKind::And(left, right) | ExprKind::Or(left, right) => {
+                let left_type = self.check_expr(left);
+                let right_type = self.check_expr(right);
+                let boolean = |t: &Type| matches!(t, Type::Bool | Type::Unknown);
+                if !boolean(&left_type) || !boolean(&right_type) {
+                    let op = if matches!(expr.kind, ExprKind::And(..)) { "&&" } else { "||" };
+                    self.error(
+                        "E0271",
+                        &[
+                            &op,
+                            &Self::type_to_readable_name(&left_type),
+                            &Self::type_to_readable_name(&right_type),
+                        ],
+                        expr.span,
+                    );
+                }
+                Type::Bool
+            }
+            
+            ExprKind::Await(inner) => {
+                // Awaiting yields the awaited call's result type
+                self.check_expr(inner)
+            }
+            
+            ExprKind::ModuleCall(receiver, method, args) if receiver == "web3" && method == "logs" => {
+                // An array of maps, which the checker cannot type yet, or
+                // why fetching them failed
+                if args.len() != 4 {
+                    self.error("E0207", &[&"web3.logs", &4, &args.len()], expr.span);
+                }
+                for arg in args {
+                    self.check_expr(arg);
+                }
+                Type::Result(Box::new(Type::Unknown), Box::new(Type::String))
+            }
+            ExprKind::ModuleCall(receiver, method, args) if receiver == "u256" => {
+                match u256::arity(method) {
+                    Some(arity) if args.len() != arity => {
+                        self.error("E0207", &[&format!("u256.{}", method), &arity, &args.len()], expr.span);
+                    }
+                    Some(_) => {}
+                    None => self.error("E0253", &[method], expr.span),
+                }
+                for arg in args {
+                    self.check_expr(arg);
+                }
+                Type::U256
+            }
+            ExprKind::ModuleCall(receiver, method, args) if receiver == "ai" && method == "infer" => {
+                for arg in args {
+                    self.check_expr(arg);
+                }
+                Type::AIResult
+            }
+            ExprKind::ModuleCall(receiver, method, args) if defi::is_protocol(receiver) => {
+                match defi::function(receiver, method) {
+                    // The contract's address comes first
+                    Some(function) if args.len() != function.inputs.len() + 1 => {
+                        self.error("E0207", &[&format!("{}.{}", receiver, method), &(function.inputs.len() + 1), &args.len()], expr.span);
+                    }
+                    Some(_) => {}
+                    None => self.error("E0256", &[receiver, method], expr.span),
+                }
+                for arg in args {
+                    self.check_expr(arg);
+                }
+                // Reads give the decoded value, which the checker cannot
+                // type, or why the call failed; anything else its calldata
+                match defi::function(receiver, method) {
+                    Some(function) if function.view => Type::Result(Box::new(Type::Unknown), Box::new(Type::String)),
+                    _ => Type::String,
+                }
+            }
+            ExprKind::ModuleCall(receiver, method, args) if crate::stdlib::is_stdlib(&format!("{}.{}", receiver, method)) => {
+                let info = crate::stdlib::get_stdlib_info(&format!("{}.{}", receiver, method)).expect("checked by the guard");
+                self.check_builtin_call(info.name, &info.params, false, &info.returns, args, expr.span)
+            }
+            ExprKind::ModuleCall(receiver, method, args) if receiver == "ed25519" || receiver == "bls" => {
+                self.error("E0256", &[receiver, method], expr.span);
+                for arg in args {
+                    self.check_expr(arg);
+                }
+                Type::Unknown
+            }
+            ExprKind::ModuleCall(receiver, method, args)
+                if receiver == "merkle" && matches!(method.as_str(), "root" | "proof" | "verify") =>
+            {
+                let arity = match method.as_str() {
+                    "root" => 1,
+                    "proof" => 2,
+                    _ => 3,
+                };
+                if args.len() != arity {
+                    self.error("E0207", &[&format!("merkle.{}", method), &arity, &args.len()], expr.span);
+                }
+                // Contract code must not depend on building whole trees
+                if self.contract_state.is_some() && method != "verify" {
+                    self.error("E0254", &[method], expr.span);
+                }
+                for arg in args {
+                    self.check_expr(arg);
+                }
+                match method.as_str() {
+                    "root" => Type::String,
+                    "verify" => Type::Bool,
+                    _ => Type::Unknown,
+                }
+            }
+            ExprKind::ModuleCall(receiver, method, args)
+                if receiver == "ipfs" && matches!(method.as_str(), "add" | "get" | "cid" | "valid") =>
+            {
+                if args.len() != 1 {
+                    self.error("E0207", &[&format!("ipfs.{}", method), &1, &args.len()], expr.span);
+                }
+                // A literal CID is checked now rather than when it is fetched
+                if let Some(Expr { kind: ExprKind::String(cid), span }) = args.first().filter(|_| method == "get") {
+                    if let Err(reason) = Cid::parse(cid) {
+                        self.error("E0246", &[cid, &reason], *span);
+                    }
+                }
+                for arg in args {
+                    self.check_expr(arg);
+                }
+                match method.as_str() {
+                    "cid" => Type::String,
+                    "valid" => Type::Bool,
+                    _ => Type::Result(Box::new(Type::String), Box::new(Type::String)),
+                }
+            }
+            ExprKind::ModuleCall(receiver, method, args)
+                if receiver == "web3" && matches!(method.as_str(), "balance" | "code" | "storage" | "deal" | "store") =>
+            {
+                let arity = match method.as_str() {
+                    "balance" | "code" => 1,
+                    "storage" | "deal" => 2,
+                    _ => 3,
+                };
+                if args.len() != arity {
+                    self.error("E0207", &[&format!("web3.{}", method), &arity, &args.len()], expr.span);
+                }
+                for arg in args {
+                    self.check_expr(arg);
+                }
+                match method.as_str() {
+                    // A 256-bit amount, which has no checker type yet
+                    "balance" => Type::Unknown,
+                    "code" | "storage" => Type::String,
+                    _ => Type::Void,
+                }
+            }
+            ExprKind::ModuleCall(receiver, method, args) if receiver == "web3" && method == "multicall" => {
+                // An array of each call's Result, or why the node could not
+                // be read
+                if args.len() != 1 {
+                    self.error("E0207", &[&"web3.multicall", &1, &args.len()], expr.span);
+                }
+                for arg in args {
+                    self.check_expr(arg);
+                }
+                Type::Result(Box::new(Type::Unknown), Box::new(Type::String))
+            }
+            ExprKind::ModuleCall(receiver, method, args)
+                if receiver == "web3" && (method == "sign_typed" || method == "verify_typed") =>
+            {
+                let (arity, expected) = if method == "sign_typed" { (3..=4, "3 or 4") } else { (5..=5, "5") };
+                if !arity.contains(&args.len()) {
+                    self.error("E0207", &[&format!("web3.{}", method), &expected, &args.len()], expr.span);
+                }
+                // A literal type description is checked now rather than
+                // when the script first signs
+                if let Some(Expr { kind: ExprKind::String(types), span }) = args.get(1) {
+                    if let Err(reason) = TypedData::parse(types) {
+                        self.error("E0244", &[types, &reason], *span);
+                    }
+                }
+                for arg in args {
+                    self.check_expr(arg);
+                }
+                if method == "sign_typed" { Type::String } else { Type::Bool }
+            }
+            ExprKind::ModuleCall(receiver, method, args)
+                if matches!(method.as_str(), "push" | "pop" | "set" | "copy" | "append" | "to_string")
+                    && self.lookup(receiver) == Some(&Type::Unknown) =>
+            {
+                // An array, map or string builder method, changing it in
+                // place
+                let arity = match method.as_str() {
+                    "push" | "append" => 1,
+                    "set" => 2,
+                    _ => 0,
+                };
+                if args.len() != arity {
+                    self.error("E0207", &[&format!("{}.{}", receiver, method), &arity, &args.len()], expr.span);
+                }
+                for arg in args {
+                    let arg_type = self.check_expr(arg);
+                    // Only text is appended; str() formats anything else
+                    if method == "append" && !matches!(arg_type, Type::String | Type::Unknown) {
+                        let readable = Self::type_to_readable_name(&arg_type);
+                        self.error("E0208", &[&0, &format!("{}.append", receiver), &"String", &readable], arg.span);
+                    }
+                }
+                match method.as_str() {
+                    "pop" => Type::Option(Box::new(Type::Unknown)),
+                    "copy" => Type::Unknown,
+                    "to_string" => Type::String,
+                    _ => Type::Void,
+                }
+            }
+            ExprKind::ModuleCall(receiver, method, args) => {
+                // A trait method called on a typed variable resolves to its impl
+                let receiver_type = self.lookup(receiver).cloned().unwrap_or(Type::Unknown);
+                let is_trait_method = self.traits.values().flatten().any(|(name, _)| name == method);
+                if receiver_type == Type::Unknown || !is_trait_method {
+                    // Module calls are handled at runtime, assume Int for now
+                    return Type::Int;
+                }
+                let qualified = format!("{}.{}", receiver_type.to_string(), method);
+                let Some(sig) = self.functions.get(&qualified).cloned() else {
+                    self.error("E0238", &[&method, &receiver_type.to_string()], expr.span);
+                    return Type::Unknown;
+                };
+                if args.len() + 1 != sig.params.len() {
+                    self.error("E0207", &[&qualified, &(sig.params.len() - 1), &args.len()], expr.span);
+                }
+                for arg in args {
+                    self.check_expr(arg);
+                }
+                sig.return_type
+            }
+        }
+    }
+
+    /// Check a prelude or stdlib call's arguments against its signature,
+    /// giving the type it returns
+    fn check_builtin_call(
+        &mut self,
+        name: &str,
+        params: &[Type],
+        variadic: bool,
+        returns: &Type,
+        args: &[Expr],
+        span: Span,
+    ) -> Type {
+        if args.len() < params.len() || (args.len() > params.len() && !variadic) {
+            self.error("E0207", &[&name, &params.len(), &args.len()], span);
+        }
+        for (i, arg) in args.iter().enumerate() {
+            let arg_type = self.check_expr(arg);
+            let Some(expected) = params.get(i) else { continue };
+            if arg_type != *expected && arg_type != Type::Unknown && *expected != Type::Unknown {
+                self.error(
+                    "E0208",
+                    &[
+                        &i,
+                        &name,
+                        &Self::type_to_readable_name(expected),
+                        &Self::type_to_readable_name(&arg_type),
+                    ],
+                    arg.span,
+                );
             }
         }
+        returns.clone()
     }
 
-    fn type_to_readable_name(t: &Type) -> &'static str {
-        match t {
-            Type::Int => "Int",
-            Type::Float => "Float",
-            Type::Bool => "Bool",
-            Type::String => "String",
-            Type::Void => "Void",
-            Type::Unknown => "Unknown",
+    /// Under `#[no_prelude]`, report a call only the prelude would answer
+    fn check_prelude(&mut self, name: &str, span: Span) {
+        let declared = self.functions.contains_key(name) || self.lookup(name).is_some();
+        if self.no_prelude && !declared && !self.imported.contains(name) && crate::prelude::get(name).is_some() {
+            self.error("E0270", &[&name], span);
+        }
+    }
+
+    /// Bind a generic function's type parameters to the types of a call's
+    /// arguments, reporting a parameter bound to two different types, and
+    /// return the call's type
+    fn bind_type_params(
+        &mut self,
+        name: &str,
+        generic: &GenericFunction,
+        args: &[Expr],
+        arg_types: &[Type],
+        return_type: Type,
+    ) -> Type {
+        let mut bound: HashMap<&str, Type> = HashMap::new();
+        for ((annotation, arg), arg_type) in generic.param_types.iter().zip(args).zip(arg_types) {
+            let Some(param) = annotation.as_deref().filter(|a| generic.type_params.iter().any(|p| p == a)) else { continue };
+            if *arg_type == Type::Unknown {
+                continue;
+            }
+            match bound.get(param) {
+                Some(earlier) if earlier != arg_type => self.error(
+                    "E0228",
+                    &[&param, &name, &Self::type_to_readable_name(arg_type), &Self::type_to_readable_name(earlier)],
+                    arg.span,
+                ),
+                Some(_) => {}
+                None => {
+                    bound.insert(param, arg_type.clone());
+                }
+            }
+        }
+
+        match generic.return_annotation.as_deref() {
+            Some(annotation) if generic.type_params.iter().any(|p| p == annotation) => {
+                bound.get(annotation).cloned().unwrap_or(Type::Unknown)
+            }
+            _ => return_type,
         }
     }
 
-    pub fn get_errors(&self) -> Vec<String> {
+    /// Record whether a `let` binding may be reassigned
+    fn declare(&mut self, name: &str, mutable: bool, span: Span) {
+        if mutable {
+            self.immutable.remove(name);
+        } else {
+            self.immutable.insert(name.to_string(), span);
+        }
+    }
+
+    /// Bind an arm's payload name to its type in the arm's scope, reporting
+    /// a pattern the value can never match
+    fn bind_pattern(&mut self, arm: &MatchArm, value_type: &Type, span: Span) {
+        let bound = match (&arm.pattern, value_type) {
+            (Pattern::Wildcard, _) | (Pattern::None, Type::Option(_) | Type::Unknown) => return,
+            (Pattern::Some(name), Type::Option(payload))
+            | (Pattern::Ok(name), Type::Result(payload, _))
+            | (Pattern::Err(name), Type::Result(_, payload)) => (name, *payload.clone()),
+            (Pattern::Some(name) | Pattern::Ok(name) | Pattern::Err(name), Type::Unknown) => (name, Type::Unknown),
+            (pattern, _) => {
+                self.error("E0241", &[pattern, &Self::type_to_readable_name(value_type)], span);
+                match pattern {
+                    Pattern::Some(name) | Pattern::Ok(name) | Pattern::Err(name) => (name, Type::Unknown),
+                    _ => return,
+                }
+            }
+        };
+        let (name, payload) = bound;
+        if name != "_" {
+            self.scope().insert(name.clone(), payload);
+        }
+    }
+
+    /// Report the first variant no arm covers. When the value's type is not
+    /// known, the patterns say whether it is an Option or a Result.
+    fn check_exhaustive(&mut self, arms: &[MatchArm], value_type: &Type, span: Span) {
+        let patterns: Vec<&Pattern> = arms.iter().map(|arm| &arm.pattern).collect();
+        if patterns.contains(&&Pattern::Wildcard) {
+            return;
+        }
+        let is_option = match value_type {
+            Type::Option(_) => true,
+            Type::Result(..) => false,
+            _ => patterns.iter().any(|p| matches!(p, Pattern::Some(_) | Pattern::None)),
+        };
+        let missing = if is_option {
+            [("Some(_)", patterns.iter().any(|p| matches!(p, Pattern::Some(_)))), ("None", patterns.contains(&&Pattern::None))]
+        } else {
+            [
+                ("Ok(_)", patterns.iter().any(|p| matches!(p, Pattern::Ok(_)))),
+                ("Err(_)", patterns.iter().any(|p| matches!(p, Pattern::Err(_)))),
+            ]
+        };
+        if let Some((variant, _)) = missing.iter().find(|(_, covered)| !covered) {
+            self.error("E0242", &[variant], span);
+        }
+    }
+
+    fn type_to_readable_name(t: &Type) -> String {
+        t.to_string()
+    }
+
+    pub fn get_errors(&self) -> Vec<CompileError> {
         self.errors.clone()
     }
 }
+
+/// Where the first `return` in a body is, outside nested functions
+fn first_return(body: &[Stmt]) -> Option<Span> {
+    body.iter().find_map(|stmt| match &stmt.kind {
+        StmtKind::Return(_) => Some(stmt.span),
+        StmtKind::If { then_body, else_body, .. } => {
+            first_return(then_body).or_else(|| first_return(else_body.as_deref().unwrap_or_default()))
+        }
+        StmtKind::While { body, .. } | StmtKind::For { body, .. } | StmtKind::Defer(body) => first_return(body),
+        StmtKind::Match { arms, .. } => arms.iter().find_map(|arm| first_return(&arm.body)),
+        _ => None,
+    })
+}
+
+/// The first contract state variable a body assigns and where, skipping
+/// locals that shadow state (`locals` collects `let` bindings as they are seen)
+fn first_state_write(body: &[Stmt], state: &[String], locals: &mut Vec<String>) -> Option<(String, Span)> {
+    for stmt in body {
+        match &stmt.kind {
+            StmtKind::Let { name, .. } => locals.push(name.clone()),
+            StmtKind::LetTuple { names, .. } => locals.extend(names.iter().cloned()),
+            StmtKind::Assign { name, .. } if state.contains(name) && !locals.contains(name) => {
+                return Some((name.clone(), stmt.span));
+            }
+            // Arrays and maps change in place
+            StmtKind::Expression(Expr { kind: ExprKind::ModuleCall(name, method, _), .. })
+            | StmtKind::Value(Expr { kind: ExprKind::ModuleCall(name, method, _), .. })
+                if matches!(method.as_str(), "push" | "pop" | "set" | "append") && state.contains(name) && !locals.contains(name) =>
+            {
+                return Some((name.clone(), stmt.span));
+            }
+            StmtKind::If { then_body, else_body, .. } => {
+                let found = first_state_write(then_body, state, locals)
+                    .or_else(|| first_state_write(else_body.as_deref().unwrap_or(&[]), state, locals));
+                if found.is_some() {
+                    return found;
+                }
+            }
+            StmtKind::While { body, .. } | StmtKind::Defer(body) => {
+                if let Some(write) = first_state_write(body, state, locals) {
+                    return Some(write);
+                }
+            }
+            StmtKind::For { var, body, .. } => {
+                locals.push(var.clone());
+                if let Some(write) = first_state_write(body, state, locals) {
+                    return Some(write);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The candidate `name` is most likely a misspelling of: the nearest by
+/// edit distance, if within a third of the name's length
+fn closest<'a>(name: &str, candidates: impl Iterator<Item = &'a String>) -> Option<&'a str> {
+    let limit = (name.chars().count() / 3).max(1);
+    candidates
+        .filter(|candidate| candidate.as_str() != name)
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= limit)
+        .min()
+        .map(|(_, candidate)| candidate.as_str())
+}
+
+/// Levenshtein distance between `a` and `b`, in characters
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substituted = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substituted.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
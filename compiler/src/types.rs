@@ -1,3 +1,6 @@
+use std::collections::HashMap;
+use crate::ast::{Stmt, StmtKind};
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Type {
     Int,
@@ -6,9 +9,76 @@ pub enum Type {
     String,
     Void,
     Unknown,
+    Tuple(Vec<Type>), // (Int, String): multiple values, e.g. from a return
+    Range,            // start..end over Int
+    Option(Box<Type>),            // Some(value) or None
+    Result(Box<Type>, Box<Type>), // Ok(value) or Err(error)
+    Address,          // An account, such as msg.sender
+    U256,             // A 256-bit unsigned amount, such as msg.value
+    AIResult,         // An inference's label and score
+    Function(Vec<Type>, Box<Type>), // fn(Int, Int) -> Int: a function used as a value
 }
 
 impl Type {
+    /// The type an annotation names, when it is one the checker knows:
+    /// `Int`, `Float`, `Bool`, `String`, `Void`, `Address`, `U256`,
+    /// `AIResult`, a tuple of them, an `Option<T>` or `Result<T, E>` over
+    /// them, or a function type over them: `fn(Int) -> Bool`, or `fn(Int)`
+    /// for one returning nothing
+    pub fn from_annotation(annotation: &str) -> Option<Type> {
+        match annotation {
+            "Int" => Some(Type::Int),
+            "Float" => Some(Type::Float),
+            "Bool" => Some(Type::Bool),
+            "String" => Some(Type::String),
+            "Void" => Some(Type::Void),
+            "Address" => Some(Type::Address),
+            "U256" => Some(Type::U256),
+            "AIResult" => Some(Type::AIResult),
+            _ => {
+                if let Some(inner) = annotation.strip_prefix("Option<").and_then(|a| a.strip_suffix('>')) {
+                    return Some(Type::Option(Box::new(Type::from_annotation(inner)?)));
+                }
+                if let Some(signature) = annotation.strip_prefix("fn(") {
+                    let (params, returns) = split_signature(signature)?;
+                    let params = split_items(params).into_iter().filter(|p| !p.is_empty()).map(Type::from_annotation);
+                    let returns = returns.map_or(Some(Type::Void), Type::from_annotation)?;
+                    return Some(Type::Function(params.collect::<Option<_>>()?, Box::new(returns)));
+                }
+                if let Some(inner) = annotation.strip_prefix("Result<").and_then(|a| a.strip_suffix('>')) {
+                    let items = split_items(inner);
+                    let [value, error] = items.as_slice() else { return None };
+                    let (value, error) = (Type::from_annotation(value)?, Type::from_annotation(error)?);
+                    return Some(Type::Result(Box::new(value), Box::new(error)));
+                }
+                let inner = annotation.strip_prefix('(')?.strip_suffix(')')?;
+                let items = split_items(inner).into_iter().map(Type::from_annotation).collect::<Option<Vec<_>>>()?;
+                Some(Type::Tuple(items))
+            }
+        }
+    }
+
+    /// The type both `self` and `other` describe, where Unknown parts (the
+    /// value of a `None`, say) take the other side's; None if they conflict
+    pub fn unify(&self, other: &Type) -> Option<Type> {
+        match (self, other) {
+            (Type::Unknown, t) | (t, Type::Unknown) => Some(t.clone()),
+            (Type::Option(a), Type::Option(b)) => Some(Type::Option(Box::new(a.unify(b)?))),
+            (Type::Result(a, e), Type::Result(b, f)) => {
+                Some(Type::Result(Box::new(a.unify(b)?), Box::new(e.unify(f)?)))
+            }
+            (Type::Tuple(a), Type::Tuple(b)) if a.len() == b.len() => {
+                a.iter().zip(b).map(|(a, b)| a.unify(b)).collect::<Option<_>>().map(Type::Tuple)
+            }
+            (Type::Function(a, r), Type::Function(b, s)) if a.len() == b.len() => {
+                let params = a.iter().zip(b).map(|(a, b)| a.unify(b)).collect::<Option<_>>()?;
+                Some(Type::Function(params, Box::new(r.unify(s)?)))
+            }
+            (a, b) if a == b => Some(a.clone()),
+            _ => None,
+        }
+    }
+
     pub fn to_string(&self) -> String {
         match self {
             Type::Int => "Int".to_string(),
@@ -17,6 +87,109 @@ impl Type {
             Type::String => "String".to_string(),
             Type::Void => "Void".to_string(),
             Type::Unknown => "Unknown".to_string(),
+            Type::Range => "Range".to_string(),
+            Type::Address => "Address".to_string(),
+            Type::U256 => "U256".to_string(),
+            Type::AIResult => "AIResult".to_string(),
+            Type::Tuple(items) => {
+                let items: Vec<String> = items.iter().map(|t| t.to_string()).collect();
+                format!("({})", items.join(", "))
+            }
+            Type::Option(value) => format!("Option<{}>", value.to_string()),
+            Type::Result(value, error) => format!("Result<{}, {}>", value.to_string(), error.to_string()),
+            Type::Function(params, returns) => {
+                let params: Vec<String> = params.iter().map(|t| t.to_string()).collect();
+                match returns.as_ref() {
+                    Type::Void => format!("fn({})", params.join(", ")),
+                    returns => format!("fn({}) -> {}", params.join(", "), returns.to_string()),
+                }
+            }
+        }
+    }
+}
+
+/// `type Wei = U256` declarations: each alias and the annotation it stands
+/// for, as written
+pub type Aliases = HashMap<String, String>;
+
+/// The type aliases `program` declares
+pub fn aliases(program: &[Stmt]) -> Aliases {
+    program
+        .iter()
+        .filter_map(|stmt| match &stmt.kind {
+            StmtKind::TypeAlias { name, target } => Some((name.clone(), target.clone())),
+            _ => None,
+        })
+        .collect()
+}
+
+/// `annotation` with every alias in it replaced by what it stands for,
+/// through aliases of aliases: `Option<Wei>` is `Option<U256>`. Err names
+/// an alias that stands for itself.
+pub fn expand_aliases(annotation: &str, aliases: &Aliases) -> Result<String, String> {
+    expand(annotation, aliases, &mut Vec::new())
+}
+
+fn expand<'a>(annotation: &str, aliases: &'a Aliases, expanding: &mut Vec<&'a str>) -> Result<String, String> {
+    let is_word = crate::text::is_ident_continue;
+    let mut text = String::new();
+    let mut rest = annotation;
+    while let Some(start) = rest.find(is_word) {
+        let end = rest[start..].find(|c| !is_word(c)).map_or(rest.len(), |len| start + len);
+        text.push_str(&rest[..start]);
+        match aliases.get_key_value(&rest[start..end]) {
+            Some((name, _)) if expanding.contains(&name.as_str()) => return Err(name.clone()),
+            Some((name, target)) => {
+                expanding.push(name);
+                text.push_str(&expand(target, aliases, expanding)?);
+                expanding.pop();
+            }
+            None => text.push_str(&rest[start..end]),
+        }
+        rest = &rest[end..];
+    }
+    text.push_str(rest);
+    Ok(text)
+}
+
+/// `A, B) -> R`, the rest of a function type after `fn(`, as its parameter
+/// list and its return type if it has one
+fn split_signature(signature: &str) -> Option<(&str, Option<&str>)> {
+    let mut depth = 0;
+    for (i, c) in signature.char_indices() {
+        match c {
+            '<' | '(' => depth += 1,
+            '>' if depth > 0 => depth -= 1,
+            ')' if depth == 0 => {
+                let rest = signature[i + 1..].trim();
+                return match rest.strip_prefix("->") {
+                    Some(returns) => Some((&signature[..i], Some(returns.trim()))),
+                    None if rest.is_empty() => Some((&signature[..i], None)),
+                    None => None,
+                };
+            }
+            ')' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// `A, B` split at the commas outside any nested `<>` or `()`
+fn split_items(list: &str) -> Vec<&str> {
+    let mut items = Vec::new();
+    let (mut depth, mut start) = (0, 0);
+    for (i, c) in list.char_indices() {
+        match c {
+            '<' | '(' => depth += 1,
+            '>' | ')' => depth -= 1,
+            ',' if depth == 0 => {
+                items.push(list[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
         }
     }
+    items.push(list[start..].trim());
+    items
 }
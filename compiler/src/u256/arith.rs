@@ -0,0 +1,69 @@
+// 256-bit arithmetic as the EVM does it, shared by the checker and the
+// runtimes (see the parent module).
+
+pub use primitive_types::U256;
+use primitive_types::U512;
+
+/// The functions of the `u256` module and how many arguments each takes
+pub const FUNCTIONS: &[(&str, usize)] = &[
+    ("from", 1),
+    ("addmod", 3),
+    ("mulmod", 3),
+    ("shl", 2),
+    ("shr", 2),
+    ("saturating_add", 2),
+    ("saturating_sub", 2),
+    ("saturating_mul", 2),
+];
+
+/// How many arguments `u256.<function>` takes, if it exists
+pub fn arity(function: &str) -> Option<usize> {
+    FUNCTIONS.iter().find(|(name, _)| *name == function).map(|(_, arity)| *arity)
+}
+
+/// `(a + b) % modulus` without overflow (ADDMOD); 0 when `modulus` is 0
+pub fn addmod(a: U256, b: U256, modulus: U256) -> U256 {
+    if modulus.is_zero() {
+        return U256::zero();
+    }
+    narrow((U512::from(a) + U512::from(b)) % U512::from(modulus))
+}
+
+/// `(a * b) % modulus` without overflow (MULMOD); 0 when `modulus` is 0
+pub fn mulmod(a: U256, b: U256, modulus: U256) -> U256 {
+    if modulus.is_zero() {
+        return U256::zero();
+    }
+    narrow(a.full_mul(b) % U512::from(modulus))
+}
+
+/// `value << bits` (SHL); 0 once every bit is shifted out
+pub fn shl(value: U256, bits: U256) -> U256 {
+    if bits >= U256::from(256) {
+        return U256::zero();
+    }
+    value << bits.as_usize()
+}
+
+/// `value >> bits` (SHR); 0 once every bit is shifted out
+pub fn shr(value: U256, bits: U256) -> U256 {
+    if bits >= U256::from(256) {
+        return U256::zero();
+    }
+    value >> bits.as_usize()
+}
+
+/// A remainder by a 256-bit modulus, which always fits
+fn narrow(value: U512) -> U256 {
+    U256::try_from(value).expect("remainder is below the modulus")
+}
+
+/// A decimal or 0x-prefixed hex string, if it fits in 256 bits
+pub fn parse(text: &str) -> Option<U256> {
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) if !hex.is_empty() && hex.len() <= 64 => U256::from_str_radix(hex, 16).ok(),
+        Some(_) => None,
+        None if !text.is_empty() && text.bytes().all(|b| b.is_ascii_digit()) => U256::from_dec_str(text).ok(),
+        None => None,
+    }
+}
@@ -0,0 +1,88 @@
+use crate::interpreter::Value;
+use crate::messages;
+
+pub mod arith;
+pub use arith::U256;
+
+// 256-bit unsigned arithmetic, the `u256` module of scripts.
+//
+//   let price = u256.from("115792089237316195423570985008687907853269984665640564039457584007913129639935")
+//   let fee = u256.mulmod(price, 3, 1000)
+//   let reserve = u256.saturating_sub(reserve, amount)
+//
+// `u256.from(value)` makes a u256 from a non-negative Int or a decimal or
+// 0x-prefixed hex string; every other function takes those too, so small
+// constants can be written as Int literals. Results never lose precision:
+// `addmod` and `mulmod` work on the full 512-bit intermediate, as the EVM's
+// ADDMOD and MULMOD do, and like them answer 0 for a modulus of 0. `shl`
+// and `shr` shift a value by a number of bits, 0 once every bit is shifted
+// out (SHL and SHR, with the value first). `saturating_add`,
+// `saturating_sub` and `saturating_mul` stop at 0 and 2^256 - 1 instead of
+// wrapping. The interpreter and the VM both call `call`, so the two agree
+// on every result; WASM builds do not support the module yet.
+
+/// Call `u256.<function>` with `args`
+pub fn call(function: &str, args: &[Value]) -> Result<Value, String> {
+    let expected = arith::arity(function).ok_or_else(|| messages::render("E0406", &[&format!("u256.{}", function)]))?;
+    if args.len() != expected {
+        return Err(messages::render("E0467", &[&function, &format!("{} arguments, got {}", expected, args.len())]));
+    }
+    let operands = args
+        .iter()
+        .map(|arg| operand(function, arg))
+        .collect::<Result<Vec<U256>, String>>()?;
+    let result = match (function, operands.as_slice()) {
+        ("from", [value]) => *value,
+        ("addmod", [a, b, modulus]) => arith::addmod(*a, *b, *modulus),
+        ("mulmod", [a, b, modulus]) => arith::mulmod(*a, *b, *modulus),
+        ("shl", [value, bits]) => arith::shl(*value, *bits),
+        ("shr", [value, bits]) => arith::shr(*value, *bits),
+        ("saturating_add", [a, b]) => a.saturating_add(*b),
+        ("saturating_sub", [a, b]) => a.saturating_sub(*b),
+        ("saturating_mul", [a, b]) => a.saturating_mul(*b),
+        _ => unreachable!("arity checked above"),
+    };
+    Ok(Value::U256(result))
+}
+
+/// An argument of `u256.<function>`: a u256, a non-negative Int, or a
+/// decimal or hex string
+fn operand(function: &str, value: &Value) -> Result<U256, String> {
+    match value {
+        Value::U256(n) => Ok(*n),
+        Value::Number(n) => u64::try_from(*n).map(U256::from).map_err(|_| messages::render("E0468", &[n])),
+        Value::String(text) => arith::parse(text).ok_or_else(|| messages::render("E0468", &[&format!("\"{}\"", text)])),
+        other => Err(messages::render(
+            "E0467",
+            &[&function, &format!("u256, number or string arguments, got a {}", crate::interpreter::type_name(other))],
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::arith::{addmod, mulmod, parse, shl, shr};
+
+    #[test]
+    fn test_modular_arithmetic_keeps_full_precision() {
+        let max = U256::MAX;
+        // (2^256 - 1)^2 overflows 256 bits; the remainder does not
+        assert_eq!(mulmod(max, max, U256::from(7)), U256::from(1));
+        assert_eq!(addmod(max, U256::from(2), max), U256::from(2));
+        assert_eq!(mulmod(max, max, U256::zero()), U256::zero());
+
+        assert_eq!(shl(U256::one(), U256::from(255)), U256::one() << 255);
+        assert_eq!(shl(U256::one(), U256::from(256)), U256::zero());
+        assert_eq!(shr(max, U256::from(252)), U256::from(15));
+
+        assert_eq!(call("saturating_add", &[Value::U256(max), Value::Number(1)]), Ok(Value::U256(max)));
+        assert_eq!(call("saturating_sub", &[Value::Number(1), Value::Number(2)]), Ok(Value::U256(U256::zero())));
+
+        assert_eq!(parse("0xff"), Some(U256::from(255)));
+        assert_eq!(parse(&max.to_string()), Some(max));
+        assert_eq!(parse("115792089237316195423570985008687907853269984665640564039457584007913129639936"), None);
+        assert!(call("from", &[Value::Number(-1)]).is_err());
+        assert!(call("shl", &[Value::Number(1)]).is_err());
+    }
+}
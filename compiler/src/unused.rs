@@ -0,0 +1,262 @@
+use std::collections::HashSet;
+use crate::ast::{Attribute, Expr, ExprKind, Pattern, Span, Stmt, StmtKind};
+use crate::edition::Warning;
+
+// Unused code, checked after type checking:
+//
+//   fn total(prices, tax) {   // W0302: tax is never used
+//       let count = 0         // W0301
+//       return sum(prices)
+//   }
+//   fn helper() { ... }       // W0303, unless something calls it
+//
+// A parameter or `let` binding is used when something reads it; assigning
+// to it does not count. A function is used when another function calls it
+// or refers to it by name, so recursion alone does not count either. `main`,
+// exported functions, #[test] functions, contract methods and trait impls
+// are entry points and never reported. Names starting with `_` are never
+// reported, which is how code marks them unused on purpose.
+
+/// Unused variable, parameter and function warnings for `stmts`, in source
+/// order
+pub fn analyze(stmts: &[Stmt]) -> Vec<Warning> {
+    analyze_parts(&[stmts]).into_iter().map(|(_, warning)| warning).collect()
+}
+
+/// Warnings for a program given in parts, such as a document's top-level
+/// items, each with the index of the part it is in. Spans are those of the
+/// part's own statements.
+pub fn analyze_parts(parts: &[&[Stmt]]) -> Vec<(usize, Warning)> {
+    let mut analysis = Analysis { part: 0, scopes: Vec::new(), functions: Vec::new(), referenced: HashSet::new(), enclosing: Vec::new(), warnings: Vec::new() };
+    for (part, stmts) in parts.iter().enumerate() {
+        analysis.part = part;
+        analysis.block(stmts, true);
+    }
+
+    let Analysis { functions, referenced, mut warnings, .. } = analysis;
+    for (part, name, span) in functions {
+        if !referenced.contains(&name) {
+            warnings.push((part, Warning::new("W0303", &[&name], span, None)));
+        }
+    }
+    warnings.sort_by_key(|(part, warning)| (*part, warning.span.start_line, warning.span.start_column));
+    warnings
+}
+
+/// A name in scope and whether anything has read it
+struct Binding {
+    name: String,
+    code: Option<&'static str>, // Warning if never read; None for names not checked
+    span: Span,
+    used: bool,
+}
+
+struct Analysis {
+    part: usize,
+    scopes: Vec<Vec<Binding>>,
+    functions: Vec<(usize, String, Span)>, // Functions that must be referenced, with their part
+    referenced: HashSet<String>,           // Function names called or named outside their own body
+    enclosing: Vec<String>,                // Functions being visited, innermost last
+    warnings: Vec<(usize, Warning)>,
+}
+
+impl Analysis {
+    fn block(&mut self, body: &[Stmt], top_level: bool) {
+        self.scopes.push(Vec::new());
+        for stmt in body {
+            self.stmt(stmt, top_level);
+        }
+        self.pop_scope();
+    }
+
+    fn stmt(&mut self, stmt: &Stmt, top_level: bool) {
+        match &stmt.kind {
+            StmtKind::Function { name, exported, attributes, .. } => {
+                let entry = (top_level && name == "main") || *exported || attributes.contains(&Attribute::Test);
+                if !entry && !name.starts_with('_') {
+                    self.functions.push((self.part, name.clone(), stmt.span));
+                }
+                self.function(stmt);
+            }
+            StmtKind::Contract { constructor, methods, .. } => {
+                for method in constructor.as_deref().into_iter().chain(methods) {
+                    self.function(method);
+                }
+            }
+            StmtKind::Impl { methods, .. } => {
+                for method in methods {
+                    self.function(method);
+                }
+            }
+            StmtKind::Let { name, value, .. } => {
+                self.expr(value);
+                self.bind(name, Some("W0301"), stmt.span);
+            }
+            StmtKind::LetTuple { names, value, .. } => {
+                self.expr(value);
+                for name in names.iter().filter(|name| *name != "_") {
+                    self.bind(name, Some("W0301"), stmt.span);
+                }
+            }
+            StmtKind::Assign { value, .. } => self.expr(value),
+            StmtKind::Expression(expr) | StmtKind::Return(expr) | StmtKind::Value(expr) | StmtKind::Panic(expr) => self.expr(expr),
+            StmtKind::Defer(body) => self.block(body, false),
+            StmtKind::If { condition, then_body, else_body } => {
+                self.expr(condition);
+                self.block(then_body, false);
+                if let Some(else_body) = else_body {
+                    self.block(else_body, false);
+                }
+            }
+            StmtKind::While { condition, body } => {
+                self.expr(condition);
+                self.block(body, false);
+            }
+            StmtKind::For { var, iterable, body } => {
+                self.expr(iterable);
+                self.scopes.push(Vec::new());
+                self.bind(var, None, stmt.span);
+                self.block(body, false);
+                self.pop_scope();
+            }
+            StmtKind::Match { value, arms } => {
+                self.expr(value);
+                for arm in arms {
+                    self.scopes.push(Vec::new());
+                    if let Pattern::Some(name) | Pattern::Ok(name) | Pattern::Err(name) = &arm.pattern {
+                        self.bind(name, None, stmt.span);
+                    }
+                    self.block(&arm.body, false);
+                    self.pop_scope();
+                }
+            }
+            StmtKind::Import { .. } | StmtKind::Trait { .. } | StmtKind::TypeAlias { .. } => {}
+        }
+    }
+
+    /// Check a function's parameters and body
+    fn function(&mut self, function: &Stmt) {
+        let StmtKind::Function { name, params, defaults, body, conditions, .. } = &function.kind else { return };
+        for default in defaults.iter().flatten() {
+            self.expr(default);
+        }
+
+        self.enclosing.push(name.clone());
+        self.scopes.push(Vec::new());
+        for param in params.iter().filter(|param| *param != "self") {
+            self.bind(param, Some("W0302"), function.span);
+        }
+        for condition in conditions {
+            self.expr(&condition.expr);
+        }
+        self.block(body, false);
+        self.pop_scope();
+        self.enclosing.pop();
+    }
+
+    fn bind(&mut self, name: &str, code: Option<&'static str>, span: Span) {
+        let code = code.filter(|_| !name.starts_with('_'));
+        let scope = self.scopes.last_mut().expect("bindings are made inside a scope");
+        scope.push(Binding { name: name.to_string(), code, span, used: false });
+    }
+
+    fn pop_scope(&mut self) {
+        let scope = self.scopes.pop().expect("scopes are balanced");
+        for binding in scope {
+            if let (Some(code), false) = (binding.code, binding.used) {
+                self.warnings.push((self.part, Warning::new(code, &[&binding.name], binding.span, None)));
+            }
+        }
+    }
+
+    /// Mark what `name` refers to as used: the innermost binding, or else a
+    /// function
+    fn read(&mut self, name: &str) {
+        let binding = self.scopes.iter_mut().rev().flat_map(|scope| scope.iter_mut().rev()).find(|b| b.name == name);
+        match binding {
+            Some(binding) => binding.used = true,
+            None if self.enclosing.last().is_some_and(|current| current == name) => {}
+            None => {
+                self.referenced.insert(name.to_string());
+            }
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match &expr.kind {
+            ExprKind::Identifier(name) => self.read(name),
+            ExprKind::Call(name, args) => {
+                self.read(name);
+                for arg in args {
+                    self.expr(arg);
+                }
+            }
+            ExprKind::ModuleCall(receiver, _, args) => {
+                self.read(receiver);
+                for arg in args {
+                    self.expr(arg);
+                }
+            }
+            ExprKind::Property(object, _) => self.read(object),
+            ExprKind::Tuple(items) | ExprKind::Array(items) => {
+                for item in items {
+                    self.expr(item);
+                }
+            }
+            ExprKind::Named(_, inner) | ExprKind::Await(inner) | ExprKind::Try(inner) | ExprKind::Cast(inner, _) => self.expr(inner),
+            ExprKind::Range(l, r) | ExprKind::Slice(l, r)
+            | ExprKind::Add(l, r) | ExprKind::Sub(l, r) | ExprKind::Mul(l, r) | ExprKind::Div(l, r) | ExprKind::Mod(l, r)
+            | ExprKind::Eq(l, r) | ExprKind::Ne(l, r) | ExprKind::Lt(l, r) | ExprKind::Le(l, r)
+            | ExprKind::Gt(l, r) | ExprKind::Ge(l, r)
+            | ExprKind::And(l, r) | ExprKind::Or(l, r) => {
+                self.expr(l);
+                self.expr(r);
+            }
+            ExprKind::Number(_) | ExprKind::Float(_) | ExprKind::Bool(_) | ExprKind::String(_) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn analyze_source(source: &str) -> Vec<Warning> {
+        analyze(&Parser::new(Lexer::new(source)).parse().unwrap())
+    }
+
+    #[test]
+    fn test_unused_bindings_and_functions_warn() {
+        let warnings = analyze_source(r#"
+            fn total(prices, tax, _region) {
+                let count = 0
+                let mut last = 0
+                last = prices
+                let (low, _) = (1, 2)
+                let _skipped = 3
+                return prices * 2
+            }
+            fn countdown(n) {
+                if n > 0 {
+                    return countdown(n - 1)
+                }
+                return 0
+            }
+            fn _scratch() {
+                return 1
+            }
+            fn main() {
+                let price = 10
+                print(total(price, 2, 3))
+            }
+        "#);
+
+        let found: Vec<(&str, usize)> = warnings.iter().map(|w| (w.code, w.span.start_line)).collect();
+        assert_eq!(found, vec![("W0302", 2), ("W0301", 3), ("W0301", 4), ("W0301", 6), ("W0303", 10)]);
+        assert!(warnings[0].message.contains("'tax'"), "{}", warnings[0].message);
+        assert!(warnings[2].message.contains("'last'"), "{}", warnings[2].message);
+        assert!(warnings[4].message.contains("'countdown'"), "{}", warnings[4].message);
+    }
+}
@@ -0,0 +1,643 @@
+use std::collections::HashMap;
+use crate::ast::{ConditionKind, Span, Stmt, StmtKind};
+use crate::ir::{IRFunction, IRInstr, IRModule};
+
+// Symbolic checking of #[requires] and #[ensures].
+//
+//   #[requires(amount > 0)]
+//   #[ensures(result < balance)]
+//   fn withdraw(amount) { ... }
+//
+// `astrixa verify` runs each contract method with an #[ensures] over its
+// lowered IR with symbolic arguments and state: values are expressions over
+// the method's inputs, and a branch on one forks the path. Each #[requires]
+// is assumed on entry, and each #[ensures] is evaluated against every path
+// that returns (a path that reverts promises nothing). A path whose
+// condition can hold while an #[ensures] is false is a counterexample;
+// concrete inputs for it are searched for among small values and the
+// constants the method and its conditions mention, so a reported
+// counterexample is always real, while a clean run is evidence rather than
+// proof.
+//
+// The search is bounded: a path is given up after `depth` symbolic
+// branches or on reaching a value the analysis cannot follow (strings,
+// runtime calls), and such paths are counted as unchecked.
+
+/// Most symbolic branches on one path, unless configured otherwise
+pub const DEFAULT_DEPTH: usize = 8;
+
+/// Most nested calls followed into other functions
+const CALL_DEPTH: usize = 4;
+
+/// Most instructions one path may execute, so loops cannot run forever
+const STEP_LIMIT: usize = 10_000;
+
+/// Most assignments tried when searching for concrete inputs
+const SEARCH_LIMIT: usize = 100_000;
+
+/// A method whose #[ensures] conditions were checked
+#[derive(Debug, Clone)]
+pub struct Report {
+    pub function: String, // `Vault.withdraw`
+    pub ensures: usize,
+    pub paths: usize,     // Returning paths checked
+    pub unchecked: usize, // Paths given up on
+    pub counterexample: Option<Counterexample>,
+}
+
+/// Inputs that satisfy a method's #[requires] and break one of its #[ensures]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Counterexample {
+    pub condition: Span,            // The #[ensures] that fails
+    pub inputs: Vec<(String, i64)>, // Arguments, state and host values it depends on
+    pub result: Option<i64>,        // What the method returned, when it depends on the inputs only
+}
+
+/// Check every contract method in `stmts` that declares an #[ensures]
+pub fn verify(stmts: &[Stmt], depth: usize) -> Vec<Report> {
+    let mut reports = Vec::new();
+    for (index, stmt) in stmts.iter().enumerate() {
+        let StmtKind::Contract { name, state, constructor, methods, .. } = &stmt.kind else { continue };
+        for method in constructor.as_deref().into_iter().chain(methods) {
+            let StmtKind::Function { conditions, .. } = &method.kind else { continue };
+            if conditions.iter().any(|c| c.kind == ConditionKind::Ensures) {
+                reports.push(verify_method(stmts, index, name, state, method, depth));
+            }
+        }
+    }
+    reports
+}
+
+fn verify_method(stmts: &[Stmt], index: usize, contract: &str, state: &[String], method: &Stmt, depth: usize) -> Report {
+    let StmtKind::Function { name, params, conditions, .. } = &method.kind else { unreachable!("verify passes methods") };
+    let qualified = format!("{}.{}", contract, name);
+
+    // Each condition becomes a method of its own returning it, lowered with
+    // the program so that it reads state and calls functions as the method does
+    let mut program = stmts.to_vec();
+    let mut condition_functions = Vec::new();
+    if let StmtKind::Contract { methods, .. } = &mut program[index].kind {
+        for (i, condition) in conditions.iter().enumerate() {
+            let mut function = method.clone();
+            let condition_name = format!("{}.{}{}", name, condition.kind.name(), i);
+            if let StmtKind::Function { name, params, param_types, defaults, body, attributes, conditions, .. } = &mut function.kind {
+                *name = condition_name.clone();
+                if condition.kind == ConditionKind::Ensures {
+                    params.push("result".to_string());
+                    param_types.push(None);
+                    defaults.push(None);
+                }
+                *body = vec![Stmt::new(StmtKind::Return(condition.expr.clone()), condition.expr.span)];
+                attributes.clear();
+                conditions.clear();
+            }
+            methods.push(function);
+            condition_functions.push((condition.kind, format!("{}.{}", contract, condition_name), condition.expr.span));
+        }
+    }
+    let module = crate::lowering::lower(&program);
+
+    // Arguments are the first inputs, then state as it is first read
+    let mut inputs: Vec<String> = params.clone();
+    let globals: HashMap<String, Sym> = state
+        .iter()
+        .map(|var| {
+            inputs.push(var.clone());
+            (format!("{}.{}", contract, var), Sym::Input(inputs.len() - 1))
+        })
+        .collect();
+    let args: Vec<Sym> = (0..params.len()).map(Sym::Input).collect();
+
+    let mut explorer = Explorer { module: &module, inputs, depth, unchecked: 0 };
+    let mut report = Report { function: qualified.clone(), ensures: 0, paths: 0, unchecked: 0, counterexample: None };
+
+    // Assume every #[requires] on entry
+    let mut starts = vec![Path { globals, assumed: Vec::new(), result: Vec::new() }];
+    for (_, function, _) in condition_functions.iter().filter(|(kind, ..)| *kind == ConditionKind::Requires) {
+        starts = starts
+            .into_iter()
+            .flat_map(|start| explorer.run(function, args.clone(), &start))
+            .filter_map(|mut path| {
+                let holds = path.result.first()?.clone();
+                path.assumed.push(holds);
+                Some(Path { result: Vec::new(), ..path })
+            })
+            .collect();
+    }
+
+    let ends: Vec<Path> = starts.iter().flat_map(|start| explorer.run(&qualified, args.clone(), start)).collect();
+    report.paths = ends.len();
+
+    'check: for (_, function, span) in condition_functions.iter().filter(|(kind, ..)| *kind == ConditionKind::Ensures) {
+        report.ensures += 1;
+        for end in &ends {
+            let result = match end.result.as_slice() {
+                [value] => value.clone(),
+                _ => Sym::Opaque,
+            };
+            let mut with_result = args.clone();
+            with_result.push(result.clone());
+            for checked in explorer.run(function, with_result, end) {
+                let Some(holds) = checked.result.first() else { continue };
+                if holds.is_opaque() {
+                    explorer.unchecked += 1;
+                    continue;
+                }
+                let mut query = checked.assumed.clone();
+                query.push(Sym::not(holds.clone()));
+                if let Some(values) = solve(&query, explorer.inputs.len()) {
+                    let inputs = used_inputs(&query)
+                        .into_iter()
+                        .map(|i| (explorer.inputs[i].clone(), values[i]))
+                        .collect();
+                    let result = result.eval(&values);
+                    report.counterexample = Some(Counterexample { condition: *span, inputs, result });
+                    break 'check;
+                }
+            }
+        }
+    }
+
+    report.unchecked = explorer.unchecked;
+    report
+}
+
+/// A value on one path: an expression over the method's inputs. Booleans
+/// are 0 and 1, as the IR treats them.
+#[derive(Debug, Clone, PartialEq)]
+enum Sym {
+    Int(i64),
+    Input(usize),
+    Op(Op, Box<Sym>, Box<Sym>),
+    Opaque, // A value the analysis does not follow: a string, a runtime call
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+}
+
+impl Op {
+    /// `a op b`, or None where the program would trap: division by zero,
+    /// or arithmetic overflow, which the runtime rejects
+    fn apply(self, a: i64, b: i64) -> Option<i64> {
+        match self {
+            Op::Add => a.checked_add(b),
+            Op::Sub => a.checked_sub(b),
+            Op::Mul => a.checked_mul(b),
+            Op::Div => a.checked_div(b),
+            Op::Mod => a.checked_rem(b),
+            Op::Eq => Some((a == b) as i64),
+            Op::Ne => Some((a != b) as i64),
+            Op::Lt => Some((a < b) as i64),
+            Op::Le => Some((a <= b) as i64),
+            Op::Gt => Some((a > b) as i64),
+            Op::Ge => Some((a >= b) as i64),
+            Op::And => Some((a != 0 && b != 0) as i64),
+            Op::Or => Some((a != 0 || b != 0) as i64),
+        }
+    }
+}
+
+impl Sym {
+    /// `a op b`, folded when both are known
+    fn op(op: Op, a: Sym, b: Sym) -> Sym {
+        match (&a, &b) {
+            (Sym::Opaque, _) | (_, Sym::Opaque) => Sym::Opaque,
+            (Sym::Int(x), Sym::Int(y)) => op.apply(*x, *y).map_or_else(|| Sym::Op(op, Box::new(a.clone()), Box::new(b.clone())), Sym::Int),
+            _ => Sym::Op(op, Box::new(a), Box::new(b)),
+        }
+    }
+
+    fn not(a: Sym) -> Sym {
+        Sym::op(Op::Eq, a, Sym::Int(0))
+    }
+
+    fn is_opaque(&self) -> bool {
+        match self {
+            Sym::Opaque => true,
+            Sym::Op(_, a, b) => a.is_opaque() || b.is_opaque(),
+            _ => false,
+        }
+    }
+
+    /// The value under `inputs`, or None where the program would trap
+    fn eval(&self, inputs: &[i64]) -> Option<i64> {
+        match self {
+            Sym::Int(n) => Some(*n),
+            Sym::Input(i) => inputs.get(*i).copied(),
+            Sym::Op(op, a, b) => op.apply(a.eval(inputs)?, b.eval(inputs)?),
+            Sym::Opaque => None,
+        }
+    }
+
+    fn visit(&self, f: &mut impl FnMut(&Sym)) {
+        f(self);
+        if let Sym::Op(_, a, b) = self {
+            a.visit(f);
+            b.visit(f);
+        }
+    }
+}
+
+/// One way through a function: what it assumed to get there, the state it
+/// left and what it returned
+#[derive(Debug, Clone)]
+struct Path {
+    globals: HashMap<String, Sym>,
+    assumed: Vec<Sym>, // Each non-zero on this path
+    result: Vec<Sym>,
+}
+
+/// A call in progress on a path
+#[derive(Debug, Clone)]
+struct Frame {
+    function: usize, // Index into the module's functions
+    pc: usize,
+    locals: Vec<Sym>,
+    vars: HashMap<String, Sym>, // Legacy named variables
+    stack: Vec<Sym>,
+}
+
+#[derive(Debug, Clone)]
+struct State {
+    frames: Vec<Frame>,
+    path: Path,
+    branches: usize,
+    steps: usize,
+}
+
+struct Explorer<'a> {
+    module: &'a IRModule,
+    inputs: Vec<String>, // Names of the inputs, which grow as host values are read
+    depth: usize,
+    unchecked: usize,
+}
+
+impl Explorer<'_> {
+    /// Every path through `function` called with `args` from `start` that
+    /// returns; paths that revert end quietly and paths given up on are
+    /// counted as unchecked
+    fn run(&mut self, function: &str, args: Vec<Sym>, start: &Path) -> Vec<Path> {
+        let Some(index) = self.module.functions.iter().position(|f| f.name == function) else {
+            self.unchecked += 1;
+            return Vec::new();
+        };
+        let mut pending = vec![State {
+            frames: vec![self.frame(index, args)],
+            path: Path { result: Vec::new(), ..start.clone() },
+            branches: 0,
+            steps: 0,
+        }];
+        let mut done = Vec::new();
+        while let Some(state) = pending.pop() {
+            match self.step(state) {
+                Step::Continue(states) => pending.extend(states),
+                Step::Returned(path) => done.push(path),
+                Step::Reverted => {}
+                Step::GaveUp => self.unchecked += 1,
+            }
+        }
+        done
+    }
+
+    fn frame(&self, function: usize, args: Vec<Sym>) -> Frame {
+        let f: &IRFunction = &self.module.functions[function];
+        let mut locals = args;
+        locals.resize(f.local_count.max(f.param_count).max(locals.len()), Sym::Int(0));
+        Frame { function, pc: 0, locals, vars: HashMap::new(), stack: Vec::new() }
+    }
+
+    fn input(&mut self, name: &str) -> Sym {
+        let index = self.inputs.iter().position(|input| input == name).unwrap_or_else(|| {
+            self.inputs.push(name.to_string());
+            self.inputs.len() - 1
+        });
+        Sym::Input(index)
+    }
+
+    /// Run `state` to its next branch, return or end
+    fn step(&mut self, mut state: State) -> Step {
+        loop {
+            state.steps += 1;
+            if state.steps > STEP_LIMIT {
+                return Step::GaveUp;
+            }
+            let nested = state.frames.len();
+            let frame = state.frames.last_mut().expect("a running path has a frame");
+            let function = &self.module.functions[frame.function];
+            let Some(instr) = function.instructions.get(frame.pc) else {
+                // Lowering ends every function with a return; treat falling
+                // off the end as returning nothing
+                frame.stack.clear();
+                frame.pc = function.instructions.len();
+                if let Some(path) = self.ret(&mut state, 0) {
+                    return Step::Returned(path);
+                }
+                continue;
+            };
+            frame.pc += 1;
+
+            match instr {
+                IRInstr::LoadConstInt(n) => frame.stack.push(Sym::Int(*n)),
+                IRInstr::LoadConstBool(b) => frame.stack.push(Sym::Int(*b as i64)),
+                IRInstr::LoadConstFloat(_) | IRInstr::LoadConstString(_) => frame.stack.push(Sym::Opaque),
+                IRInstr::LoadLocal(slot) => {
+                    let value = frame.locals.get(*slot as usize).cloned().unwrap_or(Sym::Int(0));
+                    frame.stack.push(value);
+                }
+                IRInstr::StoreLocal(slot) => {
+                    let value = frame.stack.pop().unwrap_or(Sym::Opaque);
+                    let slot = *slot as usize;
+                    if slot >= frame.locals.len() {
+                        frame.locals.resize(slot + 1, Sym::Int(0));
+                    }
+                    frame.locals[slot] = value;
+                }
+                IRInstr::LoadVar(name) => {
+                    let value = frame.vars.get(name).cloned();
+                    let value = value.unwrap_or_else(|| self.input(name));
+                    state.frames.last_mut().expect("checked above").stack.push(value);
+                }
+                IRInstr::StoreVar(name) => {
+                    let value = frame.stack.pop().unwrap_or(Sym::Opaque);
+                    frame.vars.insert(name.clone(), value);
+                }
+                IRInstr::LoadGlobal(name) => {
+                    let value = state.path.globals.get(name).cloned();
+                    let value = value.unwrap_or_else(|| self.input(name));
+                    state.frames.last_mut().expect("checked above").stack.push(value);
+                }
+                IRInstr::StoreGlobal(name) => {
+                    let value = frame.stack.pop().unwrap_or(Sym::Opaque);
+                    state.path.globals.insert(name.clone(), value);
+                }
+                IRInstr::LoadProperty(name) => {
+                    let value = self.input(name);
+                    state.frames.last_mut().expect("checked above").stack.push(value);
+                }
+                IRInstr::LoadFunction(_) => frame.stack.push(Sym::Opaque),
+                IRInstr::Add | IRInstr::Sub | IRInstr::Mul | IRInstr::Div | IRInstr::Mod
+                | IRInstr::CheckedAdd(_) | IRInstr::CheckedSub(_) | IRInstr::CheckedMul(_)
+                | IRInstr::Eq | IRInstr::Ne | IRInstr::Lt | IRInstr::Le | IRInstr::Gt | IRInstr::Ge
+                | IRInstr::And | IRInstr::Or => {
+                    let b = frame.stack.pop().unwrap_or(Sym::Opaque);
+                    let a = frame.stack.pop().unwrap_or(Sym::Opaque);
+                    frame.stack.push(Sym::op(binary_op(instr), a, b));
+                }
+                IRInstr::Not => {
+                    let a = frame.stack.pop().unwrap_or(Sym::Opaque);
+                    frame.stack.push(Sym::not(a));
+                }
+                IRInstr::Pop => {
+                    frame.stack.pop();
+                }
+                // Floats are not tracked
+                IRInstr::FloatToInt | IRInstr::IntToFloat => {
+                    frame.stack.pop();
+                    frame.stack.push(Sym::Opaque);
+                }
+                IRInstr::Dup => {
+                    let top = frame.stack.last().cloned().unwrap_or(Sym::Opaque);
+                    frame.stack.push(top);
+                }
+                IRInstr::Nop | IRInstr::Line(..) => {}
+                IRInstr::Jump(target) => frame.pc = *target,
+                IRInstr::JumpIfFalse(target) => {
+                    let condition = frame.stack.pop().unwrap_or(Sym::Opaque);
+                    match condition {
+                        Sym::Int(n) => {
+                            if n == 0 {
+                                frame.pc = *target;
+                            }
+                        }
+                        condition if condition.is_opaque() => return Step::GaveUp,
+                        condition => {
+                            if state.branches >= self.depth {
+                                return Step::GaveUp;
+                            }
+                            state.branches += 1;
+                            let mut taken = state.clone();
+                            taken.path.assumed.push(condition.clone());
+                            let mut skipped = state;
+                            skipped.path.assumed.push(Sym::not(condition));
+                            skipped.frames.last_mut().expect("checked above").pc = *target;
+                            return Step::Continue(vec![skipped, taken]);
+                        }
+                    }
+                }
+                // Which function runs is only known at run time
+                IRInstr::CallIndirect(_) => return Step::GaveUp,
+                IRInstr::Call(name, arg_count) => {
+                    let args = frame.stack.split_off(frame.stack.len().saturating_sub(*arg_count));
+                    let callee = self.module.functions.iter().position(|f| &f.name == name);
+                    match callee {
+                        Some(callee) if nested < CALL_DEPTH => {
+                            let frame = self.frame(callee, args);
+                            state.frames.push(frame);
+                        }
+                        _ => {
+                            let results = callee.map_or(1, |callee| self.module.functions[callee].result_count);
+                            frame.stack.extend(std::iter::repeat_n(Sym::Opaque, results));
+                        }
+                    }
+                }
+                IRInstr::CallStd(name) | IRInstr::CallAI(name) | IRInstr::CallWeb3(name) | IRInstr::CallFS(name) => {
+                    let info = crate::stdlib::get_stdlib_info(name);
+                    let arity = info.map_or(0, |f| f.params.len());
+                    frame.stack.truncate(frame.stack.len().saturating_sub(arity));
+                    let results = info.map_or(1, |f| f.result_count());
+                    frame.stack.extend(std::iter::repeat_n(Sym::Opaque, results));
+                }
+                IRInstr::Return => {
+                    let count = function.result_count;
+                    if let Some(path) = self.ret(&mut state, count) {
+                        return Step::Returned(path);
+                    }
+                }
+                IRInstr::Panic | IRInstr::Revert => return Step::Reverted,
+            }
+        }
+    }
+
+    /// Return `count` values from the running frame: to the caller, or as
+    /// the path's result when it is the last
+    fn ret(&mut self, state: &mut State, count: usize) -> Option<Path> {
+        let mut frame = state.frames.pop().expect("a running path has a frame");
+        let results = frame.stack.split_off(frame.stack.len().saturating_sub(count));
+        match state.frames.last_mut() {
+            Some(caller) => {
+                caller.stack.extend(results);
+                None
+            }
+            None => Some(Path { result: results, ..state.path.clone() }),
+        }
+    }
+}
+
+enum Step {
+    Continue(Vec<State>),
+    Returned(Path),
+    Reverted,
+    GaveUp,
+}
+
+fn binary_op(instr: &IRInstr) -> Op {
+    match instr {
+        IRInstr::Add | IRInstr::CheckedAdd(_) => Op::Add,
+        IRInstr::Sub | IRInstr::CheckedSub(_) => Op::Sub,
+        IRInstr::Mul | IRInstr::CheckedMul(_) => Op::Mul,
+        IRInstr::Div => Op::Div,
+        IRInstr::Mod => Op::Mod,
+        IRInstr::Eq => Op::Eq,
+        IRInstr::Ne => Op::Ne,
+        IRInstr::Lt => Op::Lt,
+        IRInstr::Le => Op::Le,
+        IRInstr::Gt => Op::Gt,
+        IRInstr::Ge => Op::Ge,
+        IRInstr::And => Op::And,
+        _ => Op::Or,
+    }
+}
+
+/// Inputs `constraints` read, in order
+fn used_inputs(constraints: &[Sym]) -> Vec<usize> {
+    let mut used = Vec::new();
+    for constraint in constraints {
+        constraint.visit(&mut |sym| {
+            if let Sym::Input(i) = sym {
+                used.push(*i);
+            }
+        });
+    }
+    used.sort_unstable();
+    used.dedup();
+    used
+}
+
+/// Values for `count` inputs under which every constraint is non-zero,
+/// trying small values and those next to the constants the constraints
+/// mention, simplest first
+fn solve(constraints: &[Sym], count: usize) -> Option<Vec<i64>> {
+    let mut candidates: Vec<i64> = vec![0, 1, -1, 2, 10, 100];
+    for constraint in constraints {
+        constraint.visit(&mut |sym| {
+            if let Sym::Int(n) = sym {
+                candidates.extend([n.saturating_sub(1), *n, n.saturating_add(1)]);
+            }
+        });
+    }
+    candidates.sort_by_key(|n| (n.unsigned_abs(), *n < 0));
+    candidates.dedup();
+
+    let used = used_inputs(constraints);
+    let mut values = vec![0; count];
+    let mut digits = vec![0; used.len()];
+    for _ in 0..SEARCH_LIMIT {
+        for (digit, input) in digits.iter().zip(&used) {
+            values[*input] = candidates[*digit];
+        }
+        if constraints.iter().all(|c| c.eval(&values).is_some_and(|v| v != 0)) {
+            return Some(values);
+        }
+        // Next assignment, as an odometer over the candidates
+        let mut position = 0;
+        loop {
+            let digit = digits.get_mut(position)?;
+            *digit += 1;
+            if *digit < candidates.len() {
+                break;
+            }
+            *digit = 0;
+            position += 1;
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn verify_source(source: &str) -> Vec<Report> {
+        verify(&Parser::new(Lexer::new(source)).parse().unwrap(), DEFAULT_DEPTH)
+    }
+
+    #[test]
+    fn test_counterexample_breaks_an_ensures() {
+        let reports = verify_source(r#"
+            contract Vault {
+                state balance
+                #[requires(amount > 0)]
+                #[ensures(result < balance)]
+                fn withdraw(amount) {
+                    if amount > balance {
+                        panic("insufficient balance")
+                    }
+                    balance = balance - amount
+                    return balance
+                }
+            }
+        "#);
+
+        assert_eq!(reports.len(), 1);
+        let report = &reports[0];
+        assert_eq!((report.function.as_str(), report.ensures, report.paths), ("Vault.withdraw", 1, 1));
+        // The new balance is never below itself
+        let counterexample = report.counterexample.as_ref().expect("a counterexample");
+        assert_eq!(counterexample.condition.start_line, 5);
+        assert_eq!(counterexample.inputs, vec![("amount".to_string(), 1), ("balance".to_string(), 1)]);
+        assert_eq!(counterexample.result, Some(0));
+    }
+
+    #[test]
+    fn test_requires_rule_out_violations() {
+        let reports = verify_source(r#"
+            contract Counter {
+                state count
+                #[requires(step > 0)]
+                #[ensures(count > result - step)]
+                fn bump(step) {
+                    let before = count
+                    count = count + step
+                    return before + step
+                }
+                #[ensures(result >= 0)]
+                fn clamp(x) {
+                    if x < 0 {
+                        return 0
+                    }
+                    return x
+                }
+                #[ensures(result != 7)]
+                fn lucky(x) {
+                    if x == 6 {
+                        return x + 1
+                    }
+                    return 0
+                }
+            }
+        "#);
+
+        let outcome: Vec<(&str, usize, bool)> = reports
+            .iter()
+            .map(|r| (r.function.as_str(), r.paths, r.counterexample.is_some()))
+            .collect();
+        assert_eq!(outcome, vec![("Counter.bump", 1, false), ("Counter.clamp", 2, false), ("Counter.lucky", 2, true)]);
+        assert_eq!(reports[2].counterexample.as_ref().unwrap().inputs, vec![("x".to_string(), 6)]);
+    }
+}
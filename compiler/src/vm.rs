@@ -1,25 +1,74 @@
 // ASTRIXA Virtual Machine: Executes Bytecode
+//
+// All execution state (stack, variables, instruction pointer) lives in the
+// VM, so a program can run in slices: `run_for` executes at most a given
+// number of instructions and returns, and the next call carries on where
+// it stopped. Embedders interleave scripts with their own event loop this
+// way, on one thread; `Scheduler` shares that thread fairly between VMs,
+// and, when asked to catch signals, stops them all gracefully on SIGINT or
+// SIGTERM.
+//
+// The program itself is never changed by running it, so VMs can share
+// one: `load_shared` takes it behind an `Arc`, as `ModuleCache` hands it
+// out, and each VM keeps only its own stack, variables and gas.
+//
+// A module's functions are laid out one after another in the program, each
+// ending in an implicit `return null`. `Call` to one of them pushes a frame
+// with the return address and the caller's variables, binds the arguments
+// to the parameters in a fresh scope, and jumps to its first instruction;
+// `Return` pops the frame and leaves the result on the caller's stack.
 
+use std::cmp::Ordering;
 use std::collections::HashMap;
-use crate::bytecode::{OpCode, Instruction};
-use crate::interpreter::{Value, BlockchainContext}; // Import both Value and BlockchainContext
+use std::sync::Arc;
+use crate::bytecode::{BytecodeModule, OpCode, Instruction};
+use crate::interpreter::{amount, collection_method, has_collection_method, slice, type_name, values_equal, Value, BlockchainContext};
+use crate::messages;
+use crate::shutdown::{self, Signals, Stop};
 use crate::gas::{gas_cost, GasContext};
 
 pub struct VM {
+    program: Arc<[Instruction]>, // Shared with every VM running it
     stack: Vec<Value>,
     vars: HashMap<String, Value>,
     ip: usize, // Instruction pointer
-    call_stack: Vec<usize>, // For function returns
+    functions: HashMap<String, (usize, Vec<String>)>, // Each function's start and parameters
+    call_stack: Vec<Frame>,
     blockchain_context: BlockchainContext,
     gas_context: GasContext,
+    output: Option<Vec<String>>, // Printed lines, while capturing them
+    shutdown_handlers: Vec<String>, // Functions on_shutdown registered, in order
+}
+
+/// A call in progress: where its caller resumes, and what it had
+struct Frame {
+    return_ip: usize,
+    vars: HashMap<String, Value>, // The caller's scope
+    stack_base: usize,            // The caller's stack height, without the arguments
+}
+
+/// Where a `run_for` slice left the program
+pub enum Tick {
+    /// The budget ran out; the next `run_for` resumes here
+    Paused,
+    /// The program returned (or ran off its end) with this value
+    Finished(Value),
+}
+
+impl Default for VM {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl VM {
     pub fn new() -> Self {
         VM {
+            program: Arc::from([]),
             stack: Vec::new(),
             vars: HashMap::new(),
             ip: 0,
+            functions: HashMap::new(),
             call_stack: Vec::new(),
             blockchain_context: BlockchainContext {
                 chain_id: 1,
@@ -31,6 +80,8 @@ impl VM {
                 tx_timestamp: 0,
             },
             gas_context: GasContext::new(1_000_000, 1), // Default: 1M gas at 1 wei/gas
+            output: None,
+            shutdown_handlers: Vec::new(),
         }
     }
 
@@ -51,185 +102,332 @@ impl VM {
         self.gas_context.remaining()
     }
 
+    /// Keep printed lines from now on instead of writing them to stdout
+    pub fn capture_output(&mut self) {
+        self.output = Some(Vec::new());
+    }
+
+    /// The lines printed since `capture_output`, which keeps capturing
+    pub fn take_output(&mut self) -> Vec<String> {
+        self.output.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    fn print(&mut self, value: Value) {
+        let line = printed(value);
+        match &mut self.output {
+            Some(lines) => lines.push(line),
+            None => println!("{}", line),
+        }
+    }
+
+    /// Run a program to completion
     pub fn run(&mut self, instructions: Vec<Instruction>) -> Result<Value, String> {
-        while self.ip < instructions.len() {
-            let instr = &instructions[self.ip];
+        self.load(instructions);
+        self.finish()
+    }
 
-            // Deduct gas before executing instruction
-            let cost = gas_cost(&instr.opcode);
-            self.gas_context.gas_used += cost;
+    /// Run a module's `entry` function to completion
+    pub fn run_module(&mut self, module: &BytecodeModule, entry: &str) -> Result<Value, String> {
+        self.load_module(module, entry)?;
+        self.finish()
+    }
 
-            // Check if we've exceeded gas limit
-            if self.gas_context.is_out_of_gas() {
-                return Err(format!(
-                    "Out of gas: used {} gas, limit was {} gas",
-                    self.gas_context.gas_used, self.gas_context.gas_limit
-                ));
+    fn finish(&mut self) -> Result<Value, String> {
+        loop {
+            if let Tick::Finished(value) = self.run_for(usize::MAX)? {
+                return Ok(value);
             }
+        }
+    }
 
-            match &instr.opcode {
-                OpCode::LoadConst => {
-                    let value = self.parse_constant(instr.operand.clone().unwrap())?;
-                    self.stack.push(value);
-                }
-                OpCode::LoadVar => {
-                    let name = instr.operand.clone().unwrap();
-                    
-                    // Check if it's a property access (e.g., "msg.sender")
-                    let value = if name.contains('.') {
-                        let parts: Vec<&str> = name.split('.').collect();
-                        if parts.len() == 2 {
-                            self.resolve_property(parts[0], parts[1])?
-                        } else {
-                            return Err(format!("Invalid property access: {}", name));
-                        }
+    /// Start `instructions` from the top on the next `run_for`. Variables
+    /// and gas used so far are kept.
+    pub fn load(&mut self, instructions: Vec<Instruction>) {
+        self.load_shared(instructions.into());
+    }
+
+    /// Start a program other VMs may be running too; like `load`, without
+    /// copying it
+    pub fn load_shared(&mut self, program: Arc<[Instruction]>) {
+        self.program = program;
+        self.ip = 0;
+        self.functions.clear();
+        self.stack.clear();
+        self.call_stack.clear();
+        self.shutdown_handlers.clear();
+    }
+
+    /// Start `entry` from the top on the next `run_for`, with every
+    /// function in `module` callable by name
+    pub fn load_module(&mut self, module: &BytecodeModule, entry: &str) -> Result<(), String> {
+        let mut program = Vec::new();
+        let mut functions = HashMap::new();
+        for function in &module.functions {
+            let start = program.len();
+            for instr in &function.instructions {
+                // Jump targets are relative to the function
+                let relocated = match instr.opcode {
+                    OpCode::Jump | OpCode::JumpIfFalse => {
+                        let target = instr.operand.as_deref().and_then(|t| t.parse::<usize>().ok())
+                            .ok_or_else(|| malformed(instr))?;
+                        Instruction::new(instr.opcode.clone(), Some((start + target).to_string()))
+                    }
+                    _ => instr.clone(),
+                };
+                program.push(relocated);
+            }
+            program.push(Instruction::const_instr("null".to_string()));
+            program.push(Instruction::new(OpCode::Return, None));
+            functions.insert(function.name.clone(), (start, function.params.clone()));
+        }
+        let (start, params) = functions.get(entry).ok_or_else(|| messages::render("E0406", &[&entry]))?;
+        if !params.is_empty() {
+            return Err(messages::render("E0492", &[&entry, &params.len()]));
+        }
+        self.load(program);
+        self.ip = *start;
+        self.functions = functions;
+        Ok(())
+    }
+
+    /// Abandon the program where it stands and run its `on_shutdown`
+    /// handlers, the last registered first as the interpreter does. A
+    /// handler failing is reported, and the handlers after it still run.
+    pub fn shut_down(&mut self) -> Result<(), String> {
+        let mut failed = None;
+        for handler in std::mem::take(&mut self.shutdown_handlers).into_iter().rev() {
+            let Some((start, _)) = self.functions.get(&handler) else { continue };
+            self.ip = *start;
+            self.stack.clear();
+            self.call_stack.clear();
+            self.vars = HashMap::new();
+            if let Err(e) = self.finish() {
+                failed.get_or_insert(e);
+            }
+        }
+        self.ip = self.program.len();
+        failed.map_or(Ok(()), Err)
+    }
+
+    /// Execute at most `max_instructions` of the loaded program. A paused
+    /// program keeps its state and resumes on the next call; once it has
+    /// finished, further calls finish again with null.
+    pub fn run_for(&mut self, max_instructions: usize) -> Result<Tick, String> {
+        for _ in 0..max_instructions {
+            if self.ip >= self.program.len() {
+                return Ok(Tick::Finished(Value::Null));
+            }
+            if let Some(value) = self.step()? {
+                self.ip = self.program.len();
+                return Ok(Tick::Finished(value));
+            }
+        }
+        if self.ip >= self.program.len() {
+            return Ok(Tick::Finished(Value::Null));
+        }
+        Ok(Tick::Paused)
+    }
+
+    /// Execute one instruction; the program's result if it returns
+    fn step(&mut self) -> Result<Option<Value>, String> {
+        let instr = self.program[self.ip].clone();
+
+        // Deduct gas before executing instruction
+        let cost = gas_cost(&instr.opcode);
+        self.gas_context.gas_used += cost;
+
+        // Check if we've exceeded gas limit
+        if self.gas_context.is_out_of_gas() {
+            return Err(messages::render("E0493", &[&self.gas_context.gas_used, &self.gas_context.gas_limit]));
+        }
+
+        match &instr.opcode {
+            OpCode::LoadConst => {
+                let value = self.parse_constant(operand(&instr)?.to_string())?;
+                self.stack.push(value);
+            }
+            OpCode::LoadVar => {
+                let name = operand(&instr)?.to_string();
+                
+                // Check if it's a property access (e.g., "msg.sender")
+                let value = if name.contains('.') {
+                    let parts: Vec<&str> = name.split('.').collect();
+                    if parts.len() == 2 {
+                        self.resolve_property(parts[0], parts[1])?
                     } else {
-                        self.vars.get(&name)
-                            .cloned()
-                            .ok_or(format!("Undefined variable: {}", name))?
-                    };
-                    self.stack.push(value);
-                }
-                OpCode::StoreVar => {
-                    let name = instr.operand.clone().unwrap();
-                    let value = self.stack.last()
+                        return Err(malformed(&instr));
+                    }
+                } else {
+                    self.vars.get(&name)
                         .cloned()
-                        .ok_or("Stack underflow")?;
-                    self.vars.insert(name, value);
-                }
-                OpCode::Pop => {
-                    self.stack.pop();
-                }
-                OpCode::Add => {
-                    self.binary_op(|a, b| match (a, b) {
-                        (Value::Number(x), Value::Number(y)) => Ok(Value::Number(x + y)),
-                        (Value::String(x), Value::String(y)) => Ok(Value::String(x + &y)),
-                        _ => Err("Type error in Add".to_string()),
-                    })?;
-                }
-                OpCode::Sub => {
-                    self.binary_op(|a, b| match (a, b) {
-                        (Value::Number(x), Value::Number(y)) => Ok(Value::Number(x - y)),
-                        _ => Err("Type error in Sub".to_string()),
-                    })?;
-                }
-                OpCode::Mul => {
-                    self.binary_op(|a, b| match (a, b) {
-                        (Value::Number(x), Value::Number(y)) => Ok(Value::Number(x * y)),
-                        _ => Err("Type error in Mul".to_string()),
-                    })?;
-                }
-                OpCode::Div => {
-                    self.binary_op(|a, b| match (a, b) {
-                        (Value::Number(x), Value::Number(0)) => Err("Division by zero".to_string()),
-                        (Value::Number(x), Value::Number(y)) => Ok(Value::Number(x / y)),
-                        _ => Err("Type error in Div".to_string()),
-                    })?;
-                }
-                OpCode::Greater => {
-                    self.binary_op(|a, b| match (a, b) {
-                        (Value::Number(x), Value::Number(y)) => Ok(Value::Bool(x > y)),
-                        _ => Err("Type error in Greater".to_string()),
-                    })?;
-                }
-                OpCode::Less => {
-                    self.binary_op(|a, b| match (a, b) {
-                        (Value::Number(x), Value::Number(y)) => Ok(Value::Bool(x < y)),
-                        _ => Err("Type error in Less".to_string()),
-                    })?;
-                }
-                OpCode::Jump => {
-                    let target = instr.operand.clone().unwrap().parse::<usize>()
-                        .map_err(|_| "Invalid jump target".to_string())?;
+                        .ok_or_else(|| messages::render("E0402", &[&name]))?
+                };
+                self.stack.push(value);
+            }
+            OpCode::StoreVar => {
+                let name = operand(&instr)?.to_string();
+                let value = self.stack.last()
+                    .cloned()
+                    .ok_or_else(underflow)?;
+                self.vars.insert(name, value);
+            }
+            OpCode::Pop => {
+                self.stack.pop();
+            }
+            OpCode::Add => {
+                self.binary_op(|a, b| match (a, b) {
+                    (Value::Number(x), Value::Number(y)) => Ok(Value::Number(x.wrapping_add(y))),
+                    (Value::String(x), Value::String(y)) => Ok(Value::String(x + &y)),
+                    _ => Err(messages::render("E0408", &[])),
+                })?;
+            }
+            OpCode::Sub => {
+                self.binary_op(|a, b| match (a, b) {
+                    (Value::Number(x), Value::Number(y)) => Ok(Value::Number(x.wrapping_sub(y))),
+                    _ => Err(messages::render("E0408", &[])),
+                })?;
+            }
+            OpCode::Mul => {
+                self.binary_op(|a, b| match (a, b) {
+                    (Value::Number(x), Value::Number(y)) => Ok(Value::Number(x.wrapping_mul(y))),
+                    _ => Err(messages::render("E0408", &[])),
+                })?;
+            }
+            OpCode::Div => {
+                self.binary_op(|a, b| match (a, b) {
+                    (Value::Number(_), Value::Number(0)) => Err(messages::render("E0407", &[])),
+                    (Value::Number(x), Value::Number(y)) => Ok(Value::Number(x.wrapping_div(y))),
+                    _ => Err(messages::render("E0408", &[])),
+                })?;
+            }
+            OpCode::Mod => {
+                self.binary_op(|a, b| match (a, b) {
+                    (Value::Number(_), Value::Number(0)) => Err(messages::render("E0407", &[])),
+                    (Value::Number(x), Value::Number(y)) => Ok(Value::Number(x.wrapping_rem(y))),
+                    _ => Err(messages::render("E0408", &[])),
+                })?;
+            }
+            OpCode::Equal => {
+                self.binary_op(|a, b| Ok(Value::Bool(values_equal(&a, &b))))?;
+            }
+            OpCode::NotEqual => {
+                self.binary_op(|a, b| Ok(Value::Bool(!values_equal(&a, &b))))?;
+            }
+            OpCode::Greater => self.compare(Ordering::is_gt)?,
+            OpCode::Less => self.compare(Ordering::is_lt)?,
+            OpCode::GreaterEqual => self.compare(Ordering::is_ge)?,
+            OpCode::LessEqual => self.compare(Ordering::is_le)?,
+            OpCode::Jump => {
+                let target = operand(&instr)?.parse::<usize>()
+                    .map_err(|_| malformed(&instr))?;
+                self.ip = target;
+                return Ok(None);
+            }
+            OpCode::JumpIfFalse => {
+                let cond = self.stack.pop().ok_or_else(underflow)?;
+                let is_false = matches!(cond, Value::Bool(false) | Value::Null);
+
+                if is_false {
+                    let target = operand(&instr)?.parse::<usize>()
+                        .map_err(|_| malformed(&instr))?;
                     self.ip = target;
-                    continue;
+                    return Ok(None);
                 }
-                OpCode::JumpIfFalse => {
-                    let cond = self.stack.pop().ok_or("Stack underflow")?;
-                    let is_false = match cond {
-                        Value::Bool(false) => true,
-                        Value::Null => true,
-                        _ => false,
-                    };
-
-                    if is_false {
-                        let target = instr.operand.clone().unwrap().parse::<usize>()
-                            .map_err(|_| "Invalid jump target".to_string())?;
-                        self.ip = target;
-                        continue;
-                    }
-                }
-                OpCode::Call => {
-                    let name = instr.operand.clone().unwrap();
-                    self.call_stdlib(&name)?;
+            }
+            OpCode::Call => {
+                let name = operand(&instr)?.to_string();
+                if let Some((start, params)) = self.functions.get(&name) {
+                    let stack_base = self.stack.len().checked_sub(params.len()).ok_or_else(underflow)?;
+                    let scope = params.iter().cloned().zip(self.stack.split_off(stack_base)).collect();
+                    let vars = std::mem::replace(&mut self.vars, scope);
+                    self.call_stack.push(Frame { return_ip: self.ip + 1, vars, stack_base });
+                    self.ip = *start;
+                    return Ok(None);
                 }
-                OpCode::Return => {
-                    let value = self.stack.pop().unwrap_or(Value::Null);
-                    return Ok(value);
+                self.call_stdlib(&name)?;
+            }
+            OpCode::Return => {
+                let value = self.stack.pop().unwrap_or(Value::Null);
+                let Some(frame) = self.call_stack.pop() else {
+                    return Ok(Some(value));
+                };
+                self.stack.truncate(frame.stack_base);
+                self.stack.push(value);
+                self.vars = frame.vars;
+                self.ip = frame.return_ip;
+                return Ok(None);
+            }
+            OpCode::Print => {
+                if let Some(value) = self.stack.pop() {
+                    self.print(value);
                 }
-                OpCode::Print => {
-                    if let Some(value) = self.stack.pop() {
-                        match value {
-                            Value::Number(n) => println!("{}", n),
-                            Value::String(s) => println!("{}", s),
-                            Value::Bool(b) => println!("{}", b),
-                            Value::Array(arr) => {
-                                let rendered: Vec<String> = arr.iter().map(|v| self.render_value(v)).collect();
-                                println!("[{}]", rendered.join(","));
-                            }
-                            Value::Null => println!("null"),
-                        }
+            }
+            OpCode::Array => {
+                let count = operand(&instr)?.parse::<usize>()
+                    .map_err(|_| malformed(&instr))?;
+                let mut arr = Vec::new();
+                for _ in 0..count {
+                    if let Some(val) = self.stack.pop() {
+                        arr.insert(0, val);
                     }
                 }
-                OpCode::Array => {
-                    let count = instr.operand.clone().unwrap().parse::<usize>()
-                        .map_err(|_| "Invalid array size".to_string())?;
-                    let mut arr = Vec::new();
-                    for _ in 0..count {
-                        if let Some(val) = self.stack.pop() {
-                            arr.insert(0, val);
-                        }
+                self.stack.push(Value::array(arr));
+            }
+            OpCode::Index => {
+                let idx = self.stack.pop().ok_or_else(underflow)?;
+                let obj = self.stack.pop().ok_or_else(underflow)?;
+
+                match (obj, idx) {
+                    (Value::Array(arr), Value::Number(i)) => {
+                        let arr = arr.borrow();
+                        let val = usize::try_from(i).ok().and_then(|at| arr.get(at))
+                            .cloned()
+                            .ok_or_else(|| messages::render("E0481", &[&i, &arr.len()]))?;
+                        self.stack.push(val);
                     }
-                    self.stack.push(Value::Array(arr));
-                }
-                OpCode::Index => {
-                    let idx = self.stack.pop().ok_or("Stack underflow")?;
-                    let obj = self.stack.pop().ok_or("Stack underflow")?;
-
-                    match (obj, idx) {
-                        (Value::Array(arr), Value::Number(i)) => {
-                            let val = arr.get(i as usize)
-                                .cloned()
-                                .ok_or("Index out of bounds".to_string())?;
-                            self.stack.push(val);
-                        }
-                        (Value::String(s), Value::Number(i)) => {
-                            let ch = s.chars().nth(i as usize)
-                                .ok_or("Index out of bounds".to_string())?;
-                            self.stack.push(Value::String(ch.to_string()));
+                    (Value::Range(s, e), Value::Number(i)) => {
+                        let len = e.saturating_sub(s).max(0);
+                        if i < 0 || i >= len {
+                            return Err(messages::render("E0481", &[&i, &len]));
                         }
-                        _ => return Err("Invalid indexing".to_string()),
+                        self.stack.push(Value::Number(s + i));
+                    }
+                    (Value::String(s), Value::Number(i)) => {
+                        let ch = usize::try_from(i).ok().and_then(|at| s.chars().nth(at))
+                            .ok_or_else(|| messages::render("E0481", &[&i, &s.chars().count()]))?;
+                        self.stack.push(Value::String(ch.to_string()));
                     }
+                    (obj, idx) => return Err(messages::render("E0494", &[&type_name(&obj), &type_name(&idx)])),
                 }
             }
-
-            self.ip += 1;
         }
 
-        Ok(Value::Null)
+        self.ip += 1;
+        Ok(None)
     }
 
     fn binary_op<F>(&mut self, op: F) -> Result<(), String>
     where
         F: Fn(Value, Value) -> Result<Value, String>,
     {
-        let b = self.stack.pop().ok_or("Stack underflow")?;
-        let a = self.stack.pop().ok_or("Stack underflow")?;
+        let b = self.stack.pop().ok_or_else(underflow)?;
+        let a = self.stack.pop().ok_or_else(underflow)?;
         let result = op(a, b)?;
         self.stack.push(result);
         Ok(())
     }
 
+    /// An ordering of two numbers, Int or U256 alike, as the interpreter's
+    fn compare(&mut self, holds: fn(Ordering) -> bool) -> Result<(), String> {
+        self.binary_op(|a, b| match (&a, &b) {
+            (Value::Number(x), Value::Number(y)) => Ok(Value::Bool(holds(x.cmp(y)))),
+            (Value::Number(_) | Value::U256(_), Value::Number(_) | Value::U256(_)) => {
+                Ok(Value::Bool(holds(amount(&a).cmp(&amount(&b)))))
+            }
+            _ => Err(messages::render("E0408", &[])),
+        })
+    }
+
     fn parse_constant(&self, s: String) -> Result<Value, String> {
         if s == "null" {
             Ok(Value::Null)
@@ -242,7 +440,7 @@ impl VM {
         } else if let Ok(n) = s.parse::<i64>() {
             Ok(Value::Number(n))
         } else {
-            Err(format!("Unknown constant: {}", s))
+            Err(messages::render("E0495", &[&"LoadConst", &s]))
         }
     }
 
@@ -254,15 +452,15 @@ impl VM {
             
             // msg properties
             ("msg", "sender") => Ok(Value::Address(self.blockchain_context.sender.clone())),
-            ("msg", "value") => Ok(Value::U256(self.blockchain_context.msg_value)),
+            ("msg", "value") => Ok(Value::U256(crate::u256::U256::from(self.blockchain_context.msg_value))),
             ("msg", "data") => Ok(Value::String(self.blockchain_context.msg_data.clone())),
             
             // tx properties
             ("tx", "hash") => Ok(Value::String(self.blockchain_context.tx_hash.clone())),
-            ("tx", "value") => Ok(Value::U256(self.blockchain_context.msg_value)),
+            ("tx", "value") => Ok(Value::U256(crate::u256::U256::from(self.blockchain_context.msg_value))),
             ("tx", "timestamp") => Ok(Value::Number(self.blockchain_context.tx_timestamp)),
             
-            _ => Err(format!("Unknown property: {}.{}", object, property)),
+            _ => Err(messages::render("E0414", &[&object, &property])),
         }
     }
 
@@ -271,68 +469,191 @@ impl VM {
         if name.starts_with("ai.") {
             return self.call_ai(name);
         }
+        if let Some(function) = name.strip_prefix("u256.") {
+            return self.call_u256(function);
+        }
+        if let Some(function) = name.strip_prefix("merkle.") {
+            return self.call_merkle(function);
+        }
+        if name.starts_with("ed25519.") || name.starts_with("bls.") {
+            return self.call_signature(name);
+        }
+        // An array or map method on a variable changes it in place, the
+        // way the interpreter's do; the receiver is not on the stack
+        if let Some((receiver, method)) = name.split_once('.') {
+            if let Some(collection) = self.vars.get(receiver).filter(|v| has_collection_method(v, method)).cloned() {
+                let arity = match method {
+                    "push" | "append" => 1,
+                    "set" => 2,
+                    _ => 0,
+                };
+                let at = self.stack.len().checked_sub(arity).ok_or_else(underflow)?;
+                let args = self.stack.split_off(at);
+                let result = collection_method(&collection, method, args)?;
+                self.stack.push(result);
+                return Ok(());
+            }
+        }
         
         match name {
-            "print" => {
+            name if crate::prelude::prints(name) => {
                 if let Some(value) = self.stack.pop() {
-                    match value {
-                        Value::Number(n) => println!("{}", n),
-                        Value::String(s) => println!("{}", s),
-                        Value::Bool(b) => println!("{}", b),
-                        Value::Array(arr) => {
-                            let rendered: Vec<String> = arr.iter().map(|v| self.render_value(v)).collect();
-                            println!("[{}]", rendered.join(","));
-                        }
-                        Value::Address(addr) => println!("{}", addr),
-                        Value::U256(n) => println!("{}", n),
-                        Value::AIResult { label, score } => println!("{}: {:.2}", label, score),
-                        Value::Null => println!("null"),
-                    }
+                    self.print(value);
                     self.stack.push(Value::Null);
                 }
                 Ok(())
             }
+            // What lowering makes of a slice: the value, then the range's
+            // bounds, as WASM builds take them
+            "str.substr" => {
+                let end = self.stack.pop().ok_or_else(underflow)?;
+                let start = self.stack.pop().ok_or_else(underflow)?;
+                let value = self.stack.pop().ok_or_else(underflow)?;
+                let (Value::Number(start), Value::Number(end)) = (start, end) else {
+                    return Err(messages::render("E0436", &[&type_name(&value), &"a range"]));
+                };
+                self.stack.push(slice(value, Value::Range(start, end))?);
+                Ok(())
+            }
+            // Lowering passes the handler by name
+            "on_shutdown" => {
+                match self.stack.pop() {
+                    Some(Value::String(handler) | Value::Function(handler)) if self.functions.contains_key(&handler) => {
+                        self.shutdown_handlers.push(handler);
+                    }
+                    other => return Err(messages::render("E0489", &[&other.as_ref().map_or("nothing", type_name)])),
+                }
+                self.stack.push(Value::Null);
+                Ok(())
+            }
             "len" => {
-                let val = self.stack.pop().ok_or("Stack underflow")?;
+                let val = self.stack.pop().ok_or_else(underflow)?;
                 let len = match val {
-                    Value::Array(ref arr) => arr.len() as i64,
-                    Value::String(ref s) => s.len() as i64,
-                    _ => return Err("len() expects array or string".to_string()),
+                    Value::Array(ref arr) => arr.borrow().len() as i64,
+                    Value::String(ref s) => s.chars().count() as i64,
+                    Value::StringBuilder(ref text) => text.borrow().chars().count() as i64,
+                    Value::Range(s, e) => e.saturating_sub(s).max(0),
+                    _ => return Err(messages::render("E0425", &[])),
                 };
                 self.stack.push(Value::Number(len));
                 Ok(())
             }
+            "collect" => {
+                let items = match self.stack.pop().ok_or_else(underflow)? {
+                    Value::Range(s, e) => (s..e).map(Value::Number).collect(),
+                    Value::Array(arr) => arr.borrow().clone(),
+                    other => return Err(messages::render("E0487", &[&type_name(&other)])),
+                };
+                self.stack.push(Value::array(items));
+                Ok(())
+            }
+            "StringBuilder" => {
+                self.stack.push(Value::StringBuilder(Default::default()));
+                Ok(())
+            }
+            "int" => {
+                let value = match self.stack.pop().ok_or_else(underflow)? {
+                    Value::String(text) => {
+                        Value::Number(text.trim().parse().map_err(|_| messages::render("E0484", &[&text]))?)
+                    }
+                    Value::Float(f) => Value::Number(f as i64),
+                    n @ Value::Number(_) => n,
+                    other => return Err(messages::render("E0483", &[&type_name(&other), &"Int"])),
+                };
+                self.stack.push(value);
+                Ok(())
+            }
+            "to_string" | "str" => {
+                let text = match self.stack.pop().ok_or_else(underflow)? {
+                    Value::String(s) => s,
+                    Value::StringBuilder(text) => text.borrow().clone(),
+                    other => render(&other),
+                };
+                self.stack.push(Value::String(text));
+                Ok(())
+            }
             "type" => {
-                let val = self.stack.pop().ok_or("Stack underflow")?;
+                let val = self.stack.pop().ok_or_else(underflow)?;
                 let type_str = match val {
                     Value::Number(_) => "number",
+                    Value::Float(_) => "float",
                     Value::String(_) => "string",
+                    Value::StringBuilder(_) => "string_builder",
                     Value::Bool(_) => "bool",
                     Value::Array(_) => "array",
+                    Value::Tuple(_) => "tuple",
+                    Value::Map(_) => "map",
+                    Value::Option(_) => "option",
+                    Value::Result(_) => "result",
+                    Value::Range(..) => "range",
+                    Value::Iter(_) => "iterator",
+                    Value::Function(_) => "function",
                     Value::Address(_) => "address",
                     Value::U256(_) => "u256",
                     Value::AIResult { .. } => "ai_result",
+                    Value::Task(_) => "task",
                     Value::Null => "null",
                 };
                 self.stack.push(Value::String(type_str.to_string()));
                 Ok(())
             }
             "range" => {
-                let end = self.stack.pop().ok_or("Stack underflow")?;
-                let start = self.stack.pop().ok_or("Stack underflow")?;
+                let end = self.stack.pop().ok_or_else(underflow)?;
+                let start = self.stack.pop().ok_or_else(underflow)?;
 
+                // Counted lazily, like the interpreter's
                 if let (Value::Number(s), Value::Number(e)) = (start, end) {
-                    let arr: Vec<Value> = (s..e).map(Value::Number).collect();
-                    self.stack.push(Value::Array(arr));
+                    self.stack.push(Value::Range(s, e));
                     Ok(())
                 } else {
-                    Err("range() expects two numbers".to_string())
+                    Err(messages::render("E0426", &[]))
                 }
             }
-            _ => Err(format!("Unknown function: {}", name)),
+            _ => Err(messages::render("E0406", &[&name])),
         }
     }
 
+    fn call_u256(&mut self, function: &str) -> Result<(), String> {
+        let arity = crate::u256::arith::arity(function).unwrap_or(0);
+        if self.stack.len() < arity {
+            return Err(underflow());
+        }
+        let args = self.stack.split_off(self.stack.len() - arity);
+        let result = crate::u256::call(function, &args)?;
+        self.stack.push(result);
+        Ok(())
+    }
+
+    fn call_merkle(&mut self, function: &str) -> Result<(), String> {
+        let arity = match function {
+            "root" => 1,
+            "proof" => 2,
+            _ => 3,
+        };
+        if self.stack.len() < arity {
+            return Err(underflow());
+        }
+        let args = self.stack.split_off(self.stack.len() - arity);
+        let result = crate::merkle::call(function, &args)?;
+        self.stack.push(result);
+        Ok(())
+    }
+
+    fn call_signature(&mut self, name: &str) -> Result<(), String> {
+        let arity = crate::stdlib::get_stdlib_info(name).map_or(0, |f| f.params.len());
+        if self.stack.len() < arity {
+            return Err(underflow());
+        }
+        let args = self.stack.split_off(self.stack.len() - arity);
+        let result = match name.split_once('.') {
+            Some(("bls", function)) => crate::bls::call(function, &args)?,
+            Some((_, function)) => crate::ed25519::call(function, &args)?,
+            None => unreachable!("called with a module prefix"),
+        };
+        self.stack.push(result);
+        Ok(())
+    }
+
     fn call_ai(&mut self, name: &str) -> Result<(), String> {
         use crate::ai_runtime::{AIRuntime, LocalAIRuntime};
         
@@ -342,12 +663,12 @@ impl VM {
         match method {
             "infer" => {
                 // Pop input and model from stack (model on top)
-                let input = self.stack.pop().ok_or("Stack underflow")?;
-                let _model = self.stack.pop().ok_or("Stack underflow")?;
+                let input = self.stack.pop().ok_or_else(underflow)?;
+                let _model = self.stack.pop().ok_or_else(underflow)?;
 
                 let input_str = match input {
                     Value::String(s) => s,
-                    _ => return Err("ai.infer() requires string input".to_string()),
+                    _ => return Err(messages::render("E0418", &[])),
                 };
 
                 // For now, use a default sentiment model
@@ -357,11 +678,11 @@ impl VM {
                 Ok(())
             }
             "embed" => {
-                let text = self.stack.pop().ok_or("Stack underflow")?;
+                let text = self.stack.pop().ok_or_else(underflow)?;
 
                 let text_str = match text {
                     Value::String(s) => s,
-                    _ => return Err("ai.embed() requires string input".to_string()),
+                    _ => return Err(messages::render("E0416", &[&"ai.embed"])),
                 };
 
                 let embeddings = ai_runtime.embed(&text_str)?;
@@ -369,51 +690,384 @@ impl VM {
                     .iter()
                     .map(|&f| Value::Number((f * 100.0) as i64))
                     .collect();
-                self.stack.push(Value::Array(values));
+                self.stack.push(Value::array(values));
                 Ok(())
             }
             "tokenize" => {
-                let text = self.stack.pop().ok_or("Stack underflow")?;
+                let text = self.stack.pop().ok_or_else(underflow)?;
 
                 let text_str = match text {
                     Value::String(s) => s,
-                    _ => return Err("ai.tokenize() requires string input".to_string()),
+                    _ => return Err(messages::render("E0416", &[&"ai.tokenize"])),
                 };
 
                 let tokens = ai_runtime.tokenize(&text_str)?;
                 let values: Vec<Value> = tokens.into_iter().map(Value::String).collect();
-                self.stack.push(Value::Array(values));
+                self.stack.push(Value::array(values));
                 Ok(())
             }
             "model" => {
-                let model_name = self.stack.pop().ok_or("Stack underflow")?;
+                let model_name = self.stack.pop().ok_or_else(underflow)?;
 
                 let name_str = match model_name {
                     Value::String(s) => s,
-                    _ => return Err("ai.model() requires string argument".to_string()),
+                    _ => return Err(messages::render("E0416", &[&"ai.model"])),
                 };
 
                 let _model = ai_runtime.model(&name_str)?;
                 self.stack.push(Value::String(name_str));
                 Ok(())
             }
-            _ => Err(format!("Unknown AI method: ai.{}", method)),
+            _ => Err(messages::render("E0420", &[&method])),
+        }
+    }
+}
+
+/// A pop from an empty stack: the bytecode is malformed
+fn underflow() -> String {
+    messages::render("E0491", &[])
+}
+
+/// The operand of an instruction that needs one
+fn operand(instr: &Instruction) -> Result<&str, String> {
+    instr.operand.as_deref().ok_or_else(|| malformed(instr))
+}
+
+/// An instruction whose operand the VM cannot use
+fn malformed(instr: &Instruction) -> String {
+    messages::render("E0495", &[&format!("{:?}", instr.opcode), &instr.operand.as_deref().unwrap_or("")])
+}
+
+/// Shares one thread between several VMs. Each `tick` is a round in which
+/// every unfinished VM runs for the same instruction budget, in the order
+/// they were spawned, so a long-running script cannot starve the others.
+/// Its stop handle ends every VM before the next round, as SIGINT and
+/// SIGTERM do once `catch_signals` has been called.
+pub struct Scheduler {
+    slice: usize, // Instruction budget of one VM per tick
+    vms: Vec<(VM, Option<Result<Value, String>>)>, // Each VM and its result once finished
+    stop: Stop,
+    signals: Option<Signals>, // While catching SIGINT and SIGTERM
+}
+
+impl Scheduler {
+    pub fn new(slice: usize) -> Self {
+        Scheduler { slice, vms: Vec::new(), stop: Stop::default(), signals: None }
+    }
+
+    /// Stop on SIGINT and SIGTERM while the scheduler is alive, as a
+    /// program run from the command line should. Embedders that handle
+    /// signals themselves leave this off.
+    pub fn catch_signals(mut self) -> Self {
+        self.signals = Some(Signals::catch(self.stop.clone()));
+        self
+    }
+
+    /// Add a VM with its program loaded; returns its id
+    pub fn spawn(&mut self, vm: VM) -> usize {
+        self.vms.push((vm, None));
+        self.vms.len() - 1
+    }
+
+    /// Run one round; returns how many VMs have yet to finish. A VM that
+    /// fails is finished with its error. Once a signal is received, every
+    /// VM still running runs its `on_shutdown` handlers and is finished as
+    /// interrupted instead, or with the error of a handler that failed.
+    pub fn tick(&mut self) -> usize {
+        if let Some(signal) = self.stop.received() {
+            let interrupted = messages::render("E0488", &[&shutdown::name(signal)]);
+            for (vm, result) in self.vms.iter_mut().filter(|(_, result)| result.is_none()) {
+                *result = Some(vm.shut_down().and(Err(interrupted.clone())));
+            }
+            shutdown::flush();
+            return 0;
+        }
+        let slice = self.slice;
+        let mut running = 0;
+        for (vm, result) in self.vms.iter_mut().filter(|(_, result)| result.is_none()) {
+            match vm.run_for(slice) {
+                Ok(Tick::Paused) => running += 1,
+                Ok(Tick::Finished(value)) => *result = Some(Ok(value)),
+                Err(e) => *result = Some(Err(e)),
+            }
+        }
+        running
+    }
+
+    /// The VM's result, once it has finished
+    pub fn result(&self, id: usize) -> Option<&Result<Value, String>> {
+        self.vms.get(id)?.1.as_ref()
+    }
+
+    pub fn vm(&self, id: usize) -> Option<&VM> {
+        self.vms.get(id).map(|(vm, _)| vm)
+    }
+
+    /// Where this notes the signal to stop on; raising one on it from
+    /// another thread stops the scheduler as that signal would
+    pub fn stop_handle(&self) -> Stop {
+        self.stop.clone()
+    }
+
+    /// The signal that stopped the scheduler, if one did
+    pub fn interrupted(&self) -> Option<i32> {
+        self.stop.received()
+    }
+}
+
+/// A value the way `print` shows it: strings without quotes
+pub fn printed(value: Value) -> String {
+    match value {
+        Value::String(s) => s,
+        Value::StringBuilder(text) => text.borrow().clone(),
+        other => render(&other),
+    }
+}
+
+fn render(v: &Value) -> String {
+    match v {
+        Value::String(s) => format!("\"{}\"", s),
+        Value::StringBuilder(text) => format!("\"{}\"", text.borrow()),
+        Value::Number(n) => n.to_string(),
+        Value::Float(f) => f.to_string(),
+        Value::Bool(b) => b.to_string(),
+        Value::Array(arr) => {
+            let rendered: Vec<String> = arr.borrow().iter().map(render).collect();
+            format!("[{}]", rendered.join(","))
+        }
+        Value::Tuple(items) => {
+            let rendered: Vec<String> = items.iter().map(render).collect();
+            format!("({})", rendered.join(", "))
         }
+        Value::Map(entries) => {
+            let rendered: Vec<String> = entries.borrow().iter().map(|(key, value)| format!("{}: {}", key, render(value))).collect();
+            format!("{{{}}}", rendered.join(", "))
+        }
+        Value::Option(Some(payload)) => format!("Some({})", render(payload)),
+        Value::Option(None) => "None".to_string(),
+        Value::Result(Ok(payload)) => format!("Ok({})", render(payload)),
+        Value::Result(Err(payload)) => format!("Err({})", render(payload)),
+        Value::Range(start, end) => format!("{}..{}", start, end),
+        Value::Iter(_) => "<iterator>".to_string(),
+        Value::Function(name) => format!("<fn {}>", name),
+        Value::Address(addr) => addr.clone(),
+        Value::U256(n) => n.to_string(),
+        Value::AIResult { label, score } => format!("{}: {:.2}", label, score),
+        Value::Task(id) => format!("<task {}>", id),
+        Value::Null => "null".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `i` counts from 0 to `n` in a loop of 10 instructions, then returns it
+    fn count_to(n: i64) -> Vec<Instruction> {
+        vec![
+            Instruction::const_instr("0".to_string()),
+            Instruction::store_var("i".to_string()),
+            Instruction::new(OpCode::Pop, None),
+            Instruction::var_instr("i".to_string()), // 3: loop test
+            Instruction::const_instr(n.to_string()),
+            Instruction::new(OpCode::Less, None),
+            Instruction::jump_if_false(13),
+            Instruction::var_instr("i".to_string()),
+            Instruction::const_instr("1".to_string()),
+            Instruction::new(OpCode::Add, None),
+            Instruction::store_var("i".to_string()),
+            Instruction::new(OpCode::Pop, None),
+            Instruction::jump_instr(3),
+            Instruction::var_instr("i".to_string()), // 13: loop exit
+            Instruction::new(OpCode::Return, None),
+        ]
     }
 
-    fn render_value(&self, v: &Value) -> String {
-        match v {
-            Value::String(s) => format!("\"{}\"", s),
-            Value::Number(n) => n.to_string(),
-            Value::Bool(b) => b.to_string(),
-            Value::Array(arr) => {
-                let rendered: Vec<String> = arr.iter().map(|x| self.render_value(x)).collect();
-                format!("[{}]", rendered.join(","))
+    fn finished(tick: Tick) -> Option<i64> {
+        match tick {
+            Tick::Finished(Value::Number(n)) => Some(n),
+            Tick::Finished(_) => panic!("unexpected result"),
+            Tick::Paused => None,
+        }
+    }
+
+    #[test]
+    fn test_run_for_pauses_and_resumes() {
+        let mut vm = VM::new();
+        vm.load(count_to(3));
+
+        // 3 to set up, 10 per iteration, 4 for the last test and 2 to return
+        let mut slices = 0;
+        let result = loop {
+            slices += 1;
+            if let Some(n) = finished(vm.run_for(5).unwrap()) {
+                break n;
+            }
+        };
+        assert_eq!((result, slices), (3, 8));
+        assert!(matches!(vm.run_for(5).unwrap(), Tick::Finished(Value::Null)));
+    }
+
+    #[test]
+    fn test_calls_run_in_frames_of_their_own() {
+        use crate::bytecode::BytecodeFunction;
+        let function = |name: &str, params: &[&str], instructions| BytecodeFunction {
+            name: name.to_string(),
+            params: params.iter().map(|p| p.to_string()).collect(),
+            instructions,
+            span: None,
+        };
+        // countdown(n) calls itself until n is 0, storing to its own `n`;
+        // main keeps its `n` and adds the 0 countdown returns
+        let countdown = function("countdown", &["n"], vec![
+            Instruction::var_instr("n".to_string()),
+            Instruction::const_instr("0".to_string()),
+            Instruction::new(OpCode::Greater, None),
+            Instruction::jump_if_false(10),
+            Instruction::var_instr("n".to_string()),
+            Instruction::const_instr("1".to_string()),
+            Instruction::new(OpCode::Sub, None),
+            Instruction::store_var("n".to_string()),
+            Instruction::call_instr("countdown".to_string()),
+            Instruction::new(OpCode::Return, None),
+            Instruction::var_instr("n".to_string()), // 10
+            Instruction::new(OpCode::Return, None),
+        ]);
+        let main = function("main", &[], vec![
+            Instruction::const_instr("5".to_string()),
+            Instruction::store_var("n".to_string()),
+            Instruction::call_instr("countdown".to_string()),
+            Instruction::var_instr("n".to_string()),
+            Instruction::new(OpCode::Add, None),
+            Instruction::new(OpCode::Return, None),
+        ]);
+        let module = BytecodeModule { functions: vec![countdown, main] };
+        assert!(matches!(VM::new().run_module(&module, "main"), Ok(Value::Number(5))));
+
+        // Slices pause and resume inside calls too
+        let mut vm = VM::new();
+        vm.load_module(&module, "main").unwrap();
+        let result = loop {
+            if let Some(n) = finished(vm.run_for(3).unwrap()) {
+                break n;
             }
-            Value::Address(addr) => addr.clone(),
-            Value::U256(n) => n.to_string(),
-            Value::AIResult { label, score } => format!("{}: {:.2}", label, score),
-            Value::Null => "null".to_string(),
+        };
+        assert_eq!(result, 5);
+        assert_eq!(VM::new().run_module(&module, "countdown").unwrap_err(), messages::render("E0492", &[&"countdown", &1]));
+    }
+
+    #[test]
+    fn test_comparisons_follow_the_interpreter() {
+        use crate::u256::U256;
+        let compare = |a: Value, opcode: OpCode, b: Value| {
+            let mut vm = VM::new();
+            vm.load(vec![Instruction::new(opcode, None), Instruction::new(OpCode::Return, None)]);
+            vm.stack.extend([a, b]);
+            match vm.run_for(2)? {
+                Tick::Finished(Value::Bool(result)) => Ok(result),
+                _ => panic!("no bool"),
+            }
+        };
+        let address = || Value::Address("0xAbC0".to_string());
+        assert_eq!(compare(address(), OpCode::Equal, Value::String("0xabc0".to_string())), Ok(true));
+        assert_eq!(compare(Value::String("1".to_string()), OpCode::Equal, Value::Number(1)), Ok(false));
+        assert_eq!(compare(Value::Number(1), OpCode::Equal, Value::Float(1.0)), Ok(false));
+        assert_eq!(compare(Value::Bool(true), OpCode::NotEqual, Value::Bool(true)), Ok(false));
+        assert_eq!(compare(Value::U256(U256::from(5u64)), OpCode::Equal, Value::Number(5)), Ok(true));
+        assert_eq!(compare(Value::U256(U256::from(7u64)), OpCode::GreaterEqual, Value::Number(7)), Ok(true));
+        assert_eq!(compare(Value::Number(-1), OpCode::LessEqual, Value::U256(U256::from(0u64))), Ok(true));
+        assert_eq!(compare(Value::String("b".to_string()), OpCode::Less, Value::String("a".to_string())), Err(messages::render("E0408", &[])));
+    }
+
+    #[test]
+    fn test_instructions_missing_their_operand_are_errors() {
+        for opcode in [OpCode::LoadConst, OpCode::LoadVar, OpCode::StoreVar, OpCode::Jump, OpCode::Call, OpCode::Array] {
+            let mut vm = VM::new();
+            vm.load(vec![Instruction::new(opcode.clone(), None)]);
+            vm.stack.push(Value::Number(1));
+            let error = vm.run_for(1).map(|_| ()).unwrap_err();
+            assert_eq!(error, messages::render("E0495", &[&format!("{:?}", opcode), &""]));
         }
     }
+
+    #[test]
+    fn test_scheduler_shares_ticks_between_vms() {
+        let mut scheduler = Scheduler::new(20);
+        let long = scheduler.spawn({
+            let mut vm = VM::new();
+            vm.load(count_to(10));
+            vm
+        });
+        let short = scheduler.spawn({
+            let mut vm = VM::new();
+            vm.load(count_to(1));
+            vm
+        });
+        let failing = scheduler.spawn({
+            let mut vm = VM::new().with_gas(50, 1);
+            vm.load(count_to(100));
+            vm
+        });
+
+        // Each VM gets 20 instructions per tick, however long the others run
+        assert_eq!(scheduler.tick(), 2);
+        assert!(matches!(scheduler.result(short), Some(Ok(Value::Number(1)))));
+        assert_eq!(scheduler.tick(), 1);
+        assert!(matches!(scheduler.result(failing), Some(Err(e)) if e.starts_with("out of gas")));
+        assert!(scheduler.result(long).is_none());
+
+        let mut ticks = 2;
+        loop {
+            ticks += 1;
+            if scheduler.tick() == 0 {
+                break;
+            }
+        }
+        // 109 instructions in slices of 20
+        assert_eq!(ticks, 6);
+        assert!(matches!(scheduler.result(long), Some(Ok(Value::Number(10)))));
+        assert!(scheduler.vm(long).unwrap().gas_used() > 0);
+    }
+
+    #[test]
+    fn test_scheduler_stops_on_a_signal() {
+        let mut scheduler = Scheduler::new(20);
+        let ids: Vec<usize> = [1, 1000]
+            .into_iter()
+            .map(|n| {
+                let mut vm = VM::new();
+                vm.load(count_to(n));
+                scheduler.spawn(vm)
+            })
+            .collect();
+        assert_eq!(scheduler.tick(), 1);
+
+        scheduler.stop_handle().raise(shutdown::SIGTERM);
+        assert_eq!(scheduler.tick(), 0);
+        assert!(matches!(scheduler.result(ids[0]), Some(Ok(Value::Number(1)))), "already finished");
+        assert!(matches!(scheduler.result(ids[1]), Some(Err(e)) if e == "Interrupted by SIGTERM"));
+    }
+
+    #[test]
+    fn test_scheduler_runs_shutdown_handlers_when_stopped() {
+        let source = "fn flush_log() {\n    print(\"flushed\")\n}\nfn close() {\n    print(\"closed\")\n}\nfn main() {\n    on_shutdown(flush_log)\n    on_shutdown(close)\n    let mut i = 0\n    while true {\n        i = i + 1\n    }\n}\n";
+        let program = crate::parser::Parser::new(crate::lexer::Lexer::new(source)).parse().unwrap();
+        crate::typechecker::TypeChecker::new().check(&program).unwrap();
+        let module = crate::translate::to_bytecode_module(&crate::lowering::lower_with(&program, true, true)).unwrap();
+
+        let mut vm = VM::new();
+        vm.capture_output();
+        vm.load_module(&module, "main").unwrap();
+        let mut scheduler = Scheduler::new(50);
+        let id = scheduler.spawn(vm);
+        assert_eq!(scheduler.tick(), 1);
+        assert_eq!(scheduler.interrupted(), None);
+
+        scheduler.stop_handle().raise(shutdown::SIGINT);
+        assert_eq!(scheduler.tick(), 0);
+        assert_eq!(scheduler.interrupted(), Some(shutdown::SIGINT));
+        assert!(matches!(scheduler.result(id), Some(Err(e)) if e == "Interrupted by SIGINT"));
+        // The last registered runs first
+        assert_eq!(scheduler.vms[id].0.output, Some(vec!["closed".to_string(), "flushed".to_string()]));
+    }
 }
@@ -1,4 +1,5 @@
 use tower_lsp::lsp_types::*;
+use astrixa::text::byte_of_utf16;
 
 /// CompletionProvider for intelligent code completion
 /// Provides context-aware suggestions for stdlib, AI ops, Web3 primitives
@@ -19,12 +20,7 @@ impl CompletionProvider {
             ""
         };
 
-        let char_pos = position.character as usize;
-        let prefix = if char_pos <= current_line.len() {
-            &current_line[..char_pos]
-        } else {
-            current_line
-        };
+        let prefix = &current_line[..byte_of_utf16(current_line, position.character)];
 
         // Context-aware completions
         if prefix.ends_with("ai.") {
@@ -43,8 +39,14 @@ impl CompletionProvider {
             return self.get_tx_completions();
         }
 
-        if prefix.ends_with("std::") || prefix.contains("std::") {
-            return self.get_stdlib_completions();
+        // std::io:: lists the module's functions, std:: the modules
+        if let Some(path) = prefix.rsplit(|c: char| !c.is_alphanumeric() && c != '_' && c != ':').next() {
+            if let Some(module) = path.strip_prefix("std::").and_then(|rest| rest.strip_suffix("::")) {
+                return self.get_stdlib_module_completions(module);
+            }
+            if path.starts_with("std::") {
+                return self.get_stdlib_completions();
+            }
         }
 
         // General completions
@@ -180,28 +182,40 @@ impl CompletionProvider {
 
     /// Standard library module completions
     fn get_stdlib_completions(&self) -> Vec<CompletionItem> {
-        vec![
-            ("io", "Input/Output operations"),
-            ("fs", "File system operations"),
-            ("net", "HTTP and WebSocket networking"),
-            ("json", "JSON parsing and serialization"),
-            ("async", "Async/await and concurrency"),
-            ("crypto", "Cryptography and Web3 signatures"),
-            ("ai", "AI text generation and classification"),
-            ("web", "Web server framework"),
-        ]
-        .into_iter()
-        .map(|(module, description)| {
-            CompletionItem {
+        let mut modules: Vec<&str> = astrixa::stdlib::stdlib_functions().iter().map(|f| f.module()).collect();
+        modules.sort();
+        modules.dedup();
+        modules
+            .into_iter()
+            .map(|module| CompletionItem {
                 label: module.to_string(),
                 kind: Some(CompletionItemKind::MODULE),
                 detail: Some(format!("std::{}", module)),
-                documentation: Some(Documentation::String(description.to_string())),
                 insert_text: Some(module.to_string()),
                 ..Default::default()
-            }
-        })
-        .collect()
+            })
+            .collect()
+    }
+
+    /// The functions of `std::module`, by their names under it
+    fn get_stdlib_module_completions(&self, module: &str) -> Vec<CompletionItem> {
+        astrixa::stdlib::stdlib_functions()
+            .iter()
+            .filter(|f| f.module() == module)
+            .map(|f| {
+                let path = f.path();
+                let label = path.trim_start_matches(&format!("std::{}::", module)).to_string();
+                CompletionItem {
+                    kind: Some(CompletionItemKind::FUNCTION),
+                    detail: Some(path),
+                    documentation: Some(Documentation::String(f.description.to_string())),
+                    insert_text: Some(format!("{}($1)", label)),
+                    insert_text_format: Some(InsertTextFormat::SNIPPET),
+                    label,
+                    ..Default::default()
+                }
+            })
+            .collect()
     }
 
     /// Language keywords
@@ -241,39 +255,25 @@ impl CompletionProvider {
         .collect()
     }
 
-    /// Standard library functions
+    /// Functions every program can call by name: the prelude's, with the
+    /// path of each from the standard library
     fn get_stdlib_functions(&self) -> Vec<CompletionItem> {
-        vec![
-            ("print", "print(msg)", "Print to stdout", "std::io"),
-            ("input", "input(prompt)", "Read from stdin", "std::io"),
-            ("read", "read(path)", "Read file contents", "std::fs"),
-            ("write", "write(path, content)", "Write to file", "std::fs"),
-            ("http_get", "http_get(url)", "HTTP GET request", "std::net"),
-            ("http_post", "http_post(url, body)", "HTTP POST", "std::net"),
-            ("parse", "parse(json)", "Parse JSON", "std::json"),
-            ("stringify", "stringify(obj)", "Convert to JSON", "std::json"),
-            ("sha256", "sha256(data)", "SHA-256 hash", "std::crypto"),
-            ("sign", "sign(key, message)", "Sign data", "std::crypto"),
-            ("sleep", "sleep(ms)", "Async sleep", "std::async"),
-            ("spawn", "spawn(task)", "Spawn async task", "std::async"),
-            ("len", "len(array)", "Get length", "builtin"),
-            ("type", "type(value)", "Get type", "builtin"),
-            ("panic", "panic(message)", "Panic with error", "builtin"),
-            ("emit", "emit(event, data)", "Emit event", "builtin"),
-        ]
-        .into_iter()
-        .map(|(func, usage, desc, module)| {
-            CompletionItem {
-                label: func.to_string(),
-                kind: Some(CompletionItemKind::FUNCTION),
-                detail: Some(format!("{} - {}", module, usage)),
-                documentation: Some(Documentation::String(desc.to_string())),
-                insert_text: Some(format!("{}($1)", func)),
-                insert_text_format: Some(InsertTextFormat::SNIPPET),
-                ..Default::default()
-            }
-        })
-        .collect()
+        astrixa::prelude::functions()
+            .iter()
+            .map(|f| {
+                let path = astrixa::stdlib::get_stdlib_info(f.name).map_or("builtin".to_string(), |info| info.path());
+                let params: Vec<String> = f.params.iter().map(|t| t.to_string()).collect();
+                CompletionItem {
+                    label: f.name.to_string(),
+                    kind: Some(CompletionItemKind::FUNCTION),
+                    detail: Some(format!("{} - {}({})", path, f.name, params.join(", "))),
+                    documentation: Some(Documentation::String(f.description.to_string())),
+                    insert_text: Some(format!("{}($1)", f.name)),
+                    insert_text_format: Some(InsertTextFormat::SNIPPET),
+                    ..Default::default()
+                }
+            })
+            .collect()
     }
 
     /// AI-related keywords
@@ -1,19 +1,28 @@
 use tower_lsp::lsp_types::*;
 use tower_lsp::Client;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use astrixa::edition::{Edition, Warning};
+use astrixa::session::Session;
+use astrixa::text::{byte_of_column, utf16_of_byte, utf16_of_column};
 
 /// DiagnosticsEngine provides human-friendly, actionable error messages
 /// Philosophy: Errors should be calm, clear, and helpful
-pub struct DiagnosticsEngine;
+pub struct DiagnosticsEngine {
+    // Persistent compiler session: only edited items are re-parsed and re-checked
+    session: Mutex<Session>,
+}
 
 impl DiagnosticsEngine {
     pub fn new() -> Self {
-        DiagnosticsEngine
+        DiagnosticsEngine {
+            session: Mutex::new(Session::new()),
+        }
     }
 
     /// Check document for errors and send diagnostics to client
     pub async fn check(&self, uri: &str, text: &str, client: &Client) {
-        let mut diagnostics = Vec::new();
+        let mut diagnostics = self.check_compiler(uri, text);
 
         // Lexical and syntax analysis
         if let Some(syntax_errors) = self.check_syntax(text) {
@@ -38,6 +47,118 @@ impl DiagnosticsEngine {
         client.publish_diagnostics(uri_parsed, diagnostics, None).await;
     }
 
+    /// Forget a closed document so its cached results can be dropped
+    pub fn close(&self, uri: &str) {
+        if let Ok(mut session) = self.session.lock() {
+            session.close(uri);
+        }
+    }
+
+    /// Errors reported by the compiler front-end itself
+    fn check_compiler(&self, uri: &str, text: &str) -> Vec<Diagnostic> {
+        let mut session = match self.session.lock() {
+            Ok(session) => session,
+            Err(_) => return Vec::new(),
+        };
+        session.update(uri, text);
+        session.set_edition(uri, project_edition(uri));
+
+        let warnings: Vec<Diagnostic> = session
+            .warnings(uri)
+            .iter()
+            .map(|warning| self.deprecation_diagnostic(text, warning))
+            .collect();
+
+        session
+            .diagnostics(uri)
+            .into_iter()
+            .map(|err| {
+                let line = err.line.saturating_sub(1) as u32;
+                let source_line = text.lines().nth(line as usize).unwrap_or_default();
+                let column = byte_of_column(source_line, err.column);
+                let message = match err.help {
+                    Some(help) => format!("{}\n{}", err.message, help),
+                    None => err.message,
+                };
+                let mut diagnostic = self.create_diagnostic(
+                    line,
+                    source_line,
+                    column,
+                    source_line.len(),
+                    message,
+                    DiagnosticSeverity::ERROR,
+                );
+                diagnostic.code = err.code.map(|code| NumberOrString::String(code.to_string()));
+                // A fix may edit elsewhere than the error, e.g. the `let` of
+                // a variable reassigned further down
+                diagnostic.data = err.fix.map(|fix| {
+                    serde_json::json!({
+                        "replacement": fix.replacement,
+                        "range": to_range(text, fix.span),
+                        "title": format!("Change to '{}'", fix.replacement),
+                    })
+                });
+                diagnostic
+            })
+            .chain(warnings)
+            .collect()
+    }
+
+    /// An edition deprecation; the mechanical fix, if any, rides along in
+    /// `data` so `quick_fixes` can offer it without re-parsing
+    fn deprecation_diagnostic(&self, text: &str, warning: &Warning) -> Diagnostic {
+        let message = match &warning.help {
+            Some(help) => format!("{}\n{}", warning.message, help),
+            None => warning.message.clone(),
+        };
+        Diagnostic {
+            range: to_range(text, warning.span),
+            severity: Some(DiagnosticSeverity::WARNING),
+            code: Some(NumberOrString::String(warning.code.to_string())),
+            message,
+            source: Some("astrixa".to_string()),
+            tags: Some(vec![DiagnosticTag::DEPRECATED]),
+            data: warning
+                .replacement
+                .as_ref()
+                .map(|replacement| serde_json::json!({ "replacement": replacement })),
+            ..Default::default()
+        }
+    }
+
+    /// Quick fixes for the deprecations and fixable errors among
+    /// `diagnostics` (as sent back by the client in a code action request)
+    pub fn quick_fixes(&self, uri: &Url, diagnostics: &[Diagnostic]) -> Vec<CodeActionOrCommand> {
+        diagnostics
+            .iter()
+            .filter(|d| d.source.as_deref() == Some("astrixa"))
+            .filter_map(|diagnostic| {
+                let data = diagnostic.data.as_ref()?;
+                let replacement = data.get("replacement")?.as_str()?;
+                let range = match data.get("range") {
+                    Some(range) => serde_json::from_value(range.clone()).ok()?,
+                    None => diagnostic.range,
+                };
+                let title = data.get("title").and_then(|t| t.as_str()).unwrap_or("Update to the project's edition");
+                let edit = TextEdit {
+                    range,
+                    new_text: replacement.to_string(),
+                };
+                Some(CodeActionOrCommand::CodeAction(CodeAction {
+                    title: title.to_string(),
+                    kind: Some(CodeActionKind::QUICKFIX),
+                    diagnostics: Some(vec![diagnostic.clone()]),
+                    edit: Some(WorkspaceEdit {
+                        changes: Some(HashMap::from([(uri.clone(), vec![edit])])),
+                        ..Default::default()
+                    }),
+                    is_preferred: Some(true),
+                    ..Default::default()
+                }))
+            })
+            .collect()
+    }
+
     /// Check for syntax errors with helpful messages
     fn check_syntax(&self, text: &str) -> Option<Vec<Diagnostic>> {
         let mut errors = Vec::new();
@@ -50,20 +171,22 @@ impl DiagnosticsEngine {
             // Malformed function definitions
             if trimmed.starts_with("fn ") {
                 if !trimmed.contains('(') {
-                    let col = line.find("fn ").unwrap_or(0) as u32;
+                    let col = line.find("fn ").unwrap_or(0);
                     errors.push(self.create_diagnostic(
                         line_num,
+                        line,
                         col,
-                        line.len() as u32,
+                        line.len(),
                         "Function needs parentheses. Try: fn name(...) { }".to_string(),
                         DiagnosticSeverity::ERROR,
                     ));
                 } else if trimmed.contains('(') && !trimmed.contains(')') && !trimmed.ends_with(',') {
-                    let col = line.find('(').unwrap_or(0) as u32;
+                    let col = line.find('(').unwrap_or(0);
                     errors.push(self.create_diagnostic(
                         line_num,
+                        line,
                         col,
-                        line.len() as u32,
+                        line.len(),
                         "Missing closing parenthesis ')' in function definition".to_string(),
                         DiagnosticSeverity::ERROR,
                     ));
@@ -72,9 +195,10 @@ impl DiagnosticsEngine {
 
             // Incomplete let statements
             if trimmed.starts_with("let ") && !trimmed.contains('=') && !line_idx + 1 < lines.len() {
-                let col = line.find("let ").unwrap_or(0) as u32;
+                let col = line.find("let ").unwrap_or(0);
                 errors.push(self.create_diagnostic(
                     line_num,
+                    line,
                     col,
                     5,
                     "Let binding needs assignment. Try: let name = value".to_string(),
@@ -87,11 +211,12 @@ impl DiagnosticsEngine {
                 if let Some(value_part) = trimmed.split("= ").nth(1) {
                     let value_clean = value_part.trim().trim_end_matches(';');
                     if value_clean.parse::<i64>().is_ok() {
-                        let col = line.find(value_clean).unwrap_or(0) as u32;
+                        let col = line.find(value_clean).unwrap_or(0);
                         errors.push(self.create_diagnostic(
                             line_num,
+                            line,
                             col,
-                            value_clean.len() as u32,
+                            value_clean.len(),
                             "Cannot assign number to string. Remove ': string' or wrap in quotes".to_string(),
                             DiagnosticSeverity::ERROR,
                         ));
@@ -108,8 +233,9 @@ impl DiagnosticsEngine {
                 // This is a warning, not an error (ASTRIXA may allow optional semicolons)
                 errors.push(self.create_diagnostic(
                     line_num,
-                    line.len() as u32,
-                    line.len() as u32,
+                    line,
+                    line.len(),
+                    line.len(),
                     "Consider adding semicolon for clarity".to_string(),
                     DiagnosticSeverity::HINT,
                 ));
@@ -121,8 +247,9 @@ impl DiagnosticsEngine {
                 if let Some(col) = line.rfind('"') {
                     errors.push(self.create_diagnostic(
                         line_num,
-                        col as u32,
-                        (col + 1) as u32,
+                        line,
+                        col,
+                        col + 1,
                         "Unclosed string literal. Missing closing quote".to_string(),
                         DiagnosticSeverity::ERROR,
                     ));
@@ -133,6 +260,7 @@ impl DiagnosticsEngine {
             if trimmed.starts_with("fucntion ") || trimmed.starts_with("funct ") {
                 errors.push(self.create_diagnostic(
                     line_num,
+                    line,
                     0,
                     8,
                     "Did you mean 'fn'? ASTRIXA uses 'fn' for functions".to_string(),
@@ -144,8 +272,9 @@ impl DiagnosticsEngine {
                 if let Some(col) = line.find(" var ") {
                     errors.push(self.create_diagnostic(
                         line_num,
-                        col as u32,
-                        (col + 4) as u32,
+                        line,
+                        col,
+                        col + 4,
                         "Use 'let' instead of 'var' in ASTRIXA".to_string(),
                         DiagnosticSeverity::WARNING,
                     ));
@@ -207,8 +336,9 @@ impl DiagnosticsEngine {
                         if let Some(col) = line.find(" + ") {
                             errors.push(self.create_diagnostic(
                                 line_num,
-                                col as u32,
-                                (col + 3) as u32,
+                                line,
+                                col,
+                                col + 3,
                                 "Cannot mix strings and numbers. Convert one to match the other".to_string(),
                                 DiagnosticSeverity::ERROR,
                             ));
@@ -222,8 +352,9 @@ impl DiagnosticsEngine {
                 if let Some(col) = line.find(" / 0") {
                     errors.push(self.create_diagnostic(
                         line_num,
-                        col as u32 + 3,
-                        col as u32 + 4,
+                        line,
+                        col + 3,
+                        col + 4,
                         "Division by zero will cause a runtime error".to_string(),
                         DiagnosticSeverity::WARNING,
                     ));
@@ -235,8 +366,9 @@ impl DiagnosticsEngine {
                 if let Some(col) = line.find("ai.infer") {
                     errors.push(self.create_diagnostic(
                         line_num,
-                        col as u32,
-                        (col + 8) as u32,
+                        line,
+                        col,
+                        col + 8,
                         "ai.infer() needs a model. Use ai.infer(ai.model(\"name\"), text)".to_string(),
                         DiagnosticSeverity::ERROR,
                     ));
@@ -273,8 +405,9 @@ impl DiagnosticsEngine {
                 if fn_lines > 50 {
                     warnings.push(self.create_diagnostic(
                         line_num,
+                        line,
                         0,
-                        line.len() as u32,
+                        line.len(),
                         "Consider breaking this function into smaller pieces".to_string(),
                         DiagnosticSeverity::HINT,
                     ));
@@ -289,8 +422,9 @@ impl DiagnosticsEngine {
                         if fn_name.chars().next().map(|c| c.is_uppercase()).unwrap_or(false) {
                             warnings.push(self.create_diagnostic(
                                 line_num,
-                                (name_start + 3) as u32,
-                                name_end as u32,
+                                line,
+                                name_start + 3,
+                                name_end,
                                 "Function names should start with lowercase in ASTRIXA".to_string(),
                                 DiagnosticSeverity::HINT,
                             ));
@@ -303,12 +437,13 @@ impl DiagnosticsEngine {
         if warnings.is_empty() { None } else { Some(warnings) }
     }
 
-    /// Create a diagnostic with proper range
+    /// Create a diagnostic over bytes `start..end` of `text`, line `line`
     fn create_diagnostic(
         &self,
         line: u32,
-        start_char: u32,
-        end_char: u32,
+        text: &str,
+        start: usize,
+        end: usize,
         message: String,
         severity: DiagnosticSeverity,
     ) -> Diagnostic {
@@ -316,11 +451,11 @@ impl DiagnosticsEngine {
             range: Range {
                 start: Position {
                     line,
-                    character: start_char,
+                    character: utf16_of_byte(text, start),
                 },
                 end: Position {
                     line,
-                    character: end_char,
+                    character: utf16_of_byte(text, end),
                 },
             },
             severity: Some(severity),
@@ -330,20 +465,33 @@ impl DiagnosticsEngine {
         }
     }
 }
-                },
-                end: Position {
-                    line,
-                    character: column,
-                },
-            },
-            severity: Some(severity),
-            code: None,
-            code_description: None,
-            source: Some("astrixa".to_string()),
-            message,
-            related_information: None,
-            tags: None,
-            data: None,
+
+/// Edition from the nearest astrixa.toml above the document; the default
+/// edition when there is none or it can't be read
+fn project_edition(uri: &str) -> Edition {
+    let Some(path) = Url::parse(uri).ok().and_then(|u| u.to_file_path().ok()) else {
+        return Edition::default();
+    };
+
+    path.ancestors()
+        .map(|dir| dir.join("astrixa.toml"))
+        .find(|manifest| manifest.exists())
+        .and_then(|manifest| std::fs::read_to_string(manifest).ok())
+        .and_then(|text| Edition::from_manifest(&text).ok())
+        .unwrap_or_default()
+}
+
+/// LSP range of a compiler span (1-based lines and char columns) in `text`
+fn to_range(text: &str, span: astrixa::ast::Span) -> Range {
+    let position = |line: usize, column: usize| {
+        let line = line.saturating_sub(1);
+        Position {
+            line: line as u32,
+            character: utf16_of_column(text.lines().nth(line).unwrap_or_default(), column),
         }
+    };
+    Range {
+        start: position(span.start_line, span.start_column),
+        end: position(span.end_line, span.end_column),
     }
 }
@@ -1,6 +1,6 @@
 use tower_lsp::jsonrpc::Result;
 use tower_lsp::lsp_types::*;
-use tower_lsp::{Client, LanguageServer, LsBuilder};
+use tower_lsp::{Client, LanguageServer, LspService, Server};
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::RwLock;
@@ -10,11 +10,13 @@ mod diagnostics;
 mod completion;
 mod hover;
 mod symbols;
+mod refactor;
 
 use diagnostics::DiagnosticsEngine;
 use completion::CompletionProvider;
 use hover::HoverProvider;
 use symbols::SymbolProvider;
+use refactor::RefactorProvider;
 
 #[derive(Clone)]
 struct AstrixaLanguageServer {
@@ -24,6 +26,7 @@ struct AstrixaLanguageServer {
     completion: Arc<CompletionProvider>,
     hover: Arc<HoverProvider>,
     symbols: Arc<SymbolProvider>,
+    refactor: Arc<RefactorProvider>,
 }
 
 #[tower_lsp::async_trait]
@@ -41,13 +44,22 @@ impl LanguageServer for AstrixaLanguageServer {
                     trigger_characters: Some(vec![":".to_string(), ".".to_string()]),
                     ..Default::default()
                 }),
-                hover_provider: Some(HoverServerCapabilities::Simple(true)),
+                hover_provider: Some(HoverProviderCapability::Simple(true)),
                 definition_provider: Some(OneOf::Left(true)),
                 document_symbol_provider: Some(OneOf::Left(true)),
+                code_action_provider: Some(CodeActionProviderCapability::Options(
+                    CodeActionOptions {
+                        code_action_kinds: Some(vec![
+                            CodeActionKind::QUICKFIX,
+                            CodeActionKind::REFACTOR_EXTRACT,
+                        ]),
+                        ..Default::default()
+                    }
+                )),
                 diagnostic_provider: Some(DiagnosticServerCapabilities::Options(
                     DiagnosticOptions {
                         inter_file_dependencies: false,
-                        work_du_file_diagnostics: false,
+                        workspace_diagnostics: false,
                         ..Default::default()
                     }
                 )),
@@ -108,6 +120,15 @@ impl LanguageServer for AstrixaLanguageServer {
         }
     }
 
+    async fn did_close(&self, params: DidCloseTextDocumentParams) {
+        let uri = params.text_document.uri.to_string();
+
+        info!("Document closed: {}", uri);
+
+        self.documents.write().await.remove(&uri);
+        self.diagnostics.close(&uri);
+    }
+
     async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
         let uri = params.text_document_position.text_document.uri.to_string();
         let position = params.text_document_position.position;
@@ -163,6 +184,19 @@ impl LanguageServer for AstrixaLanguageServer {
             Ok(None)
         }
     }
+
+    async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
+        let uri = params.text_document.uri;
+        
+        let documents = self.documents.read().await;
+        if let Some(text) = documents.get(&uri.to_string()) {
+            let mut actions = self.diagnostics.quick_fixes(&uri, &params.context.diagnostics);
+            actions.extend(self.refactor.get_code_actions(&uri, text, params.range).await);
+            Ok(Some(actions))
+        } else {
+            Ok(None)
+        }
+    }
 }
 
 #[tokio::main]
@@ -174,18 +208,16 @@ async fn main() {
     let stdin = tokio::io::stdin();
     let stdout = tokio::io::stdout();
 
-    let (service, socket) = LsBuilder::new(AstrixaLanguageServer {
-        client: Client::new(),
+    let (service, socket) = LspService::new(|client| AstrixaLanguageServer {
+        client,
         documents: Arc::new(RwLock::new(HashMap::new())),
         diagnostics: Arc::new(DiagnosticsEngine::new()),
         completion: Arc::new(CompletionProvider::new()),
         hover: Arc::new(HoverProvider::new()),
         symbols: Arc::new(SymbolProvider::new()),
-    })
-    .build_socket(stdin, stdout);
+        refactor: Arc::new(RefactorProvider::new()),
+    });
 
     info!("ASTRIXA LSP starting");
-    tower_lsp::run(service, socket)
-        .await
-        .expect("LSP server crashed");
+    Server::new(stdin, stdout, socket).serve(service).await;
 }
@@ -0,0 +1,485 @@
+use tower_lsp::lsp_types::*;
+use std::collections::HashMap;
+use astrixa::ast::{Expr, ExprKind, Pattern, Stmt, StmtKind};
+use astrixa::lexer::Lexer;
+use astrixa::parser::Parser;
+use astrixa::text::utf16_len;
+
+/// RefactorProvider offers code actions that restructure source code
+/// Currently supports: extract selected statements into a new function
+pub struct RefactorProvider;
+
+/// Statements chosen for extraction, as the parser found them
+struct Selection<'a> {
+    function: &'a Stmt,   // The top-level function they are in
+    stmts: &'a [Stmt],    // Consecutive statements of one block
+    rest: &'a [Stmt],     // The statements after them in that block
+    loops: Vec<&'a Stmt>, // Loops around them, which run them again
+}
+
+/// How a value the extracted statements produce gets back to the caller
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Output {
+    Let { mutable: bool }, // Bound by a `let` among them
+    Assign,                // Assigned to a variable declared before them
+}
+
+impl RefactorProvider {
+    pub fn new() -> Self {
+        RefactorProvider
+    }
+
+    /// Compute the refactorings available for the selected range
+    pub async fn get_code_actions(&self, uri: &Url, text: &str, range: Range) -> Vec<CodeActionOrCommand> {
+        let mut actions = Vec::new();
+
+        if let Some(edit) = self.extract_function(uri, text, range) {
+            actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                title: "Extract into new function".to_string(),
+                kind: Some(CodeActionKind::REFACTOR_EXTRACT),
+                edit: Some(edit),
+                ..Default::default()
+            }));
+        }
+
+        actions
+    }
+
+    /// Build the WorkspaceEdit for "extract function", or None when the
+    /// selection can't be extracted safely
+    fn extract_function(&self, uri: &Url, text: &str, range: Range) -> Option<WorkspaceEdit> {
+        let program = Parser::new(Lexer::new(text)).parse().ok()?;
+        let lines: Vec<&str> = text.lines().collect();
+
+        // Whole lines are extracted; a selection ending at column 0 stops on
+        // the previous line. Spans count lines from 1.
+        let first = range.start.line as usize + 1;
+        let mut last = range.end.line as usize + 1;
+        if range.end.character == 0 && last > first {
+            last -= 1;
+        }
+
+        let selection = select(&program, first, last)?;
+        let StmtKind::Function { params: function_params, body: function_body, .. } = &selection.function.kind else {
+            return None;
+        };
+        let stmts = selection.stmts;
+        let from = stmts[0].span.start_line;
+        let to = stmts[stmts.len() - 1].span.end_line;
+
+        // Only statements that run straight through can move: a return,
+        // `?`, final value or defer means something else in a new function,
+        // and a helper would leave the scope that calls it
+        let mut movable = true;
+        walk(stmts, &mut |stmt| {
+            movable &= !matches!(
+                stmt.kind,
+                StmtKind::Return(_) | StmtKind::Value(_) | StmtKind::Defer(_) | StmtKind::Function { .. }
+            );
+            for expr in stmt_exprs(&stmt.kind) {
+                each_expr(expr, &mut |e| movable &= !matches!(e.kind, ExprKind::Try(_)));
+            }
+        });
+        if !movable {
+            return None;
+        }
+
+        // Variables visible before the selection: parameters, and what
+        // statements starting earlier bind
+        let mut defined_before = function_params.clone();
+        walk(function_body, &mut |stmt| {
+            if stmt.span.start_line < from {
+                defined_before.extend(bindings(&stmt.kind));
+            }
+        });
+
+        // Inputs: variables the selection uses that were defined before it
+        let mut params: Vec<String> = Vec::new();
+        let mut defined_inside: Vec<(String, bool)> = Vec::new();
+        for stmt in stmts {
+            for name in reads(std::slice::from_ref(stmt)) {
+                let shadowed = defined_inside.iter().any(|(v, _)| *v == name);
+                if defined_before.contains(&name) && !shadowed && !params.contains(&name) {
+                    params.push(name);
+                }
+            }
+            let mutable = matches!(stmt.kind, StmtKind::Let { mutable: true, .. } | StmtKind::LetTuple { mutable: true, .. });
+            defined_inside.extend(bindings(&stmt.kind).into_iter().map(|name| (name, mutable)));
+        }
+
+        // Outputs: its bindings the rest of the block reads, then variables
+        // it assigns that are read after it, or again by a loop around it
+        let read_later = reads(selection.rest);
+        let mut read_again: Vec<String> = Vec::new();
+        walk(function_body, &mut |stmt| {
+            if stmt.span.start_line > to {
+                read_again.extend(reads(std::slice::from_ref(stmt)));
+            }
+        });
+        for looped in &selection.loops {
+            read_again.extend(reads(std::slice::from_ref(*looped)));
+        }
+        let mut outputs: Vec<(String, Output)> = defined_inside
+            .iter()
+            .filter(|(name, _)| read_later.contains(name))
+            .map(|(name, mutable)| (name.clone(), Output::Let { mutable: *mutable }))
+            .collect();
+        walk(stmts, &mut |stmt| {
+            if let StmtKind::Assign { name, .. } = &stmt.kind {
+                let declared_inside = defined_inside.iter().any(|(v, _)| v == name);
+                let known = outputs.iter().any(|(v, _)| v == name);
+                if !declared_inside && !known && defined_before.contains(name) && read_again.contains(name) {
+                    outputs.push((name.clone(), Output::Assign));
+                }
+            }
+        });
+
+        let name = fresh_function_name(&program);
+        let call = format!("{}({})", name, params.join(", "));
+        let indent = leading_whitespace(lines[from - 1]);
+
+        // Several outputs come back as a tuple; an assigned one through a
+        // new binding, since a `let` would shadow the variable
+        let taken = names_in(selection.function);
+        let replacement = match outputs.as_slice() {
+            [] => format!("{}{}", indent, call),
+            [(var, Output::Let { mutable: true })] => format!("{}let mut {} = {}", indent, var, call),
+            [(var, Output::Let { mutable: false })] => format!("{}let {} = {}", indent, var, call),
+            [(var, Output::Assign)] => format!("{}{} = {}", indent, var, call),
+            _ => {
+                let mut names = Vec::new();
+                let mut assignments = String::new();
+                for (var, output) in &outputs {
+                    match output {
+                        Output::Let { .. } => names.push(var.clone()),
+                        Output::Assign => {
+                            let fresh = fresh_name(&format!("new_{}", var), &taken);
+                            assignments.push_str(&format!("\n{}{} = {}", indent, var, fresh));
+                            names.push(fresh);
+                        }
+                    }
+                }
+                let mutable = outputs.iter().any(|(_, output)| *output == Output::Let { mutable: true });
+                let keyword = if mutable { "let mut" } else { "let" };
+                format!("{}{} ({}) = {}{}", indent, keyword, names.join(", "), call, assignments)
+            }
+        };
+
+        // Re-indent the body relative to the first selected line
+        let mut body = String::new();
+        for line in &lines[from - 1..to] {
+            let stripped = line.strip_prefix(indent.as_str()).unwrap_or(line.trim_start());
+            body.push_str("    ");
+            body.push_str(stripped);
+            body.push('\n');
+        }
+        let returned: Vec<&str> = outputs.iter().map(|(var, _)| var.as_str()).collect();
+        match returned.as_slice() {
+            [] => {}
+            [var] => body.push_str(&format!("    return {}\n", var)),
+            vars => body.push_str(&format!("    return ({})\n", vars.join(", "))),
+        }
+
+        let new_function = format!("\n\nfn {}({}) {{\n{}}}", name, params.join(", "), body);
+
+        let end_line = selection.function.span.end_line - 1;
+        let end = utf16_len(lines[end_line]);
+        let edits = vec![
+            TextEdit {
+                range: Range {
+                    start: Position { line: from as u32 - 1, character: 0 },
+                    end: Position { line: to as u32 - 1, character: utf16_len(lines[to - 1]) },
+                },
+                new_text: replacement,
+            },
+            TextEdit {
+                range: Range {
+                    start: Position { line: end_line as u32, character: end },
+                    end: Position { line: end_line as u32, character: end },
+                },
+                new_text: new_function,
+            },
+        ];
+
+        let mut changes = HashMap::new();
+        changes.insert(uri.clone(), edits);
+
+        Some(WorkspaceEdit {
+            changes: Some(changes),
+            ..Default::default()
+        })
+    }
+}
+
+/// The consecutive statements of one block in a top-level function that
+/// lines `first` to `last` cover, if they cut through no statement
+fn select(program: &[Stmt], first: usize, last: usize) -> Option<Selection<'_>> {
+    let function = program.iter().find(|stmt| {
+        matches!(stmt.kind, StmtKind::Function { .. }) && stmt.span.start_line < first && last < stmt.span.end_line
+    })?;
+    let StmtKind::Function { body, .. } = &function.kind else { return None };
+
+    let overlaps = |stmt: &Stmt| stmt.span.start_line <= last && stmt.span.end_line >= first;
+    let mut loops = Vec::new();
+    let mut block: &[Stmt] = body;
+    loop {
+        let i = block.iter().position(overlaps)?;
+        let j = block.iter().rposition(overlaps)?;
+        if block[i].span.start_line >= first && block[j].span.end_line <= last {
+            return Some(Selection { function, stmts: &block[i..=j], rest: &block[j + 1..], loops });
+        }
+
+        // Within one statement, the selection may be inside one of its blocks
+        let stmt = &block[i];
+        if i != j || matches!(stmt.kind, StmtKind::Function { .. }) {
+            return None;
+        }
+        if matches!(stmt.kind, StmtKind::While { .. } | StmtKind::For { .. }) {
+            loops.push(stmt);
+        }
+        block = blocks(&stmt.kind).into_iter().find(|inner| inner.iter().any(overlaps))?;
+    }
+}
+
+/// Pick "extracted", "extracted_2", ... avoiding existing function names
+fn fresh_function_name(program: &[Stmt]) -> String {
+    let mut existing = Vec::new();
+    let mut collect = |stmt: &Stmt| {
+        if let StmtKind::Function { name, .. } = &stmt.kind {
+            existing.push(name.clone());
+        }
+    };
+    for stmt in program {
+        collect(stmt);
+        match &stmt.kind {
+            StmtKind::Function { body, .. } => walk_all(body, &mut collect),
+            StmtKind::Contract { constructor, methods, .. } => {
+                constructor.iter().map(|c| c.as_ref()).chain(methods).for_each(&mut collect)
+            }
+            _ => {}
+        }
+    }
+    fresh_name("extracted", &existing)
+}
+
+/// `base`, or `base_2`, `base_3`, ... whichever is not taken
+fn fresh_name(base: &str, taken: &[String]) -> String {
+    let mut name = base.to_string();
+    let mut n = 2;
+    while taken.contains(&name) {
+        name = format!("{}_{}", base, n);
+        n += 1;
+    }
+    name
+}
+
+/// Every name a function's parameters and body bind or use
+fn names_in(function: &Stmt) -> Vec<String> {
+    let StmtKind::Function { params, body, .. } = &function.kind else { return Vec::new() };
+    let mut names = params.clone();
+    names.extend(reads(body));
+    walk(body, &mut |stmt| names.extend(bindings(&stmt.kind)));
+    names
+}
+
+/// The variables `body` reads, in order, assigned ones included: a
+/// variable's own name, never a keyword or a called function's
+fn reads(body: &[Stmt]) -> Vec<String> {
+    let mut names = Vec::new();
+    walk(body, &mut |stmt| {
+        if let StmtKind::Assign { name, .. } = &stmt.kind {
+            names.push(name.clone());
+        }
+        for expr in stmt_exprs(&stmt.kind) {
+            each_expr(expr, &mut |e| match &e.kind {
+                ExprKind::Identifier(name) | ExprKind::ModuleCall(name, ..) | ExprKind::Property(name, _) => {
+                    names.push(name.clone())
+                }
+                _ => {}
+            });
+        }
+    });
+    names
+}
+
+/// The names a statement binds for the rest of its block, or in its body
+fn bindings(kind: &StmtKind) -> Vec<String> {
+    match kind {
+        StmtKind::Let { name, .. } => vec![name.clone()],
+        StmtKind::LetTuple { names, .. } => names.iter().filter(|name| *name != "_").cloned().collect(),
+        StmtKind::For { var, .. } => vec![var.clone()],
+        StmtKind::Match { arms, .. } => arms
+            .iter()
+            .filter_map(|arm| match &arm.pattern {
+                Pattern::Some(name) | Pattern::Ok(name) | Pattern::Err(name) => Some(name.clone()),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// The expressions a statement evaluates itself, outside its blocks
+fn stmt_exprs(kind: &StmtKind) -> Vec<&Expr> {
+    match kind {
+        StmtKind::Let { value, .. } | StmtKind::LetTuple { value, .. } | StmtKind::Assign { value, .. }
+        | StmtKind::Expression(value) | StmtKind::Return(value) | StmtKind::Value(value) | StmtKind::Panic(value)
+        | StmtKind::If { condition: value, .. } | StmtKind::While { condition: value, .. }
+        | StmtKind::For { iterable: value, .. } | StmtKind::Match { value, .. } => vec![value],
+        _ => Vec::new(),
+    }
+}
+
+/// Visit `expr` and each expression inside it
+fn each_expr<'a>(expr: &'a Expr, visit: &mut impl FnMut(&'a Expr)) {
+    visit(expr);
+    match &expr.kind {
+        ExprKind::Call(_, args) | ExprKind::ModuleCall(_, _, args) | ExprKind::Tuple(args) | ExprKind::Array(args) => {
+            for arg in args {
+                each_expr(arg, visit);
+            }
+        }
+        ExprKind::Named(_, inner) | ExprKind::Await(inner) | ExprKind::Try(inner) | ExprKind::Cast(inner, _) => {
+            each_expr(inner, visit)
+        }
+        ExprKind::Range(l, r) | ExprKind::Slice(l, r)
+        | ExprKind::Add(l, r) | ExprKind::Sub(l, r) | ExprKind::Mul(l, r) | ExprKind::Div(l, r) | ExprKind::Mod(l, r)
+        | ExprKind::Eq(l, r) | ExprKind::Ne(l, r) | ExprKind::Lt(l, r) | ExprKind::Le(l, r)
+        | ExprKind::Gt(l, r) | ExprKind::Ge(l, r)
+        | ExprKind::And(l, r) | ExprKind::Or(l, r) => {
+            each_expr(l, visit);
+            each_expr(r, visit);
+        }
+        ExprKind::Number(_) | ExprKind::Float(_) | ExprKind::Bool(_) | ExprKind::String(_)
+        | ExprKind::Identifier(_) | ExprKind::Property(..) => {}
+    }
+}
+
+/// Visit each statement of `body` and its blocks, not entering helpers
+fn walk<'a>(body: &'a [Stmt], visit: &mut impl FnMut(&'a Stmt)) {
+    for stmt in body {
+        visit(stmt);
+        for block in blocks(&stmt.kind) {
+            walk(block, visit);
+        }
+    }
+}
+
+/// Visit each statement of `body`, its blocks and its helpers
+fn walk_all<'a>(body: &'a [Stmt], visit: &mut impl FnMut(&'a Stmt)) {
+    for stmt in body {
+        visit(stmt);
+        match &stmt.kind {
+            StmtKind::Function { body, .. } => walk_all(body, visit),
+            kind => {
+                for block in blocks(kind) {
+                    walk_all(block, visit);
+                }
+            }
+        }
+    }
+}
+
+/// The blocks a statement runs; a helper's body is not one of them
+fn blocks(kind: &StmtKind) -> Vec<&Vec<Stmt>> {
+    match kind {
+        StmtKind::If { then_body, else_body, .. } => std::iter::once(then_body).chain(else_body.as_ref()).collect(),
+        StmtKind::While { body, .. } | StmtKind::For { body, .. } | StmtKind::Defer(body) => vec![body],
+        StmtKind::Match { arms, .. } => arms.iter().map(|arm| &arm.body).collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn leading_whitespace(line: &str) -> String {
+    line.chars().take_while(|c| c.is_whitespace()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn extract(text: &str, first: u32, last: u32) -> Option<Vec<TextEdit>> {
+        let uri = Url::parse("file:///test.ax").unwrap();
+        let range = Range {
+            start: Position { line: first, character: 0 },
+            end: Position { line: last + 1, character: 0 },
+        };
+        let edit = RefactorProvider::new().extract_function(&uri, text, range)?;
+        edit.changes?.remove(&uri)
+    }
+
+    #[test]
+    fn test_extract_with_parameters_and_output() {
+        let text = "fn main(a) {\n    let b = a + 1\n    let c = b * 2\n    print(c)\n}";
+        let edits = extract(text, 1, 2).unwrap();
+
+        assert_eq!(edits[0].new_text, "    let c = extracted(a)");
+        assert_eq!(
+            edits[1].new_text,
+            "\n\nfn extracted(a) {\n    let b = a + 1\n    let c = b * 2\n    return c\n}"
+        );
+        assert_eq!(edits[1].range.start, Position { line: 4, character: 1 });
+    }
+
+    #[test]
+    fn test_extract_assignment_output() {
+        let text = "fn main() {\n    let x = 1\n    x = x + 1\n    print(x)\n}";
+        let edits = extract(text, 2, 2).unwrap();
+
+        assert_eq!(edits[0].new_text, "    x = extracted(x)");
+    }
+
+    #[test]
+    fn test_extract_rejects_unsafe_selections() {
+        let text = "fn main(a) {\n    if a {\n        return 1\n    }\n    return 0\n}";
+
+        // Unbalanced braces
+        assert!(extract(text, 1, 1).is_none());
+        // Contains a return
+        assert!(extract(text, 1, 3).is_none());
+        // Outside any function body
+        assert!(extract(text, 0, 0).is_none());
+
+        // Part of a statement spanning several lines
+        let text = "fn main() {\n    let total = add(\n        1,\n        2)\n    print(total)\n}";
+        assert!(extract(text, 1, 2).is_none());
+    }
+
+    #[test]
+    fn test_extract_keeps_mutability() {
+        let text = "fn main() {\n    let mut total = 1\n    total = total + 1\n    print(total)\n}";
+        let edits = extract(text, 1, 1).unwrap();
+
+        assert_eq!(edits[0].new_text, "    let mut total = extracted()");
+        assert_eq!(edits[1].new_text, "\n\nfn extracted() {\n    let mut total = 1\n    return total\n}");
+    }
+
+    #[test]
+    fn test_extract_returns_several_outputs_as_a_tuple() {
+        let text = "fn main(n) {\n    let mut count = 0\n    let low = n - 1\n    let high = n + 1\n    count = count + 1\n    print(low + high + count)\n}";
+        let edits = extract(text, 2, 4).unwrap();
+
+        assert_eq!(edits[0].new_text, "    let (low, high, new_count) = extracted(n, count)\n    count = new_count");
+        assert_eq!(
+            edits[1].new_text,
+            "\n\nfn extracted(n, count) {\n    let low = n - 1\n    let high = n + 1\n    count = count + 1\n    return (low, high, count)\n}"
+        );
+    }
+
+    #[test]
+    fn test_extract_reads_variables_from_the_syntax_tree() {
+        // Keywords are never variables, a loop's counter is its own, and a
+        // variable the loop reads again is an output
+        let text = "fn main(n) {\n    let mut total = 0\n    let mut i = 0\n    while i < n {\n        for step in 0..n {\n            total = total + step\n        }\n        i = i + 1\n    }\n    print(total)\n}";
+        let edits = extract(text, 4, 6).unwrap();
+        assert_eq!(edits[0].new_text, "        total = extracted(n, total)");
+
+        let edits = extract(text, 7, 7).unwrap();
+        assert_eq!(edits[0].new_text, "        i = extracted(i)");
+
+        // Inside a match arm, its binding is an input
+        let text = "fn main(value) {\n    match value {\n        Some(found) => {\n            let doubled = found * 2\n            print(doubled)\n        }\n        None => print(0)\n    }\n}";
+        let edits = extract(text, 3, 3).unwrap();
+        assert_eq!(edits[0].new_text, "            let doubled = extracted(found)");
+    }
+}
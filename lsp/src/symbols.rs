@@ -1,5 +1,6 @@
 use tower_lsp::lsp_types::*;
 use std::collections::HashMap;
+use astrixa::text::{byte_of_utf16, is_ident_continue, utf16_len, word_at};
 
 pub struct SymbolProvider;
 
@@ -16,7 +17,7 @@ impl SymbolProvider {
         }
 
         let line = lines[position.line as usize];
-        let character = position.character as usize;
+        let character = byte_of_utf16(line, position.character);
 
         // Find word at cursor
         if let Some(word) = self.get_word_at_position(line, character) {
@@ -49,7 +50,7 @@ impl SymbolProvider {
                                 },
                                 end: Position {
                                     line: line_idx as u32,
-                                    character: line.len() as u32,
+                                    character: utf16_len(line),
                                 },
                             },
                         },
@@ -75,7 +76,7 @@ impl SymbolProvider {
                                 },
                                 end: Position {
                                     line: line_idx as u32,
-                                    character: line.len() as u32,
+                                    character: utf16_len(line),
                                 },
                             },
                         },
@@ -101,7 +102,7 @@ impl SymbolProvider {
                                 },
                                 end: Position {
                                     line: line_idx as u32,
-                                    character: line.len() as u32,
+                                    character: utf16_len(line),
                                 },
                             },
                         },
@@ -117,30 +118,8 @@ impl SymbolProvider {
     }
 
     fn get_word_at_position(&self, line: &str, pos: usize) -> Option<String> {
-        if pos > line.len() {
-            return None;
-        }
-
-        let mut start = pos;
-        let mut end = pos;
-
-        // Expand left
-        while start > 0 && (line.chars().nth(start - 1).unwrap().is_alphanumeric() || 
-                           line.chars().nth(start - 1).unwrap() == '_') {
-            start -= 1;
-        }
-
-        // Expand right
-        while end < line.len() && (line.chars().nth(end).unwrap().is_alphanumeric() || 
-                                   line.chars().nth(end).unwrap() == '_') {
-            end += 1;
-        }
-
-        if start < end {
-            Some(line[start..end].to_string())
-        } else {
-            None
-        }
+        let word = word_at(line, pos, is_ident_continue);
+        (!word.is_empty()).then(|| word.to_string())
     }
 
     fn find_definition(&self, word: &str, lines: &[&str]) -> Option<Location> {
@@ -158,7 +137,7 @@ impl SymbolProvider {
                                 },
                                 end: Position {
                                     line: line_idx as u32,
-                                    character: line.len() as u32,
+                                    character: utf16_len(line),
                                 },
                             },
                         });
@@ -179,7 +158,7 @@ impl SymbolProvider {
                                 },
                                 end: Position {
                                     line: line_idx as u32,
-                                    character: line.len() as u32,
+                                    character: utf16_len(line),
                                 },
                             },
                         });